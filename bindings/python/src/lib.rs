@@ -2,102 +2,420 @@
 // PyO3 wrapper for ironbase-core
 
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyTuple};
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyTuple};
 // Arc and RwLock are used internally by DatabaseCore/CollectionCore
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
 
-use ironbase_core::{DatabaseCore, CollectionCore, DocumentId, InsertManyResult};
+use arrow_array::{Array, RecordBatch, StructArray};
+
+use ironbase_core::{DatabaseCore, CollectionCore, CompactionStats, DeleteResult, DocumentId, Durability, InsertManyResult, ReturnDocument, UpdateResult, StorageConfig};
+
+pyo3::create_exception!(ironbase, DatabaseClosedError, pyo3::exceptions::PyRuntimeError);
+pyo3::create_exception!(ironbase, TransactionError, pyo3::exceptions::PyRuntimeError);
+// Lost a conflict with another transaction and was rolled back - retry it (see with_retry)
+pyo3::create_exception!(ironbase, WriteConflictError, TransactionError);
+
+/// Error of a transaction call: WriteConflictError for conflicts worth a
+/// retry, TransactionError for other transaction failures, RuntimeError else
+fn transaction_error(e: ironbase_core::MongoLiteError) -> PyErr {
+    use ironbase_core::MongoLiteError;
+
+    if e.is_transient() {
+        WriteConflictError::new_err(e.to_string())
+    } else if matches!(e.root(), MongoLiteError::TransactionAborted(_) | MongoLiteError::TransactionCommitted) {
+        TransactionError::new_err(e.to_string())
+    } else {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())
+    }
+}
+
+// Both pyclasses are shared between Python threads, and the core is called
+// with the GIL released, so the core handles must be Send + Sync
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DatabaseCore>();
+    assert_send_sync::<CollectionCore>();
+};
+
+/// Run a core call with the GIL released: other Python threads keep running,
+/// and a thread blocked on a core lock cannot deadlock against the holder
+/// needing the GIL (e.g. an index build calling its progress callback)
+fn without_gil<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    Python::with_gil(|py| py.allow_threads(f))
+}
 
 /// IronBase Database - Python wrapper
-#[pyclass]
+/// Usable as a context manager: `with IronBase(path) as db:` closes on exit
+/// Safe to share between threads
+#[pyclass(frozen)]
 pub struct IronBase {
-    db: DatabaseCore,
+    /// None once closed; collections only hold weak references to it
+    db: Mutex<Option<Arc<DatabaseCore>>>,
+    path: String,
+}
+
+impl IronBase {
+    fn db(&self) -> PyResult<Arc<DatabaseCore>> {
+        self.db.lock().unwrap().clone()
+            .ok_or_else(|| DatabaseClosedError::new_err(format!("database '{}' is closed", self.path)))
+    }
+
+    fn with_db<T: Send>(&self, f: impl FnOnce(&DatabaseCore) -> T + Send) -> PyResult<T> {
+        let db = self.db()?;
+        Ok(without_gil(|| f(&db)))
+    }
 }
 
 #[pymethods]
 impl IronBase {
     /// Új adatbázis megnyitása vagy létrehozása
+    /// page_size: only used when the file is created (power of two, 512 - 1MB)
+    /// max_document_size: largest serialized document in bytes (default 16MB)
     #[new]
-    fn new(path: String) -> PyResult<Self> {
-        let db = DatabaseCore::open(&path)
+    #[pyo3(signature = (path, page_size=None, max_document_size=None))]
+    fn new(path: String, page_size: Option<u32>, max_document_size: Option<usize>) -> PyResult<Self> {
+        let mut config = StorageConfig::default();
+        if let Some(page_size) = page_size {
+            config = config.with_page_size(page_size);
+        }
+        if let Some(max_document_size) = max_document_size {
+            config = config.with_max_document_size(max_document_size);
+        }
+        let db = DatabaseCore::open_with_config(&path, &config)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
-        Ok(IronBase { db })
+        Ok(IronBase { db: Mutex::new(Some(Arc::new(db))), path })
     }
 
     /// Collection lekérése (ha nem létezik, létrehozza)
     fn collection(&self, name: String) -> PyResult<Collection> {
-        let coll_core = self.db.collection(&name)
+        let db = self.db()?;
+        let coll_core = without_gil(|| db.collection(&name))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Ok(Collection { core: coll_core, db: Arc::downgrade(&db) })
+    }
+
+    /// Create a read-only view of `source` through an aggregation pipeline
+    ///
+    /// Args:
+    ///     materialized: None (rerun the pipeline on every read), "on_demand"
+    ///         (store the results, recompute on refresh_view) or
+    ///         "incremental" (store the results, catch up before every read)
+    #[pyo3(signature = (name, source, pipeline, materialized=None))]
+    fn create_view(&self, name: &str, source: &str, pipeline: &PyList, materialized: Option<&str>) -> PyResult<()> {
+        let pipeline_json = python_to_json(pipeline)?;
+        let materialized = materialized.map(str::parse::<ironbase_core::ViewRefresh>)
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_db(|db| db.create_view(name, source, &pipeline_json, materialized))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Handle for reading a view
+    fn view(&self, name: &str) -> PyResult<View> {
+        let db = self.db()?;
+        let core = without_gil(|| db.view(name))
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(View { core, db: Arc::downgrade(&db) })
+    }
+
+    /// Named sequence for application numbers: `db.get_sequence("invoice_no").next()`
+    fn get_sequence(&self, name: &str) -> PyResult<Sequence> {
+        let db = self.db()?;
+        Ok(Sequence { core: db.get_sequence(name), db: Arc::downgrade(&db) })
+    }
 
-        Ok(Collection { core: coll_core })
+    /// Recompute a materialized view's results
+    fn refresh_view(&self, name: &str) -> PyResult<()> {
+        self.with_db(|db| db.refresh_view(name))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
     /// Collection-ök listája
     fn list_collections(&self) -> PyResult<Vec<String>> {
-        Ok(self.db.list_collections())
+        self.with_db(|db| db.list_collections())
     }
 
     /// Collection törlése
     fn drop_collection(&self, name: String) -> PyResult<()> {
-        self.db.drop_collection(&name)
+        self.with_db(|db| db.drop_collection(&name))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
+    /// Run an administrative command, e.g. {"compact": "users"},
+    /// {"validate": "users", "full": True} or {"serverStatus": 1}
+    /// Returns a dict with "ok": 1
+    fn command(&self, command: &PyDict) -> PyResult<PyObject> {
+        let command_json = python_dict_to_json_value(command)?;
+        let result = self.with_db(|db| db.command(&command_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| Ok(json_to_python_dict(py, &result)?.into()))
+    }
+
+    /// Delete the database files and close the database
+    fn drop_database(&self) -> PyResult<()> {
+        self.with_db(|db| db.drop_database())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        *self.db.lock().unwrap() = None;
+        Ok(())
+    }
+
     /// Adatbázis bezárása és flush
+    /// Closing twice is a no-op; any other use afterwards raises DatabaseClosedError
     fn close(&self) -> PyResult<()> {
-        self.db.flush()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+        let db = self.db.lock().unwrap().clone();
+        if let Some(db) = db {
+            without_gil(|| db.flush())
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            *self.db.lock().unwrap() = None;
+        }
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+        slf.db()?;
+        Ok(slf)
+    }
+
+    /// Flushes and closes; exceptions from the with-block propagate
+    fn __exit__(&self, _exc_type: &PyAny, _exc_value: &PyAny, _traceback: &PyAny) -> PyResult<bool> {
+        self.close()?;
+        Ok(false)
     }
 
     /// Adatbázis statisztikák
     fn stats(&self) -> PyResult<String> {
-        Ok(serde_json::to_string_pretty(&self.db.stats()).unwrap())
+        Ok(serde_json::to_string_pretty(&self.with_db(|db| db.stats())?).unwrap())
+    }
+
+    /// Lock contention report: per-lock acquisitions and wait times
+    /// Returns a dict
+    fn metrics(&self) -> PyResult<PyObject> {
+        let metrics = self.db()?.metrics();
+        Python::with_gil(|py| Ok(json_to_python_dict(py, &metrics)?.into()))
+    }
+
+    /// Zero the lock contention counters
+    fn reset_metrics(&self) -> PyResult<()> {
+        self.db()?.reset_metrics();
+        Ok(())
+    }
+
+    /// Finds and aggregations currently running, oldest first
+    /// Returns a list of dicts (id, op, collection, query, running_ms, ...)
+    fn current_ops(&self) -> PyResult<PyObject> {
+        let ops: Vec<Value> = self.db()?.current_ops().iter().map(|op| op.to_json()).collect();
+        Python::with_gil(|py| json_value_to_python(py, &Value::Array(ops)))
+    }
+
+    /// Kill a running operation by id; it fails at its next check
+    /// Returns False if no such operation is running
+    fn kill_op(&self, op_id: u64) -> PyResult<bool> {
+        Ok(self.db()?.kill_op(op_id))
+    }
+
+    /// Capture every collection operation into a trace file (JSON lines)
+    fn start_trace(&self, path: &str) -> PyResult<()> {
+        self.with_db(|db| db.start_trace(path))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Stop the capture; returns the number of operations captured
+    fn stop_trace(&self) -> PyResult<u64> {
+        self.with_db(|db| db.stop_trace())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Re-run a captured trace against this database
+    /// Returns a dict (operations, failed, diverged, first_divergence, elapsed_ms)
+    fn replay_trace(&self, path: &str) -> PyResult<PyObject> {
+        let stats = self.with_db(|db| db.replay_trace(path))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let stats = serde_json::json!({
+            "operations": stats.operations,
+            "failed": stats.failed,
+            "diverged": stats.diverged,
+            "first_divergence": stats.first_divergence,
+            "elapsed_ms": stats.elapsed.as_secs_f64() * 1000.0,
+        });
+        Python::with_gil(|py| json_value_to_python(py, &stats))
     }
 
     /// Storage compaction - removes tombstones and old document versions
     /// Returns compaction statistics as a dict
     fn compact(&self) -> PyResult<PyObject> {
-        let stats = self.db.compact()
+        let stats = self.with_db(|db| db.compact())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| compaction_stats_to_python(py, &stats))
+    }
+
+    /// Compact one collection in place, leaving the others untouched
+    /// Returns compaction statistics as a dict
+    fn compact_collection(&self, name: &str) -> PyResult<PyObject> {
+        let stats = self.with_db(|db| db.compact_collection(name))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| compaction_stats_to_python(py, &stats))
+    }
+
+    /// What takes the file's space: bytes by collection, document size
+    /// percentiles, heaviest fields, tombstones and index files
+    /// Returns a dict
+    fn analyze_space(&self) -> PyResult<PyObject> {
+        let report = self.with_db(|db| db.analyze_space())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let report = serde_json::to_value(&report)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| Ok(json_to_python_dict(py, &report)?.into()))
+    }
+
+    /// Default memory limit in bytes of queries and aggregations (None = unlimited)
+    #[pyo3(signature = (limit=None))]
+    fn set_query_memory_limit(&self, limit: Option<usize>) -> PyResult<()> {
+        self.with_db(|db| db.set_query_memory_limit(limit))?;
+        Ok(())
+    }
+
+    /// Key (32 bytes) for encrypted fields; None removes it
+    #[pyo3(signature = (key=None))]
+    fn set_encryption_key(&self, key: Option<Vec<u8>>) -> PyResult<()> {
+        let key = key.map(|key| ironbase_core::EncryptionKey::from_slice(&key))
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_db(|db| db.set_encryption_key(key))?;
+        Ok(())
+    }
+
+    /// Add a user for server/CLI access
+    /// grants: list of (role, collection) tuples; role is read, readWrite or
+    /// dbAdmin, collection "*" means every collection
+    #[pyo3(signature = (user, password, grants=Vec::new()))]
+    fn create_user(&self, user: &str, password: &str, grants: Vec<(String, String)>) -> PyResult<()> {
+        let grants = grants.into_iter()
+            .map(|(role, collection)| role.parse().map(|role| ironbase_core::Grant::new(role, collection)))
+            .collect::<ironbase_core::Result<Vec<_>>>()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_db(|db| db.create_user(user, password, grants))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Remove a user; returns False if there was none
+    fn drop_user(&self, user: &str) -> PyResult<bool> {
+        self.with_db(|db| db.drop_user(user))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Users and their grants: list of {"name": ..., "grants": [...]}
+    fn users(&self) -> PyResult<PyObject> {
+        let users = self.with_db(|db| db.users())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let users = serde_json::to_value(users)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| json_value_to_python(py, &users))
+    }
+
+    /// Check a user's password; returns the user's grants, raises on failure
+    fn authenticate(&self, user: &str, password: &str) -> PyResult<PyObject> {
+        let user = self.with_db(|db| db.authenticate(user, password))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let user = serde_json::to_value(user)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| json_value_to_python(py, &user))
+    }
+
+    /// Seconds a transaction waits for a document another transaction is writing
+    fn set_lock_timeout(&self, seconds: f64) -> PyResult<()> {
+        let timeout = std::time::Duration::try_from_secs_f64(seconds)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.db()?.set_lock_timeout(timeout);
+        Ok(())
+    }
+
+    /// Oplog be/kikapcsolása (capped `_oplog` collection)
+    #[pyo3(signature = (enabled=true, max_entries=1000))]
+    fn set_oplog(&self, enabled: bool, max_entries: u64) -> PyResult<()> {
+        self.with_db(|db| db.set_oplog_config(ironbase_core::OplogConfig { enabled, max_entries }))?;
+        Ok(())
+    }
+
+    /// Oplog entries after the given sequence number, oldest first
+    #[pyo3(signature = (after=0, limit=1000))]
+    fn read_oplog(&self, after: u64, limit: usize) -> PyResult<PyObject> {
+        let entries = self.with_db(|db| db.read_oplog(after, limit))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
+        Python::with_gil(|py| {
+            let py_list = PyList::empty(py);
+            for entry in entries {
+                py_list.append(json_to_python_dict(py, &entry)?)?;
+            }
+            Ok(py_list.into())
+        })
+    }
+
+    /// Corrupted database salvage - writes surviving documents to a new file
+    /// Usable when IronBase(path) itself fails to open
+    #[staticmethod]
+    fn repair(path: String, output_path: String) -> PyResult<PyObject> {
+        let stats = DatabaseCore::repair(&path, &output_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
         Python::with_gil(|py| {
             let dict = PyDict::new(py);
-            dict.set_item("size_before", stats.size_before)?;
-            dict.set_item("size_after", stats.size_after)?;
-            dict.set_item("space_saved", stats.space_saved())?;
-            dict.set_item("documents_scanned", stats.documents_scanned)?;
-            dict.set_item("documents_kept", stats.documents_kept)?;
-            dict.set_item("tombstones_removed", stats.tombstones_removed)?;
-            dict.set_item("peak_memory_mb", stats.peak_memory_mb)?;
-            dict.set_item("compression_ratio", stats.compression_ratio())?;
+            dict.set_item("records_scanned", stats.records_scanned)?;
+            dict.set_item("documents_recovered", stats.documents_recovered)?;
+            dict.set_item("tombstones_applied", stats.tombstones_applied)?;
+            dict.set_item("corrupted_blocks", stats.corrupted_blocks)?;
+            dict.set_item("bytes_skipped", stats.bytes_skipped)?;
+            dict.set_item("collections_recovered", stats.collections_recovered)?;
+            dict.set_item("metadata_recovered", stats.metadata_recovered)?;
             Ok(dict.into())
         })
     }
 
     fn __repr__(&self) -> String {
-        format!("IronBase('{}')", self.db.path())
+        match *self.db.lock().unwrap() {
+            Some(_) => format!("IronBase('{}')", self.path),
+            None => format!("IronBase('{}', closed)", self.path),
+        }
     }
 
     // ========== ACD TRANSACTION API ==========
 
     /// Begin a new transaction
     /// Returns the transaction ID
-    fn begin_transaction(&self) -> PyResult<u64> {
-        Ok(self.db.begin_transaction())
+    ///
+    /// durability: "durable" (default) waits for fsync on commit, "relaxed"
+    /// returns once written and lets the sync thread fsync shortly after
+    #[pyo3(signature = (durability="durable"))]
+    fn begin_transaction(&self, durability: &str) -> PyResult<u64> {
+        let durability = match durability {
+            "durable" => Durability::Durable,
+            "relaxed" => Durability::Relaxed,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    format!("Unknown durability '{}': expected 'durable' or 'relaxed'", other),
+                ))
+            }
+        };
+        self.with_db(|db| db.begin_transaction_with_durability(durability))
     }
 
     /// Commit a transaction (applies all buffered operations atomically)
     fn commit_transaction(&self, tx_id: u64) -> PyResult<()> {
-        self.db.commit_transaction(tx_id)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        self.with_db(|db| db.commit_transaction(tx_id))?
+            .map_err(transaction_error)
     }
 
     /// Rollback a transaction (discard all buffered operations)
     fn rollback_transaction(&self, tx_id: u64) -> PyResult<()> {
-        self.db.rollback_transaction(tx_id)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+        self.with_db(|db| db.rollback_transaction(tx_id))?
+            .map_err(transaction_error)
     }
 
     // ========== COLLECTION TRANSACTION METHODS ==========
@@ -126,8 +444,8 @@ impl IronBase {
         }
 
         // Call Rust core (ALL logic in core)
-        let inserted_id = self.db.insert_one_tx(&collection_name, doc_map, tx_id)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let inserted_id = self.with_db(|db| db.insert_one_tx(&collection_name, doc_map, tx_id))?
+            .map_err(transaction_error)?;
 
         // Return result
         Python::with_gil(|py| {
@@ -166,17 +484,11 @@ impl IronBase {
         let new_doc_json = python_dict_to_json_value(new_doc)?;
 
         // Call Rust core (ALL logic in core)
-        let (matched_count, modified_count) = self.db.update_one_tx(&collection_name, &query_json, new_doc_json, tx_id)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let result = self.with_db(|db| db.update_one_tx(&collection_name, &query_json, new_doc_json, tx_id))?
+            .map_err(transaction_error)?;
 
         // Return result
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("matched_count", matched_count)?;
-            result.set_item("modified_count", modified_count)?;
-            Ok(result.into())
-        })
+        Python::with_gil(|py| update_result_to_python(py, &result))
     }
 
     /// Delete one document within a transaction
@@ -198,23 +510,31 @@ impl IronBase {
         let query_json = python_dict_to_json_value(query)?;
 
         // Call Rust core (ALL logic in core)
-        let deleted_count = self.db.delete_one_tx(&collection_name, &query_json, tx_id)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let result = self.with_db(|db| db.delete_one_tx(&collection_name, &query_json, tx_id))?
+            .map_err(transaction_error)?;
 
         // Return result
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("deleted_count", deleted_count)?;
-            Ok(result.into())
-        })
+        Python::with_gil(|py| delete_result_to_python(py, &result))
     }
 }
 
 /// Collection - Python wrapper for CollectionCore
-#[pyclass]
+#[pyclass(frozen)]
 pub struct Collection {
     core: CollectionCore,
+    /// Weak so a closed database is detected instead of kept alive
+    db: Weak<DatabaseCore>,
+}
+
+impl Collection {
+    /// Run a core call with the GIL released, keeping the database open
+    /// until it returns
+    fn with_core<T: Send>(&self, f: impl FnOnce(&CollectionCore) -> T + Send) -> PyResult<T> {
+        let _db = self.db.upgrade().ok_or_else(|| DatabaseClosedError::new_err(format!(
+            "collection '{}' used after its database was closed", self.core.name
+        )))?;
+        Ok(without_gil(|| f(&self.core)))
+    }
 }
 
 #[pymethods]
@@ -231,7 +551,7 @@ impl Collection {
         }
 
         // Call core method
-        let inserted_id = self.core.insert_one(doc_map)
+        let inserted_id = self.with_core(|core| core.insert_one(doc_map))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Eredmény visszaadása
@@ -268,13 +588,13 @@ impl Collection {
         }
 
         // Call Rust core insert_many (ALL logic in core)
-        let result = self.core.insert_many(docs)
+        let result = self.with_core(|core| core.insert_many(docs))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert result back to Python
         Python::with_gil(|py| {
             let result_dict = PyDict::new(py);
-            result_dict.set_item("acknowledged", true)?;
+            result_dict.set_item("acknowledged", result.acknowledged)?;
             result_dict.set_item("inserted_count", result.inserted_count)?;
 
             // Convert inserted_ids to Python list
@@ -292,8 +612,56 @@ impl Collection {
         })
     }
 
+    /// Insert or replace documents by key in one batch
+    ///
+    /// Args:
+    ///     documents: list of dicts
+    ///     key_fields: list of field names identifying a document
+    ///
+    /// Returns:
+    ///     dict: {"acknowledged", "matched_count", "upserted_count", "upserted_ids"}
+    ///
+    /// Example:
+    ///     products.bulk_upsert(rows, ["sku"])
+    fn bulk_upsert(&self, documents: &PyList, key_fields: Vec<String>) -> PyResult<PyObject> {
+        let mut docs = Vec::with_capacity(documents.len());
+        for doc in documents.iter() {
+            let doc_dict: &PyDict = doc.downcast()?;
+            let mut fields = HashMap::new();
+            for (key, value) in doc_dict.iter() {
+                fields.insert(key.extract::<String>()?, python_to_json(value)?);
+            }
+            docs.push(fields);
+        }
+        let key_fields: Vec<&str> = key_fields.iter().map(String::as_str).collect();
+
+        let result = self.with_core(|core| core.bulk_upsert(docs, &key_fields))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let result_dict = PyDict::new(py);
+            result_dict.set_item("acknowledged", true)?;
+            result_dict.set_item("matched_count", result.matched_count)?;
+            result_dict.set_item("upserted_count", result.upserted_count)?;
+            let ids_list = PyList::empty(py);
+            for doc_id in result.upserted_ids {
+                match doc_id {
+                    DocumentId::Int(i) => ids_list.append(i)?,
+                    DocumentId::String(s) => ids_list.append(s)?,
+                    DocumentId::ObjectId(oid) => ids_list.append(oid)?,
+                }
+            }
+            result_dict.set_item("upserted_ids", ids_list)?;
+            Ok(result_dict.into())
+        })
+    }
+
     /// Find documents with optional projection, sort, limit, skip
-    #[pyo3(signature = (query=None, projection=None, sort=None, limit=None, skip=None))]
+    /// max_memory: memory limit in bytes for this query (default: the database's)
+    /// max_time_ms: time limit - the query fails once it runs longer
+    /// read_concern: "local" (default), "snapshot" or "linearizable"
+    #[pyo3(signature = (query=None, projection=None, sort=None, limit=None, skip=None, max_memory=None, max_time_ms=None, read_concern=None))]
+    #[allow(clippy::too_many_arguments)] // mirrors the Python keyword arguments
     fn find(
         &self,
         query: Option<&PyDict>,
@@ -301,63 +669,145 @@ impl Collection {
         sort: Option<&PyList>,
         limit: Option<usize>,
         skip: Option<usize>,
+        max_memory: Option<usize>,
+        max_time_ms: Option<u64>,
+        read_concern: Option<&str>,
     ) -> PyResult<PyObject> {
-        use ironbase_core::find_options::FindOptions;
-        use std::collections::HashMap;
-
         // Parse query (empty query = all documents)
         let query_json = match query {
             Some(q) => python_dict_to_json_value(q)?,
             None => serde_json::json!({}),
         };
+        let options = build_find_options(projection, sort, limit, skip, max_memory, max_time_ms, read_concern)?;
 
-        // Build FindOptions
-        let mut options = FindOptions::new();
+        // Call core method
+        let results = self.with_core(|core| core.find_with_options(&query_json, options))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        // Convert projection
-        if let Some(proj) = projection {
-            let mut projection_map = HashMap::new();
-            for (key, value) in proj.iter() {
-                let field: String = key.extract()?;
-                let action: i32 = value.extract()?;
-                projection_map.insert(field, action);
+        // Convert to Python list
+        Python::with_gil(|py| {
+            let py_list = PyList::empty(py);
+
+            for doc in results {
+                let py_dict = json_to_python_dict(py, &doc)?;
+                py_list.append(py_dict)?;
             }
-            options.projection = Some(projection_map);
-        }
 
-        // Convert sort
+            Ok(py_list.into())
+        })
+    }
+
+    /// Find documents as a pyarrow.RecordBatch (one column per field)
+    /// The batch is built in the core, as for export, and handed to pyarrow
+    /// without copying through the Arrow C data interface
+    #[pyo3(signature = (query=None, projection=None, sort=None, limit=None, skip=None, max_memory=None, max_time_ms=None, read_concern=None))]
+    #[allow(clippy::too_many_arguments)] // mirrors the Python keyword arguments
+    fn find_arrow(
+        &self,
+        query: Option<&PyDict>,
+        projection: Option<&PyDict>,
+        sort: Option<&PyList>,
+        limit: Option<usize>,
+        skip: Option<usize>,
+        max_memory: Option<usize>,
+        max_time_ms: Option<u64>,
+        read_concern: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let query_json = match query {
+            Some(q) => python_dict_to_json_value(q)?,
+            None => serde_json::json!({}),
+        };
+        let options = build_find_options(projection, sort, limit, skip, max_memory, max_time_ms, read_concern)?;
+
+        let batch = self.with_core(|core| core.find_arrow(&query_json, options))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let pyarrow = import_optional(py, "pyarrow", "find_arrow")?;
+            record_batch_to_pyarrow(pyarrow, batch)
+        })
+    }
+
+    /// Find documents as a pandas DataFrame (via pyarrow)
+    #[pyo3(signature = (query=None, projection=None, sort=None, limit=None, skip=None, max_memory=None, max_time_ms=None, read_concern=None))]
+    #[allow(clippy::too_many_arguments)] // mirrors the Python keyword arguments
+    fn find_pandas(
+        &self,
+        query: Option<&PyDict>,
+        projection: Option<&PyDict>,
+        sort: Option<&PyList>,
+        limit: Option<usize>,
+        skip: Option<usize>,
+        max_memory: Option<usize>,
+        max_time_ms: Option<u64>,
+        read_concern: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let batch = self.find_arrow(query, projection, sort, limit, skip, max_memory, max_time_ms, read_concern)?;
+
+        Python::with_gil(|py| {
+            import_optional(py, "pandas", "find_pandas")?;
+            Ok(batch.as_ref(py).call_method0("to_pandas")?.into())
+        })
+    }
+
+    /// Keyset pagination - returns {"documents": [...], "next_page_token": str | None}
+    #[pyo3(signature = (query=None, sort=None, page_token=None, page_size=100))]
+    fn find_page(
+        &self,
+        query: Option<&PyDict>,
+        sort: Option<&PyList>,
+        page_token: Option<&str>,
+        page_size: usize,
+    ) -> PyResult<PyObject> {
+        let query_json = match query {
+            Some(q) => python_dict_to_json_value(q)?,
+            None => serde_json::json!({}),
+        };
+
+        let mut sort_vec = Vec::new();
         if let Some(sort_list) = sort {
-            let mut sort_vec = Vec::new();
             for item in sort_list.iter() {
                 let tuple: &PyTuple = item.downcast()?;
                 let field: String = tuple.get_item(0)?.extract()?;
                 let direction: i32 = tuple.get_item(1)?.extract()?;
                 sort_vec.push((field, direction));
             }
-            options.sort = Some(sort_vec);
         }
 
-        // Set limit and skip
-        options.limit = limit;
-        options.skip = skip;
-
-        // Call core method
-        let results = self.core.find_with_options(&query_json, options)
+        let page = self.with_core(|core| core.find_page(&query_json, &sort_vec, page_token, page_size))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        // Convert to Python list
         Python::with_gil(|py| {
             let py_list = PyList::empty(py);
-
-            for doc in results {
-                let py_dict = json_to_python_dict(py, &doc)?;
-                py_list.append(py_dict)?;
+            for doc in page.documents {
+                py_list.append(json_to_python_dict(py, &doc)?)?;
             }
 
-            Ok(py_list.into())
+            let result = PyDict::new(py);
+            result.set_item("documents", py_list)?;
+            result.set_item("next_page_token", page.next_page_token)?;
+            Ok(result.into())
         })
     }
 
+    /// Find documents and resolve references to other collections
+    ///
+    /// Args:
+    ///     query: dict - Query filter
+    ///     populate: list of {"field": ..., "from": ..., "as": ...} dicts;
+    ///         "as" defaults to "field" (the _id is replaced)
+    ///
+    /// Example:
+    ///     orders.find_with_populate({}, [{"field": "user_id", "from": "users", "as": "user"}])
+    fn find_with_populate(&self, query: &PyDict, populate: &PyList) -> PyResult<PyObject> {
+        let query_json = python_dict_to_json_value(query)?;
+        let populate: Vec<ironbase_core::Populate> = serde_json::from_value(python_to_json(populate)?)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("invalid populate spec: {}", e)))?;
+        let results = self.with_core(|core| core.find_with_populate(&query_json, &populate))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| json_value_to_python(py, &Value::Array(results)))
+    }
+
     /// Find one document
     fn find_one(&self, query: Option<&PyDict>) -> PyResult<PyObject> {
         let query_json = match query {
@@ -366,7 +816,7 @@ impl Collection {
         };
 
         // Call core method
-        let result = self.core.find_one(&query_json)
+        let result = self.with_core(|core| core.find_one(&query_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert to Python
@@ -381,6 +831,32 @@ impl Collection {
         })
     }
 
+    /// Fetch many documents by _id in one call
+    /// Returns a list in input order, with None for missing ids
+    fn find_by_ids(&self, ids: &PyList) -> PyResult<PyObject> {
+        let mut doc_ids = Vec::with_capacity(ids.len());
+        for id in ids.iter() {
+            let id_json = python_to_json(id)?;
+            let doc_id: DocumentId = serde_json::from_value(id_json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            doc_ids.push(doc_id);
+        }
+
+        let results = self.with_core(|core| core.find_by_ids(&doc_ids))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let py_list = PyList::empty(py);
+            for result in results {
+                match result {
+                    Some(doc) => py_list.append(json_to_python_dict(py, &doc)?)?,
+                    None => py_list.append(py.None())?,
+                }
+            }
+            Ok(py_list.into())
+        })
+    }
+
     /// Count documents
     fn count_documents(&self, query: Option<&PyDict>) -> PyResult<u64> {
         let query_json = match query {
@@ -388,7 +864,7 @@ impl Collection {
             None => serde_json::json!({}),
         };
 
-        self.core.count_documents(&query_json)
+        self.with_core(|core| core.count_documents(&query_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
@@ -399,7 +875,7 @@ impl Collection {
             None => serde_json::json!({}),
         };
 
-        let distinct_values = self.core.distinct(field, &query_json)
+        let distinct_values = self.with_core(|core| core.distinct(field, &query_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert to Python list
@@ -418,16 +894,10 @@ impl Collection {
         let query_json = python_dict_to_json_value(query)?;
         let update_json = python_dict_to_json_value(update)?;
 
-        let (matched_count, modified_count) = self.core.update_one(&query_json, &update_json)
+        let result = self.with_core(|core| core.update_one(&query_json, &update_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("matched_count", matched_count)?;
-            result.set_item("modified_count", modified_count)?;
-            Ok(result.into())
-        })
+        Python::with_gil(|py| update_result_to_python(py, &result))
     }
 
     /// Update many documents
@@ -435,15 +905,65 @@ impl Collection {
         let query_json = python_dict_to_json_value(query)?;
         let update_json = python_dict_to_json_value(update)?;
 
-        let (matched_count, modified_count) = self.core.update_many(&query_json, &update_json)
+        let result = self.with_core(|core| core.update_many(&query_json, &update_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("matched_count", matched_count)?;
-            result.set_item("modified_count", modified_count)?;
-            Ok(result.into())
+        Python::with_gil(|py| update_result_to_python(py, &result))
+    }
+
+    /// Replace the first matching document in one atomic step
+    ///
+    /// Args:
+    ///     query: dict - Query to match the document
+    ///     replacement: dict - New document (keeps the existing _id)
+    ///     return_document: str - "before" (default) or "after"
+    ///
+    /// Returns:
+    ///     dict | None - The document before/after replacement, None if nothing matched
+    #[pyo3(signature = (query, replacement, return_document="before"))]
+    fn find_one_and_replace(&self, query: &PyDict, replacement: &PyDict, return_document: &str) -> PyResult<PyObject> {
+        let query_json = python_dict_to_json_value(query)?;
+        let replacement_json = python_dict_to_json_value(replacement)?;
+        let return_document = match return_document {
+            "before" => ReturnDocument::Before,
+            "after" => ReturnDocument::After,
+            other => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("return_document must be 'before' or 'after', got '{}'", other)
+            )),
+        };
+
+        let result = self.with_core(|core| core.find_one_and_replace(&query_json, &replacement_json, return_document))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| match result {
+            Some(doc) => Ok(json_to_python_dict(py, &doc)?.into()),
+            None => Ok(py.None()),
+        })
+    }
+
+    /// Atomically add `delta` to a numeric field of the first matching document
+    ///
+    /// Args:
+    ///     query: dict - Query to match the document
+    ///     field: str - Counter field (a missing field counts as 0)
+    ///     delta: int | float - Amount to add (default 1)
+    ///
+    /// Returns:
+    ///     int | float | None - The new value, None if nothing matched
+    #[pyo3(signature = (query, field, delta=None))]
+    fn increment(&self, query: &PyDict, field: &str, delta: Option<&PyAny>) -> PyResult<PyObject> {
+        let query_json = python_dict_to_json_value(query)?;
+        let delta_json = match delta {
+            Some(delta) => python_to_json(delta)?,
+            None => Value::from(1),
+        };
+
+        let result = self.with_core(|core| core.increment(&query_json, field, &delta_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| match result {
+            Some(value) => json_value_to_python(py, &value),
+            None => Ok(py.None()),
         })
     }
 
@@ -451,30 +971,46 @@ impl Collection {
     fn delete_one(&self, query: &PyDict) -> PyResult<PyObject> {
         let query_json = python_dict_to_json_value(query)?;
 
-        let deleted_count = self.core.delete_one(&query_json)
+        let result = self.with_core(|core| core.delete_one(&query_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("deleted_count", deleted_count)?;
-            Ok(result.into())
-        })
+        Python::with_gil(|py| delete_result_to_python(py, &result))
     }
 
     /// Delete many documents
     fn delete_many(&self, query: &PyDict) -> PyResult<PyObject> {
         let query_json = python_dict_to_json_value(query)?;
 
-        let deleted_count = self.core.delete_many(&query_json)
+        let result = self.with_core(|core| core.delete_many(&query_json))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("acknowledged", true)?;
-            result.set_item("deleted_count", deleted_count)?;
-            Ok(result.into())
-        })
+        Python::with_gil(|py| delete_result_to_python(py, &result))
+    }
+
+    /// Delete documents by _id (no scan); unknown ids are skipped
+    fn delete_many_by_ids(&self, ids: &PyList) -> PyResult<PyObject> {
+        let mut doc_ids = Vec::with_capacity(ids.len());
+        for id in ids.iter() {
+            let id_json = python_to_json(id)?;
+            let doc_id: DocumentId = serde_json::from_value(id_json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            doc_ids.push(doc_id);
+        }
+
+        let result = self.with_core(|core| core.delete_many_by_ids(&doc_ids))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| delete_result_to_python(py, &result))
+    }
+
+    /// delete_many() returning the deleted documents
+    fn find_and_delete_many(&self, query: &PyDict) -> PyResult<PyObject> {
+        let query_json = python_dict_to_json_value(query)?;
+
+        let removed = self.with_core(|core| core.find_and_delete_many(&query_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| json_value_to_python(py, &Value::Array(removed)))
     }
 
     /// Create an index on a field
@@ -482,16 +1018,246 @@ impl Collection {
     /// Args:
     ///     field: str - Field name to index
     ///     unique: bool - Whether the index should enforce uniqueness (default: False)
+    ///     progress: callable - Optional progress(processed, total) callback;
+    ///               returning False cancels the build
     ///
     /// Returns:
     ///     str - Index name
     ///
     /// Example:
     ///     collection.create_index("email", unique=True)
-    ///     collection.create_index("age")
-    #[pyo3(signature = (field, unique=false))]
-    fn create_index(&self, field: String, unique: bool) -> PyResult<String> {
-        self.core.create_index(field, unique)
+    ///     collection.create_index("age", progress=lambda done, total: print(f"{done}/{total}"))
+    #[pyo3(signature = (field, unique=false, progress=None))]
+    fn create_index(&self, field: String, unique: bool, progress: Option<PyObject>) -> PyResult<String> {
+        let callback = match progress {
+            Some(callback) => callback,
+            None => {
+                return self.with_core(|core| core.create_index(field, unique))?
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()));
+            }
+        };
+
+        // An exception raised by the callback cancels the build and is re-raised
+        let mut callback_error = None;
+        let result = self.with_core(|core| core.create_index_with_progress(field, unique, |p| {
+            Python::with_gil(|py| match callback.call1(py, (p.processed, p.total)) {
+                Ok(ret) => !matches!(ret.extract::<bool>(py), Ok(false)),
+                Err(e) => {
+                    callback_error = Some(e);
+                    false
+                }
+            })
+        }))?;
+
+        if let Some(e) = callback_error {
+            return Err(e);
+        }
+        result.map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Rebuild indexes from the documents, keeping their definitions
+    ///
+    /// Args:
+    ///     index_name: str - Index to rebuild (e.g. "users_email"); all if omitted
+    ///     progress: callable - Optional progress(processed, total) callback;
+    ///               returning False cancels the rebuild
+    ///
+    /// Returns:
+    ///     dict - Keys in each rebuilt index, by index name
+    ///
+    /// Example:
+    ///     collection.reindex()  # {"users_id": 1200, "users_email": 1200}
+    #[pyo3(signature = (index_name=None, progress=None))]
+    fn reindex(&self, index_name: Option<&str>, progress: Option<PyObject>) -> PyResult<HashMap<String, u64>> {
+        // An exception raised by the callback cancels the rebuild and is re-raised
+        let mut callback_error = None;
+        let result = self.with_core(|core| core.reindex_with_progress(index_name, |p| {
+            let Some(callback) = &progress else {
+                return true;
+            };
+            Python::with_gil(|py| match callback.call1(py, (p.processed, p.total)) {
+                Ok(ret) => !matches!(ret.extract::<bool>(py), Ok(false)),
+                Err(e) => {
+                    callback_error = Some(e);
+                    false
+                }
+            })
+        }))?;
+
+        if let Some(e) = callback_error {
+            return Err(e);
+        }
+        let rebuilt = result.map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(rebuilt.into_iter().map(|index| (index.index, index.keys)).collect())
+    }
+
+    /// Store a field encrypted from now on (needs db.set_encryption_key)
+    ///
+    /// Args:
+    ///     field: str - Top-level field name
+    ///     mode: str - "deterministic" (equality queries and indexes work)
+    ///         or "randomized" (only $exists queries)
+    #[pyo3(signature = (field, mode="deterministic"))]
+    fn encrypt_field(&self, field: &str, mode: &str) -> PyResult<()> {
+        let mode: ironbase_core::EncryptionMode = mode.parse()
+            .map_err(|e: ironbase_core::MongoLiteError| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_core(|core| core.encrypt_field(field, mode))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Encrypted fields and their modes, as a dict
+    fn encrypted_fields(&self) -> PyResult<HashMap<String, String>> {
+        let fields = self.with_core(|core| core.encrypted_fields())?;
+        Ok(fields.into_iter()
+            .map(|(field, mode)| (field, format!("{:?}", mode).to_lowercase()))
+            .collect())
+    }
+
+    /// Store an expression's result as a field of every document, recomputed
+    /// on each insert and update (existing documents are updated now)
+    ///
+    /// Args:
+    ///     name: str - Top-level field name
+    ///     expression: aggregation expression, e.g.
+    ///         {"$concat": ["$first", " ", "$last"]} or {"$toLower": "$email"}
+    fn add_computed_field(&self, name: &str, expression: &PyAny) -> PyResult<()> {
+        let expression = python_to_json(expression)?;
+        self.with_core(|core| core.add_computed_field(name, &expression))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Stop maintaining a computed field (stored values are kept);
+    /// returns False if there was none
+    fn drop_computed_field(&self, name: &str) -> PyResult<bool> {
+        self.with_core(|core| core.drop_computed_field(name))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Computed fields as a list of {"name", "expression"} dicts
+    fn computed_fields(&self) -> PyResult<Vec<PyObject>> {
+        let fields = self.with_core(|core| core.computed_fields())?;
+        Python::with_gil(|py| fields.iter()
+            .map(|field| Ok(json_to_python_dict(py, &serde_json::to_value(field).unwrap())?.into()))
+            .collect())
+    }
+
+    /// Keep per-segment Bloom filters on a top-level field, so full scans
+    /// for equality or $in on it skip file regions without a match
+    fn add_bloom_filter(&self, field: &str) -> PyResult<()> {
+        self.with_core(|core| core.add_bloom_filter(field))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Stop keeping Bloom filters on a field; returns False if there were none
+    fn drop_bloom_filter(&self, field: &str) -> PyResult<bool> {
+        self.with_core(|core| core.drop_bloom_filter(field))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Fields with Bloom filters
+    fn bloom_filters(&self) -> PyResult<Vec<String>> {
+        self.with_core(|core| core.bloom_filters())
+    }
+
+    /// Require `field` to hold the _id of a document in another collection
+    ///
+    /// Args:
+    ///     field: str - Top-level field name (missing or None is allowed)
+    ///     collection: str - Referenced collection
+    ///     on_delete: str - "restrict" (deleting a referenced document raises)
+    ///         or "cascade" (referencing documents are deleted too)
+    #[pyo3(signature = (field, collection, on_delete="restrict"))]
+    fn add_reference(&self, field: &str, collection: &str, on_delete: &str) -> PyResult<()> {
+        let on_delete: ironbase_core::OnDelete = on_delete.parse()
+            .map_err(|e: ironbase_core::MongoLiteError| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_core(|core| core.add_reference(field, collection, on_delete))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Remove the reference constraint on `field`; returns False if there was none
+    fn drop_reference(&self, field: &str) -> PyResult<bool> {
+        self.with_core(|core| core.drop_reference(field))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Reference constraints as a list of {"field", "collection", "on_delete"} dicts
+    fn references(&self) -> PyResult<Vec<HashMap<String, String>>> {
+        let references = self.with_core(|core| core.references())?;
+        Ok(references.into_iter()
+            .map(|reference| HashMap::from([
+                ("field".to_string(), reference.field),
+                ("collection".to_string(), reference.collection),
+                ("on_delete".to_string(), format!("{:?}", reference.on_delete).to_lowercase()),
+            ]))
+            .collect())
+    }
+
+    /// Limit live documents and/or bytes; None removes a limit
+    /// Inserts past a limit raise ("Quota exceeded: ...") and write nothing
+    #[pyo3(signature = (max_documents=None, max_bytes=None))]
+    fn set_quota(&self, max_documents: Option<u64>, max_bytes: Option<u64>) -> PyResult<()> {
+        let quota = ironbase_core::CollectionQuota { max_documents, max_bytes };
+        self.with_core(|core| core.set_quota(quota))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Current quota as a dict (max_documents, max_bytes; None = unlimited)
+    fn quota(&self) -> PyResult<HashMap<String, Option<u64>>> {
+        let quota = self.with_core(|core| core.quota())?;
+        Ok(HashMap::from([
+            ("max_documents".to_string(), quota.max_documents),
+            ("max_bytes".to_string(), quota.max_bytes),
+        ]))
+    }
+
+    /// Register a write hook (trigger) on this collection
+    ///
+    /// Args:
+    ///     event: str - before_insert, after_insert, before_update,
+    ///         after_update, before_delete or after_delete
+    ///     callback: callable(doc) - before-hooks may return a changed dict
+    ///         (None keeps the document) and raise to reject the write
+    ///
+    /// Example:
+    ///     def require_email(doc):
+    ///         if "email" not in doc:
+    ///             raise ValueError("email is required")
+    ///         return {**doc, "email": doc["email"].lower()}
+    ///     users.add_hook("before_insert", require_email)
+    fn add_hook(&self, event: &str, callback: PyObject) -> PyResult<()> {
+        let event: ironbase_core::HookEvent = event.parse()
+            .map_err(|e: ironbase_core::MongoLiteError| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        self.with_core(|core| core.add_hook(event, move |doc| {
+            Python::with_gil(|py| {
+                let ret = callback.call1(py, (json_value_to_python(py, doc)?,))?;
+                if let Ok(dict) = ret.downcast::<PyDict>(py) {
+                    *doc = python_dict_to_json_value(dict)?;
+                }
+                Ok::<_, PyErr>(())
+            }).map_err(|e| ironbase_core::MongoLiteError::HookRejected(e.to_string()))
+        }))
+    }
+
+    /// Remove every hook of this collection
+    fn clear_hooks(&self) -> PyResult<()> {
+        self.with_core(|core| core.clear_hooks())
+    }
+
+    /// Create a hashed index on a field
+    ///
+    /// Stores hashes of the values - compact for long strings, but only
+    /// equality and $in queries can use it (no ranges, no covered queries).
+    ///
+    /// Args:
+    ///     field: str - Field name to index
+    ///
+    /// Returns:
+    ///     str - Index name
+    ///
+    /// Example:
+    ///     collection.create_hashed_index("url")  # "pages_url_hashed"
+    fn create_hashed_index(&self, field: String) -> PyResult<String> {
+        self.with_core(|core| core.create_hashed_index(field))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
@@ -503,7 +1269,7 @@ impl Collection {
     /// Example:
     ///     collection.drop_index("users_email")
     fn drop_index(&self, index_name: String) -> PyResult<()> {
-        self.core.drop_index(&index_name)
+        self.with_core(|core| core.drop_index(&index_name))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
     }
 
@@ -516,13 +1282,14 @@ impl Collection {
     ///     indexes = collection.list_indexes()
     ///     print(indexes)  # ['users_id', 'users_email', 'users_age']
     fn list_indexes(&self) -> PyResult<Vec<String>> {
-        Ok(self.core.list_indexes())
+        self.with_core(|core| core.list_indexes())
     }
 
     /// Explain the query execution plan without executing the query
     ///
     /// Args:
     ///     query: dict - MongoDB-style query
+    ///     projection: dict - Optional projection, used to detect covered queries
     ///
     /// Returns:
     ///     dict - Query plan with information about index usage
@@ -531,10 +1298,26 @@ impl Collection {
     ///     plan = collection.explain({"age": 25})
     ///     print(plan["queryPlan"])  # "IndexScan" or "CollectionScan"
     ///     print(plan["indexUsed"])  # "users_age" or null
-    fn explain(&self, query: &PyDict) -> PyResult<PyObject> {
+    ///     plan = collection.explain({"age": 25}, projection={"age": 1, "_id": 0})
+    ///     print(plan["coveredQuery"])  # True - answered from the index alone
+    #[pyo3(signature = (query, projection=None))]
+    fn explain(&self, query: &PyDict, projection: Option<&PyDict>) -> PyResult<PyObject> {
         let query_json = python_dict_to_json_value(query)?;
 
-        let plan = self.core.explain(&query_json)
+        use ironbase_core::find_options::FindOptions;
+
+        let mut options = FindOptions::new();
+        if let Some(proj) = projection {
+            let mut projection_map = HashMap::new();
+            for (key, value) in proj.iter() {
+                let field: String = key.extract()?;
+                let action: i32 = value.extract()?;
+                projection_map.insert(field, action);
+            }
+            options.projection = Some(projection_map);
+        }
+
+        let plan = self.with_core(|core| core.explain_with_options(&query_json, &options))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert JSON Value to Python dict
@@ -544,6 +1327,101 @@ impl Collection {
         })
     }
 
+    /// Explain an aggregation pipeline without running it
+    ///
+    /// Args:
+    ///     pipeline: list - Aggregation pipeline stages
+    ///
+    /// Returns:
+    ///     dict - Stages in execution order, index use of the leading $match,
+    ///     blocking stages and estimated memory
+    ///
+    /// Example:
+    ///     plan = collection.explain_aggregate([{"$match": {"age": 25}}, {"$limit": 10}])
+    ///     print(plan["input"])           # "INDEX" or "SNAPSHOT_SCAN"
+    ///     print(plan["limitPushdown"])   # 10 - documents read at most
+    fn explain_aggregate(&self, pipeline: &PyList) -> PyResult<PyObject> {
+        let mut stages = Vec::new();
+        for stage in pipeline.iter() {
+            let stage_dict: &PyDict = stage.downcast()?;
+            stages.push(python_dict_to_json_value(stage_dict)?);
+        }
+        let pipeline_json = serde_json::Value::Array(stages);
+
+        let plan = self.with_core(|core| core.explain_aggregate(&pipeline_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let py_dict = json_to_python_dict(py, &plan)?;
+            Ok(py_dict.into())
+        })
+    }
+
+    /// Storage statistics for this collection
+    ///
+    /// Returns:
+    ///     dict - live/tombstone counts, live and dead bytes, index sizes
+    ///
+    /// Example:
+    ///     stats = collection.stats()
+    ///     if stats["dead_bytes"] > stats["live_bytes"]:
+    ///         db.compact()
+    fn stats(&self) -> PyResult<PyObject> {
+        let stats = self.with_core(|core| core.stats())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let py_dict = json_to_python_dict(py, &stats)?;
+            Ok(py_dict.into())
+        })
+    }
+
+    /// Gather the statistics the query planner chooses indexes by
+    ///
+    /// Returns:
+    ///     dict - document counts and per-field distinct counts and histograms
+    ///
+    /// Example:
+    ///     collection.analyze()
+    ///     print(collection.explain({"status": "open", "city": "Pécs"})["estimatedDocuments"])
+    fn analyze(&self) -> PyResult<PyObject> {
+        let statistics = self.with_core(|core| core.analyze())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        let statistics = serde_json::to_value(&statistics)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let py_dict = json_to_python_dict(py, &statistics)?;
+            Ok(py_dict.into())
+        })
+    }
+
+    /// Check collection integrity (catalog, records and indexes)
+    ///
+    /// Args:
+    ///     full: bool - also walk the whole data region (slower)
+    ///
+    /// Returns:
+    ///     dict - report with "valid", counters and a list of "issues"
+    ///
+    /// Example:
+    ///     report = collection.validate(full=True)
+    ///     if not report["valid"]:
+    ///         print(report["issues"])
+    #[pyo3(signature = (full=false))]
+    fn validate(&self, full: bool) -> PyResult<PyObject> {
+        let report = self.with_core(|core| core.validate(full))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        let report_json = serde_json::to_value(&report)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let py_dict = json_to_python_dict(py, &report_json)?;
+            Ok(py_dict.into())
+        })
+    }
+
     /// Execute a query with manual index selection (hint)
     ///
     /// Args:
@@ -559,7 +1437,7 @@ impl Collection {
     fn find_with_hint(&self, query: &PyDict, hint: String) -> PyResult<PyObject> {
         let query_json = python_dict_to_json_value(query)?;
 
-        let results = self.core.find_with_hint(&query_json, &hint)
+        let results = self.with_core(|core| core.find_with_hint(&query_json, &hint))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert to Python list
@@ -579,6 +1457,7 @@ impl Collection {
     ///
     /// Args:
     ///     pipeline: list - List of aggregation stage dictionaries
+    ///     max_time_ms: int - Time limit; the pipeline fails once it runs longer (optional)
     ///
     /// Returns:
     ///     list - Aggregation results
@@ -590,7 +1469,8 @@ impl Collection {
     ///         {"$group": {"_id": "$city", "count": {"$sum": 1}}},
     ///         {"$sort": {"count": -1}}
     ///     ])
-    fn aggregate(&self, pipeline: &PyList) -> PyResult<PyObject> {
+    #[pyo3(signature = (pipeline, read_concern=None, max_time_ms=None))]
+    fn aggregate(&self, pipeline: &PyList, read_concern: Option<&str>, max_time_ms: Option<u64>) -> PyResult<PyObject> {
         // Convert Python list to JSON array
         let mut stages = Vec::new();
         for stage in pipeline.iter() {
@@ -602,7 +1482,8 @@ impl Collection {
         let pipeline_json = serde_json::Value::Array(stages);
 
         // Execute aggregation
-        let results = self.core.aggregate(&pipeline_json)
+        let options = build_find_options(None, None, None, None, None, max_time_ms, read_concern)?;
+        let results = self.with_core(|core| core.aggregate_with_options(&pipeline_json, &options))?
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
 
         // Convert to Python list
@@ -618,6 +1499,40 @@ impl Collection {
         })
     }
 
+    /// Bulk insert the rows of a pandas DataFrame as documents
+    /// Missing values (None / NaN / NaT) leave the field out of the document
+    fn insert_dataframe(&self, dataframe: &PyAny) -> PyResult<PyObject> {
+        let row_count = dataframe.len()?;
+        let mut docs: Vec<HashMap<String, Value>> = vec![HashMap::new(); row_count];
+
+        // Column by column: one tolist() call per column instead of one dict per row
+        for column in dataframe.getattr("columns")?.iter()? {
+            let column = column?;
+            let name = column.str()?.to_string();
+            let values = dataframe.get_item(column)?.call_method0("tolist")?;
+            let values: &PyList = values.downcast()?;
+
+            for (doc, value) in docs.iter_mut().zip(values.iter()) {
+                match dataframe_cell_to_json(value)? {
+                    Value::Null => {}
+                    value => {
+                        doc.insert(name.clone(), value);
+                    }
+                }
+            }
+        }
+
+        let result = self.with_core(|core| core.insert_many(docs))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+        Python::with_gil(|py| {
+            let result_dict = PyDict::new(py);
+            result_dict.set_item("acknowledged", result.acknowledged)?;
+            result_dict.set_item("inserted_count", result.inserted_count)?;
+            Ok(result_dict.into())
+        })
+    }
+
     fn __repr__(&self) -> String {
         format!("Collection('{}')", self.core.name)
     }
@@ -625,18 +1540,52 @@ impl Collection {
 
 // ========== PYTHON <-> JSON CONVERSION HELPERS ==========
 
+/// UpdateResult -> {"acknowledged", "matched_count", "modified_count", "upserted_id"}
+fn update_result_to_python(py: Python, result: &UpdateResult) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("acknowledged", result.acknowledged)?;
+    dict.set_item("matched_count", result.matched_count)?;
+    dict.set_item("modified_count", result.modified_count)?;
+    let upserted_id = serde_json::to_value(&result.upserted_id)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    dict.set_item("upserted_id", json_value_to_python(py, &upserted_id)?)?;
+    Ok(dict.into())
+}
+
+/// DeleteResult -> {"acknowledged", "deleted_count"}
+fn delete_result_to_python(py: Python, result: &DeleteResult) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("acknowledged", result.acknowledged)?;
+    dict.set_item("deleted_count", result.deleted_count)?;
+    Ok(dict.into())
+}
+
 /// Python érték -> JSON konverzió
+///
+/// Numbers follow ironbase_core::numeric: ints stay ints (i64, or u64 above
+/// i64::MAX) and floats stay floats; values that cannot be kept exactly
+/// (ints beyond u64, NaN/inf) are rejected rather than stored altered.
 fn python_to_json(value: &PyAny) -> PyResult<Value> {
     if value.is_none() {
         Ok(Value::Null)
-    } else if let Ok(b) = value.extract::<bool>() {
-        Ok(Value::Bool(b))
-    } else if let Ok(i) = value.extract::<i64>() {
-        Ok(Value::Number(i.into()))
-    } else if let Ok(f) = value.extract::<f64>() {
-        Ok(serde_json::Number::from_f64(f)
+    } else if let Ok(b) = value.downcast::<PyBool>() {
+        Ok(Value::Bool(b.is_true()))
+    } else if value.is_instance_of::<PyLong>() {
+        if let Ok(i) = value.extract::<i64>() {
+            Ok(Value::Number(i.into()))
+        } else if let Ok(u) = value.extract::<u64>() {
+            Ok(Value::Number(u.into()))
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyOverflowError, _>(
+                format!("integer {} does not fit in 64 bits", value)
+            ))
+        }
+    } else if let Ok(f) = value.downcast::<PyFloat>() {
+        serde_json::Number::from_f64(f.value())
             .map(Value::Number)
-            .unwrap_or(Value::Null))
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("cannot store non-finite float {}", f.value())
+            ))
     } else if let Ok(s) = value.extract::<String>() {
         Ok(Value::String(s))
     } else if let Ok(list) = value.downcast::<PyList>() {
@@ -652,6 +1601,13 @@ fn python_to_json(value: &PyAny) -> PyResult<Value> {
             map.insert(key, python_to_json(v)?);
         }
         Ok(Value::Object(map))
+    } else if let Ok(i) = value.extract::<i64>() {
+        // Other integer types (e.g. numpy.int64) via __index__
+        Ok(Value::Number(i.into()))
+    } else if let Ok(u) = value.extract::<u64>() {
+        Ok(Value::Number(u.into()))
+    } else if let Some(f) = value.extract::<f64>().ok().and_then(serde_json::Number::from_f64) {
+        Ok(Value::Number(f))
     } else {
         Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
             format!("Unsupported type: {:?}", value.get_type())
@@ -691,6 +1647,8 @@ fn json_value_to_python(py: Python, value: &Value) -> PyResult<PyObject> {
         Value::Number(n) => {
             if let Some(i) = n.as_i64() {
                 Ok(i.into_py(py))
+            } else if let Some(u) = n.as_u64() {
+                Ok(u.into_py(py))
             } else if let Some(f) = n.as_f64() {
                 Ok(f.into_py(py))
             } else {
@@ -715,10 +1673,272 @@ fn json_value_to_python(py: Python, value: &Value) -> PyResult<PyObject> {
     }
 }
 
+// ========== FIND / DATAFRAME HELPERS ==========
+
+/// Python find() arguments -> FindOptions
+fn build_find_options(
+    projection: Option<&PyDict>,
+    sort: Option<&PyList>,
+    limit: Option<usize>,
+    skip: Option<usize>,
+    max_memory: Option<usize>,
+    max_time_ms: Option<u64>,
+    read_concern: Option<&str>,
+) -> PyResult<ironbase_core::FindOptions> {
+    let mut options = ironbase_core::FindOptions::new();
+
+    // Convert projection
+    if let Some(proj) = projection {
+        let mut projection_map = HashMap::new();
+        for (key, value) in proj.iter() {
+            let field: String = key.extract()?;
+            let action: i32 = value.extract()?;
+            projection_map.insert(field, action);
+        }
+        options.projection = Some(projection_map);
+    }
+
+    // Convert sort
+    if let Some(sort_list) = sort {
+        let mut sort_vec = Vec::new();
+        for item in sort_list.iter() {
+            let tuple: &PyTuple = item.downcast()?;
+            let field: String = tuple.get_item(0)?.extract()?;
+            let direction: i32 = tuple.get_item(1)?.extract()?;
+            sort_vec.push((field, direction));
+        }
+        options.sort = Some(sort_vec);
+    }
+
+    // Set limit, skip, memory and time limits
+    options.limit = limit;
+    options.skip = skip;
+    options.max_memory_bytes = max_memory;
+    options.max_time_ms = max_time_ms;
+    if let Some(read_concern) = read_concern {
+        options.read_concern = read_concern.parse()
+            .map_err(|e: ironbase_core::MongoLiteError| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    }
+
+    Ok(options)
+}
+
+/// Import an optional dependency with a helpful error
+fn compaction_stats_to_python(py: Python, stats: &CompactionStats) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("size_before", stats.size_before)?;
+    dict.set_item("size_after", stats.size_after)?;
+    dict.set_item("space_saved", stats.space_saved())?;
+    dict.set_item("documents_scanned", stats.documents_scanned)?;
+    dict.set_item("documents_kept", stats.documents_kept)?;
+    dict.set_item("tombstones_removed", stats.tombstones_removed)?;
+    dict.set_item("peak_memory_mb", stats.peak_memory_mb)?;
+    dict.set_item("compression_ratio", stats.compression_ratio())?;
+
+    let collections = PyDict::new(py);
+    for (name, coll_stats) in &stats.collections {
+        let entry = PyDict::new(py);
+        entry.set_item("documents_scanned", coll_stats.documents_scanned)?;
+        entry.set_item("documents_kept", coll_stats.documents_kept)?;
+        entry.set_item("tombstones_removed", coll_stats.tombstones_removed)?;
+        entry.set_item("bytes_reclaimed", coll_stats.bytes_reclaimed)?;
+        collections.set_item(name, entry)?;
+    }
+    dict.set_item("collections", collections)?;
+    Ok(dict.into())
+}
+
+fn import_optional<'a>(py: Python<'a>, module: &str, feature: &str) -> PyResult<&'a PyModule> {
+    py.import(module).map_err(|_| PyErr::new::<pyo3::exceptions::PyImportError, _>(
+        format!("{}() requires the '{}' package", feature, module)
+    ))
+}
+
+/// Record batch -> pyarrow.RecordBatch through the Arrow C data interface
+/// pyarrow moves the buffers out of the exported structs, so they only need
+/// to live until the import returns (dropping them releases nothing twice)
+fn record_batch_to_pyarrow(pyarrow: &PyModule, batch: RecordBatch) -> PyResult<PyObject> {
+    let data = StructArray::from(batch).into_data();
+    let (mut array, mut schema) = arrow_array::ffi::to_ffi(&data)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+
+    let batch = pyarrow.getattr("RecordBatch")?.call_method1("_import_from_c", (
+        std::ptr::addr_of_mut!(array) as usize,
+        std::ptr::addr_of_mut!(schema) as usize,
+    ))?;
+    Ok(batch.into())
+}
+
+/// DataFrame cell (after tolist()) -> JSON
+/// Timestamps and other date-like values are stored as ISO 8601 strings
+fn dataframe_cell_to_json(value: &PyAny) -> PyResult<Value> {
+    // NaT compares unequal to itself like NaN, and has no usable isoformat()
+    if !value.is_none() && value.ne(value)? {
+        return Ok(Value::Null);
+    }
+    match python_to_json(value) {
+        Ok(json) => Ok(json),
+        Err(e) => match value.getattr("isoformat") {
+            Ok(isoformat) => Ok(Value::String(isoformat.call0()?.extract()?)),
+            Err(_) => Err(e),
+        },
+    }
+}
+
 /// Python modul inicializálás
+/// Read-only view (see IronBase.create_view)
+#[pyclass(frozen)]
+pub struct View {
+    core: ironbase_core::View,
+    db: Weak<DatabaseCore>,
+}
+
+impl View {
+    fn with_core<T: Send>(&self, f: impl FnOnce(&ironbase_core::View) -> T + Send) -> PyResult<T> {
+        let _db = self.db.upgrade().ok_or_else(|| DatabaseClosedError::new_err(format!(
+            "view '{}' used after its database was closed", self.core.name()
+        )))?;
+        Ok(without_gil(|| f(&self.core)))
+    }
+}
+
+#[pymethods]
+impl View {
+    #[getter]
+    fn name(&self) -> String {
+        self.core.name().to_string()
+    }
+
+    /// Documents of the view matching the query
+    #[pyo3(signature = (query=None))]
+    fn find(&self, query: Option<&PyDict>) -> PyResult<PyObject> {
+        let query_json = match query {
+            Some(q) => python_dict_to_json_value(q)?,
+            None => serde_json::json!({}),
+        };
+        let results = self.with_core(|core| core.find(&query_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| json_value_to_python(py, &Value::Array(results)))
+    }
+
+    #[pyo3(signature = (query=None))]
+    fn count_documents(&self, query: Option<&PyDict>) -> PyResult<u64> {
+        let query_json = match query {
+            Some(q) => python_dict_to_json_value(q)?,
+            None => serde_json::json!({}),
+        };
+        self.with_core(|core| core.count_documents(&query_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Run a pipeline on the view's documents
+    fn aggregate(&self, pipeline: &PyList) -> PyResult<PyObject> {
+        let pipeline_json = python_to_json(pipeline)?;
+        let results = self.with_core(|core| core.aggregate(&pipeline_json))?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Python::with_gil(|py| json_value_to_python(py, &Value::Array(results)))
+    }
+
+    /// Recompute a materialized view's results
+    fn refresh(&self) -> PyResult<()> {
+        self.with_core(|core| core.refresh())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+}
+
+/// Named sequence (see IronBase.get_sequence)
+#[pyclass(frozen)]
+pub struct Sequence {
+    core: ironbase_core::Sequence,
+    db: Weak<DatabaseCore>,
+}
+
+impl Sequence {
+    fn with_core<T: Send>(&self, f: impl FnOnce(&ironbase_core::Sequence) -> T + Send) -> PyResult<T> {
+        let _db = self.db.upgrade().ok_or_else(|| DatabaseClosedError::new_err(format!(
+            "sequence '{}' used after its database was closed", self.core.name()
+        )))?;
+        Ok(without_gil(|| f(&self.core)))
+    }
+}
+
+#[pymethods]
+impl Sequence {
+    #[getter]
+    fn name(&self) -> String {
+        self.core.name().to_string()
+    }
+
+    /// Allocate the next value (the first one is 1; a crash may leave a gap)
+    fn next(&self) -> PyResult<u64> {
+        self.with_core(|core| core.next())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Last value allocated, 0 if none
+    fn current(&self) -> PyResult<u64> {
+        self.with_core(|core| core.current())?
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+}
+
+/// Run `func(tx_id)` in a transaction and commit it, starting over in a new
+/// transaction when it loses a conflict with another one (WriteConflictError)
+///
+/// Args:
+///     db: IronBase
+///     func: callable taking the transaction id; what it returns is returned
+///     max_attempts: int - attempts before the last WriteConflictError is raised
+///     backoff: float - seconds slept before the first retry, doubled after each
+///     durability: "durable" or "relaxed", as for begin_transaction
+///
+/// Other exceptions roll the transaction back and propagate at once.
+///
+/// Example:
+///     def rename(tx_id):
+///         db.update_one_tx("users", {"name": "Alice"}, {"name": "Alicia"}, tx_id)
+///     ironbase.with_retry(db, rename, max_attempts=5)
+#[pyfunction]
+#[pyo3(signature = (db, func, max_attempts=5, backoff=0.01, durability="durable"))]
+fn with_retry(py: Python, db: PyRef<'_, IronBase>, func: &PyAny, max_attempts: u32, backoff: f64, durability: &str) -> PyResult<PyObject> {
+    if max_attempts == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_attempts must be at least 1"));
+    }
+    let mut delay = std::time::Duration::try_from_secs_f64(backoff)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+
+    let mut attempt = 1;
+    loop {
+        let tx_id = db.begin_transaction(durability)?;
+        let outcome = func.call1((tx_id,)).and_then(|result| {
+            db.commit_transaction(tx_id)?;
+            Ok(result.into_py(py))
+        });
+        let error = match outcome {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+
+        // A lost conflict has rolled the transaction back already, other failures have not
+        let _ = db.rollback_transaction(tx_id);
+        if attempt == max_attempts || !error.is_instance_of::<WriteConflictError>(py) {
+            return Err(error);
+        }
+        py.allow_threads(|| std::thread::sleep(delay));
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
 #[pymodule]
-fn ironbase(_py: Python, m: &PyModule) -> PyResult<()> {
+fn ironbase(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<IronBase>()?;
     m.add_class::<Collection>()?;
+    m.add_class::<View>()?;
+    m.add_class::<Sequence>()?;
+    m.add("DatabaseClosedError", py.get_type::<DatabaseClosedError>())?;
+    m.add("TransactionError", py.get_type::<TransactionError>())?;
+    m.add("WriteConflictError", py.get_type::<WriteConflictError>())?;
+    m.add_function(wrap_pyfunction!(with_retry, m)?)?;
     Ok(())
 }
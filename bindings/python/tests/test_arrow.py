@@ -0,0 +1,98 @@
+# find_arrow / find_pandas / insert_dataframe of the Python bindings
+import pytest
+
+from ironbase import IronBase
+
+pa = pytest.importorskip("pyarrow")
+
+
+@pytest.fixture
+def db(tmp_path):
+    db = IronBase(str(tmp_path / "test.mlite"))
+    yield db
+    db.close()
+
+
+def test_find_arrow_columns_and_types(db):
+    users = db.collection("users")
+    users.insert_many([
+        {"name": "alice", "age": 30, "score": 1.5, "active": True},
+        {"name": "bob", "age": 25, "score": 2, "active": False},
+    ])
+
+    batch = users.find_arrow(sort=[("name", 1)])
+    assert isinstance(batch, pa.RecordBatch)
+    assert batch.num_rows == 2
+    assert batch.schema.names[0] == "_id"
+    assert batch.schema.field("name").type == pa.string()
+    assert batch.schema.field("age").type == pa.int64()
+    # Ints and floats in one column widen to float64
+    assert batch.schema.field("score").type == pa.float64()
+    assert batch.schema.field("active").type == pa.bool_()
+    assert batch.column("name").to_pylist() == ["alice", "bob"]
+    assert batch.column("score").to_pylist() == [1.5, 2.0]
+
+    # Projection, filter and limit apply before the columns are built
+    batch = users.find_arrow({"age": {"$gt": 26}}, projection={"name": 1, "_id": 0}, limit=5)
+    assert batch.schema.names == ["name"]
+    assert batch.column("name").to_pylist() == ["alice"]
+
+
+def test_find_arrow_nulls(db):
+    users = db.collection("users")
+    users.insert_many([
+        {"name": "alice", "age": 30},
+        {"name": "bob", "age": None},
+        {"name": "carol"},
+        {"name": "dave", "nickname": None},
+    ])
+
+    batch = users.find_arrow(sort=[("name", 1)])
+    # Missing fields and nulls are both nulls of the column's type
+    assert batch.schema.field("age").type == pa.int64()
+    assert batch.column("age").to_pylist() == [30, None, None, None]
+    assert batch.column("age").null_count == 3
+    # A column of nulls only is a string column
+    assert batch.schema.field("nickname").type == pa.string()
+    assert batch.column("nickname").null_count == 4
+
+
+def test_find_arrow_mixed_type_columns(db):
+    items = db.collection("items")
+    items.insert_many([
+        {"n": 1, "value": 1},
+        {"n": 2, "value": "a"},
+        {"n": 3, "value": [1, 2]},
+        {"n": 4, "value": {"x": True}},
+        {"n": 5},
+    ])
+
+    batch = items.find_arrow(sort=[("n", 1)])
+    # Mixed types, arrays and objects are JSON text
+    assert batch.schema.field("value").type == pa.string()
+    assert batch.column("value").to_pylist() == ["1", '"a"', "[1,2]", '{"x":true}', None]
+
+
+def test_find_arrow_without_results(db):
+    batch = db.collection("users").find_arrow({"name": "nobody"})
+    assert batch.num_rows == 0
+    assert batch.num_columns == 0
+
+
+def test_dataframe_round_trip(db):
+    pd = pytest.importorskip("pandas")
+    users = db.collection("users")
+    frame = pd.DataFrame({
+        "name": ["alice", "bob", "carol"],
+        "age": [30, 25, 41],
+        "score": [1.5, None, 3.25],
+        "active": [True, False, True],
+    })
+
+    assert users.insert_dataframe(frame) == {"acknowledged": True, "inserted_count": 3}
+    # NaN leaves the field out of the document
+    assert "score" not in users.find_one({"name": "bob"})
+
+    result = users.find_pandas(projection={"_id": 0}, sort=[("name", 1)])
+    assert isinstance(result, pd.DataFrame)
+    pd.testing.assert_frame_equal(result[list(frame.columns)], frame)
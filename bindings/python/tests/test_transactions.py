@@ -0,0 +1,89 @@
+# Transaction errors and with_retry of the Python bindings
+import pytest
+
+import ironbase
+from ironbase import IronBase, TransactionError, WriteConflictError, with_retry
+
+
+@pytest.fixture
+def db(tmp_path):
+    db = IronBase(str(tmp_path / "test.mlite"))
+    db.collection("accounts").insert_one({"name": "a", "balance": 100})
+    db.set_lock_timeout(0.05)
+    yield db
+    db.close()
+
+
+def balance(db, name):
+    return db.collection("accounts").find_one({"name": name})["balance"]
+
+
+def test_latch_timeout_is_a_write_conflict(db):
+    holder = db.begin_transaction()
+    db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 1}, holder)
+
+    loser = db.begin_transaction()
+    with pytest.raises(WriteConflictError):
+        db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 2}, loser)
+    assert issubclass(WriteConflictError, TransactionError)
+
+    # The loser was rolled back; committing it again is a plain TransactionError
+    with pytest.raises(TransactionError) as error:
+        db.commit_transaction(loser)
+    assert not isinstance(error.value, WriteConflictError)
+    db.commit_transaction(holder)
+    assert balance(db, "a") == 1
+
+
+def test_with_retry_starts_over_after_a_write_conflict(db):
+    holder = db.begin_transaction()
+    db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 1}, holder)
+    attempts = []
+
+    def pay(tx_id):
+        attempts.append(tx_id)
+        if len(attempts) == 2:
+            db.commit_transaction(holder)
+        db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 50}, tx_id)
+        return "paid"
+
+    assert with_retry(db, pay, max_attempts=3, backoff=0) == "paid"
+    assert len(attempts) == 2 and attempts[0] != attempts[1]
+    assert balance(db, "a") == 50
+
+
+def test_with_retry_gives_up_after_max_attempts(db):
+    holder = db.begin_transaction()
+    db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 1}, holder)
+    attempts = []
+
+    def pay(tx_id):
+        attempts.append(tx_id)
+        db.update_one_tx("accounts", {"name": "a"}, {"name": "a", "balance": 50}, tx_id)
+
+    with pytest.raises(WriteConflictError):
+        with_retry(db, pay, max_attempts=2, backoff=0)
+    assert len(attempts) == 2
+    db.rollback_transaction(holder)
+
+
+def test_with_retry_does_not_retry_other_errors(db):
+    attempts = []
+
+    def fail(tx_id):
+        attempts.append(tx_id)
+        db.insert_one_tx("accounts", {"name": "b", "balance": 0}, tx_id)
+        raise ValueError("not a conflict")
+
+    with pytest.raises(ValueError):
+        with_retry(db, fail, backoff=0)
+    assert len(attempts) == 1
+    # Rolled back: the insert never lands
+    assert db.collection("accounts").find_one({"name": "b"}) is None
+    with pytest.raises(ValueError):
+        with_retry(db, fail, max_attempts=0)
+
+
+def test_module_exports():
+    for name in ("TransactionError", "WriteConflictError", "with_retry"):
+        assert hasattr(ironbase, name)
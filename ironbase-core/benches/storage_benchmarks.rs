@@ -0,0 +1,199 @@
+// Criterion benchmarks for the storage paths a storage redesign touches:
+// insert throughput, index vs scan lookups, range queries, aggregation,
+// index builds, commits and compaction
+//
+//     cargo bench -p ironbase-core --bench storage_benchmarks
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use ironbase_core::{CollectionCore, DatabaseCore, Durability};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+/// Documents in the collections lookups, scans and aggregations run on
+const DOCS: i64 = 10_000;
+
+/// Documents per insert batch
+const BATCH: i64 = 100;
+
+const CITIES: [&str; 4] = ["Budapest", "Vienna", "Prague", "Warsaw"];
+
+fn doc(n: i64) -> HashMap<String, Value> {
+    serde_json::from_value(json!({
+        "n": n,
+        "email": format!("user{}@example.com", n),
+        "age": 18 + n % 60,
+        "city": CITIES[(n % 4) as usize],
+        "score": (n * 7919) % 1000,
+    }))
+    .unwrap()
+}
+
+/// A database with DOCS documents in "users", with indexes on `indexed`
+fn populated(indexed: &[&str]) -> (TempDir, DatabaseCore, CollectionCore) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("bench.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    for field in indexed {
+        users.create_index(field.to_string(), *field == "email").unwrap();
+    }
+    users.insert_many((0..DOCS).map(doc).collect()).unwrap();
+    db.flush().unwrap();
+    (temp_dir, db, users)
+}
+
+// ========== INSERT BENCHMARKS ==========
+
+fn bench_insert_indexes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_batch");
+    group.throughput(Throughput::Elements(BATCH as u64));
+
+    for indexes in [0usize, 1, 3] {
+        group.bench_with_input(BenchmarkId::new("indexes", indexes), &indexes, |b, &indexes| {
+            let temp_dir = TempDir::new().unwrap();
+            let db = DatabaseCore::open(temp_dir.path().join("bench.mlite")).unwrap();
+            let users = db.collection("users").unwrap();
+            for field in ["age", "city", "score"].iter().take(indexes) {
+                users.create_index(field.to_string(), false).unwrap();
+            }
+
+            let mut next = 0;
+            b.iter(|| {
+                let docs = (next..next + BATCH).map(doc).collect();
+                next += BATCH;
+                black_box(users.insert_many(docs).unwrap());
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_commit_durability(c: &mut Criterion) {
+    let mut group = c.benchmark_group("commit");
+    group.throughput(Throughput::Elements(1));
+
+    for (name, durability) in [("durable", Durability::Durable), ("relaxed", Durability::Relaxed)] {
+        group.bench_function(name, |b| {
+            let temp_dir = TempDir::new().unwrap();
+            let db = DatabaseCore::open(temp_dir.path().join("bench.mlite")).unwrap();
+            db.collection("users").unwrap();
+
+            let mut next = 0;
+            b.iter(|| {
+                let tx = db.begin_transaction_with_durability(durability);
+                db.insert_one_tx("users", doc(next), tx).unwrap();
+                db.commit_transaction(tx).unwrap();
+                next += 1;
+            });
+        });
+    }
+    group.finish();
+}
+
+// ========== QUERY BENCHMARKS ==========
+
+fn bench_point_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("point_lookup");
+    let (_indexed_dir, _indexed_db, indexed) = populated(&["email"]);
+    let (_plain_dir, _plain_db, plain) = populated(&[]);
+
+    for (name, users) in [("index", &indexed), ("scan", &plain)] {
+        let mut n = 0;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                n = (n + 7919) % DOCS;
+                black_box(users.find(&json!({"email": format!("user{}@example.com", n)})).unwrap());
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_range_query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("range_query");
+    let (_indexed_dir, _indexed_db, indexed) = populated(&["score"]);
+    let (_plain_dir, _plain_db, plain) = populated(&[]);
+    // About 1% of the documents
+    let query = json!({"score": {"$gte": 500, "$lt": 510}});
+
+    for (name, users) in [("index", &indexed), ("scan", &plain)] {
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(users.find(&query).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+fn bench_aggregation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aggregate");
+    let (_temp_dir, _db, users) = populated(&[]);
+
+    let pipelines = [
+        ("group_by_city", json!([
+            {"$group": {"_id": "$city", "count": {"$sum": 1}, "avgAge": {"$avg": "$age"}}},
+        ])),
+        ("match_sort_limit", json!([
+            {"$match": {"age": {"$gte": 40}}},
+            {"$sort": {"score": -1}},
+            {"$limit": 10},
+        ])),
+        ("match_project", json!([
+            {"$match": {"city": "Vienna"}},
+            {"$project": {"email": 1, "score": 1}},
+        ])),
+    ];
+    for (name, pipeline) in &pipelines {
+        group.bench_with_input(BenchmarkId::from_parameter(name), pipeline, |b, pipeline| {
+            b.iter(|| black_box(users.aggregate(pipeline).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+// ========== MAINTENANCE BENCHMARKS ==========
+
+fn bench_index_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index_build");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(DOCS as u64));
+
+    for (field, unique) in [("email", true), ("city", false)] {
+        group.bench_function(field, |b| {
+            b.iter_batched(
+                || populated(&[]),
+                |(_temp_dir, _db, users)| users.create_index(field.to_string(), unique).unwrap(),
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn bench_compaction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compaction");
+    group.sample_size(10);
+
+    // Half of the documents deleted, half of the rest updated
+    let fragmented = || {
+        let (temp_dir, db, users) = populated(&["email"]);
+        users.delete_many(&json!({"n": {"$mod": [2, 0]}})).unwrap();
+        users.update_many(&json!({"n": {"$mod": [4, 1]}}), &json!({"$inc": {"score": 1}})).unwrap();
+        db.flush().unwrap();
+        (temp_dir, db)
+    };
+    group.bench_function("fragmented", |b| {
+        b.iter_batched(fragmented, |(_temp_dir, db)| db.compact().unwrap(), BatchSize::PerIteration);
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_insert_indexes,
+    bench_commit_durability,
+    bench_point_lookup,
+    bench_range_query,
+    bench_aggregation,
+    bench_index_build,
+    bench_compaction,
+);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+// Crash-recovery fuzzing: any input is a workload (see ironbase_core::testing)
+//
+//     cargo +nightly fuzz run crash_recovery
+#![no_main]
+
+use ironbase_core::testing::{CrashHarness, Op};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let ops = Op::decode(data);
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    if let Err(violation) = CrashHarness::run(temp_dir.path().join("fuzz.mlite"), &ops) {
+        panic!("{:?}: {}", ops, violation);
+    }
+});
@@ -1,16 +1,34 @@
 // src/aggregation.rs
 // Aggregation pipeline implementation
+//
+// Documents stream through the pipeline one at a time: $match, $project,
+// $addFields, $skip and $limit pass each on as it arrives, and $limit stops
+// pulling input once it has enough. Only the blocking stages - $group and
+// $sort - hold every document they are given.
+//
+// Parsed pipelines are optimized before they run (see optimize()): empty
+// $project stages are dropped, a $match moves ahead of a $project that
+// passes every field it reads through unchanged, consecutive $match stages
+// merge into one, and $sort followed by $limit becomes a top-k sort that
+// only holds the documents it returns.
 
 use serde_json::Value;
 use crate::document::Document;
 use crate::query::Query;
+use crate::expression::Expression;
 use crate::error::{Result, MongoLiteError};
+use crate::memory::{MemoryTracker, estimate_all, estimate_size};
+use crate::top_k::TopK;
 use std::collections::HashMap;
 
+/// Documents flowing into and between pipeline stages
+pub type DocumentStream<'a> = Box<dyn Iterator<Item = Result<Value>> + 'a>;
+
 /// Aggregation pipeline
 #[derive(Debug, Clone)]
 pub struct Pipeline {
     stages: Vec<Stage>,
+    output: Option<OutputStage>,
 }
 
 /// Pipeline stage
@@ -18,16 +36,55 @@ pub struct Pipeline {
 pub enum Stage {
     Match(MatchStage),
     Project(ProjectStage),
+    AddFields(AddFieldsStage),
     Group(GroupStage),
     Sort(SortStage),
     Limit(LimitStage),
     Skip(SkipStage),
+    /// $sort + $limit, fused by the optimizer
+    TopK(TopKStage),
+}
+
+/// $out / $merge - terminal stage writing the results to a collection
+/// Parsed here, applied by CollectionCore::aggregate (needs storage access)
+#[derive(Debug, Clone)]
+pub enum OutputStage {
+    /// Replace the target collection's contents with the results
+    Out(String),
+    /// Upsert the results into the target by _id
+    Merge(MergeStage),
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeStage {
+    pub into: String,
+    pub when_matched: WhenMatched,
+    pub when_not_matched: WhenNotMatched,
+}
+
+/// $merge action for a result whose _id already exists in the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenMatched {
+    Replace,
+    Merge,                      // top-level fields of the result overwrite the existing ones
+    KeepExisting,
+    Fail,
+}
+
+/// $merge action for a result whose _id is not in the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenNotMatched {
+    Insert,
+    Discard,
+    Fail,
 }
 
 /// $match stage - filter documents
 #[derive(Debug, Clone)]
 pub struct MatchStage {
     query: Query,
+    /// The filter as given, for planning it against indexes
+    filter: Value,
 }
 
 /// $project stage - reshape documents
@@ -41,6 +98,13 @@ pub enum ProjectField {
     Include,                    // 1
     Exclude,                    // 0
     Rename(String),             // "$fieldName"
+    Expression(Expression),     // {"$concat": [...]}, {"a": "$x"}, ...
+}
+
+/// $addFields / $set stage - add or overwrite computed fields
+#[derive(Debug, Clone)]
+pub struct AddFieldsStage {
+    fields: Vec<(String, Expression)>,
 }
 
 /// $group stage - group documents and compute aggregates
@@ -54,23 +118,29 @@ pub struct GroupStage {
 pub enum GroupId {
     Field(String),              // "$city"
     Null,                       // null (all documents in one group)
+    Expression(Expression),     // {"city": "$city", "year": "$year"}, {"$toLower": "$city"}, constants
 }
 
 #[derive(Debug, Clone)]
 pub enum Accumulator {
     Sum(SumExpression),
-    Avg(String),                // Field name
-    Min(String),
-    Max(String),
-    First(String),
-    Last(String),
-    Count,
+    Avg(Expression),            // "$field" or any expression
+    Min(Expression),
+    Max(Expression),
+    First(Expression),
+    Last(Expression),
+    Push(Expression),           // all values, in input order
+    AddToSet(Expression),       // distinct values, first-seen order
+    StdDevPop(Expression),
+    StdDevSamp(Expression),
+    Count,                      // {"$count": {}}
 }
 
 #[derive(Debug, Clone)]
 pub enum SumExpression {
     Constant(i64),              // {"$sum": 1} - count
     Field(String),              // {"$sum": "$amount"} - sum field values
+    Expression(Expression),     // {"$sum": {"$multiply": ["$price", "$qty"]}}
 }
 
 /// $sort stage - sort documents
@@ -85,6 +155,14 @@ pub enum SortDirection {
     Descending,
 }
 
+/// $sort followed by $limit - keeps the first `limit` documents in sort
+/// order as they arrive
+#[derive(Debug, Clone)]
+pub struct TopKStage {
+    sort: SortStage,
+    limit: usize,
+}
+
 /// $limit stage - limit number of documents
 #[derive(Debug, Clone)]
 pub struct LimitStage {
@@ -106,26 +184,233 @@ impl Pipeline {
             }
 
             let mut stages = Vec::new();
-            for stage_json in stages_array {
-                let stage = Stage::from_json(stage_json)?;
-                stages.push(stage);
+            let mut output = None;
+            for (i, stage_json) in stages_array.iter().enumerate() {
+                if let Some(output_stage) = OutputStage::from_json(stage_json)? {
+                    if i + 1 != stages_array.len() {
+                        return Err(MongoLiteError::AggregationError(
+                            "$out / $merge must be the last stage".to_string()
+                        ));
+                    }
+                    output = Some(output_stage);
+                } else {
+                    stages.push(Stage::from_json(stage_json)?);
+                }
             }
 
-            Ok(Pipeline { stages })
+            Ok(Pipeline { stages: optimize(stages)?, output })
         } else {
             Err(MongoLiteError::AggregationError("Pipeline must be an array".to_string()))
         }
     }
 
+    /// Terminal $out / $merge stage, if any
+    pub fn output(&self) -> Option<&OutputStage> {
+        self.output.as_ref()
+    }
+
+    /// True if every stage looks at one document at a time and keeps its _id
+    /// ($match, $addFields, $project not touching _id) - a changed input
+    /// document then only changes the result with the same _id
+    pub fn is_per_document(&self) -> bool {
+        self.stages.iter().all(|stage| match stage {
+            Stage::Match(_) => true,
+            Stage::AddFields(add) => add.fields.iter().all(|(name, _)| name != "_id"),
+            Stage::Project(project) => matches!(project.fields.get("_id"), None | Some(ProjectField::Include)),
+            Stage::Group(_) | Stage::Sort(_) | Stage::Limit(_) | Stage::Skip(_) | Stage::TopK(_) => false,
+        })
+    }
+
+    /// Filter of a leading $match and the pipeline after it, for answering
+    /// the filter from an index before documents enter the pipeline
+    pub fn split_leading_match(&self) -> Option<(&Value, Pipeline)> {
+        match self.stages.first() {
+            Some(Stage::Match(stage)) => Some((&stage.filter, Pipeline {
+                stages: self.stages[1..].to_vec(),
+                output: self.output.clone(),
+            })),
+            _ => None,
+        }
+    }
+
+    /// Input documents the pipeline pulls at most: set by a $limit that
+    /// only $project, $addFields and $skip stages come before
+    pub fn input_limit(&self) -> Option<usize> {
+        input_limit(&self.stages)
+    }
+
+    /// Explain output for the stages, in the order they run, over
+    /// `input_documents` documents of `average_size` bytes
+    ///
+    /// Each stage reports whether it is blocking and the most documents it
+    /// passes on; `estimatedMemory` is the most the pipeline holds at once -
+    /// the input of its largest blocking stage or the results. Pass the
+    /// find explain of the leading $match as `index_plan` when an index
+    /// answers it: the $match is then pushed down to the input and any
+    /// limit applies to the index walk.
+    pub fn explain(&self, input_documents: usize, average_size: usize, index_plan: Option<Value>) -> Value {
+        let pushed_down = index_plan.is_some() && matches!(self.stages.first(), Some(Stage::Match(_)));
+        let limit = input_limit(&self.stages[usize::from(pushed_down)..]);
+        let mut documents = input_documents.min(limit.unwrap_or(usize::MAX));
+        let mut held = 0;
+        let mut stages = Vec::with_capacity(self.stages.len());
+        let mut blocking = Vec::new();
+
+        for (i, stage) in self.stages.iter().enumerate() {
+            if stage.is_blocking() {
+                held = held.max(match stage {
+                    Stage::TopK(stage) => documents.min(stage.limit),
+                    _ => documents,
+                });
+                blocking.push(i);
+            }
+            documents = match stage {
+                Stage::Limit(stage) => documents.min(stage.limit),
+                Stage::TopK(stage) => documents.min(stage.limit),
+                Stage::Skip(stage) => documents.saturating_sub(stage.skip),
+                _ => documents,
+            };
+            let mut entry = serde_json::json!({
+                "stage": stage.name(),
+                "blocking": stage.is_blocking(),
+                "maxDocuments": documents,
+            });
+            if let Stage::Match(_) = stage {
+                let index_plan = index_plan.as_ref().filter(|_| i == 0);
+                entry["pushedDown"] = Value::Bool(index_plan.is_some());
+                entry["usesIndex"] = Value::Bool(index_plan.is_some());
+                if let Some(index_plan) = index_plan {
+                    entry["inputPlan"] = index_plan.clone();
+                }
+            }
+            stages.push(entry);
+        }
+        held = held.max(documents);
+
+        serde_json::json!({
+            "stages": stages,
+            "blockingStages": blocking,
+            "limitPushdown": limit,
+            "estimatedMemory": held.saturating_mul(average_size),
+            "output": self.output.as_ref().map(OutputStage::name),
+        })
+    }
+
     /// Execute pipeline on documents
-    pub fn execute(&self, mut docs: Vec<Value>) -> Result<Vec<Value>> {
+    /// A terminal $out / $merge is not applied here - see output()
+    pub fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
+        self.execute_tracked(docs, &mut MemoryTracker::unlimited())
+    }
+
+    /// Execute with the documents held by blocking stages and the results
+    /// charged to `memory` (see execute_stream)
+    pub fn execute_tracked(&self, docs: Vec<Value>, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        self.execute_stream(docs.into_iter().map(Ok), memory)
+    }
+
+    /// Execute over a stream of input documents, pulling no more of it than
+    /// the pipeline needs
+    ///
+    /// Only the documents a blocking stage ($group, $sort) buffers and the
+    /// results are charged to `memory`; its deadline and cancellation are
+    /// checked for every input document.
+    pub fn execute_stream<'a, I>(&'a self, input: I, memory: &mut MemoryTracker) -> Result<Vec<Value>>
+    where
+        I: IntoIterator<Item = Result<Value>>,
+        I::IntoIter: 'a,
+    {
+        let interrupts = memory.clone();
+        let mut stream: DocumentStream<'a> = Box::new(input.into_iter()
+            .map(move |doc| interrupts.check_interrupt().and(doc)));
+
         for stage in &self.stages {
-            docs = stage.execute(docs)?;
+            stream = if let Stage::TopK(stage) = stage {
+                Box::new(stage.execute_stream(stream, memory)?.into_iter().map(Ok))
+            } else if stage.is_blocking() {
+                let docs = collect_tracked(stream, memory)?;
+                let output = stage.execute(docs)?;
+                memory.set_used(estimate_all(&output))?;
+                Box::new(output.into_iter().map(Ok))
+            } else {
+                stage.stream(stream)
+            };
         }
-        Ok(docs)
+        collect_tracked(stream, memory)
     }
 }
 
+/// Rewrite parsed stages into a cheaper pipeline with the same results
+///
+/// Walks the stages once, stepping back after each rewrite so a moved or
+/// merged $match is checked against the stage now before it.
+fn optimize(mut stages: Vec<Stage>) -> Result<Vec<Stage>> {
+    stages.retain(|stage| !matches!(stage, Stage::Project(project) if project.fields.is_empty()));
+
+    let mut i = 1;
+    while i < stages.len() {
+        match (&stages[i - 1], &stages[i]) {
+            (Stage::Project(project), Stage::Match(stage)) if project.passes_through(&stage.filter) => {
+                stages.swap(i - 1, i);
+                i = (i - 1).max(1);
+            }
+            (Stage::Match(first), Stage::Match(second)) => {
+                stages[i - 1] = Stage::Match(first.and(second)?);
+                stages.remove(i);
+                i = (i - 1).max(1);
+            }
+            (Stage::Sort(sort), Stage::Limit(limit)) => {
+                stages[i - 1] = Stage::TopK(TopKStage { sort: sort.clone(), limit: limit.limit });
+                stages.remove(i);
+            }
+            _ => i += 1,
+        }
+    }
+    Ok(stages)
+}
+
+/// Fields a $match filter reads, None if it has top-level operators other
+/// than $and / $or / $nor
+fn filter_fields(filter: &Value) -> Option<Vec<&str>> {
+    let mut fields = Vec::new();
+    for (key, value) in filter.as_object()? {
+        match key.as_str() {
+            "$and" | "$or" | "$nor" => {
+                for clause in value.as_array()? {
+                    fields.extend(filter_fields(clause)?);
+                }
+            }
+            operator if operator.starts_with('$') => return None,
+            field => fields.push(field),
+        }
+    }
+    Some(fields)
+}
+
+/// Input documents `stages` pull at most (see Pipeline::input_limit)
+fn input_limit(stages: &[Stage]) -> Option<usize> {
+    let mut skipped = 0usize;
+    for stage in stages {
+        match stage {
+            Stage::Project(_) | Stage::AddFields(_) => {}
+            Stage::Skip(stage) => skipped = skipped.saturating_add(stage.skip),
+            Stage::Limit(stage) => return Some(skipped.saturating_add(stage.limit)),
+            Stage::Match(_) | Stage::Group(_) | Stage::Sort(_) | Stage::TopK(_) => return None,
+        }
+    }
+    None
+}
+
+/// Drain `stream`, charging every document to `memory`
+fn collect_tracked(stream: DocumentStream<'_>, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+    let mut docs = Vec::new();
+    for doc in stream {
+        let doc = doc?;
+        memory.charge_value(&doc)?;
+        docs.push(doc);
+    }
+    Ok(docs)
+}
+
 impl Stage {
     /// Parse stage from JSON
     fn from_json(stage_json: &Value) -> Result<Self> {
@@ -142,6 +427,7 @@ impl Stage {
             match stage_name.as_str() {
                 "$match" => Ok(Stage::Match(MatchStage::from_json(stage_spec)?)),
                 "$project" => Ok(Stage::Project(ProjectStage::from_json(stage_spec)?)),
+                "$addFields" | "$set" => Ok(Stage::AddFields(AddFieldsStage::from_json(stage_spec)?)),
                 "$group" => Ok(Stage::Group(GroupStage::from_json(stage_spec)?)),
                 "$sort" => Ok(Stage::Sort(SortStage::from_json(stage_spec)?)),
                 "$limit" => Ok(Stage::Limit(LimitStage::from_json(stage_spec)?)),
@@ -155,50 +441,198 @@ impl Stage {
         }
     }
 
+    /// Stage operator, as written in pipelines
+    fn name(&self) -> &'static str {
+        match self {
+            Stage::Match(_) => "$match",
+            Stage::Project(_) => "$project",
+            Stage::AddFields(_) => "$addFields",
+            Stage::Group(_) => "$group",
+            Stage::Sort(_) => "$sort",
+            Stage::Limit(_) => "$limit",
+            Stage::Skip(_) => "$skip",
+            Stage::TopK(_) => "$sort+$limit",
+        }
+    }
+
+    /// True for stages that need all of their input before the first
+    /// output document
+    fn is_blocking(&self) -> bool {
+        matches!(self, Stage::Group(_) | Stage::Sort(_) | Stage::TopK(_))
+    }
+
+    /// Apply a non-blocking stage to a stream, document by document
+    fn stream<'a>(&'a self, input: DocumentStream<'a>) -> DocumentStream<'a> {
+        match self {
+            Stage::Match(stage) => Box::new(input.filter_map(move |doc| {
+                doc.and_then(|doc| Ok(stage.matches(&doc)?.then_some(doc))).transpose()
+            })),
+            Stage::Project(stage) => Box::new(input.map(move |doc| stage.project_document(&doc?))),
+            Stage::AddFields(stage) => Box::new(input.map(move |doc| stage.add_fields(doc?))),
+            Stage::Limit(stage) => Box::new(input.take(stage.limit)),
+            Stage::Skip(stage) => Box::new(input.skip(stage.skip)),
+            Stage::Group(_) | Stage::Sort(_) | Stage::TopK(_) => unreachable!("blocking stages are not streamed"),
+        }
+    }
+
     /// Execute this stage
     fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
         match self {
             Stage::Match(stage) => stage.execute(docs),
             Stage::Project(stage) => stage.execute(docs),
+            Stage::AddFields(stage) => stage.execute(docs),
             Stage::Group(stage) => stage.execute(docs),
             Stage::Sort(stage) => stage.execute(docs),
             Stage::Limit(stage) => stage.execute(docs),
+            Stage::TopK(stage) => stage.execute(docs),
             Stage::Skip(stage) => stage.execute(docs),
         }
     }
 }
 
+impl OutputStage {
+    /// Parse a $out / $merge stage; None for any other stage
+    fn from_json(stage_json: &Value) -> Result<Option<Self>> {
+        let (name, spec) = match stage_json.as_object().and_then(|obj| obj.iter().next()) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        match name.as_str() {
+            "$out" => {
+                let target = spec.as_str().ok_or_else(|| MongoLiteError::AggregationError(
+                    "$out requires a collection name".to_string()
+                ))?;
+                Ok(Some(OutputStage::Out(Self::target_name("$out", target)?)))
+            }
+            "$merge" => {
+                let merge = match spec {
+                    Value::String(into) => MergeStage {
+                        into: Self::target_name("$merge", into)?,
+                        when_matched: WhenMatched::Merge,
+                        when_not_matched: WhenNotMatched::Insert,
+                    },
+                    Value::Object(obj) => {
+                        let into = obj.get("into").and_then(|v| v.as_str()).ok_or_else(|| {
+                            MongoLiteError::AggregationError("$merge requires 'into'".to_string())
+                        })?;
+                        if let Some(on) = obj.get("on") {
+                            if on != "_id" {
+                                return Err(MongoLiteError::AggregationError(
+                                    "$merge only supports on: \"_id\"".to_string()
+                                ));
+                            }
+                        }
+
+                        let when_matched = match obj.get("whenMatched").and_then(|v| v.as_str()) {
+                            None | Some("merge") => WhenMatched::Merge,
+                            Some("replace") => WhenMatched::Replace,
+                            Some("keepExisting") => WhenMatched::KeepExisting,
+                            Some("fail") => WhenMatched::Fail,
+                            Some(other) => return Err(MongoLiteError::AggregationError(
+                                format!("Invalid $merge whenMatched: {}", other)
+                            )),
+                        };
+                        let when_not_matched = match obj.get("whenNotMatched").and_then(|v| v.as_str()) {
+                            None | Some("insert") => WhenNotMatched::Insert,
+                            Some("discard") => WhenNotMatched::Discard,
+                            Some("fail") => WhenNotMatched::Fail,
+                            Some(other) => return Err(MongoLiteError::AggregationError(
+                                format!("Invalid $merge whenNotMatched: {}", other)
+                            )),
+                        };
+
+                        MergeStage { into: Self::target_name("$merge", into)?, when_matched, when_not_matched }
+                    }
+                    _ => return Err(MongoLiteError::AggregationError(
+                        "$merge must be a collection name or an object".to_string()
+                    )),
+                };
+                Ok(Some(OutputStage::Merge(merge)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn target_name(stage: &str, name: &str) -> Result<String> {
+        if name.is_empty() || name == crate::storage::OPLOG_COLLECTION || name == crate::auth::USERS_COLLECTION {
+            return Err(MongoLiteError::AggregationError(
+                format!("{}: invalid target collection '{}'", stage, name)
+            ));
+        }
+        Ok(name.to_string())
+    }
+
+    /// Stage operator, as written in pipelines
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputStage::Out(_) => "$out",
+            OutputStage::Merge(_) => "$merge",
+        }
+    }
+
+    /// Target collection name
+    pub fn collection(&self) -> &str {
+        match self {
+            OutputStage::Out(name) => name,
+            OutputStage::Merge(merge) => &merge.into,
+        }
+    }
+}
+
 impl MatchStage {
     fn from_json(spec: &Value) -> Result<Self> {
         let query = Query::from_json(spec)?;
-        Ok(MatchStage { query })
+        Ok(MatchStage { query, filter: spec.clone() })
     }
 
     fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
         let mut results = Vec::new();
 
         for doc in docs {
-            // Add _id if not present (for aggregation intermediate results)
-            let doc_with_id = if doc.get("_id").is_none() {
-                let mut doc_obj = doc.clone();
-                if let Value::Object(ref mut map) = doc_obj {
-                    map.insert("_id".to_string(), Value::from(0)); // Temporary _id
-                }
-                doc_obj
-            } else {
-                doc.clone()
-            };
-
-            let doc_json_str = serde_json::to_string(&doc_with_id)?;
-            let document = Document::from_json(&doc_json_str)?;
-
-            if self.query.matches(&document) {
+            if self.matches(&doc)? {
                 results.push(doc);
             }
         }
 
         Ok(results)
     }
+
+    /// One $match for this stage followed by `other`
+    fn and(&self, other: &MatchStage) -> Result<MatchStage> {
+        let filter = match (&self.filter, &other.filter) {
+            // Plain field conditions on different fields combine into one
+            // object, which the query planner can still use an index for
+            (Value::Object(first), Value::Object(second))
+                if first.keys().chain(second.keys()).all(|key| !key.starts_with('$'))
+                    && first.keys().all(|key| !second.contains_key(key)) =>
+            {
+                let mut merged = first.clone();
+                merged.extend(second.clone());
+                Value::Object(merged)
+            }
+            (first, second) => serde_json::json!({"$and": [first, second]}),
+        };
+        MatchStage::from_json(&filter)
+    }
+
+    fn matches(&self, doc: &Value) -> Result<bool> {
+        // Add _id if not present (for aggregation intermediate results)
+        let doc_with_id = if doc.get("_id").is_none() {
+            let mut doc_obj = doc.clone();
+            if let Value::Object(ref mut map) = doc_obj {
+                map.insert("_id".to_string(), Value::from(0)); // Temporary _id
+            }
+            doc_obj
+        } else {
+            doc.clone()
+        };
+
+        let doc_json_str = serde_json::to_string(&doc_with_id)?;
+        let document = Document::from_json(&doc_json_str)?;
+
+        Ok(self.query.matches(&document))
+    }
 }
 
 impl ProjectStage {
@@ -223,9 +657,11 @@ impl ProjectStage {
                             format!("Invalid project expression: {}", s)
                         ));
                     }
+                } else if value.is_object() {
+                    ProjectField::Expression(Expression::from_json(value)?)
                 } else {
                     return Err(MongoLiteError::AggregationError(
-                        "Project field must be 0, 1, or field reference".to_string()
+                        "Project field must be 0, 1, field reference or expression".to_string()
                     ));
                 };
 
@@ -249,20 +685,40 @@ impl ProjectStage {
         Ok(results)
     }
 
+    /// True if only the listed fields come out (otherwise all but the
+    /// excluded ones do)
+    fn include_mode(&self) -> bool {
+        let has_inclusions = self.fields.values().any(|f| matches!(f, ProjectField::Include | ProjectField::Rename(_) | ProjectField::Expression(_)));
+        let has_non_id_exclusions = self.fields.iter()
+            .any(|(field, action)| matches!(action, ProjectField::Exclude) && field != "_id");
+
+        // Determine mode: if we have any inclusions, we're in include mode
+        // Exception: excluding _id is allowed in include mode
+        has_inclusions && !has_non_id_exclusions
+    }
+
+    /// True if every field `filter` reads comes out of this stage as it
+    /// went in - the filter then matches the same documents before it
+    fn passes_through(&self, filter: &Value) -> bool {
+        let Some(fields) = filter_fields(filter) else {
+            return false;
+        };
+        let include_mode = self.include_mode();
+        fields.iter().all(|field| {
+            let root = field.split('.').next().unwrap_or(field);
+            let mut listed = self.fields.iter()
+                .filter(|(name, _)| name.split('.').next() == Some(root))
+                .peekable();
+            let kept = !include_mode || listed.peek().is_some();
+            kept && listed.all(|(_, action)| matches!(action, ProjectField::Include))
+        })
+    }
+
     fn project_document(&self, doc: &Value) -> Result<Value> {
         let mut result = serde_json::Map::new();
 
         if let Value::Object(obj) = doc {
-            // Check if we're in include mode or exclude mode
-            let has_inclusions = self.fields.values().any(|f| matches!(f, ProjectField::Include | ProjectField::Rename(_)));
-            let has_non_id_exclusions = self.fields.iter()
-                .any(|(field, action)| matches!(action, ProjectField::Exclude) && field != "_id");
-
-            // Determine mode: if we have any inclusions, we're in include mode
-            // Exception: excluding _id is allowed in include mode
-            let include_mode = has_inclusions && !has_non_id_exclusions;
-
-            if include_mode {
+            if self.include_mode() {
                 // Include mode: only include specified fields
                 for (field, action) in &self.fields {
                     match action {
@@ -277,6 +733,9 @@ impl ProjectStage {
                                 result.insert(field.clone(), value.clone());
                             }
                         }
+                        ProjectField::Expression(expr) => {
+                            result.insert(field.clone(), expr.evaluate(doc)?);
+                        }
                         ProjectField::Exclude => {
                             // Should not happen in include mode
                         }
@@ -293,7 +752,7 @@ impl ProjectStage {
                             ProjectField::Include => {
                                 result.insert(field.clone(), value.clone());
                             }
-                            ProjectField::Rename(_) => {
+                            ProjectField::Rename(_) | ProjectField::Expression(_) => {
                                 // Handled below
                             }
                         }
@@ -303,13 +762,19 @@ impl ProjectStage {
                     }
                 }
 
-                // Handle renames in exclude mode
+                // Handle renames and computed fields in exclude mode
                 for (target_field, action) in &self.fields {
-                    if let ProjectField::Rename(source) = action {
-                        let source_field = source.trim_start_matches('$');
-                        if let Some(value) = obj.get(source_field) {
-                            result.insert(target_field.clone(), value.clone());
+                    match action {
+                        ProjectField::Rename(source) => {
+                            let source_field = source.trim_start_matches('$');
+                            if let Some(value) = obj.get(source_field) {
+                                result.insert(target_field.clone(), value.clone());
+                            }
                         }
+                        ProjectField::Expression(expr) => {
+                            result.insert(target_field.clone(), expr.evaluate(doc)?);
+                        }
+                        ProjectField::Include | ProjectField::Exclude => {}
                     }
                 }
             }
@@ -319,6 +784,38 @@ impl ProjectStage {
     }
 }
 
+impl AddFieldsStage {
+    fn from_json(spec: &Value) -> Result<Self> {
+        if let Value::Object(obj) = spec {
+            let mut fields = Vec::new();
+            for (field, value) in obj {
+                fields.push((field.clone(), Expression::from_json(value)?));
+            }
+            Ok(AddFieldsStage { fields })
+        } else {
+            Err(MongoLiteError::AggregationError("$addFields must be an object".to_string()))
+        }
+    }
+
+    fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
+        docs.into_iter().map(|doc| self.add_fields(doc)).collect()
+    }
+
+    fn add_fields(&self, mut doc: Value) -> Result<Value> {
+        // Evaluate everything against the input document before writing
+        let computed = self.fields.iter()
+            .map(|(field, expr)| Ok((field.clone(), expr.evaluate(&doc)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Value::Object(ref mut map) = doc {
+            for (field, value) in computed {
+                map.insert(field, value);
+            }
+        }
+        Ok(doc)
+    }
+}
+
 impl GroupStage {
     fn from_json(spec: &Value) -> Result<Self> {
         if let Value::Object(obj) = spec {
@@ -326,18 +823,10 @@ impl GroupStage {
             let id = if let Some(id_value) = obj.get("_id") {
                 if id_value.is_null() {
                     GroupId::Null
-                } else if let Some(s) = id_value.as_str() {
-                    if s.starts_with('$') {
-                        GroupId::Field(s.to_string())
-                    } else {
-                        return Err(MongoLiteError::AggregationError(
-                            "Group _id field reference must start with $".to_string()
-                        ));
-                    }
+                } else if let Some(s) = id_value.as_str().filter(|s| s.starts_with('$')) {
+                    GroupId::Field(s.to_string())
                 } else {
-                    return Err(MongoLiteError::AggregationError(
-                        "Group _id must be null or field reference".to_string()
-                    ));
+                    GroupId::Expression(Expression::from_json(id_value)?)
                 }
             } else {
                 return Err(MongoLiteError::AggregationError(
@@ -364,11 +853,19 @@ impl GroupStage {
 
     fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
         // Step 1: Group documents by _id expression
-        let mut groups: HashMap<String, Vec<Value>> = HashMap::new();
+        // Groups are kept in first-seen order so the output is deterministic
+        let mut groups: Vec<(String, Vec<Value>)> = Vec::new();
+        let mut group_positions: HashMap<String, usize> = HashMap::new();
 
         for doc in docs {
             let group_key = self.extract_group_key(&doc)?;
-            groups.entry(group_key).or_insert_with(Vec::new).push(doc);
+            match group_positions.get(&group_key) {
+                Some(&pos) => groups[pos].1.push(doc),
+                None => {
+                    group_positions.insert(group_key.clone(), groups.len());
+                    groups.push((group_key, vec![doc]));
+                }
+            }
         }
 
         // Step 2: Compute accumulators for each group
@@ -398,11 +895,15 @@ impl GroupStage {
             GroupId::Field(field) => {
                 let field_name = field.trim_start_matches('$');
                 if let Some(value) = doc.get(field_name) {
-                    Ok(serde_json::to_string(value)?)
+                    Ok(serde_json::to_string(&canonical_group_value(value))?)
                 } else {
                     Ok("null".to_string())
                 }
             }
+            GroupId::Expression(expr) => {
+                let value = expr.evaluate(doc)?;
+                Ok(serde_json::to_string(&canonical_group_value(&value))?)
+            }
         }
     }
 
@@ -415,6 +916,26 @@ impl GroupStage {
     }
 }
 
+/// Normalize a group key so equal keys serialize identically
+/// Object keys are already sorted (serde_json map); integral floats become integers so 1 and 1.0 group together
+fn canonical_group_value(value: &Value) -> Value {
+    match value {
+        Value::Number(n) if n.is_f64() => {
+            let f = n.as_f64().unwrap_or(f64::NAN);
+            if f.fract() == 0.0 && f.abs() < 9.0e15 {
+                Value::from(f as i64)
+            } else {
+                value.clone()
+            }
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonical_group_value).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), canonical_group_value(v))).collect()
+        ),
+        _ => value.clone(),
+    }
+}
+
 impl Accumulator {
     fn from_json(spec: &Value) -> Result<Self> {
         if let Value::Object(obj) = spec {
@@ -438,87 +959,32 @@ impl Accumulator {
                                 "$sum field reference must start with $".to_string()
                             ))
                         }
+                    } else if value.is_object() {
+                        Ok(Accumulator::Sum(SumExpression::Expression(Expression::from_json(value)?)))
                     } else {
                         Err(MongoLiteError::AggregationError(
-                            "$sum must be a number or field reference".to_string()
+                            "$sum must be a number, field reference or expression".to_string()
                         ))
                     }
                 }
-                "$avg" => {
-                    if let Some(s) = value.as_str() {
-                        if s.starts_with('$') {
-                            Ok(Accumulator::Avg(s.trim_start_matches('$').to_string()))
-                        } else {
-                            Err(MongoLiteError::AggregationError(
-                                "$avg field reference must start with $".to_string()
-                            ))
-                        }
+                "$count" => {
+                    if value.as_object().is_some_and(|o| o.is_empty()) {
+                        Ok(Accumulator::Count)
                     } else {
                         Err(MongoLiteError::AggregationError(
-                            "$avg must be a field reference".to_string()
-                        ))
-                    }
-                }
-                "$min" => {
-                    if let Some(s) = value.as_str() {
-                        if s.starts_with('$') {
-                            Ok(Accumulator::Min(s.trim_start_matches('$').to_string()))
-                        } else {
-                            Err(MongoLiteError::AggregationError(
-                                "$min field reference must start with $".to_string()
-                            ))
-                        }
-                    } else {
-                        Err(MongoLiteError::AggregationError(
-                            "$min must be a field reference".to_string()
-                        ))
-                    }
-                }
-                "$max" => {
-                    if let Some(s) = value.as_str() {
-                        if s.starts_with('$') {
-                            Ok(Accumulator::Max(s.trim_start_matches('$').to_string()))
-                        } else {
-                            Err(MongoLiteError::AggregationError(
-                                "$max field reference must start with $".to_string()
-                            ))
-                        }
-                    } else {
-                        Err(MongoLiteError::AggregationError(
-                            "$max must be a field reference".to_string()
-                        ))
-                    }
-                }
-                "$first" => {
-                    if let Some(s) = value.as_str() {
-                        if s.starts_with('$') {
-                            Ok(Accumulator::First(s.trim_start_matches('$').to_string()))
-                        } else {
-                            Err(MongoLiteError::AggregationError(
-                                "$first field reference must start with $".to_string()
-                            ))
-                        }
-                    } else {
-                        Err(MongoLiteError::AggregationError(
-                            "$first must be a field reference".to_string()
-                        ))
-                    }
-                }
-                "$last" => {
-                    if let Some(s) = value.as_str() {
-                        if s.starts_with('$') {
-                            Ok(Accumulator::Last(s.trim_start_matches('$').to_string()))
-                        } else {
-                            Err(MongoLiteError::AggregationError(
-                                "$last field reference must start with $".to_string()
-                            ))
-                        }
-                    } else {
-                        Err(MongoLiteError::AggregationError(
-                            "$last must be a field reference".to_string()
+                            "$count accumulator takes no arguments: {\"$count\": {}}".to_string()
                         ))
                     }
                 }
+                "$avg" => Ok(Accumulator::Avg(Self::parse_operand(op, value)?)),
+                "$min" => Ok(Accumulator::Min(Self::parse_operand(op, value)?)),
+                "$max" => Ok(Accumulator::Max(Self::parse_operand(op, value)?)),
+                "$first" => Ok(Accumulator::First(Self::parse_operand(op, value)?)),
+                "$last" => Ok(Accumulator::Last(Self::parse_operand(op, value)?)),
+                "$push" => Ok(Accumulator::Push(Self::parse_operand(op, value)?)),
+                "$addToSet" => Ok(Accumulator::AddToSet(Self::parse_operand(op, value)?)),
+                "$stdDevPop" => Ok(Accumulator::StdDevPop(Self::parse_operand(op, value)?)),
+                "$stdDevSamp" => Ok(Accumulator::StdDevSamp(Self::parse_operand(op, value)?)),
                 _ => Err(MongoLiteError::AggregationError(
                     format!("Unknown accumulator: {}", op)
                 )),
@@ -530,6 +996,24 @@ impl Accumulator {
         }
     }
 
+    /// Accumulator argument: "$field" or an expression object
+    fn parse_operand(op: &str, value: &Value) -> Result<Expression> {
+        match value {
+            Value::String(s) if !s.starts_with('$') => Err(MongoLiteError::AggregationError(
+                format!("{} field reference must start with $", op)
+            )),
+            Value::String(_) | Value::Object(_) => Expression::from_json(value),
+            _ => Err(MongoLiteError::AggregationError(
+                format!("{} must be a field reference or expression", op)
+            )),
+        }
+    }
+
+    /// Evaluate the accumulator's expression for every document in the group
+    fn evaluate_all(expr: &Expression, docs: &[Value]) -> Result<Vec<Value>> {
+        docs.iter().map(|doc| expr.evaluate(doc)).collect()
+    }
+
     fn compute(&self, docs: &[Value]) -> Result<Value> {
         match self {
             Accumulator::Count => {
@@ -542,110 +1026,111 @@ impl Accumulator {
                         Ok(Value::from((*n) * (docs.len() as i64)))
                     }
                     SumExpression::Field(field) => {
-                        let mut sum_int: i64 = 0;
-                        let mut sum_float: f64 = 0.0;
-                        let mut has_float = false;
-
-                        for doc in docs {
-                            if let Some(value) = doc.get(field) {
-                                if let Some(n) = value.as_i64() {
-                                    sum_int += n;
-                                } else if let Some(f) = value.as_f64() {
-                                    sum_float += f;
-                                    has_float = true;
-                                }
-                            }
-                        }
-
-                        if has_float {
-                            Ok(Value::from(sum_float + sum_int as f64))
-                        } else {
-                            Ok(Value::from(sum_int))
-                        }
+                        Ok(sum_values(docs.iter().filter_map(|doc| doc.get(field).cloned())))
+                    }
+                    SumExpression::Expression(expr) => {
+                        Ok(sum_values(Self::evaluate_all(expr, docs)?))
                     }
                 }
             }
 
-            Accumulator::Avg(field) => {
-                let mut sum = 0.0;
-                let mut count = 0;
+            Accumulator::Avg(expr) => {
+                let numbers = numeric_values(Self::evaluate_all(expr, docs)?);
 
-                for doc in docs {
-                    if let Some(value) = doc.get(field) {
-                        if let Some(n) = value.as_f64() {
-                            sum += n;
-                            count += 1;
-                        } else if let Some(n) = value.as_i64() {
-                            sum += n as f64;
-                            count += 1;
-                        }
-                    }
-                }
-
-                if count > 0 {
-                    Ok(Value::from(sum / count as f64))
-                } else {
+                if numbers.is_empty() {
                     Ok(Value::Null)
+                } else {
+                    Ok(Value::from(numbers.iter().sum::<f64>() / numbers.len() as f64))
                 }
             }
 
-            Accumulator::Min(field) => {
-                let mut min: Option<f64> = None;
-
-                for doc in docs {
-                    if let Some(value) = doc.get(field) {
-                        let num = if let Some(n) = value.as_f64() {
-                            n
-                        } else if let Some(n) = value.as_i64() {
-                            n as f64
-                        } else {
-                            continue;
-                        };
-
-                        min = Some(min.map_or(num, |m| m.min(num)));
-                    }
-                }
+            Accumulator::Min(expr) => {
+                let min = numeric_values(Self::evaluate_all(expr, docs)?)
+                    .into_iter()
+                    .reduce(f64::min);
 
                 Ok(min.map(Value::from).unwrap_or(Value::Null))
             }
 
-            Accumulator::Max(field) => {
-                let mut max: Option<f64> = None;
-
-                for doc in docs {
-                    if let Some(value) = doc.get(field) {
-                        let num = if let Some(n) = value.as_f64() {
-                            n
-                        } else if let Some(n) = value.as_i64() {
-                            n as f64
-                        } else {
-                            continue;
-                        };
-
-                        max = Some(max.map_or(num, |m| m.max(num)));
-                    }
-                }
+            Accumulator::Max(expr) => {
+                let max = numeric_values(Self::evaluate_all(expr, docs)?)
+                    .into_iter()
+                    .reduce(f64::max);
 
                 Ok(max.map(Value::from).unwrap_or(Value::Null))
             }
 
-            Accumulator::First(field) => {
+            Accumulator::First(expr) => {
                 docs.first()
-                    .and_then(|doc| doc.get(field))
-                    .cloned()
                     .ok_or_else(|| MongoLiteError::AggregationError("No documents in group".to_string()))
+                    .and_then(|doc| expr.evaluate(doc))
             }
 
-            Accumulator::Last(field) => {
+            Accumulator::Last(expr) => {
                 docs.last()
-                    .and_then(|doc| doc.get(field))
-                    .cloned()
                     .ok_or_else(|| MongoLiteError::AggregationError("No documents in group".to_string()))
+                    .and_then(|doc| expr.evaluate(doc))
+            }
+
+            Accumulator::Push(expr) => {
+                Ok(Value::Array(Self::evaluate_all(expr, docs)?))
+            }
+
+            Accumulator::AddToSet(expr) => {
+                // First-seen order; serialized form as the identity (object keys are sorted)
+                let mut seen = std::collections::HashSet::new();
+                let values = Self::evaluate_all(expr, docs)?
+                    .into_iter()
+                    .filter(|v| seen.insert(v.to_string()))
+                    .collect();
+                Ok(Value::Array(values))
+            }
+
+            Accumulator::StdDevPop(expr) | Accumulator::StdDevSamp(expr) => {
+                let numbers = numeric_values(Self::evaluate_all(expr, docs)?);
+                let sample = matches!(self, Accumulator::StdDevSamp(_));
+
+                let n = numbers.len();
+                if n == 0 || (sample && n < 2) {
+                    return Ok(Value::Null);
+                }
+
+                let mean = numbers.iter().sum::<f64>() / n as f64;
+                let squares: f64 = numbers.iter().map(|x| (x - mean).powi(2)).sum();
+                let divisor = if sample { n - 1 } else { n } as f64;
+                Ok(Value::from((squares / divisor).sqrt()))
             }
         }
     }
 }
 
+/// Numeric values as f64 (non-numbers are ignored)
+fn numeric_values(values: Vec<Value>) -> Vec<f64> {
+    values.iter().filter_map(Value::as_f64).collect()
+}
+
+/// Sum numeric values, staying integer unless a float is seen (non-numbers are ignored)
+fn sum_values(values: impl IntoIterator<Item = Value>) -> Value {
+    let mut sum_int: i64 = 0;
+    let mut sum_float: f64 = 0.0;
+    let mut has_float = false;
+
+    for value in values {
+        if let Some(n) = value.as_i64() {
+            sum_int += n;
+        } else if let Some(f) = value.as_f64() {
+            sum_float += f;
+            has_float = true;
+        }
+    }
+
+    if has_float {
+        Value::from(sum_float + sum_int as f64)
+    } else {
+        Value::from(sum_int)
+    }
+}
+
 impl SortStage {
     fn from_json(spec: &Value) -> Result<Self> {
         if let Value::Object(obj) = spec {
@@ -676,25 +1161,48 @@ impl SortStage {
     }
 
     fn execute(&self, mut docs: Vec<Value>) -> Result<Vec<Value>> {
-        docs.sort_by(|a, b| {
-            for (field, direction) in &self.fields {
-                let val_a = a.get(field);
-                let val_b = b.get(field);
-
-                let cmp = compare_values(val_a, val_b);
-                let cmp = match direction {
-                    SortDirection::Ascending => cmp,
-                    SortDirection::Descending => cmp.reverse(),
-                };
+        docs.sort_by(|a, b| self.compare(a, b));
+        Ok(docs)
+    }
 
-                if cmp != std::cmp::Ordering::Equal {
-                    return cmp;
-                }
+    fn compare(&self, a: &Value, b: &Value) -> std::cmp::Ordering {
+        for (field, direction) in &self.fields {
+            let val_a = a.get(field);
+            let val_b = b.get(field);
+
+            let cmp = compare_values(val_a, val_b);
+            let cmp = match direction {
+                SortDirection::Ascending => cmp,
+                SortDirection::Descending => cmp.reverse(),
+            };
+
+            if cmp != std::cmp::Ordering::Equal {
+                return cmp;
             }
-            std::cmp::Ordering::Equal
-        });
+        }
+        std::cmp::Ordering::Equal
+    }
+}
 
-        Ok(docs)
+impl TopKStage {
+    fn execute(&self, docs: Vec<Value>) -> Result<Vec<Value>> {
+        let order = |a: &Value, b: &Value| self.sort.compare(a, b);
+        Ok(crate::top_k::top_k(docs, self.limit, &order))
+    }
+
+    /// Keep the first `limit` documents of `input` (see top_k.rs), charging
+    /// only those to `memory`
+    fn execute_stream(&self, input: DocumentStream<'_>, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        let order = |a: &Value, b: &Value| self.sort.compare(a, b);
+        let mut top = TopK::new(self.limit, &order);
+        for doc in input {
+            let doc = doc?;
+            memory.charge_value(&doc)?;
+            if let Some(dropped) = top.push(doc) {
+                memory.release(estimate_size(&dropped));
+            }
+        }
+        Ok(top.into_sorted_vec())
     }
 }
 
@@ -710,8 +1218,8 @@ fn compare_values(a: Option<&Value>, b: Option<&Value>) -> std::cmp::Ordering {
             }
 
             // Number comparison
-            if let (Some(n1), Some(n2)) = (a.as_f64(), b.as_f64()) {
-                return n1.partial_cmp(&n2).unwrap_or(std::cmp::Ordering::Equal);
+            if let (Value::Number(n1), Value::Number(n2)) = (a, b) {
+                return crate::numeric::compare(n1, n2);
             }
 
             // Boolean comparison
@@ -850,6 +1358,247 @@ mod tests {
         assert_eq!(results[0]["id"], 2);
     }
 
+    #[test]
+    fn test_project_computed_fields() {
+        let docs = vec![
+            json!({"first": "Ada", "last": "Lovelace", "price": 10, "qty": 3}),
+        ];
+
+        let stage = ProjectStage::from_json(&json!({
+            "name": {"$concat": ["$first", " ", "$last"]},
+            "total": {"$multiply": ["$price", "$qty"]},
+            "price": 1
+        })).unwrap();
+        let results = stage.execute(docs).unwrap();
+
+        assert_eq!(results[0], json!({"name": "Ada Lovelace", "total": 30, "price": 10}));
+    }
+
+    #[test]
+    fn test_add_fields_stage() {
+        let docs = vec![
+            json!({"name": "a", "qty": 150}),
+            json!({"name": "b", "qty": 5}),
+        ];
+
+        let pipeline = Pipeline::from_json(&json!([
+            {"$addFields": {"size": {"$cond": [{"$gte": ["$qty", 100]}, "bulk", "retail"]}}},
+            {"$set": {"qty": {"$add": ["$qty", 1]}}}
+        ])).unwrap();
+        let results = pipeline.execute(docs).unwrap();
+
+        assert_eq!(results[0], json!({"name": "a", "qty": 151, "size": "bulk"}));
+        assert_eq!(results[1], json!({"name": "b", "qty": 6, "size": "retail"}));
+    }
+
+    #[test]
+    fn test_group_sum_expression() {
+        let docs = vec![
+            json!({"city": "NYC", "price": 10, "qty": 2}),
+            json!({"city": "NYC", "price": 5, "qty": 4}),
+            json!({"city": "LA", "price": 1.5, "qty": 2}),
+        ];
+
+        let pipeline = Pipeline::from_json(&json!([
+            {"$group": {"_id": "$city", "revenue": {"$sum": {"$multiply": ["$price", "$qty"]}}}},
+            {"$sort": {"_id": 1}}
+        ])).unwrap();
+        let results = pipeline.execute(docs).unwrap();
+
+        assert_eq!(results[0], json!({"_id": "LA", "revenue": 3.0}));
+        assert_eq!(results[1], json!({"_id": "NYC", "revenue": 40}));
+    }
+
+    #[test]
+    fn test_group_collection_accumulators() {
+        let docs = vec![
+            json!({"team": "a", "name": "x", "tag": "red", "score": 2}),
+            json!({"team": "a", "name": "y", "tag": "blue", "score": 4}),
+            json!({"team": "a", "name": "z", "tag": "red", "score": 4}),
+            json!({"team": "a", "name": "w", "tag": "red", "score": 5}),
+        ];
+
+        let stage = GroupStage::from_json(&json!({
+            "_id": "$team",
+            "names": {"$push": "$name"},
+            "tags": {"$addToSet": "$tag"},
+            "n": {"$count": {}},
+            "pop": {"$stdDevPop": "$score"},
+            "samp": {"$stdDevSamp": "$score"},
+            "labels": {"$push": {"$concat": ["$name", ":", "$tag"]}}
+        })).unwrap();
+        let results = stage.execute(docs).unwrap();
+
+        assert_eq!(results.len(), 1);
+        let group = &results[0];
+        assert_eq!(group["names"], json!(["x", "y", "z", "w"]));
+        assert_eq!(group["tags"], json!(["red", "blue"]));
+        assert_eq!(group["n"], json!(4));
+        assert!((group["pop"].as_f64().unwrap() - 1.0897247358851685).abs() < 1e-12);
+        assert!((group["samp"].as_f64().unwrap() - 1.2583057392117916).abs() < 1e-12);
+        assert_eq!(group["labels"][0], json!("x:red"));
+    }
+
+    #[test]
+    fn test_group_accumulator_expressions() {
+        let docs = vec![
+            json!({"price": 10, "qty": 2}),
+            json!({"price": 4, "qty": 5}),
+        ];
+
+        let stage = GroupStage::from_json(&json!({
+            "_id": null,
+            "maxTotal": {"$max": {"$multiply": ["$price", "$qty"]}},
+            "avgTotal": {"$avg": {"$multiply": ["$price", "$qty"]}},
+            "firstMissing": {"$first": "$nope"},
+            "single": {"$stdDevSamp": {"$literal": 3}}
+        })).unwrap();
+        let results = stage.execute(docs).unwrap();
+
+        assert_eq!(results[0]["maxTotal"], json!(20.0));
+        assert_eq!(results[0]["avgTotal"], json!(20.0));
+        assert_eq!(results[0]["firstMissing"], Value::Null);
+        assert_eq!(results[0]["single"], json!(0.0));
+
+        assert!(GroupStage::from_json(&json!({"_id": null, "n": {"$count": 1}})).is_err());
+        assert!(GroupStage::from_json(&json!({"_id": null, "a": {"$push": "name"}})).is_err());
+    }
+
+    #[test]
+    fn test_group_compound_id() {
+        let docs = vec![
+            json!({"city": "NYC", "year": 2023, "amount": 1}),
+            json!({"year": 2023.0, "city": "NYC", "amount": 2}),
+            json!({"city": "NYC", "year": 2024, "amount": 4}),
+            json!({"city": "LA", "year": 2023, "amount": 8}),
+        ];
+
+        let pipeline = Pipeline::from_json(&json!([
+            {"$group": {"_id": {"city": "$city", "year": "$year"}, "total": {"$sum": "$amount"}}},
+            {"$sort": {"total": 1}}
+        ])).unwrap();
+        let results = pipeline.execute(docs.clone()).unwrap();
+
+        // 2023 and 2023.0 are the same key
+        assert_eq!(results, vec![
+            json!({"_id": {"city": "NYC", "year": 2023}, "total": 3}),
+            json!({"_id": {"city": "NYC", "year": 2024}, "total": 4}),
+            json!({"_id": {"city": "LA", "year": 2023}, "total": 8}),
+        ]);
+
+        // Computed and constant _id
+        let stage = GroupStage::from_json(&json!({
+            "_id": {"$toLower": "$city"},
+            "n": {"$sum": 1}
+        })).unwrap();
+        let mut ids: Vec<Value> = stage.execute(docs.clone()).unwrap().into_iter().map(|g| g["_id"].clone()).collect();
+        ids.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        assert_eq!(ids, vec![json!("la"), json!("nyc")]);
+
+        let stage = GroupStage::from_json(&json!({"_id": "all", "n": {"$sum": 1}})).unwrap();
+        assert_eq!(stage.execute(docs).unwrap(), vec![json!({"_id": "all", "n": 4})]);
+    }
+
+    #[test]
+    fn test_group_output_in_first_seen_order() {
+        let cities = ["Paris", "Oslo", "Lima", "Oslo", "Rome", "Lima", "Kyiv", "Baku", "Paris", "Nuuk"];
+        let docs: Vec<Value> = cities.iter().map(|c| json!({"city": c})).collect();
+
+        let stage = GroupStage::from_json(&json!({"_id": "$city", "n": {"$sum": 1}})).unwrap();
+
+        for _ in 0..5 {
+            let ids: Vec<Value> = stage.execute(docs.clone()).unwrap().into_iter().map(|g| g["_id"].clone()).collect();
+            assert_eq!(ids, vec![
+                json!("Paris"), json!("Oslo"), json!("Lima"), json!("Rome"),
+                json!("Kyiv"), json!("Baku"), json!("Nuuk"),
+            ]);
+        }
+    }
+
+    #[test]
+    fn test_output_stage_parsing() {
+        let pipeline = Pipeline::from_json(&json!([
+            {"$match": {"a": 1}},
+            {"$merge": {"into": "target", "whenMatched": "replace", "whenNotMatched": "discard"}}
+        ])).unwrap();
+
+        match pipeline.output() {
+            Some(OutputStage::Merge(merge)) => {
+                assert_eq!(merge.into, "target");
+                assert_eq!(merge.when_matched, WhenMatched::Replace);
+                assert_eq!(merge.when_not_matched, WhenNotMatched::Discard);
+            }
+            other => panic!("unexpected output stage: {:?}", other),
+        }
+
+        let pipeline = Pipeline::from_json(&json!([{"$out": "copy"}])).unwrap();
+        assert_eq!(pipeline.output().map(|o| o.collection()), Some("copy"));
+
+        assert!(Pipeline::from_json(&json!([{"$out": "copy"}, {"$limit": 1}])).is_err());
+        assert!(Pipeline::from_json(&json!([{"$merge": {"into": "t", "on": "name"}}])).is_err());
+        assert!(Pipeline::from_json(&json!([{"$merge": {"into": "t", "whenMatched": "bogus"}}])).is_err());
+        assert!(Pipeline::from_json(&json!([{"$out": "_oplog"}])).is_err());
+    }
+
+    #[test]
+    fn test_optimizer_rewrites() {
+        let optimized = |stages: Value| Pipeline::from_json(&stages).unwrap().stages;
+        let names = |stages: Value| optimized(stages).iter().map(Stage::name).collect::<Vec<_>>();
+        let filter = |stages: Value| match &optimized(stages)[0] {
+            Stage::Match(stage) => stage.filter.clone(),
+            other => panic!("expected $match, got {}", other.name()),
+        };
+
+        // $match moves ahead of the $project, merges with the next one; $sort + $limit fuse
+        let stages = json!([
+            {"$project": {"_id": 1, "a": 1, "b": 1}},
+            {"$match": {"a": 1}},
+            {"$match": {"b": {"$gt": 0}}},
+            {"$project": {}},
+            {"$sort": {"a": 1}},
+            {"$limit": 2}
+        ]);
+        assert_eq!(names(stages.clone()), vec!["$match", "$project", "$sort+$limit"]);
+        assert_eq!(filter(stages), json!({"a": 1, "b": {"$gt": 0}}));
+        assert_eq!(filter(json!([{"$match": {"a": 1}}, {"$match": {"a": 2}}])), json!({"$and": [{"a": 1}, {"a": 2}]}));
+
+        // Only past a $project that leaves the filtered fields alone
+        assert_eq!(names(json!([{"$project": {"x": 0}}, {"$match": {"a.b": 1}}])), vec!["$match", "$project"]);
+        assert_eq!(names(json!([{"$project": {"a": 0}}, {"$match": {"a": {"$exists": false}}}])), vec!["$project", "$match"]);
+        assert_eq!(names(json!([{"$project": {"c": "$a"}}, {"$match": {"c": 1}}])), vec!["$project", "$match"]);
+        assert_eq!(names(json!([{"$project": {"a": 1}}, {"$match": {"_id": 1}}])), vec!["$project", "$match"]);
+        assert_eq!(names(json!([{"$project": {"a": 1}}, {"$match": {"$or": [{"a": 1}, {"b": 1}]}}])), vec!["$project", "$match"]);
+        assert_eq!(names(json!([{"$sort": {"a": 1}}, {"$skip": 1}, {"$limit": 2}])), vec!["$sort", "$skip", "$limit"]);
+    }
+
+    #[test]
+    fn test_top_k_matches_sort_then_limit() {
+        let docs: Vec<Value> = (0..50).map(|n| json!({"n": n, "key": (n * 7) % 5})).collect();
+
+        let sort = SortStage::from_json(&json!({"key": -1})).unwrap();
+        let expected = LimitStage::from_json(&json!(12)).unwrap()
+            .execute(sort.execute(docs.clone()).unwrap()).unwrap();
+
+        let pipeline = Pipeline::from_json(&json!([{"$sort": {"key": -1}}, {"$limit": 12}])).unwrap();
+        let mut memory = MemoryTracker::unlimited();
+        let results = pipeline.execute_stream(docs.into_iter().map(Ok), &mut memory).unwrap();
+        assert_eq!(results, expected);
+
+        // The heap holds one document more than it returns, not all 50
+        let per_doc = estimate_size(&expected[0]);
+        assert!(memory.peak() <= (13 + 12) * per_doc);
+    }
+
+    #[test]
+    fn test_is_per_document() {
+        let per_document = |stages: Value| Pipeline::from_json(&stages).unwrap().is_per_document();
+        assert!(per_document(json!([{"$match": {"a": 1}}, {"$addFields": {"b": "$a"}}, {"$project": {"b": 1}}])));
+        assert!(!per_document(json!([{"$project": {"_id": 0, "b": 1}}])));
+        assert!(!per_document(json!([{"$addFields": {"_id": "$a"}}])));
+        assert!(!per_document(json!([{"$match": {}}, {"$limit": 5}])));
+        assert!(!per_document(json!([{"$group": {"_id": "$a"}}])));
+    }
+
     #[test]
     fn test_full_pipeline() {
         let docs = vec![
@@ -872,4 +1621,25 @@ mod tests {
         assert_eq!(results[0]["_id"], "NYC");
         assert_eq!(results[0]["count"], 2);
     }
+
+    #[test]
+    fn test_stream_pulls_only_needed_input() {
+        let pulled = std::cell::Cell::new(0);
+        let input = (0..1000).map(|n| {
+            pulled.set(pulled.get() + 1);
+            Ok(json!({"n": n, "even": n % 2 == 0}))
+        });
+
+        let pipeline = Pipeline::from_json(&json!([
+            {"$match": {"even": true}},
+            {"$skip": 2},
+            {"$addFields": {"half": {"$divide": ["$n", 2]}}},
+            {"$limit": 3}
+        ])).unwrap();
+        let results = pipeline.execute_stream(input, &mut MemoryTracker::unlimited()).unwrap();
+
+        let halves: Vec<_> = results.iter().map(|doc| doc["half"].as_f64().unwrap()).collect();
+        assert_eq!(halves, vec![2.0, 3.0, 4.0]);
+        assert_eq!(pulled.get(), 9);
+    }
 }
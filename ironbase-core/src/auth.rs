@@ -0,0 +1,229 @@
+// ironbase-core/src/auth.rs
+// Users, roles and password checks for server/CLI front ends
+
+use aes_gcm_siv::aead::rand_core::RngCore;
+use aes_gcm_siv::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::encryption::{from_hex, to_hex};
+use crate::error::{Result, MongoLiteError};
+
+/// System collection holding the users (hidden from list_collections)
+pub const USERS_COLLECTION: &str = "_users";
+
+/// PBKDF2-HMAC-SHA256 rounds for new passwords (stored per user, so it can be
+/// raised without invalidating existing ones)
+const PBKDF2_ITERATIONS: u32 = 100_000;
+/// Fewest rounds a stored hash may have been made with; a record below it
+/// never verifies rather than accept a hash that is cheap to brute-force
+const MIN_PBKDF2_ITERATIONS: u32 = 10_000;
+const SALT_LEN: usize = 16;
+/// Salt and hash checked against for users that do not exist
+const DUMMY_SALT: [u8; SALT_LEN] = [0u8; SALT_LEN];
+const DUMMY_HASH: [u8; 32] = [0u8; 32];
+
+/// What a request wants to do with a collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// find, count, distinct, aggregate, ...
+    Read,
+    /// insert, update, delete
+    Write,
+    /// Indexes, drop, compact, validation, encryption, users
+    Admin,
+}
+
+/// Role a user holds on a collection
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+    Read,
+    ReadWrite,
+    DbAdmin,
+}
+
+impl Role {
+    pub fn allows(self, action: Action) -> bool {
+        match self {
+            Role::Read => action == Action::Read,
+            Role::ReadWrite => action != Action::Admin,
+            Role::DbAdmin => true,
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Role::Read),
+            "readWrite" => Ok(Role::ReadWrite),
+            "dbAdmin" => Ok(Role::DbAdmin),
+            other => Err(MongoLiteError::InvalidConfig(format!(
+                "unknown role '{}' (expected read, readWrite or dbAdmin)", other
+            ))),
+        }
+    }
+}
+
+/// A role on one collection, or on every collection ("*")
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub role: Role,
+    pub collection: String,
+}
+
+impl Grant {
+    pub fn new(role: Role, collection: impl Into<String>) -> Self {
+        Grant { role, collection: collection.into() }
+    }
+
+    fn covers(&self, collection: &str) -> bool {
+        self.collection == "*" || self.collection == collection
+    }
+}
+
+/// An authenticated user and its grants
+///
+/// The database never checks grants itself - the front end serving a request
+/// calls authorize() before running it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub grants: Vec<Grant>,
+}
+
+impl User {
+    /// May this user perform `action` on `collection`?
+    ///
+    /// The users collection itself needs dbAdmin on "*".
+    pub fn is_allowed(&self, collection: &str, action: Action) -> bool {
+        if collection == USERS_COLLECTION {
+            return self.grants.iter().any(|g| g.collection == "*" && g.role == Role::DbAdmin);
+        }
+        self.grants.iter().any(|g| g.covers(collection) && g.role.allows(action))
+    }
+
+    pub fn authorize(&self, collection: &str, action: Action) -> Result<()> {
+        if self.is_allowed(collection, action) {
+            Ok(())
+        } else {
+            Err(MongoLiteError::Unauthorized(format!(
+                "user '{}' may not {:?} '{}'", self.name, action, collection
+            )))
+        }
+    }
+}
+
+/// Stored form of a user: one document of USERS_COLLECTION
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct UserRecord {
+    pub user: String,
+    salt: String,
+    hash: String,
+    iterations: u32,
+    pub grants: Vec<Grant>,
+}
+
+impl UserRecord {
+    /// New record with a fresh salt
+    pub fn new(user: &str, password: &str, grants: Vec<Grant>) -> Result<Self> {
+        if user.is_empty() || password.is_empty() {
+            return Err(MongoLiteError::InvalidConfig("user name and password must not be empty".to_string()));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Ok(UserRecord {
+            user: user.to_string(),
+            salt: to_hex(&salt),
+            hash: to_hex(&hash_password(password, &salt, PBKDF2_ITERATIONS)),
+            iterations: PBKDF2_ITERATIONS,
+            grants,
+        })
+    }
+
+    pub fn from_json(doc: &Value) -> Result<Self> {
+        serde_json::from_value(doc.clone())
+            .map_err(|e| MongoLiteError::Corruption(format!("invalid user record: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("user record serializes")
+    }
+
+    /// Constant-time password check
+    pub fn verify(&self, password: &str) -> bool {
+        match (from_hex(&self.salt), from_hex(&self.hash)) {
+            (Some(salt), Some(expected)) if self.iterations >= MIN_PBKDF2_ITERATIONS => {
+                hashes_equal(&expected, &hash_password(password, &salt, self.iterations))
+            }
+            _ => Self::verify_unknown(password),
+        }
+    }
+
+    /// The check verify() makes, against a fixed salt and hash no password
+    /// matches: a user that does not exist (or whose record is unusable)
+    /// takes as long to refuse as a wrong password
+    pub fn verify_unknown(password: &str) -> bool {
+        let actual = hash_password(password, &DUMMY_SALT, PBKDF2_ITERATIONS);
+        std::hint::black_box(hashes_equal(&DUMMY_HASH, &actual));
+        false
+    }
+
+    pub fn into_user(self) -> User {
+        User { name: self.user, grants: self.grants }
+    }
+}
+
+fn hash_password(password: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut hash);
+    hash
+}
+
+fn hashes_equal(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected.iter().zip(actual.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_and_passwords() {
+        let user = User {
+            name: "app".to_string(),
+            grants: vec![Grant::new(Role::ReadWrite, "orders"), Grant::new(Role::Read, "*")],
+        };
+        assert!(user.is_allowed("orders", Action::Write));
+        assert!(user.is_allowed("products", Action::Read));
+        assert!(!user.is_allowed("products", Action::Write));
+        assert!(!user.is_allowed("orders", Action::Admin));
+        assert!(!user.is_allowed(USERS_COLLECTION, Action::Read));
+        assert!(matches!(user.authorize("products", Action::Write), Err(MongoLiteError::Unauthorized(_))));
+
+        let admin = User { name: "root".to_string(), grants: vec![Grant::new(Role::DbAdmin, "*")] };
+        assert!(admin.is_allowed(USERS_COLLECTION, Action::Write));
+        assert_eq!("readWrite".parse::<Role>().unwrap(), Role::ReadWrite);
+
+        let record = UserRecord::new("app", "s3cret", user.grants.clone()).unwrap();
+        assert!(record.verify("s3cret"));
+        assert!(!record.verify("s3cret "));
+        // Salted: the same password hashes differently per user
+        assert_ne!(record.hash, UserRecord::new("other", "s3cret", Vec::new()).unwrap().hash);
+        let stored = UserRecord::from_json(&record.to_json()).unwrap();
+        assert!(!stored.to_json().to_string().contains("s3cret"));
+        assert_eq!(stored.into_user(), user);
+
+        // A hash made with too few rounds is refused, not checked cheaply
+        let mut weak = UserRecord::new("weak", "pw", Vec::new()).unwrap();
+        weak.iterations = 0;
+        weak.hash = to_hex(&hash_password("pw", &from_hex(&weak.salt).unwrap(), 0));
+        assert!(!weak.verify("pw"));
+        assert!(!UserRecord::verify_unknown("pw"));
+    }
+}
@@ -0,0 +1,87 @@
+// ironbase-core/src/backup.rs
+// Online backup: a copy of the database as of one snapshot, taken while writers go on
+//
+// Every collection is read through a SnapshotScan at the same LSN, so the
+// copy holds each transaction committed by then and nothing of later ones.
+// Documents keep their _id and stored form (encrypted fields stay
+// encrypted - open the copy with the same key). Collection settings and
+// index definitions are copied; the indexes are rebuilt when the copy is
+// opened. The copy is written next to the target and moved into place once
+// complete.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::contention::TimedRwLock;
+use crate::error::{MongoLiteError, Result};
+use crate::session::Lsn;
+use crate::snapshot_scan::SnapshotScan;
+use crate::storage::StorageEngine;
+
+/// Result of a backup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupStats {
+    /// LSN the copy was taken at
+    pub lsn: Lsn,
+    /// Documents copied, per collection
+    pub collections: BTreeMap<String, u64>,
+}
+
+impl BackupStats {
+    /// Documents copied from all collections
+    pub fn documents(&self) -> u64 {
+        self.collections.values().sum()
+    }
+}
+
+/// Copy the database behind `storage` to a new database file at `path`
+pub fn backup<P: AsRef<Path>>(storage: &Arc<TimedRwLock<StorageEngine>>, path: P) -> Result<BackupStats> {
+    let path = path.as_ref();
+    if path.exists() {
+        return Err(MongoLiteError::InvalidConfig(format!("backup target {} already exists", path.display())));
+    }
+
+    // The snapshot and the collections it covers, under one lock
+    let (snapshot, metas) = {
+        let engine = storage.read();
+        let metas: Vec<_> = engine.list_collections().iter()
+            .filter_map(|name| engine.get_collection_meta(name).cloned())
+            .collect();
+        (engine.snapshot(), metas)
+    };
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp.mlite");
+    let temp_path = path.with_file_name(temp_name);
+    let mut stats = BackupStats { lsn: snapshot.lsn(), ..Default::default() };
+
+    let copied = (|| {
+        let mut target = StorageEngine::open(&temp_path)?;
+        for meta in &metas {
+            target.create_collection_like(meta)?;
+            let mut scan = SnapshotScan::new(Arc::clone(storage), &meta.name, snapshot.clone())?;
+            let mut documents = 0;
+            while let Some(batch) = scan.next_batch()? {
+                documents += batch.len() as u64;
+                target.write_batch(|target| {
+                    batch.iter().try_for_each(|(doc_id, data)| target.write_document(&meta.name, doc_id, data).map(|_| ()))
+                })?;
+            }
+            stats.collections.insert(meta.name.clone(), documents);
+        }
+        target.flush()?;
+        drop(target);
+        crate::durable_fs::atomic_replace(&temp_path, path).map_err(MongoLiteError::from)
+    })();
+
+    // The temporary file's WAL and journal are empty once it is flushed
+    for extension in ["wal", "journal"] {
+        let _ = std::fs::remove_file(temp_path.with_extension(extension));
+    }
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    Ok(stats)
+}
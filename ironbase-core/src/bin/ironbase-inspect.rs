@@ -0,0 +1,59 @@
+// ironbase-core/src/bin/ironbase-inspect.rs
+// Print the on-disk structures of a database file (see storage/inspect.rs)
+//
+// Usage: ironbase-inspect <database> [--json] [--no-records]
+
+use std::process::ExitCode;
+
+use ironbase_core::DatabaseCore;
+
+const USAGE: &str = "usage: ironbase-inspect <database> [--json] [--no-records]";
+
+fn main() -> ExitCode {
+    let mut path = None;
+    let mut json = false;
+    let mut records = true;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--no-records" => records = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let mut report = match DatabaseCore::inspect(&path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("ironbase-inspect: {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !records {
+        report.records.clear();
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("ironbase-inspect: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        print!("{}", report);
+    }
+    ExitCode::SUCCESS
+}
@@ -1,69 +1,91 @@
 // catalog_serde.rs
-// Custom serialization for HashMap<DocumentId, u64> to preserve DocumentId types in JSON
+// Custom serialization of the document catalog in the collection metadata
+//
+// Since format version 3 the metadata holds only the catalog's shard records
+// (`{"shards":[[offset,size],...]}`, see storage/catalog.rs). Older files kept
+// every entry inline as an array of [type_tag, value, offset] tuples; those
+// still load, with every shard marked for writing.
 
-use std::collections::HashMap;
-use serde::{Serializer, Deserializer};
-use serde::ser::SerializeSeq;
-use serde::de::{SeqAccess, Visitor};
+use std::fmt;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
 use crate::document::DocumentId;
+use crate::storage::DocumentCatalog;
 
-/// Serialize HashMap<DocumentId, u64> as array of [type_tag, value, offset] tuples
-/// This preserves DocumentId type information in JSON metadata
-pub fn serialize<S>(catalog: &HashMap<DocumentId, u64>, serializer: S) -> Result<S::Ok, S::Error>
+/// Serialize the catalog as its shard record locations
+pub fn serialize<S>(catalog: &DocumentCatalog, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let mut seq = serializer.serialize_seq(Some(catalog.len()))?;
-    for (doc_id, offset) in catalog {
-        // Serialize as [type_tag, value, offset]
-        // type_tag: "i" = Int, "s" = String, "o" = ObjectId
-        let entry: (&str, String, u64) = match doc_id {
-            DocumentId::Int(i) => ("i", i.to_string(), *offset),
-            DocumentId::String(s) => ("s", s.clone(), *offset),
-            DocumentId::ObjectId(oid) => ("o", oid.clone(), *offset),
-        };
-        seq.serialize_element(&entry)?;
+    #[derive(Serialize)]
+    struct Shards<'a> {
+        shards: &'a [(u64, u64)],
     }
-    seq.end()
+
+    Shards { shards: catalog.shard_records() }.serialize(serializer)
 }
 
-/// Deserialize array of [type_tag, value, offset] tuples back to HashMap<DocumentId, u64>
-pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<DocumentId, u64>, D::Error>
+/// Deserialize shard record locations, or the inline entries of older files
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DocumentCatalog, D::Error>
 where
     D: Deserializer<'de>,
 {
     struct CatalogVisitor;
 
     impl<'de> Visitor<'de> for CatalogVisitor {
-        type Value = HashMap<DocumentId, u64>;
+        type Value = DocumentCatalog;
 
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str("an array of [type_tag, value, offset] tuples")
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("catalog shards or an array of [type_tag, value, offset] tuples")
         }
 
         fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
         where
             A: SeqAccess<'de>,
         {
-            let mut catalog = HashMap::new();
-
+            let mut entries = Vec::new();
             while let Some((type_tag, value_str, offset)) = seq.next_element::<(String, String, u64)>()? {
-                let doc_id = match type_tag.as_str() {
-                    "i" => {
-                        let val = value_str.parse::<i64>()
-                            .map_err(|e| serde::de::Error::custom(format!("Invalid Int value: {}", e)))?;
-                        DocumentId::Int(val)
-                    },
-                    "s" => DocumentId::String(value_str),
-                    "o" => DocumentId::ObjectId(value_str),
-                    _ => return Err(serde::de::Error::custom(format!("Unknown type tag: {}", type_tag))),
-                };
-                catalog.insert(doc_id, offset);
+                let doc_id = decode_entry(&type_tag, value_str).map_err(serde::de::Error::custom)?;
+                entries.push((doc_id, offset));
+            }
+            Ok(entries.into_iter().collect())
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            #[derive(Deserialize)]
+            struct Shards {
+                shards: Vec<(u64, u64)>,
             }
 
-            Ok(catalog)
+            let shards = Shards::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+            Ok(DocumentCatalog::with_records(shards.shards))
         }
     }
 
-    deserializer.deserialize_seq(CatalogVisitor)
+    deserializer.deserialize_any(CatalogVisitor)
+}
+
+/// A catalog entry as [type_tag, value, offset]
+/// type_tag: "i" = Int, "s" = String, "o" = ObjectId
+pub fn encode_entry(doc_id: &DocumentId, offset: u64) -> (String, String, u64) {
+    match doc_id {
+        DocumentId::Int(i) => ("i".to_string(), i.to_string(), offset),
+        DocumentId::String(s) => ("s".to_string(), s.clone(), offset),
+        DocumentId::ObjectId(oid) => ("o".to_string(), oid.clone(), offset),
+    }
+}
+
+/// The DocumentId of an encoded entry
+pub fn decode_entry(type_tag: &str, value_str: String) -> Result<DocumentId, String> {
+    match type_tag {
+        "i" => value_str.parse::<i64>()
+            .map(DocumentId::Int)
+            .map_err(|e| format!("Invalid Int value: {}", e)),
+        "s" => Ok(DocumentId::String(value_str)),
+        "o" => Ok(DocumentId::ObjectId(value_str)),
+        _ => Err(format!("Unknown type tag: {}", type_tag)),
+    }
 }
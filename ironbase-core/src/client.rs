@@ -0,0 +1,231 @@
+// ironbase-core/src/client.rs
+// Several named databases under one root directory, sharing background threads
+//
+// client.database("analytics") opens (or creates) `<root>/analytics.mlite`
+// once and hands out the same DatabaseCore after that. The databases stay
+// independent files - transactions, references and $lookup only span
+// collections of one database - but share the process's background work:
+// one sync thread runs the WAL and data file fsyncs of all of them (see
+// wal::SyncThread), and one maintenance thread compacts the collections
+// whose dead space passes ClientOptions::compaction_threshold.
+//
+// Database names are letters, digits, '_' and '-', so a name maps to its
+// files (data file, WAL, journal and `<name>.<index>.idx`) and back.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::database::DatabaseCore;
+use crate::error::{MongoLiteError, Result};
+use crate::storage::StorageConfig;
+use crate::wal::SyncThread;
+
+/// Longest database name
+pub const MAX_DATABASE_NAME: usize = 64;
+
+/// Settings of a Client
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Storage configuration every database is opened with (its sync
+    /// thread is replaced by the client's shared one)
+    pub storage: StorageConfig,
+    /// How often the maintenance thread looks for collections to compact
+    /// (default: 5 minutes); None runs no maintenance thread
+    pub compaction_interval: Option<Duration>,
+    /// Share of a collection's bytes that is dead before it is compacted
+    /// (default: 0.5)
+    pub compaction_threshold: f64,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            storage: StorageConfig::default(),
+            compaction_interval: Some(Duration::from_secs(300)),
+            compaction_threshold: 0.5,
+        }
+    }
+}
+
+struct ClientInner {
+    root: PathBuf,
+    storage: StorageConfig,
+    compaction_threshold: f64,
+    /// Databases opened so far
+    open: Mutex<HashMap<String, Arc<DatabaseCore>>>,
+}
+
+/// Named databases under a root directory (see client.rs)
+pub struct Client {
+    inner: Arc<ClientInner>,
+    /// Maintenance thread and the channel whose closing stops it
+    maintenance: Option<(mpsc::Sender<()>, std::thread::JoinHandle<()>)>,
+}
+
+impl Client {
+    /// Open the databases under `root` (created if missing)
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self> {
+        Self::open_with_options(root, &ClientOptions::default())
+    }
+
+    pub fn open_with_options<P: AsRef<Path>>(root: P, options: &ClientOptions) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        std::fs::create_dir_all(&root)?;
+
+        let inner = Arc::new(ClientInner {
+            root,
+            storage: options.storage.clone().with_sync_thread(SyncThread::new()),
+            compaction_threshold: options.compaction_threshold,
+            open: Mutex::new(HashMap::new()),
+        });
+
+        let maintenance = match options.compaction_interval {
+            Some(interval) => {
+                let (stop, stopped) = mpsc::channel();
+                let worker_inner = Arc::clone(&inner);
+                let worker = std::thread::Builder::new()
+                    .name("ironbase-maintenance".to_string())
+                    .spawn(move || loop {
+                        match stopped.recv_timeout(interval) {
+                            Err(RecvTimeoutError::Timeout) => {
+                                worker_inner.compact_dead_collections();
+                            }
+                            _ => return,
+                        }
+                    })
+                    .expect("failed to spawn maintenance thread");
+                Some((stop, worker))
+            }
+            None => None,
+        };
+
+        Ok(Client { inner, maintenance })
+    }
+
+    /// The root directory
+    pub fn path(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Get database (creates its file if it doesn't exist)
+    pub fn database(&self, name: &str) -> Result<Arc<DatabaseCore>> {
+        check_database_name(name)?;
+        let mut open = self.inner.open.lock();
+        if let Some(db) = open.get(name) {
+            return Ok(Arc::clone(db));
+        }
+        let db = Arc::new(DatabaseCore::open_with_config(self.inner.data_file(name), &self.inner.storage)?);
+        open.insert(name.to_string(), Arc::clone(&db));
+        Ok(db)
+    }
+
+    /// Names of the databases under the root directory, sorted
+    pub fn list_databases(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.inner.root)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_suffix(".mlite") {
+                if check_database_name(name).is_ok() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Drop database by deleting its files; false if there is none by
+    /// that name
+    ///
+    /// Handles still open on the database keep working on the deleted
+    /// files; nothing they write is kept.
+    pub fn drop_database(&self, name: &str) -> Result<bool> {
+        check_database_name(name)?;
+        let mut open = self.inner.open.lock();
+        if !self.inner.data_file(name).exists() {
+            return Ok(false);
+        }
+
+        if let Some(db) = open.remove(name) {
+            db.drop_database()?;
+        }
+
+        // <name>.mlite, <name>.wal, <name>.journal, <name>.<index>.idx
+        let prefix = format!("{}.", name);
+        for entry in std::fs::read_dir(&self.inner.root)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Flush every open database to disk
+    pub fn flush(&self) -> Result<()> {
+        for db in self.inner.open_databases() {
+            db.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Compact the collections whose dead space passes the compaction
+    /// threshold, as the maintenance thread does; returns how many were
+    pub fn compact_dead_collections(&self) -> usize {
+        self.inner.compact_dead_collections()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some((stop, worker)) = self.maintenance.take() {
+            drop(stop);
+            let _ = worker.join();
+        }
+    }
+}
+
+impl ClientInner {
+    fn data_file(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.mlite", name))
+    }
+
+    fn open_databases(&self) -> Vec<Arc<DatabaseCore>> {
+        self.open.lock().values().cloned().collect()
+    }
+
+    fn compact_dead_collections(&self) -> usize {
+        let mut compacted = 0;
+        for db in self.open_databases() {
+            for name in db.list_collections() {
+                let dead_ratio = db.collection(&name)
+                    .and_then(|collection| collection.stats())
+                    .map(|stats| stats["dead_ratio"].as_f64().unwrap_or(0.0))
+                    .unwrap_or(0.0);
+                // A failed compaction leaves the collection as it was; the next round retries
+                if dead_ratio >= self.compaction_threshold && db.compact_collection(&name).is_ok() {
+                    compacted += 1;
+                }
+            }
+        }
+        compacted
+    }
+}
+
+fn check_database_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_DATABASE_NAME
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(MongoLiteError::InvalidConfig(format!(
+            "invalid database name '{}': use up to {} letters, digits, '_' or '-'", name, MAX_DATABASE_NAME
+        )))
+    }
+}
@@ -4,16 +4,28 @@
 // FILE STRUCTURE (1,244 lines):
 // ├── Constructor (lines 25-125)
 // ├── CRUD Operations (lines 127-595)
-// │   ├── insert_one, update_one, update_many
-// │   ├── delete_one, delete_many
+// │   ├── insert_one, bulk_upsert, update_one, update_many
+// │   ├── find_one_and_replace, increment (atomic find + write)
+// │   ├── delete_one, delete_many, delete_many_by_ids, find_and_delete_many
 // │   └── distinct
 // ├── Query Operations (lines 186-664)
-// │   ├── find, find_one, count_documents
-// │   ├── find_with_options, find_with_hint
+// │   ├── find, find_one, find_by_ids, find_with_populate, count_documents
+// │   ├── find_at, find_by_id_at (snapshot reads)
+// │   ├── find_with_options, find_with_hint, find_page
 // │   └── explain
 // ├── Aggregation (lines 906-917)
+// │   └── aggregate ($out / $merge write helpers)
 // ├── Index Operations (lines 922-1004)
 // │   ├── create_index, drop_index, list_indexes
+// ├── Hooks
+// │   └── add_hook, clear_hooks (write triggers, see hooks.rs)
+// ├── Field Encryption, Computed Fields & Quotas
+// │   └── encrypt_field, encrypted_fields, add_computed_field, drop_computed_field,
+// │       computed_fields, set_quota, quota
+// ├── References
+// │   └── add_reference, drop_reference, references
+// ├── Statistics & Validation
+// │   └── stats, validate
 // ├── Transaction Operations (lines 1012-1124)
 // │   ├── insert_one_tx, update_one_tx, delete_one_tx
 // └── Private Helpers (lines 1126-1244)
@@ -24,40 +36,161 @@
 // FUTURE REFACTOR: See COLLECTION_DESIGN.md for modular architecture plan
 
 use std::sync::Arc;
-use parking_lot::RwLock;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::storage::StorageEngine;
+use crate::storage::{bloom_segment, CollectionQuota, StorageEngine};
+use crate::numeric;
+use crate::contention::{LockKind, TimedRwLock};
+use parking_lot::RwLockWriteGuard;
 use crate::document::{Document, DocumentId};
-use crate::error::{Result, MongoLiteError};
+use crate::error::{Result, ResultExt, MongoLiteError};
 use crate::query::Query;
-use crate::index::{IndexManager, IndexKey};
+use crate::index::{check_index_file, BPlusTree, IndexFileStatus, IndexManager, IndexKey};
 use crate::query_planner::{QueryPlanner, QueryPlan};
 use crate::query_cache::{QueryCache, QueryHash};
+use crate::plan_cache::{CachedPlan, PlanCache, QueryShape};
+use crate::memory::MemoryTracker;
+use crate::operations::OperationGuard;
+use crate::hooks::{Hook, HookEvent, HookRegistry};
+use crate::trace::{TraceRecorder, TracedOp};
+use crate::encryption::{EncryptionMode, FieldEncryptor};
+use crate::computed::{ComputedField, ComputedFields};
+use crate::storage::{OnDelete, Reference};
+use crate::find_options::{Populate, ReadConcern, ReturnDocument};
+use crate::storage::Snapshot;
+use crate::snapshot_scan::SnapshotScan;
+use crate::validation::{ValidationReport, ValidationIssue};
+use crate::statistics::{CollectionStatistics, ANALYZE_SAMPLE};
 
 /// Result of insert_many operation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct InsertManyResult {
+    pub acknowledged: bool,
     pub inserted_ids: Vec<DocumentId>,
     pub inserted_count: usize,
 }
 
+impl InsertManyResult {
+    fn new(inserted_ids: Vec<DocumentId>) -> Self {
+        InsertManyResult {
+            acknowledged: true,
+            inserted_count: inserted_ids.len(),
+            inserted_ids,
+        }
+    }
+}
+
+/// Result of update_one / update_many (and their transactional forms)
+///
+/// Non-exhaustive so fields can be added without breaking callers; match
+/// with `UpdateResult { matched_count, .. }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UpdateResult {
+    /// Every write here is acknowledged once it returns; kept for parity
+    /// with the bindings' result dicts
+    pub acknowledged: bool,
+    pub matched_count: u64,
+    pub modified_count: u64,
+    /// _id of a document inserted by an upsert (None: nothing was inserted)
+    pub upserted_id: Option<DocumentId>,
+}
+
+impl UpdateResult {
+    pub(crate) fn new(matched_count: u64, modified_count: u64) -> Self {
+        UpdateResult {
+            acknowledged: true,
+            matched_count,
+            modified_count,
+            upserted_id: None,
+        }
+    }
+}
+
+/// Result of delete_one / delete_many / delete_many_by_ids (and delete_one_tx)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeleteResult {
+    pub acknowledged: bool,
+    pub deleted_count: u64,
+}
+
+impl DeleteResult {
+    pub(crate) fn new(deleted_count: u64) -> Self {
+        DeleteResult { acknowledged: true, deleted_count }
+    }
+}
+
+/// Result of bulk_upsert operation
+#[derive(Debug, Clone, Default)]
+pub struct BulkUpsertResult {
+    /// Existing documents replaced
+    pub matched_count: usize,
+    pub upserted_ids: Vec<DocumentId>,
+    pub upserted_count: usize,
+}
+
+/// An index rebuilt by reindex()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReindexResult {
+    pub index: String,
+    /// Keys in the rebuilt index
+    pub keys: u64,
+}
+
+/// Planned writes for a $out / $merge stage
+#[derive(Debug, Default)]
+struct AggregationOutputPlan {
+    /// (oplog op, _id, document) in result order
+    writes: Vec<(&'static str, DocumentId, Value)>,
+    /// Existing documents to remove ($out, view refresh)
+    removals: Vec<DocumentId>,
+}
+
+/// A leading $match aggregate() reads through an index
+struct IndexedMatch {
+    /// The filter, encrypted like find() queries
+    query: Value,
+    field: String,
+    plan: QueryPlan,
+    /// The stages after the $match
+    rest: crate::aggregation::Pipeline,
+}
+
+/// Documents explain_aggregate samples for their average size
+const AGGREGATE_EXPLAIN_SAMPLE: usize = 100;
+
 /// Pure Rust Collection - language-independent core logic
 pub struct CollectionCore {
     pub name: String,
-    pub storage: Arc<RwLock<StorageEngine>>,
+    pub storage: Arc<TimedRwLock<StorageEngine>>,
     /// Index manager for B+ tree indexes
-    pub indexes: Arc<RwLock<IndexManager>>,
+    pub indexes: Arc<TimedRwLock<IndexManager>>,
     /// Query result cache with LRU eviction (capacity: 1000 queries)
     pub query_cache: Arc<QueryCache>,
+    /// Query plans by query shape (capacity: 1000 shapes), emptied on index changes
+    pub plan_cache: Arc<PlanCache>,
+    /// Collection LSN the cached results were computed at - writes through
+    /// other handles or transactions move it and empty the cache
+    cache_lsn: Arc<std::sync::atomic::AtomicU64>,
+    /// Write hooks of the database (see hooks.rs)
+    hooks: Arc<HookRegistry>,
+    /// Operation capture of the database (see trace.rs)
+    trace: Arc<TraceRecorder>,
+    /// Collection LSN as of which new() read or rebuilt the indexes
+    pub(crate) opened_lsn: crate::session::Lsn,
+    /// CollectionMeta::index_epoch the indexes were built at - sync_indexes
+    /// rebuilds them once it moves
+    index_epoch: Arc<std::sync::atomic::AtomicU64>,
 }
 
 impl CollectionCore {
     // ========== CONSTRUCTOR ==========
 
     /// Create new collection (or get existing)
-    pub fn new(name: String, storage: Arc<RwLock<StorageEngine>>) -> Result<Self> {
+    pub fn new(name: String, storage: Arc<TimedRwLock<StorageEngine>>) -> Result<Self> {
         // Collection létrehozása, ha nem létezik
         {
             let mut storage_guard = storage.write();
@@ -78,22 +211,30 @@ impl CollectionCore {
         )?;
 
         // PERSISTENCE FIX: Load persisted indexes and rebuild from document catalog
+        // One hold of the storage lock: what is read or rebuilt is the
+        // collection as of opened_lsn
+        let opened_lsn;
+        let index_epoch;
         {
-            let storage_guard = storage.write();
+            let mut storage_guard = storage.write();
             let meta = storage_guard.get_collection_meta(&name)
                 .ok_or_else(|| MongoLiteError::CollectionNotFound(name.clone()))?;
 
             // Clone metadata to avoid borrow issues
             let catalog = meta.document_catalog.clone();
             let persisted_indexes = meta.indexes.clone();
+            let index_files = meta.index_files.clone();
+            opened_lsn = meta.last_lsn;
+            index_epoch = meta.index_epoch;
+            if let Some(id_index) = index_manager.get_btree_index_mut(&id_index_name) {
+                id_index.metadata.uuid = meta.uuid.clone();
+            }
 
             eprintln!("🔍 DEBUG: Collection '{}' - catalog size: {}, persisted indexes: {}",
                      name, catalog.len(), persisted_indexes.len());
             use std::io::Write;
             let _ = std::io::stderr().flush();
 
-            drop(storage_guard); // Release write lock before rebuilding
-
             // Load persisted custom indexes (if any)
             for index_meta in &persisted_indexes {
                 // Skip _id index (already created)
@@ -105,18 +246,44 @@ impl CollectionCore {
                          index_meta.name, index_meta.field);
 
                 // Create index
-                index_manager.create_btree_index(
-                    index_meta.name.clone(),
-                    index_meta.field.clone(),
-                    index_meta.unique
-                )?;
+                if index_meta.hashed {
+                    index_manager.create_hashed_index(
+                        index_meta.name.clone(),
+                        index_meta.field.clone()
+                    )?;
+                } else {
+                    index_manager.create_btree_index(
+                        index_meta.name.clone(),
+                        index_meta.field.clone(),
+                        index_meta.unique
+                    )?;
+                }
+                if let Some(index) = index_manager.get_btree_index_mut(&index_meta.name) {
+                    index.metadata.uuid = index_meta.uuid.clone();
+                }
             }
 
-            // Rebuild all indexes from document catalog
-            eprintln!("🔍 DEBUG: Starting index rebuild from {} catalog entries", catalog.len());
-            let mut storage_guard = storage.write();
+            // Index files that hold their index as of the documents are read;
+            // the other indexes are rebuilt from the documents
+            let mut rebuilt: Vec<String> = Vec::new();
+            let index_names = std::iter::once(&id_index_name)
+                .chain(persisted_indexes.iter().map(|index_meta| &index_meta.name).filter(|index_name| **index_name != id_index_name));
+            for index_name in index_names {
+                let read = index_files.get(index_name).and_then(|save_id| {
+                    let index = index_manager.get_btree_index(index_name)?;
+                    read_current_index_file(index, &storage_guard.index_file_path(index_name), save_id)
+                });
+                match read {
+                    Some(tree) => index_manager.replace_btree_index(tree),
+                    None => rebuilt.push(index_name.clone()),
+                }
+            }
+
+            // Rebuild indexes from document catalog
+            eprintln!("🔍 DEBUG: Starting index rebuild of {:?} from {} catalog entries", rebuilt, catalog.len());
             let mut rebuilt_count = 0;
-            for (_id_key, offset) in catalog.iter() {
+            let mut complete = true;
+            for (_id_key, offset) in catalog.iter().filter(|_| !rebuilt.is_empty()) {
                 // Read document from disk (absolute offset)
                 match storage_guard.read_document_at(&name, *offset) {
                     Ok(doc_bytes) => {
@@ -127,27 +294,22 @@ impl CollectionCore {
                                     continue;
                                 }
 
-                                // Rebuild ALL indexes
                                 if let Some(id_value) = doc.get("_id") {
                                     if let Ok(doc_id) = serde_json::from_value::<DocumentId>(id_value.clone()) {
-                                        // Rebuild _id index
-                                        let index_key = IndexKey::from(id_value);
-                                        if let Some(id_index) = index_manager.get_btree_index_mut(&id_index_name) {
-                                            let _ = id_index.insert(index_key, doc_id.clone());
-                                        }
-
-                                        // Rebuild custom indexes
-                                        for index_meta in &persisted_indexes {
-                                            if index_meta.name == id_index_name {
+                                        for index_name in &rebuilt {
+                                            let Some(index) = index_manager.get_btree_index_mut(index_name) else {
                                                 continue;
-                                            }
-
-                                            if let Some(field_value) = doc.get(&index_meta.field) {
-                                                let key = IndexKey::from(field_value);
-                                                if let Some(index) = index_manager.get_btree_index_mut(&index_meta.name) {
-                                                    let _ = index.insert(key, doc_id.clone());
-                                                    rebuilt_count += 1;
-                                                }
+                                            };
+                                            // _id index keys are the _id value itself
+                                            let field_value = if *index_name == id_index_name {
+                                                Some(id_value)
+                                            } else {
+                                                doc.get(&index.metadata.field)
+                                            };
+                                            if let Some(field_value) = field_value {
+                                                let key = index.key_for(field_value);
+                                                let _ = index.insert(key, doc_id.clone());
+                                                rebuilt_count += 1;
                                             }
                                         }
                                     }
@@ -155,40 +317,76 @@ impl CollectionCore {
                             }
                             Err(e) => {
                                 eprintln!("🔍 DEBUG: Failed to parse document JSON: {:?}", e);
+                                complete = false;
                                 continue;
                             }
                         }
                     }
                     Err(e) => {
                         eprintln!("🔍 DEBUG: Failed to read document at offset: {:?}", e);
+                        complete = false;
                         continue;
                     }
                 }
             }
             eprintln!("🔍 DEBUG: Index rebuild completed - {} index entries rebuilt", rebuilt_count);
+
+            // Index files of an older format or of a dropped namesake; a
+            // rewritten one holds its index as of the documents if every
+            // document was read
+            for index_name in &rebuilt {
+                let path = storage_guard.index_file_path(index_name);
+                let save_id = index_manager.get_btree_index_mut(index_name)
+                    .and_then(|index| refresh_stale_index_file(index, &path));
+                if let (Some(save_id), true) = (save_id, complete) {
+                    if let Some(meta) = storage_guard.get_collection_meta_mut(&name) {
+                        meta.index_files.insert(index_name.clone(), save_id);
+                    }
+                }
+            }
         }
 
+        let indexes = TimedRwLock::new(index_manager, storage.metrics(), LockKind::Indexes);
+        let hooks = storage.read().hooks();
+        let trace = storage.read().trace();
         Ok(CollectionCore {
             name,
             storage,
-            indexes: Arc::new(RwLock::new(index_manager)),
+            indexes: Arc::new(indexes),
             query_cache: Arc::new(QueryCache::new(1000)),  // LRU cache with 1000 query capacity
+            plan_cache: Arc::new(PlanCache::new(1000)),
+            cache_lsn: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            hooks,
+            trace,
+            opened_lsn,
+            index_epoch: Arc::new(std::sync::atomic::AtomicU64::new(index_epoch)),
         })
     }
 
     // ========== CRUD OPERATIONS ==========
 
     /// Insert one document - returns inserted DocumentId
-    pub fn insert_one(&self, mut fields: HashMap<String, Value>) -> Result<DocumentId> {
+    pub fn insert_one(&self, fields: HashMap<String, Value>) -> Result<DocumentId> {
+        let doc = self.trace.is_active().then(|| Value::Object(fields.clone().into_iter().collect()));
+        let result = self.insert_one_inner(fields).in_operation("insert_one", &self.name);
+        if let Some(doc) = doc {
+            self.record_trace(|| TracedOp::InsertOne { doc }, &result);
+        }
+        result
+    }
+
+    fn insert_one_inner(&self, mut fields: HashMap<String, Value>) -> Result<DocumentId> {
+        self.check_writable()?;
+        let encryptor = self.encryptor();
+        let computed = self.computed()?;
         let mut storage = self.storage.write();
 
         // Get mutable reference to collection metadata
         let meta = storage.get_collection_meta_mut(&self.name)
             .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
 
-        // ID generálás
+        // ID generálás (last_id only advances once the document is accepted)
         let doc_id = DocumentId::new_auto(meta.last_id);
-        meta.last_id += 1;
 
         // Add _id to fields for query matching (From<Document> will not duplicate it)
         fields.insert("_id".to_string(), serde_json::to_value(&doc_id).unwrap());
@@ -197,7 +395,16 @@ impl CollectionCore {
         fields.insert("_collection".to_string(), Value::String(self.name.clone()));
 
         // Dokumentum létrehozása
-        let doc = Document::new(doc_id.clone(), fields);
+        let mut doc = self.run_before_hooks(HookEvent::BeforeInsert, Document::new(doc_id.clone(), fields))?;
+        computed.apply_document(&mut doc)?;
+        encryptor.encrypt_fields(doc.fields.iter_mut())?;
+        storage.check_references(&self.name, |field| doc.get(field))?;
+
+        // Szerializálás - an oversized document is rejected before indexes change
+        let doc_json = doc.to_json()?;
+        storage.check_document_size(doc_json.len())?;
+        storage.check_quota(&self.name, 1, doc_json.len() as u64)?;
+        storage.next_id(&self.name)?;
 
         // Update indexes BEFORE writing to storage
         {
@@ -223,33 +430,49 @@ impl CollectionCore {
                 if let Some(index) = indexes.get_btree_index_mut(&index_name) {
                     let field = &index.metadata.field;
                     if let Some(field_value) = doc.get(field) {
-                        let index_key = IndexKey::from(field_value);
+                        let index_key = index.key_for(field_value);
                         index.insert(index_key, doc_id.clone())?;
                     }
                 }
             }
         }
 
-        // Szerializálás és írás - USE NEW write_document with catalog tracking
-        let doc_json = doc.to_json()?;
+        // Írás - USE NEW write_document with catalog tracking
         storage.write_document(&self.name, &doc_id, doc_json.as_bytes())?;
 
+        if storage.oplog_enabled() {
+            storage.log_operation("insert", &self.name, &doc_id, serde_json::from_str(&doc_json)?)?;
+        }
+        drop(storage);
+
         // Invalidate query cache (collection has changed)
         self.query_cache.invalidate_collection(&self.name);
 
+        self.run_after_hooks(HookEvent::AfterInsert, [Value::from(doc)])?;
         Ok(doc_id)
     }
 
     /// Insert many documents - optimized batch insert
     /// Returns InsertManyResult with all inserted document IDs
     pub fn insert_many(&self, documents: Vec<HashMap<String, Value>>) -> Result<InsertManyResult> {
+        let docs = self.trace.is_active().then(|| {
+            documents.iter().map(|fields| Value::Object(fields.clone().into_iter().collect())).collect()
+        });
+        let result = self.insert_many_inner(documents).in_operation("insert_many", &self.name);
+        if let Some(docs) = docs {
+            self.record_trace(|| TracedOp::InsertMany { docs }, &result);
+        }
+        result
+    }
+
+    fn insert_many_inner(&self, documents: Vec<HashMap<String, Value>>) -> Result<InsertManyResult> {
+        self.check_writable()?;
         if documents.is_empty() {
-            return Ok(InsertManyResult {
-                inserted_ids: Vec::new(),
-                inserted_count: 0,
-            });
+            return Ok(InsertManyResult::new(Vec::new()));
         }
 
+        let encryptor = self.encryptor();
+        let computed = self.computed()?;
         let mut storage = self.storage.write();
         let mut inserted_ids = Vec::with_capacity(documents.len());
 
@@ -257,15 +480,15 @@ impl CollectionCore {
         let meta = storage.get_collection_meta_mut(&self.name)
             .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
 
-        // Generate all IDs upfront
+        // Generate all IDs upfront (claimed once every document is accepted)
         let start_id = meta.last_id;
-        meta.last_id += documents.len() as u64;
+        let count = documents.len() as u64;
 
         // Prepare all documents with IDs
         let mut prepared_docs = Vec::with_capacity(documents.len());
         for (idx, mut fields) in documents.into_iter().enumerate() {
-            // new_auto adds 1, so subtract 1 from the sequence
-            let doc_id = DocumentId::new_auto(start_id - 1 + idx as u64);
+            // Same sequence as insert_one: ids start after last_id
+            let doc_id = DocumentId::new_auto(start_id + idx as u64);
 
             // Add _id to fields
             fields.insert("_id".to_string(), serde_json::to_value(&doc_id).unwrap());
@@ -273,18 +496,27 @@ impl CollectionCore {
             // Add _collection field
             fields.insert("_collection".to_string(), Value::String(self.name.clone()));
 
-            // Create document
-            let doc = Document::new(doc_id.clone(), fields);
-            prepared_docs.push((doc_id.clone(), doc));
+            // Create and serialize document - one oversized (or vetoed) document rejects the batch
+            let mut doc = self.run_before_hooks(HookEvent::BeforeInsert, Document::new(doc_id.clone(), fields))?;
+            computed.apply_document(&mut doc)?;
+            encryptor.encrypt_fields(doc.fields.iter_mut())?;
+            storage.check_references(&self.name, |field| doc.get(field))?;
+            let doc_json = doc.to_json()?;
+            storage.check_document_size(doc_json.len())?;
+            prepared_docs.push((doc_id.clone(), doc, doc_json));
             inserted_ids.push(doc_id);
         }
+        // The whole batch has to fit
+        let bytes = prepared_docs.iter().map(|(_, _, doc_json)| doc_json.len() as u64).sum();
+        storage.check_quota(&self.name, count, bytes)?;
+        storage.claim_ids(&self.name, start_id + count)?;
 
         // Update indexes in batch BEFORE writing to storage
         {
             let mut indexes = self.indexes.write();
             let id_index_name = format!("{}_id", self.name);
 
-            for (doc_id, doc) in &prepared_docs {
+            for (doc_id, doc, _) in &prepared_docs {
                 // Update _id index
                 if let Some(id_index) = indexes.get_btree_index_mut(&id_index_name) {
                     let id_key = match &doc_id {
@@ -304,7 +536,7 @@ impl CollectionCore {
                     if let Some(index) = indexes.get_btree_index_mut(&index_name) {
                         let field = &index.metadata.field;
                         if let Some(field_value) = doc.get(field) {
-                            let index_key = IndexKey::from(field_value);
+                            let index_key = index.key_for(field_value);
                             index.insert(index_key, doc_id.clone())?;
                         }
                     }
@@ -312,30 +544,203 @@ impl CollectionCore {
             }
         }
 
-        // Write all documents to storage
-        for (doc_id, doc) in prepared_docs {
-            let doc_json = doc.to_json()?;
-            storage.write_document(&self.name, &doc_id, doc_json.as_bytes())?;
-        }
+        // Write all documents to storage, as one write batch
+        let mut inserted = Vec::with_capacity(prepared_docs.len());
+        storage.write_batch(|storage| {
+            for (doc_id, doc, doc_json) in prepared_docs {
+                storage.write_document(&self.name, &doc_id, doc_json.as_bytes())?;
+
+                if storage.oplog_enabled() {
+                    storage.log_operation("insert", &self.name, &doc_id, serde_json::from_str(&doc_json)?)?;
+                }
+                inserted.push(Value::from(doc));
+            }
+            Ok(())
+        })?;
+        drop(storage);
 
         // Invalidate query cache (collection has changed)
         self.query_cache.invalidate_collection(&self.name);
+        self.run_after_hooks(HookEvent::AfterInsert, inserted)?;
 
-        Ok(InsertManyResult {
-            inserted_count: inserted_ids.len(),
-            inserted_ids,
-        })
+        Ok(InsertManyResult::new(inserted_ids))
+    }
+
+    /// Insert or replace many documents by key - returns matched and inserted counts
+    ///
+    /// Each document replaces the live document whose `key_fields` hold the
+    /// same values (a missing field matches null; if several do, one of them),
+    /// or is inserted with a new _id if there is none. A later document of the
+    /// batch with the same key replaces the earlier one. Keys are taken from
+    /// the documents as given, before hooks and computed fields.
+    ///
+    /// The collection is scanned once and the indexes are updated in one pass
+    /// before anything is written: a vetoing hook, unique index violation,
+    /// quota or reference error rejects the whole batch.
+    pub fn bulk_upsert(&self, documents: Vec<HashMap<String, Value>>, key_fields: &[&str]) -> Result<BulkUpsertResult> {
+        self.check_writable()?;
+        if key_fields.is_empty() {
+            return Err(MongoLiteError::InvalidQuery("bulk_upsert needs at least one key field".to_string()));
+        }
+        let encryptor = self.encryptor();
+        if let Some(field) = key_fields.iter().find(|field| encryptor.mode(field) == Some(EncryptionMode::Randomized)) {
+            return Err(MongoLiteError::InvalidQuery(format!(
+                "key field '{}' is encrypted in randomized mode and cannot be matched", field
+            )));
+        }
+        let computed = self.computed()?;
+        // Stored documents hold encrypted values, so keys compare encrypted
+        let key_of = |doc: &Value| -> Result<String> {
+            let values: Vec<&Value> = key_fields.iter().map(|field| doc.get(*field).unwrap_or(&Value::Null)).collect();
+            Ok(serde_json::to_string(&values)?)
+        };
+
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        let mut existing = HashMap::new();
+        for (doc_id, doc) in self.scan_catalog_locked(&mut storage, &mut memory)? {
+            existing.insert(key_of(&doc)?, (doc_id, doc));
+        }
+        let mut next_id = storage.get_collection_meta(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?
+            .last_id;
+
+        // (_id, stored version, new version, serialized) - key -> position
+        let mut writes: Vec<(DocumentId, Option<Value>, Value, String)> = Vec::new();
+        let mut planned: HashMap<String, usize> = HashMap::new();
+        for mut fields in documents {
+            let mut probe = Value::Object(fields.clone().into_iter().collect());
+            if let Value::Object(map) = &mut probe {
+                encryptor.encrypt_fields(map.iter_mut())?;
+            }
+            let key = key_of(&probe)?;
+
+            let (doc_id, old) = match planned.get(&key) {
+                Some(&pos) => (writes[pos].0.clone(), writes[pos].1.clone()),
+                None => match existing.remove(&key) {
+                    Some((doc_id, doc)) => (doc_id, Some(doc)),
+                    None => {
+                        let doc_id = DocumentId::new_auto(next_id);
+                        next_id += 1;
+                        (doc_id, None)
+                    }
+                },
+            };
+
+            fields.insert("_id".to_string(), serde_json::to_value(&doc_id)?);
+            fields.insert("_collection".to_string(), Value::String(self.name.clone()));
+            let event = if old.is_some() { HookEvent::BeforeUpdate } else { HookEvent::BeforeInsert };
+            let mut doc = self.run_before_hooks(event, Document::new(doc_id.clone(), fields))?;
+            computed.apply_document(&mut doc)?;
+            encryptor.encrypt_fields(doc.fields.iter_mut())?;
+            storage.check_references(&self.name, |field| doc.get(field))?;
+            let doc_json = doc.to_json()?;
+            storage.check_document_size(doc_json.len())?;
+            let new = Value::from(doc);
+
+            match planned.get(&key) {
+                Some(&pos) => {
+                    writes[pos].2 = new;
+                    writes[pos].3 = doc_json;
+                }
+                None => {
+                    planned.insert(key, writes.len());
+                    writes.push((doc_id, old, new, doc_json));
+                }
+            }
+        }
+
+        let inserts = writes.iter().filter(|(_, old, _, _)| old.is_none());
+        let bytes = inserts.clone().map(|(_, _, _, doc_json)| doc_json.len() as u64).sum();
+        storage.check_quota(&self.name, inserts.count() as u64, bytes)?;
+
+        // One index pass - a unique violation undoes the entries moved so far
+        for (pos, (doc_id, old, new, _)) in writes.iter().enumerate() {
+            if let Err(e) = self.update_index_entries(doc_id, old.as_ref(), Some(new)) {
+                for (doc_id, old, new, _) in writes[..pos].iter().rev() {
+                    self.update_index_entries(doc_id, Some(new), old.as_ref())?;
+                }
+                return Err(e);
+            }
+        }
+
+        // Write; on failure the catalog and indexes go back to where they were
+        let snapshot = storage.get_collection_meta(&self.name).cloned()
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let written = storage.write_batch(|storage| writes.iter()
+            .try_for_each(|(doc_id, _, _, doc_json)| storage.write_document(&self.name, doc_id, doc_json.as_bytes()).map(|_| ())));
+        if let Err(e) = written {
+            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
+                *meta = snapshot;
+            }
+            for (doc_id, old, new, _) in writes.iter().rev() {
+                self.update_index_entries(doc_id, Some(new), old.as_ref())?;
+            }
+            return Err(e);
+        }
+        storage.claim_ids(&self.name, next_id)?;
+
+        let mut result = BulkUpsertResult::default();
+        let (mut inserted, mut replaced) = (Vec::new(), Vec::new());
+        for (doc_id, old, new, _) in writes {
+            let op = if old.is_some() { "update" } else { "insert" };
+            if storage.oplog_enabled() {
+                storage.log_operation(op, &self.name, &doc_id, new.clone())?;
+            }
+            if old.is_some() {
+                result.matched_count += 1;
+                replaced.push(new);
+            } else {
+                result.upserted_ids.push(doc_id);
+                inserted.push(new);
+            }
+        }
+        result.upserted_count = result.upserted_ids.len();
+        drop(storage);
+
+        self.query_cache.invalidate_collection(&self.name);
+        self.run_after_hooks(HookEvent::AfterInsert, inserted)?;
+        self.run_after_hooks(HookEvent::AfterUpdate, replaced)?;
+        Ok(result)
     }
 
     // ========== QUERY OPERATIONS ==========
 
     /// Find documents matching query
+    ///
+    /// Documents answered through an index come in index order; the others
+    /// in natural order, the order of their records in the data file. Both
+    /// are the same from call to call until the collection is written to.
+    /// Natural order starts out as insertion order, but an updated document
+    /// may move, so sort explicitly when the order matters.
     pub fn find(&self, query_json: &Value) -> Result<Vec<Value>> {
+        let result = self.find_inner(query_json);
+        self.record_trace(|| TracedOp::Find { filter: query_json.clone() }, &result);
+        result
+    }
+
+    fn find_inner(&self, query_json: &Value) -> Result<Vec<Value>> {
+        let encryptor = self.encryptor();
+        let query = encryptor.encrypt_query(query_json)?;
+        let options = crate::find_options::FindOptions::default();
+        let (mut memory, _op) = self.operation_tracker("find", &query, &options);
+        let mut docs = self.find_tracked(&query, &mut memory).in_operation("find", &self.name)?;
+        encryptor.decrypt_documents(&mut docs)?;
+        Ok(docs)
+    }
+
+    /// find() charging the documents it holds to `memory`
+    fn find_tracked(&self, query_json: &Value, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
         eprintln!("🔍 DEBUG: find() called with query: {:?}", query_json);
         use std::io::Write;
         let _ = std::io::stderr().flush();
 
-        // Check query cache first
+        // Check query cache first (after dropping results older than the
+        // collection's last write)
+        let collection_lsn = self.storage.read().collection_lsn(&self.name);
+        if self.cache_lsn.swap(collection_lsn, std::sync::atomic::Ordering::SeqCst) != collection_lsn {
+            self.query_cache.invalidate_collection(&self.name);
+        }
         let query_hash = QueryHash::new(&self.name, query_json);
         if let Some(cached_doc_ids) = self.query_cache.get(&query_hash) {
             eprintln!("🔍 DEBUG: Query cache HIT! {} cached doc IDs", cached_doc_ids.len());
@@ -344,6 +749,7 @@ impl CollectionCore {
             let mut results = Vec::with_capacity(cached_doc_ids.len());
             for doc_id in cached_doc_ids {
                 if let Some(doc) = self.read_document_by_id(&doc_id)? {
+                    memory.charge_value(&doc)?;
                     results.push(doc);
                 }
             }
@@ -355,29 +761,22 @@ impl CollectionCore {
 
         // Cache miss - execute query normally
         let parsed_query = Query::from_json(query_json)?;
+        self.sync_indexes()?;
 
         // Try to use an index
-        let indexes = self.indexes.read();
-        let available_indexes = indexes.list_indexes();
-
-        eprintln!("🔍 DEBUG: Available indexes: {:?}", available_indexes);
-        let _ = std::io::stderr().flush();
-
-        let result_docs = if let Some((field, plan)) = QueryPlanner::analyze_query(query_json, &available_indexes) {
+        let result_docs = if let Some((field, plan)) = self.plan_query(query_json) {
             // Use index-based execution
             eprintln!("🔍 DEBUG: Using index for field '{}': {:?}", field, plan);
             let _ = std::io::stderr().flush();
-            drop(indexes);
-            self.find_with_index(parsed_query, plan)?
+            self.find_with_index(parsed_query, plan, memory)?
         } else {
             // Fall back to full collection scan
             eprintln!("🔍 DEBUG: No suitable index - using full scan");
             let _ = std::io::stderr().flush();
-            drop(indexes); // Release read lock before write lock
 
             // OPTIMIZATION: Use catalog iteration instead of full file scan
-            let docs_by_id = self.scan_documents_via_catalog()?;
-            self.filter_documents(docs_by_id, &parsed_query)?
+            let docs_by_id = self.scan_candidates(&parsed_query, memory)?;
+            self.filter_documents(docs_by_id, &parsed_query, memory)?
         };
 
         // Extract DocumentIds from results and cache them
@@ -400,16 +799,93 @@ impl CollectionCore {
     ) -> Result<Vec<Value>> {
         use crate::find_options::{apply_projection, apply_sort, apply_limit_skip};
 
-        // 1. Get matching documents (use existing find() logic)
-        let mut docs = self.find(query_json)?;
-
-        // 2. Apply sort
-        if let Some(ref sort) = options.sort {
-            apply_sort(&mut docs, sort);
+        self.sync_indexes()?;
+        let encryptor = self.encryptor();
+        let query = encryptor.encrypt_query(query_json)?;
+        let query_json: &Value = &query;
+
+        // 1. Get matching documents - straight from the index when it covers
+        //    the query, otherwise use existing find() logic
+        let (mut memory, _op) = self.operation_tracker("find", query_json, &options);
+        let mut sorted = false;
+        let mut windowed = false;
+        let mut docs = match options.read_concern {
+            // Indexes only describe the current state - snapshot reads scan
+            ReadConcern::Snapshot => {
+                let snapshot = self.storage.read().snapshot();
+                self.find_at_tracked(query_json, &snapshot, &mut memory)?
+            }
+            read_concern => {
+                if read_concern == ReadConcern::Linearizable {
+                    self.wait_durable()?;
+                }
+                let covered_plan = options.projection.as_ref()
+                    .and_then(|projection| self.covered_plan(query_json, projection, options.sort.as_deref()));
+                let skip = options.skip.unwrap_or(0);
+                let window = match (&covered_plan, options.sort.as_deref()) {
+                    (None, Some([(field, direction)])) => {
+                        self.find_index_window(query_json, field, *direction != 1, skip, options.limit, &mut memory)?
+                    }
+                    // Without a sort, an index plan already returns index order
+                    (None, None) if options.skip.is_some() || options.limit.is_some() => {
+                        match query_json.as_object().filter(|map| map.len() == 1).and_then(|map| map.keys().next()) {
+                            Some(field) => self.find_index_window(query_json, field, false, skip, options.limit, &mut memory)?,
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let wanted = options.limit.map(|limit| limit + skip);
+                let index_sorted = match (&covered_plan, &window, options.sort.as_deref()) {
+                    (None, None, Some(sort)) => self.find_index_sorted(query_json, sort, wanted, &mut memory)?,
+                    _ => None,
+                };
+                match (covered_plan, window, index_sorted) {
+                    (_, Some(docs), _) => {
+                        sorted = true;
+                        windowed = true;
+                        docs
+                    }
+                    (_, None, Some(docs)) => {
+                        sorted = true;
+                        docs
+                    }
+                    (Some(plan), None, None) => self.find_covered(query_json, &plan, &mut memory)?,
+                    // A full scan decodes only what the filter, sort and projection
+                    // read, and with a sort and limit keeps only the first documents
+                    (None, None, None) => {
+                        let fields = crate::find_options::pushdown_fields(query_json, &options);
+                        let unindexed = self.plan_query(query_json).is_none();
+                        match (options.sort.as_deref(), wanted, fields) {
+                            (Some(sort), Some(wanted), fields) if unindexed => {
+                                sorted = true;
+                                self.scan_top_k(query_json, fields.as_ref(), sort, wanted, &encryptor, &mut memory)?
+                            }
+                            (_, _, Some(fields)) if unindexed => self.scan_fields(query_json, &fields, &mut memory)?,
+                            _ => self.find_tracked(query_json, &mut memory)?,
+                        }
+                    }
+                }
+            }
+        };
+        encryptor.decrypt_documents(&mut docs)?;
+
+        // 2. Apply sort (unless the documents came in index order) - only
+        //    the first skip + limit when there is a limit
+        if let (Some(ref sort), false) = (&options.sort, sorted) {
+            match options.limit {
+                Some(limit) => {
+                    let wanted = limit.saturating_add(options.skip.unwrap_or(0));
+                    docs = crate::top_k::top_k(docs, wanted, &crate::find_options::sort_order(sort));
+                }
+                None => apply_sort(&mut docs, sort),
+            }
         }
 
-        // 3. Apply skip and limit
-        docs = apply_limit_skip(docs, options.limit, options.skip);
+        // 3. Apply skip and limit (unless the index walk already did)
+        if !windowed {
+            docs = apply_limit_skip(docs, options.limit, options.skip);
+        }
 
         // 4. Apply projection
         if let Some(ref projection) = options.projection {
@@ -421,193 +897,536 @@ impl CollectionCore {
         Ok(docs)
     }
 
-    /// Find one document matching query
-    pub fn find_one(&self, query_json: &Value) -> Result<Option<Value>> {
-        let parsed_query = Query::from_json(query_json)?;
+    /// Keyset pagination: one page of matching documents in `sort` order
+    ///
+    /// `_id` is appended as a tie-breaker so the order is total. Pass the
+    /// returned token back to get the next page; each page starts strictly after
+    /// the last key of the previous one, so concurrent inserts don't shift pages.
+    ///
+    /// With an ordered index on the first sort field, a page is read from the
+    /// index starting at the token's key, so later pages cost no more than the
+    /// first (see find_page_indexed). Otherwise every page scans all matching
+    /// documents and keeps those after the token.
+    pub fn find_page(
+        &self,
+        query_json: &Value,
+        sort: &[(String, i32)],
+        page_token: Option<&str>,
+        page_size: usize,
+    ) -> Result<crate::find_options::Page> {
+        use crate::find_options::{Page, PageToken, pagination_sort, pagination_key, compare_pagination_keys};
+
+        if page_size == 0 {
+            return Err(MongoLiteError::InvalidQuery("page_size must be greater than 0".to_string()));
+        }
 
-        // OPTIMIZATION: Check if this is an _id equality query (O(1) lookup)
-        if let Some(query_obj) = query_json.as_object() {
-            if query_obj.len() == 1 && query_obj.contains_key("_id") {
-                if let Some(id_val) = query_obj.get("_id") {
-                    // Direct O(1) lookup using document_catalog (direct DocumentId conversion!)
-                    if let Ok(doc_id) = serde_json::from_value::<DocumentId>(id_val.clone()) {
-                        if let Some(doc) = self.read_document_by_id(&doc_id)? {
-                            // Verify query still matches (for consistency)
-                            let doc_json_str = serde_json::to_string(&doc)?;
-                            let document = Document::from_json(&doc_json_str)?;
-
-                            if parsed_query.matches(&document) {
-                                return Ok(Some(doc));
-                            }
-                        }
-                    }
-                    return Ok(None);
+        let sort = pagination_sort(sort);
+        let after = match page_token {
+            Some(token) => {
+                let token = PageToken::decode(token)?;
+                if token.sort != sort {
+                    return Err(MongoLiteError::InvalidQuery(
+                        "Page token was created with a different sort".to_string()
+                    ));
                 }
+                Some(token.last_key)
             }
+            None => None,
+        };
+
+        let mut keyed = match self.find_page_indexed(query_json, &sort, after.as_deref(), page_size)? {
+            Some(keyed) => keyed,
+            None => self.find(query_json)?
+                .into_iter()
+                .map(|doc| (pagination_key(&doc, &sort), doc))
+                .filter(|(key, _)| match &after {
+                    Some(last) => compare_pagination_keys(key, last, &sort) == std::cmp::Ordering::Greater,
+                    None => true,
+                })
+                .collect(),
+        };
+
+        // Only the first page_size + 1 keys need to be in order
+        let has_more = keyed.len() > page_size;
+        if has_more {
+            keyed.select_nth_unstable_by(page_size, |(a, _), (b, _)| compare_pagination_keys(a, b, &sort));
+            keyed.truncate(page_size);
         }
+        keyed.sort_by(|(a, _), (b, _)| compare_pagination_keys(a, b, &sort));
 
-        // Fallback: Full scan using catalog iteration (still faster than file scan)
-        let docs_by_id = self.scan_documents_via_catalog()?;
+        let next_page_token = if has_more {
+            keyed.last().map(|(key, _)| PageToken { sort: sort.clone(), last_key: key.clone() }.encode())
+        } else {
+            None
+        };
 
-        // Find first matching document (skip tombstones)
-        for (_, doc) in docs_by_id {
-            let doc_json_str = serde_json::to_string(&doc)?;
-            let document = Document::from_json(&doc_json_str)?;
+        Ok(Page {
+            documents: keyed.into_iter().map(|(_, doc)| doc).collect(),
+            next_page_token,
+        })
+    }
 
-            if parsed_query.matches(&document) {
-                return Ok(Some(doc));
+    /// The documents of a find_page() page and the next one, in `sort`
+    /// order, read by walking the index on the first sort field from the
+    /// token's key: the walk stops at the first key after page_size + 1
+    /// matches, so a page costs about page_size reads wherever it starts.
+    /// Entries with equal keys are read together and ordered by the rest of
+    /// the sort. None when find_index_sorted would not use the index either,
+    /// or the sort field is encrypted.
+    fn find_page_indexed(
+        &self,
+        query_json: &Value,
+        sort: &[(String, i32)],
+        after: Option<&[Option<Value>]>,
+        page_size: usize,
+    ) -> Result<Option<crate::find_options::KeyedDocuments>> {
+        use crate::find_options::{pagination_key, compare_pagination_keys};
+        use std::ops::Bound;
+
+        let Some((field, direction)) = sort.first() else {
+            return Ok(None);
+        };
+        self.sync_indexes()?;
+        let encryptor = self.encryptor();
+        if encryptor.mode(field).is_some() {
+            return Ok(None);
+        }
+        let query = encryptor.encrypt_query(query_json)?;
+        let query_json: &Value = &query;
+        let document_count = match self.storage.read().get_collection_meta(&self.name) {
+            Some(meta) => meta.document_catalog.len() as u64,
+            None => return Ok(None),
+        };
+        if self.plan_query(query_json).is_some_and(|(planned, _)| planned != *field) {
+            return Ok(None);
+        }
+
+        // The index and where the walk resumes: the token's key, entries
+        // equal to it included
+        let (index_name, mut from) = {
+            let indexes = self.indexes.read();
+            let available_indexes = indexes.list_indexes();
+            let index = available_indexes.iter()
+                .filter_map(|name| indexes.get_btree_index(name))
+                .find(|index| index.metadata.field == *field && !index.metadata.hashed);
+            let Some(index) = index.filter(|index| index.size() == document_count) else {
+                return Ok(None);
+            };
+            // Null and booleans sort first in the index - one check covers all
+            let first = index.scan_entries(Bound::Unbounded, Bound::Unbounded, false, 0, Some(1))?;
+            if first.first().is_some_and(|(key, _)| matches!(key, IndexKey::Null | IndexKey::Bool(_))) {
+                return Ok(None);
+            }
+            let from = after.and_then(|last| last.first()?.as_ref()).map(|value| index.key_for(value));
+            (index.metadata.name.clone(), from)
+        }; // indexes read lock dropped here
+
+        let reverse = *direction == -1;
+        let by_sort = |(a, _): &(Vec<Option<Value>>, Value), (b, _): &(Vec<Option<Value>>, Value)| {
+            compare_pagination_keys(a, b, sort)
+        };
+        let parsed_query = Query::from_json(query_json)?;
+        let (mut memory, _op) = self.operation_tracker("find", query_json, &crate::find_options::FindOptions::default());
+
+        let mut page = Vec::new();
+        // Matches of the key being read, and the documents its entries named
+        let mut group = Vec::new();
+        let mut group_key: Option<IndexKey> = None;
+        let mut group_ids: HashSet<DocumentId> = HashSet::new();
+        let mut batch = page_size.saturating_add(1);
+        loop {
+            let entries = {
+                let indexes = self.indexes.read();
+                let Some(index) = indexes.get_btree_index(&index_name) else {
+                    return Ok(None);
+                };
+                let bound = from.as_ref().map_or(Bound::Unbounded, Bound::Included);
+                let (start, end) = if reverse { (Bound::Unbounded, bound) } else { (bound, Bound::Unbounded) };
+                index.scan_entries(start, end, reverse, 0, Some(batch))?
+            };
+            let exhausted = entries.len() < batch;
+            let one_key = entries.first().map(|(key, _)| key) == entries.last().map(|(key, _)| key);
+
+            for (index_key, doc_id) in entries {
+                memory.check_interrupt()?;
+                if group_key.as_ref() != Some(&index_key) {
+                    // Every entry of the previous key is in
+                    group.sort_by(by_sort);
+                    page.append(&mut group);
+                    if page.len() > page_size {
+                        return Ok(Some(page));
+                    }
+                    group_key = Some(index_key);
+                    group_ids.clear();
+                }
+                // Entries of the last key are read again by the next batch
+                if !group_ids.insert(doc_id.clone()) {
+                    continue;
+                }
+                let Some(mut doc) = self.read_document_by_id(&doc_id)? else {
+                    continue;
+                };
+                let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+                if !parsed_query.matches(&document) {
+                    continue;
+                }
+                encryptor.decrypt_document(&mut doc)?;
+                let key = pagination_key(&doc, sort);
+                if after.is_some_and(|last| compare_pagination_keys(&key, last, sort) != std::cmp::Ordering::Greater) {
+                    continue;
+                }
+                memory.charge_value(&doc)?;
+                group.push((key, doc));
+            }
+
+            if exhausted {
+                group.sort_by(by_sort);
+                page.append(&mut group);
+                return Ok(Some(page));
+            }
+            // The last key may have more entries than the batch held
+            from = group_key.clone();
+            if one_key {
+                batch = batch.saturating_mul(2);
             }
         }
+    }
 
-        Ok(None)
+    /// Find one document matching query
+    pub fn find_one(&self, query_json: &Value) -> Result<Option<Value>> {
+        let result = self.find_one_inner(query_json);
+        self.record_trace(|| TracedOp::FindOne { filter: query_json.clone() }, &result);
+        result
     }
 
-    /// Count documents matching query
-    pub fn count_documents(&self, query_json: &Value) -> Result<u64> {
+    fn find_one_inner(&self, query_json: &Value) -> Result<Option<Value>> {
+        let encryptor = self.encryptor();
+        let mut doc = self.find_one_stored(&*encryptor.encrypt_query(query_json)?).in_operation("find_one", &self.name)?;
+        if let Some(doc) = &mut doc {
+            encryptor.decrypt_document(doc)?;
+        }
+        Ok(doc)
+    }
+
+    /// find_one() with the document as stored (encrypted fields encrypted)
+    fn find_one_stored(&self, query_json: &Value) -> Result<Option<Value>> {
         let parsed_query = Query::from_json(query_json)?;
 
-        // OPTIMIZATION: Use catalog iteration instead of full file scan
-        let docs_by_id = self.scan_documents_via_catalog()?;
+        // An _id equality reads just that document (see id_equality), anything
+        // else scans the catalog
+        let docs_by_id = {
+            let mut memory = self.memory_tracker(None);
+            let mut storage = self.storage.write();
+            self.targets_locked(&mut storage, query_json, &parsed_query, &mut memory)?
+        };
 
-        // Count matching documents (skip tombstones already filtered by catalog scan)
-        let mut count = 0u64;
+        // Find first matching document (skip tombstones)
         for (_, doc) in docs_by_id {
             let doc_json_str = serde_json::to_string(&doc)?;
             let document = Document::from_json(&doc_json_str)?;
 
             if parsed_query.matches(&document) {
-                count += 1;
+                return Ok(Some(doc));
             }
         }
 
-        Ok(count)
+        Ok(None)
+    }
+
+    /// Find documents matching query as of a snapshot
+    ///
+    /// Sees the collection exactly as it was when the snapshot was taken;
+    /// later writes (including deletes) are invisible. Always a full scan -
+    /// indexes and the query cache only describe the current state. Reads
+    /// through a SnapshotScan, so writers are not held up for all of it.
+    pub fn find_at(&self, query_json: &Value, snapshot: &Snapshot) -> Result<Vec<Value>> {
+        let encryptor = self.encryptor();
+        let query = encryptor.encrypt_query(query_json)?;
+        let mut docs = self.find_at_tracked(&query, snapshot, &mut self.memory_tracker(None))?;
+        encryptor.decrypt_documents(&mut docs)?;
+        Ok(docs)
     }
 
-    /// Update one document - returns (matched_count, modified_count)
-    pub fn update_one(&self, query_json: &Value, update_json: &Value) -> Result<(u64, u64)> {
+    fn find_at_tracked(&self, query_json: &Value, snapshot: &Snapshot, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
         let parsed_query = Query::from_json(query_json)?;
 
-        // OPTIMIZATION: Check if this is an _id equality query (O(1) lookup)
-        let docs_by_id = if let Some(query_obj) = query_json.as_object() {
-            if query_obj.len() == 1 && query_obj.contains_key("_id") {
-                if let Some(id_val) = query_obj.get("_id") {
-                    // Direct O(1) lookup using document_catalog (direct DocumentId conversion!)
-                    if let Ok(doc_id) = serde_json::from_value::<DocumentId>(id_val.clone()) {
-                        if let Some(doc) = self.read_document_by_id(&doc_id)? {
-                            let mut single_doc_map = HashMap::new();
-                            single_doc_map.insert(doc_id, doc);
-                            single_doc_map
-                        } else {
-                            HashMap::new()
-                        }
-                    } else {
-                        HashMap::new()
-                    }
-                } else {
-                    self.scan_documents_via_catalog()?
+        let mut scan = self.snapshot_scan_at(snapshot)?;
+        let mut results = Vec::new();
+        while let Some(batch) = scan.next_batch()? {
+            for (_, data) in batch {
+                memory.check_interrupt()?;
+                let doc: Value = serde_json::from_slice(&data)?;
+                let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+                if parsed_query.matches(&document) {
+                    memory.charge_value(&doc)?;
+                    results.push(doc);
                 }
-            } else {
-                // Fallback: Full scan using catalog iteration
-                self.scan_documents_via_catalog()?
             }
-        } else {
-            self.scan_documents_via_catalog()?
-        };
+        }
+        Ok(results)
+    }
 
-        // Find first matching and update (skip tombstones already filtered by catalog scan)
-        let mut matched = 0u64;
-        let mut modified = 0u64;
-        let mut storage = self.storage.write();
+    /// Iterate over the collection as of a new snapshot, a batch of
+    /// documents per storage lock (see snapshot_scan.rs)
+    pub fn snapshot_scan(&self) -> Result<SnapshotScan> {
+        let snapshot = self.storage.read().snapshot();
+        SnapshotScan::new(Arc::clone(&self.storage), &self.name, snapshot)
+    }
 
-        for (_, doc) in docs_by_id {
-            if matched > 0 {
-                break; // Only update first match
-            }
+    /// Documents matching a query, read as they are iterated from a snapshot
+    /// scan - what a server-side cursor pulls its batches from (see cursor.rs)
+    pub fn find_iter(&self, query_json: &Value) -> Result<impl Iterator<Item = Result<Value>> + Send + 'static> {
+        let parsed_query = Query::from_json(query_json)?;
+        let scan = self.snapshot_scan()?;
+        Ok(scan.filter_map(move |doc| {
+            let matched = doc.and_then(|doc| {
+                let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+                Ok(parsed_query.matches(&document).then_some(doc))
+            });
+            matched.transpose()
+        }))
+    }
 
-            let doc_json_str = serde_json::to_string(&doc)?;
-            let mut document = Document::from_json(&doc_json_str)?;
+    /// Iterate over the collection as of `snapshot` (see snapshot_scan())
+    pub fn snapshot_scan_at(&self, snapshot: &Snapshot) -> Result<SnapshotScan> {
+        SnapshotScan::new(Arc::clone(&self.storage), &self.name, snapshot.clone())
+    }
 
-            // Check if matches query
-            if parsed_query.matches(&document) {
-                matched = 1;
+    /// A document by _id as of a snapshot
+    pub fn find_by_id_at(&self, id: &DocumentId, snapshot: &Snapshot) -> Result<Option<Value>> {
+        let encryptor = self.encryptor();
+        let data = self.storage.write().read_version_at(&self.name, id, snapshot.lsn())?;
+        let mut doc: Option<Value> = data.map(|data| serde_json::from_slice(&data)).transpose()?;
+        if let Some(doc) = &mut doc {
+            encryptor.decrypt_document(doc)?;
+        }
+        Ok(doc)
+    }
 
-                // Apply update operators
-                let was_modified = self.apply_update_operators(&mut document, update_json)?;
+    /// Fetch many documents by _id under a single lock acquisition
+    /// Results are in input order; missing or deleted ids yield None
+    pub fn find_by_ids(&self, ids: &[DocumentId]) -> Result<Vec<Option<Value>>> {
+        let encryptor = self.encryptor();
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
 
-                if was_modified {
-                    // Mark old document as tombstone
-                    let mut tombstone = doc.clone();
-                    if let Value::Object(ref mut map) = tombstone {
-                        map.insert("_tombstone".to_string(), Value::Bool(true));
-                        map.insert("_collection".to_string(), Value::String(self.name.clone()));
-                    }
-                    let tombstone_json = serde_json::to_string(&tombstone)?;
+        // (input position, offset) - read in file order for locality
+        let mut lookups: Vec<(usize, u64)> = ids.iter()
+            .enumerate()
+            .filter_map(|(pos, id)| meta.document_catalog.get(id).map(|&offset| (pos, offset)))
+            .collect();
+        lookups.sort_unstable_by_key(|&(_, offset)| offset);
 
-                    // Write tombstone (no catalog tracking for tombstones)
-                    storage.write_data(tombstone_json.as_bytes())?;
+        let mut results: Vec<Option<Value>> = vec![None; ids.len()];
+        for (pos, offset) in lookups {
+            let doc_bytes = storage.read_data(offset).for_document(&self.name, &ids[pos])?;
+            let mut doc: Value = serde_json::from_slice(&doc_bytes).for_document(&self.name, &ids[pos]).at_offset(offset)?;
 
-                    // ✅ Ensure updated document has _collection
-                    document.set("_collection".to_string(), Value::String(self.name.clone()));
+            if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
+            }
 
-                    // Write updated document WITH catalog tracking
-                    let updated_json = document.to_json()?;
-                    storage.write_document(&self.name, &document.id, updated_json.as_bytes())?;
+            encryptor.decrypt_document(&mut doc)?;
+            results[pos] = Some(doc);
+        }
 
-                    modified = 1;
+        Ok(results)
+    }
+
+    /// find() with referenced documents filled in (see find_options::Populate)
+    ///
+    /// Each spec is resolved with one find_by_ids() on its collection for all
+    /// matched documents. A single _id becomes the referenced document (null
+    /// if there is none), an array of _ids an array of the documents found;
+    /// a missing field stays missing.
+    pub fn find_with_populate(&self, query_json: &Value, populate: &[Populate]) -> Result<Vec<Value>> {
+        let mut docs = self.find(query_json)?;
+        for spec in populate {
+            let from = {
+                let storage = self.storage.read();
+                match storage.get_collection_meta(&spec.from) {
+                    None => return Err(MongoLiteError::CollectionNotFound(spec.from.clone())),
+                    Some(meta) if meta.view.as_ref().is_some_and(|view| view.materialized.is_none()) => {
+                        return Err(MongoLiteError::InvalidQuery(format!(
+                            "'{}' is a view - only collections can be populated from", spec.from
+                        )));
+                    }
+                    Some(_) => {}
+                }
+                drop(storage);
+                CollectionCore::new(spec.from.clone(), Arc::clone(&self.storage))?
+            };
+
+            // Every distinct _id once; a string may also be an ObjectId
+            let mut ids = Vec::new();
+            for doc in &docs {
+                let values = match doc.get(&spec.field) {
+                    Some(Value::Array(values)) => values.iter().collect(),
+                    Some(value) => vec![value],
+                    None => Vec::new(),
+                };
+                for id in values.into_iter().filter_map(|value| serde_json::from_value::<DocumentId>(value.clone()).ok()) {
+                    if let DocumentId::String(s) = &id {
+                        ids.push(DocumentId::ObjectId(s.clone()));
+                    }
+                    ids.push(id);
+                }
+            }
+            ids.sort();
+            ids.dedup();
+
+            let found: HashMap<String, Value> = from.find_by_ids(&ids)?.into_iter()
+                .flatten()
+                .filter_map(|doc| Some((doc.get("_id")?.to_string(), doc)))
+                .collect();
+            let resolve = |id: &Value| found.get(&id.to_string()).cloned();
+            for doc in &mut docs {
+                let Some(value) = doc.get(&spec.field) else {
+                    continue;
+                };
+                let populated = match value {
+                    Value::Array(values) => Value::Array(values.iter().filter_map(resolve).collect()),
+                    value => resolve(value).unwrap_or(Value::Null),
+                };
+                if let Value::Object(map) = doc {
+                    map.insert(spec.target().to_string(), populated);
                 }
             }
         }
+        Ok(docs)
+    }
 
-        // Invalidate query cache if any document was modified
-        if modified > 0 {
-            self.query_cache.invalidate_collection(&self.name);
+    /// Count documents matching query
+    pub fn count_documents(&self, query_json: &Value) -> Result<u64> {
+        let result = self.count_documents_inner(query_json);
+        self.record_trace(|| TracedOp::CountDocuments { filter: query_json.clone() }, &result);
+        result
+    }
+
+    fn count_documents_inner(&self, query_json: &Value) -> Result<u64> {
+        let parsed_query = Query::from_json(&*self.encryptor().encrypt_query(query_json)?)?;
+
+        // OPTIMIZATION: Use catalog iteration instead of full file scan
+        let docs_by_id = self.scan_candidates(&parsed_query, &mut self.memory_tracker(None))
+            .in_operation("count_documents", &self.name)?;
+
+        // Count matching documents (skip tombstones already filtered by catalog scan)
+        let mut count = 0u64;
+        for (_, doc) in docs_by_id {
+            let doc_json_str = serde_json::to_string(&doc)?;
+            let document = Document::from_json(&doc_json_str)?;
+
+            if parsed_query.matches(&document) {
+                count += 1;
+            }
         }
 
-        Ok((matched, modified))
+        Ok(count)
+    }
+
+    /// Update one document
+    pub fn update_one(&self, query_json: &Value, update_json: &Value) -> Result<UpdateResult> {
+        let result = self.update_one_inner(query_json, update_json).in_operation("update_one", &self.name);
+        self.record_trace(|| TracedOp::UpdateOne { filter: query_json.clone(), update: update_json.clone() }, &result);
+        result
     }
 
-    /// Update many documents - returns (matched_count, modified_count)
-    pub fn update_many(&self, query_json: &Value, update_json: &Value) -> Result<(u64, u64)> {
+    fn update_one_inner(&self, query_json: &Value, update_json: &Value) -> Result<UpdateResult> {
+        self.check_writable()?;
+        let encryptor = self.encryptor();
+        let computed = self.computed()?;
+        let (query, update) = (encryptor.encrypt_query(query_json)?, encryptor.encrypt_update(update_json)?);
+        let (query_json, update_json): (&Value, &Value) = (&query, &update);
         let parsed_query = Query::from_json(query_json)?;
 
+        // An _id equality reads just that document (see id_equality)
+        let mut memory = self.memory_tracker(None);
         let mut storage = self.storage.write();
-        let meta = storage.get_collection_meta(&self.name)
-            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let docs_by_id = self.targets_locked(&mut storage, query_json, &parsed_query, &mut memory)?;
 
-        let file_len = storage.file_len()?;
+        // Find first matching and update (skip tombstones already filtered by catalog scan)
+        let mut matched = 0u64;
+        let mut modified = 0u64;
+        let mut updated = Vec::new();
 
-        // First pass: collect all documents by _id (latest version only)
-        let mut docs_by_id: HashMap<String, Value> = HashMap::new();
-        let mut current_offset = meta.data_offset;
+        for (_, doc) in docs_by_id {
+            if matched > 0 {
+                break; // Only update first match
+            }
 
-        while current_offset < file_len {
-            match storage.read_data(current_offset) {
-                Ok(doc_bytes) => {
-                    let doc: Value = serde_json::from_slice(&doc_bytes)?;
+            let doc_json_str = serde_json::to_string(&doc)?;
+            let mut document = Document::from_json(&doc_json_str)?;
 
-                    // ✅ FILTER: Only include documents from THIS collection
-                    let doc_collection = doc.get("_collection")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
+            // Check if matches query
+            if parsed_query.matches(&document) {
+                matched = 1;
 
-                    if doc_collection == self.name {
-                        // Track latest version (include tombstones so they overwrite originals)
-                        if let Some(id_value) = doc.get("_id") {
-                            let id_key = serde_json::to_string(id_value)
-                                .unwrap_or_else(|_| "unknown".to_string());
-                            docs_by_id.insert(id_key, doc);
-                        }
+                // Apply update operators
+                let was_modified = self.apply_update_operators(&mut document, update_json)?;
+
+                if was_modified {
+                    // ✅ Ensure updated document has _collection
+                    document.set("_collection".to_string(), Value::String(self.name.clone()));
+                    let mut document = self.run_before_hooks(HookEvent::BeforeUpdate, document)?;
+                    computed.apply_document(&mut document)?;
+                    storage.check_references(&self.name, |field| document.get(field))?;
+                    let updated_json = document.to_json()?;
+                    storage.check_document_size(updated_json.len())?;
+
+                    // Move index entries first - a unique violation aborts before anything is written
+                    let updated_doc: Value = serde_json::from_str(&updated_json)?;
+                    self.update_index_entries(&document.id, Some(&doc), Some(&updated_doc))?;
+
+                    // Write updated document WITH catalog tracking - it supersedes the
+                    // old version (no tombstone: the new version may reuse a free
+                    // region, and an appended tombstone would then come after it)
+                    storage.write_document(&self.name, &document.id, updated_json.as_bytes())?;
+
+                    if storage.oplog_enabled() {
+                        storage.log_operation("update", &self.name, &document.id, update_json.clone())?;
                     }
 
-                    current_offset += 4 + doc_bytes.len() as u64;
+                    modified = 1;
+                    updated.push(updated_doc);
                 }
-                Err(_) => break,
             }
         }
+        drop(storage);
+
+        // Invalidate query cache if any document was modified
+        if modified > 0 {
+            self.query_cache.invalidate_collection(&self.name);
+        }
+        self.run_after_hooks(HookEvent::AfterUpdate, updated)?;
+
+        Ok(UpdateResult::new(matched, modified))
+    }
+
+    /// Update many documents
+    pub fn update_many(&self, query_json: &Value, update_json: &Value) -> Result<UpdateResult> {
+        let result = self.update_many_inner(query_json, update_json).in_operation("update_many", &self.name);
+        self.record_trace(|| TracedOp::UpdateMany { filter: query_json.clone(), update: update_json.clone() }, &result);
+        result
+    }
+
+    fn update_many_inner(&self, query_json: &Value, update_json: &Value) -> Result<UpdateResult> {
+        self.check_writable()?;
+        let encryptor = self.encryptor();
+        let computed = self.computed()?;
+        let (query, update) = (encryptor.encrypt_query(query_json)?, encryptor.encrypt_update(update_json)?);
+        let (query_json, update_json): (&Value, &Value) = (&query, &update);
+        let parsed_query = Query::from_json(query_json)?;
+
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        // An _id equality reads just that document (see id_equality)
+        let docs_by_id = self.targets_locked(&mut storage, query_json, &parsed_query, &mut memory)?;
 
         // Second pass: find all matching and update (skip tombstones)
         let mut matched = 0u64;
         let mut modified = 0u64;
+        let mut updated = Vec::new();
 
         for (_, doc) in docs_by_id {
             // Skip tombstones (deleted documents)
@@ -626,71 +1445,179 @@ impl CollectionCore {
                 let was_modified = self.apply_update_operators(&mut document, update_json)?;
 
                 if was_modified {
-                    // Mark old document as tombstone
-                    let mut tombstone = doc.clone();
-                    if let Value::Object(ref mut map) = tombstone {
-                        map.insert("_tombstone".to_string(), Value::Bool(true));
-                        map.insert("_collection".to_string(), Value::String(self.name.clone()));
-                    }
-                    let tombstone_json = serde_json::to_string(&tombstone)?;
-
-                    // Write tombstone (no catalog tracking for tombstones)
-                    storage.write_data(tombstone_json.as_bytes())?;
-
                     // ✅ Ensure updated document has _collection
                     document.set("_collection".to_string(), Value::String(self.name.clone()));
-
-                    // Write updated document WITH catalog tracking
+                    let mut document = self.run_before_hooks(HookEvent::BeforeUpdate, document)?;
+                    computed.apply_document(&mut document)?;
+                    storage.check_references(&self.name, |field| document.get(field))?;
                     let updated_json = document.to_json()?;
+                    storage.check_document_size(updated_json.len())?;
+
+                    // Move index entries first - a unique violation aborts before anything is written
+                    let updated_doc: Value = serde_json::from_str(&updated_json)?;
+                    self.update_index_entries(&document.id, Some(&doc), Some(&updated_doc))?;
+
+                    // Write updated document WITH catalog tracking - it supersedes the
+                    // old version (no tombstone: the new version may reuse a free
+                    // region, and an appended tombstone would then come after it)
                     storage.write_document(&self.name, &document.id, updated_json.as_bytes())?;
 
+                    if storage.oplog_enabled() {
+                        storage.log_operation("update", &self.name, &document.id, update_json.clone())?;
+                    }
+
                     modified += 1;
+                    updated.push(updated_doc);
                 }
             }
         }
+        drop(storage);
 
         // Invalidate query cache if any document was modified
         if modified > 0 {
             self.query_cache.invalidate_collection(&self.name);
         }
+        self.run_after_hooks(HookEvent::AfterUpdate, updated)?;
 
-        Ok((matched, modified))
+        Ok(UpdateResult::new(matched, modified))
     }
 
-    /// Delete one document - returns deleted_count
-    pub fn delete_one(&self, query_json: &Value) -> Result<u64> {
-        let parsed_query = Query::from_json(query_json)?;
+    /// Replace the first document matching `query_json` with `replacement`
+    ///
+    /// Find and write happen under one storage lock, so no other writer can
+    /// change the document in between. The document keeps its _id (a
+    /// different _id in `replacement` is an error). Returns the document as
+    /// it was before or after the replacement, or None if nothing matched.
+    pub fn find_one_and_replace(
+        &self,
+        query_json: &Value,
+        replacement: &Value,
+        return_document: ReturnDocument,
+    ) -> Result<Option<Value>> {
+        self.check_writable()?;
+        let encryptor = self.encryptor();
+        let computed = self.computed()?;
+        let query = encryptor.encrypt_query(query_json)?;
+        let parsed_query = Query::from_json(&query)?;
+        let fields = replacement.as_object()
+            .ok_or_else(|| MongoLiteError::InvalidQuery("replacement must be a document".to_string()))?;
+        if fields.keys().any(|key| key.starts_with('$')) {
+            return Err(MongoLiteError::InvalidQuery(
+                "replacement must not contain update operators".to_string()
+            ));
+        }
 
-        // OPTIMIZATION: Check if this is an _id equality query (O(1) lookup)
-        let docs_by_id = if let Some(query_obj) = query_json.as_object() {
-            if query_obj.len() == 1 && query_obj.contains_key("_id") {
-                if let Some(id_val) = query_obj.get("_id") {
-                    // Direct O(1) lookup using document_catalog (direct DocumentId conversion!)
-                    if let Ok(doc_id) = serde_json::from_value::<DocumentId>(id_val.clone()) {
-                        if let Some(doc) = self.read_document_by_id(&doc_id)? {
-                            let mut single_doc_map = HashMap::new();
-                            single_doc_map.insert(doc_id, doc);
-                            single_doc_map
-                        } else {
-                            HashMap::new()
-                        }
-                    } else {
-                        HashMap::new()
-                    }
-                } else {
-                    self.scan_documents_via_catalog()?
-                }
-            } else {
-                // Fallback: Full scan using catalog iteration
-                self.scan_documents_via_catalog()?
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        let Some((doc_id, current)) = self.first_match_locked(&mut storage, &query, &parsed_query, &mut memory)? else {
+            return Ok(None);
+        };
+
+        let id_value = serde_json::to_value(&doc_id)?;
+        if fields.get("_id").is_some_and(|id| *id != id_value) {
+            return Err(MongoLiteError::InvalidQuery(
+                "find_one_and_replace cannot change a document's _id".to_string()
+            ));
+        }
+
+        let mut replaced = fields.clone();
+        replaced.insert("_id".to_string(), id_value);
+        replaced.insert("_collection".to_string(), Value::String(self.name.clone()));
+        let mut replaced = Value::Object(replaced);
+        self.hooks.run(&self.name, HookEvent::BeforeUpdate, &mut replaced)?;
+        computed.apply_value(&mut replaced)?;
+        if let Value::Object(map) = &mut replaced {
+            encryptor.encrypt_fields(map.iter_mut())?;
+        }
+        storage.check_references(&self.name, |field| replaced.get(field))?;
+
+        self.write_version_locked(&mut storage, &doc_id, &current, &replaced, replaced.clone())?;
+        drop(storage);
+        self.run_after_hooks(HookEvent::AfterUpdate, [replaced.clone()])?;
+
+        let mut returned = match return_document {
+            ReturnDocument::Before => current,
+            ReturnDocument::After => replaced,
+        };
+        encryptor.decrypt_document(&mut returned)?;
+        Ok(Some(returned))
+    }
+
+    /// Atomically add `delta` to a numeric field of the first document
+    /// matching `query_json`; returns the new value, or None if nothing matched
+    ///
+    /// A missing field counts as 0. Integers stay integers when `delta` is an
+    /// integer too; a non-numeric field or delta is an error.
+    pub fn increment(&self, query_json: &Value, field: &str, delta: &Value) -> Result<Option<Value>> {
+        self.check_writable()?;
+        let encryptor = self.encryptor();
+        let query = encryptor.encrypt_query(query_json)?;
+        let parsed_query = Query::from_json(&query)?;
+        let computed = self.computed()?;
+        if field == "_id" || field == "_collection" || encryptor.mode(field).is_some() || computed.contains(field) {
+            return Err(MongoLiteError::InvalidQuery(format!("cannot increment '{}'", field)));
+        }
+        if !delta.is_number() {
+            return Err(MongoLiteError::InvalidQuery(format!("increment delta must be a number, got {}", delta)));
+        }
+
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        let Some((doc_id, current)) = self.first_match_locked(&mut storage, &query, &parsed_query, &mut memory)? else {
+            return Ok(None);
+        };
+
+        let new_value = match current.get(field) {
+            None | Some(Value::Null) => delta.clone(),
+            Some(Value::Number(value)) => {
+                let Value::Number(delta) = delta else { unreachable!() };
+                numeric::add(value, delta).map(Value::Number)
+                    .ok_or_else(|| MongoLiteError::InvalidQuery(format!("increment of '{}' overflows", field)))?
             }
-        } else {
-            self.scan_documents_via_catalog()?
+            Some(_) => return Err(MongoLiteError::InvalidQuery(format!(
+                "cannot increment non-numeric field '{}'", field
+            ))),
         };
 
+        let mut updated = current.clone();
+        if let Value::Object(ref mut map) = updated {
+            map.insert(field.to_string(), new_value);
+        }
+        self.hooks.run(&self.name, HookEvent::BeforeUpdate, &mut updated)?;
+        computed.apply_value(&mut updated)?;
+        storage.check_references(&self.name, |field| updated.get(field))?;
+        let new_value = updated.get(field).cloned().unwrap_or(Value::Null);
+
+        // The oplog records the resulting value, so replaying it is idempotent
+        let delta_op = serde_json::json!({"$set": {field: new_value.clone()}});
+        self.write_version_locked(&mut storage, &doc_id, &current, &updated, delta_op)?;
+        drop(storage);
+        self.run_after_hooks(HookEvent::AfterUpdate, [updated])?;
+
+        Ok(Some(new_value))
+    }
+
+    /// Delete one document
+    pub fn delete_one(&self, query_json: &Value) -> Result<DeleteResult> {
+        let result = self.delete_one_inner(query_json).in_operation("delete_one", &self.name);
+        self.record_trace(|| TracedOp::DeleteOne { filter: query_json.clone() }, &result);
+        result
+    }
+
+    fn delete_one_inner(&self, query_json: &Value) -> Result<DeleteResult> {
+        self.check_writable()?;
+        let query = self.encryptor().encrypt_query(query_json)?;
+        let query_json: &Value = &query;
+        let parsed_query = Query::from_json(query_json)?;
+
+        let mut memory = self.memory_tracker(None);
+        let mut storage = self.storage.write();
+        let docs_by_id = self.targets_locked(&mut storage, query_json, &parsed_query, &mut memory)?;
+
         // Find first matching and delete (skip tombstones already filtered by catalog scan)
         let mut deleted = 0u64;
-        let mut storage = self.storage.write();
+        let mut removed = Vec::new();
+        let mut cascades = Vec::new();
 
         for (_, doc) in docs_by_id {
             if deleted > 0 {
@@ -702,6 +1629,11 @@ impl CollectionCore {
 
             // Check if matches query
             if parsed_query.matches(&document) {
+                // Before-hooks and restricting references may veto;
+                // changes to a deleted document are moot
+                self.hooks.run(&self.name, HookEvent::BeforeDelete, &mut doc.clone())?;
+                cascades = self.check_referrers_locked(&mut storage, std::slice::from_ref(&doc))?;
+
                 // Mark as tombstone (logical delete)
                 let mut tombstone = doc.clone();
                 if let Value::Object(ref mut map) = tombstone {
@@ -712,61 +1644,90 @@ impl CollectionCore {
 
                 // Write tombstone WITH catalog tracking (updates catalog entry)
                 storage.write_document(&self.name, &document.id, tombstone_json.as_bytes())?;
+                self.update_index_entries(&document.id, Some(&doc), None)?;
+
+                if storage.oplog_enabled() {
+                    storage.log_operation("delete", &self.name, &document.id, Value::Null)?;
+                }
 
                 deleted = 1;
+                removed.push(doc);
             }
         }
+        drop(storage);
 
         // Invalidate query cache if any document was deleted
         if deleted > 0 {
             self.query_cache.invalidate_collection(&self.name);
         }
+        self.run_cascades(cascades)?;
+        self.run_after_hooks(HookEvent::AfterDelete, removed)?;
 
-        Ok(deleted)
+        Ok(DeleteResult::new(deleted))
     }
 
-    /// Delete many documents - returns deleted_count
-    pub fn delete_many(&self, query_json: &Value) -> Result<u64> {
-        let parsed_query = Query::from_json(query_json)?;
+    /// Delete many documents
+    pub fn delete_many(&self, query_json: &Value) -> Result<DeleteResult> {
+        let result = self.delete_matching(query_json).in_operation("delete_many", &self.name)
+            .map(|deleted| DeleteResult::new(deleted.len() as u64));
+        self.record_trace(|| TracedOp::DeleteMany { filter: query_json.clone() }, &result);
+        result
+    }
+
+    /// delete_many() that returns the deleted documents, so they can be
+    /// archived without a separate read racing with the delete
+    pub fn find_and_delete_many(&self, query_json: &Value) -> Result<Vec<Value>> {
+        let encryptor = self.encryptor();
+        let mut removed = self.delete_matching(query_json)?;
+        for doc in &mut removed {
+            encryptor.decrypt_document(doc)?;
+        }
+        Ok(removed)
+    }
 
+    /// Delete documents by _id through the catalog (no scan)
+    /// Ids that do not exist or are listed twice are skipped
+    pub fn delete_many_by_ids(&self, ids: &[DocumentId]) -> Result<DeleteResult> {
+        self.check_writable()?;
         let mut storage = self.storage.write();
         let meta = storage.get_collection_meta(&self.name)
             .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
 
-        let file_len = storage.file_len()?;
-
-        // First pass: collect all documents by _id (latest version only)
-        let mut docs_by_id: HashMap<String, Value> = HashMap::new();
-        let mut current_offset = meta.data_offset;
-
-        while current_offset < file_len {
-            match storage.read_data(current_offset) {
-                Ok(doc_bytes) => {
-                    let doc: Value = serde_json::from_slice(&doc_bytes)?;
-
-                    // ✅ FILTER: Only include documents from THIS collection
-                    let doc_collection = doc.get("_collection")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-
-                    if doc_collection == self.name {
-                        // Track latest version (include tombstones so they overwrite originals)
-                        if let Some(id_value) = doc.get("_id") {
-                            let id_key = serde_json::to_string(id_value)
-                                .unwrap_or_else(|_| "unknown".to_string());
-                            docs_by_id.insert(id_key, doc);
-                        }
-                    }
+        let mut seen = std::collections::HashSet::new();
+        let lookups: Vec<(DocumentId, u64)> = ids.iter()
+            .filter(|id| seen.insert(*id))
+            .filter_map(|id| meta.document_catalog.get(id).map(|&offset| (id.clone(), offset)))
+            .collect();
 
-                    current_offset += 4 + doc_bytes.len() as u64;
-                }
-                Err(_) => break,
+        let mut matched = Vec::with_capacity(lookups.len());
+        for (doc_id, offset) in lookups {
+            let doc_bytes = storage.read_data(offset).for_document(&self.name, &doc_id)?;
+            let doc: Value = serde_json::from_slice(&doc_bytes).for_document(&self.name, &doc_id).at_offset(offset)?;
+            if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
             }
+            self.hooks.run(&self.name, HookEvent::BeforeDelete, &mut doc.clone())?;
+            matched.push((doc_id, doc));
         }
 
-        // Second pass: find all matching and delete (skip tombstones)
-        let mut deleted = 0u64;
+        Ok(DeleteResult::new(self.delete_documents_locked(storage, matched)?.len() as u64))
+    }
+
+    /// Delete the documents matching the query; returns them as stored
+    fn delete_matching(&self, query_json: &Value) -> Result<Vec<Value>> {
+        self.check_writable()?;
+        let query = self.encryptor().encrypt_query(query_json)?;
+        let query_json: &Value = &query;
+        let parsed_query = Query::from_json(query_json)?;
+
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        // An _id equality reads just that document (see id_equality)
+        let docs_by_id = self.targets_locked(&mut storage, query_json, &parsed_query, &mut memory)?;
 
+        // Second pass: find all matching (skip tombstones). Before-hooks and
+        // restricting references may veto; changes to a deleted document are moot
+        let mut matched = Vec::new();
         for (_, doc) in docs_by_id {
             // Skip tombstones (already deleted documents)
             if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
@@ -778,41 +1739,73 @@ impl CollectionCore {
 
             // Check if matches query
             if parsed_query.matches(&document) {
-                // Mark as tombstone (logical delete)
-                let mut tombstone = doc.clone();
-                if let Value::Object(ref mut map) = tombstone {
-                    map.insert("_tombstone".to_string(), Value::Bool(true));
-                    map.insert("_collection".to_string(), Value::String(self.name.clone()));
-                }
-                let tombstone_json = serde_json::to_string(&tombstone)?;
+                self.hooks.run(&self.name, HookEvent::BeforeDelete, &mut doc.clone())?;
+                matched.push((document.id, doc));
+            }
+        }
 
-                // Write tombstone WITH catalog tracking (updates catalog entry)
-                storage.write_document(&self.name, &document.id, tombstone_json.as_bytes())?;
+        self.delete_documents_locked(storage, matched)
+    }
+
+    /// Delete documents already matched (and passed by the before-hooks) under
+    /// the caller's storage lock, which is released before cascades and
+    /// after-hooks run; returns the deleted documents as stored
+    fn delete_documents_locked(
+        &self,
+        mut storage: RwLockWriteGuard<'_, StorageEngine>,
+        matched: Vec<(DocumentId, Value)>,
+    ) -> Result<Vec<Value>> {
+        // Restricting references may veto the whole batch
+        let docs: Vec<Value> = matched.iter().map(|(_, doc)| doc.clone()).collect();
+        let cascades = self.check_referrers_locked(&mut storage, &docs)?;
+
+        let mut removed = Vec::with_capacity(matched.len());
+        for (doc_id, doc) in matched {
+            // Mark as tombstone (logical delete)
+            let mut tombstone = doc.clone();
+            if let Value::Object(ref mut map) = tombstone {
+                map.insert("_tombstone".to_string(), Value::Bool(true));
+                map.insert("_collection".to_string(), Value::String(self.name.clone()));
+            }
+            let tombstone_json = serde_json::to_string(&tombstone)?;
 
-                deleted += 1;
+            // Write tombstone WITH catalog tracking (updates catalog entry)
+            storage.write_document(&self.name, &doc_id, tombstone_json.as_bytes())?;
+            self.update_index_entries(&doc_id, Some(&doc), None)?;
+
+            if storage.oplog_enabled() {
+                storage.log_operation("delete", &self.name, &doc_id, Value::Null)?;
             }
+
+            removed.push(doc);
         }
+        drop(storage);
 
         // Invalidate query cache if any document was deleted
-        if deleted > 0 {
+        if !removed.is_empty() {
             self.query_cache.invalidate_collection(&self.name);
         }
+        self.run_cascades(cascades)?;
+        self.run_after_hooks(HookEvent::AfterDelete, removed.clone())?;
 
-        Ok(deleted)
+        Ok(removed)
     }
 
     /// Distinct values for a field
     pub fn distinct(&self, field: &str, query_json: &Value) -> Result<Vec<Value>> {
-        let parsed_query = Query::from_json(query_json)?;
+        let encryptor = self.encryptor();
+        let parsed_query = Query::from_json(&*encryptor.encrypt_query(query_json)?)?;
 
         let mut storage = self.storage.write();
         let meta = storage.get_collection_meta(&self.name)
-            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?
+            .clone();
 
         let file_len = storage.file_len()?;
 
         // Use HashMap to track latest version of each document by _id
         let mut docs_by_id: HashMap<String, Value> = HashMap::new();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
         let mut current_offset = meta.data_offset;
 
         while current_offset < file_len {
@@ -826,9 +1819,10 @@ impl CollectionCore {
                         .unwrap_or("");
 
                     if doc_collection == self.name {
-                        if let Some(id_value) = doc.get("_id") {
+                        if let Some(id_value) = doc.get("_id").filter(|id| !meta.is_superseded(id, current_offset)) {
                             let id_key = serde_json::to_string(id_value)
                                 .unwrap_or_else(|_| "unknown".to_string());
+                            memory.charge_value(&doc)?;
                             docs_by_id.insert(id_key, doc);
                         }
                     }
@@ -854,15 +1848,16 @@ impl CollectionCore {
 
             // Check if matches query
             if parsed_query.matches(&document) {
-                // Extract field value
-                if let Some(field_value) = doc.get(field) {
+                // Extract field value (plaintext, so randomized ciphertexts of
+                // one value count once)
+                if let Some(field_value) = doc.get(field).map(|value| encryptor.decrypt_value(field, value)).transpose()? {
                     // Use JSON string representation for uniqueness check
-                    let value_key = serde_json::to_string(field_value)
+                    let value_key = serde_json::to_string(&field_value)
                         .unwrap_or_else(|_| "null".to_string());
 
                     // Only add if not seen before
                     if seen_values.insert(value_key) {
-                        distinct_values.push(field_value.clone());
+                        distinct_values.push(field_value);
                     }
                 }
             }
@@ -890,6 +1885,10 @@ impl CollectionCore {
             if let Some(value) = map.get(field) {
                 // Check for operators
                 if let Value::Object(ref ops) = value {
+                    if let Some(Value::Array(values)) = ops.get("$in") {
+                        return Ok(QueryPlanner::in_plan(index_name.to_string(), field.to_string(), values));
+                    }
+
                     // Range query
                     let has_gt = ops.contains_key("$gt");
                     let has_gte = ops.contains_key("$gte");
@@ -939,78 +1938,336 @@ impl CollectionCore {
         ))
     }
 
-    /// Execute query using an index
-    fn find_with_index(&self, parsed_query: Query, plan: QueryPlan) -> Result<Vec<Value>> {
-        eprintln!("🔍 DEBUG: find_with_index() called with plan: {:?}", plan);
-        use std::io::Write;
-        let _ = std::io::stderr().flush();
-
-        // Get candidate document IDs from index
-        let doc_ids: Vec<DocumentId> = {
-            let indexes = self.indexes.read();
+    /// Index plan for a query whose filter, projection and sort are all served
+    /// by the index key and `_id`
+    fn covered_plan(
+        &self,
+        query_json: &Value,
+        projection: &HashMap<String, i32>,
+        sort: Option<&[(String, i32)]>,
+    ) -> Option<QueryPlan> {
+        self.plan_query(query_json)
+            .map(|(_, plan)| plan)
+            .filter(|plan| QueryPlanner::is_covered(query_json, plan, projection, sort))
+    }
 
-            match plan {
-                QueryPlan::IndexScan { ref index_name, ref key, .. } => {
-                    eprintln!("🔍 DEBUG: IndexScan - index: {}, key: {:?}", index_name, key);
-                    let _ = std::io::stderr().flush();
-                    if let Some(index) = indexes.get_btree_index(index_name) {
-                        // Use range scan with same start and end to get ALL matching documents
-                        // (B+ tree may have multiple documents with same key value)
-                        let ids = index.range_scan(key, key, true, true);
-                        eprintln!("🔍 DEBUG: IndexScan returned {} doc IDs", ids.len());
-                        let _ = std::io::stderr().flush();
-                        ids
-                    } else {
-                        eprintln!("🔍 DEBUG: Index '{}' NOT FOUND!", index_name);
-                        let _ = std::io::stderr().flush();
-                        vec![]
-                    }
-                }
-                QueryPlan::IndexRangeScan {
-                    ref index_name,
-                    ref start,
-                    ref end,
-                    inclusive_start,
-                    inclusive_end,
-                    ..
-                } => {
-                    eprintln!("🔍 DEBUG: IndexRangeScan - index: {}, start: {:?}, end: {:?}",
-                             index_name, start, end);
-                    let _ = std::io::stderr().flush();
-                    if let Some(index) = indexes.get_btree_index(index_name) {
-                        // Range scan
-                        let default_start = IndexKey::Null;
-                        let default_end = IndexKey::String("\u{10ffff}".repeat(100));
-
-                        let start_key = start.as_ref().unwrap_or(&default_start);
-                        let end_key = end.as_ref().unwrap_or(&default_end);
-
-                        let ids = index.range_scan(start_key, end_key, inclusive_start, inclusive_end);
-                        eprintln!("🔍 DEBUG: IndexRangeScan returned {} doc IDs", ids.len());
-                        let _ = std::io::stderr().flush();
-                        ids
-                    } else {
-                        eprintln!("🔍 DEBUG: Index '{}' NOT FOUND!", index_name);
-                        let _ = std::io::stderr().flush();
-                        vec![]
-                    }
-                }
-                QueryPlan::CollectionScan => {
-                    eprintln!("🔍 DEBUG: CollectionScan (shouldn't happen in find_with_index!)");
-                    let _ = std::io::stderr().flush();
-                    // This shouldn't happen, but fall back to empty
-                    vec![]
-                }
+    /// Index plan for a query, from the plan cache when a query of the same
+    /// shape was planned before. Must not be called with the indexes lock held.
+    fn plan_query(&self, query_json: &Value) -> CachedPlan {
+        let shape = QueryShape::new(&self.name, query_json);
+        if let Some(cached) = self.plan_cache.get(&shape) {
+            let bound = match &cached {
+                Some((field, plan)) => QueryPlanner::bind(plan, query_json).map(|plan| Some((field.clone(), plan))),
+                None => Some(None),
+            };
+            if let Some(plan) = bound {
+                return plan;
             }
-        }; // indexes read lock dropped here
+        }
 
-        eprintln!("🔍 DEBUG: Got {} candidate doc IDs from index", doc_ids.len());
+        let available_indexes = self.indexes.read().list_indexes();
+        let plan = {
+            let storage = self.storage.read();
+            let statistics = storage.get_collection_meta(&self.name).and_then(|meta| meta.statistics.as_ref());
+            QueryPlanner::analyze_query_with_statistics(query_json, &available_indexes, statistics)
+        };
+        self.plan_cache.insert(shape, plan.clone());
+        plan
+    }
+
+    /// Matching documents of a query the index on `field` decides alone (see
+    /// QueryPlanner::exact_bounds), read as a window of index entries: skip
+    /// and limit are applied while walking the index - in descending key
+    /// order if `reverse` - so only the returned documents are fetched, and
+    /// the latest N of a large collection cost N reads. None when there is
+    /// no ordered index on `field`, the filter needs checking against the
+    /// documents, or for an empty filter some documents lack the field or
+    /// hold null, booleans, arrays or objects, which index order places
+    /// differently from apply_sort.
+    fn find_index_window(
+        &self,
+        query_json: &Value,
+        field: &str,
+        reverse: bool,
+        skip: usize,
+        limit: Option<usize>,
+        memory: &mut MemoryTracker,
+    ) -> Result<Option<Vec<Value>>> {
+        use std::ops::Bound;
+
+        let Some((start, end)) = QueryPlanner::exact_bounds(query_json, field) else {
+            return Ok(None);
+        };
+        let unfiltered = matches!((&start, &end), (Bound::Unbounded, Bound::Unbounded));
+        let document_count = match self.storage.read().get_collection_meta(&self.name) {
+            Some(meta) => meta.document_catalog.len() as u64,
+            None => return Ok(None),
+        };
+
+        let entries = {
+            let indexes = self.indexes.read();
+            let available_indexes = indexes.list_indexes();
+            let index = available_indexes.iter()
+                .filter_map(|name| indexes.get_btree_index(name))
+                .find(|index| index.metadata.field == field && !index.metadata.hashed);
+            let Some(index) = index else {
+                return Ok(None);
+            };
+            if unfiltered {
+                // Null and booleans sort first in the index - one check covers all
+                let first = index.scan_entries(Bound::Unbounded, Bound::Unbounded, false, 0, Some(1))?;
+                if index.size() != document_count
+                    || first.first().is_some_and(|(key, _)| matches!(key, IndexKey::Null | IndexKey::Bool(_)))
+                {
+                    return Ok(None);
+                }
+            }
+            index.scan_entries(start.as_ref(), end.as_ref(), reverse, skip, limit)?
+        }; // indexes read lock dropped here
+
+        let mut docs = Vec::with_capacity(entries.len());
+        for (_, doc_id) in entries {
+            if let Some(doc) = self.read_document_by_id(&doc_id)? {
+                memory.charge_value(&doc)?;
+                docs.push(doc);
+            }
+        }
+        Ok(Some(docs))
+    }
+
+    /// Matching documents in the order of a single-field sort, read in an
+    /// index's key order instead of sorted afterwards. Stops after `wanted`
+    /// matches. None when no index gives the sort order: the field is not
+    /// indexed (or only hashed), some documents lack it, or some values are
+    /// null, booleans, arrays or objects, which index order places
+    /// differently from apply_sort. Also None when the filter can use an
+    /// index on another field - narrowing first is cheaper.
+    fn find_index_sorted(
+        &self,
+        query_json: &Value,
+        sort: &[(String, i32)],
+        wanted: Option<usize>,
+        memory: &mut MemoryTracker,
+    ) -> Result<Option<Vec<Value>>> {
+        let [(field, direction)] = sort else {
+            return Ok(None);
+        };
+        let document_count = match self.storage.read().get_collection_meta(&self.name) {
+            Some(meta) => meta.document_catalog.len() as u64,
+            None => return Ok(None),
+        };
+
+        if self.plan_query(query_json).is_some_and(|(planned, _)| planned != *field) {
+            return Ok(None);
+        }
+        let mut entries = {
+            let indexes = self.indexes.read();
+            let available_indexes = indexes.list_indexes();
+            let index = available_indexes.iter()
+                .filter_map(|name| indexes.get_btree_index(name))
+                .find(|index| index.metadata.field == *field && !index.metadata.hashed);
+            match index {
+                Some(index) if index.size() == document_count => index.entries()?,
+                _ => return Ok(None),
+            }
+        }; // indexes read lock dropped here
+
+        // Null and booleans sort first in the index - one check covers all
+        if entries.first().is_some_and(|(key, _)| matches!(key, IndexKey::Null | IndexKey::Bool(_))) {
+            return Ok(None);
+        }
+        if *direction != 1 {
+            entries.reverse();
+        }
+
+        let parsed_query = Query::from_json(query_json)?;
+        let mut docs = Vec::new();
+        for (_, doc_id) in entries {
+            memory.check_interrupt()?;
+            if wanted.is_some_and(|wanted| docs.len() >= wanted) {
+                break;
+            }
+            let Some(doc) = self.read_document_by_id(&doc_id)? else {
+                continue;
+            };
+            let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+            if parsed_query.matches(&document) {
+                memory.charge_value(&doc)?;
+                docs.push(doc);
+            }
+        }
+        Ok(Some(docs))
+    }
+
+    /// Execute a covered query: documents are rebuilt from index entries as
+    /// `{_id, field}` and never read from storage
+    fn find_covered(&self, query_json: &Value, plan: &QueryPlan, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        let parsed_query = Query::from_json(query_json)?;
+
+        let (field, entries) = {
+            let indexes = self.indexes.read();
+
+            match plan {
+                QueryPlan::IndexScan { index_name, field, key } => {
+                    let entries = indexes.get_btree_index(index_name)
+                        .map(|index| index.range_scan_entries(key, key, true, true))
+                        .transpose()?
+                        .unwrap_or_default();
+                    (field, entries)
+                }
+                QueryPlan::IndexRangeScan { index_name, field, start, end, inclusive_start, inclusive_end } => {
+                    let default_start = IndexKey::Null;
+                    let default_end = IndexKey::String("\u{10ffff}".repeat(100));
+
+                    let start_key = start.as_ref().unwrap_or(&default_start);
+                    let end_key = end.as_ref().unwrap_or(&default_end);
+
+                    let entries = indexes.get_btree_index(index_name)
+                        .map(|index| index.range_scan_entries(start_key, end_key, *inclusive_start, *inclusive_end))
+                        .transpose()?
+                        .unwrap_or_default();
+                    (field, entries)
+                }
+                QueryPlan::HashedIndexScan { .. }
+                | QueryPlan::IndexInScan { .. }
+                | QueryPlan::IndexUnion { .. }
+                | QueryPlan::CollectionScan => return Ok(vec![]),
+            }
+        }; // indexes read lock dropped here
+
+        let mut matching_docs = Vec::new();
+
+        for (key, doc_id) in entries {
+            memory.check_interrupt()?;
+            // Null keys also stand for arrays and objects - only the document knows
+            let doc = if key == IndexKey::Null {
+                match self.read_document_by_id(&doc_id)? {
+                    Some(doc) => doc,
+                    None => continue,
+                }
+            } else {
+                let mut doc = serde_json::Map::new();
+                doc.insert("_id".to_string(), serde_json::to_value(&doc_id)?);
+                doc.insert(field.clone(), key.to_value());
+                Value::Object(doc)
+            };
+
+            let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+            if parsed_query.matches(&document) {
+                memory.charge_value(&doc)?;
+                matching_docs.push(doc);
+            }
+        }
+
+        Ok(matching_docs)
+    }
+
+    /// Candidate document IDs of an index plan, in index order
+    fn index_plan_ids(indexes: &IndexManager, plan: &QueryPlan) -> Result<Vec<DocumentId>> {
+        use std::io::Write;
+
+        let ids = match plan {
+            QueryPlan::IndexScan { index_name, key, .. } => {
+                eprintln!("🔍 DEBUG: IndexScan - index: {}, key: {:?}", index_name, key);
+                let _ = std::io::stderr().flush();
+                if let Some(index) = indexes.get_btree_index(index_name) {
+                    // Use range scan with same start and end to get ALL matching documents
+                    // (B+ tree may have multiple documents with same key value)
+                    let ids = index.range_scan(key, key, true, true)?;
+                    eprintln!("🔍 DEBUG: IndexScan returned {} doc IDs", ids.len());
+                    let _ = std::io::stderr().flush();
+                    ids
+                } else {
+                    eprintln!("🔍 DEBUG: Index '{}' NOT FOUND!", index_name);
+                    let _ = std::io::stderr().flush();
+                    vec![]
+                }
+            }
+            QueryPlan::IndexRangeScan {
+                index_name,
+                start,
+                end,
+                inclusive_start,
+                inclusive_end,
+                ..
+            } => {
+                eprintln!("🔍 DEBUG: IndexRangeScan - index: {}, start: {:?}, end: {:?}",
+                         index_name, start, end);
+                let _ = std::io::stderr().flush();
+                if let Some(index) = indexes.get_btree_index(index_name) {
+                    // Range scan
+                    let default_start = IndexKey::Null;
+                    let default_end = IndexKey::String("\u{10ffff}".repeat(100));
+
+                    let start_key = start.as_ref().unwrap_or(&default_start);
+                    let end_key = end.as_ref().unwrap_or(&default_end);
+
+                    let ids = index.range_scan(start_key, end_key, *inclusive_start, *inclusive_end)?;
+                    eprintln!("🔍 DEBUG: IndexRangeScan returned {} doc IDs", ids.len());
+                    let _ = std::io::stderr().flush();
+                    ids
+                } else {
+                    eprintln!("🔍 DEBUG: Index '{}' NOT FOUND!", index_name);
+                    let _ = std::io::stderr().flush();
+                    vec![]
+                }
+            }
+            QueryPlan::HashedIndexScan { index_name, keys, .. }
+            | QueryPlan::IndexInScan { index_name, keys, .. } => {
+                // Hash collisions and Null-keyed arrays are weeded out by the query filter
+                if let Some(index) = indexes.get_btree_index(index_name) {
+                    let mut ids = Vec::new();
+                    for key in keys {
+                        ids.extend(index.range_scan(key, key, true, true)?);
+                    }
+                    ids
+                } else {
+                    vec![]
+                }
+            }
+            QueryPlan::IndexUnion { plans } => {
+                // A document matching several branches is fetched once
+                let mut seen = std::collections::HashSet::new();
+                let mut ids = Vec::new();
+                for plan in plans {
+                    for id in Self::index_plan_ids(indexes, plan)? {
+                        if seen.insert(id.clone()) {
+                            ids.push(id);
+                        }
+                    }
+                }
+                ids
+            }
+            QueryPlan::CollectionScan => {
+                eprintln!("🔍 DEBUG: CollectionScan (shouldn't happen in find_with_index!)");
+                let _ = std::io::stderr().flush();
+                // This shouldn't happen, but fall back to empty
+                vec![]
+            }
+        };
+        Ok(ids)
+    }
+
+    /// Execute query using an index
+    fn find_with_index(&self, parsed_query: Query, plan: QueryPlan, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        eprintln!("🔍 DEBUG: find_with_index() called with plan: {:?}", plan);
+        use std::io::Write;
+        let _ = std::io::stderr().flush();
+
+        // Get candidate document IDs from index
+        let doc_ids = {
+            let indexes = self.indexes.read();
+            Self::index_plan_ids(&indexes, &plan)?
+        }; // indexes read lock dropped here
+
+        eprintln!("🔍 DEBUG: Got {} candidate doc IDs from index", doc_ids.len());
         let _ = std::io::stderr().flush();
 
         // OPTIMIZATION: Use catalog-based lookup for index results instead of full file scan
         let mut matching_docs = Vec::new();
 
         for doc_id in &doc_ids {
+            memory.check_interrupt()?;
             eprintln!("🔍 DEBUG: Looking up doc_id: {:?}", doc_id);
             let _ = std::io::stderr().flush();
             // O(1) lookup using document_catalog (direct DocumentId lookup!)
@@ -1024,6 +2281,7 @@ impl CollectionCore {
                 if parsed_query.matches(&document) {
                     eprintln!("🔍 DEBUG: Document MATCHES query!");
                     let _ = std::io::stderr().flush();
+                    memory.charge_value(&doc)?;
                     matching_docs.push(doc);
                 } else {
                     eprintln!("🔍 DEBUG: Document DOES NOT match query");
@@ -1059,15 +2317,13 @@ impl CollectionCore {
                     "$inc" => {
                         if let Value::Object(ref field_values) = fields {
                             for (field, inc_value) in field_values {
-                                if let Some(current) = document.get(field) {
-                                    // Try int first to preserve integer types
-                                    if let (Some(curr_int), Some(inc_int)) = (current.as_i64(), inc_value.as_i64()) {
-                                        document.set(field.clone(), Value::from(curr_int + inc_int));
-                                        was_modified = true;
-                                    } else if let (Some(curr_num), Some(inc_num)) = (current.as_f64(), inc_value.as_f64()) {
-                                        document.set(field.clone(), Value::from(curr_num + inc_num));
-                                        was_modified = true;
-                                    }
+                                // Integers stay exact integers; see numeric::add
+                                if let (Some(Value::Number(current)), Value::Number(inc)) = (document.get(field), inc_value) {
+                                    let sum = numeric::add(current, inc).ok_or_else(|| MongoLiteError::InvalidQuery(
+                                        format!("$inc of '{}' overflows", field)
+                                    ))?;
+                                    document.set(field.clone(), Value::Number(sum));
+                                    was_modified = true;
                                 }
                             }
                         }
@@ -1250,6 +2506,22 @@ impl CollectionCore {
                             }
                         }
                     }
+                    "$mergeObjects" => {
+                        // Deep merge (JSON merge patch, RFC 7396): objects merge
+                        // key by key, null removes a key, anything else replaces
+                        if let Value::Object(ref field_values) = fields {
+                            for (field, patch) in field_values {
+                                let mut merged = document.get(field).cloned().unwrap_or(Value::Null);
+                                merge_patch(&mut merged, patch);
+                                if merged.is_null() {
+                                    was_modified |= document.remove(field).is_some();
+                                } else if document.get(field) != Some(&merged) {
+                                    document.set(field.clone(), merged);
+                                    was_modified = true;
+                                }
+                            }
+                        }
+                    }
                     _ => {
                         return Err(MongoLiteError::InvalidQuery(format!("Unsupported update operator: {}", op)));
                     }
@@ -1275,15 +2547,11 @@ impl CollectionCore {
                 // Evaluate query operators
                 for (op, op_value) in cond_obj {
                     match op.as_str() {
-                        "$eq" => {
-                            if value != op_value {
-                                return false;
-                            }
+                        "$eq" if !numeric::values_equal(value, op_value) => {
+                            return false;
                         }
-                        "$ne" => {
-                            if value == op_value {
-                                return false;
-                            }
+                        "$ne" if numeric::values_equal(value, op_value) => {
+                            return false;
                         }
                         "$gt" => {
                             use std::cmp::Ordering;
@@ -1311,14 +2579,14 @@ impl CollectionCore {
                         }
                         "$in" => {
                             if let Value::Array(ref arr) = op_value {
-                                if !arr.contains(value) {
+                                if !arr.iter().any(|v| numeric::values_equal(value, v)) {
                                     return false;
                                 }
                             }
                         }
                         "$nin" => {
                             if let Value::Array(ref arr) = op_value {
-                                if arr.contains(value) {
+                                if arr.iter().any(|v| numeric::values_equal(value, v)) {
                                     return false;
                                 }
                             }
@@ -1331,17 +2599,13 @@ impl CollectionCore {
         }
 
         // Direct equality comparison
-        value == condition
+        numeric::values_equal(value, condition)
     }
 
     /// Helper to compare two JSON values for ordering
     fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
         match (a, b) {
-            (Value::Number(n1), Value::Number(n2)) => {
-                let f1 = n1.as_f64()?;
-                let f2 = n2.as_f64()?;
-                f1.partial_cmp(&f2)
-            }
+            (Value::Number(n1), Value::Number(n2)) => Some(numeric::compare(n1, n2)),
             (Value::String(s1), Value::String(s2)) => Some(s1.cmp(s2)),
             (Value::Bool(b1), Value::Bool(b2)) => Some(b1.cmp(b2)),
             _ => None,
@@ -1352,36 +2616,71 @@ impl CollectionCore {
 
     /// Explain query execution plan without executing
     pub fn explain(&self, query_json: &Value) -> Result<Value> {
-        let indexes = self.indexes.read();
-        let available_indexes = indexes.list_indexes();
+        self.explain_with_options(query_json, &crate::find_options::FindOptions::default())
+    }
 
-        let plan = QueryPlanner::explain_query(query_json, &available_indexes);
+    /// Explain a find with options; reports `"coveredQuery": true` when the
+    /// projection and sort let the query be answered from the index alone,
+    /// and `estimatedDocuments` once the collection is analyzed
+    pub fn explain_with_options(&self, query_json: &Value, options: &crate::find_options::FindOptions) -> Result<Value> {
+        self.sync_indexes()?;
+
+        let available_indexes = self.indexes.read().list_indexes();
+
+        let storage = self.storage.read();
+        let meta = storage.get_collection_meta(&self.name);
+        let statistics = meta.and_then(|meta| meta.statistics.as_ref());
+        let mut plan = QueryPlanner::explain_query_with_options(
+            query_json,
+            &available_indexes,
+            options.projection.as_ref(),
+            options.sort.as_deref(),
+            statistics,
+        );
+        if let (Some(meta), Some(statistics)) = (meta, statistics) {
+            plan["estimatedDocuments"] = statistics.estimate(query_json, meta.document_catalog.len() as u64).into();
+        }
         Ok(plan)
     }
 
     /// Find with manual index hint
     pub fn find_with_hint(&self, query_json: &Value, hint: &str) -> Result<Vec<Value>> {
+        let encryptor = self.encryptor();
+        let query = encryptor.encrypt_query(query_json)?;
+        let query_json: &Value = &query;
         let parsed_query = Query::from_json(query_json)?;
+        self.sync_indexes()?;
 
         // Verify hint index exists
-        {
+        let hashed = {
             let indexes = self.indexes.read();
-            if indexes.get_btree_index(hint).is_none() {
-                return Err(MongoLiteError::IndexError(
+            match indexes.get_btree_index(hint) {
+                Some(index) => index.metadata.hashed,
+                None => return Err(MongoLiteError::IndexError(
                     format!("Index '{}' not found (hint)", hint)
-                ));
+                )),
             }
-        }
-
-        // Try to create a plan using the hinted index
-        // For now, we try to match the query to the index field
-        let field = self.extract_field_from_index_name(hint);
+        };
 
         // Create a forced plan
-        let plan = self.create_plan_for_hint(query_json, hint, &field)?;
+        let plan = if hashed {
+            // Planning against the hinted index alone yields its plan if it can serve the query
+            QueryPlanner::analyze_query(query_json, &[hint.to_string()])
+                .map(|(_, plan)| plan)
+                .ok_or_else(|| MongoLiteError::IndexError(
+                    format!("Cannot use index '{}' for this query", hint)
+                ))?
+        } else {
+            // Try to create a plan using the hinted index
+            // For now, we try to match the query to the index field
+            let field = self.extract_field_from_index_name(hint);
+            self.create_plan_for_hint(query_json, hint, &field)?
+        };
 
         // Execute with the forced plan
-        self.find_with_index(parsed_query, plan)
+        let mut docs = self.find_with_index(parsed_query, plan, &mut self.memory_tracker(None))?;
+        encryptor.decrypt_documents(&mut docs)?;
+        Ok(docs)
     }
 
     // ========== AGGREGATION ==========
@@ -1406,99 +2705,1266 @@ impl CollectionCore {
     /// ])).unwrap();
     /// ```
     pub fn aggregate(&self, pipeline_json: &Value) -> Result<Vec<Value>> {
+        self.aggregate_with_options(pipeline_json, &crate::find_options::FindOptions::default())
+    }
+
+    /// aggregate() with a read concern and memory limit
+    /// (the other FindOptions fields do not apply - use pipeline stages)
+    ///
+    /// The collection is read through a snapshot scan (every read concern
+    /// reads the state it starts at): documents stream into the pipeline a
+    /// batch at a time, and a $out / $merge target never holds part of a
+    /// transaction. Otherwise a leading $match an index can answer is read
+    /// through the index like find(), outside snapshot reads (see
+    /// explain_aggregate).
+    pub fn aggregate_with_options(&self, pipeline_json: &Value, options: &crate::find_options::FindOptions) -> Result<Vec<Value>> {
+        let result = self.aggregate_with_options_inner(pipeline_json, options).in_operation("aggregate", &self.name);
+        self.record_trace(|| TracedOp::Aggregate { pipeline: pipeline_json.clone() }, &result);
+        result
+    }
+
+    fn aggregate_with_options_inner(&self, pipeline_json: &Value, options: &crate::find_options::FindOptions) -> Result<Vec<Value>> {
         use crate::aggregation::Pipeline;
 
-        // Parse pipeline
-        let pipeline = Pipeline::from_json(pipeline_json)?;
+        // Parse pipeline
+        let pipeline = Pipeline::from_json(pipeline_json)?;
+
+        let (mut memory, _op) = self.operation_tracker("aggregate", pipeline_json, options);
+        if options.read_concern == ReadConcern::Linearizable {
+            self.wait_durable()?;
+        }
+
+        // Execute pipeline (on plaintext - stages see decrypted fields)
+        let results = match self.indexed_match(&pipeline, options)? {
+            Some(matched) => {
+                let mut docs = self.read_indexed_match(&matched, &mut memory)?;
+                self.encryptor().decrypt_documents(&mut docs)?;
+                matched.rest.execute_stream(docs.into_iter().map(Ok), &mut memory)?
+            }
+            // Stream the collection in
+            None => pipeline.execute_stream(self.snapshot_scan()?, &mut memory)?,
+        };
+
+        // $out / $merge: results are fully materialized at this point,
+        // so a failing stage never touches the target collection
+        match pipeline.output() {
+            Some(output) => {
+                self.check_writable_target(output.collection())?;
+                self.write_aggregation_output(output, results, Vec::new())?;
+                Ok(Vec::new())
+            }
+            None => Ok(results),
+        }
+    }
+
+    /// Explain an aggregation pipeline without running it
+    ///
+    /// Lists the stages in the order they run, whether the leading $match
+    /// is pushed down to an index (with its find explain as `inputPlan`),
+    /// the blocking stages, how many input documents a $limit bounds the
+    /// read to (`limitPushdown`) and the memory the pipeline holds at most.
+    /// Document counts and `estimatedMemory` are upper bounds from the
+    /// collection size and a sample of its documents.
+    pub fn explain_aggregate(&self, pipeline_json: &Value) -> Result<Value> {
+        let pipeline = crate::aggregation::Pipeline::from_json(pipeline_json)?;
+        let options = crate::find_options::FindOptions::default();
+
+        let index_plan = self.indexed_match(&pipeline, &options)?.map(|matched| {
+            let available_indexes = self.indexes.read().list_indexes();
+            QueryPlanner::explain_query(&matched.query, &available_indexes)
+        });
+        let documents = self.storage.read().get_collection_meta(&self.name)
+            .map_or(0, |meta| meta.document_catalog.len());
+        let sample = self.snapshot_scan()?
+            .take(AGGREGATE_EXPLAIN_SAMPLE)
+            .collect::<Result<Vec<_>>>()?;
+        let average_size = crate::memory::estimate_all(&sample) / sample.len().max(1);
+
+        let mut plan = pipeline.explain(documents, average_size, index_plan);
+        plan["input"] = Value::from(if plan["stages"][0]["usesIndex"] == true { "INDEX" } else { "SNAPSHOT_SCAN" });
+        plan["documentCount"] = Value::from(documents);
+        plan["averageDocumentSize"] = Value::from(average_size);
+        Ok(plan)
+    }
+
+    /// The leading $match of `pipeline` when aggregate() answers it from an
+    /// index - not for snapshot reads or $out / $merge, which read as of a
+    /// snapshot that indexes do not describe
+    fn indexed_match(&self, pipeline: &crate::aggregation::Pipeline, options: &crate::find_options::FindOptions) -> Result<Option<IndexedMatch>> {
+        if pipeline.output().is_some() || options.read_concern == ReadConcern::Snapshot {
+            return Ok(None);
+        }
+        let Some((filter, rest)) = pipeline.split_leading_match() else {
+            return Ok(None);
+        };
+        // Stages see plaintext: a filter find() refuses on encrypted fields
+        // still runs as a stage
+        let Ok(query) = self.encryptor().encrypt_query(filter).map(|query| query.into_owned()) else {
+            return Ok(None);
+        };
+        self.sync_indexes()?;
+        Ok(self.plan_query(&query).map(|(field, plan)| IndexedMatch { query, field, plan, rest }))
+    }
+
+    /// Documents of an indexed $match (as stored), only the first ones
+    /// when the rest of the pipeline pulls no more
+    fn read_indexed_match(&self, matched: &IndexedMatch, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        if let Some(limit) = matched.rest.input_limit() {
+            if let Some(docs) = self.find_index_window(&matched.query, &matched.field, false, 0, Some(limit), memory)? {
+                return Ok(docs);
+            }
+        }
+        self.find_with_index(Query::from_json(&matched.query)?, matched.plan.clone(), memory)
+    }
+
+    /// Export the documents matching `query_json` to an Arrow IPC or Parquet file
+    /// The schema is inferred from the documents (see export::infer_schema)
+    #[cfg(feature = "arrow")]
+    pub fn export<P: AsRef<std::path::Path>>(
+        &self,
+        query_json: &Value,
+        path: P,
+        format: crate::export::ExportFormat,
+    ) -> Result<crate::export::ExportStats> {
+        // As of one snapshot: writes during the export are left out whole
+        let snapshot = self.storage.read().snapshot();
+        let docs = self.find_at(query_json, &snapshot)?;
+        crate::export::write_documents(&docs, path, format)
+    }
+
+    /// Find documents as an Arrow record batch, built column by column
+    /// The schema is inferred from the results, as for export
+    #[cfg(feature = "arrow")]
+    pub fn find_arrow(
+        &self,
+        query_json: &Value,
+        options: crate::find_options::FindOptions,
+    ) -> Result<crate::export::arrow_array::RecordBatch> {
+        let docs = self.find_with_options(query_json, options)?;
+        crate::export::to_record_batch(&docs, &crate::export::infer_schema(&docs))
+    }
+
+    /// Export the results of an aggregation pipeline to an Arrow IPC or Parquet file
+    #[cfg(feature = "arrow")]
+    pub fn export_aggregation<P: AsRef<std::path::Path>>(
+        &self,
+        pipeline_json: &Value,
+        path: P,
+        format: crate::export::ExportFormat,
+    ) -> Result<crate::export::ExportStats> {
+        if crate::aggregation::Pipeline::from_json(pipeline_json)?.output().is_some() {
+            return Err(MongoLiteError::ExportError(
+                "$out / $merge pipelines write to a collection and cannot be exported".to_string()
+            ));
+        }
+
+        let results = self.aggregate(pipeline_json)?;
+        crate::export::write_documents(&results, path, format)
+    }
+
+    /// Apply a terminal $out / $merge stage as a single unit
+    ///
+    /// All writes are planned (and every "fail" condition checked) before the
+    /// first byte is written. If a write still fails, the target's catalog is
+    /// restored, so the target shows either all results or none.
+    ///
+    /// `removals` are further documents of the target to remove in the same
+    /// unit (incremental view refresh); ids the target does not hold are skipped.
+    ///
+    /// The writes bypass index maintenance: the target's open handles,
+    /// this one included, rebuild their indexes before their next read.
+    pub(crate) fn write_aggregation_output(
+        &self,
+        output: &crate::aggregation::OutputStage,
+        mut results: Vec<Value>,
+        removals: Vec<DocumentId>,
+    ) -> Result<()> {
+        let target = output.collection();
+        let mut storage = self.storage.write();
+
+        // The target's computed and encrypted fields are written like any insert
+        let computed = storage.computed_fields(target)?;
+        let encryptor = storage.field_encryptor(target);
+        for result in &mut results {
+            computed.apply_value(result)?;
+            if let Value::Object(map) = result {
+                encryptor.encrypt_fields(map.iter_mut())?;
+            }
+            storage.check_references(target, |field| result.get(field))?;
+        }
+
+        let created = storage.get_collection_meta(target).is_none();
+        if created {
+            storage.create_collection(target)?;
+        }
+        let snapshot = storage.get_collection_meta(target).cloned()
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(target.to_string()))?;
+
+        let outcome = Self::plan_aggregation_output(&mut storage, output, results)
+            .and_then(|mut plan| {
+                plan.removals.extend(removals.into_iter().filter(|id| snapshot.document_catalog.contains_key(id)));
+                Self::apply_aggregation_output(&mut storage, target, plan)
+            });
+
+        match outcome {
+            Ok(logged) => {
+                storage.mark_indexes_stale(target);
+                storage.flush()?;
+                if storage.oplog_enabled() {
+                    for (op, doc_id, delta) in logged {
+                        storage.log_operation(op, target, &doc_id, delta)?;
+                    }
+                }
+            }
+            Err(e) => {
+                if created {
+                    storage.drop_collection(target)?;
+                } else if let Some(meta) = storage.get_collection_meta_mut(target) {
+                    *meta = snapshot;
+                }
+                return Err(e);
+            }
+        }
+
+        self.query_cache.invalidate_collection(target);
+        Ok(())
+    }
+
+    /// Work out the writes for $out / $merge without modifying anything
+    fn plan_aggregation_output(
+        storage: &mut StorageEngine,
+        output: &crate::aggregation::OutputStage,
+        results: Vec<Value>,
+    ) -> Result<AggregationOutputPlan> {
+        use crate::aggregation::{OutputStage, WhenMatched, WhenNotMatched};
+
+        let target = output.collection().to_string();
+        let stage = match output {
+            OutputStage::Out(_) => "$out",
+            OutputStage::Merge(_) => "$merge",
+        };
+        let meta = storage.get_collection_meta(&target)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(target.clone()))?;
+        let catalog = meta.document_catalog.clone();
+        let mut next_id = meta.last_id;
+
+        let mut plan = AggregationOutputPlan::default();
+        // _id -> position in plan.writes, so repeated ids within the results see earlier ones
+        let mut planned: HashMap<DocumentId, usize> = HashMap::new();
+
+        for mut doc in results {
+            let map = doc.as_object_mut().ok_or_else(|| MongoLiteError::AggregationError(
+                format!("{}: results must be documents", stage)
+            ))?;
+            map.remove("_collection");
+
+            let doc_id = match map.get("_id") {
+                Some(id) => Some(serde_json::from_value::<DocumentId>(id.clone()).map_err(|_| {
+                    MongoLiteError::AggregationError(format!("{}: _id {} is not a valid document id", stage, id))
+                })?),
+                None => None,
+            };
+
+            match output {
+                OutputStage::Out(_) => {
+                    let doc_id = doc_id.unwrap_or_else(|| {
+                        next_id += 1;
+                        DocumentId::Int(next_id as i64)
+                    });
+                    if planned.insert(doc_id.clone(), plan.writes.len()).is_some() {
+                        return Err(MongoLiteError::AggregationError(
+                            format!("$out: duplicate _id {:?} in results", doc_id)
+                        ));
+                    }
+                    plan.writes.push(("insert", doc_id, doc));
+                }
+                OutputStage::Merge(merge) => {
+                    // Current version: planned earlier in this run, else stored and not deleted
+                    let existing = match &doc_id {
+                        Some(id) => match planned.get(id) {
+                            Some(&pos) => Some(plan.writes[pos].2.clone()),
+                            None => match catalog.get(id) {
+                                Some(&offset) => {
+                                    let stored: Value = serde_json::from_slice(&storage.read_document_at(&target, offset)?)?;
+                                    let deleted = stored.get("_tombstone").and_then(|t| t.as_bool()).unwrap_or(false);
+                                    if deleted { None } else { Some(stored) }
+                                }
+                                None => None,
+                            },
+                        },
+                        None => None,
+                    };
+
+                    let (op, doc_id, new_doc) = match (existing, doc_id) {
+                        (Some(current), Some(doc_id)) => match merge.when_matched {
+                            WhenMatched::Replace => ("update", doc_id, doc),
+                            WhenMatched::Merge => {
+                                let mut merged = current;
+                                if let (Some(merged_map), Value::Object(fields)) = (merged.as_object_mut(), doc) {
+                                    merged_map.extend(fields);
+                                }
+                                ("update", doc_id, merged)
+                            }
+                            WhenMatched::KeepExisting => continue,
+                            WhenMatched::Fail => return Err(MongoLiteError::AggregationError(
+                                format!("$merge: _id {:?} already exists in '{}'", doc_id, target)
+                            )),
+                        },
+                        (_, doc_id) => match merge.when_not_matched {
+                            WhenNotMatched::Insert => {
+                                let doc_id = doc_id.unwrap_or_else(|| {
+                                    next_id += 1;
+                                    DocumentId::Int(next_id as i64)
+                                });
+                                ("insert", doc_id, doc)
+                            }
+                            WhenNotMatched::Discard => continue,
+                            WhenNotMatched::Fail => return Err(MongoLiteError::AggregationError(
+                                format!("$merge: no document with _id {:?} in '{}'", doc_id, target)
+                            )),
+                        },
+                    };
+
+                    match planned.get(&doc_id) {
+                        Some(&pos) => plan.writes[pos] = (plan.writes[pos].0, doc_id, new_doc),
+                        None => {
+                            planned.insert(doc_id.clone(), plan.writes.len());
+                            plan.writes.push((op, doc_id, new_doc));
+                        }
+                    }
+                }
+            }
+        }
+
+        // $out replaces the whole collection: everything not rewritten goes away
+        if let OutputStage::Out(_) = output {
+            plan.removals = catalog.into_keys().filter(|id| !planned.contains_key(id)).collect();
+        }
+
+        Ok(plan)
+    }
+
+    /// Execute a plan; returns the oplog entries to record once it has succeeded
+    fn apply_aggregation_output(
+        storage: &mut StorageEngine,
+        target: &str,
+        plan: AggregationOutputPlan,
+    ) -> Result<Vec<(&'static str, DocumentId, Value)>> {
+        let mut logged = Vec::with_capacity(plan.writes.len() + plan.removals.len());
+
+        // Tombstones keep compaction from resurrecting the old documents;
+        // dropping the catalog entries keeps the catalog at the live set
+        for doc_id in plan.removals {
+            storage.write_tombstone(target, &doc_id)?;
+            logged.push(("delete", doc_id, Value::Null));
+        }
+
+        let mut max_id = 0u64;
+        for (op, doc_id, mut doc) in plan.writes {
+            if let Value::Object(ref mut map) = doc {
+                map.insert("_id".to_string(), serde_json::to_value(&doc_id)?);
+                map.insert("_collection".to_string(), Value::String(target.to_string()));
+            }
+            storage.write_document(target, &doc_id, &serde_json::to_vec(&doc)?)?;
+
+            if let DocumentId::Int(i) = doc_id {
+                max_id = max_id.max(i.max(0) as u64);
+            }
+            logged.push((op, doc_id, doc));
+        }
+
+        storage.claim_ids(target, max_id)?;
+
+        Ok(logged)
+    }
+
+    // ========== INDEX OPERATIONS ==========
+
+    /// Create a B+ tree index on a field
+    pub fn create_index(&self, field: String, unique: bool) -> Result<String> {
+        let index_name = format!("{}_{}", self.name, field);
+        let result = self.build_index(index_name, field.clone(), unique, false, &mut |_| true);
+        self.record_trace(|| TracedOp::CreateIndex { field, unique }, &result);
+        result
+    }
+
+    /// Create a B+ tree index, reporting build progress
+    ///
+    /// `on_progress` is called after every `INDEX_BUILD_PROGRESS_INTERVAL`
+    /// documents and after the last one. Returning `false` cancels the build:
+    /// the partial index is dropped and `MongoLiteError::Cancelled` returned.
+    pub fn create_index_with_progress<F>(&self, field: String, unique: bool, mut on_progress: F) -> Result<String>
+    where
+        F: FnMut(crate::index::IndexBuildProgress) -> bool,
+    {
+        let index_name = format!("{}_{}", self.name, field);
+        self.build_index(index_name, field, unique, false, &mut on_progress)
+    }
+
+    /// Create a hashed index on a field
+    ///
+    /// Stores a 64-bit hash of the value instead of the value itself, so long
+    /// strings make small keys that compare in constant time. Only equality,
+    /// `$eq` and `$in` queries use it; ranges and covered queries need an
+    /// ordinary index.
+    pub fn create_hashed_index(&self, field: String) -> Result<String> {
+        let index_name = format!("{}_{}{}", self.name, field, crate::index::HASHED_INDEX_SUFFIX);
+        self.build_index(index_name, field, false, true, &mut |_| true)
+    }
+
+    /// Create, populate and persist an index
+    fn build_index(
+        &self,
+        index_name: String,
+        field: String,
+        unique: bool,
+        hashed: bool,
+        on_progress: &mut dyn FnMut(crate::index::IndexBuildProgress) -> bool,
+    ) -> Result<String> {
+        use crate::index::IndexMetadata;
+
+        self.check_writable()?;
+
+        // Another handle may already have created (or dropped) it
+        self.sync_indexes()?;
+
+        // Randomized ciphertexts never repeat - nothing could use the index
+        if self.encryptor().mode(&field) == Some(EncryptionMode::Randomized) {
+            return Err(MongoLiteError::IndexError(format!(
+                "cannot index '{}': it uses randomized encryption", field
+            )));
+        }
+
+        let index_meta = IndexMetadata {
+            name: index_name.clone(),
+            field,
+            unique,
+            sparse: false,
+            num_keys: 0,
+            tree_height: 1,
+            root_offset: 0,
+            hashed,
+            uuid: uuid::Uuid::new_v4().to_string(),
+        };
+        self.load_index(&index_meta, on_progress)?;
+
+        // PERSIST index metadata to collection metadata
+        {
+            let mut storage = self.storage.write();
+            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
+                // Add to persisted indexes list
+                meta.indexes.push(index_meta);
+
+                // Save metadata to disk
+                storage.flush()?;
+            }
+        }
+
+        Ok(index_name)
+    }
+
+    /// Create an index in this handle's IndexManager and fill it from the catalog
+    fn load_index(
+        &self,
+        index_meta: &crate::index::IndexMetadata,
+        on_progress: &mut dyn FnMut(crate::index::IndexBuildProgress) -> bool,
+    ) -> Result<()> {
+        use crate::index::{IndexBuildProgress, INDEX_BUILD_PROGRESS_INTERVAL};
+
+        let index_name = &index_meta.name;
+        let field = &index_meta.field;
+
+        let mut indexes = self.indexes.write();
+        if index_meta.hashed {
+            indexes.create_hashed_index(index_name.clone(), field.clone())?;
+        } else {
+            indexes.create_btree_index(index_name.clone(), field.clone(), index_meta.unique)?;
+        }
+        if let Some(index) = indexes.get_btree_index_mut(index_name) {
+            index.metadata.uuid = index_meta.uuid.clone();
+        }
+        self.plan_cache.invalidate();
+
+        // Populate index with existing documents
+        let docs_by_id = {
+            drop(indexes); // Release write lock before acquiring storage lock
+            self.scan_documents_via_catalog(&mut MemoryTracker::unlimited())?
+        };
+
+        // Re-acquire write lock to populate index
+        let mut indexes = self.indexes.write();
+        let total = docs_by_id.len() as u64;
+        let mut processed = 0u64;
+
+        for batch in docs_by_id.chunks(INDEX_BUILD_PROGRESS_INTERVAL) {
+            for (doc_id, doc) in batch {
+                // Extract field value and add to index (no DocumentId parsing needed!)
+                if let Some(field_value) = doc.get(field) {
+                    if let Some(index) = indexes.get_btree_index_mut(index_name) {
+                        let key = index.key_for(field_value);
+                        let _ = index.insert(key, doc_id.clone());
+                    }
+                }
+            }
+
+            // The build can be abandoned up to the last report - nothing is persisted yet
+            processed += batch.len() as u64;
+            if !on_progress(IndexBuildProgress { processed, total }) {
+                indexes.drop_index(index_name)?;
+                self.plan_cache.invalidate();
+                return Err(MongoLiteError::Cancelled(format!("Index build '{}' cancelled", index_name)));
+            }
+        }
+
+        // A file left by an index of the same name that was dropped
+        let index_path = self.storage.read().index_file_path(index_name);
+        if let Some(index) = indexes.get_btree_index_mut(index_name) {
+            refresh_stale_index_file(index, &index_path);
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild indexes from the live documents, keeping their definitions
+    ///
+    /// `index_name` picks one index (the _id index included); None rebuilds
+    /// them all. For use after a repair or a format migration, or when an
+    /// index is suspected to be damaged. The rebuilt indexes replace the
+    /// current ones only once all of them are complete, and their index
+    /// files (if any) are rewritten; writes to the collection wait meanwhile.
+    pub fn reindex(&self, index_name: Option<&str>) -> Result<Vec<ReindexResult>> {
+        self.reindex_with_progress(index_name, |_| true)
+    }
+
+    /// Rebuild indexes, reporting progress (see create_index_with_progress)
+    ///
+    /// Progress counts documents: every selected index is filled in the same
+    /// pass. Returning `false` cancels the rebuild and leaves the current
+    /// indexes in place.
+    pub fn reindex_with_progress<F>(&self, index_name: Option<&str>, mut on_progress: F) -> Result<Vec<ReindexResult>>
+    where
+        F: FnMut(crate::index::IndexBuildProgress) -> bool,
+    {
+        use crate::index::{IndexBuildProgress, INDEX_BUILD_PROGRESS_INTERVAL};
+
+        self.sync_indexes()?;
+        let names = match index_name {
+            None => self.list_indexes(),
+            Some(name) if self.list_indexes().iter().any(|index| index == name) => vec![name.to_string()],
+            Some(name) => return Err(MongoLiteError::IndexError(format!("Index not found: {}", name))),
+        };
+
+        // Same definitions, empty trees
+        let mut trees = {
+            let indexes = self.indexes.read();
+            names.iter()
+                .map(|name| indexes.get_btree_index(name).map(BPlusTree::empty_like).ok_or_else(|| {
+                    MongoLiteError::IndexError(format!("Index not found: {}", name))
+                }))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        // The storage lock keeps writes out until the new trees are in place
+        let storage = &mut *self.storage.write();
+        let docs_by_id = self.scan_catalog_locked(storage, &mut MemoryTracker::unlimited())?;
+        let total = docs_by_id.len() as u64;
+        let mut processed = 0u64;
+
+        for batch in docs_by_id.chunks(INDEX_BUILD_PROGRESS_INTERVAL) {
+            for (doc_id, doc) in batch {
+                for tree in &mut trees {
+                    if let Some(field_value) = doc.get(&tree.metadata.field) {
+                        let key = tree.key_for(field_value);
+                        tree.insert(key, doc_id.clone())?;
+                    }
+                }
+            }
+
+            processed += batch.len() as u64;
+            if !on_progress(IndexBuildProgress { processed, total }) {
+                return Err(MongoLiteError::Cancelled(format!("Reindex of '{}' cancelled", self.name)));
+            }
+        }
+
+        let mut indexes = self.indexes.write();
+        let mut results = Vec::with_capacity(trees.len());
+        for mut tree in trees {
+            let path = storage.index_file_path(&tree.metadata.name);
+            if path.exists() {
+                let temp_path = tree.prepare_changes(&path)?;
+                BPlusTree::commit_prepared_changes(&temp_path, &path)?;
+                // Built from the documents under the same lock
+                if let (Some(stamp), Some(meta)) = (tree.saved_stamp(), storage.get_collection_meta_mut(&self.name)) {
+                    meta.index_files.insert(tree.metadata.name.clone(), stamp.save_id.clone());
+                }
+            }
+            results.push(ReindexResult { index: tree.metadata.name.clone(), keys: tree.size() });
+            indexes.replace_btree_index(tree);
+        }
+        self.plan_cache.invalidate();
+        // Results read through a damaged index may be cached
+        self.query_cache.invalidate_collection(&self.name);
+
+        Ok(results)
+    }
+
+    /// Bring this handle's indexes in line with the definitions persisted in
+    /// the collection metadata - other handles create and drop indexes too -
+    /// and with writes that bypassed them (see CollectionMeta::index_epoch)
+    fn sync_indexes(&self) -> Result<()> {
+        let (persisted, epoch) = {
+            let storage = self.storage.read();
+            storage.get_collection_meta(&self.name)
+                .map(|meta| (meta.indexes.clone(), meta.index_epoch))
+                .unwrap_or_default()
+        };
+        let id_index_name = format!("{}_id", self.name);
+        let current = self.indexes.read().list_indexes();
+
+        for name in &current {
+            if *name != id_index_name && !persisted.iter().any(|index_meta| &index_meta.name == name) {
+                // Dropped elsewhere (a concurrent sync may have beaten us to it)
+                let _ = self.indexes.write().drop_index(name);
+                self.plan_cache.invalidate();
+            }
+        }
+
+        for index_meta in &persisted {
+            if index_meta.name == id_index_name || current.contains(&index_meta.name) {
+                continue;
+            }
+            if let Err(e) = self.load_index(index_meta, &mut |_| true) {
+                // A concurrent sync on this handle may have loaded it first
+                if self.indexes.read().get_btree_index(&index_meta.name).is_none() {
+                    return Err(e);
+                }
+            }
+        }
+
+        if epoch != self.index_epoch.load(std::sync::atomic::Ordering::SeqCst) {
+            self.rebuild_stale_indexes()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rebuild every index of this handle from the documents, unless another
+    /// sync did since the index epoch moved
+    fn rebuild_stale_indexes(&self) -> Result<()> {
+        // The storage lock keeps writes out until the new trees are in place
+        let storage = &mut *self.storage.write();
+        let epoch = storage.get_collection_meta(&self.name).map_or(0, |meta| meta.index_epoch);
+        if epoch == self.index_epoch.load(std::sync::atomic::Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut trees = {
+            let indexes = self.indexes.read();
+            indexes.list_indexes().iter()
+                .filter_map(|name| indexes.get_btree_index(name).map(BPlusTree::empty_like))
+                .collect::<Vec<_>>()
+        };
+        for (doc_id, doc) in self.scan_catalog_locked(storage, &mut MemoryTracker::unlimited())? {
+            for tree in &mut trees {
+                if let Some(field_value) = doc.get(&tree.metadata.field) {
+                    let key = tree.key_for(field_value);
+                    tree.insert(key, doc_id.clone())?;
+                }
+            }
+        }
+
+        let mut indexes = self.indexes.write();
+        for tree in trees {
+            indexes.replace_btree_index(tree);
+        }
+        self.index_epoch.store(epoch, std::sync::atomic::Ordering::SeqCst);
+        self.plan_cache.invalidate();
+        self.query_cache.invalidate_collection(&self.name);
+        Ok(())
+    }
+
+    /// Drop an index
+    pub fn drop_index(&self, index_name: &str) -> Result<()> {
+        let result = self.drop_index_inner(index_name);
+        self.record_trace(|| TracedOp::DropIndex { name: index_name.to_string() }, &result);
+        result
+    }
+
+    fn drop_index_inner(&self, index_name: &str) -> Result<()> {
+        self.sync_indexes()?;
+
+        let mut indexes = self.indexes.write();
+        indexes.drop_index(index_name)?;
+        self.plan_cache.invalidate();
+
+        drop(indexes); // Release lock
+
+        // Remove from persisted metadata
+        {
+            let mut storage = self.storage.write();
+            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
+                meta.indexes.retain(|idx| idx.name != index_name);
+                meta.index_files.remove(index_name);
+                storage.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// List all indexes
+    ///
+    /// Read from the persisted definitions, so indexes created or dropped
+    /// through another handle are reflected
+    pub fn list_indexes(&self) -> Vec<String> {
+        let storage = self.storage.read();
+
+        let mut names = vec![format!("{}_id", self.name)];
+        if let Some(meta) = storage.get_collection_meta(&self.name) {
+            names.extend(meta.indexes.iter().map(|index_meta| index_meta.name.clone()));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // ========== HOOKS ==========
+
+    /// Register a write hook on this collection (see hooks::HookEvent)
+    ///
+    /// Hooks belong to the database, so every handle of the collection runs
+    /// them. Transactional writes run only the before-hooks, when the write
+    /// is added to the transaction.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ironbase_core::{DatabaseCore, HookEvent, MongoLiteError};
+    ///
+    /// let db = DatabaseCore::open("test.db").unwrap();
+    /// let users = db.collection("users").unwrap();
+    ///
+    /// users.add_hook(HookEvent::BeforeInsert, |doc| {
+    ///     if doc.get("email").is_none() {
+    ///         return Err(MongoLiteError::InvalidQuery("email is required".into()));
+    ///     }
+    ///     doc["email_lower"] = doc["email"].as_str().unwrap_or("").to_lowercase().into();
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn add_hook<F>(&self, event: HookEvent, hook: F)
+    where
+        F: Fn(&mut Value) -> Result<()> + Send + Sync + 'static,
+    {
+        let hook: Hook = Arc::new(hook);
+        self.hooks.register(&self.name, event, hook);
+    }
+
+    /// Remove every hook of this collection
+    pub fn clear_hooks(&self) {
+        self.hooks.clear(&self.name);
+    }
+
+    // ========== FIELD ENCRYPTION ==========
+
+    /// Store `field` encrypted from now on (see encryption.rs)
+    ///
+    /// Needs the database's encryption key for writes; reads without it
+    /// return the ciphertexts. Only top-level fields can be encrypted, and only
+    /// before any document holds a value for them. Deterministic fields can be
+    /// queried for equality and indexed, randomized ones only with $exists.
+    /// Hooks and the oplog see the encrypted values.
+    pub fn encrypt_field(&self, field: &str, mode: EncryptionMode) -> Result<()> {
+        self.check_writable()?;
+        if field.is_empty() || field.contains('.') || field.starts_with('$') || field == "_id" || field == "_collection" {
+            return Err(MongoLiteError::InvalidConfig(format!("field '{}' cannot be encrypted", field)));
+        }
+        let indexed = {
+            let indexes = self.indexes.read();
+            indexes.list_indexes().iter()
+                .any(|name| indexes.get_btree_index(name).is_some_and(|index| index.metadata.field == field))
+        };
+        if mode == EncryptionMode::Randomized && indexed {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "field '{}' is indexed - randomized encryption would make the index useless", field
+            )));
+        }
+        if !self.computed_fields().is_empty() {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "collection '{}' has computed fields - they cannot be combined with encryption", self.name
+            )));
+        }
+        let existing = serde_json::json!({field: {"$exists": true, "$ne": null}});
+        if self.encryptor().mode(field).is_none() && self.count_documents(&existing)? > 0 {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "documents already hold plaintext values of '{}' - encrypt fields before storing them", field
+            )));
+        }
+
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        match meta.encrypted_fields.insert(field.to_string(), mode) {
+            Some(previous) if previous != mode => {
+                meta.encrypted_fields.insert(field.to_string(), previous);
+                return Err(MongoLiteError::InvalidConfig(format!(
+                    "field '{}' is already encrypted as {:?}", field, previous
+                )));
+            }
+            _ => {}
+        }
+        storage.flush()
+    }
+
+    /// Encrypted fields of this collection and their modes
+    pub fn encrypted_fields(&self) -> HashMap<String, EncryptionMode> {
+        self.storage.read().get_collection_meta(&self.name)
+            .map(|meta| meta.encrypted_fields.clone())
+            .unwrap_or_default()
+    }
+
+    // ========== COMPUTED FIELDS ==========
+
+    /// Store the result of `expression` (an aggregation expression, see
+    /// expression.rs) as field `name` of every document
+    ///
+    /// The engine recomputes it on every insert and update, after write hooks,
+    /// so it can be queried and indexed like any other field; a value written
+    /// for it by the caller is overwritten. Fields are computed in the order
+    /// they were added and can use earlier ones. A null result leaves the
+    /// field out. Adding a field with an existing name replaces its
+    /// expression. Existing documents are updated before the definition is
+    /// saved - if that fails (e.g. a unique index rejects a value), the
+    /// documents already updated keep their values.
+    pub fn add_computed_field(&self, name: &str, expression: &Value) -> Result<()> {
+        self.check_writable()?;
+        let field = ComputedField::new(name, expression.clone())?;
+        if !self.encrypted_fields().is_empty() {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "collection '{}' has encrypted fields - they cannot be computed from", self.name
+            )));
+        }
+        let mut fields = self.computed_fields();
+        match fields.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => *existing = field,
+            None => fields.push(field),
+        }
+        let computed = ComputedFields::new(&fields)?;
+
+        let mut storage = self.storage.write();
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        for (doc_id, current) in self.scan_catalog_locked(&mut storage, &mut memory)? {
+            let mut updated = current.clone();
+            computed.apply_value(&mut updated)?;
+            if updated != current {
+                self.write_version_locked(&mut storage, &doc_id, &current, &updated, updated.clone())?;
+            }
+        }
+
+        storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?
+            .computed_fields = fields;
+        storage.flush()
+    }
+
+    /// Stop maintaining computed field `name`; returns false if there was none.
+    /// Stored values stay in the documents as plain fields.
+    pub fn drop_computed_field(&self, name: &str) -> Result<bool> {
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let before = meta.computed_fields.len();
+        meta.computed_fields.retain(|field| field.name != name);
+        if meta.computed_fields.len() == before {
+            return Ok(false);
+        }
+        storage.flush()?;
+        Ok(true)
+    }
+
+    /// Computed fields of this collection, in computation order
+    pub fn computed_fields(&self) -> Vec<ComputedField> {
+        self.storage.read().get_collection_meta(&self.name)
+            .map(|meta| meta.computed_fields.clone())
+            .unwrap_or_default()
+    }
+
+    // ========== BLOOM FILTERS ==========
+
+    /// Keep per-segment Bloom filters on top-level `field` (see storage/bloom.rs)
+    ///
+    /// Full scans for an equality or $in query on the field then skip the
+    /// parts of the data file that cannot hold a match - cheaper to keep than
+    /// an index, and no help for ranges or sorts. The filters live in memory
+    /// and are built by the first query that uses them.
+    pub fn add_bloom_filter(&self, field: &str) -> Result<()> {
+        self.check_writable()?;
+        if field.is_empty() || field.contains('.') || field.starts_with('$') || field == "_id" || field == "_collection" {
+            return Err(MongoLiteError::InvalidConfig(format!("field '{}' cannot have a Bloom filter", field)));
+        }
+        if self.encryptor().mode(field).is_some() {
+            return Err(MongoLiteError::InvalidConfig(format!("field '{}' is encrypted", field)));
+        }
+
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        if meta.bloom_fields.iter().any(|existing| existing == field) {
+            return Ok(());
+        }
+        meta.bloom_fields.push(field.to_string());
+        storage.flush()
+    }
+
+    /// Stop keeping Bloom filters on `field`; returns false if there were none
+    pub fn drop_bloom_filter(&self, field: &str) -> Result<bool> {
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let before = meta.bloom_fields.len();
+        meta.bloom_fields.retain(|existing| existing != field);
+        if meta.bloom_fields.len() == before {
+            return Ok(false);
+        }
+        storage.flush()?;
+        Ok(true)
+    }
+
+    /// Fields with Bloom filters, in the order they were added
+    pub fn bloom_filters(&self) -> Vec<String> {
+        self.storage.read().get_collection_meta(&self.name)
+            .map(|meta| meta.bloom_fields.clone())
+            .unwrap_or_default()
+    }
+
+    // ========== REFERENCES ==========
+
+    /// Require `field` to hold the _id of a live document in `collection`
+    /// (which may be this collection) - see storage::Reference
+    ///
+    /// Inserts, updates and aggregation output are checked; a missing or null
+    /// field is allowed. Deleting a referenced document fails (Restrict) or
+    /// deletes the referencing documents after it (Cascade); a restricting
+    /// reference further down a cascade fails the delete that reached it only.
+    /// Replaces an existing reference on `field`. Existing documents must
+    /// already satisfy it.
+    pub fn add_reference(&self, field: &str, collection: &str, on_delete: OnDelete) -> Result<()> {
+        self.check_writable()?;
+        if field.is_empty() || field.contains('.') || field.starts_with('$') || field == "_id" || field == "_collection" {
+            return Err(MongoLiteError::InvalidConfig(format!("field '{}' cannot be a reference", field)));
+        }
+        if self.encryptor().mode(field).is_some() {
+            return Err(MongoLiteError::InvalidConfig(format!("field '{}' is encrypted", field)));
+        }
+
+        let mut storage = self.storage.write();
+        match storage.get_collection_meta(collection) {
+            None => return Err(MongoLiteError::CollectionNotFound(collection.to_string())),
+            Some(meta) if meta.view.is_some() => return Err(MongoLiteError::InvalidConfig(format!(
+                "'{}' is a view - references must point at a collection", collection
+            ))),
+            Some(_) => {}
+        }
+
+        let reference = Reference { field: field.to_string(), collection: collection.to_string(), on_delete };
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let previous = meta.references.clone();
+        meta.references.retain(|existing| existing.field != field);
+        meta.references.push(reference);
+
+        let mut memory = MemoryTracker::new(storage.query_memory_limit());
+        let checked = self.scan_catalog_locked(&mut storage, &mut memory).and_then(|docs| {
+            docs.iter().try_for_each(|(_, doc)| storage.check_references(&self.name, |field| doc.get(field)))
+        });
+        if let Err(e) = checked {
+            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
+                meta.references = previous;
+            }
+            return Err(e);
+        }
+        storage.flush()
+    }
+
+    /// Remove the reference constraint on `field`; returns false if there was none
+    pub fn drop_reference(&self, field: &str) -> Result<bool> {
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let before = meta.references.len();
+        meta.references.retain(|reference| reference.field != field);
+        if meta.references.len() == before {
+            return Ok(false);
+        }
+        storage.flush()?;
+        Ok(true)
+    }
+
+    /// Reference constraints of this collection's fields
+    pub fn references(&self) -> Vec<Reference> {
+        self.storage.read().get_collection_meta(&self.name)
+            .map(|meta| meta.references.clone())
+            .unwrap_or_default()
+    }
 
-        // Get all documents (TODO: optimize with index if $match is first stage)
-        let docs = self.find(&serde_json::json!({}))?;
+    // ========== QUOTAS ==========
 
-        // Execute pipeline
-        pipeline.execute(docs)
+    /// Limit this collection's live documents and bytes (persisted)
+    ///
+    /// Inserts that would go past a limit fail with QuotaExceeded and write
+    /// nothing; a batch from insert_many fails as a whole. Lowering a quota
+    /// below the current size only blocks further inserts.
+    pub fn set_quota(&self, quota: CollectionQuota) -> Result<()> {
+        let mut storage = self.storage.write();
+        let meta = storage.get_collection_meta_mut(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        meta.quota = quota;
+        storage.flush()
     }
 
-    // ========== INDEX OPERATIONS ==========
+    /// Current quota (unlimited unless set)
+    pub fn quota(&self) -> CollectionQuota {
+        self.storage.read().get_collection_meta(&self.name)
+            .map(|meta| meta.quota)
+            .unwrap_or_default()
+    }
 
-    /// Create a B+ tree index on a field
-    pub fn create_index(&self, field: String, unique: bool) -> Result<String> {
-        let index_name = format!("{}_{}", self.name, field);
+    // ========== STATISTICS ==========
 
-        let mut indexes = self.indexes.write();
-        indexes.create_btree_index(index_name.clone(), field.clone(), unique)?;
+    /// Storage statistics for this collection
+    ///
+    /// A record is live if the catalog points at it and it is not a tombstone,
+    /// everything else (old versions, tombstones) is dead space that free-space
+    /// reuse or compaction reclaims. The counts are kept up to date on every
+    /// write (see storage::GarbageStats), so this does not scan the file.
+    pub fn stats(&self) -> Result<Value> {
+        let storage = self.storage.read();
 
-        // Populate index with existing documents
-        let docs_by_id = {
-            drop(indexes); // Release write lock before acquiring storage lock
-            self.scan_documents_via_catalog()?
+        let meta = storage.get_collection_meta(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let catalog_size = meta.document_catalog.len();
+        let garbage = meta.garbage.unwrap_or_default();
+
+        drop(storage);
+
+        let index_sizes: serde_json::Map<String, Value> = {
+            let indexes = self.indexes.read();
+            indexes.list_indexes()
+                .into_iter()
+                .filter_map(|name| {
+                    let size = indexes.get_btree_index(&name)?.size();
+                    Some((name, Value::from(size)))
+                })
+                .collect()
         };
 
-        // Re-acquire write lock to populate index
-        let mut indexes = self.indexes.write();
+        let avg_document_size = garbage.live_bytes.checked_div(garbage.live_records).unwrap_or(0);
+
+        Ok(serde_json::json!({
+            "collection": self.name,
+            "document_count": garbage.live_records,
+            "tombstone_count": garbage.tombstones,
+            "avg_document_size": avg_document_size,
+            "live_bytes": garbage.live_bytes,
+            "dead_records": garbage.dead_records,
+            "dead_bytes": garbage.dead_bytes,
+            "dead_ratio": garbage.dead_ratio(),
+            "total_bytes": garbage.live_bytes + garbage.dead_bytes,
+            "catalog_size": catalog_size,
+            "index_sizes": index_sizes,
+        }))
+    }
 
-        for (doc_id, doc) in &docs_by_id {
-            // Extract field value and add to index (no DocumentId parsing needed!)
-            if let Some(field_value) = doc.get(&field) {
-                let key = IndexKey::from(field_value);
+    /// Gather the statistics the query planner chooses indexes by (see
+    /// statistics.rs) from a sample of the documents, replacing any
+    /// gathered before
+    pub fn analyze(&self) -> Result<CollectionStatistics> {
+        let mut storage = self.storage.write();
+        let catalog = self.natural_order(&storage)?;
+        let stride = catalog.len().div_ceil(ANALYZE_SAMPLE).max(1);
 
-                if let Some(index) = indexes.get_btree_index_mut(&index_name) {
-                    let _ = index.insert(key, doc_id.clone());
-                }
+        let mut sample = Vec::with_capacity(catalog.len().min(ANALYZE_SAMPLE));
+        for (_, offset) in catalog.iter().step_by(stride) {
+            let Ok(doc_bytes) = storage.read_data(*offset) else {
+                continue; // Skip corrupted entries, as scan_catalog_locked does
+            };
+            let doc: Value = serde_json::from_slice(&doc_bytes)?;
+            if doc.get("_tombstone").and_then(Value::as_bool).unwrap_or(false) {
+                continue;
             }
+            sample.push(doc);
         }
 
-        drop(indexes); // Release index lock
+        let statistics = CollectionStatistics::build(&sample, catalog.len() as u64);
+        storage.set_statistics(&self.name, statistics.clone())?;
+        drop(storage);
+
+        self.plan_cache.invalidate();
+        Ok(statistics)
+    }
+
+    // ========== VALIDATION ==========
+
+    /// Check collection integrity
+    ///
+    /// Cross-checks every catalog entry against the file, every index entry
+    /// against the live documents and every live document against the indexes.
+    /// With `full = true` the whole data region is also walked to verify that
+    /// catalog offsets land on record boundaries and the file has no garbage tail.
+    /// Nothing is modified - inconsistencies are only reported.
+    pub fn validate(&self, full: bool) -> Result<ValidationReport> {
+        let mut report = ValidationReport::new(&self.name, full);
+
+        // Live documents keyed by their JSON _id (catalog and index ids may differ in variant)
+        let mut live: HashMap<String, Value> = HashMap::new();
 
-        // PERSIST index metadata to collection metadata
         {
             let mut storage = self.storage.write();
-            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
-                // Create IndexMetadata
-                use crate::index::IndexMetadata;
-                let index_meta = IndexMetadata {
-                    name: index_name.clone(),
-                    field: field.clone(),
-                    unique,
-                    sparse: false,
-                    num_keys: 0,
-                    tree_height: 1,
-                    root_offset: 0,
+            let catalog = storage.get_collection_meta(&self.name)
+                .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?
+                .document_catalog
+                .clone();
+
+            let mut readable_offsets: Vec<(DocumentId, u64)> = Vec::new();
+
+            for (doc_id, &offset) in &catalog {
+                if offset < crate::storage::DATA_START_OFFSET {
+                    report.add_issue(ValidationIssue::UnreadableRecord {
+                        doc_id: doc_id.clone(),
+                        offset,
+                        error: "offset inside reserved metadata area".to_string(),
+                    });
+                    continue;
+                }
+
+                let data = match storage.read_data_checked(offset) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        report.add_issue(ValidationIssue::UnreadableRecord {
+                            doc_id: doc_id.clone(),
+                            offset,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
                 };
+                readable_offsets.push((doc_id.clone(), offset));
 
-                // Add to persisted indexes list
-                meta.indexes.push(index_meta);
+                let doc: Value = match serde_json::from_slice(&data) {
+                    Ok(doc) => doc,
+                    Err(e) => {
+                        report.add_issue(ValidationIssue::MalformedDocument {
+                            doc_id: doc_id.clone(),
+                            offset,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
 
-                // Save metadata to disk
-                storage.flush()?;
-            }
-        }
+                let found_collection = doc.get("_collection").and_then(|c| c.as_str());
+                if found_collection != Some(self.name.as_str()) {
+                    report.add_issue(ValidationIssue::CollectionMismatch {
+                        doc_id: doc_id.clone(),
+                        offset,
+                        found: found_collection.map(|c| c.to_string()),
+                    });
+                    continue;
+                }
 
-        Ok(index_name)
-    }
+                let expected_id = serde_json::to_value(doc_id)?;
+                if doc.get("_id") != Some(&expected_id) {
+                    report.add_issue(ValidationIssue::IdMismatch {
+                        doc_id: doc_id.clone(),
+                        offset,
+                        found: doc.get("_id")
+                            .and_then(|id| serde_json::from_value(id.clone()).ok()),
+                    });
+                    continue;
+                }
 
-    /// Drop an index
-    pub fn drop_index(&self, index_name: &str) -> Result<()> {
-        let mut indexes = self.indexes.write();
-        indexes.drop_index(index_name)?;
+                if doc.get("_tombstone").and_then(|t| t.as_bool()).unwrap_or(false) {
+                    report.tombstones_checked += 1;
+                    continue;
+                }
 
-        drop(indexes); // Release lock
+                report.documents_checked += 1;
+                live.insert(expected_id.to_string(), doc);
+            }
 
-        // Remove from persisted metadata
-        {
-            let mut storage = self.storage.write();
-            if let Some(meta) = storage.get_collection_meta_mut(&self.name) {
-                meta.indexes.retain(|idx| idx.name != index_name);
-                storage.flush()?;
+            if full {
+                let file_len = storage.file_len()?;
+                let mut record_offsets = std::collections::HashSet::new();
+                let mut region_end = crate::storage::DATA_START_OFFSET.min(file_len);
+
+                storage.for_each_record(|offset, data| {
+                    record_offsets.insert(offset);
+                    region_end = offset + 4 + data.len() as u64;
+                    Ok(())
+                })?;
+                report.records_scanned = record_offsets.len() as u64;
+
+                for (doc_id, offset) in readable_offsets {
+                    if !record_offsets.contains(&offset) {
+                        report.add_issue(ValidationIssue::MisalignedOffset { doc_id, offset });
+                    }
+                }
+
+                if region_end < file_len {
+                    report.add_issue(ValidationIssue::TrailingGarbage {
+                        offset: region_end,
+                        bytes: file_len - region_end,
+                    });
+                }
             }
         }
 
-        Ok(())
-    }
-
-    /// List all indexes
-    pub fn list_indexes(&self) -> Vec<String> {
+        // Index <-> document cross-check
         let indexes = self.indexes.read();
-        indexes.list_indexes()
+        for index_name in indexes.list_indexes() {
+            let index = match indexes.get_btree_index(&index_name) {
+                Some(index) => index,
+                None => continue,
+            };
+            let field = &index.metadata.field;
+            let mut indexed: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+            for (key, doc_id) in index.entries()? {
+                report.index_entries_checked += 1;
+                let id_key = serde_json::to_value(&doc_id)?.to_string();
+
+                match live.get(&id_key) {
+                    None => {
+                        report.add_issue(ValidationIssue::StaleIndexEntry {
+                            index: index_name.clone(),
+                            doc_id,
+                        });
+                    }
+                    Some(doc) => {
+                        if doc.get(field).map(|value| index.key_for(value)).as_ref() != Some(&key) {
+                            report.add_issue(ValidationIssue::IndexKeyMismatch {
+                                index: index_name.clone(),
+                                doc_id,
+                            });
+                        }
+                        indexed.insert(id_key);
+                    }
+                }
+            }
+
+            for (id_key, doc) in &live {
+                if doc.get(field).is_some() && !indexed.contains(id_key) {
+                    let doc_id = doc.get("_id")
+                        .and_then(|id| serde_json::from_value(id.clone()).ok())
+                        .unwrap_or(DocumentId::String(id_key.clone()));
+                    report.add_issue(ValidationIssue::MissingIndexEntry {
+                        index: index_name.clone(),
+                        doc_id,
+                    });
+                }
+            }
+        }
+
+        Ok(report)
     }
 
     // ========== TRANSACTION OPERATIONS ==========
@@ -1510,19 +3976,34 @@ impl CollectionCore {
     pub fn insert_one_tx(&self, doc: HashMap<String, Value>, tx: &mut crate::transaction::Transaction) -> Result<DocumentId> {
         use crate::transaction::Operation;
 
+        self.check_writable()?;
+
         // Generate document ID
         let mut storage = self.storage.write();
         let meta = storage.get_collection_meta_mut(&self.name)
             .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
 
         let doc_id = DocumentId::new_auto(meta.last_id);
-        meta.last_id += 1;
-        drop(storage); // Release lock early
 
         // Create document with _id and _collection
         let mut doc_with_id = doc.clone();
         doc_with_id.insert("_id".to_string(), serde_json::json!(doc_id.clone()));
         doc_with_id.insert("_collection".to_string(), Value::String(self.name.clone()));
+        let mut doc_with_id = serde_json::json!(doc_with_id);
+        self.hooks.run(&self.name, HookEvent::BeforeInsert, &mut doc_with_id)?;
+        storage.computed_fields(&self.name)?.apply_value(&mut doc_with_id)?;
+        if let Value::Object(map) = &mut doc_with_id {
+            storage.field_encryptor(&self.name).encrypt_fields(map.iter_mut())?;
+        }
+        storage.check_references(&self.name, |field| doc_with_id.get(field))?;
+
+        // Reject an oversized document (or one over quota) now rather than at
+        // commit; other pending inserts of the transaction are not counted
+        let size = serde_json::to_vec(&doc_with_id)?.len();
+        storage.check_document_size(size)?;
+        storage.check_quota(&self.name, 1, size as u64)?;
+        storage.next_id(&self.name)?;
+        drop(storage); // Release lock early
 
         // Add operation to transaction
         tx.add_operation(Operation::Insert {
@@ -1540,7 +4021,7 @@ impl CollectionCore {
 
                 // Get the field value from the document
                 if let Some(key_value) = doc_with_id.get(field_name) {
-                    let key = crate::transaction::IndexKey::from(key_value);
+                    let key = crate::transaction::IndexKey::from(&btree_index.key_for(key_value).to_value());
                     tx.add_index_change(
                         index_name.clone(),
                         crate::transaction::IndexChange {
@@ -1561,32 +4042,44 @@ impl CollectionCore {
     /// Note: Pass the new_doc directly (not update operators).
     /// Index changes are tracked but not yet applied atomically.
     /// See INDEX_CONSISTENCY.md for future two-phase commit implementation.
-    pub fn update_one_tx(&self, query: &Value, new_doc: Value, tx: &mut crate::transaction::Transaction) -> Result<(u64, u64)> {
+    pub fn update_one_tx(&self, query: &Value, new_doc: Value, tx: &mut crate::transaction::Transaction) -> Result<UpdateResult> {
         use crate::transaction::Operation;
 
-        // Find the document first
-        let doc = self.find_one(query)?;
+        self.check_writable()?;
+
+        // Find the document first (as stored)
+        let encryptor = self.encryptor();
+        let doc = self.find_one_stored(&*encryptor.encrypt_query(query)?)?;
 
         if let Some(old_doc) = doc {
             // Extract document ID from _id field
             let id_value = old_doc.get("_id")
                 .ok_or_else(|| MongoLiteError::DocumentNotFound)?;
 
+            // Integer ids are i64; a larger u64 is rejected, not wrapped
             let doc_id = match id_value {
                 Value::Number(n) if n.is_i64() => DocumentId::Int(n.as_i64().unwrap()),
-                Value::Number(n) if n.is_u64() => DocumentId::Int(n.as_u64().unwrap() as i64),
                 Value::String(s) => DocumentId::String(s.clone()),
-                _ => return Err(MongoLiteError::Serialization("Invalid _id type".to_string())),
+                _ => return Err(MongoLiteError::Serialization(format!("Invalid _id: {}", id_value))),
             };
 
             // Ensure new_doc has _id and _collection fields
-            let new_doc_with_meta = if let Value::Object(mut map) = new_doc {
+            let mut new_doc_with_meta = if let Value::Object(mut map) = new_doc {
                 map.insert("_id".to_string(), id_value.clone());
                 map.insert("_collection".to_string(), Value::String(self.name.clone()));
                 Value::Object(map)
             } else {
                 return Err(MongoLiteError::Serialization("new_doc must be an object".to_string()));
             };
+            self.hooks.run(&self.name, HookEvent::BeforeUpdate, &mut new_doc_with_meta)?;
+            self.computed()?.apply_value(&mut new_doc_with_meta)?;
+            if let Value::Object(map) = &mut new_doc_with_meta {
+                encryptor.encrypt_fields(map.iter_mut())?;
+            }
+
+            // Reject an oversized document (or a dangling reference) now rather than at commit
+            self.storage.read().check_document_size(serde_json::to_vec(&new_doc_with_meta)?.len())?;
+            self.storage.write().check_references(&self.name, |field| new_doc_with_meta.get(field))?;
 
             // Prepare new_doc for index tracking
             let new_doc_for_tracking = new_doc_with_meta.clone();
@@ -1615,7 +4108,7 @@ impl CollectionCore {
 
                     // Delete old key if exists
                     if let Some(old_val) = old_value {
-                        let old_key = crate::transaction::IndexKey::from(old_val);
+                        let old_key = crate::transaction::IndexKey::from(&btree_index.key_for(old_val).to_value());
                         tx.add_index_change(
                             index_name.clone(),
                             crate::transaction::IndexChange {
@@ -1628,7 +4121,7 @@ impl CollectionCore {
 
                     // Insert new key if exists
                     if let Some(new_val) = new_value {
-                        let new_key = crate::transaction::IndexKey::from(new_val);
+                        let new_key = crate::transaction::IndexKey::from(&btree_index.key_for(new_val).to_value());
                         tx.add_index_change(
                             index_name.clone(),
                             crate::transaction::IndexChange {
@@ -1641,9 +4134,9 @@ impl CollectionCore {
                 }
             }
 
-            Ok((1, 1)) // matched_count, modified_count
+            Ok(UpdateResult::new(1, 1))
         } else {
-            Ok((0, 0))
+            Ok(UpdateResult::new(0, 0))
         }
     }
 
@@ -1651,23 +4144,33 @@ impl CollectionCore {
     ///
     /// Note: Index changes are tracked but not yet applied atomically.
     /// See INDEX_CONSISTENCY.md for future two-phase commit implementation.
-    pub fn delete_one_tx(&self, query: &Value, tx: &mut crate::transaction::Transaction) -> Result<u64> {
+    pub fn delete_one_tx(&self, query: &Value, tx: &mut crate::transaction::Transaction) -> Result<DeleteResult> {
         use crate::transaction::Operation;
 
-        // Find the document first
-        let doc = self.find_one(query)?;
+        self.check_writable()?;
+
+        // Find the document first (as stored)
+        let encryptor = self.encryptor();
+        let doc = self.find_one_stored(&*encryptor.encrypt_query(query)?)?;
 
         if let Some(old_doc) = doc {
             // Extract document ID from _id field
             let id_value = old_doc.get("_id")
                 .ok_or_else(|| MongoLiteError::DocumentNotFound)?;
 
+            // Integer ids are i64; a larger u64 is rejected, not wrapped
             let doc_id = match id_value {
                 Value::Number(n) if n.is_i64() => DocumentId::Int(n.as_i64().unwrap()),
-                Value::Number(n) if n.is_u64() => DocumentId::Int(n.as_u64().unwrap() as i64),
                 Value::String(s) => DocumentId::String(s.clone()),
-                _ => return Err(MongoLiteError::Serialization("Invalid _id type".to_string())),
+                _ => return Err(MongoLiteError::Serialization(format!("Invalid _id: {}", id_value))),
             };
+            self.hooks.run(&self.name, HookEvent::BeforeDelete, &mut old_doc.clone())?;
+            let cascades = self.check_referrers_locked(&mut self.storage.write(), std::slice::from_ref(&old_doc))?;
+            if !cascades.is_empty() {
+                return Err(MongoLiteError::InvalidQuery(
+                    "cascading deletes are not supported in transactions".to_string()
+                ));
+            }
 
             // Add operation to transaction
             tx.add_operation(Operation::Delete {
@@ -1684,7 +4187,7 @@ impl CollectionCore {
 
                     // Delete key from index if exists
                     if let Some(old_val) = old_doc.get(field_name) {
-                        let old_key = crate::transaction::IndexKey::from(old_val);
+                        let old_key = crate::transaction::IndexKey::from(&btree_index.key_for(old_val).to_value());
                         tx.add_index_change(
                             index_name.clone(),
                             crate::transaction::IndexChange {
@@ -1697,15 +4200,47 @@ impl CollectionCore {
                 }
             }
 
-            Ok(1) // deleted_count
+            Ok(DeleteResult::new(1))
         } else {
-            Ok(0)
+            Ok(DeleteResult::new(0))
         }
     }
 
     // ========== PRIVATE HELPER METHODS ==========
     // These methods provide internal utility functions for CRUD and query operations
 
+    /// Move a document's index entries from its old version to its new one
+    /// (`new` is None for deletes). Caller must hold the storage lock.
+    fn update_index_entries(&self, doc_id: &DocumentId, old: Option<&Value>, new: Option<&Value>) -> Result<()> {
+        let mut indexes = self.indexes.write();
+
+        for index_name in indexes.list_indexes() {
+            if let Some(index) = indexes.get_btree_index_mut(&index_name) {
+                let field = index.metadata.field.clone();
+                let old_key = old.and_then(|doc| doc.get(&field)).map(|value| index.key_for(value));
+                let new_key = new.and_then(|doc| doc.get(&field)).map(|value| index.key_for(value));
+
+                if old_key == new_key {
+                    continue;
+                }
+                if let Some(key) = &old_key {
+                    index.delete(key, doc_id)?;
+                }
+                if let Some(key) = new_key {
+                    if let Err(e) = index.insert(key, doc_id.clone()) {
+                        // Put the old entry back so the index still matches storage
+                        if let Some(key) = old_key {
+                            index.insert(key, doc_id.clone())?;
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Read a single document by _id using document_catalog (O(1) lookup)
     /// Returns None if document not found or is tombstone
     fn read_document_by_id(&self, doc_id: &DocumentId) -> Result<Option<Value>> {
@@ -1722,8 +4257,8 @@ impl CollectionCore {
         if let Some(&offset) = meta.document_catalog.get(doc_id) {
             eprintln!("🔍 DEBUG: Found doc_id {:?} at offset {}", doc_id, offset);
             let _ = std::io::stderr().flush();
-            let doc_bytes = storage.read_data(offset)?;
-            let doc: Value = serde_json::from_slice(&doc_bytes)?;
+            let doc_bytes = storage.read_data(offset).for_document(&self.name, doc_id)?;
+            let doc: Value = serde_json::from_slice(&doc_bytes).for_document(&self.name, doc_id).at_offset(offset)?;
 
             // Check if document is a tombstone (deleted)
             if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
@@ -1741,29 +4276,241 @@ impl CollectionCore {
         }
     }
 
+    /// First live document matching `query`, read under the caller's storage lock
+    fn first_match_locked(
+        &self,
+        storage: &mut StorageEngine,
+        query_json: &Value,
+        query: &Query,
+        memory: &mut MemoryTracker,
+    ) -> Result<Option<(DocumentId, Value)>> {
+        for (doc_id, doc) in self.targets_locked(storage, query_json, query, memory)? {
+            let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+            if query.matches(&document) {
+                return Ok(Some((doc_id, doc)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Store a new version of a document under the caller's storage lock:
+    /// indexes first (a unique violation aborts before anything is written),
+    /// then the document, the oplog entry and the query cache
+    fn write_version_locked(
+        &self,
+        storage: &mut StorageEngine,
+        doc_id: &DocumentId,
+        old: &Value,
+        new: &Value,
+        oplog_delta: Value,
+    ) -> Result<()> {
+        let new_json = serde_json::to_string(new)?;
+        storage.check_document_size(new_json.len())?;
+        self.update_index_entries(doc_id, Some(old), Some(new))?;
+        storage.write_document(&self.name, doc_id, new_json.as_bytes())?;
+
+        if storage.oplog_enabled() {
+            storage.log_operation("update", &self.name, doc_id, oplog_delta)?;
+        }
+
+        self.query_cache.invalidate_collection(&self.name);
+        Ok(())
+    }
+
+    /// Wait until every write committed so far is on disk (linearizable reads)
+    fn wait_durable(&self) -> Result<()> {
+        let (data_sync, ticket) = {
+            let storage = self.storage.write();
+            let data_sync = storage.data_sync();
+            let ticket = data_sync.register();
+            (data_sync, ticket)
+        };
+        data_sync.sync_to(ticket)
+    }
+
+    /// Views are read-only - only their refresh writes them (see views.rs)
+    fn check_writable(&self) -> Result<()> {
+        self.check_writable_target(&self.name)
+    }
+
+    fn check_writable_target(&self, collection: &str) -> Result<()> {
+        let is_view = self.storage.read().get_collection_meta(collection)
+            .is_some_and(|meta| meta.view.is_some());
+        if is_view {
+            return Err(MongoLiteError::InvalidQuery(format!("'{}' is a view - views are read-only", collection)));
+        }
+        Ok(())
+    }
+
+    /// Encryptor for this collection's encrypted fields with the current key
+    fn encryptor(&self) -> FieldEncryptor {
+        self.storage.read().field_encryptor(&self.name)
+    }
+
+    /// Computed fields of this collection, parsed
+    fn computed(&self) -> Result<ComputedFields> {
+        self.storage.read().computed_fields(&self.name)
+    }
+
+    /// Before `docs` of this collection are deleted: ReferenceViolation if a
+    /// restricting reference points at one of them, else the documents to
+    /// delete afterwards, by collection. Documents among `docs` don't count.
+    fn check_referrers_locked(&self, storage: &mut StorageEngine, docs: &[Value]) -> Result<Vec<(String, Vec<Value>)>> {
+        let referrers = storage.referrers(&self.name);
+        if referrers.is_empty() || docs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<Value> = docs.iter().filter_map(|doc| doc.get("_id").cloned()).collect();
+
+        let mut cascades = Vec::new();
+        for (collection, reference) in referrers {
+            let mut referencing: Vec<Value> = storage.find_referencing(&collection, &reference.field, &ids)?
+                .into_iter()
+                .filter_map(|doc| doc.get("_id").cloned())
+                .collect();
+            if collection == self.name {
+                referencing.retain(|id| !ids.contains(id));
+            }
+            if referencing.is_empty() {
+                continue;
+            }
+            match reference.on_delete {
+                OnDelete::Restrict => return Err(MongoLiteError::ReferenceViolation(format!(
+                    "{} document(s) in '{}' still reference this document through '{}'",
+                    referencing.len(), collection, reference.field
+                ))),
+                OnDelete::Cascade => cascades.push((collection, referencing)),
+            }
+        }
+        Ok(cascades)
+    }
+
+    /// Delete what check_referrers_locked() found; their own references cascade in turn
+    fn run_cascades(&self, cascades: Vec<(String, Vec<Value>)>) -> Result<()> {
+        for (collection, ids) in cascades {
+            CollectionCore::new(collection, Arc::clone(&self.storage))?
+                .delete_many(&serde_json::json!({"_id": {"$in": ids}}))?;
+        }
+        Ok(())
+    }
+
+    /// Run the before-hooks of `event` on a document about to be written
+    fn run_before_hooks(&self, event: HookEvent, document: Document) -> Result<Document> {
+        if !self.hooks.has(&self.name, event) {
+            return Ok(document);
+        }
+        let mut value = Value::from(document);
+        self.hooks.run(&self.name, event, &mut value)?;
+        Ok(Document::from_json(&value.to_string())?)
+    }
+
+    /// Run the after-hooks of `event` on each written document
+    /// (called once the storage lock is released)
+    fn run_after_hooks(&self, event: HookEvent, docs: impl IntoIterator<Item = Value>) -> Result<()> {
+        if !self.hooks.has(&self.name, event) {
+            return Ok(());
+        }
+        for mut doc in docs {
+            self.hooks.run(&self.name, event, &mut doc)?;
+        }
+        Ok(())
+    }
+
+    /// Capture a finished operation if a trace is running (see trace.rs)
+    fn record_trace<T>(&self, op: impl FnOnce() -> TracedOp, result: &Result<T>) {
+        if self.trace.is_active() {
+            self.trace.record(&self.name, op(), result.is_ok());
+        }
+    }
+
+    /// Memory accountant for one operation: `limit` or the database default
+    fn memory_tracker(&self, limit: Option<usize>) -> MemoryTracker {
+        MemoryTracker::new(limit.or_else(|| self.storage.read().query_memory_limit()))
+    }
+
+    /// Accountant for a find or aggregate: its memory limit, time limit and
+    /// cancellation tokens. The operation is listed in current_ops() - and
+    /// can be killed - until the returned guard is dropped.
+    fn operation_tracker(
+        &self,
+        op: &'static str,
+        query: &Value,
+        options: &crate::find_options::FindOptions,
+    ) -> (MemoryTracker, OperationGuard) {
+        let guard = self.storage.read().operations().register(op, &self.name, query);
+        let mut memory = self.memory_tracker(options.max_memory_bytes).with_cancellation(guard.token());
+        if let Some(max_time_ms) = options.max_time_ms {
+            memory = memory.with_max_time_ms(max_time_ms);
+        }
+        if let Some(token) = &options.cancellation {
+            memory = memory.with_cancellation(token.clone());
+        }
+        (memory, guard)
+    }
+
     /// Scan documents via document_catalog instead of full file scan
     /// Much faster than scan_documents() for large collections
-    fn scan_documents_via_catalog(&self) -> Result<HashMap<DocumentId, Value>> {
+    fn scan_documents_via_catalog(&self, memory: &mut MemoryTracker) -> Result<Vec<(DocumentId, Value)>> {
+        let mut storage = self.storage.write();
+        self.scan_catalog_locked(&mut storage, memory)
+    }
+
+    /// scan_documents_via_catalog() for callers already holding the storage lock
+    ///
+    /// Documents come in natural order - the order of their records in the
+    /// data file - so scans without a sort return the same order every time.
+    /// Inserts append, but an updated document may move (to the end, or into
+    /// a freed region), so natural order is not insertion order.
+    fn scan_catalog_locked(&self, storage: &mut StorageEngine, memory: &mut MemoryTracker) -> Result<Vec<(DocumentId, Value)>> {
+        let catalog = self.natural_order(storage)?;
+        self.read_catalog_locked(storage, &catalog, memory)
+    }
+
+    /// scan_documents_via_catalog() without the file segments the
+    /// collection's Bloom filters rule out for `query` (see storage/bloom.rs)
+    fn scan_candidates(&self, query: &Query, memory: &mut MemoryTracker) -> Result<Vec<(DocumentId, Value)>> {
         let mut storage = self.storage.write();
+        let catalog = self.candidates(&mut storage, query)?;
+        self.read_catalog_locked(&mut storage, &catalog, memory)
+    }
 
-        // Clone the catalog to avoid borrow checker issues
-        let catalog = {
-            let meta = storage.get_collection_meta(&self.name)
-                .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
-            meta.document_catalog.clone()
+    /// Live documents that may match `query`, read under the caller's storage
+    /// lock: only the catalog entry an _id equality names (see id_equality),
+    /// else the documents scan_candidates() reads
+    fn targets_locked(
+        &self,
+        storage: &mut StorageEngine,
+        query_json: &Value,
+        query: &Query,
+        memory: &mut MemoryTracker,
+    ) -> Result<Vec<(DocumentId, Value)>> {
+        let catalog = match id_equality(query_json) {
+            Some(doc_id) => {
+                let meta = storage.get_collection_meta(&self.name)
+                    .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+                meta.document_catalog.get(&doc_id)
+                    .map(|&offset| vec![(doc_id, offset)])
+                    .unwrap_or_default()
+            }
+            None => self.candidates(storage, query)?,
         };
+        self.read_catalog_locked(storage, &catalog, memory)
+    }
 
-        let mut docs_by_id: HashMap<DocumentId, Value> = HashMap::new();
+    /// Live documents of catalog entries, tombstones and unreadable records skipped
+    fn read_catalog_locked(&self, storage: &mut StorageEngine, catalog: &[(DocumentId, u64)], memory: &mut MemoryTracker) -> Result<Vec<(DocumentId, Value)>> {
+        let mut docs_by_id = Vec::with_capacity(catalog.len());
 
         // Iterate over catalog instead of sequential file scan (direct DocumentId iteration!)
-        for (doc_id, offset) in &catalog {
+        for (doc_id, offset) in catalog {
             match storage.read_data(*offset) {
                 Ok(doc_bytes) => {
-                    let doc: Value = serde_json::from_slice(&doc_bytes)?;
+                    let doc: Value = serde_json::from_slice(&doc_bytes).for_document(&self.name, doc_id).at_offset(*offset)?;
 
                     // Skip tombstones (deleted documents)
                     if !doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
-                        docs_by_id.insert(doc_id.clone(), doc);
+                        memory.charge_value(&doc)?;
+                        docs_by_id.push((doc_id.clone(), doc));
                     }
                 }
                 Err(_) => continue, // Skip corrupted entries
@@ -1779,12 +4526,111 @@ impl CollectionCore {
     // Dead code removed - use scan_documents_via_catalog() instead
     // which is faster (O(n) catalog iteration vs O(n) file scan)
 
+    /// Catalog entries ordered by offset
+    fn natural_order(&self, storage: &StorageEngine) -> Result<Vec<(DocumentId, u64)>> {
+        let meta = storage.get_collection_meta(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?;
+        let mut catalog: Vec<(DocumentId, u64)> = meta.document_catalog.iter()
+            .map(|(doc_id, offset)| (doc_id.clone(), *offset))
+            .collect();
+        catalog.sort_unstable_by_key(|(_, offset)| *offset);
+        Ok(catalog)
+    }
+
+    /// natural_order() of the documents that may match `query`, going by the
+    /// collection's Bloom filters
+    fn candidates(&self, storage: &mut StorageEngine, query: &Query) -> Result<Vec<(DocumentId, u64)>> {
+        let skipped = storage.bloom_skipped_segments(&self.name, query)?;
+        let mut catalog = self.natural_order(storage)?;
+        if !skipped.is_empty() {
+            catalog.retain(|(_, offset)| !skipped.contains(&bloom_segment(*offset)));
+        }
+        Ok(catalog)
+    }
+
+    /// Full scan of the documents matching a query, decoding only `fields`
+    /// of each (projection pushdown - see find_options::pushdown_fields)
+    fn scan_fields(&self, query_json: &Value, fields: &HashSet<String>, memory: &mut MemoryTracker) -> Result<Vec<Value>> {
+        let query = Query::from_json(query_json)?;
+        let mut fields = fields.clone();
+        fields.insert("_tombstone".to_string());
+
+        let mut storage = self.storage.write();
+        let mut results = Vec::new();
+        for (doc_id, offset) in self.candidates(&mut storage, &query)? {
+            memory.check_interrupt()?;
+            let Ok(doc_bytes) = storage.read_data(offset) else {
+                continue; // Skip corrupted entries, as scan_catalog_locked does
+            };
+            let mut doc = crate::find_options::decode_fields(&doc_bytes, &fields)
+                .for_document(&self.name, &doc_id).at_offset(offset)?;
+            if let Some(Value::Bool(true)) = doc.as_object_mut().and_then(|map| map.remove("_tombstone")) {
+                continue;
+            }
+            let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+            if query.matches(&document) {
+                memory.charge_value(&doc)?;
+                results.push(doc);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Full scan of the documents matching a query that keeps only the first
+    /// `limit` in `sort` order (see top_k.rs) - memory for those instead of
+    /// every match. Decodes only `fields` when given; the documents come
+    /// back decrypted, as the sort has to see them.
+    fn scan_top_k(
+        &self,
+        query_json: &Value,
+        fields: Option<&HashSet<String>>,
+        sort: &[(String, i32)],
+        limit: usize,
+        encryptor: &FieldEncryptor,
+        memory: &mut MemoryTracker,
+    ) -> Result<Vec<Value>> {
+        let query = Query::from_json(query_json)?;
+        let fields = fields.map(|fields| {
+            let mut fields = fields.clone();
+            fields.insert("_tombstone".to_string());
+            fields
+        });
+        let order = crate::find_options::sort_order(sort);
+        let mut top = crate::top_k::TopK::new(limit, &order);
+
+        let mut storage = self.storage.write();
+        for (doc_id, offset) in self.candidates(&mut storage, &query)? {
+            memory.check_interrupt()?;
+            let Ok(doc_bytes) = storage.read_data(offset) else {
+                continue; // Skip corrupted entries, as scan_catalog_locked does
+            };
+            let mut doc = match &fields {
+                Some(fields) => crate::find_options::decode_fields(&doc_bytes, fields),
+                None => serde_json::from_slice::<Value>(&doc_bytes),
+            }.for_document(&self.name, &doc_id).at_offset(offset)?;
+            if let Some(Value::Bool(true)) = doc.as_object_mut().and_then(|map| map.remove("_tombstone")) {
+                continue;
+            }
+            let document = Document::from_json(&serde_json::to_string(&doc)?)?;
+            if query.matches(&document) {
+                encryptor.decrypt_document(&mut doc)?;
+                memory.charge_value(&doc)?;
+                if let Some(dropped) = top.push(doc) {
+                    memory.release(crate::memory::estimate_size(&dropped));
+                }
+            }
+        }
+        Ok(top.into_sorted_vec())
+    }
+
     /// Filter documents by query and exclude tombstones
     /// Returns only live documents matching the query
-    fn filter_documents(&self, docs_by_id: HashMap<DocumentId, Value>, query: &Query) -> Result<Vec<Value>> {
+    fn filter_documents(&self, docs_by_id: Vec<(DocumentId, Value)>, query: &Query, memory: &MemoryTracker) -> Result<Vec<Value>> {
         let mut results = Vec::new();
 
         for (_, doc) in docs_by_id {
+            memory.check_interrupt()?;
+
             // Skip tombstones
             if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
                 continue;
@@ -1802,3 +4648,79 @@ impl CollectionCore {
         Ok(results)
     }
 }
+
+/// Rewrite an index file written by another format version or for another
+/// index of the same name from the (just rebuilt) tree; returns the save id
+/// of the rewritten file. An index whose file is not rewritten is rebuilt on
+/// open, so failing to rewrite one is only logged.
+fn refresh_stale_index_file(index: &mut BPlusTree, path: &std::path::Path) -> Option<String> {
+    let result = match check_index_file(path, &index.metadata.uuid) {
+        Ok(IndexFileStatus::Stale(reason)) => {
+            eprintln!("WARN: Index file {:?} is stale ({}) - rewriting it", path, reason);
+            let final_path = path.to_path_buf();
+            index.prepare_changes(&final_path)
+                .and_then(|temp_path| BPlusTree::commit_prepared_changes(&temp_path, &final_path))
+                .map(|_| index.saved_stamp().map(|stamp| stamp.save_id.clone()))
+        }
+        Ok(_) => Ok(None),
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("WARN: Could not rewrite index file {:?}: {:?}", path, e);
+        None
+    })
+}
+
+/// The tree in the index file at `path`, read whole, if the file is the one
+/// the collection metadata lists for `index` (`save_id`) - see
+/// CollectionMeta::index_files. None (and the index is rebuilt) otherwise.
+fn read_current_index_file(index: &BPlusTree, path: &std::path::Path, save_id: &str) -> Option<BPlusTree> {
+    let read = BPlusTree::read_from_file(path, index.metadata.clone()).and_then(|(tree, stamp)| {
+        match IndexFileStatus::of(Some(&stamp), &index.metadata.uuid) {
+            IndexFileStatus::Stale(reason) => Err(MongoLiteError::IndexError(format!("stale: {}", reason))),
+            _ if stamp.save_id != save_id => Err(MongoLiteError::IndexError(
+                format!("save {:?}, expected {:?}", stamp.save_id, save_id)
+            )),
+            _ => Ok(tree),
+        }
+    });
+    match read {
+        Ok(tree) => Some(tree),
+        Err(e) => {
+            eprintln!("WARN: Index file {:?} is not current ({}) - rebuilding the index", path, e);
+            None
+        }
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7396) to `target` - used by $mergeObjects
+/// The _id a query pins to one document - `{"_id": X}` or `{"_id": {"$eq": X}}`,
+/// next to any conditions on other fields - so it can be looked up in the
+/// document catalog instead of scanned for
+fn id_equality(query_json: &Value) -> Option<DocumentId> {
+    let id = match query_json.get("_id")? {
+        Value::Object(operators) if operators.len() == 1 => operators.get("$eq")?,
+        Value::Object(_) => return None,
+        id => id,
+    };
+    serde_json::from_value(id.clone()).ok()
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
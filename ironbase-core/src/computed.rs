@@ -0,0 +1,121 @@
+// ironbase-core/src/computed.rs
+// Stored computed fields: expression results the engine writes into every
+// document on insert and update, so they can be queried and indexed
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::document::Document;
+use crate::expression::Expression;
+use crate::error::{Result, MongoLiteError};
+
+/// A computed field as stored in its collection's metadata
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComputedField {
+    pub name: String,
+    /// Aggregation expression (see expression.rs), e.g.
+    /// {"$concat": ["$first", " ", "$last"]}
+    pub expression: Value,
+}
+
+impl ComputedField {
+    pub fn new(name: &str, expression: Value) -> Result<Self> {
+        if name.is_empty() || name.starts_with('$') || name.contains('.') {
+            return Err(MongoLiteError::InvalidQuery(format!(
+                "invalid computed field name '{}' (must be a top-level field)", name
+            )));
+        }
+        if name == "_id" || name == "_collection" {
+            return Err(MongoLiteError::InvalidQuery(format!("'{}' cannot be computed", name)));
+        }
+        Expression::from_json(&expression)?;
+        Ok(ComputedField { name: name.to_string(), expression })
+    }
+}
+
+/// The parsed computed fields of one collection, in definition order
+#[derive(Debug, Clone, Default)]
+pub struct ComputedFields {
+    fields: Vec<(String, Expression)>,
+}
+
+impl ComputedFields {
+    pub fn new(fields: &[ComputedField]) -> Result<Self> {
+        let fields = fields.iter()
+            .map(|field| Ok((field.name.clone(), Expression::from_json(&field.expression)?)))
+            .collect::<Result<_>>()?;
+        Ok(ComputedFields { fields })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.fields.iter().any(|(field, _)| field == name)
+    }
+
+    /// (Re)compute every field of `doc`. Each field sees the ones defined
+    /// before it; a null result removes the field, so documents without the
+    /// inputs don't collide on null in a unique index
+    pub fn apply(&self, doc: &mut Map<String, Value>) -> Result<()> {
+        for (name, expression) in &self.fields {
+            let value = expression.evaluate_fields(doc)?;
+            if value.is_null() {
+                doc.remove(name);
+            } else {
+                doc.insert(name.clone(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as apply, for a Document
+    pub fn apply_document(&self, doc: &mut Document) -> Result<()> {
+        for (name, expression) in &self.fields {
+            let value = expression.evaluate_document(doc)?;
+            if value.is_null() {
+                doc.remove(name);
+            } else {
+                doc.set(name.clone(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as apply, for a document held as a JSON value
+    pub fn apply_value(&self, doc: &mut Value) -> Result<()> {
+        match doc {
+            Value::Object(map) => self.apply(map),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_in_order() {
+        let fields = ComputedFields::new(&[
+            ComputedField::new("full_name", json!({"$concat": ["$first", " ", "$last"]})).unwrap(),
+            ComputedField::new("key", json!({"$toLower": "$full_name"})).unwrap(),
+        ]).unwrap();
+
+        let mut doc = json!({"first": "Ada", "last": "Lovelace"}).as_object().unwrap().clone();
+        fields.apply(&mut doc).unwrap();
+        assert_eq!(doc["full_name"], json!("Ada Lovelace"));
+        assert_eq!(doc["key"], json!("ada lovelace"));
+
+        let mut doc = json!({"full_name": "stale", "key": "stale"}).as_object().unwrap().clone();
+        fields.apply(&mut doc).unwrap();
+        assert!(!doc.contains_key("full_name"));
+        assert_eq!(doc["key"], json!(""));
+
+        assert!(ComputedField::new("_id", json!("$x")).is_err());
+        assert!(ComputedField::new("a.b", json!("$x")).is_err());
+        assert!(ComputedField::new("x", json!({"$nope": 1})).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+// ironbase-core/src/contention.rs
+// Lock contention telemetry for the storage and index locks
+//
+// Every acquisition first tries the lock without blocking; only when that
+// fails is the wait timed, so an uncontended lock costs one extra atomic
+// increment. The counters are shared by every lock of a kind (all collection
+// handles' index locks add up to one "indexes" entry) and reported by
+// DatabaseCore::metrics().
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde::Serialize;
+
+/// Counters of one lock mode (shared or exclusive)
+#[derive(Debug, Default)]
+struct ModeCounters {
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+impl ModeCounters {
+    fn record(&self, wait: Option<Duration>) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if let Some(wait) = wait {
+            let nanos = wait.as_nanos().min(u64::MAX as u128) as u64;
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            self.wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+            self.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> LockModeStats {
+        let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+        let contended = self.contended.load(Ordering::Relaxed);
+        LockModeStats {
+            acquisitions,
+            contended,
+            contention_ratio: if acquisitions == 0 { 0.0 } else { contended as f64 / acquisitions as f64 },
+            total_wait: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.acquisitions.store(0, Ordering::Relaxed);
+        self.contended.store(0, Ordering::Relaxed);
+        self.wait_nanos.store(0, Ordering::Relaxed);
+        self.max_wait_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Wait counters of one kind of lock
+#[derive(Debug, Default)]
+pub struct LockCounters {
+    read: ModeCounters,
+    write: ModeCounters,
+}
+
+impl LockCounters {
+    pub fn snapshot(&self) -> LockStats {
+        LockStats {
+            read: self.read.snapshot(),
+            write: self.write.snapshot(),
+        }
+    }
+
+    fn reset(&self) {
+        self.read.reset();
+        self.write.reset();
+    }
+}
+
+/// Counters of every instrumented lock of a database
+#[derive(Debug, Default)]
+pub struct LockMetrics {
+    pub storage: LockCounters,
+    pub indexes: LockCounters,
+}
+
+impl LockMetrics {
+    pub fn reset(&self) {
+        self.storage.reset();
+        self.indexes.reset();
+    }
+}
+
+/// Acquisitions and waits of one lock mode
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LockModeStats {
+    pub acquisitions: u64,
+    /// Acquisitions that had to wait for another holder
+    pub contended: u64,
+    pub contention_ratio: f64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl LockModeStats {
+    /// Average wait of the acquisitions that waited
+    pub fn mean_wait(&self) -> Duration {
+        if self.contended == 0 {
+            Duration::ZERO
+        } else {
+            self.total_wait / self.contended as u32
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "acquisitions": self.acquisitions,
+            "contended": self.contended,
+            "contention_ratio": self.contention_ratio,
+            "total_wait_ms": self.total_wait.as_secs_f64() * 1000.0,
+            "mean_wait_ms": self.mean_wait().as_secs_f64() * 1000.0,
+            "max_wait_ms": self.max_wait.as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// Shared (read) and exclusive (write) statistics of a lock
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LockStats {
+    pub read: LockModeStats,
+    pub write: LockModeStats,
+}
+
+impl LockStats {
+    pub fn total_wait(&self) -> Duration {
+        self.read.total_wait + self.write.total_wait
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "read": self.read.to_json(),
+            "write": self.write.to_json(),
+            "total_wait_ms": self.total_wait().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// Which counters a lock reports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Storage,
+    Indexes,
+}
+
+/// A parking_lot RwLock that records how long acquisitions wait
+#[derive(Debug)]
+pub struct TimedRwLock<T> {
+    lock: RwLock<T>,
+    metrics: Arc<LockMetrics>,
+    kind: LockKind,
+}
+
+impl<T> TimedRwLock<T> {
+    pub fn new(value: T, metrics: Arc<LockMetrics>, kind: LockKind) -> Self {
+        TimedRwLock {
+            lock: RwLock::new(value),
+            metrics,
+            kind,
+        }
+    }
+
+    fn counters(&self) -> &LockCounters {
+        match self.kind {
+            LockKind::Storage => &self.metrics.storage,
+            LockKind::Indexes => &self.metrics.indexes,
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        if let Some(guard) = self.lock.try_read() {
+            self.counters().read.record(None);
+            return guard;
+        }
+        let start = Instant::now();
+        let guard = self.lock.read();
+        self.counters().read.record(Some(start.elapsed()));
+        guard
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        if let Some(guard) = self.lock.try_write() {
+            self.counters().write.record(None);
+            return guard;
+        }
+        let start = Instant::now();
+        let guard = self.lock.write();
+        self.counters().write.record(Some(start.elapsed()));
+        guard
+    }
+
+    /// Counters this lock (and its siblings of the same kind) report to
+    pub fn metrics(&self) -> Arc<LockMetrics> {
+        Arc::clone(&self.metrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uncontended_acquisitions_do_not_wait() {
+        let lock = TimedRwLock::new(0, Arc::new(LockMetrics::default()), LockKind::Storage);
+        *lock.write() += 1;
+        let _a = lock.read();
+        let _b = lock.read();
+
+        let stats = lock.metrics().storage.snapshot();
+        assert_eq!((stats.read.acquisitions, stats.read.contended), (2, 0));
+        assert_eq!((stats.write.acquisitions, stats.write.contended), (1, 0));
+        assert_eq!(stats.total_wait(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_blocked_writer_records_wait() {
+        let metrics = Arc::new(LockMetrics::default());
+        let lock = Arc::new(TimedRwLock::new(0, Arc::clone(&metrics), LockKind::Indexes));
+
+        let guard = lock.read();
+        let writer = {
+            let lock = Arc::clone(&lock);
+            std::thread::spawn(move || *lock.write() += 1)
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        writer.join().unwrap();
+
+        let stats = metrics.indexes.snapshot();
+        assert_eq!(stats.write.contended, 1);
+        assert!(stats.write.max_wait >= Duration::from_millis(40));
+        assert_eq!(stats.write.mean_wait(), stats.write.total_wait);
+        assert_eq!(metrics.storage.snapshot(), LockStats::default());
+
+        metrics.reset();
+        assert_eq!(metrics.indexes.snapshot(), LockStats::default());
+    }
+}
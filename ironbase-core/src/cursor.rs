@@ -0,0 +1,206 @@
+// ironbase-core/src/cursor.rs
+// Server-side cursors: a result set handed out a batch at a time
+//
+// A front end serving clients over a network (HTTP, a wire protocol) cannot
+// return a large result in one response. It opens a cursor instead: open()
+// returns the first batch and a cursor id, get_more() the following ones,
+// and the cursor id comes back as 0 with the last batch. The documents are
+// pulled from the source as batches are asked for, so a cursor over
+// CollectionCore::find_iter() holds a snapshot scan, not the result set.
+//
+// Cursors belong to the connection that opened them - another connection's
+// ids are not found - and a connection holds at most max_per_connection.
+// A cursor not asked for more within idle_timeout is closed; the registry
+// reaps them when used, or whenever reap_expired() is called. Close a
+// connection's cursors with close_connection() when it goes away.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use crate::error::{MongoLiteError, Result};
+
+/// Identifier of an open cursor; 0 in a batch means the cursor is done
+pub type CursorId = u64;
+
+/// Identifier a front end gives each client connection
+pub type ConnectionId = u64;
+
+/// Documents a cursor hands out
+pub type CursorSource = Box<dyn Iterator<Item = Result<Value>> + Send>;
+
+/// Cursor limits of a CursorRegistry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorConfig {
+    /// Documents per batch when a request names no batch size
+    pub batch_size: usize,
+    /// A cursor not asked for more this long is closed
+    pub idle_timeout: Duration,
+    /// Open cursors one connection may hold
+    pub max_per_connection: usize,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        CursorConfig {
+            batch_size: 101,
+            idle_timeout: Duration::from_secs(600),
+            max_per_connection: 100,
+        }
+    }
+}
+
+/// One batch of a cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorBatch {
+    /// The cursor to ask for more, 0 once this is the last batch
+    pub cursor_id: CursorId,
+    pub documents: Vec<Value>,
+    /// The batch open() returned
+    pub first: bool,
+}
+
+impl CursorBatch {
+    /// Whether the cursor is done
+    pub fn exhausted(&self) -> bool {
+        self.cursor_id == 0
+    }
+
+    /// The batch as a response: `{"cursor": {"id", "firstBatch" | "nextBatch"}}`
+    pub fn to_json(&self) -> Value {
+        let mut cursor = serde_json::json!({"id": self.cursor_id});
+        let key = if self.first { "firstBatch" } else { "nextBatch" };
+        cursor[key] = Value::from(self.documents.clone());
+        serde_json::json!({"cursor": cursor})
+    }
+}
+
+struct OpenCursor {
+    connection: ConnectionId,
+    source: CursorSource,
+    /// Read ahead of the last batch
+    peeked: Option<Value>,
+    last_used: Instant,
+}
+
+/// Open cursors of all connections to one database (see cursor.rs)
+pub struct CursorRegistry {
+    config: CursorConfig,
+    next_id: AtomicU64,
+    open: Mutex<HashMap<CursorId, OpenCursor>>,
+}
+
+impl CursorRegistry {
+    pub fn new(config: CursorConfig) -> Self {
+        CursorRegistry {
+            config,
+            next_id: AtomicU64::new(0),
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> CursorConfig {
+        self.config
+    }
+
+    /// Open a cursor over `source` for `connection` and read its first
+    /// batch; no cursor stays open if that is all there is
+    pub fn open(&self, connection: ConnectionId, source: CursorSource, batch_size: Option<usize>) -> Result<CursorBatch> {
+        self.reap_expired();
+        let open = self.open.lock().values().filter(|cursor| cursor.connection == connection).count();
+        if open >= self.config.max_per_connection {
+            return Err(MongoLiteError::TooManyCursors { limit: self.config.max_per_connection });
+        }
+
+        let mut cursor = OpenCursor { connection, source, peeked: None, last_used: Instant::now() };
+        let (documents, exhausted) = self.read_batch(&mut cursor, batch_size)?;
+        let cursor_id = if exhausted {
+            0
+        } else {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+            self.open.lock().insert(id, cursor);
+            id
+        };
+        Ok(CursorBatch { cursor_id, documents, first: true })
+    }
+
+    /// The next batch of a cursor `connection` opened. A cursor is
+    /// unavailable while a get_more() on it runs - a concurrent one does
+    /// not find it - and closed once exhausted or when reading fails.
+    pub fn get_more(&self, connection: ConnectionId, cursor_id: CursorId, batch_size: Option<usize>) -> Result<CursorBatch> {
+        self.reap_expired();
+        let mut cursor = {
+            let mut open = self.open.lock();
+            match open.get(&cursor_id) {
+                Some(cursor) if cursor.connection == connection => open.remove(&cursor_id),
+                _ => None,
+            }
+        }
+        .ok_or(MongoLiteError::CursorNotFound(cursor_id))?;
+
+        let (documents, exhausted) = self.read_batch(&mut cursor, batch_size)?;
+        if exhausted {
+            return Ok(CursorBatch { cursor_id: 0, documents, first: false });
+        }
+        cursor.last_used = Instant::now();
+        self.open.lock().insert(cursor_id, cursor);
+        Ok(CursorBatch { cursor_id, documents, first: false })
+    }
+
+    /// Close a cursor of `connection`; false if it has none by that id
+    pub fn kill(&self, connection: ConnectionId, cursor_id: CursorId) -> bool {
+        let mut open = self.open.lock();
+        match open.get(&cursor_id) {
+            Some(cursor) if cursor.connection == connection => open.remove(&cursor_id).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Close all cursors of a connection; returns how many were open
+    pub fn close_connection(&self, connection: ConnectionId) -> usize {
+        let mut open = self.open.lock();
+        let before = open.len();
+        open.retain(|_, cursor| cursor.connection != connection);
+        before - open.len()
+    }
+
+    /// Close the cursors idle for longer than the idle timeout; returns
+    /// how many were
+    pub fn reap_expired(&self) -> usize {
+        let timeout = self.config.idle_timeout;
+        let mut open = self.open.lock();
+        let before = open.len();
+        open.retain(|_, cursor| cursor.last_used.elapsed() < timeout);
+        before - open.len()
+    }
+
+    /// Open cursors, of all connections
+    pub fn open_cursors(&self) -> usize {
+        self.open.lock().len()
+    }
+
+    /// Up to a batch of documents, and whether the source is done. Reads
+    /// one document ahead so the last batch closes the cursor.
+    fn read_batch(&self, cursor: &mut OpenCursor, batch_size: Option<usize>) -> Result<(Vec<Value>, bool)> {
+        let batch_size = batch_size.unwrap_or(self.config.batch_size).max(1);
+        let mut documents = Vec::with_capacity(batch_size.min(1024));
+        documents.extend(cursor.peeked.take());
+        while documents.len() < batch_size {
+            match cursor.source.next() {
+                Some(doc) => documents.push(doc?),
+                None => return Ok((documents, true)),
+            }
+        }
+        cursor.peeked = cursor.source.next().transpose()?;
+        Ok((documents, cursor.peeked.is_none()))
+    }
+}
+
+impl Default for CursorRegistry {
+    fn default() -> Self {
+        Self::new(CursorConfig::default())
+    }
+}
@@ -2,16 +2,18 @@
 // Pure Rust database API - NO PyO3 dependencies
 
 use std::sync::Arc;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::collections::HashMap;
 
-use crate::storage::StorageEngine;
+use crate::storage::{StorageConfig, StorageEngine};
 use crate::collection_core::CollectionCore;
-use crate::error::Result;
-use crate::transaction::{Transaction, TransactionId};
+use crate::error::{MongoLiteError, Result};
+use crate::transaction::{Durability, Transaction, TransactionId};
 use crate::document::DocumentId;
+use crate::lock_manager::LockManager;
+use crate::contention::{LockKind, LockMetrics, TimedRwLock};
 use serde_json::Value;
 
 /// Convert transaction::IndexKey to index::IndexKey
@@ -22,32 +24,50 @@ fn convert_index_key(tx_key: &crate::transaction::IndexKey) -> crate::index::Ind
         crate::transaction::IndexKey::Float(f) => crate::index::IndexKey::Float(crate::index::OrderedFloat(f.value())),
         crate::transaction::IndexKey::Bool(b) => crate::index::IndexKey::Bool(*b),
         crate::transaction::IndexKey::Null => crate::index::IndexKey::Null,
+        crate::transaction::IndexKey::UInt(u) => crate::index::IndexKey::UInt(*u),
     }
 }
 
+/// Commands DatabaseCore::command() runs
+const ADMIN_COMMANDS: &[&str] = &[
+    "ping", "listCollections", "dbStats", "collStats", "serverStatus",
+    "compact", "validate", "analyze", "drop", "currentOp", "killOp",
+];
+
 /// Pure Rust MongoLite Database - language-independent
 pub struct DatabaseCore {
-    storage: Arc<RwLock<StorageEngine>>,
+    storage: Arc<TimedRwLock<StorageEngine>>,
     db_path: String,
     next_tx_id: AtomicU64,
-    active_transactions: Arc<RwLock<std::collections::HashMap<TransactionId, Transaction>>>,
+    /// Active transactions, each behind its own mutex so transactions
+    /// working on different documents do not wait for each other
+    active_transactions: Arc<RwLock<std::collections::HashMap<TransactionId, Arc<Mutex<Transaction>>>>>,
+    /// Document write latches held by active transactions
+    locks: Arc<LockManager>,
 }
 
 impl DatabaseCore {
     /// Open or create database
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, &StorageConfig::default())
+    }
+
+    /// Open or create database with a storage configuration
+    /// (the page size only applies when the file is created)
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: &StorageConfig) -> Result<Self> {
         let path_str = path.as_ref().to_string_lossy().to_string();
-        let mut storage = StorageEngine::open(&path_str)?;
+        let mut storage = StorageEngine::open_with_config(&path_str, config)?;
 
         // Recover from WAL (includes both data and index changes)
         let (_wal_entries, recovered_index_changes) = storage.recover_from_wal()?;
 
         // Create DatabaseCore instance
         let db = DatabaseCore {
-            storage: Arc::new(RwLock::new(storage)),
+            storage: Arc::new(TimedRwLock::new(storage, Arc::new(LockMetrics::default()), LockKind::Storage)),
             db_path: path_str,
             next_tx_id: AtomicU64::new(1),
             active_transactions: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            locks: Arc::new(LockManager::new()),
         };
 
         // Apply recovered index changes to collections
@@ -74,13 +94,17 @@ impl DatabaseCore {
                         // Convert transaction::IndexKey to index::IndexKey
                         let index_key = convert_index_key(&change.key);
 
+                        // Indexes are built from the recovered documents, so
+                        // the change may be in already
+                        let present = btree_index.search(&index_key)?.contains(&change.doc_id);
                         match change.operation {
-                            crate::transaction::IndexOperation::Insert => {
+                            crate::transaction::IndexOperation::Insert if !present => {
                                 btree_index.insert(index_key, change.doc_id)?;
                             }
-                            crate::transaction::IndexOperation::Delete => {
+                            crate::transaction::IndexOperation::Delete if present => {
                                 btree_index.delete(&index_key, &change.doc_id)?;
                             }
+                            _ => {}
                         }
                     }
                 }
@@ -91,14 +115,34 @@ impl DatabaseCore {
     }
 
     /// Get collection (creates if doesn't exist)
+    /// Plain views have no documents of their own - see view()
     pub fn collection(&self, name: &str) -> Result<CollectionCore> {
+        let plain_view = self.storage.read().get_collection_meta(name)
+            .and_then(|meta| meta.view.as_ref())
+            .is_some_and(|view| view.materialized.is_none());
+        if plain_view {
+            return Err(crate::error::MongoLiteError::InvalidQuery(format!(
+                "'{}' is a view - read it with DatabaseCore::view()", name
+            )));
+        }
         CollectionCore::new(name.to_string(), Arc::clone(&self.storage))
     }
 
-    /// List all collection names
+    /// Get or create a collection of typed documents (see typed::Collection)
+    pub fn typed_collection<T>(&self, name: &str) -> Result<crate::typed::Collection<T>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        Ok(crate::typed::Collection::new(self.collection(name)?))
+    }
+
+    /// List all collection names (without the users, sequences and statistics collections)
     pub fn list_collections(&self) -> Vec<String> {
         let storage = self.storage.read();
-        storage.list_collections()
+        let mut names = storage.list_collections();
+        let hidden = [crate::auth::USERS_COLLECTION, crate::sequence::SEQUENCES_COLLECTION, crate::statistics::STATS_COLLECTION];
+        names.retain(|name| !hidden.contains(&name.as_str()));
+        names
     }
 
     /// Drop collection
@@ -119,17 +163,511 @@ impl DatabaseCore {
         storage.stats()
     }
 
+    /// Wait statistics of the storage lock and the (combined) index locks
+    /// since open or the last reset_metrics()
+    pub fn lock_stats(&self) -> (crate::contention::LockStats, crate::contention::LockStats) {
+        let metrics = self.storage.metrics();
+        (metrics.storage.snapshot(), metrics.indexes.snapshot())
+    }
+
+    /// Runtime metrics as JSON, currently the lock contention report:
+    /// acquisitions, how many had to wait and for how long, per lock and
+    /// mode. A high wait total next to low throughput means threads are
+    /// queueing on locks rather than on disk.
+    pub fn metrics(&self) -> serde_json::Value {
+        let (storage, indexes) = self.lock_stats();
+        let total_wait = storage.total_wait() + indexes.total_wait();
+        let most_contended = if total_wait.is_zero() {
+            Value::Null
+        } else if storage.total_wait() >= indexes.total_wait() {
+            Value::from("storage")
+        } else {
+            Value::from("indexes")
+        };
+        serde_json::json!({
+            "locks": {
+                "storage": storage.to_json(),
+                "indexes": indexes.to_json(),
+            },
+            "contention": {
+                "total_wait_ms": total_wait.as_secs_f64() * 1000.0,
+                "most_contended": most_contended,
+            },
+        })
+    }
+
+    /// Zero the lock contention counters
+    pub fn reset_metrics(&self) {
+        self.storage.metrics().reset();
+    }
+
+    /// Delete the database: its data file, WAL, journal and index files
+    ///
+    /// Runs under the exclusive storage lock once everything pending is
+    /// flushed, so no write is half-done when the files go. Handles still
+    /// open on the database (this one, collections, sessions) keep working
+    /// on the deleted files; nothing they write is kept.
+    pub fn drop_database(&self) -> Result<()> {
+        self.storage.write().remove_files()
+    }
+
+    /// Run an administrative command and return its result with `"ok": 1`.
+    /// The command is the key of `command` naming one of these, other keys
+    /// are its options:
+    ///
+    /// - `{"ping": 1}`
+    /// - `{"listCollections": 1}`
+    /// - `{"dbStats": 1}`, `{"collStats": "<collection>"}`
+    /// - `{"serverStatus": 1}` - storage stats, lock metrics, snapshots and running operations
+    /// - `{"compact": 1}` (whole file) or `{"compact": "<collection>"}`
+    /// - `{"validate": "<collection>", "full": <bool>}`
+    /// - `{"analyze": "<collection>"}`
+    /// - `{"drop": "<collection>"}`
+    /// - `{"currentOp": 1}`, `{"killOp": 1, "op": <id>}`
+    pub fn command(&self, command: &Value) -> Result<Value> {
+        let map = command.as_object()
+            .ok_or_else(|| MongoLiteError::InvalidQuery("command must be an object".to_string()))?;
+        let (name, argument) = map.iter()
+            .find(|(key, _)| ADMIN_COMMANDS.contains(&key.as_str()))
+            .ok_or_else(|| MongoLiteError::InvalidQuery(format!(
+                "unknown command {}", serde_json::to_string(&map.keys().collect::<Vec<_>>()).unwrap_or_default()
+            )))?;
+        let collection = || argument.as_str()
+            .ok_or_else(|| MongoLiteError::InvalidQuery(format!("{}: expected a collection name", name)));
+
+        let mut result = match name.as_str() {
+            "ping" => serde_json::json!({}),
+            "listCollections" => serde_json::json!({"collections": self.list_collections()}),
+            "dbStats" => self.stats(),
+            "collStats" => self.collection(collection()?)?.stats()?,
+            "serverStatus" => {
+                let mvcc = self.mvcc_stats();
+                serde_json::json!({
+                    "storage": self.stats(),
+                    "metrics": self.metrics(),
+                    "mvcc": {
+                        "open_snapshots": mvcc.open_snapshots,
+                        "oldest_snapshot": mvcc.oldest_snapshot,
+                        "retained_versions": mvcc.retained_versions,
+                    },
+                    "lsn": self.current_lsn(),
+                    "active_transactions": self.active_transactions.read().len(),
+                    "current_ops": self.current_ops().len(),
+                })
+            }
+            "compact" => match argument {
+                Value::String(collection) => self.compact_collection(collection)?.to_json(),
+                _ => self.compact()?.to_json(),
+            },
+            "validate" => {
+                let full = command.get("full").and_then(Value::as_bool).unwrap_or(false);
+                serde_json::to_value(self.collection(collection()?)?.validate(full)?)?
+            }
+            "analyze" => serde_json::to_value(self.collection(collection()?)?.analyze()?)?,
+            "drop" => {
+                self.drop_collection(collection()?)?;
+                serde_json::json!({})
+            }
+            "currentOp" => serde_json::json!({
+                "inprog": self.current_ops().iter().map(|op| op.to_json()).collect::<Vec<_>>(),
+            }),
+            "killOp" => {
+                let op = command.get("op").and_then(Value::as_u64)
+                    .ok_or_else(|| MongoLiteError::InvalidQuery("killOp: expected an \"op\" id".to_string()))?;
+                serde_json::json!({"killed": self.kill_op(op)})
+            }
+            other => unreachable!("command '{}' missing from dispatch", other),
+        };
+        if let Value::Object(map) = &mut result {
+            map.insert("ok".to_string(), Value::from(1));
+        }
+        Ok(result)
+    }
+
     /// Storage compaction - removes tombstones and old document versions
     pub fn compact(&self) -> Result<crate::storage::CompactionStats> {
         let mut storage = self.storage.write();
         storage.compact()
     }
 
+    /// Compact one collection in place, leaving the others untouched
+    /// (see StorageEngine::compact_collection)
+    pub fn compact_collection(&self, name: &str) -> Result<crate::storage::CompactionStats> {
+        let mut storage = self.storage.write();
+        storage.compact_collection(name)
+    }
+
+    /// Salvage a corrupted database file into a fresh one at `output_path`
+    /// Does not need (or open) the corrupted database - use when open() fails
+    pub fn repair<P: AsRef<Path>, Q: AsRef<Path>>(path: P, output_path: Q) -> Result<crate::storage::RepairStats> {
+        StorageEngine::repair(path, output_path)
+    }
+
+    /// Bytes by collection, document size percentiles, heaviest fields,
+    /// tombstone overhead and index file sizes (see storage/space.rs)
+    pub fn analyze_space(&self) -> Result<crate::storage::SpaceReport> {
+        let mut storage = self.storage.write();
+        storage.analyze_space()
+    }
+
+    /// Dump the on-disk structures of a database without opening it
+    /// (see storage/inspect.rs and the ironbase-inspect binary)
+    pub fn inspect<P: AsRef<Path>>(path: P) -> Result<crate::storage::InspectReport> {
+        StorageEngine::inspect(path)
+    }
+
     /// Get database path
     pub fn path(&self) -> &str {
         &self.db_path
     }
 
+    // ========== Causal Consistency ==========
+
+    /// LSN of the last committed write
+    pub fn current_lsn(&self) -> crate::session::Lsn {
+        let storage = self.storage.read();
+        storage.lsn_clock().current()
+    }
+
+    /// Block until the database has committed up to `lsn`
+    pub fn wait_for_lsn(&self, lsn: crate::session::Lsn, timeout: std::time::Duration) -> Result<crate::session::Lsn> {
+        let clock = self.storage.read().lsn_clock();
+        clock.wait_for(lsn, timeout)
+    }
+
+    /// Start a session with read-your-writes guarantees
+    pub fn start_session(&self) -> crate::session::Session {
+        let clock = self.storage.read().lsn_clock();
+        crate::session::Session::new(clock)
+    }
+
+    // ========== Snapshots (MVCC) ==========
+
+    /// Open a read snapshot at the current LSN
+    ///
+    /// Reads through it (CollectionCore::find_at) ignore every write
+    /// committed later. Old versions are retained until it is dropped.
+    pub fn snapshot(&self) -> crate::storage::Snapshot {
+        self.storage.read().snapshot()
+    }
+
+    /// Open snapshots and the old versions kept for them
+    pub fn mvcc_stats(&self) -> crate::storage::MvccStats {
+        self.storage.read().mvcc_stats()
+    }
+
+    // ========== Memory limits ==========
+
+    /// Default memory limit (bytes) of each query, scan and aggregation;
+    /// operations over it fail with QueryExceededMemoryLimit. None = unlimited
+    pub fn set_query_memory_limit(&self, limit: Option<usize>) {
+        let mut storage = self.storage.write();
+        storage.set_query_memory_limit(limit);
+    }
+
+    pub fn query_memory_limit(&self) -> Option<usize> {
+        let storage = self.storage.read();
+        storage.query_memory_limit()
+    }
+
+    // ========== Views ==========
+
+    /// Create a read-only view of `source` through `pipeline`
+    ///
+    /// A plain view (`materialized: None`) reruns the pipeline on every read.
+    /// A materialized one stores the results as its own documents - their
+    /// `_id`s must be valid document ids, as for $out - and refreshes them as
+    /// `ViewRefresh` says. Drop a view with drop_collection().
+    pub fn create_view(
+        &self,
+        name: &str,
+        source: &str,
+        pipeline: &Value,
+        materialized: Option<crate::views::ViewRefresh>,
+    ) -> Result<()> {
+        use crate::error::MongoLiteError;
+
+        let definition = crate::views::ViewDefinition::new(source, pipeline.clone(), materialized)?;
+        {
+            let mut storage = self.storage.write();
+            if storage.get_collection_meta(name).is_some() {
+                return Err(MongoLiteError::CollectionExists(name.to_string()));
+            }
+            let reserved = [
+                crate::storage::OPLOG_COLLECTION,
+                crate::auth::USERS_COLLECTION,
+                crate::sequence::SEQUENCES_COLLECTION,
+                crate::statistics::STATS_COLLECTION,
+            ];
+            if name.is_empty() || name == source || reserved.contains(&name) || reserved.contains(&source) {
+                return Err(MongoLiteError::InvalidConfig(format!("cannot create view '{}' on '{}'", name, source)));
+            }
+            if let Some(meta) = storage.get_collection_meta(source) {
+                if meta.view.is_some() {
+                    return Err(MongoLiteError::InvalidConfig(format!("'{}' is a view - views of views are not supported", source)));
+                }
+                if materialized.is_some() && !meta.encrypted_fields.is_empty() {
+                    return Err(MongoLiteError::InvalidConfig(format!(
+                        "'{}' has encrypted fields - a materialized view would store them in plaintext", source
+                    )));
+                }
+            }
+            storage.create_collection(name)?;
+            if let Some(meta) = storage.get_collection_meta_mut(name) {
+                meta.view = Some(definition);
+            }
+            storage.flush()?;
+        }
+
+        if materialized.is_some() {
+            if let Err(e) = self.refresh_view(name) {
+                self.drop_collection(name)?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle for reading a view
+    pub fn view(&self, name: &str) -> Result<crate::views::View> {
+        let view = crate::views::View::new(name, Arc::clone(&self.storage));
+        view.definition()?;
+        Ok(view)
+    }
+
+    /// Handle on the named sequence (created by its first `next()`)
+    pub fn get_sequence(&self, name: &str) -> crate::sequence::Sequence {
+        crate::sequence::Sequence::new(name, Arc::clone(&self.storage))
+    }
+
+    /// Recompute a materialized view's results
+    pub fn refresh_view(&self, name: &str) -> Result<()> {
+        self.view(name)?.refresh()
+    }
+
+    // ========== Field encryption ==========
+
+    /// Key for the encrypted fields of every collection (see
+    /// CollectionCore::encrypt_field); never stored. None = reads return
+    /// ciphertexts and writes to encrypted fields fail
+    pub fn set_encryption_key(&self, key: Option<crate::encryption::EncryptionKey>) {
+        let mut storage = self.storage.write();
+        storage.set_encryption_key(key);
+    }
+
+    // ========== Access control ==========
+
+    // Users live in the `_users` collection with salted PBKDF2 password
+    // hashes. Nothing here restricts the embedded API - server and CLI front
+    // ends authenticate() each connection and authorize() each request.
+
+    /// Are any users defined? Front ends require a login once there are
+    pub fn auth_enabled(&self) -> bool {
+        let storage = self.storage.read();
+        storage.get_collection_meta(crate::auth::USERS_COLLECTION)
+            .is_some_and(|meta| !meta.document_catalog.is_empty())
+    }
+
+    fn users_collection(&self) -> Result<CollectionCore> {
+        let users = self.collection(crate::auth::USERS_COLLECTION)?;
+        let index = format!("{}_user", crate::auth::USERS_COLLECTION);
+        if !users.list_indexes().contains(&index) {
+            users.create_index("user".to_string(), true)?;
+        }
+        Ok(users)
+    }
+
+    /// Add a user; fails if the name is taken
+    pub fn create_user(&self, user: &str, password: &str, grants: Vec<crate::auth::Grant>) -> Result<()> {
+        let record = crate::auth::UserRecord::new(user, password, grants)?;
+        let users = self.users_collection()?;
+        if users.find_one(&serde_json::json!({"user": user}))?.is_some() {
+            return Err(crate::error::MongoLiteError::InvalidConfig(format!("user '{}' already exists", user)));
+        }
+        let doc = serde_json::from_value(record.to_json())?;
+        users.insert_one(doc)?;
+        Ok(())
+    }
+
+    /// Remove a user; false if there was none
+    pub fn drop_user(&self, user: &str) -> Result<bool> {
+        let users = self.users_collection()?;
+        Ok(users.delete_one(&serde_json::json!({"user": user}))?.deleted_count > 0)
+    }
+
+    /// Replace a user's grants
+    pub fn set_user_grants(&self, user: &str, grants: Vec<crate::auth::Grant>) -> Result<()> {
+        let users = self.users_collection()?;
+        let result = users.update_one(
+            &serde_json::json!({"user": user}),
+            &serde_json::json!({"$set": {"grants": serde_json::to_value(grants)?}}),
+        )?;
+        if result.matched_count == 0 {
+            return Err(crate::error::MongoLiteError::DocumentNotFound);
+        }
+        Ok(())
+    }
+
+    /// Set a new password (with a new salt)
+    pub fn change_password(&self, user: &str, password: &str) -> Result<()> {
+        let users = self.users_collection()?;
+        let stored = users.find_one(&serde_json::json!({"user": user}))?
+            .ok_or(crate::error::MongoLiteError::DocumentNotFound)?;
+        let grants = crate::auth::UserRecord::from_json(&stored)?.grants;
+        let record = crate::auth::UserRecord::new(user, password, grants)?;
+        let mut replacement = record.to_json();
+        replacement["_id"] = stored["_id"].clone();
+        users.find_one_and_replace(
+            &serde_json::json!({"user": user}), &replacement, crate::find_options::ReturnDocument::After,
+        )?;
+        Ok(())
+    }
+
+    /// All users and their grants, by name
+    pub fn users(&self) -> Result<Vec<crate::auth::User>> {
+        if !self.auth_enabled() {
+            return Ok(Vec::new());
+        }
+        let users = self.users_collection()?;
+        let mut all = users.find(&serde_json::json!({}))?
+            .iter()
+            .map(|doc| crate::auth::UserRecord::from_json(doc).map(|record| record.into_user()))
+            .collect::<Result<Vec<_>>>()?;
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(all)
+    }
+
+    /// Check a password; unknown users and wrong passwords fail alike
+    pub fn authenticate(&self, user: &str, password: &str) -> Result<crate::auth::User> {
+        if !self.auth_enabled() {
+            return Err(crate::error::MongoLiteError::AuthenticationFailed);
+        }
+        let users = self.users_collection()?;
+        let stored = match users.find_one(&serde_json::json!({"user": user}))? {
+            Some(doc) => crate::auth::UserRecord::from_json(&doc)?,
+            None => {
+                // As slow as a wrong password, so timing does not tell
+                // which users exist
+                crate::auth::UserRecord::verify_unknown(password);
+                return Err(crate::error::MongoLiteError::AuthenticationFailed);
+            }
+        };
+        if !stored.verify(password) {
+            return Err(crate::error::MongoLiteError::AuthenticationFailed);
+        }
+        Ok(stored.into_user())
+    }
+
+    // ========== Running operations ==========
+
+    /// Finds and aggregations currently running on any collection, oldest first
+    pub fn current_ops(&self) -> Vec<crate::operations::CurrentOp> {
+        let operations = self.storage.read().operations();
+        operations.current_ops()
+    }
+
+    /// Kill a running operation (see current_ops)
+    ///
+    /// Cooperative: the operation fails with Cancelled the next time its scan
+    /// checks in. Returns false if no operation with this id is running.
+    pub fn kill_op(&self, id: crate::operations::OpId) -> bool {
+        let operations = self.storage.read().operations();
+        operations.kill(id)
+    }
+
+    // ========== Operation traces ==========
+
+    /// Capture every collection operation into a trace file (see trace.rs)
+    /// Runtime setting - nothing is captured after the next open()
+    pub fn start_trace<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let trace = self.storage.read().trace();
+        trace.start(path)
+    }
+
+    /// Stop the capture; returns the number of operations captured
+    pub fn stop_trace(&self) -> Result<u64> {
+        let trace = self.storage.read().trace();
+        trace.stop()
+    }
+
+    /// Re-run a captured trace against this database
+    pub fn replay_trace<P: AsRef<Path>>(&self, path: P) -> Result<crate::trace::ReplayStats> {
+        crate::trace::replay(self, path)
+    }
+
+    // ========== Import ==========
+
+    /// Import a mongodump / mongoexport directory (see import.rs)
+    pub fn import_mongodump<P: AsRef<Path>>(&self, dir: P, options: &crate::import::ImportOptions) -> Result<crate::import::ImportStats> {
+        crate::import::import_mongodump(self, dir, options)
+    }
+
+    /// Import every table of a SQLite database (needs the `sqlite` cargo feature)
+    #[cfg(feature = "sqlite")]
+    pub fn import_sqlite<P: AsRef<Path>>(&self, path: P, options: &crate::import::ImportOptions) -> Result<crate::import::ImportStats> {
+        crate::import::import_sqlite(self, path, options)
+    }
+
+    // ========== Backup ==========
+
+    /// Copy the database to a new file at `path` as of one snapshot, while
+    /// writes go on (see backup.rs)
+    pub fn backup<P: AsRef<Path>>(&self, path: P) -> Result<crate::backup::BackupStats> {
+        crate::backup::backup(&self.storage, path)
+    }
+
+    // ========== Oplog ==========
+
+    /// Enable/disable the `_oplog` collection and set its retention
+    /// Runtime setting - the oplog is off after every open() until enabled again
+    pub fn set_oplog_config(&self, config: crate::storage::OplogConfig) {
+        let mut storage = self.storage.write();
+        storage.set_oplog_config(config);
+    }
+
+    /// Current oplog configuration
+    pub fn oplog_config(&self) -> crate::storage::OplogConfig {
+        let storage = self.storage.read();
+        storage.oplog_config().clone()
+    }
+
+    /// Oplog entries with sequence number greater than `after`, oldest first
+    /// Tail the oplog by passing the `_id` of the last entry seen
+    pub fn read_oplog(&self, after: u64, limit: usize) -> Result<Vec<Value>> {
+        if !self.list_collections().iter().any(|c| c == crate::storage::OPLOG_COLLECTION) {
+            return Ok(Vec::new());
+        }
+
+        let oplog = self.collection(crate::storage::OPLOG_COLLECTION)?;
+        let options = crate::find_options::FindOptions::new()
+            .with_sort(vec![("_id".to_string(), 1)])
+            .with_limit(limit);
+
+        oplog.find_with_options(&serde_json::json!({"_id": {"$gt": after}}), options)
+    }
+
+    // ========== Replication ==========
+
+    /// WAL stream of this database, for followers to tail
+    pub fn replication_source(&self) -> crate::replication::ReplicationSource {
+        crate::replication::ReplicationSource::new(Path::new(&self.db_path).with_extension("wal"))
+    }
+
+    /// Apply a transaction received from a leader (follower side)
+    ///
+    /// The operations are written straight to storage: open handles of the
+    /// collections they touch rebuild their indexes before their next read
+    /// (see CollectionMeta::index_epoch).
+    pub fn apply_replicated(&self, tx: &crate::replication::ReplicatedTransaction) -> Result<()> {
+        let mut storage = self.storage.write();
+
+        for operation in &tx.operations {
+            crate::replication::apply_operation(&mut storage, operation)?;
+        }
+
+        storage.flush()
+    }
+
     // ========== ACD Transaction API ==========
 
     /// Begin a new transaction
@@ -139,45 +677,79 @@ impl DatabaseCore {
         let transaction = Transaction::new(tx_id);
 
         let mut active = self.active_transactions.write();
-        active.insert(tx_id, transaction);
+        active.insert(tx_id, Arc::new(Mutex::new(transaction)));
+
+        tx_id
+    }
 
+    /// Begin a transaction that rolls back when the returned guard is
+    /// dropped without commit() or rollback() - on an early return, `?` or
+    /// a panic
+    pub fn begin(&self) -> TransactionGuard<'_> {
+        TransactionGuard { db: self, id: self.begin_transaction(), finished: false }
+    }
+
+    /// Begin a transaction with a commit acknowledgment level
+    ///
+    /// `Durability::Relaxed` commits return as soon as they are written and
+    /// leave the fsyncs to the sync thread - much lower latency, at the cost
+    /// of losing the latest commits if the machine crashes.
+    pub fn begin_transaction_with_durability(&self, durability: Durability) -> TransactionId {
+        let tx_id = self.begin_transaction();
+        let _ = self.with_transaction(tx_id, |tx| tx.set_durability(durability));
         tx_id
     }
 
     /// Commit a transaction (applies all buffered operations atomically)
     pub fn commit_transaction(&self, tx_id: TransactionId) -> Result<()> {
         // Remove transaction from active list
-        let mut transaction = {
-            let mut active = self.active_transactions.write();
-            active.remove(&tx_id)
-                .ok_or_else(|| crate::error::MongoLiteError::TransactionAborted(
-                    format!("Transaction {} not found", tx_id)
-                ))?
-        };
+        let mut transaction = self.take_transaction(tx_id)?;
 
         // Commit through storage engine
-        let mut storage = self.storage.write();
-        storage.commit_transaction(&mut transaction)?;
+        let result = self.group_commit(&mut transaction);
+        self.locks.release_all(tx_id);
+        result
+    }
 
-        Ok(())
+    /// Commit through the storage engine with group commit
+    ///
+    /// WAL appends and data writes happen under the storage lock, their fsyncs
+    /// on the sync threads - concurrent committers are all covered by the next
+    /// fsync instead of paying one each in turn. Durable commits wait for the
+    /// fsyncs, relaxed ones only queue them.
+    fn group_commit(&self, transaction: &mut Transaction) -> Result<()> {
+        let durable = transaction.durability() == Durability::Durable;
+
+        let (wal_sync, wal_ticket) = {
+            let mut storage = self.storage.write();
+            let ticket = storage.log_transaction(transaction)?;
+            (storage.wal_sync(), ticket)
+        };
+        if durable {
+            wal_sync.sync_to(wal_ticket)?;
+        }
+
+        let (data_sync, data_ticket) = {
+            let mut storage = self.storage.write();
+            let ticket = storage.apply_transaction(transaction)?;
+            (storage.data_sync(), ticket)
+        };
+        if durable {
+            data_sync.sync_to(data_ticket)?;
+        }
+
+        transaction.mark_committed()
     }
 
     /// Rollback a transaction (discard all buffered operations)
     pub fn rollback_transaction(&self, tx_id: TransactionId) -> Result<()> {
         // Remove transaction from active list
-        let mut transaction = {
-            let mut active = self.active_transactions.write();
-            active.remove(&tx_id)
-                .ok_or_else(|| crate::error::MongoLiteError::TransactionAborted(
-                    format!("Transaction {} not found", tx_id)
-                ))?
-        };
+        let mut transaction = self.take_transaction(tx_id)?;
 
         // Rollback through storage engine
-        let mut storage = self.storage.write();
-        storage.rollback_transaction(&mut transaction)?;
-
-        Ok(())
+        let result = self.storage.write().rollback_transaction(&mut transaction);
+        self.locks.release_all(tx_id);
+        result
     }
 
     /// Commit transaction with atomic index updates (two-phase commit)
@@ -185,12 +757,16 @@ impl DatabaseCore {
     /// # Two-Phase Commit Protocol
     /// 1. PREPARE: Apply index changes to in-memory IndexManager
     /// 2. PREPARE: Create temp index files (.idx.tmp) via prepare_changes()
-    /// 3. COMMIT: Delegate to StorageEngine::commit_transaction() (WAL + data)
-    /// 4. FINALIZE: Atomic rename .idx.tmp → .idx via commit_prepared_changes()
+    /// 3. COMMIT: Group commit through the StorageEngine (WAL + data)
+    /// 4. FINALIZE: Atomic rename .idx.tmp → .idx via commit_prepared_changes(),
+    ///    and list the files as current in the metadata, so open() reads them
+    ///    rather than rebuilding the indexes (see record_index_files)
     ///
     /// # Crash Recovery
-    /// - If crash before COMMIT: WAL rollback cleans up temp files
-    /// - If crash after COMMIT: WAL recovery replays index changes from WAL
+    /// - If crash before COMMIT: open() deletes the temp files, whose
+    ///   transaction is not committed in the WAL
+    /// - If crash after COMMIT: open() commits the temp files it finds, and
+    ///   WAL recovery replays index changes from WAL
     ///
     /// # Arguments
     /// * `tx_id` - Transaction ID to commit
@@ -205,18 +781,13 @@ impl DatabaseCore {
         // ========== PHASE 0: EXTRACT TRANSACTION ==========
 
         // 1. Extract transaction from active list
-        let mut transaction = {
-            let mut active = self.active_transactions.write();
-            active.remove(&tx_id)
-                .ok_or_else(|| crate::error::MongoLiteError::TransactionAborted(
-                    format!("Transaction {} not found", tx_id)
-                ))?
-        };
+        let mut transaction = self.take_transaction(tx_id)?;
 
         // 2. If transaction has no index changes, delegate to simple commit
         if transaction.index_changes().is_empty() {
-            let mut storage = self.storage.write();
-            return storage.commit_transaction(&mut transaction);
+            let result = self.group_commit(&mut transaction);
+            self.locks.release_all(tx_id);
+            return result;
         }
 
         // 3. Extract collection name from first operation
@@ -227,8 +798,8 @@ impl DatabaseCore {
 
         // ========== PHASE 1: PREPARE INDEXES ==========
 
-        // Track all temp files for atomic rename
-        let mut prepared_indexes: Vec<(PathBuf, PathBuf)> = Vec::new();
+        // Track all temp files for atomic rename, with their index and save id
+        let mut prepared_indexes: Vec<(PathBuf, PathBuf, String, String)> = Vec::new();
 
         // Get collection (creates if doesn't exist)
         let collection = self.collection(&collection_name)?;
@@ -240,53 +811,44 @@ impl DatabaseCore {
         }
 
         // Apply changes to in-memory indexes and prepare temp files
+        let changed: Vec<String> = changes_by_index.keys().cloned().collect();
         for (index_name, changes) in changes_by_index {
             let mut indexes = collection.indexes.write();
 
             if let Some(index) = indexes.get_btree_index_mut(&index_name) {
-                // Apply all changes to in-memory index
-                for change in &changes {
-                    let result = match change.operation {
-                        crate::transaction::IndexOperation::Insert => {
-                            let key = convert_index_key(&change.key);
-                            index.insert(key, change.doc_id.clone())
-                        }
-                        crate::transaction::IndexOperation::Delete => {
-                            let key = convert_index_key(&change.key);
-                            index.delete(&key, &change.doc_id)
-                        }
-                    };
-
-                    // If index modification fails, cleanup temp files and restore transaction
-                    if let Err(e) = result {
-                        // Cleanup all prepared temp files
-                        for (temp_path, _) in &prepared_indexes {
-                            let _ = crate::index::BPlusTree::rollback_prepared_changes(temp_path);
-                        }
+                // Apply all changes to in-memory index as one batch
+                let result = index.apply_batch(changes.iter().map(|change| {
+                    (change.operation.clone(), convert_index_key(&change.key), change.doc_id.clone())
+                }));
+
+                // If index modification fails, cleanup temp files and restore transaction
+                if let Err(e) = result {
+                    // Cleanup all prepared temp files
+                    for (temp_path, ..) in &prepared_indexes {
+                        let _ = crate::index::BPlusTree::rollback_prepared_changes(temp_path);
+                    }
 
-                        // Re-insert transaction into active list for potential rollback
-                        let mut active = self.active_transactions.write();
-                        active.insert(tx_id, transaction);
+                    // Re-insert transaction into active list for potential rollback
+                    self.restore_transaction(transaction);
 
-                        return Err(e);
-                    }
+                    return Err(e);
                 }
 
                 // Prepare temp file with updated index
                 let base_path = self.get_index_file_path(&collection_name, &index_name);
-                match index.prepare_changes(&base_path) {
+                match index.prepare_transaction_changes(&base_path, tx_id) {
                     Ok(temp_path) => {
-                        prepared_indexes.push((temp_path, base_path));
+                        let save_id = index.saved_stamp().map(|stamp| stamp.save_id.clone()).unwrap_or_default();
+                        prepared_indexes.push((temp_path, base_path, index_name.clone(), save_id));
                     }
                     Err(e) => {
                         // Cleanup all prepared temp files
-                        for (temp_path, _) in &prepared_indexes {
+                        for (temp_path, ..) in &prepared_indexes {
                             let _ = crate::index::BPlusTree::rollback_prepared_changes(temp_path);
                         }
 
                         // Re-insert transaction into active list for potential rollback
-                        let mut active = self.active_transactions.write();
-                        active.insert(tx_id, transaction);
+                        self.restore_transaction(transaction);
 
                         return Err(e);
                     }
@@ -299,21 +861,19 @@ impl DatabaseCore {
 
         // ========== PHASE 2: COMMIT DATA + WAL ==========
 
-        // Delegate to existing StorageEngine commit
+        // Group commit through the StorageEngine
         // This handles:
         // - Writing WAL entries (Operations + IndexChanges)
         // - Fsync WAL
         // - Applying operations to data
         // - Fsync data
         // - Marking transaction committed
-        let commit_result = {
-            let mut storage = self.storage.write();
-            storage.commit_transaction(&mut transaction)
-        };
+        let commit_result = self.group_commit(&mut transaction);
+        self.locks.release_all(tx_id);
 
         // If commit fails, cleanup temp files (transaction not committed)
         if let Err(e) = commit_result {
-            for (temp_path, _) in &prepared_indexes {
+            for (temp_path, ..) in &prepared_indexes {
                 let _ = crate::index::BPlusTree::rollback_prepared_changes(temp_path);
             }
             return Err(e);
@@ -324,28 +884,66 @@ impl DatabaseCore {
         // Atomic rename all temp files to final paths
         // NOTE: If finalize fails, transaction is already committed (durable in WAL)
         // Temp files will be cleaned up on next startup, indexes rebuilt from WAL
-        for (temp_path, final_path) in prepared_indexes {
+        let mut committed = Vec::with_capacity(prepared_indexes.len());
+        for (temp_path, final_path, index_name, save_id) in prepared_indexes {
             if let Err(e) = crate::index::BPlusTree::commit_prepared_changes(&temp_path, &final_path) {
                 // Log error but DON'T fail transaction (already committed)
                 eprintln!("WARN: Index finalize failed for {:?}: {:?}", final_path, e);
                 eprintln!("WARN: Index will be rebuilt from WAL on next open()");
                 // Continue with next index
+                continue;
             }
+            committed.push((index_name, save_id));
         }
+        self.record_index_files(&collection_name, tx_id, &changed, committed, collection.opened_lsn);
 
         Ok(())
     }
 
+    /// Record which index files of `collection` hold its indexes as of the
+    /// committed transaction `tx_id`, if it still made the last write (see
+    /// CollectionMeta::index_files)
+    ///
+    /// The files of indexes the transaction did not change stay as current
+    /// as they were before it. `committed` (index, save id) were written from
+    /// trees built as of `opened_lsn` plus the transaction's changes, so they
+    /// are current if the transaction came right after `opened_lsn`.
+    fn record_index_files(
+        &self,
+        collection: &str,
+        tx_id: TransactionId,
+        changed: &[String],
+        committed: Vec<(String, String)>,
+        opened_lsn: crate::session::Lsn,
+    ) {
+        let mut storage = self.storage.write();
+        let Some(meta) = storage.get_collection_meta_mut(collection) else {
+            return;
+        };
+        let Some(last) = meta.last_transaction.take_if(|last| last.id == tx_id) else {
+            return;
+        };
+
+        for (index_name, save_id) in last.index_files {
+            if !changed.contains(&index_name) {
+                meta.index_files.insert(index_name, save_id);
+            }
+        }
+        if last.previous_lsn == opened_lsn {
+            meta.index_files.extend(committed);
+        }
+    }
+
     /// Get a reference to an active transaction (for adding operations)
     pub fn get_transaction(&self, tx_id: TransactionId) -> Option<Transaction> {
         let active = self.active_transactions.read();
-        active.get(&tx_id).cloned()
+        active.get(&tx_id).map(|transaction| transaction.lock().clone())
     }
 
     /// Update a transaction (after adding operations)
     pub fn update_transaction(&self, tx_id: TransactionId, transaction: Transaction) -> Result<()> {
         let mut active = self.active_transactions.write();
-        active.insert(tx_id, transaction);
+        active.insert(tx_id, Arc::new(Mutex::new(transaction)));
         Ok(())
     }
 
@@ -355,13 +953,86 @@ impl DatabaseCore {
     where
         F: FnOnce(&mut Transaction) -> Result<R>,
     {
-        let mut active = self.active_transactions.write();
-        let transaction = active.get_mut(&tx_id)
+        // Only this transaction's mutex is held while `f` runs
+        let transaction = self.active_transactions.read().get(&tx_id).cloned()
+            .ok_or_else(|| crate::error::MongoLiteError::TransactionAborted(
+                format!("Transaction {} not found", tx_id)
+            ))?;
+
+        let mut transaction = transaction.lock();
+        f(&mut transaction)
+    }
+
+    /// Remove a transaction from the active list (for commit / rollback)
+    fn take_transaction(&self, tx_id: TransactionId) -> Result<Transaction> {
+        let transaction = self.active_transactions.write().remove(&tx_id)
             .ok_or_else(|| crate::error::MongoLiteError::TransactionAborted(
                 format!("Transaction {} not found", tx_id)
             ))?;
 
-        f(transaction)
+        // Another thread may still be inside with_transaction(); wait for it
+        Ok(match Arc::try_unwrap(transaction) {
+            Ok(transaction) => transaction.into_inner(),
+            Err(shared) => shared.lock().clone(),
+        })
+    }
+
+    /// Put a transaction whose commit failed back into the active list
+    fn restore_transaction(&self, transaction: Transaction) {
+        let mut active = self.active_transactions.write();
+        active.insert(transaction.id, Arc::new(Mutex::new(transaction)));
+    }
+
+    // ========== Document Locks ==========
+
+    /// How long a transaction waits for a document another transaction is
+    /// writing before it is rolled back with a LockTimeout error (default 5s)
+    pub fn set_lock_timeout(&self, timeout: std::time::Duration) {
+        self.locks.set_timeout(timeout);
+    }
+
+    pub fn lock_timeout(&self) -> std::time::Duration {
+        self.locks.timeout()
+    }
+
+    /// Document write latches of active transactions
+    pub fn lock_manager(&self) -> &LockManager {
+        &self.locks
+    }
+
+    /// Latch a document for a transaction; if the latch cannot be had
+    /// (deadlock or timeout) the transaction is rolled back
+    fn lock_document(&self, tx_id: TransactionId, collection: &str, doc_id: &DocumentId) -> Result<()> {
+        if let Err(e) = self.locks.acquire(tx_id, collection, doc_id) {
+            let _ = self.rollback_transaction(tx_id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Latch the document `query` selects and return a query pinned to it,
+    /// so the transactional write that follows cannot pick another document
+    ///
+    /// Returns None when nothing matches. Once latched, no other transaction
+    /// can commit the document, so the match is re-checked under the latch.
+    fn lock_query_target(&self, tx_id: TransactionId, collection: &CollectionCore, query: &Value) -> Result<Option<Value>> {
+        loop {
+            let Some(doc) = collection.find_one(query)? else {
+                return Ok(None);
+            };
+            let id_value = doc.get("_id").cloned().unwrap_or(Value::Null);
+            let doc_id: DocumentId = serde_json::from_value(id_value.clone())?;
+            self.lock_document(tx_id, &collection.name, &doc_id)?;
+
+            let pinned = match query.as_object() {
+                Some(fields) if fields.len() == 1 && fields.contains_key("_id") => query.clone(),
+                _ => serde_json::json!({"$and": [query, {"_id": id_value}]}),
+            };
+            if collection.find_one(&pinned)?.is_some() {
+                return Ok(Some(pinned));
+            }
+            // Changed by a transaction that committed before we got the latch - look again
+        }
     }
 
     // ========== Transaction Convenience Methods ==========
@@ -385,56 +1056,120 @@ impl DatabaseCore {
 
     /// Update one document within a transaction (convenience method)
     ///
-    /// Returns (matched_count, modified_count)
+    /// The document stays latched for this transaction until it commits or
+    /// rolls back. Waiting for a latch can fail with Deadlock or LockTimeout, in
+    /// which case the transaction has been rolled back.
     pub fn update_one_tx(
         &self,
         collection_name: &str,
         query: &Value,
         update: Value,
         tx_id: TransactionId
-    ) -> Result<(u64, u64)> {
+    ) -> Result<crate::collection_core::UpdateResult> {
         let collection = self.collection(collection_name)?;
+        self.with_transaction(tx_id, |_| Ok(()))?;
+
+        // Latch the target first: a concurrent transaction writing the same
+        // document makes this one wait until it commits or rolls back
+        let Some(pinned) = self.lock_query_target(tx_id, &collection, query)? else {
+            return Ok(crate::collection_core::UpdateResult::new(0, 0));
+        };
 
         self.with_transaction(tx_id, |transaction| {
-            collection.update_one_tx(query, update, transaction)
+            collection.update_one_tx(&pinned, update, transaction)
         })
     }
 
     /// Delete one document within a transaction (convenience method)
     ///
-    /// Returns deleted_count
+    /// Latches the document like update_one_tx().
     pub fn delete_one_tx(
         &self,
         collection_name: &str,
         query: &Value,
         tx_id: TransactionId
-    ) -> Result<u64> {
+    ) -> Result<crate::collection_core::DeleteResult> {
         let collection = self.collection(collection_name)?;
+        self.with_transaction(tx_id, |_| Ok(()))?;
+
+        let Some(pinned) = self.lock_query_target(tx_id, &collection, query)? else {
+            return Ok(crate::collection_core::DeleteResult::new(0));
+        };
 
         self.with_transaction(tx_id, |transaction| {
-            collection.delete_one_tx(query, transaction)
+            collection.delete_one_tx(&pinned, transaction)
         })
     }
 
-    // ========== Two-Phase Commit Helper Methods ==========
-
-    /// Construct index file path for a collection's index
-    /// Format: {db_path_without_ext}.{index_name}.idx
-    ///
-    /// Example: "/data/myapp.mlite" + "users_age" → "/data/myapp.users_age.idx"
-    fn get_index_file_path(&self, _collection_name: &str, index_name: &str) -> std::path::PathBuf {
-        use std::path::PathBuf;
+    /// Find documents within a transaction: the collection as read with
+    /// `options.read_concern`, plus the transaction's own uncommitted writes
+    pub fn find_tx(
+        &self,
+        collection_name: &str,
+        query: &Value,
+        options: crate::find_options::FindOptions,
+        tx_id: TransactionId
+    ) -> Result<Vec<Value>> {
+        use crate::find_options::{apply_projection, apply_sort, apply_limit_skip, FindOptions};
+        use crate::transaction::Operation;
+
+        // The transaction's latest version of each document it wrote (None = deleted)
+        let written: HashMap<DocumentId, Option<Value>> = self.with_transaction(tx_id, |transaction| {
+            let mut written = HashMap::new();
+            for operation in transaction.operations() {
+                match operation {
+                    Operation::Insert { collection, doc_id, doc }
+                    | Operation::Update { collection, doc_id, new_doc: doc, .. } if collection == collection_name => {
+                        written.insert(doc_id.clone(), Some(doc.clone()));
+                    }
+                    Operation::Delete { collection, doc_id, .. } if collection == collection_name => {
+                        written.insert(doc_id.clone(), None);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(written)
+        })?;
+
+        // Committed documents, without the ones the transaction replaced
+        let committed = FindOptions {
+            max_memory_bytes: options.max_memory_bytes,
+            max_time_ms: options.max_time_ms,
+            cancellation: options.cancellation.clone(),
+            read_concern: options.read_concern,
+            ..FindOptions::default()
+        };
+        let mut docs: Vec<Value> = self.collection(collection_name)?
+            .find_with_options(query, committed)?
+            .into_iter()
+            .filter(|doc| {
+                let id = doc.get("_id").and_then(|id| serde_json::from_value::<DocumentId>(id.clone()).ok());
+                !id.is_some_and(|id| written.contains_key(&id))
+            })
+            .collect();
 
-        let mut path = PathBuf::from(&self.db_path);
+        let parsed_query = crate::query::Query::from_json(query)?;
+        for doc in written.into_values().flatten() {
+            if parsed_query.matches(&crate::document::Document::from_json(&doc.to_string())?) {
+                docs.push(doc);
+            }
+        }
 
-        // Remove .mlite extension if present
-        if path.extension().map(|e| e == "mlite").unwrap_or(false) {
-            path.set_extension("");
+        if let Some(ref sort) = options.sort {
+            apply_sort(&mut docs, sort);
         }
+        docs = apply_limit_skip(docs, options.limit, options.skip);
+        if let Some(ref projection) = options.projection {
+            docs = docs.iter().map(|doc| apply_projection(doc, projection)).collect();
+        }
+        Ok(docs)
+    }
 
-        // Append index name and .idx extension
-        let index_file = format!("{}.{}.idx", path.display(), index_name);
-        PathBuf::from(index_file)
+    // ========== Two-Phase Commit Helper Methods ==========
+
+    /// Construct index file path for a collection's index (see storage::index_file_path)
+    fn get_index_file_path(&self, _collection_name: &str, index_name: &str) -> std::path::PathBuf {
+        crate::storage::index_file_path(&self.db_path, index_name)
     }
 
     /// Extract collection name from transaction's first operation
@@ -449,6 +1184,65 @@ impl DatabaseCore {
     }
 }
 
+/// A transaction begun with DatabaseCore::begin()
+///
+/// Dropping the guard without commit() or rollback() rolls the transaction
+/// back, so no path out of the caller leaves it active with its document
+/// latches held.
+pub struct TransactionGuard<'db> {
+    db: &'db DatabaseCore,
+    id: TransactionId,
+    /// Committed or rolled back - nothing left for Drop to do
+    finished: bool,
+}
+
+impl<'db> TransactionGuard<'db> {
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    pub fn set_durability(&self, durability: Durability) -> Result<()> {
+        self.db.with_transaction(self.id, |tx| tx.set_durability(durability))
+    }
+
+    pub fn insert_one(&self, collection: &str, document: HashMap<String, Value>) -> Result<DocumentId> {
+        self.db.insert_one_tx(collection, document, self.id)
+    }
+
+    pub fn update_one(&self, collection: &str, query: &Value, update: Value) -> Result<crate::collection_core::UpdateResult> {
+        self.db.update_one_tx(collection, query, update, self.id)
+    }
+
+    pub fn delete_one(&self, collection: &str, query: &Value) -> Result<crate::collection_core::DeleteResult> {
+        self.db.delete_one_tx(collection, query, self.id)
+    }
+
+    pub fn find(&self, collection: &str, query: &Value, options: crate::find_options::FindOptions) -> Result<Vec<Value>> {
+        self.db.find_tx(collection, query, options, self.id)
+    }
+
+    /// Commit the transaction; it is no longer active afterwards, even if
+    /// the commit failed
+    pub fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        self.db.commit_transaction(self.id)
+    }
+
+    pub fn rollback(mut self) -> Result<()> {
+        self.finished = true;
+        self.db.rollback_transaction(self.id)
+    }
+}
+
+impl Drop for TransactionGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Already gone if a lost latch conflict rolled it back
+            let _ = self.db.rollback_transaction(self.id);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,185 @@
+// ironbase-core/src/directory.rs
+// File-per-collection layout: a database directory with one data file per collection
+//
+// Each collection is a database of its own (data file, WAL, journal and
+// index files) named in the directory's manifest. Collections do not share
+// a file, a lock or free space: compacting one rewrites only its file, and
+// dropping one deletes its files instead of leaving dead records behind.
+//
+// Features that span collections - transactions, references, views and
+// $lookup - only see collections in the same file, so in this layout they
+// are limited to a single collection.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+
+use crate::collection_core::CollectionCore;
+use crate::database::DatabaseCore;
+use crate::error::{MongoLiteError, Result};
+use crate::storage::{CompactionStats, StorageConfig};
+
+/// Manifest file in the database directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Manifest format written by this version
+const MANIFEST_VERSION: u32 = 1;
+
+/// Which data file holds which collection
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Manifest {
+    version: u32,
+    /// Numbers data file names (`c<n>.mlite`) - collection names need not
+    /// be valid file names
+    next_file: u64,
+    /// Collection -> data file name
+    collections: BTreeMap<String, String>,
+}
+
+/// Database stored as a directory with one data file per collection
+pub struct DirectoryDatabase {
+    dir: PathBuf,
+    config: StorageConfig,
+    manifest: Mutex<Manifest>,
+    /// Collection databases opened so far
+    open: Mutex<HashMap<String, Arc<DatabaseCore>>>,
+}
+
+impl DirectoryDatabase {
+    /// Open or create a database directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        Self::open_with_config(dir, &StorageConfig::default())
+    }
+
+    /// Open or create a database directory; every collection file is opened
+    /// with `config`
+    pub fn open_with_config<P: AsRef<Path>>(dir: P, config: &StorageConfig) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            let manifest: Manifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)?;
+            if manifest.version > MANIFEST_VERSION {
+                return Err(MongoLiteError::Corruption(format!(
+                    "manifest version {} is newer than this version supports ({})", manifest.version, MANIFEST_VERSION
+                )));
+            }
+            manifest
+        } else {
+            let manifest = Manifest { version: MANIFEST_VERSION, ..Default::default() };
+            write_manifest(&dir, &manifest)?;
+            manifest
+        };
+
+        Ok(DirectoryDatabase {
+            dir,
+            config: config.clone(),
+            manifest: Mutex::new(manifest),
+            open: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The database directory
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get collection (creates it, and its data file, if it doesn't exist)
+    pub fn collection(&self, name: &str) -> Result<CollectionCore> {
+        self.database(name)?.collection(name)
+    }
+
+    /// The database holding collection `name` alone, for what CollectionCore
+    /// does not cover (transactions, sessions, stats); created if missing
+    pub fn database(&self, name: &str) -> Result<Arc<DatabaseCore>> {
+        let mut open = self.open.lock();
+        if let Some(db) = open.get(name) {
+            return Ok(Arc::clone(db));
+        }
+
+        let mut manifest = self.manifest.lock();
+        let file = match manifest.collections.get(name) {
+            Some(file) => file.clone(),
+            None => {
+                manifest.next_file += 1;
+                let file = format!("c{}.mlite", manifest.next_file);
+                let mut updated = manifest.clone();
+                updated.collections.insert(name.to_string(), file.clone());
+                write_manifest(&self.dir, &updated)?;
+                *manifest = updated;
+                file
+            }
+        };
+
+        let db = Arc::new(DatabaseCore::open_with_config(self.dir.join(file), &self.config)?);
+        open.insert(name.to_string(), Arc::clone(&db));
+        Ok(db)
+    }
+
+    /// List all collection names
+    pub fn list_collections(&self) -> Vec<String> {
+        self.manifest.lock().collections.keys().cloned().collect()
+    }
+
+    /// Drop collection by deleting its files
+    ///
+    /// Handles still open on the collection keep working on the deleted
+    /// files; nothing they write is kept.
+    pub fn drop_collection(&self, name: &str) -> Result<()> {
+        let mut open = self.open.lock();
+        let mut manifest = self.manifest.lock();
+        let file = manifest.collections.get(name)
+            .cloned()
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(name.to_string()))?;
+
+        // The manifest goes first: a crash before the files are gone leaves
+        // unreferenced files, never a collection without its data
+        let mut updated = manifest.clone();
+        updated.collections.remove(name);
+        write_manifest(&self.dir, &updated)?;
+        *manifest = updated;
+        open.remove(name);
+
+        // c<n>.mlite, c<n>.wal, c<n>.journal, c<n>.<index>.idx
+        let prefix = format!("{}.", file.trim_end_matches(".mlite"));
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Compact one collection: rewrites only its data file
+    pub fn compact_collection(&self, name: &str) -> Result<CompactionStats> {
+        if !self.manifest.lock().collections.contains_key(name) {
+            return Err(MongoLiteError::CollectionNotFound(name.to_string()));
+        }
+        self.database(name)?.compact()
+    }
+
+    /// Flush every open collection to disk
+    pub fn flush(&self) -> Result<()> {
+        let open: Vec<Arc<DatabaseCore>> = self.open.lock().values().cloned().collect();
+        for db in open {
+            db.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Durably replace the manifest of `dir`
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    let path = dir.join(MANIFEST_FILE);
+    let temp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    file.sync_all()?;
+    crate::durable_fs::atomic_replace(&temp_path, &path)?;
+    Ok(())
+}
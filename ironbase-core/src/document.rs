@@ -17,7 +17,9 @@ pub struct Document {
 /// Dokumentum ID típusok
 /// FONTOS: Untagged, hogy a dokumentumokban egyszerű értékként jelenjen meg: {"_id": 2}
 /// A metadat catalog-ban külön kezeljük a típus megőrzést custom serialization-nel.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Ordered by kind (Int < String < ObjectId), then value - the order of
+/// equal keys in an index.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(untagged)]
 pub enum DocumentId {
     Int(i64),
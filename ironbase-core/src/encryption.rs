@@ -0,0 +1,386 @@
+// ironbase-core/src/encryption.rs
+// Field-level encryption of selected document fields (AES-256-GCM-SIV)
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use aes_gcm_siv::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{Result, MongoLiteError};
+
+/// Prefix of every encrypted value; the mode letter and hex payload follow
+const MARKER: &str = "$enc:";
+
+/// How an encrypted field is encrypted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// Equal values give equal ciphertexts: equality queries ($eq, $ne, $in,
+    /// $nin) and indexes work, at the cost of revealing which documents share
+    /// a value
+    Deterministic,
+    /// Every write gets a fresh nonce: reveals nothing, but the field cannot
+    /// be queried or indexed
+    Randomized,
+}
+
+impl std::str::FromStr for EncryptionMode {
+    type Err = MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "deterministic" => Ok(EncryptionMode::Deterministic),
+            "randomized" => Ok(EncryptionMode::Randomized),
+            other => Err(MongoLiteError::InvalidConfig(format!(
+                "unknown encryption mode '{}' (expected deterministic or randomized)", other
+            ))),
+        }
+    }
+}
+
+/// 256-bit key for field encryption - never written to the database file
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        EncryptionKey(bytes)
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| MongoLiteError::InvalidConfig(format!(
+            "encryption key must be 32 bytes, got {}", bytes.len()
+        )))?;
+        Ok(EncryptionKey(bytes))
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// True if `value` is a ciphertext written by FieldEncryptor
+pub fn is_encrypted(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.starts_with(MARKER))
+}
+
+/// Encrypts and decrypts the configured top-level fields of one collection
+///
+/// Values are stored as `"$enc:<d|r>:<hex>"` strings; the field name is bound
+/// in as associated data, so a ciphertext copied to another field does not
+/// decrypt. Null values are stored as they are. Without a key, reads return
+/// the ciphertexts and writes to encrypted fields fail.
+#[derive(Debug, Clone, Default)]
+pub struct FieldEncryptor {
+    fields: HashMap<String, EncryptionMode>,
+    key: Option<EncryptionKey>,
+}
+
+impl FieldEncryptor {
+    pub fn new(fields: HashMap<String, EncryptionMode>, key: Option<EncryptionKey>) -> Self {
+        FieldEncryptor { fields, key }
+    }
+
+    /// No encrypted fields - every method is a no-op
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn mode(&self, field: &str) -> Option<EncryptionMode> {
+        self.fields.get(field).copied()
+    }
+
+    fn cipher(&self, field: &str) -> Result<Aes256GcmSiv> {
+        let key = self.key.as_ref().ok_or_else(|| MongoLiteError::EncryptionError(format!(
+            "field '{}' is encrypted but no encryption key is set", field
+        )))?;
+        Ok(Aes256GcmSiv::new((&key.0).into()))
+    }
+
+    /// Ciphertext of `value` for `field` (nulls and ciphertexts pass through)
+    ///
+    /// A string carrying the ciphertext marker passes through only if it
+    /// decrypts as `field` with the key; any other is refused rather than
+    /// stored as plaintext. Without a key it cannot be checked and is kept as
+    /// it is, so documents read without the key can be written back.
+    pub fn encrypt_value(&self, field: &str, value: &Value) -> Result<Value> {
+        let Some(mode) = self.mode(field) else {
+            return Ok(value.clone());
+        };
+        if value.is_null() {
+            return Ok(value.clone());
+        }
+        if is_encrypted(value) {
+            return match self.decrypt_value(field, value) {
+                Ok(_) => Ok(value.clone()),
+                Err(_) => Err(MongoLiteError::EncryptionError(format!(
+                    "value of field '{}' starts with \"{}\" but is not a ciphertext of it", field, MARKER
+                ))),
+            };
+        }
+
+        let cipher = self.cipher(field)?;
+        let plaintext = serde_json::to_vec(value)?;
+        let (tag, nonce) = match mode {
+            EncryptionMode::Deterministic => ('d', Nonce::default()),
+            EncryptionMode::Randomized => ('r', Aes256GcmSiv::generate_nonce(&mut OsRng)),
+        };
+        let ciphertext = cipher.encrypt(&nonce, Payload { msg: &plaintext, aad: field.as_bytes() })
+            .map_err(|_| MongoLiteError::EncryptionError(format!("cannot encrypt field '{}'", field)))?;
+
+        let mut payload = Vec::with_capacity(nonce.len() + ciphertext.len());
+        if mode == EncryptionMode::Randomized {
+            payload.extend_from_slice(&nonce);
+        }
+        payload.extend_from_slice(&ciphertext);
+        Ok(Value::String(format!("{}{}:{}", MARKER, tag, to_hex(&payload))))
+    }
+
+    /// Plaintext of a ciphertext of `field` (other values, and every value
+    /// when no key is set, pass through)
+    pub fn decrypt_value(&self, field: &str, value: &Value) -> Result<Value> {
+        let encrypted = value.as_str().and_then(|s| s.strip_prefix(MARKER));
+        let Some(encrypted) = encrypted.filter(|_| self.key.is_some() && self.fields.contains_key(field)) else {
+            return Ok(value.clone());
+        };
+        let corrupt = || MongoLiteError::EncryptionError(format!(
+            "cannot decrypt field '{}' (wrong key or damaged value)", field
+        ));
+
+        let cipher = self.cipher(field)?;
+        let (nonce, ciphertext) = match encrypted.split_once(':') {
+            Some(("d", hex)) => (Nonce::default(), from_hex(hex).ok_or_else(corrupt)?),
+            Some(("r", hex)) => {
+                let payload = from_hex(hex).ok_or_else(corrupt)?;
+                if payload.len() < 12 {
+                    return Err(corrupt());
+                }
+                (*Nonce::from_slice(&payload[..12]), payload[12..].to_vec())
+            }
+            _ => return Err(corrupt()),
+        };
+        let plaintext = cipher.decrypt(&nonce, Payload { msg: &ciphertext, aad: field.as_bytes() })
+            .map_err(|_| corrupt())?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Encrypt the encrypted fields of a document about to be stored
+    /// (takes the `iter_mut()` of its field map)
+    pub fn encrypt_fields<'a>(&self, fields: impl IntoIterator<Item = (&'a String, &'a mut Value)>) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for (field, value) in fields {
+            if self.fields.contains_key(field) {
+                *value = self.encrypt_value(field, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrypt a stored document for the caller; without a key it is left
+    /// as stored
+    pub fn decrypt_document(&self, doc: &mut Value) -> Result<()> {
+        if self.is_empty() || self.key.is_none() {
+            return Ok(());
+        }
+        if let Value::Object(map) = doc {
+            for (field, value) in map.iter_mut() {
+                if self.fields.contains_key(field) {
+                    *value = self.decrypt_value(field, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// decrypt_document() for each of `docs`
+    pub fn decrypt_documents(&self, docs: &mut [Value]) -> Result<()> {
+        docs.iter_mut().try_for_each(|doc| self.decrypt_document(doc))
+    }
+
+    /// Rewrite a query to match stored ciphertexts
+    ///
+    /// Deterministic fields support equality operators ($eq, $ne, $in, $nin)
+    /// and $exists; randomized fields only $exists.
+    pub fn encrypt_query<'a>(&self, query: &'a Value) -> Result<Cow<'a, Value>> {
+        match (self.is_empty(), query) {
+            (false, Value::Object(map)) => Ok(Cow::Owned(self.rewrite_query(map)?)),
+            _ => Ok(Cow::Borrowed(query)),
+        }
+    }
+
+    fn rewrite_query(&self, map: &Map<String, Value>) -> Result<Value> {
+        let mut rewritten = Map::with_capacity(map.len());
+        for (key, condition) in map {
+            let condition = match (key.as_str(), condition) {
+                ("$and" | "$or" | "$nor", Value::Array(clauses)) => Value::Array(
+                    clauses.iter().map(|clause| self.encrypt_query(clause).map(Cow::into_owned)).collect::<Result<_>>()?
+                ),
+                (field, condition) if self.fields.contains_key(field) => self.encrypt_condition(field, condition)?,
+                _ => condition.clone(),
+            };
+            rewritten.insert(key.clone(), condition);
+        }
+        Ok(Value::Object(rewritten))
+    }
+
+    fn encrypt_condition(&self, field: &str, condition: &Value) -> Result<Value> {
+        let mode = self.fields[field];
+        let operators = condition.as_object().filter(|ops| ops.keys().any(|op| op.starts_with('$')));
+        let Some(operators) = operators else {
+            // Plain equality
+            return match mode {
+                EncryptionMode::Deterministic => self.encrypt_value(field, condition),
+                EncryptionMode::Randomized => Err(unqueryable(field)),
+            };
+        };
+
+        let mut rewritten = Map::with_capacity(operators.len());
+        for (op, operand) in operators {
+            let operand = match (op.as_str(), mode) {
+                ("$exists", _) => operand.clone(),
+                ("$eq" | "$ne", EncryptionMode::Deterministic) => self.encrypt_value(field, operand)?,
+                ("$in" | "$nin", EncryptionMode::Deterministic) => match operand {
+                    Value::Array(values) => Value::Array(
+                        values.iter().map(|value| self.encrypt_value(field, value)).collect::<Result<_>>()?
+                    ),
+                    other => other.clone(),
+                },
+                (_, EncryptionMode::Randomized) => return Err(unqueryable(field)),
+                (op, EncryptionMode::Deterministic) => return Err(MongoLiteError::InvalidQuery(format!(
+                    "{} is not supported on encrypted field '{}' (only equality)", op, field
+                ))),
+            };
+            rewritten.insert(op.clone(), operand);
+        }
+        Ok(Value::Object(rewritten))
+    }
+
+    /// Rewrite an update: $set values of encrypted fields are encrypted;
+    /// $unset is allowed, any other operator on them is an error
+    pub fn encrypt_update<'a>(&self, update: &'a Value) -> Result<Cow<'a, Value>> {
+        match (self.is_empty(), update) {
+            (false, Value::Object(operators)) => Ok(Cow::Owned(self.rewrite_update(operators)?)),
+            _ => Ok(Cow::Borrowed(update)),
+        }
+    }
+
+    fn rewrite_update(&self, operators: &Map<String, Value>) -> Result<Value> {
+        let mut rewritten = Map::with_capacity(operators.len());
+        for (op, fields) in operators {
+            let Value::Object(fields) = fields else {
+                rewritten.insert(op.clone(), fields.clone());
+                continue;
+            };
+            let mut values = Map::with_capacity(fields.len());
+            for (field, value) in fields {
+                // $rename names its target in the value
+                let touches_encrypted = self.fields.contains_key(field)
+                    || (op == "$rename" && value.as_str().is_some_and(|target| self.fields.contains_key(target)));
+                let value = match op.as_str() {
+                    "$set" | "$setOnInsert" => self.encrypt_value(field, value)?,
+                    "$unset" => value.clone(),
+                    _ if touches_encrypted => return Err(MongoLiteError::InvalidQuery(format!(
+                        "{} is not supported on encrypted field '{}'", op, field
+                    ))),
+                    _ => value.clone(),
+                };
+                values.insert(field.clone(), value);
+            }
+            rewritten.insert(op.clone(), Value::Object(values));
+        }
+        Ok(Value::Object(rewritten))
+    }
+}
+
+fn unqueryable(field: &str) -> MongoLiteError {
+    MongoLiteError::InvalidQuery(format!(
+        "field '{}' uses randomized encryption and can only be queried with $exists", field
+    ))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encryptor(key: Option<EncryptionKey>) -> FieldEncryptor {
+        let fields = HashMap::from([
+            ("ssn".to_string(), EncryptionMode::Deterministic),
+            ("notes".to_string(), EncryptionMode::Randomized),
+        ]);
+        FieldEncryptor::new(fields, key)
+    }
+
+    #[test]
+    fn test_round_trip_and_modes() {
+        let enc = encryptor(Some(EncryptionKey::new([7; 32])));
+        let mut doc = json!({"_id": 1, "ssn": "123-45-6789", "notes": {"a": [1, 2]}, "age": 30, "tag": null});
+        enc.encrypt_fields(doc.as_object_mut().unwrap().iter_mut()).unwrap();
+        assert!(is_encrypted(&doc["ssn"]) && is_encrypted(&doc["notes"]));
+        assert_eq!(doc["age"], json!(30));
+
+        // Deterministic values repeat, randomized ones do not
+        assert_eq!(enc.encrypt_value("ssn", &json!("123-45-6789")).unwrap(), doc["ssn"]);
+        assert_ne!(enc.encrypt_value("notes", &json!({"a": [1, 2]})).unwrap(), doc["notes"]);
+        // The field name is bound in
+        assert_ne!(enc.encrypt_value("ssn", &json!("x")).unwrap(), FieldEncryptor::new(
+            HashMap::from([("other".to_string(), EncryptionMode::Deterministic)]), Some(EncryptionKey::new([7; 32])),
+        ).encrypt_value("other", &json!("x")).unwrap());
+
+        let stored = doc.clone();
+        enc.decrypt_document(&mut doc).unwrap();
+        assert_eq!(doc, json!({"_id": 1, "ssn": "123-45-6789", "notes": {"a": [1, 2]}, "age": 30, "tag": null}));
+
+        // Without the key reads see ciphertexts, with a wrong key they fail
+        let mut without_key = stored.clone();
+        encryptor(None).decrypt_document(&mut without_key).unwrap();
+        assert_eq!(without_key, stored);
+        assert!(encryptor(None).encrypt_value("ssn", &json!("1")).is_err());
+        let mut wrong_key = stored.clone();
+        assert!(encryptor(Some(EncryptionKey::new([8; 32]))).decrypt_document(&mut wrong_key).is_err());
+
+        // Ciphertexts pass through; plaintext carrying the marker does not
+        assert_eq!(enc.encrypt_value("ssn", &stored["ssn"]).unwrap(), stored["ssn"]);
+        assert!(matches!(enc.encrypt_value("ssn", &json!("$enc:plain")), Err(MongoLiteError::EncryptionError(_))));
+        assert!(enc.encrypt_value("ssn", &json!("$enc:d:00")).is_err());
+        assert!(enc.encrypt_value("notes", &stored["ssn"]).is_err());
+    }
+
+    #[test]
+    fn test_query_and_update_rewrites() {
+        let enc = encryptor(Some(EncryptionKey::new([7; 32])));
+        let ssn = enc.encrypt_value("ssn", &json!("1")).unwrap();
+
+        let query = json!({"$or": [{"ssn": "1"}, {"ssn": {"$in": ["1"]}}], "age": {"$gt": 3}});
+        let query = enc.encrypt_query(&query).unwrap();
+        assert_eq!(*query, json!({"$or": [{"ssn": ssn}, {"ssn": {"$in": [ssn]}}], "age": {"$gt": 3}}));
+        assert!(enc.encrypt_query(&json!({"ssn": {"$gt": "1"}})).is_err());
+        assert!(enc.encrypt_query(&json!({"notes": "x"})).is_err());
+        assert!(enc.encrypt_query(&json!({"notes": {"$exists": true}})).is_ok());
+
+        let update = json!({"$set": {"ssn": "1", "age": 4}, "$unset": {"notes": ""}});
+        let update = enc.encrypt_update(&update).unwrap();
+        assert_eq!(*update, json!({"$set": {"ssn": ssn, "age": 4}, "$unset": {"notes": ""}}));
+        assert!(enc.encrypt_update(&json!({"$push": {"notes": 1}})).is_err());
+        assert!(enc.encrypt_update(&json!({"$rename": {"age": "ssn"}})).is_err());
+    }
+}
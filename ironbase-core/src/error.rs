@@ -1,5 +1,7 @@
 // src/error.rs
+use std::fmt;
 use thiserror::Error;
+use crate::document::DocumentId;
 
 #[derive(Error, Debug)]
 pub enum MongoLiteError {
@@ -42,8 +44,202 @@ pub enum MongoLiteError {
     #[error("WAL corruption detected")]
     WALCorruption,
 
+    #[error("Replication error: {0}")]
+    ReplicationError(String),
+
+    #[error("Export error: {0}")]
+    ExportError(String),
+
+    #[error("Deadlock detected: {0}")]
+    Deadlock(String),
+
+    #[error("Operation timed out: {0}")]
+    Timeout(String),
+
+    /// A transaction waited too long for a document another one is writing
+    #[error("Document latch wait timed out: {0}")]
+    LockTimeout(String),
+
+    #[error("Operation cancelled: {0}")]
+    Cancelled(String),
+
+    #[error("Unsupported file format version {found} (this build supports up to {supported})")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("Operation exceeded its memory limit ({used} bytes in use, limit {limit})")]
+    QueryExceededMemoryLimit { used: usize, limit: usize },
+
+    #[error("Operation exceeded its time limit of {max_time_ms} ms")]
+    OperationTimedOut { max_time_ms: u64 },
+
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
+    #[error("Authentication failed")]
+    AuthenticationFailed,
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Write rejected by hook: {0}")]
+    HookRejected(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Reference violation: {0}")]
+    ReferenceViolation(String),
+
+    #[error("Document of {size} bytes exceeds the maximum document size of {max} bytes")]
+    DocumentTooLarge { size: usize, max: usize },
+
+    #[error("Cursor {0} not found")]
+    CursorNotFound(u64),
+
+    #[error("Too many open cursors on this connection (limit {limit})")]
+    TooManyCursors { limit: usize },
+
     #[error("Unknown error: {0}")]
     Unknown(String),
+
+    /// A storage-level error with where it happened (see ErrorContext)
+    #[error("{source} ({context})")]
+    Context {
+        context: ErrorContext,
+        #[source]
+        source: Box<MongoLiteError>,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, MongoLiteError>;
+
+/// Where an error happened: the operation, collection, document and file
+/// offset involved, as far as they are known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    pub operation: Option<String>,
+    pub collection: Option<String>,
+    pub doc_id: Option<String>,
+    pub offset: Option<u64>,
+}
+
+impl ErrorContext {
+    /// Fill the fields `self` is missing from `outer`
+    fn merge(&mut self, outer: ErrorContext) {
+        self.operation = self.operation.take().or(outer.operation);
+        self.collection = self.collection.take().or(outer.collection);
+        self.doc_id = self.doc_id.take().or(outer.doc_id);
+        self.offset = self.offset.or(outer.offset);
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(operation) = &self.operation {
+            parts.push(format!("operation {}", operation));
+        }
+        if let Some(collection) = &self.collection {
+            parts.push(format!("collection '{}'", collection));
+        }
+        if let Some(doc_id) = &self.doc_id {
+            parts.push(format!("_id {}", doc_id));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {}", offset));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl MongoLiteError {
+    /// The error without its context
+    pub fn root(&self) -> &MongoLiteError {
+        match self {
+            MongoLiteError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Where the error happened, if recorded
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            MongoLiteError::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Whether a transaction failing with the error can be retried from the
+    /// start: it lost a conflict over a document another transaction was
+    /// writing (Deadlock or LockTimeout) and was rolled back. Other timeouts,
+    /// such as a session waiting for its LSN, roll nothing back.
+    pub fn is_transient(&self) -> bool {
+        matches!(self.root(), MongoLiteError::Deadlock(_) | MongoLiteError::LockTimeout(_))
+    }
+
+    /// Whether the error says nothing about where it happened by itself
+    ///
+    /// Only these get a context: the others already name what they are about
+    /// and callers match on their variant.
+    fn is_storage_error(&self) -> bool {
+        matches!(
+            self,
+            MongoLiteError::Io(_)
+                | MongoLiteError::Serialization(_)
+                | MongoLiteError::Deserialization(_)
+                | MongoLiteError::Corruption(_)
+                | MongoLiteError::WALCorruption
+                | MongoLiteError::Context { .. }
+        )
+    }
+
+    /// Attach `context`, filling in the fields an existing context lacks
+    pub fn with_context(self, context: ErrorContext) -> MongoLiteError {
+        match self {
+            MongoLiteError::Context { context: mut inner, source } => {
+                inner.merge(context);
+                MongoLiteError::Context { context: inner, source }
+            }
+            error if error.is_storage_error() => {
+                MongoLiteError::Context { context, source: Box::new(error) }
+            }
+            error => error,
+        }
+    }
+}
+
+/// Attach an ErrorContext to the error of a Result
+pub trait ResultExt<T> {
+    fn in_operation(self, operation: &str, collection: &str) -> Result<T>;
+    fn for_document(self, collection: &str, doc_id: &DocumentId) -> Result<T>;
+    fn at_offset(self, offset: u64) -> Result<T>;
+}
+
+impl<T, E: Into<MongoLiteError>> ResultExt<T> for std::result::Result<T, E> {
+    fn in_operation(self, operation: &str, collection: &str) -> Result<T> {
+        self.map_err(|e| e.into().with_context(ErrorContext {
+            operation: Some(operation.to_string()),
+            collection: Some(collection.to_string()),
+            ..ErrorContext::default()
+        }))
+    }
+
+    fn for_document(self, collection: &str, doc_id: &DocumentId) -> Result<T> {
+        self.map_err(|e| e.into().with_context(ErrorContext {
+            collection: Some(collection.to_string()),
+            doc_id: serde_json::to_string(doc_id).ok(),
+            ..ErrorContext::default()
+        }))
+    }
+
+    fn at_offset(self, offset: u64) -> Result<T> {
+        self.map_err(|e| e.into().with_context(ErrorContext {
+            offset: Some(offset),
+            ..ErrorContext::default()
+        }))
+    }
+}
@@ -0,0 +1,298 @@
+// ironbase-core/src/export.rs
+// Arrow IPC / Parquet export of collections and aggregation results
+//
+// Documents are turned into columns with a schema inferred from the values:
+// one nullable column per field, in first-seen order with _id first. The
+// writers need the `arrow` cargo feature; schema inference is always built.
+
+use std::path::Path;
+use serde_json::Value;
+
+/// Column type inferred from the documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Only nulls / missing so far (written as a string column)
+    Null,
+    Boolean,
+    Int64,
+    Float64,
+    Utf8,
+    /// Arrays, objects and mixed types - written as JSON text
+    Json,
+}
+
+impl ColumnType {
+    /// Type of a single value
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => ColumnType::Null,
+            Value::Bool(_) => ColumnType::Boolean,
+            Value::Number(n) if n.is_i64() => ColumnType::Int64,
+            Value::Number(_) => ColumnType::Float64,
+            Value::String(_) => ColumnType::Utf8,
+            Value::Array(_) | Value::Object(_) => ColumnType::Json,
+        }
+    }
+
+    /// Widen to a type holding both
+    pub fn merge(self, other: ColumnType) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Null, other) | (other, Null) => other,
+            (Int64, Float64) | (Float64, Int64) => Float64,
+            _ => Json,
+        }
+    }
+}
+
+/// One column of an export schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportColumn {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+/// Inferred schema of a set of documents
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportSchema {
+    pub columns: Vec<ExportColumn>,
+}
+
+/// Infer the export schema of `docs`
+/// `_collection` is internal and left out; every column is nullable
+pub fn infer_schema(docs: &[Value]) -> ExportSchema {
+    let mut columns: Vec<ExportColumn> = Vec::new();
+    let mut positions: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+
+    for doc in docs {
+        let map = match doc.as_object() {
+            Some(map) => map,
+            None => continue,
+        };
+        for (name, value) in map {
+            if name == "_collection" {
+                continue;
+            }
+            match positions.get(name.as_str()) {
+                Some(&pos) => {
+                    let column = &mut columns[pos];
+                    column.column_type = column.column_type.merge(ColumnType::of(value));
+                }
+                None => {
+                    positions.insert(name, columns.len());
+                    columns.push(ExportColumn {
+                        name: name.clone(),
+                        column_type: ColumnType::of(value),
+                    });
+                }
+            }
+        }
+    }
+
+    // _id first, the rest in first-seen order
+    if let Some(pos) = columns.iter().position(|column| column.name == "_id") {
+        let id = columns.remove(pos);
+        columns.insert(0, id);
+    }
+
+    ExportSchema { columns }
+}
+
+/// Export file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Arrow IPC file format (.arrow / .feather)
+    ArrowIpc,
+    /// Parquet with snappy compression
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Format from a file extension (.arrow, .ipc, .feather, .parquet)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "arrow" | "ipc" | "feather" => Some(ExportFormat::ArrowIpc),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Result of an export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    pub rows: usize,
+    pub columns: usize,
+}
+
+#[cfg(feature = "arrow")]
+pub use writer::{to_record_batch, write_documents};
+
+/// The Arrow / Parquet crates the writers are built on, for reading exports back
+#[cfg(feature = "arrow")]
+pub use {arrow_array, arrow_ipc, arrow_schema, parquet};
+
+#[cfg(feature = "arrow")]
+mod writer {
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use arrow_array::builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+    use arrow_array::{ArrayRef, RecordBatch, RecordBatchOptions};
+    use arrow_schema::{DataType, Field, Schema};
+    use serde_json::Value;
+
+    use super::{infer_schema, ColumnType, ExportFormat, ExportSchema, ExportStats};
+    use crate::error::{Result, MongoLiteError};
+
+    fn export_error<E: std::fmt::Display>(e: E) -> MongoLiteError {
+        MongoLiteError::ExportError(e.to_string())
+    }
+
+    fn data_type(column_type: ColumnType) -> DataType {
+        match column_type {
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Null | ColumnType::Utf8 | ColumnType::Json => DataType::Utf8,
+        }
+    }
+
+    fn build_column(docs: &[Value], name: &str, column_type: ColumnType) -> ArrayRef {
+        let values = docs.iter().map(|doc| doc.get(name).filter(|value| !value.is_null()));
+
+        match column_type {
+            ColumnType::Boolean => {
+                let mut builder = BooleanBuilder::with_capacity(docs.len());
+                values.for_each(|value| builder.append_option(value.and_then(Value::as_bool)));
+                Arc::new(builder.finish())
+            }
+            ColumnType::Int64 => {
+                let mut builder = Int64Builder::with_capacity(docs.len());
+                values.for_each(|value| builder.append_option(value.and_then(Value::as_i64)));
+                Arc::new(builder.finish())
+            }
+            ColumnType::Float64 => {
+                let mut builder = Float64Builder::with_capacity(docs.len());
+                values.for_each(|value| builder.append_option(value.and_then(Value::as_f64)));
+                Arc::new(builder.finish())
+            }
+            ColumnType::Null | ColumnType::Utf8 => {
+                let mut builder = StringBuilder::new();
+                values.for_each(|value| builder.append_option(value.and_then(Value::as_str)));
+                Arc::new(builder.finish())
+            }
+            ColumnType::Json => {
+                let mut builder = StringBuilder::new();
+                values.for_each(|value| builder.append_option(value.map(Value::to_string)));
+                Arc::new(builder.finish())
+            }
+        }
+    }
+
+    /// Convert documents to a record batch with the given schema
+    pub fn to_record_batch(docs: &[Value], schema: &ExportSchema) -> Result<RecordBatch> {
+        let fields: Vec<Field> = schema.columns.iter()
+            .map(|column| Field::new(column.name.as_str(), data_type(column.column_type), true))
+            .collect();
+        let arrays: Vec<ArrayRef> = schema.columns.iter()
+            .map(|column| build_column(docs, &column.name, column.column_type))
+            .collect();
+
+        // An explicit row count keeps schemas without columns valid
+        let options = RecordBatchOptions::new().with_row_count(Some(docs.len()));
+        RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), arrays, &options)
+            .map_err(export_error)
+    }
+
+    /// Write documents to `path` in `format`
+    ///
+    /// The file is written next to the target and moved into place, so an
+    /// interrupted export never leaves a truncated file behind.
+    pub fn write_documents<P: AsRef<Path>>(docs: &[Value], path: P, format: ExportFormat) -> Result<ExportStats> {
+        let path = path.as_ref();
+        let schema = infer_schema(docs);
+        let batch = to_record_batch(docs, &schema)?;
+
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let written = write_batch(&batch, &temp_path, format)
+            .and_then(|_| crate::durable_fs::atomic_replace(&temp_path, path).map_err(MongoLiteError::from));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        Ok(ExportStats {
+            rows: batch.num_rows(),
+            columns: batch.num_columns(),
+        })
+    }
+
+    fn write_batch(batch: &RecordBatch, path: &Path, format: ExportFormat) -> Result<()> {
+        let file = File::create(path)?;
+
+        match format {
+            ExportFormat::ArrowIpc => {
+                let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &batch.schema())
+                    .map_err(export_error)?;
+                writer.write(batch).map_err(export_error)?;
+                writer.finish().map_err(export_error)?;
+                writer.get_ref().sync_all()?;
+            }
+            ExportFormat::Parquet => {
+                let properties = parquet::file::properties::WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::SNAPPY)
+                    .build();
+                let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), Some(properties))
+                    .map_err(export_error)?;
+                writer.write(batch).map_err(export_error)?;
+                let file = writer.into_inner().map_err(export_error)?;
+                file.sync_all()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_schema_orders_and_widens() {
+        let docs = vec![
+            json!({"name": "alice", "_id": 1, "_collection": "users", "score": 3}),
+            json!({"_id": 2, "score": 4.5, "tags": ["a"], "active": true}),
+            json!({"_id": 3, "name": null, "active": "yes"}),
+        ];
+
+        let schema = infer_schema(&docs);
+        let columns: Vec<(&str, ColumnType)> = schema.columns.iter()
+            .map(|column| (column.name.as_str(), column.column_type))
+            .collect();
+
+        assert_eq!(columns, vec![
+            ("_id", ColumnType::Int64),
+            ("name", ColumnType::Utf8),
+            ("score", ColumnType::Float64),
+            ("active", ColumnType::Json),
+            ("tags", ColumnType::Json),
+        ]);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ExportFormat::from_path("out/users.parquet"), Some(ExportFormat::Parquet));
+        assert_eq!(ExportFormat::from_path("users.ARROW"), Some(ExportFormat::ArrowIpc));
+        assert_eq!(ExportFormat::from_path("users.csv"), None);
+        assert_eq!(ExportFormat::from_path("users"), None);
+    }
+}
@@ -0,0 +1,591 @@
+// src/expression.rs
+// Aggregation expression language
+//
+// Expressions are parsed once from JSON and evaluated per document:
+//   "$field" / "$a.b"          - field path (missing -> null)
+//   {"$literal": v}            - literal value, not evaluated
+//   {"$add": [e1, e2, ...]}    - operator with arguments
+//   {"k": e, ...}              - object whose values are expressions
+//   [e1, e2]                   - array whose elements are expressions
+//   anything else              - literal
+//
+// Used by $project/$addFields/$group in the aggregation pipeline and by the
+// top-level $expr query operator.
+
+use std::cmp::Ordering;
+use serde_json::{Map, Value};
+use crate::document::Document;
+use crate::error::{Result, MongoLiteError};
+
+/// Parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    FieldPath(String),
+    Object(Vec<(String, Expression)>),
+    Array(Vec<Expression>),
+
+    // Arithmetic
+    Add(Vec<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Vec<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Mod(Box<Expression>, Box<Expression>),
+
+    // String
+    Concat(Vec<Expression>),
+    ToLower(Box<Expression>),
+    ToUpper(Box<Expression>),
+    Substr(Box<Expression>, Box<Expression>, Box<Expression>),
+
+    // Conditional
+    Cond(Box<Expression>, Box<Expression>, Box<Expression>),
+    IfNull(Box<Expression>, Box<Expression>),
+
+    // Comparison
+    Compare(CompareOp, Box<Expression>, Box<Expression>),
+
+    // Boolean
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+    Not(Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Expression {
+    /// Parse an expression from JSON
+    pub fn from_json(json: &Value) -> Result<Self> {
+        match json {
+            Value::String(s) if s.starts_with('$') => {
+                let path = &s[1..];
+                if path.is_empty() || path.starts_with('$') {
+                    return Err(MongoLiteError::AggregationError(
+                        format!("Invalid field path: {}", s)
+                    ));
+                }
+                Ok(Expression::FieldPath(path.to_string()))
+            }
+            Value::Array(items) => {
+                let items = items.iter().map(Self::from_json).collect::<Result<Vec<_>>>()?;
+                Ok(Expression::Array(items))
+            }
+            Value::Object(obj) => {
+                if let Some((op, args)) = obj.iter().next().filter(|(k, _)| k.starts_with('$')) {
+                    if obj.len() != 1 {
+                        return Err(MongoLiteError::AggregationError(
+                            format!("Expression {} must be the only key in its object", op)
+                        ));
+                    }
+                    Self::parse_operator(op, args)
+                } else {
+                    let mut fields = Vec::with_capacity(obj.len());
+                    for (key, value) in obj {
+                        if key.starts_with('$') {
+                            return Err(MongoLiteError::AggregationError(
+                                format!("Unexpected operator {} in expression object", key)
+                            ));
+                        }
+                        fields.push((key.clone(), Self::from_json(value)?));
+                    }
+                    Ok(Expression::Object(fields))
+                }
+            }
+            _ => Ok(Expression::Literal(json.clone())),
+        }
+    }
+
+    fn parse_operator(op: &str, args: &Value) -> Result<Self> {
+        match op {
+            "$literal" => Ok(Expression::Literal(args.clone())),
+
+            "$add" => Ok(Expression::Add(Self::parse_args(op, args)?)),
+            "$multiply" => Ok(Expression::Multiply(Self::parse_args(op, args)?)),
+            "$subtract" => {
+                let [a, b] = Self::parse_fixed::<2>(op, args)?;
+                Ok(Expression::Subtract(Box::new(a), Box::new(b)))
+            }
+            "$divide" => {
+                let [a, b] = Self::parse_fixed::<2>(op, args)?;
+                Ok(Expression::Divide(Box::new(a), Box::new(b)))
+            }
+            "$mod" => {
+                let [a, b] = Self::parse_fixed::<2>(op, args)?;
+                Ok(Expression::Mod(Box::new(a), Box::new(b)))
+            }
+
+            "$concat" => Ok(Expression::Concat(Self::parse_args(op, args)?)),
+            "$toLower" => {
+                let [a] = Self::parse_fixed::<1>(op, args)?;
+                Ok(Expression::ToLower(Box::new(a)))
+            }
+            "$toUpper" => {
+                let [a] = Self::parse_fixed::<1>(op, args)?;
+                Ok(Expression::ToUpper(Box::new(a)))
+            }
+            "$substr" => {
+                let [s, start, len] = Self::parse_fixed::<3>(op, args)?;
+                Ok(Expression::Substr(Box::new(s), Box::new(start), Box::new(len)))
+            }
+
+            "$cond" => {
+                // Array form [if, then, else] or object form {if, then, else}
+                let [cond, then, otherwise] = if let Value::Object(obj) = args {
+                    let part = |key: &str| -> Result<Expression> {
+                        obj.get(key)
+                            .ok_or_else(|| MongoLiteError::AggregationError(format!("$cond requires '{}'", key)))
+                            .and_then(Self::from_json)
+                    };
+                    [part("if")?, part("then")?, part("else")?]
+                } else {
+                    Self::parse_fixed::<3>(op, args)?
+                };
+                Ok(Expression::Cond(Box::new(cond), Box::new(then), Box::new(otherwise)))
+            }
+            "$ifNull" => {
+                let [a, b] = Self::parse_fixed::<2>(op, args)?;
+                Ok(Expression::IfNull(Box::new(a), Box::new(b)))
+            }
+
+            "$eq" | "$ne" | "$gt" | "$gte" | "$lt" | "$lte" => {
+                let cmp = match op {
+                    "$eq" => CompareOp::Eq,
+                    "$ne" => CompareOp::Ne,
+                    "$gt" => CompareOp::Gt,
+                    "$gte" => CompareOp::Gte,
+                    "$lt" => CompareOp::Lt,
+                    _ => CompareOp::Lte,
+                };
+                let [a, b] = Self::parse_fixed::<2>(op, args)?;
+                Ok(Expression::Compare(cmp, Box::new(a), Box::new(b)))
+            }
+
+            "$and" => Ok(Expression::And(Self::parse_args(op, args)?)),
+            "$or" => Ok(Expression::Or(Self::parse_args(op, args)?)),
+            "$not" => {
+                let [a] = Self::parse_fixed::<1>(op, args)?;
+                Ok(Expression::Not(Box::new(a)))
+            }
+
+            _ => Err(MongoLiteError::AggregationError(
+                format!("Unknown expression operator: {}", op)
+            )),
+        }
+    }
+
+    /// Variadic arguments: an array, or a single non-array value
+    fn parse_args(op: &str, args: &Value) -> Result<Vec<Expression>> {
+        match args {
+            Value::Array(items) => items.iter().map(Self::from_json).collect(),
+            other => Self::from_json(other)
+                .map(|e| vec![e])
+                .map_err(|e| MongoLiteError::AggregationError(format!("{}: {}", op, e))),
+        }
+    }
+
+    /// Exactly N arguments (a single argument may be given without the array)
+    fn parse_fixed<const N: usize>(op: &str, args: &Value) -> Result<[Expression; N]> {
+        let parsed = match args {
+            Value::Array(items) => items.iter().map(Self::from_json).collect::<Result<Vec<_>>>()?,
+            other if N == 1 => vec![Self::from_json(other)?],
+            _ => Vec::new(),
+        };
+        let count = parsed.len();
+        parsed.try_into().map_err(|_| MongoLiteError::AggregationError(
+            format!("{} requires exactly {} argument(s), got {}", op, N, count)
+        ))
+    }
+
+    /// Evaluate against a JSON document
+    pub fn evaluate(&self, doc: &Value) -> Result<Value> {
+        self.eval(&|field| doc.get(field))
+    }
+
+    /// Evaluate against a query Document (used by $expr)
+    pub fn evaluate_document(&self, doc: &Document) -> Result<Value> {
+        self.eval(&|field| doc.get(field))
+    }
+
+    /// Evaluate against a document's top-level fields
+    pub fn evaluate_fields(&self, doc: &Map<String, Value>) -> Result<Value> {
+        self.eval(&|field| doc.get(field))
+    }
+
+    fn eval<'a, F>(&self, root: &F) -> Result<Value>
+    where
+        F: Fn(&str) -> Option<&'a Value>,
+    {
+        match self {
+            Expression::Literal(v) => Ok(v.clone()),
+            Expression::FieldPath(path) => Ok(resolve_path(root, path).cloned().unwrap_or(Value::Null)),
+            Expression::Object(fields) => {
+                let mut map = Map::new();
+                for (key, expr) in fields {
+                    map.insert(key.clone(), expr.eval(root)?);
+                }
+                Ok(Value::Object(map))
+            }
+            Expression::Array(items) => {
+                items.iter().map(|e| e.eval(root)).collect::<Result<Vec<_>>>().map(Value::Array)
+            }
+
+            Expression::Add(args) => {
+                let mut acc = Number::Int(0);
+                for arg in args {
+                    match to_number("$add", &arg.eval(root)?)? {
+                        Some(n) => acc = acc.add(n),
+                        None => return Ok(Value::Null),
+                    }
+                }
+                Ok(acc.into_value())
+            }
+            Expression::Multiply(args) => {
+                let mut acc = Number::Int(1);
+                for arg in args {
+                    match to_number("$multiply", &arg.eval(root)?)? {
+                        Some(n) => acc = acc.mul(n),
+                        None => return Ok(Value::Null),
+                    }
+                }
+                Ok(acc.into_value())
+            }
+            Expression::Subtract(a, b) => {
+                let (a, b) = match Self::eval_pair("$subtract", a, b, root)? {
+                    Some(pair) => pair,
+                    None => return Ok(Value::Null),
+                };
+                Ok(a.sub(b).into_value())
+            }
+            Expression::Divide(a, b) => {
+                let (a, b) = match Self::eval_pair("$divide", a, b, root)? {
+                    Some(pair) => pair,
+                    None => return Ok(Value::Null),
+                };
+                if b.as_f64() == 0.0 {
+                    return Err(MongoLiteError::AggregationError("$divide by zero".to_string()));
+                }
+                Ok(Value::from(a.as_f64() / b.as_f64()))
+            }
+            Expression::Mod(a, b) => {
+                let (a, b) = match Self::eval_pair("$mod", a, b, root)? {
+                    Some(pair) => pair,
+                    None => return Ok(Value::Null),
+                };
+                if b.as_f64() == 0.0 {
+                    return Err(MongoLiteError::AggregationError("$mod by zero".to_string()));
+                }
+                match (a, b) {
+                    (Number::Int(x), Number::Int(y)) => Ok(Value::from(x.wrapping_rem(y))),
+                    (x, y) => Ok(Value::from(x.as_f64() % y.as_f64())),
+                }
+            }
+
+            Expression::Concat(args) => {
+                let mut out = String::new();
+                for arg in args {
+                    match arg.eval(root)? {
+                        Value::String(s) => out.push_str(&s),
+                        Value::Null => return Ok(Value::Null),
+                        other => return Err(MongoLiteError::AggregationError(
+                            format!("$concat only supports strings, got {}", other)
+                        )),
+                    }
+                }
+                Ok(Value::String(out))
+            }
+            Expression::ToLower(a) => Ok(Value::String(to_string_lossy("$toLower", &a.eval(root)?)?.to_lowercase())),
+            Expression::ToUpper(a) => Ok(Value::String(to_string_lossy("$toUpper", &a.eval(root)?)?.to_uppercase())),
+            Expression::Substr(s, start, len) => {
+                let s = to_string_lossy("$substr", &s.eval(root)?)?;
+                let start = to_index("$substr", &start.eval(root)?)?;
+                let len = to_index("$substr", &len.eval(root)?)?;
+
+                // Character based; a negative length means "to the end"
+                let chars = s.chars().skip(start.max(0) as usize);
+                let out: String = if len < 0 { chars.collect() } else { chars.take(len as usize).collect() };
+                Ok(Value::String(out))
+            }
+
+            Expression::Cond(cond, then, otherwise) => {
+                if is_truthy(&cond.eval(root)?) {
+                    then.eval(root)
+                } else {
+                    otherwise.eval(root)
+                }
+            }
+            Expression::IfNull(a, b) => {
+                let value = a.eval(root)?;
+                if value.is_null() { b.eval(root) } else { Ok(value) }
+            }
+
+            Expression::Compare(op, a, b) => {
+                let ord = compare(&a.eval(root)?, &b.eval(root)?);
+                let result = match op {
+                    CompareOp::Eq => ord == Ordering::Equal,
+                    CompareOp::Ne => ord != Ordering::Equal,
+                    CompareOp::Gt => ord == Ordering::Greater,
+                    CompareOp::Gte => ord != Ordering::Less,
+                    CompareOp::Lt => ord == Ordering::Less,
+                    CompareOp::Lte => ord != Ordering::Greater,
+                };
+                Ok(Value::Bool(result))
+            }
+
+            Expression::And(args) => {
+                for arg in args {
+                    if !is_truthy(&arg.eval(root)?) {
+                        return Ok(Value::Bool(false));
+                    }
+                }
+                Ok(Value::Bool(true))
+            }
+            Expression::Or(args) => {
+                for arg in args {
+                    if is_truthy(&arg.eval(root)?) {
+                        return Ok(Value::Bool(true));
+                    }
+                }
+                Ok(Value::Bool(false))
+            }
+            Expression::Not(a) => Ok(Value::Bool(!is_truthy(&a.eval(root)?))),
+        }
+    }
+
+    /// Evaluate two numeric operands; None if either is null/missing
+    fn eval_pair<'a, F>(op: &str, a: &Expression, b: &Expression, root: &F) -> Result<Option<(Number, Number)>>
+    where
+        F: Fn(&str) -> Option<&'a Value>,
+    {
+        let a = to_number(op, &a.eval(root)?)?;
+        let b = to_number(op, &b.eval(root)?)?;
+        Ok(a.zip(b))
+    }
+}
+
+/// Integer-preserving arithmetic: stays i64 until a float shows up or an operation overflows
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+
+    fn combine(self, other: Number, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other) {
+            if let Some(result) = int_op(a, b) {
+                return Number::Int(result);
+            }
+        }
+        Number::Float(float_op(self.as_f64(), other.as_f64()))
+    }
+
+    fn add(self, other: Number) -> Number {
+        self.combine(other, i64::checked_add, |a, b| a + b)
+    }
+
+    fn sub(self, other: Number) -> Number {
+        self.combine(other, i64::checked_sub, |a, b| a - b)
+    }
+
+    fn mul(self, other: Number) -> Number {
+        self.combine(other, i64::checked_mul, |a, b| a * b)
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Number::Int(i) => Value::from(i),
+            Number::Float(f) => Value::from(f),
+        }
+    }
+}
+
+/// Resolve a dotted path ("a.b.c") from the root lookup
+fn resolve_path<'a, F>(root: &F, path: &str) -> Option<&'a Value>
+where
+    F: Fn(&str) -> Option<&'a Value>,
+{
+    let mut parts = path.split('.');
+    let mut current = root(parts.next()?)?;
+    for part in parts {
+        current = match current {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+fn to_number(op: &str, value: &Value) -> Result<Option<Number>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(n) => Ok(Some(match n.as_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::Float(n.as_f64().unwrap_or(f64::NAN)),
+        })),
+        other => Err(MongoLiteError::AggregationError(
+            format!("{} only supports numeric types, got {}", op, other)
+        )),
+    }
+}
+
+/// String conversion for the string operators (null -> "")
+fn to_string_lossy(op: &str, value: &Value) -> Result<String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(MongoLiteError::AggregationError(
+            format!("{} cannot convert {} to a string", op, other)
+        )),
+    }
+}
+
+fn to_index(op: &str, value: &Value) -> Result<i64> {
+    value.as_i64()
+        .or_else(|| value.as_f64().map(|f| f as i64))
+        .ok_or_else(|| MongoLiteError::AggregationError(
+            format!("{} requires numeric start and length, got {}", op, value)
+        ))
+}
+
+/// false, null (missing) and 0 are false; everything else is true
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
+        _ => true,
+    }
+}
+
+/// Total order across types: null < numbers < strings < objects < arrays < bools
+pub fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Object(_) => 3,
+            Value::Array(_) => 4,
+            Value::Bool(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => crate::numeric::compare(x, y),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => {
+            for (x, y) in x.iter().zip(y) {
+                let ord = compare(x, y);
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            x.len().cmp(&y.len())
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            for ((kx, vx), (ky, vy)) in x.iter().zip(y) {
+                let ord = kx.cmp(ky).then_with(|| compare(vx, vy));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            x.len().cmp(&y.len())
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(expr: Value, doc: Value) -> Value {
+        Expression::from_json(&expr).unwrap().evaluate(&doc).unwrap()
+    }
+
+    #[test]
+    fn test_field_paths_and_literals() {
+        let doc = json!({"a": 1, "b": {"c": "x"}, "arr": [10, 20]});
+        assert_eq!(eval(json!("$a"), doc.clone()), json!(1));
+        assert_eq!(eval(json!("$b.c"), doc.clone()), json!("x"));
+        assert_eq!(eval(json!("$arr.1"), doc.clone()), json!(20));
+        assert_eq!(eval(json!("$missing"), doc.clone()), Value::Null);
+        assert_eq!(eval(json!("plain"), doc.clone()), json!("plain"));
+        assert_eq!(eval(json!({"$literal": "$a"}), doc.clone()), json!("$a"));
+        assert_eq!(eval(json!({"x": "$a", "y": ["$b.c", 2]}), doc), json!({"x": 1, "y": ["x", 2]}));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let doc = json!({"price": 10, "qty": 3, "discount": 2.5});
+        assert_eq!(eval(json!({"$add": ["$price", "$qty", 1]}), doc.clone()), json!(14));
+        assert_eq!(eval(json!({"$subtract": ["$price", "$discount"]}), doc.clone()), json!(7.5));
+        assert_eq!(eval(json!({"$multiply": ["$price", "$qty"]}), doc.clone()), json!(30));
+        assert_eq!(eval(json!({"$divide": ["$price", 4]}), doc.clone()), json!(2.5));
+        assert_eq!(eval(json!({"$mod": ["$price", "$qty"]}), doc.clone()), json!(1));
+        assert_eq!(eval(json!({"$add": ["$price", "$missing"]}), doc.clone()), Value::Null);
+
+        // Overflow falls back to float instead of wrapping
+        assert!(eval(json!({"$add": [i64::MAX, 1]}), doc.clone()).is_f64());
+
+        let div = Expression::from_json(&json!({"$divide": ["$price", 0]})).unwrap();
+        assert!(div.evaluate(&doc).is_err());
+        let bad = Expression::from_json(&json!({"$add": ["$price", "abc"]})).unwrap();
+        assert!(bad.evaluate(&doc).is_err());
+    }
+
+    #[test]
+    fn test_string_operators() {
+        let doc = json!({"first": "Ada", "last": "Lovelace"});
+        assert_eq!(eval(json!({"$concat": ["$first", " ", "$last"]}), doc.clone()), json!("Ada Lovelace"));
+        assert_eq!(eval(json!({"$concat": ["$first", "$missing"]}), doc.clone()), Value::Null);
+        assert_eq!(eval(json!({"$toLower": "$last"}), doc.clone()), json!("lovelace"));
+        assert_eq!(eval(json!({"$toUpper": "$first"}), doc.clone()), json!("ADA"));
+        assert_eq!(eval(json!({"$substr": ["$last", 0, 4]}), doc.clone()), json!("Love"));
+        assert_eq!(eval(json!({"$substr": ["$last", 4, -1]}), doc), json!("lace"));
+    }
+
+    #[test]
+    fn test_conditional_and_comparison() {
+        let doc = json!({"qty": 250, "nickname": null});
+        assert_eq!(eval(json!({"$cond": [{"$gte": ["$qty", 100]}, "bulk", "retail"]}), doc.clone()), json!("bulk"));
+        assert_eq!(
+            eval(json!({"$cond": {"if": {"$lt": ["$qty", 100]}, "then": 1, "else": 0}}), doc.clone()),
+            json!(0)
+        );
+        assert_eq!(eval(json!({"$ifNull": ["$nickname", "n/a"]}), doc.clone()), json!("n/a"));
+        assert_eq!(eval(json!({"$ifNull": ["$qty", 0]}), doc.clone()), json!(250));
+        assert_eq!(eval(json!({"$eq": ["$qty", 250.0]}), doc.clone()), json!(true));
+        assert_eq!(eval(json!({"$ne": ["$qty", "250"]}), doc.clone()), json!(true));
+        assert_eq!(eval(json!({"$and": [{"$gt": ["$qty", 1]}, {"$not": ["$nickname"]}]}), doc), json!(true));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expression::from_json(&json!({"$bogus": 1})).is_err());
+        assert!(Expression::from_json(&json!({"$subtract": [1]})).is_err());
+        assert!(Expression::from_json(&json!({"$cond": {"if": true, "then": 1}})).is_err());
+        assert!(Expression::from_json(&json!({"$add": [1], "x": 2})).is_err());
+        assert!(Expression::from_json(&json!("$")).is_err());
+    }
+}
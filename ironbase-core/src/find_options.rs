@@ -1,9 +1,13 @@
 // ironbase-core/src/find_options.rs
-// Find query options: projection, sort, limit, skip
+// Find query options: projection, sort, limit, skip, keyset pagination
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use serde::Deserialize;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
 use serde_json::Value;
 
+use crate::memory::CancellationToken;
+
 /// Options for find queries
 #[derive(Debug, Clone, Default)]
 pub struct FindOptions {
@@ -12,6 +16,10 @@ pub struct FindOptions {
     pub projection: Option<HashMap<String, i32>>,
 
     /// Sort: [(field, direction)], direction: 1 (asc) or -1 (desc)
+    ///
+    /// None: index order when an index answers the query, natural order (see
+    /// CollectionCore::find) otherwise - stable between calls, so skip and
+    /// limit page consistently while the collection is not written to
     pub sort: Option<Vec<(String, i32)>>,
 
     /// Limit: maximum number of documents to return
@@ -19,6 +27,19 @@ pub struct FindOptions {
 
     /// Skip: number of documents to skip (for pagination)
     pub skip: Option<usize>,
+
+    /// Memory limit in bytes for this query, overriding the database default
+    pub max_memory_bytes: Option<usize>,
+
+    /// Time limit: the query fails with OperationTimedOut after this many
+    /// milliseconds
+    pub max_time_ms: Option<u64>,
+
+    /// Token to cancel the query from another thread (fails with Cancelled)
+    pub cancellation: Option<CancellationToken>,
+
+    /// Isolation of the read (default: local)
+    pub read_concern: ReadConcern,
 }
 
 impl FindOptions {
@@ -45,6 +66,58 @@ impl FindOptions {
         self.skip = Some(skip);
         self
     }
+
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_max_time_ms(mut self, max_time_ms: u64) -> Self {
+        self.max_time_ms = Some(max_time_ms);
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_read_concern(mut self, read_concern: ReadConcern) -> Self {
+        self.read_concern = read_concern;
+        self
+    }
+}
+
+/// Read concern: which data a find / aggregate reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadConcern {
+    /// The latest committed data; a long scan may see writes committed
+    /// while it runs
+    #[default]
+    Local,
+    /// A consistent view as of the start of the operation (MVCC snapshot):
+    /// writes committed while it runs are invisible
+    Snapshot,
+    /// The latest committed data once every write acknowledged before the
+    /// read started is durable - a read that cannot be rolled back by a crash.
+    /// Inside a transaction (DatabaseCore::find_tx) it also sees the
+    /// transaction's own writes
+    Linearizable,
+}
+
+impl std::str::FromStr for ReadConcern {
+    type Err = crate::error::MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ReadConcern::Local),
+            "snapshot" => Ok(ReadConcern::Snapshot),
+            "linearizable" => Ok(ReadConcern::Linearizable),
+            other => Err(crate::error::MongoLiteError::InvalidQuery(format!(
+                "unknown read concern '{}' (expected local, snapshot or linearizable)", other
+            ))),
+        }
+    }
 }
 
 /// Apply projection to a document
@@ -53,12 +126,7 @@ pub fn apply_projection(doc: &Value, projection: &HashMap<String, i32>) -> Value
         return doc.clone();
     }
 
-    // Detect mode
-    let has_inclusions = projection.values().any(|&v| v == 1);
-    let has_non_id_exclusions = projection.iter()
-        .any(|(field, &action)| action == 0 && field != "_id");
-
-    let include_mode = has_inclusions && !has_non_id_exclusions;
+    let include_mode = is_include_mode(projection);
 
     if let Value::Object(obj) = doc {
         let mut result = serde_json::Map::new();
@@ -94,13 +162,96 @@ pub fn apply_projection(doc: &Value, projection: &HashMap<String, i32>) -> Value
     }
 }
 
+/// True if a projection lists the fields to keep (rather than to drop)
+fn is_include_mode(projection: &HashMap<String, i32>) -> bool {
+    let has_inclusions = projection.values().any(|&v| v == 1);
+    let has_non_id_exclusions = projection.iter()
+        .any(|(field, &action)| action == 0 && field != "_id");
+    has_inclusions && !has_non_id_exclusions
+}
+
+/// Top-level fields a find reads when its projection keeps only some:
+/// those of the filter, the projection and the sort, and _id
+///
+/// None when any field may be needed - no projection, an exclusion
+/// projection, or a `$expr` filter (whose expressions can name any field).
+pub fn pushdown_fields(query: &Value, options: &FindOptions) -> Option<HashSet<String>> {
+    let projection = options.projection.as_ref().filter(|projection| is_include_mode(projection))?;
+    let mut fields: HashSet<String> = projection.keys().cloned().collect();
+    fields.insert("_id".to_string());
+    fields.extend(options.sort.iter().flatten().map(|(field, _)| field.clone()));
+    collect_filter_fields(query, &mut fields).then_some(fields)
+}
+
+/// Add the top-level fields a filter reads to `fields`; false if it may read any
+fn collect_filter_fields(query: &Value, fields: &mut HashSet<String>) -> bool {
+    let Value::Object(map) = query else {
+        return false;
+    };
+    map.iter().all(|(key, condition)| match key.as_str() {
+        "$and" | "$or" | "$nor" => condition.as_array()
+            .is_some_and(|clauses| clauses.iter().all(|clause| collect_filter_fields(clause, fields))),
+        operator if operator.starts_with('$') => false,
+        // "a.b" reads within "a"
+        field => {
+            fields.insert(field.split('.').next().unwrap_or(field).to_string());
+            true
+        }
+    })
+}
+
+/// Decode only `fields` of a serialized document; the values of the other
+/// fields are skipped over without being built
+pub fn decode_fields(bytes: &[u8], fields: &HashSet<String>) -> serde_json::Result<Value> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let doc = FieldSubset(fields).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(doc)
+}
+
+struct FieldSubset<'a>(&'a HashSet<String>);
+
+impl<'de> DeserializeSeed<'de> for FieldSubset<'_> {
+    type Value = Value;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldSubset<'_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut doc = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if self.0.contains(&key) {
+                // A repeated key keeps its last value, as for a full decode
+                doc.insert(key, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(Value::Object(doc))
+    }
+}
+
 /// Apply sort to documents
 pub fn apply_sort(docs: &mut [Value], sort: &[(String, i32)]) {
     if sort.is_empty() {
         return;
     }
 
-    docs.sort_by(|a, b| {
+    docs.sort_by(sort_order(sort));
+}
+
+/// Document order of a sort specification
+pub fn sort_order(sort: &[(String, i32)]) -> impl Fn(&Value, &Value) -> std::cmp::Ordering + '_ {
+    move |a, b| {
         for (field, direction) in sort {
             let val_a = a.get(field);
             let val_b = b.get(field);
@@ -112,7 +263,7 @@ pub fn apply_sort(docs: &mut [Value], sort: &[(String, i32)]) {
             }
         }
         std::cmp::Ordering::Equal
-    });
+    }
 }
 
 /// Compare two JSON values for sorting
@@ -124,11 +275,7 @@ fn compare_values(a: Option<&Value>, b: Option<&Value>) -> std::cmp::Ordering {
         (None, Some(_)) => Ordering::Less,    // null < any value
         (Some(_), None) => Ordering::Greater,
 
-        (Some(Value::Number(n1)), Some(Value::Number(n2))) => {
-            let f1 = n1.as_f64().unwrap_or(0.0);
-            let f2 = n2.as_f64().unwrap_or(0.0);
-            f1.partial_cmp(&f2).unwrap_or(Ordering::Equal)
-        }
+        (Some(Value::Number(n1)), Some(Value::Number(n2))) => crate::numeric::compare(n1, n2),
 
         (Some(Value::String(s1)), Some(Value::String(s2))) => s1.cmp(s2),
 
@@ -153,6 +300,79 @@ fn type_priority(val: &Value) -> u8 {
     }
 }
 
+/// One page of a find_page() result
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub documents: Vec<Value>,
+    /// Pass back to find_page() for the next page; None on the last page
+    pub next_page_token: Option<String>,
+}
+
+/// Keyset pagination cursor
+///
+/// Records the sort spec and the sort key (+ _id) of the last document returned.
+/// The next page starts strictly after that key, so inserts and deletes
+/// elsewhere in the collection never shift or repeat documents.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PageToken {
+    pub sort: Vec<(String, i32)>,
+    pub last_key: Vec<Option<Value>>,
+}
+
+impl PageToken {
+    /// Opaque string form (hex-encoded JSON)
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("page token serializes");
+        json.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(token: &str) -> crate::error::Result<Self> {
+        let invalid = || crate::error::MongoLiteError::InvalidQuery("Invalid page token".to_string());
+
+        let bytes = token.as_bytes()
+            .chunks(2)
+            .map(|pair| match pair {
+                [hi, lo] => std::str::from_utf8(pair).ok()
+                    .filter(|_| hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+/// Sort spec used for pagination: the requested fields plus _id as a tie-breaker
+pub fn pagination_sort(sort: &[(String, i32)]) -> Vec<(String, i32)> {
+    let mut full = sort.to_vec();
+    if !full.iter().any(|(field, _)| field == "_id") {
+        full.push(("_id".to_string(), 1));
+    }
+    full
+}
+
+/// Documents along with their pagination keys
+pub(crate) type KeyedDocuments = Vec<(Vec<Option<Value>>, Value)>;
+
+/// Sort key of a document; missing and null compare the same
+pub fn pagination_key(doc: &Value, sort: &[(String, i32)]) -> Vec<Option<Value>> {
+    sort.iter()
+        .map(|(field, _)| doc.get(field).filter(|v| !v.is_null()).cloned())
+        .collect()
+}
+
+/// Compare two pagination keys under a sort spec
+pub fn compare_pagination_keys(a: &[Option<Value>], b: &[Option<Value>], sort: &[(String, i32)]) -> std::cmp::Ordering {
+    for ((val_a, val_b), (_, direction)) in a.iter().zip(b).zip(sort) {
+        let cmp = compare_values(val_a.as_ref(), val_b.as_ref());
+        if cmp != std::cmp::Ordering::Equal {
+            return if *direction == -1 { cmp.reverse() } else { cmp };
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
 /// Apply limit and skip to documents
 pub fn apply_limit_skip(docs: Vec<Value>, limit: Option<usize>, skip: Option<usize>) -> Vec<Value> {
     let skip_count = skip.unwrap_or(0);
@@ -171,6 +391,41 @@ pub fn apply_limit_skip(docs: Vec<Value>, limit: Option<usize>, skip: Option<usi
     docs[start..end].to_vec()
 }
 
+/// Which version find_one_and_replace() returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReturnDocument {
+    /// The document as it was before the write
+    #[default]
+    Before,
+    /// The document as written
+    After,
+}
+
+/// One reference to resolve in find_with_populate()
+///
+/// JSON form: {"field": "user_id", "from": "users", "as": "user"}; without
+/// "as" the referenced document replaces the _id in `field`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Populate {
+    /// Field holding an _id, or an array of _ids, of `from`
+    pub field: String,
+    /// Collection the _ids belong to
+    pub from: String,
+    /// Field the referenced document(s) are written to
+    #[serde(rename = "as", default)]
+    pub as_field: Option<String>,
+}
+
+impl Populate {
+    pub fn new(field: &str, from: &str, as_field: &str) -> Self {
+        Populate { field: field.to_string(), from: from.to_string(), as_field: Some(as_field.to_string()) }
+    }
+
+    pub fn target(&self) -> &str {
+        self.as_field.as_deref().unwrap_or(&self.field)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -218,6 +473,34 @@ mod tests {
         assert!(result.get("city").is_none());  // Excluded
     }
 
+    #[test]
+    fn test_pushdown_fields() {
+        let options = FindOptions::new()
+            .with_projection(HashMap::from([("name".to_string(), 1)]))
+            .with_sort(vec![("age".to_string(), -1)]);
+        let fields = pushdown_fields(&json!({"$or": [{"city": "NYC"}, {"address.zip": "10001"}]}), &options).unwrap();
+        let mut fields: Vec<_> = fields.into_iter().collect();
+        fields.sort();
+        assert_eq!(fields, vec!["_id", "address", "age", "city", "name"]);
+
+        // Every field may be needed
+        assert!(pushdown_fields(&json!({"$expr": {"$gt": ["$a", "$b"]}}), &options).is_none());
+        assert!(pushdown_fields(&json!({}), &FindOptions::new()).is_none());
+        let exclude = FindOptions::new().with_projection(HashMap::from([("bio".to_string(), 0)]));
+        assert!(pushdown_fields(&json!({}), &exclude).is_none());
+    }
+
+    #[test]
+    fn test_decode_fields() {
+        let fields = HashSet::from(["_id".to_string(), "name".to_string()]);
+        let bytes = br#"{"_id":1,"bio":{"long":["nested",{"x":1}]},"name":"Alice","_id":2}"#;
+        assert_eq!(decode_fields(bytes, &fields).unwrap(), json!({"_id": 2, "name": "Alice"}));
+        // Reused regions pad records with spaces
+        assert_eq!(decode_fields(br#"{"age":3}   "#, &fields).unwrap(), json!({}));
+        assert!(decode_fields(br#"{"name":"#, &fields).is_err());
+        assert!(decode_fields(b"[1]", &fields).is_err());
+    }
+
     #[test]
     fn test_sort_single_field() {
         let mut docs = vec![
@@ -342,6 +625,32 @@ mod tests {
         assert_eq!(result[1].get("n").unwrap(), 3);
     }
 
+    #[test]
+    fn test_page_token_roundtrip() {
+        let token = PageToken {
+            sort: pagination_sort(&[("age".to_string(), -1)]),
+            last_key: vec![Some(json!(30)), Some(json!(7))],
+        };
+        assert_eq!(token.sort, vec![("age".to_string(), -1), ("_id".to_string(), 1)]);
+
+        let encoded = token.encode();
+        assert_eq!(PageToken::decode(&encoded).unwrap(), token);
+        assert!(PageToken::decode("zz").is_err());
+        assert!(PageToken::decode("abc").is_err());
+    }
+
+    #[test]
+    fn test_pagination_key_order() {
+        let sort = pagination_sort(&[("age".to_string(), -1)]);
+        let older = pagination_key(&json!({"_id": 2, "age": 40}), &sort);
+        let younger = pagination_key(&json!({"_id": 1, "age": 30}), &sort);
+        let missing = pagination_key(&json!({"_id": 3, "age": null}), &sort);
+
+        assert_eq!(compare_pagination_keys(&older, &younger, &sort), std::cmp::Ordering::Less);
+        assert_eq!(compare_pagination_keys(&younger, &missing, &sort), std::cmp::Ordering::Less);
+        assert_eq!(missing[0], None);
+    }
+
     #[test]
     fn test_skip_beyond_length() {
         let docs = vec![
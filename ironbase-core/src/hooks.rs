@@ -0,0 +1,169 @@
+// ironbase-core/src/hooks.rs
+// Per-collection write hooks (triggers)
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::error::{Result, MongoLiteError};
+
+/// Write event a hook runs on
+///
+/// Before-hooks see the document about to be written and may change it in
+/// place (insert/update) or veto the write by returning an error, which the
+/// write then fails with. After-hooks see the document as stored (for delete:
+/// as it was) once the write is done; their changes are discarded and an
+/// error is reported to the caller, but the write stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    BeforeInsert,
+    AfterInsert,
+    BeforeUpdate,
+    AfterUpdate,
+    BeforeDelete,
+    AfterDelete,
+}
+
+impl HookEvent {
+    pub fn is_before(self) -> bool {
+        matches!(self, HookEvent::BeforeInsert | HookEvent::BeforeUpdate | HookEvent::BeforeDelete)
+    }
+}
+
+impl std::str::FromStr for HookEvent {
+    type Err = MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "before_insert" => Ok(HookEvent::BeforeInsert),
+            "after_insert" => Ok(HookEvent::AfterInsert),
+            "before_update" => Ok(HookEvent::BeforeUpdate),
+            "after_update" => Ok(HookEvent::AfterUpdate),
+            "before_delete" => Ok(HookEvent::BeforeDelete),
+            "after_delete" => Ok(HookEvent::AfterDelete),
+            other => Err(MongoLiteError::InvalidQuery(format!(
+                "unknown hook event '{}' (expected before_/after_ insert, update or delete)", other
+            ))),
+        }
+    }
+}
+
+/// Hook callback - gets the document, errors veto (before-hooks)
+pub type Hook = Arc<dyn Fn(&mut Value) -> Result<()> + Send + Sync>;
+
+/// Hooks of every collection of a database, in registration order
+///
+/// Hooks run while the collection's write holds the storage lock, so they must
+/// not call back into the same database.
+#[derive(Default)]
+pub struct HookRegistry {
+    hooks: RwLock<HashMap<(String, HookEvent), Vec<Hook>>>,
+}
+
+impl std::fmt::Debug for HookRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookRegistry")
+            .field("events", &self.hooks.read().keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl HookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, collection: &str, event: HookEvent, hook: Hook) {
+        self.hooks.write()
+            .entry((collection.to_string(), event))
+            .or_default()
+            .push(hook);
+    }
+
+    /// Any hooks for `event` on `collection`?
+    pub fn has(&self, collection: &str, event: HookEvent) -> bool {
+        self.hooks.read().contains_key(&(collection.to_string(), event))
+    }
+
+    /// Remove every hook of `collection`
+    pub fn clear(&self, collection: &str) {
+        self.hooks.write().retain(|(name, _), _| name != collection);
+    }
+
+    /// Run the hooks of `event` on `doc`; the first error stops the chain
+    ///
+    /// Before-hooks may not change `_id` or `_collection`.
+    pub fn run(&self, collection: &str, event: HookEvent, doc: &mut Value) -> Result<()> {
+        // Clone the list so hooks can register further hooks
+        let hooks = match self.hooks.read().get(&(collection.to_string(), event)) {
+            Some(hooks) => hooks.clone(),
+            None => return Ok(()),
+        };
+
+        if !event.is_before() {
+            let mut copy = doc.clone();
+            for hook in &hooks {
+                hook(&mut copy)?;
+            }
+            return Ok(());
+        }
+
+        let id = doc.get("_id").cloned();
+        for hook in &hooks {
+            hook(doc)?;
+        }
+        if !doc.is_object() || doc.get("_id").cloned() != id {
+            return Err(MongoLiteError::InvalidQuery(format!(
+                "{:?} hook on '{}' must keep the document's _id", event, collection
+            )));
+        }
+        if let Value::Object(map) = doc {
+            map.insert("_collection".to_string(), Value::String(collection.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hooks_run_in_order_and_guard_id() {
+        let registry = HookRegistry::new();
+        registry.register("users", HookEvent::BeforeInsert, Arc::new(|doc: &mut Value| {
+            doc["n"] = json!(1);
+            Ok(())
+        }));
+        registry.register("users", HookEvent::BeforeInsert, Arc::new(|doc: &mut Value| {
+            doc["n"] = json!(doc["n"].as_i64().unwrap() * 10);
+            Ok(())
+        }));
+
+        let mut doc = json!({"_id": 1});
+        registry.run("users", HookEvent::BeforeInsert, &mut doc).unwrap();
+        assert_eq!(doc["n"], json!(10));
+        assert_eq!(doc["_collection"], json!("users"));
+
+        // Other collections and events are untouched
+        let mut other = json!({"_id": 1});
+        registry.run("orders", HookEvent::BeforeInsert, &mut other).unwrap();
+        registry.run("users", HookEvent::BeforeUpdate, &mut other).unwrap();
+        assert_eq!(other, json!({"_id": 1}));
+
+        registry.register("users", HookEvent::BeforeUpdate, Arc::new(|doc: &mut Value| {
+            doc["_id"] = json!(2);
+            Ok(())
+        }));
+        assert!(registry.run("users", HookEvent::BeforeUpdate, &mut json!({"_id": 1})).is_err());
+
+        registry.clear("users");
+        let mut doc = json!({"_id": 1});
+        registry.run("users", HookEvent::BeforeInsert, &mut doc).unwrap();
+        assert_eq!(doc, json!({"_id": 1}));
+        assert_eq!("after_delete".parse::<HookEvent>().unwrap(), HookEvent::AfterDelete);
+    }
+}
@@ -0,0 +1,519 @@
+// ironbase-core/src/import.rs
+// Import from SQLite databases and MongoDB dumps
+//
+// SQLite: every table becomes a collection and every row a document, one
+// field per column. mongodump: every `<collection>.bson` file of a dump
+// directory becomes a collection, and mongoexport-style `<collection>.json`
+// files (Extended JSON, one document per line or a JSON array) are read too.
+// Single-field indexes of the source are recreated. The SQLite reader needs
+// the `sqlite` cargo feature and the BSON reader the `bson` feature;
+// Extended JSON is always built. mongodump --archive and --gzip output is
+// not read - dump to a directory without compression.
+//
+// Type mapping (the database has JSON types only):
+//   SQLite INTEGER / REAL / TEXT / NULL -> number / number / string / null
+//   SQLite BLOB                         -> base64 string
+//   columns declared BOOLEAN / JSON     -> bool / parsed JSON (text if invalid)
+//   ObjectId, Symbol, UUID              -> string
+//   Int32, Int64, Double                -> number (NaN and infinities -> null)
+//   Decimal128                          -> number, string if out of range
+//   DateTime                            -> RFC 3339 string in UTC
+//   Binary                              -> base64 string
+//   Regex                               -> "/pattern/options"
+//   Timestamp                           -> {"t": seconds, "i": increment}
+//   JavaScript code                     -> string (its scope is dropped)
+//   MinKey, MaxKey, Undefined           -> null
+//
+// The database assigns its own _id, so a source _id is kept in
+// `ImportOptions::source_id_field`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::collection_core::CollectionCore;
+use crate::database::DatabaseCore;
+use crate::error::{MongoLiteError, Result};
+
+/// Options of an import
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Field the source _id (or an `_id` column) is kept in
+    pub source_id_field: String,
+    /// Documents per insert_many
+    pub batch_size: usize,
+    /// Recreate the single-field indexes of the source
+    pub create_indexes: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            source_id_field: "source_id".to_string(),
+            batch_size: 1000,
+            create_indexes: true,
+        }
+    }
+}
+
+/// Result of an import
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    /// Documents imported, per collection
+    pub collections: BTreeMap<String, u64>,
+    /// Indexes created
+    pub indexes: u64,
+}
+
+impl ImportStats {
+    /// Documents imported into all collections
+    pub fn documents(&self) -> u64 {
+        self.collections.values().sum()
+    }
+}
+
+/// Convert an Extended JSON value (canonical or relaxed) to plain JSON,
+/// following the type mapping above
+pub fn from_extended_json(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(from_extended_json).collect()),
+        Value::Object(map) => match convert_wrapper(&map) {
+            Some(converted) => converted,
+            None => Value::Object(map.into_iter().map(|(key, value)| (key, from_extended_json(value))).collect()),
+        },
+        other => other,
+    }
+}
+
+/// The plain value of a type wrapper like {"$oid": "..."}; None for ordinary objects
+fn convert_wrapper(map: &Map<String, Value>) -> Option<Value> {
+    let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    let string = |key: &str| map.get(key).and_then(Value::as_str);
+
+    let converted = match keys.as_slice() {
+        ["$oid"] => Value::String(string("$oid")?.to_string()),
+        ["$symbol"] => Value::String(string("$symbol")?.to_string()),
+        ["$uuid"] => Value::String(string("$uuid")?.to_string()),
+        ["$code"] | ["$code", "$scope"] => Value::String(string("$code")?.to_string()),
+        ["$numberInt"] | ["$numberLong"] => Value::from(map.values().next()?.as_str()?.parse::<i64>().ok()?),
+        ["$numberDouble"] => float(string("$numberDouble")?.parse().ok()?),
+        ["$numberDecimal"] => {
+            let text = string("$numberDecimal")?;
+            match text.parse::<f64>() {
+                Ok(number) if number.is_finite() => float(number),
+                _ => Value::String(text.to_string()),
+            }
+        }
+        ["$date"] => date(&map["$date"])?,
+        ["$binary"] => match &map["$binary"] {
+            // Canonical: {"$binary": {"base64": "...", "subType": "00"}}
+            Value::Object(binary) => Value::String(binary.get("base64")?.as_str()?.to_string()),
+            // Legacy, without the subtype
+            Value::String(base64) => Value::String(base64.clone()),
+            _ => return None,
+        },
+        ["$binary", "$type"] => Value::String(string("$binary")?.to_string()),
+        ["$regularExpression"] => {
+            let regex = map["$regularExpression"].as_object()?;
+            regex_string(regex.get("pattern")?.as_str()?, regex.get("options")?.as_str()?)
+        }
+        ["$options", "$regex"] => regex_string(string("$regex")?, string("$options")?),
+        ["$timestamp"] => {
+            let timestamp = map["$timestamp"].as_object()?;
+            serde_json::json!({"t": timestamp.get("t")?.as_u64()?, "i": timestamp.get("i")?.as_u64()?})
+        }
+        ["$minKey"] | ["$maxKey"] | ["$undefined"] => Value::Null,
+        _ => return None,
+    };
+    Some(converted)
+}
+
+fn float(number: f64) -> Value {
+    serde_json::Number::from_f64(number).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn regex_string(pattern: &str, options: &str) -> Value {
+    Value::String(format!("/{}/{}", pattern, options))
+}
+
+/// {"$date": ...}: relaxed ISO string, canonical {"$numberLong": "ms"} or legacy ms
+fn date(value: &Value) -> Option<Value> {
+    use chrono::{DateTime, SecondsFormat, Utc};
+
+    let millis = match value {
+        Value::String(iso) => {
+            let parsed = DateTime::parse_from_rfc3339(iso).ok()?;
+            return Some(Value::String(parsed.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true)));
+        }
+        Value::Number(number) => number.as_i64()?,
+        Value::Object(map) => map.get("$numberLong")?.as_str()?.parse().ok()?,
+        _ => return None,
+    };
+    let date = DateTime::<Utc>::from_timestamp_millis(millis)?;
+    Some(Value::String(date.to_rfc3339_opts(SecondsFormat::Millis, true)))
+}
+
+/// Import a mongodump (or mongoexport) directory of one database
+///
+/// `<name>.bson` and `<name>.json` files are imported into collection
+/// `<name>`, with the indexes listed in `<name>.metadata.json`.
+pub fn import_mongodump<P: AsRef<Path>>(db: &DatabaseCore, dir: P, options: &ImportOptions) -> Result<ImportStats> {
+    let dir = dir.as_ref();
+    let mut files: Vec<_> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    files.sort();
+
+    let mut stats = ImportStats::default();
+    for path in files {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if file_name.ends_with(".metadata.json") {
+            continue;
+        }
+        if file_name.ends_with(".gz") {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "{}: compressed dumps are not supported, run mongodump without --gzip", path.display()
+            )));
+        }
+        let (name, is_bson) = match file_name.rsplit_once('.') {
+            Some((name, "bson")) => (name, true),
+            Some((name, "json")) => (name, false),
+            _ => continue,
+        };
+
+        let collection = db.collection(name)?;
+        let imported = if is_bson {
+            import_bson(&collection, &path, options)?
+        } else {
+            import_extended_json(&collection, &path, options)?
+        };
+        stats.collections.insert(name.to_string(), imported);
+
+        let metadata = dir.join(format!("{}.metadata.json", name));
+        if options.create_indexes && metadata.exists() {
+            stats.indexes += create_dump_indexes(&collection, &metadata, options)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Import a file of Extended JSON documents into `collection`
+///
+/// The documents are one per line (mongoexport) or in a JSON array
+/// (mongoexport --jsonArray). Returns the number of documents imported.
+pub fn import_extended_json<P: AsRef<Path>>(collection: &CollectionCore, path: P, options: &ImportOptions) -> Result<u64> {
+    let path = path.as_ref();
+    let reader = BufReader::new(File::open(path)?);
+    let mut batch = Batch::new(collection, options);
+
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+        let value = value.map_err(|e| MongoLiteError::Corruption(format!("{}: {}", path.display(), e)))?;
+        let docs = match value {
+            Value::Array(docs) => docs,
+            doc => vec![doc],
+        };
+        for doc in docs {
+            match from_extended_json(doc) {
+                Value::Object(fields) => batch.push(fields)?,
+                other => {
+                    return Err(MongoLiteError::Corruption(format!(
+                        "{}: expected a document, found {}", path.display(), other
+                    )))
+                }
+            }
+        }
+    }
+    batch.finish()
+}
+
+/// Recreate the single-field indexes listed in a `.metadata.json` file
+fn create_dump_indexes(collection: &CollectionCore, path: &Path, options: &ImportOptions) -> Result<u64> {
+    let metadata: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let mut created = 0;
+
+    for index in metadata.get("indexes").and_then(Value::as_array).into_iter().flatten() {
+        // Compound, text and 2d indexes have no equivalent
+        let Some(key) = index.get("key").and_then(Value::as_object).filter(|key| key.len() == 1) else {
+            continue;
+        };
+        let (field, kind) = key.iter().next().expect("one key");
+        let field = if field == "_id" { &options.source_id_field } else { field };
+        let unique = index.get("unique").and_then(Value::as_bool).unwrap_or(false);
+        let hashed = kind.as_str() == Some("hashed");
+        created += create_index(collection, field, unique, hashed)?;
+    }
+    Ok(created)
+}
+
+/// Create an index unless the collection already has it; returns 1 if created
+///
+/// Index names are `<collection>_<field>`, so a field named `id` counts as
+/// indexed: its name is taken by the _id index.
+fn create_index(collection: &CollectionCore, field: &str, unique: bool, hashed: bool) -> Result<u64> {
+    let name = format!("{}_{}", collection.name, field);
+    let hashed_name = format!("{}{}", name, crate::index::HASHED_INDEX_SUFFIX);
+    if collection.list_indexes().iter().any(|index| *index == name || *index == hashed_name) {
+        return Ok(0);
+    }
+    if hashed {
+        collection.create_hashed_index(field.to_string())?;
+    } else {
+        collection.create_index(field.to_string(), unique)?;
+    }
+    Ok(1)
+}
+
+/// Documents waiting for the next insert_many
+struct Batch<'a> {
+    collection: &'a CollectionCore,
+    options: &'a ImportOptions,
+    docs: Vec<HashMap<String, Value>>,
+    inserted: u64,
+}
+
+impl<'a> Batch<'a> {
+    fn new(collection: &'a CollectionCore, options: &'a ImportOptions) -> Self {
+        Batch { collection, options, docs: Vec::new(), inserted: 0 }
+    }
+
+    fn push(&mut self, fields: Map<String, Value>) -> Result<()> {
+        let mut doc: HashMap<String, Value> = fields.into_iter().collect();
+        if let Some(id) = doc.remove("_id") {
+            doc.insert(self.options.source_id_field.clone(), id);
+        }
+        self.docs.push(doc);
+        if self.docs.len() >= self.options.batch_size.max(1) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.docs.is_empty() {
+            let docs = std::mem::take(&mut self.docs);
+            let count = docs.len() as u64;
+            self.collection.insert_many(docs)?;
+            self.inserted += count;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<u64> {
+        self.flush()?;
+        Ok(self.inserted)
+    }
+}
+
+#[cfg(feature = "bson")]
+pub use bson_reader::import_bson;
+
+/// The BSON crate the reader is built on, for writing test dumps
+#[cfg(feature = "bson")]
+pub use bson;
+
+/// Import a mongodump `.bson` file - needs the `bson` cargo feature
+#[cfg(not(feature = "bson"))]
+pub fn import_bson<P: AsRef<Path>>(_collection: &CollectionCore, path: P, _options: &ImportOptions) -> Result<u64> {
+    Err(MongoLiteError::InvalidConfig(format!(
+        "{}: reading BSON needs the `bson` cargo feature", path.as_ref().display()
+    )))
+}
+
+#[cfg(feature = "bson")]
+mod bson_reader {
+    use std::fs::File;
+    use std::io::{BufReader, ErrorKind, Read};
+    use std::path::Path;
+
+    use serde_json::Value;
+
+    use super::{from_extended_json, Batch, ImportOptions};
+    use crate::collection_core::CollectionCore;
+    use crate::error::{MongoLiteError, Result};
+
+    /// Import a mongodump `.bson` file (concatenated BSON documents) into
+    /// `collection`; returns the number of documents imported
+    pub fn import_bson<P: AsRef<Path>>(collection: &CollectionCore, path: P, options: &ImportOptions) -> Result<u64> {
+        let path = path.as_ref();
+        let corrupt = |message: String| MongoLiteError::Corruption(format!("{}: {}", path.display(), message));
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut batch = Batch::new(collection, options);
+
+        loop {
+            // Every document starts with its total length, the prefix included
+            let mut length = [0u8; 4];
+            match reader.read_exact(&mut length) {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                read => read?,
+            }
+            let size = i32::from_le_bytes(length);
+            if size < 5 {
+                return Err(corrupt(format!("invalid document length {}", size)));
+            }
+            let mut bytes = length.to_vec();
+            bytes.resize(size as usize, 0);
+            reader.read_exact(&mut bytes[4..])
+                .map_err(|_| corrupt("truncated document".to_string()))?;
+
+            let doc = bson::Document::from_reader(bytes.as_slice()).map_err(|e| corrupt(e.to_string()))?;
+            match from_extended_json(bson::Bson::Document(doc).into_relaxed_extjson()) {
+                Value::Object(fields) => batch.push(fields)?,
+                _ => unreachable!("a BSON document converts to an object"),
+            }
+        }
+        batch.finish()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite_reader::import_sqlite;
+
+/// The SQLite crate the reader is built on, for writing test databases
+#[cfg(feature = "sqlite")]
+pub use rusqlite;
+
+#[cfg(feature = "sqlite")]
+mod sqlite_reader {
+    use std::path::Path;
+
+    use base64::Engine;
+    use rusqlite::types::ValueRef;
+    use rusqlite::{Connection, OpenFlags};
+    use serde_json::{Map, Value};
+
+    use super::{create_index, float, Batch, ImportOptions, ImportStats};
+    use crate::database::DatabaseCore;
+    use crate::error::{MongoLiteError, Result};
+
+    fn sqlite_error(e: rusqlite::Error) -> MongoLiteError {
+        MongoLiteError::InvalidQuery(format!("sqlite: {}", e))
+    }
+
+    /// How a column's values are converted, from its declared type
+    #[derive(Clone, Copy)]
+    enum ColumnKind {
+        Plain,
+        Boolean,
+        Json,
+    }
+
+    impl ColumnKind {
+        fn from_declared(declared: Option<&str>) -> Self {
+            match declared.map(str::to_ascii_uppercase).as_deref() {
+                Some("BOOLEAN" | "BOOL") => ColumnKind::Boolean,
+                Some("JSON" | "JSONB") => ColumnKind::Json,
+                _ => ColumnKind::Plain,
+            }
+        }
+
+        fn convert(self, value: ValueRef<'_>) -> Value {
+            match (self, value) {
+                (_, ValueRef::Null) => Value::Null,
+                (ColumnKind::Boolean, ValueRef::Integer(number)) => Value::Bool(number != 0),
+                (_, ValueRef::Integer(number)) => Value::from(number),
+                (_, ValueRef::Real(number)) => float(number),
+                (ColumnKind::Json, ValueRef::Text(text)) => serde_json::from_slice(text)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(text).into_owned())),
+                (_, ValueRef::Text(text)) => Value::String(String::from_utf8_lossy(text).into_owned()),
+                (_, ValueRef::Blob(bytes)) => Value::String(base64::engine::general_purpose::STANDARD.encode(bytes)),
+            }
+        }
+    }
+
+    /// Import every table of a SQLite database into a collection of the same name
+    ///
+    /// The database is opened read-only. Single-column indexes, and the
+    /// primary key when it is a single column, become indexes.
+    pub fn import_sqlite<P: AsRef<Path>>(db: &DatabaseCore, path: P, options: &ImportOptions) -> Result<ImportStats> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(sqlite_error)?;
+        let tables: Vec<String> = connection
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+            .and_then(|mut statement| statement.query_map([], |row| row.get(0))?.collect())
+            .map_err(sqlite_error)?;
+
+        let mut stats = ImportStats::default();
+        for table in tables {
+            let collection = db.collection(&table)?;
+            let mut statement = connection
+                .prepare(&format!("SELECT * FROM {}", quote(&table)))
+                .map_err(sqlite_error)?;
+            let columns: Vec<(String, ColumnKind)> = statement.columns().iter()
+                .map(|column| (column.name().to_string(), ColumnKind::from_declared(column.decl_type())))
+                .collect();
+
+            let mut batch = Batch::new(&collection, options);
+            let mut rows = statement.query([]).map_err(sqlite_error)?;
+            while let Some(row) = rows.next().map_err(sqlite_error)? {
+                let mut fields = Map::new();
+                for (i, (name, kind)) in columns.iter().enumerate() {
+                    fields.insert(name.clone(), kind.convert(row.get_ref(i).map_err(sqlite_error)?));
+                }
+                batch.push(fields)?;
+            }
+            stats.collections.insert(table.clone(), batch.finish()?);
+
+            if options.create_indexes {
+                for (column, unique) in single_column_indexes(&connection, &table)? {
+                    let field = if column == "_id" { &options.source_id_field } else { &column };
+                    stats.indexes += create_index(&collection, field, unique, false)?;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// (column, unique) of the table's single-column indexes and primary key
+    fn single_column_indexes(connection: &Connection, table: &str) -> Result<Vec<(String, bool)>> {
+        let mut indexes = Vec::new();
+
+        // table_info's pk column is the position in the primary key, 0 if not part of it
+        let primary_key: Vec<String> = connection
+            .prepare(&format!("PRAGMA table_info({})", quote(table)))
+            .and_then(|mut statement| {
+                statement.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, i64>(5)?)))?
+                    .filter(|column| !matches!(column, Ok((_, 0))))
+                    .map(|column| column.map(|(name, _)| name))
+                    .collect()
+            })
+            .map_err(sqlite_error)?;
+        if let [column] = primary_key.as_slice() {
+            indexes.push((column.clone(), true));
+        }
+
+        // Partial indexes only cover some rows - an index here would cover all
+        let listed: Vec<(String, bool)> = connection
+            .prepare(&format!("PRAGMA index_list({})", quote(table)))
+            .and_then(|mut statement| {
+                statement.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, bool>(2)?, row.get::<_, bool>(4)?)))?
+                    .filter(|index| !matches!(index, Ok((_, _, true))))
+                    .map(|index| index.map(|(name, unique, _)| (name, unique)))
+                    .collect()
+            })
+            .map_err(sqlite_error)?;
+        for (name, unique) in listed {
+            let columns: Vec<Option<String>> = connection
+                .prepare(&format!("PRAGMA index_info({})", quote(&name)))
+                .and_then(|mut statement| statement.query_map([], |row| row.get(2))?.collect())
+                .map_err(sqlite_error)?;
+            // Expression indexes have no column name
+            if let [Some(column)] = columns.as_slice() {
+                if !indexes.iter().any(|(indexed, _)| indexed == column) {
+                    indexes.push((column.clone(), unique));
+                }
+            }
+        }
+        Ok(indexes)
+    }
+
+    /// An SQL identifier, quoted
+    fn quote(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
@@ -1,35 +1,54 @@
 // src/index.rs
 // B+ Tree Index Implementation
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Read, Write, Seek, SeekFrom};
 use std::fs::File;
-use std::path::PathBuf;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use parking_lot::Mutex;
 use serde::{Serialize, Deserialize};
 use crate::document::DocumentId;
 use crate::error::{Result, MongoLiteError};
+use crate::transaction::IndexOperation;
 
 // B+ Tree Configuration
-#[allow(dead_code)]
 const BTREE_ORDER: usize = 32;
-#[allow(dead_code)]
 const MAX_KEYS: usize = BTREE_ORDER - 1;  // 31
-#[allow(dead_code)]
-const MIN_KEYS: usize = BTREE_ORDER / 2;   // 16
+const MIN_KEYS: usize = MAX_KEYS / 2;     // 15 - both halves of a split hold at least this
 
 // Node page constants (for file-based persistence)
 pub const NODE_PAGE_SIZE: usize = 4096; // 4KB pages
+
+/// Bytes a node with `data_len` bytes of JSON occupies on disk: the 5-byte
+/// header plus data, rounded up to whole pages
+fn node_span(data_len: usize) -> usize {
+    (5 + data_len).div_ceil(NODE_PAGE_SIZE) * NODE_PAGE_SIZE
+}
 const NODE_TYPE_INTERNAL: u8 = 0;
 const NODE_TYPE_LEAF: u8 = 1;
+const NODE_TYPE_STAMP: u8 = 2;
+const NODE_TYPE_PATCH: u8 = 3;
+
+/// Format version of index files, stamped on their first page. Files with
+/// another version (or none) are rewritten from the rebuilt index on open.
+pub const INDEX_FILE_VERSION: u32 = 1;
 
 /// Index key - supported types for indexing
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Numbers keep their kind (an Int key reads back as an integer, a Float key
+/// as a float) but compare by value, so 1 and 1.0 are the same key - see
+/// crate::numeric.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IndexKey {
     Null,
     Bool(bool),
     Int(i64),
     Float(OrderedFloat),
     String(String),
+    /// Integers above i64::MAX
+    UInt(u64),
 }
 
 /// OrderedFloat wrapper for f64 to enable Ord
@@ -61,6 +80,14 @@ impl Ord for OrderedFloat {
     }
 }
 
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
 /// Implement Ord for IndexKey - defines ordering for B+ tree
 impl PartialOrd for IndexKey {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
@@ -80,15 +107,12 @@ impl Ord for IndexKey {
             (Bool(_), _) => std::cmp::Ordering::Less,
             (_, Bool(_)) => std::cmp::Ordering::Greater,
 
-            (Int(a), Int(b)) => a.cmp(b),
-            (Int(_), _) => std::cmp::Ordering::Less,
-            (_, Int(_)) => std::cmp::Ordering::Greater,
-
-            (Float(a), Float(b)) => a.cmp(b),
-            (Float(_), _) => std::cmp::Ordering::Less,
-            (_, Float(_)) => std::cmp::Ordering::Greater,
-
             (String(a), String(b)) => a.cmp(b),
+            (String(_), _) => std::cmp::Ordering::Greater,
+            (_, String(_)) => std::cmp::Ordering::Less,
+
+            // Numbers of any kind, by value
+            (a, b) => a.number().cmp(b.number()),
         }
     }
 }
@@ -102,6 +126,8 @@ impl From<&serde_json::Value> for IndexKey {
             serde_json::Value::Number(n) => {
                 if let Some(i) = n.as_i64() {
                     IndexKey::Int(i)
+                } else if let Some(u) = n.as_u64() {
+                    IndexKey::UInt(u)
                 } else if let Some(f) = n.as_f64() {
                     IndexKey::Float(OrderedFloat(f))
                 } else {
@@ -114,7 +140,75 @@ impl From<&serde_json::Value> for IndexKey {
     }
 }
 
-/// B+ Tree Node types
+impl IndexKey {
+    /// Numeric value of an Int, UInt or Float key
+    fn number(&self) -> crate::numeric::Num {
+        use crate::numeric::Num;
+        match self {
+            IndexKey::Int(i) => Num::Int(*i as i128),
+            IndexKey::UInt(u) => Num::Int(*u as i128),
+            IndexKey::Float(f) => Num::Float(f.0),
+            _ => unreachable!("not a numeric key"),
+        }
+    }
+
+    /// Key stored by hashed indexes: a 64-bit FNV-1a hash of the value's
+    /// index key. Only useful for equality - hash order says nothing about
+    /// value order, and unrelated values may collide.
+    pub fn hashed(value: &serde_json::Value) -> Self {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        // Equal numbers must hash alike: integral floats hash as integers
+        let key = match IndexKey::from(value) {
+            IndexKey::Float(f) if f.0.fract() == 0.0 && f.0 >= i64::MIN as f64 && f.0 < u64::MAX as f64 => {
+                if f.0 < i64::MAX as f64 { IndexKey::Int(f.0 as i64) } else { IndexKey::UInt(f.0 as u64) }
+            }
+            key => key,
+        };
+        match key {
+            IndexKey::Null => feed(&[0]),
+            IndexKey::Bool(b) => feed(&[1, b as u8]),
+            IndexKey::Int(i) => {
+                feed(&[2]);
+                feed(&i.to_le_bytes());
+            }
+            IndexKey::UInt(u) => {
+                feed(&[2]);
+                feed(&(u as i128).to_le_bytes());
+            }
+            IndexKey::Float(f) => {
+                feed(&[3]);
+                feed(&f.0.to_bits().to_le_bytes());
+            }
+            IndexKey::String(s) => {
+                feed(&[4]);
+                feed(s.as_bytes());
+            }
+        }
+
+        IndexKey::Int(hash as i64)
+    }
+
+    /// JSON value of the key (inverse of From<&Value> for scalars)
+    pub fn to_value(&self) -> serde_json::Value {
+        match self {
+            IndexKey::Null => serde_json::Value::Null,
+            IndexKey::Bool(b) => serde_json::Value::Bool(*b),
+            IndexKey::Int(i) => serde_json::Value::from(*i),
+            IndexKey::UInt(u) => serde_json::Value::from(*u),
+            IndexKey::Float(f) => serde_json::Value::from(f.0),
+            IndexKey::String(s) => serde_json::Value::String(s.clone()),
+        }
+    }
+}
+
+/// On-disk B+ tree node: one JSON-encoded node per run of NODE_PAGE_SIZE pages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BTreeNode {
     Internal(InternalNode),
@@ -125,6 +219,9 @@ pub enum BTreeNode {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InternalNode {
     pub keys: Vec<IndexKey>,
+    /// Document ids completing the routing keys (separators are key + id)
+    #[serde(default)]
+    pub document_ids: Vec<DocumentId>,
     pub children_offsets: Vec<u64>,
 }
 
@@ -133,14 +230,156 @@ pub struct InternalNode {
 pub struct LeafNode {
     pub keys: Vec<IndexKey>,
     pub document_ids: Vec<DocumentId>,
-    pub next_leaf_offset: u64,  // File offset to next leaf node (0 = none)
+    /// File offset of the next leaf (0 = none). Written as 0: leaves are
+    /// copied on write, and a link on disk would send every leaf before a
+    /// copied one to a new run too. The chain is kept in memory instead
+    /// (see Node), so links in older files are not read.
+    pub next_leaf_offset: u64,
+}
+
+/// Position of a node in the tree's arena. Nodes of a tree loaded from a
+/// file keep the index of their first page (offset / NODE_PAGE_SIZE), so a
+/// child link read from disk maps to its slot directly.
+type NodeId = usize;
+
+/// An index entry. Entries order by key, then document id, so each one -
+/// even among many equal keys of a non-unique index - has a single place in
+/// the tree, and finding or deleting it is one descent.
+type Entry = (IndexKey, DocumentId);
+
+/// In-memory node
+///
+/// Child i of an internal node holds the entries in
+/// [separators[i-1], separators[i]). Leaves are chained left to right
+/// through `next`; a leaf read from a file has its link looked up through
+/// the parents the first time a scan moves past it (see next_leaf).
+#[derive(Debug, Clone)]
+enum Node {
+    Internal { separators: Vec<Entry>, children: Vec<NodeId> },
+    Leaf { entries: Vec<Entry>, next: OnceLock<Option<NodeId>> },
 }
 
+impl Node {
+    fn empty_leaf() -> Self {
+        Node::Leaf { entries: Vec::new(), next: OnceLock::from(None) }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Internal { separators, .. } => separators.len(),
+            Node::Leaf { entries, .. } => entries.len(),
+        }
+    }
+
+    /// Node of a page, with links turned into the ids of `pages` page slots
+    /// (a leaf's next link is left to be looked up)
+    fn from_page(page: BTreeNode, offset: u64, pages: usize) -> Result<Self> {
+        let link = |target: u64| -> Result<NodeId> {
+            let id = (target / NODE_PAGE_SIZE as u64) as usize;
+            if (id * NODE_PAGE_SIZE) as u64 != target || id >= pages {
+                return Err(MongoLiteError::Corruption(
+                    format!("Index node at offset {} links to invalid offset {}", offset, target)
+                ));
+            }
+            Ok(id)
+        };
+        match page {
+            BTreeNode::Internal(internal)
+                if internal.children_offsets.len() == internal.keys.len() + 1
+                    && internal.document_ids.len() == internal.keys.len() =>
+            {
+                Ok(Node::Internal {
+                    separators: internal.keys.into_iter().zip(internal.document_ids).collect(),
+                    children: internal.children_offsets.into_iter().map(link).collect::<Result<_>>()?,
+                })
+            }
+            BTreeNode::Leaf(leaf) if leaf.document_ids.len() == leaf.keys.len() => {
+                Ok(Node::Leaf { entries: leaf.keys.into_iter().zip(leaf.document_ids).collect(), next: OnceLock::new() })
+            }
+            _ => Err(MongoLiteError::Corruption(
+                format!("Index node at offset {} has mismatched keys and pointers", offset)
+            )),
+        }
+    }
+}
+
+/// Child that holds (or would hold) `entry`
+fn child_for(separators: &[Entry], entry: &Entry) -> usize {
+    separators.partition_point(|separator| separator <= entry)
+}
+
+/// Leftmost child that may hold entries with `key`
+fn first_child_for_key(separators: &[Entry], key: &IndexKey) -> usize {
+    separators.partition_point(|(separator, _)| separator < key)
+}
+
+fn before_start(key: &IndexKey, start: Bound<&IndexKey>) -> bool {
+    match start {
+        Bound::Included(start) => key < start,
+        Bound::Excluded(start) => key <= start,
+        Bound::Unbounded => false,
+    }
+}
+
+fn after_end(key: &IndexKey, end: Bound<&IndexKey>) -> bool {
+    match end {
+        Bound::Included(end) => key > end,
+        Bound::Excluded(end) => key >= end,
+        Bound::Unbounded => false,
+    }
+}
+
+/// File a lazily loaded tree reads its remaining nodes from
+type NodeSource = Arc<Mutex<File>>;
+
 /// B+ Tree - main index structure
 #[derive(Debug, Clone)]
 pub struct BPlusTree {
-    root: Box<BTreeNode>,
+    /// Node arena; an empty slot of a loaded tree is read from `source`
+    nodes: Vec<OnceLock<Node>>,
+    /// Slots released by merges, reused by splits
+    free: Vec<NodeId>,
+    root: NodeId,
     pub metadata: IndexMetadata,
+    /// Set while nodes of a loaded tree are still on disk
+    source: Option<NodeSource>,
+    /// Nodes changed since the tree was last written to its index file
+    dirty: HashSet<NodeId>,
+    /// Where the nodes are in that file, for incremental saves
+    persisted: Option<PersistedPages>,
+}
+
+/// Page run of each node: its offset and capacity (whole pages)
+type PageMap = HashMap<NodeId, (u64, usize)>;
+
+/// Page runs of the index file a tree was last written to
+///
+/// Saves never write over a run the tree in the file uses: a changed node
+/// is written to a free or new run (see prepare_changes), and its old run
+/// is freed once the stamp that stops pointing at it is in the file.
+#[derive(Debug, Clone)]
+struct PersistedPages {
+    path: PathBuf,
+    /// Stamp the file carries once the last prepared changes are committed
+    stamp: IndexFileStamp,
+    pages: PageMap,
+    /// Runs no tree the file may carry uses
+    free: Vec<(u64, usize)>,
+    /// Runs the last prepared patch stopped using, free once it is committed
+    retired: Vec<(u64, usize)>,
+    /// Runs of nodes released since the last save
+    released: Vec<(u64, usize)>,
+    /// End of the last run
+    end: u64,
+}
+
+/// First page of a prepared patch: the runs it writes, in order, and the
+/// stamp that then points the file at the new root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PatchHeader {
+    stamp: IndexFileStamp,
+    /// Offset and length of each page image following the header
+    pages: Vec<(u64, usize)>,
 }
 
 /// Index metadata
@@ -154,20 +393,122 @@ pub struct IndexMetadata {
     pub tree_height: u32,
     #[serde(default)]
     pub root_offset: u64,  // File offset to root node (0 = in-memory only)
+    /// Keys are hashes of the field value (equality lookups only)
+    #[serde(default)]
+    pub hashed: bool,
+    /// Identity of this definition, stamped on its index file - an index
+    /// created later under the same name gets another one (empty for
+    /// definitions from before it was kept; _id indexes use the collection's)
+    #[serde(default)]
+    pub uuid: String,
 }
 
+/// First page of an index file: `[u8 2][u32 len][JSON]`, padded to a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexFileStamp {
+    pub version: u32,
+    /// `IndexMetadata::uuid` of the index the file was written for
+    pub uuid: String,
+    /// Offset of the root node (0 in files from before it was kept)
+    #[serde(default)]
+    pub root_offset: u64,
+    /// Bumped by every save, so a tree can tell whether the file still
+    /// holds what it last wrote
+    #[serde(default)]
+    pub generation: u64,
+    /// Transaction the file (or patch) was prepared for, so a file a crash
+    /// left prepared can be committed if the transaction was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<u64>,
+    /// Identity of the save that wrote the file (a fresh uuid each time),
+    /// which the collection metadata records while the file holds the index
+    /// as of the documents (see CollectionMeta::index_files)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub save_id: String,
+}
+
+/// How an index file relates to the index it is named after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexFileStatus {
+    Missing,
+    Current,
+    /// Written by another format version or for another index; the reason
+    Stale(String),
+}
+
+impl IndexFileStamp {
+    /// Stamp of the current format for the index with `uuid`
+    pub fn current(uuid: &str) -> Self {
+        IndexFileStamp {
+            version: INDEX_FILE_VERSION,
+            uuid: uuid.to_string(),
+            root_offset: 0,
+            generation: 0,
+            transaction: None,
+            save_id: String::new(),
+        }
+    }
+
+    /// Stamp at the start of `data`, None if the file has none (written
+    /// before stamps were, or damaged)
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 5 || data[0] != NODE_TYPE_STAMP {
+            return None;
+        }
+        let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+        let bytes = data.get(5..5usize.checked_add(len)?)?;
+        serde_json::from_slice(bytes).ok()
+    }
+
+    /// Write the stamp page at the current position of `file`
+    fn write(&self, file: &mut File) -> Result<()> {
+        let bytes = serde_json::to_vec(self)?;
+        let mut page = vec![0u8; node_span(bytes.len())];
+        page[0] = NODE_TYPE_STAMP;
+        page[1..5].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+        page[5..5 + bytes.len()].copy_from_slice(&bytes);
+        file.write_all(&page)?;
+        Ok(())
+    }
+}
+
+/// Check the stamp of the index file at `path` against the index's uuid
+pub fn check_index_file(path: &Path, uuid: &str) -> Result<IndexFileStatus> {
+    let mut first_page = Vec::with_capacity(NODE_PAGE_SIZE);
+    match File::open(path) {
+        Ok(file) => { file.take(NODE_PAGE_SIZE as u64).read_to_end(&mut first_page)?; }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(IndexFileStatus::Missing),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(IndexFileStatus::of(IndexFileStamp::parse(&first_page).as_ref(), uuid))
+}
+
+impl IndexFileStatus {
+    /// Status of an existing file with `stamp` for the index with `uuid`
+    pub fn of(stamp: Option<&IndexFileStamp>, uuid: &str) -> Self {
+        match stamp {
+            None => IndexFileStatus::Stale("no format stamp".to_string()),
+            Some(stamp) if stamp.version != INDEX_FILE_VERSION => IndexFileStatus::Stale(
+                format!("format version {}, expected {}", stamp.version, INDEX_FILE_VERSION)
+            ),
+            Some(stamp) if stamp.uuid != uuid => IndexFileStatus::Stale(
+                format!("written for index {:?}, expected {:?}", stamp.uuid, uuid)
+            ),
+            Some(_) => IndexFileStatus::Current,
+        }
+    }
+}
+
+/// Name suffix of hashed indexes ("users_email_hashed")
+pub const HASHED_INDEX_SUFFIX: &str = "_hashed";
+
 impl BPlusTree {
     /// Create new B+ tree index
     pub fn new(name: String, field: String, unique: bool) -> Self {
-        // Start with empty leaf node as root
-        let root = Box::new(BTreeNode::Leaf(LeafNode {
-            keys: Vec::new(),
-            document_ids: Vec::new(),
-            next_leaf_offset: 0,
-        }));
-
         BPlusTree {
-            root,
+            nodes: vec![OnceLock::from(Node::empty_leaf())],
+            free: Vec::new(),
+            root: 0,
             metadata: IndexMetadata {
                 name,
                 field,
@@ -176,106 +517,600 @@ impl BPlusTree {
                 num_keys: 0,
                 tree_height: 1,
                 root_offset: 0,
+                hashed: false,
+                uuid: String::new(),
             },
+            source: None,
+            dirty: HashSet::new(),
+            persisted: None,
         }
     }
 
-    /// Search for a key in the index
-    pub fn search(&self, key: &IndexKey) -> Option<DocumentId> {
-        self.search_in_node(&self.root, key)
+    /// Create new hashed index (never unique - hashes may collide)
+    pub fn new_hashed(name: String, field: String) -> Self {
+        let mut tree = Self::new(name, field, false);
+        tree.metadata.hashed = true;
+        tree
+    }
+
+    /// Empty tree with the same definition (name, field, options and uuid)
+    pub fn empty_like(&self) -> Self {
+        let mut tree = Self::new(self.metadata.name.clone(), self.metadata.field.clone(), self.metadata.unique);
+        tree.metadata.sparse = self.metadata.sparse;
+        tree.metadata.hashed = self.metadata.hashed;
+        tree.metadata.uuid = self.metadata.uuid.clone();
+        tree
     }
 
-    fn search_in_node(&self, node: &BTreeNode, key: &IndexKey) -> Option<DocumentId> {
-        match node {
-            BTreeNode::Internal(internal) => {
-                // Find which child to descend into
-                let _child_index = self.find_child_index(&internal.keys, key);
-                // In real implementation, would load child from disk
-                // For now, simplified in-memory version
-                None // TODO: implement child loading
+    /// Index key for a field value - hashed for hashed indexes
+    pub fn key_for(&self, value: &serde_json::Value) -> IndexKey {
+        if self.metadata.hashed {
+            IndexKey::hashed(value)
+        } else {
+            IndexKey::from(value)
+        }
+    }
+
+    /// Node by id, read from the source file on first access
+    fn node(&self, id: NodeId) -> Result<&Node> {
+        let slot = self.nodes.get(id).ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index node {} does not exist", id)
+        ))?;
+        if let Some(node) = slot.get() {
+            return Ok(node);
+        }
+        let offset = (id * NODE_PAGE_SIZE) as u64;
+        let source = self.source.as_ref().ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index node at offset {} is not loaded and the tree has no file", offset)
+        ))?;
+        let page = Self::load_node(&mut source.lock(), offset)?;
+        let node = Node::from_page(page, offset, self.nodes.len())?;
+        // A concurrent reader may have loaded it first - either copy will do
+        Ok(slot.get_or_init(|| node))
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut Node> {
+        self.node(id)?;
+        self.dirty.insert(id);
+        self.nodes[id].get_mut().ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index node {} failed to load", id)
+        ))
+    }
+
+    fn children(&self, id: NodeId) -> Result<Vec<NodeId>> {
+        match self.node(id)? {
+            Node::Internal { children, .. } => Ok(children.clone()),
+            Node::Leaf { .. } => Ok(Vec::new()),
+        }
+    }
+
+    fn alloc(&mut self, node: Node) -> NodeId {
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = OnceLock::from(node);
+                id
             }
-            BTreeNode::Leaf(leaf) => {
-                // Binary search in leaf
-                match leaf.keys.binary_search(key) {
-                    Ok(index) => Some(leaf.document_ids[index].clone()),
-                    Err(_) => None,
-                }
+            None => {
+                self.nodes.push(OnceLock::from(node));
+                self.nodes.len() - 1
             }
+        };
+        self.dirty.insert(id);
+        id
+    }
+
+    fn release(&mut self, id: NodeId) -> Result<Node> {
+        self.node(id)?;
+        let node = self.nodes[id].take().ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index node {} failed to load", id)
+        ))?;
+        self.free.push(id);
+        self.dirty.remove(&id);
+        if let Some(persisted) = &mut self.persisted {
+            persisted.released.extend(persisted.pages.remove(&id));
         }
+        Ok(node)
+    }
+
+    /// Search for a key in the index: every document with it, in id order
+    /// (at most one for a unique index)
+    pub fn search(&self, key: &IndexKey) -> Result<Vec<DocumentId>> {
+        self.range_scan(key, key, true, true)
+    }
+
+    /// Whether the index holds `key` for any document
+    pub fn contains_key(&self, key: &IndexKey) -> Result<bool> {
+        let mut found = Vec::new();
+        self.scan(Bound::Included(key), Bound::Included(key), Some(1), &mut found)?;
+        Ok(!found.is_empty())
     }
 
     /// Insert key-value pair into index
     pub fn insert(&mut self, key: IndexKey, doc_id: DocumentId) -> Result<()> {
         // Check unique constraint
-        if self.metadata.unique && self.search(&key).is_some() {
+        if self.metadata.unique && self.contains_key(&key)? {
             return Err(MongoLiteError::IndexError(
                 format!("Duplicate key: {:?} (unique index)", key)
             ));
         }
 
-        // For now, simplified insert into leaf
-        // Full implementation would handle splits and internal nodes
-        if let BTreeNode::Leaf(ref mut leaf) = *self.root {
-            let insert_pos = leaf.keys.binary_search(&key).unwrap_or_else(|pos| pos);
-            leaf.keys.insert(insert_pos, key);
-            leaf.document_ids.insert(insert_pos, doc_id);
-            self.metadata.num_keys += 1;
+        if let Some((separator, right)) = self.insert_into(self.root, (key, doc_id))? {
+            // Root split - the tree grows one level
+            let left = self.root;
+            self.root = self.alloc(Node::Internal { separators: vec![separator], children: vec![left, right] });
+            self.metadata.tree_height += 1;
         }
+        self.metadata.num_keys += 1;
 
         Ok(())
     }
 
+    /// Insert below node `id`; returns the separator and new right sibling if it split
+    fn insert_into(&mut self, id: NodeId, entry: Entry) -> Result<Option<(Entry, NodeId)>> {
+        let (idx, child) = match self.node_mut(id)? {
+            Node::Leaf { entries, next } => {
+                let pos = entries.partition_point(|existing| existing <= &entry);
+                entries.insert(pos, entry);
+
+                if entries.len() <= MAX_KEYS {
+                    return Ok(None);
+                }
+                let right_entries = entries.split_off(entries.len() / 2);
+                let separator = right_entries[0].clone();
+                let right = Node::Leaf { entries: right_entries, next: next.clone() };
+                let right = self.alloc(right);
+                // The new leaf goes into the chain right after this one
+                if let Node::Leaf { next, .. } = self.node_mut(id)? {
+                    *next = OnceLock::from(Some(right));
+                }
+                return Ok(Some((separator, right)));
+            }
+            Node::Internal { separators, children } => {
+                let idx = child_for(separators, &entry);
+                (idx, children[idx])
+            }
+        };
+
+        let Some((separator, right)) = self.insert_into(child, entry)? else {
+            return Ok(None);
+        };
+        let Node::Internal { separators, children } = self.node_mut(id)? else {
+            return Ok(None);
+        };
+        separators.insert(idx, separator);
+        children.insert(idx + 1, right);
+
+        if separators.len() <= MAX_KEYS {
+            return Ok(None);
+        }
+        // The middle separator moves up; it stays in neither half
+        let mid = separators.len() / 2;
+        let right_separators = separators.split_off(mid + 1);
+        let right_children = children.split_off(mid + 1);
+        let Some(separator) = separators.pop() else {
+            return Ok(None);
+        };
+        let right = self.alloc(Node::Internal { separators: right_separators, children: right_children });
+        Ok(Some((separator, right)))
+    }
+
     /// Delete key-document pair from index
     pub fn delete(&mut self, key: &IndexKey, doc_id: &DocumentId) -> Result<()> {
-        // For now, simplified delete from leaf
-        // Full implementation would handle merges and internal nodes
-        if let BTreeNode::Leaf(ref mut leaf) = *self.root {
-            // Find the key position
-            if let Ok(pos) = leaf.keys.binary_search(key) {
-                // Verify this is the correct document ID
-                if &leaf.document_ids[pos] == doc_id {
-                    leaf.keys.remove(pos);
-                    leaf.document_ids.remove(pos);
-                    self.metadata.num_keys -= 1;
+        self.delete_entry(&(key.clone(), doc_id.clone())).map(|_| ())
+    }
+
+    /// Apply a transaction's changes to this index in one pass
+    ///
+    /// Changes that cancel out (an entry inserted and deleted again) are
+    /// dropped. The rest are applied deletes first, each in entry order, so
+    /// neighbouring changes land in the same leaves and a unique key can
+    /// move from one document to another within the batch. All or nothing:
+    /// on an error the changes applied before it are undone.
+    pub fn apply_batch<I>(&mut self, changes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (IndexOperation, IndexKey, DocumentId)>,
+    {
+        let mut net: BTreeMap<Entry, i64> = BTreeMap::new();
+        for (operation, key, doc_id) in changes {
+            *net.entry((key, doc_id)).or_default() += match operation {
+                IndexOperation::Insert => 1,
+                IndexOperation::Delete => -1,
+            };
+        }
+        let (inserts, deletes): (Vec<_>, Vec<_>) = net.into_iter()
+            .filter(|(_, count)| *count != 0)
+            .partition(|(_, count)| *count > 0);
+
+        let mut deleted = Vec::with_capacity(deletes.len());
+        let mut inserted = Vec::with_capacity(inserts.len());
+        let apply = || -> Result<()> {
+            for (entry, _) in deletes {
+                if self.delete_entry(&entry)? {
+                    deleted.push(entry);
+                }
+            }
+            for (entry, _) in inserts {
+                self.insert(entry.0.clone(), entry.1.clone())?;
+                inserted.push(entry);
+            }
+            Ok(())
+        };
+        let Err(error) = apply() else {
+            return Ok(());
+        };
+
+        for entry in inserted.iter().rev() {
+            self.delete_entry(entry)?;
+        }
+        for (key, doc_id) in deleted.into_iter().rev() {
+            self.insert(key, doc_id)?;
+        }
+        Err(error)
+    }
+
+    /// Delete an entry; false if the index does not hold it
+    fn delete_entry(&mut self, entry: &Entry) -> Result<bool> {
+        if !self.delete_from(self.root, entry)? {
+            return Ok(false);
+        }
+        self.metadata.num_keys -= 1;
+
+        // An internal root left with a single child hands the root to it
+        while let Node::Internal { separators, children } = self.node(self.root)? {
+            if !separators.is_empty() {
+                break;
+            }
+            let child = children[0];
+            self.release(self.root)?;
+            self.root = child;
+            self.metadata.tree_height -= 1;
+        }
+
+        Ok(true)
+    }
+
+    /// Delete below node `id`, rebalancing a child left under MIN_KEYS
+    fn delete_from(&mut self, id: NodeId, entry: &Entry) -> Result<bool> {
+        let (idx, child) = match self.node_mut(id)? {
+            Node::Leaf { entries, .. } => {
+                let found = entries.binary_search(entry);
+                if let Ok(pos) = found {
+                    entries.remove(pos);
+                }
+                return Ok(found.is_ok());
+            }
+            Node::Internal { separators, children } => {
+                let idx = child_for(separators, entry);
+                (idx, children[idx])
+            }
+        };
+
+        if !self.delete_from(child, entry)? {
+            return Ok(false);
+        }
+        if self.node(child)?.len() < MIN_KEYS {
+            self.rebalance(id, idx)?;
+        }
+        Ok(true)
+    }
+
+    /// Refill child `idx` of `parent` from a sibling with entries to spare,
+    /// or merge it with one
+    fn rebalance(&mut self, parent: NodeId, idx: usize) -> Result<()> {
+        let children = self.children(parent)?;
+        if idx > 0 && self.node(children[idx - 1])?.len() > MIN_KEYS {
+            return self.borrow_from_left(parent, children[idx - 1], children[idx], idx - 1);
+        }
+        if idx + 1 < children.len() && self.node(children[idx + 1])?.len() > MIN_KEYS {
+            return self.borrow_from_right(parent, children[idx], children[idx + 1], idx);
+        }
+        if children.len() < 2 {
+            return Ok(());
+        }
+        self.merge(parent, idx.saturating_sub(1))
+    }
+
+    /// Separator `idx` of internal node `parent`
+    fn separator_mut(&mut self, parent: NodeId, idx: usize) -> Result<&mut Entry> {
+        match self.node_mut(parent)? {
+            Node::Internal { separators, .. } => Ok(&mut separators[idx]),
+            Node::Leaf { .. } => Err(MongoLiteError::Corruption(
+                format!("Index node {} has no separators", parent)
+            )),
+        }
+    }
+
+    /// Move the last entry of `left` to the front of its right sibling `child`
+    fn borrow_from_left(&mut self, parent: NodeId, left: NodeId, child: NodeId, separator: usize) -> Result<()> {
+        match self.node_mut(left)? {
+            Node::Leaf { entries, .. } => {
+                let Some(entry) = entries.pop() else { return Ok(()) };
+                // The moved entry is now the first of `child`, so it separates them
+                *self.separator_mut(parent, separator)? = entry.clone();
+                if let Node::Leaf { entries, .. } = self.node_mut(child)? {
+                    entries.insert(0, entry);
+                }
+            }
+            Node::Internal { separators, children } => {
+                let (Some(up), Some(grandchild)) = (separators.pop(), children.pop()) else { return Ok(()) };
+                // Rotate: the left sibling's last separator goes up, the old one down
+                let down = std::mem::replace(self.separator_mut(parent, separator)?, up);
+                if let Node::Internal { separators, children } = self.node_mut(child)? {
+                    separators.insert(0, down);
+                    children.insert(0, grandchild);
                 }
             }
         }
+        Ok(())
+    }
 
+    /// Move the first entry of `right` to the end of its left sibling `child`
+    fn borrow_from_right(&mut self, parent: NodeId, child: NodeId, right: NodeId, separator: usize) -> Result<()> {
+        match self.node_mut(right)? {
+            Node::Leaf { entries, .. } => {
+                let entry = entries.remove(0);
+                let Some(first) = entries.first().cloned() else { return Ok(()) };
+                *self.separator_mut(parent, separator)? = first;
+                if let Node::Leaf { entries, .. } = self.node_mut(child)? {
+                    entries.push(entry);
+                }
+            }
+            Node::Internal { separators, children } => {
+                let up = separators.remove(0);
+                let grandchild = children.remove(0);
+                let down = std::mem::replace(self.separator_mut(parent, separator)?, up);
+                if let Node::Internal { separators, children } = self.node_mut(child)? {
+                    separators.push(down);
+                    children.push(grandchild);
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Find child index for key in internal node
-    fn find_child_index(&self, keys: &[IndexKey], key: &IndexKey) -> usize {
-        keys.binary_search(key).unwrap_or_else(|pos| pos)
+    /// Merge child `idx + 1` of `parent` into child `idx`
+    fn merge(&mut self, parent: NodeId, idx: usize) -> Result<()> {
+        let (separator, left, right) = match self.node_mut(parent)? {
+            Node::Internal { separators, children } => (separators.remove(idx), children[idx], children.remove(idx + 1)),
+            Node::Leaf { .. } => return Ok(()),
+        };
+        let right = self.release(right)?;
+        match (self.node_mut(left)?, right) {
+            (Node::Leaf { entries, next }, Node::Leaf { entries: right_entries, next: right_next }) => {
+                entries.extend(right_entries);
+                // The merged leaf leaves the chain
+                *next = right_next;
+            }
+            (
+                Node::Internal { separators, children },
+                Node::Internal { separators: right_separators, children: right_children },
+            ) => {
+                separators.push(separator);
+                separators.extend(right_separators);
+                children.extend(right_children);
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
-    /// Range scan: find all keys between start and end
-    pub fn range_scan(
+    /// Leaf where a scan from `start` begins: the leftmost one that may hold it
+    fn first_leaf(&self, start: Bound<&IndexKey>) -> Result<NodeId> {
+        let mut id = self.root;
+        while let Node::Internal { separators, children } = self.node(id)? {
+            id = match start {
+                Bound::Included(start) | Bound::Excluded(start) => children[first_child_for_key(separators, start)],
+                Bound::Unbounded => children[0],
+            };
+        }
+        Ok(id)
+    }
+
+    /// Leaf after `leaf` in the chain, None for the last
+    ///
+    /// Leaves read from a file carry no link (see LeafNode). Theirs is found
+    /// once, through the parents from the leaf's first entry: down the path
+    /// to the leaf, then down the leftmost path of the nearest subtree to
+    /// its right. Splits and merges keep it from then on.
+    fn next_leaf(&self, leaf: NodeId) -> Result<Option<NodeId>> {
+        let Node::Leaf { entries, next } = self.node(leaf)? else {
+            return Err(MongoLiteError::Corruption(
+                format!("Leaf chain of index {} reaches an internal node", self.metadata.name)
+            ));
+        };
+        if let Some(next) = next.get() {
+            return Ok(*next);
+        }
+        let found = self.leaf_after(leaf, entries.first())?;
+        Ok(*next.get_or_init(|| found))
+    }
+
+    /// Leaf after `leaf` in key order, looked up through the parents; no
+    /// other leaf is read
+    fn leaf_after(&self, leaf: NodeId, first: Option<&Entry>) -> Result<Option<NodeId>> {
+        // Only an empty root leaf has no entries
+        let Some(first) = first else {
+            return Ok(None);
+        };
+        let mut id = self.root;
+        let mut depth = 0;
+        let mut right = None;
+        while id != leaf {
+            let Node::Internal { separators, children } = self.node(id)? else {
+                return Err(MongoLiteError::Corruption(
+                    format!("Leaf {} of index {} is not where its first entry leads", leaf, self.metadata.name)
+                ));
+            };
+            let idx = child_for(separators, first);
+            depth += 1;
+            if idx + 1 < children.len() {
+                right = Some((children[idx + 1], depth));
+            }
+            id = children[idx];
+        }
+        let Some((mut id, mut at)) = right else {
+            return Ok(None);
+        };
+        while at < depth {
+            let Node::Internal { children, .. } = self.node(id)? else {
+                return Err(MongoLiteError::Corruption(
+                    format!("Leaves of index {} are not all at the same depth", self.metadata.name)
+                ));
+            };
+            id = children[0];
+            at += 1;
+        }
+        Ok(Some(id))
+    }
+
+    /// Collect entries within the bounds in key order: one descent to the
+    /// first leaf, then along the leaf chain until the end bound (or `limit`)
+    fn scan(
         &self,
-        start: &IndexKey,
-        end: &IndexKey,
-        inclusive_start: bool,
-        inclusive_end: bool,
-    ) -> Vec<DocumentId> {
-        let mut results = Vec::new();
+        start: Bound<&IndexKey>,
+        end: Bound<&IndexKey>,
+        limit: Option<usize>,
+        results: &mut Vec<(IndexKey, DocumentId)>,
+    ) -> Result<()> {
+        self.walk(start, end, &mut |(key, doc_id)| {
+            if limit.is_some_and(|limit| results.len() >= limit) {
+                return false;
+            }
+            results.push((key.clone(), doc_id.clone()));
+            true
+        })
+    }
 
-        if let BTreeNode::Leaf(leaf) = &*self.root {
-            for (i, key) in leaf.keys.iter().enumerate() {
-                // Check start bound
-                if *key < *start || (!inclusive_start && *key == *start) {
+    /// Visit entries within the bounds in key order until `visit` returns false
+    fn walk(&self, start: Bound<&IndexKey>, end: Bound<&IndexKey>, visit: &mut dyn FnMut(&Entry) -> bool) -> Result<()> {
+        let mut leaf = Some(self.first_leaf(start)?);
+        while let Some(id) = leaf {
+            let Node::Leaf { entries, .. } = self.node(id)? else {
+                return Err(MongoLiteError::Corruption(
+                    format!("Leaf chain of index {} reaches an internal node", self.metadata.name)
+                ));
+            };
+            for entry in entries {
+                if before_start(&entry.0, start) {
                     continue;
                 }
+                if after_end(&entry.0, end) || !visit(entry) {
+                    return Ok(());
+                }
+            }
+            leaf = self.next_leaf(id)?;
+        }
+        Ok(())
+    }
 
-                // Check end bound
-                if *key > *end || (!inclusive_end && *key == *end) {
-                    break;
+    /// Visit entries below node `id` within the bounds in descending order.
+    /// Leaves only link forward, so this descends right to left, skipping
+    /// children that start past the end bound. Returns false once stopped.
+    fn walk_back(
+        &self,
+        id: NodeId,
+        start: Bound<&IndexKey>,
+        end: Bound<&IndexKey>,
+        visit: &mut dyn FnMut(&Entry) -> bool,
+    ) -> Result<bool> {
+        match self.node(id)? {
+            Node::Leaf { entries, .. } => {
+                for entry in entries.iter().rev() {
+                    if after_end(&entry.0, end) {
+                        continue;
+                    }
+                    if before_start(&entry.0, start) || !visit(entry) {
+                        return Ok(false);
+                    }
                 }
+                Ok(true)
+            }
+            Node::Internal { separators, children } => {
+                // Child i starts at separator i-1, so the last child that can
+                // hold entries up to the end bound follows the separators below it
+                let last = match end {
+                    Bound::Included(end) => separators.partition_point(|(separator, _)| separator <= end),
+                    Bound::Excluded(end) => first_child_for_key(separators, end),
+                    Bound::Unbounded => separators.len(),
+                };
+                for child in children[..=last].iter().rev() {
+                    if !self.walk_back(*child, start, end, visit)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
 
-                results.push(leaf.document_ids[i].clone());
+    /// Entries within the bounds in key order, or descending if `reverse`,
+    /// without the first `skip` and at most `limit` of them. Skipped entries
+    /// are passed over in the leaves, never collected, so a window of a large
+    /// index costs about skip + limit entries.
+    pub fn scan_entries(
+        &self,
+        start: Bound<&IndexKey>,
+        end: Bound<&IndexKey>,
+        reverse: bool,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(IndexKey, DocumentId)>> {
+        let mut results = Vec::new();
+        let mut skipped = 0;
+        let mut visit = |(key, doc_id): &Entry| {
+            if skipped < skip {
+                skipped += 1;
+                return true;
             }
+            if limit.is_some_and(|limit| results.len() >= limit) {
+                return false;
+            }
+            results.push((key.clone(), doc_id.clone()));
+            true
+        };
+        if reverse {
+            self.walk_back(self.root, start, end, &mut visit)?;
+        } else {
+            self.walk(start, end, &mut visit)?;
         }
+        Ok(results)
+    }
+
+    /// Range scan: find all keys between start and end
+    pub fn range_scan(
+        &self,
+        start: &IndexKey,
+        end: &IndexKey,
+        inclusive_start: bool,
+        inclusive_end: bool,
+    ) -> Result<Vec<DocumentId>> {
+        Ok(self.range_scan_entries(start, end, inclusive_start, inclusive_end)?
+            .into_iter()
+            .map(|(_, doc_id)| doc_id)
+            .collect())
+    }
+
+    /// Range scan returning the keys along with the document ids (for covered queries)
+    pub fn range_scan_entries(
+        &self,
+        start: &IndexKey,
+        end: &IndexKey,
+        inclusive_start: bool,
+        inclusive_end: bool,
+    ) -> Result<Vec<(IndexKey, DocumentId)>> {
+        let start = if inclusive_start { Bound::Included(start) } else { Bound::Excluded(start) };
+        let end = if inclusive_end { Bound::Included(end) } else { Bound::Excluded(end) };
+
+        let mut results = Vec::new();
+        self.scan(start, end, None, &mut results)?;
+        Ok(results)
+    }
 
-        results
+    /// All (key, document) entries in key order
+    pub fn entries(&self) -> Result<Vec<(IndexKey, DocumentId)>> {
+        let mut results = Vec::new();
+        self.scan(Bound::Unbounded, Bound::Unbounded, None, &mut results)?;
+        Ok(results)
     }
 
     /// Get index size (number of keys)
@@ -283,27 +1118,28 @@ impl BPlusTree {
         self.metadata.num_keys
     }
 
-    // ===== FILE-BASED PERSISTENCE =====
+    /// Get tree height (1 = the root is a leaf)
+    pub fn height(&self) -> u32 {
+        self.metadata.tree_height
+    }
 
-    /// Save a single node to file and return its offset
-    fn save_node(file: &mut File, node: &BTreeNode) -> Result<u64> {
-        // Get current file position (where this node will be written)
-        let offset = file.seek(SeekFrom::End(0))?;
+    // ===== FILE-BASED PERSISTENCE =====
 
+    /// Page image of a node: `[u8 type][u32 len][JSON]`, zero padded to
+    /// whole pages
+    fn encode_node(node: &BTreeNode) -> Result<Vec<u8>> {
         // Serialize node to JSON (more compatible than bincode with untagged enums)
         let node_json = serde_json::to_string(node)
             .map_err(|e| MongoLiteError::Serialization(format!("Failed to serialize node: {}", e)))?;
         let node_bytes = node_json.as_bytes();
 
-        // Ensure node fits in a page (4KB)
-        if node_bytes.len() > NODE_PAGE_SIZE - 5 {
-            return Err(MongoLiteError::IndexError(
-                format!("Node size {} exceeds page size {}", node_bytes.len(), NODE_PAGE_SIZE - 5)
-            ));
-        }
+        let data_len = u32::try_from(node_bytes.len()).map_err(|_| MongoLiteError::IndexError(
+            format!("Node size {} exceeds the u32 length prefix", node_bytes.len())
+        ))?;
 
-        // Create page buffer (4KB) and write node data
-        let mut page = vec![0u8; NODE_PAGE_SIZE];
+        // A node larger than one page spans as many consecutive pages as it
+        // needs (same header, zero padded to the page multiple)
+        let mut page = vec![0u8; node_span(node_bytes.len())];
 
         // Write node type (1 byte)
         page[0] = match node {
@@ -312,14 +1148,21 @@ impl BPlusTree {
         };
 
         // Write data length (4 bytes, u32)
-        let len_bytes = (node_bytes.len() as u32).to_le_bytes();
-        page[1..5].copy_from_slice(&len_bytes);
+        page[1..5].copy_from_slice(&data_len.to_le_bytes());
 
         // Write node data
-        page[5..(5 + node_bytes.len())].copy_from_slice(&node_bytes);
+        page[5..(5 + node_bytes.len())].copy_from_slice(node_bytes);
+
+        Ok(page)
+    }
+
+    /// Save a single node to file and return its offset
+    fn save_node(file: &mut File, node: &BTreeNode) -> Result<u64> {
+        // Get current file position (where this node will be written)
+        let offset = file.seek(SeekFrom::End(0))?;
 
         // Write page to file
-        file.write_all(&page)?;
+        file.write_all(&Self::encode_node(node)?)?;
         file.flush()?;
 
         Ok(offset)
@@ -330,7 +1173,7 @@ impl BPlusTree {
         // Seek to node offset
         file.seek(SeekFrom::Start(offset))?;
 
-        // Read page (4KB)
+        // Read the first page (4KB)
         let mut page = vec![0u8; NODE_PAGE_SIZE];
         file.read_exact(&mut page)?;
 
@@ -341,6 +1184,19 @@ impl BPlusTree {
         let len_bytes: [u8; 4] = page[1..5].try_into().unwrap();
         let data_len = u32::from_le_bytes(len_bytes) as usize;
 
+        // Read the pages a multi-page node continues into
+        let span = node_span(data_len);
+        if span > NODE_PAGE_SIZE {
+            let file_len = file.metadata()?.len();
+            if offset.saturating_add(span as u64) > file_len {
+                return Err(MongoLiteError::Corruption(
+                    format!("Node at offset {} claims {} bytes past the end of the file", offset, data_len)
+                ));
+            }
+            page.resize(span, 0);
+            file.read_exact(&mut page[NODE_PAGE_SIZE..])?;
+        }
+
         // Read node data
         let node_bytes = &page[5..(5 + data_len)];
 
@@ -362,64 +1218,168 @@ impl BPlusTree {
 
     /// Save entire tree to file (recursive)
     pub fn save_to_file(&mut self, file: &mut File) -> Result<u64> {
-        // Clone root to avoid borrowing issues
-        let root_clone = self.root.clone();
-        let root_offset = self.save_node_recursive(file, &root_clone)?;
-        self.metadata.root_offset = root_offset;
+        let (root_offset, _) = self.save_tree(file)?;
         Ok(root_offset)
     }
 
-    /// Save node and children recursively
-    fn save_node_recursive(&mut self, file: &mut File, node: &BTreeNode) -> Result<u64> {
-        match node {
-            BTreeNode::Internal(internal) => {
-                // First, save all children and collect their offsets
-                let mut saved_offsets = Vec::new();
-                for &child_offset in &internal.children_offsets {
-                    if child_offset == 0 {
-                        // This is a placeholder, skip
-                        saved_offsets.push(0);
-                        continue;
-                    }
-                    // In a real implementation, we'd load the child node here
-                    // For now, just preserve the offset
-                    saved_offsets.push(child_offset);
-                }
+    /// Write every node at the end of `file`, children before their parents;
+    /// returns the root offset and the page run of each node
+    fn save_tree(&mut self, file: &mut File) -> Result<(u64, PageMap)> {
+        // Every node must be in memory before the old file can be let go
+        let mut pending = vec![self.root];
+        while let Some(id) = pending.pop() {
+            pending.extend(self.children(id)?);
+        }
+        self.source = None;
+
+        let mut pages = PageMap::new();
+        let root_offset = self.save_subtree(self.root, file, &mut pages)?;
+        self.metadata.root_offset = root_offset;
+        Ok((root_offset, pages))
+    }
 
-                // Create new internal node with updated offsets
-                let updated_node = BTreeNode::Internal(InternalNode {
-                    keys: internal.keys.clone(),
-                    children_offsets: saved_offsets,
-                });
+    /// Save node `id` after the nodes below it
+    fn save_subtree(&self, id: NodeId, file: &mut File, pages: &mut PageMap) -> Result<u64> {
+        for child in self.children(id)? {
+            self.save_subtree(child, file, pages)?;
+        }
+        let page = self.page_of(id, &|child| pages.get(&child).map(|&(offset, _)| offset))?;
+        let offset = Self::save_node(file, &page)?;
+        pages.insert(id, (offset, (file.stream_position()? - offset) as usize));
+        Ok(offset)
+    }
 
-                // Save this internal node
-                Self::save_node(file, &updated_node)
+    /// On-disk form of node `id`, its links resolved by `offset_of`
+    fn page_of(&self, id: NodeId, offset_of: &dyn Fn(NodeId) -> Option<u64>) -> Result<BTreeNode> {
+        Ok(match self.node(id)? {
+            Node::Leaf { entries, .. } => {
+                let (keys, document_ids) = entries.iter().cloned().unzip();
+                BTreeNode::Leaf(LeafNode { keys, document_ids, next_leaf_offset: 0 })
+            }
+            Node::Internal { separators, children } => {
+                let (keys, document_ids) = separators.iter().cloned().unzip();
+                let children_offsets = children.iter()
+                    .map(|child| offset_of(*child).ok_or_else(|| MongoLiteError::Corruption(
+                        format!("Index node {} of index {} has no page", child, self.metadata.name)
+                    )))
+                    .collect::<Result<_>>()?;
+                BTreeNode::Internal(InternalNode { keys, document_ids, children_offsets })
             }
-            BTreeNode::Leaf(_) => {
-                // Leaf nodes can be saved directly
-                Self::save_node(file, node)
+        })
+    }
+
+    /// Changed nodes from `id` down, children before their parents
+    fn changed_subtree(&self, id: NodeId, order: &mut Vec<NodeId>) -> Result<()> {
+        for child in self.children(id)? {
+            if self.dirty.contains(&child) {
+                self.changed_subtree(child, order)?;
             }
         }
+        order.push(id);
+        Ok(())
     }
 
     /// Load tree from file given root offset
+    ///
+    /// Only the root is read here; other nodes are read from (a handle to)
+    /// the same file when first reached, so it must not change meanwhile.
+    /// Nodes start on page boundaries (after the stamp page, if any).
     pub fn load_from_file(file: &mut File, metadata: IndexMetadata) -> Result<Self> {
         // Note: offset 0 is valid (start of file), so we don't check for it
         // An empty file would fail on load_node instead
+        let root = (metadata.root_offset / NODE_PAGE_SIZE as u64) as usize;
+        if (root * NODE_PAGE_SIZE) as u64 != metadata.root_offset {
+            return Err(MongoLiteError::Corruption(
+                format!("Index root offset {} is not on a page boundary", metadata.root_offset)
+            ));
+        }
+        let pages = file.metadata()?.len().div_ceil(NODE_PAGE_SIZE as u64) as usize;
+        let page = Self::load_node(file, metadata.root_offset)?;
+        let root_node = Node::from_page(page, metadata.root_offset, pages)?;
 
-        // Load root node
-        let root = Box::new(Self::load_node(file, metadata.root_offset)?);
-
+        let mut nodes: Vec<OnceLock<Node>> = (0..pages).map(|_| OnceLock::new()).collect();
+        nodes[root] = OnceLock::from(root_node);
         Ok(BPlusTree {
+            nodes,
+            free: Vec::new(),
             root,
             metadata,
+            source: Some(Arc::new(Mutex::new(file.try_clone()?))),
+            dirty: HashSet::new(),
+            persisted: None,
         })
     }
 
+    /// Read the whole tree in the index file at `path`, with the file's stamp
+    ///
+    /// Unlike load_from_file, every node is read here and the file is not
+    /// kept open, so it may be replaced or patched afterwards. The root comes
+    /// from the stamp; the key count and height from the nodes read.
+    pub fn read_from_file(path: &Path, mut metadata: IndexMetadata) -> Result<(Self, IndexFileStamp)> {
+        let mut file = File::open(path)?;
+        let mut first_page = Vec::with_capacity(NODE_PAGE_SIZE);
+        (&mut file).take(NODE_PAGE_SIZE as u64).read_to_end(&mut first_page)?;
+        let stamp = IndexFileStamp::parse(&first_page).ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index file {:?} has no stamp", path)
+        ))?;
+
+        metadata.root_offset = stamp.root_offset;
+        let mut tree = Self::load_from_file(&mut file, metadata)?;
+        let (num_keys, tree_height) = tree.read_subtree(tree.root)?;
+        tree.metadata.num_keys = num_keys;
+        tree.metadata.tree_height = tree_height;
+        tree.source = None;
+        Ok((tree, stamp))
+    }
+
+    /// Read every node from `id` down; returns the keys below it and its height
+    fn read_subtree(&self, id: NodeId) -> Result<(u64, u32)> {
+        match self.node(id)? {
+            Node::Leaf { entries, .. } => Ok((entries.len() as u64, 1)),
+            Node::Internal { children, .. } => {
+                let mut keys = 0;
+                let mut height = 0;
+                for &child in children {
+                    let (child_keys, child_height) = self.read_subtree(child)?;
+                    if height != 0 && child_height != height {
+                        return Err(MongoLiteError::Corruption(
+                            format!("Leaves of index {} are not all at the same depth", self.metadata.name)
+                        ));
+                    }
+                    keys += child_keys;
+                    height = child_height;
+                }
+                Ok((keys, height + 1))
+            }
+        }
+    }
+
+    /// Stamp of the file this tree was last saved to (prepared to, until
+    /// the prepared changes are committed); None if it was not saved
+    pub fn saved_stamp(&self) -> Option<&IndexFileStamp> {
+        self.persisted.as_ref().map(|persisted| &persisted.stamp)
+    }
+
     /// Two-Phase Commit: Phase 1 - Prepare changes to a temporary file
-    /// Creates a .tmp file with the current index state
+    ///
+    /// If `base_path` still holds what this tree last wrote there, the .tmp
+    /// file is a patch: page images of the nodes changed since, and a stamp
+    /// pointing at the new root. Pages are copied on write - every changed
+    /// node, the nodes above it included, goes to a run the tree in the file
+    /// does not use - so the stamp is the only page of the file a patch
+    /// writes over. Otherwise the .tmp file is the whole index.
     /// Returns the path to the temporary file
-    pub fn prepare_changes(&mut self, base_path: &PathBuf) -> Result<PathBuf> {
+    pub fn prepare_changes(&mut self, base_path: &Path) -> Result<PathBuf> {
+        self.prepare(base_path, None)
+    }
+
+    /// prepare_changes for the commit of `transaction`, whose id goes into
+    /// the stamp (see recover_prepared_changes)
+    pub fn prepare_transaction_changes(&mut self, base_path: &Path, transaction: u64) -> Result<PathBuf> {
+        self.prepare(base_path, Some(transaction))
+    }
+
+    fn prepare(&mut self, base_path: &Path, transaction: Option<u64>) -> Result<PathBuf> {
         use std::fs::OpenOptions;
 
         // Create temp file path: {base_path}.tmp
@@ -433,8 +1393,11 @@ impl BPlusTree {
             .open(&temp_path)
             .map_err(|e| MongoLiteError::Io(e))?;
 
-        // Save current tree state to temp file
-        self.save_to_file(&mut temp_file)?;
+        if self.is_persisted_at(base_path)? {
+            self.write_patch(&mut temp_file, transaction)?;
+        } else {
+            self.write_full(&mut temp_file, base_path, transaction)?;
+        }
 
         // Ensure data is written to disk
         temp_file.sync_all()
@@ -443,8 +1406,119 @@ impl BPlusTree {
         Ok(temp_path)
     }
 
+    /// Whether the file at `path` holds what this tree last wrote there
+    fn is_persisted_at(&self, path: &Path) -> Result<bool> {
+        let Some(persisted) = self.persisted.as_ref().filter(|persisted| persisted.path == path) else {
+            return Ok(false);
+        };
+        let mut first_page = Vec::with_capacity(NODE_PAGE_SIZE);
+        match File::open(path) {
+            Ok(file) => { file.take(NODE_PAGE_SIZE as u64).read_to_end(&mut first_page)?; }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        Ok(IndexFileStamp::parse(&first_page).as_ref() == Some(&persisted.stamp))
+    }
+
+    /// Write the whole tree to `file` and remember where its nodes are, for
+    /// patches to the file at `path` once this one is committed there
+    fn write_full(&mut self, file: &mut File, path: &Path, transaction: Option<u64>) -> Result<()> {
+        let mut stamp = IndexFileStamp::current(&self.metadata.uuid);
+        stamp.generation = self.persisted.as_ref().map_or(1, |persisted| persisted.stamp.generation + 1);
+        stamp.transaction = transaction;
+        stamp.save_id = uuid::Uuid::new_v4().to_string();
+
+        // Stamp first, the nodes follow from the second page; the stamp is
+        // written again once the root offset is known
+        stamp.write(file)?;
+        let (root_offset, pages) = self.save_tree(file)?;
+        let end = file.stream_position()?;
+        stamp.root_offset = root_offset;
+        file.seek(SeekFrom::Start(0))?;
+        stamp.write(file)?;
+
+        self.persisted = Some(PersistedPages {
+            path: path.to_path_buf(),
+            stamp,
+            pages,
+            free: Vec::new(),
+            retired: Vec::new(),
+            released: Vec::new(),
+            end,
+        });
+        self.dirty.clear();
+        Ok(())
+    }
+
+    /// Write the nodes changed since the last save to `file` as a patch
+    /// (see prepare_changes)
+    fn write_patch(&mut self, file: &mut File, transaction: Option<u64>) -> Result<()> {
+        // Left unset if this fails: the next save writes the whole tree
+        let mut persisted = self.persisted.take().ok_or_else(|| MongoLiteError::IndexError(
+            format!("Index {} has not been saved yet", self.metadata.name)
+        ))?;
+        // The file carries the last patch, so the runs it stopped using are free
+        let retired = std::mem::take(&mut persisted.retired);
+        persisted.free.extend(retired);
+
+        // Changes reach a node from the root through node_mut, so the nodes
+        // above a changed node are changed too. Children go first, so each
+        // node is written knowing where the ones below it went.
+        let mut order = Vec::with_capacity(self.dirty.len());
+        if self.dirty.contains(&self.root) {
+            self.changed_subtree(self.root, &mut order)?;
+        }
+        if order.len() != self.dirty.len() {
+            return Err(MongoLiteError::Corruption(
+                format!("Changed nodes of index {} are not all below changed parents", self.metadata.name)
+            ));
+        }
+
+        let mut runs = PageMap::new();
+        let mut page_runs = Vec::with_capacity(order.len());
+        let mut images = Vec::with_capacity(order.len());
+        for id in order {
+            let offset_of = |child: NodeId| runs.get(&child).or_else(|| persisted.pages.get(&child)).map(|&(offset, _)| offset);
+            let image = Self::encode_node(&self.page_of(id, &offset_of)?)?;
+            let run = allocate_run(&mut persisted.free, &mut persisted.end, image.len());
+            page_runs.push((run.0, image.len()));
+            images.push(image);
+            runs.insert(id, run);
+        }
+        persisted.retired = std::mem::take(&mut persisted.released);
+        for (id, run) in runs {
+            persisted.retired.extend(persisted.pages.insert(id, run));
+        }
+
+        let mut stamp = persisted.stamp.clone();
+        stamp.generation += 1;
+        stamp.transaction = transaction;
+        stamp.save_id = uuid::Uuid::new_v4().to_string();
+        stamp.root_offset = persisted.pages.get(&self.root).map(|&(offset, _)| offset).ok_or_else(|| MongoLiteError::Corruption(
+            format!("Root of index {} has no page", self.metadata.name)
+        ))?;
+        let header = PatchHeader { stamp, pages: page_runs };
+
+        let bytes = serde_json::to_vec(&header)?;
+        let mut page = vec![0u8; node_span(bytes.len())];
+        page[0] = NODE_TYPE_PATCH;
+        page[1..5].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+        page[5..5 + bytes.len()].copy_from_slice(&bytes);
+        file.write_all(&page)?;
+        for image in &images {
+            file.write_all(image)?;
+        }
+
+        self.metadata.root_offset = header.stamp.root_offset;
+        persisted.stamp = header.stamp;
+        self.persisted = Some(persisted);
+        self.dirty.clear();
+        Ok(())
+    }
+
     /// Two-Phase Commit: Phase 2 - Commit prepared changes atomically
-    /// Performs atomic rename from temp file to final file
+    /// A patch is written into the final file (see apply_patch); a whole
+    /// index atomically and durably replaces it
     /// If final_path doesn't exist yet, creates parent directories
     pub fn commit_prepared_changes(temp_path: &PathBuf, final_path: &PathBuf) -> Result<()> {
         use std::fs;
@@ -455,13 +1529,91 @@ impl BPlusTree {
                 .map_err(|e| MongoLiteError::Io(e))?;
         }
 
-        // Atomic rename: temp → final
-        fs::rename(temp_path, final_path)
+        if Self::is_patch(temp_path)? {
+            return Self::apply_patch(temp_path, final_path);
+        }
+
+        // Atomic replace: temp → final (durable across power loss)
+        crate::durable_fs::atomic_replace(temp_path, final_path)
             .map_err(|e| MongoLiteError::Io(e))?;
 
         Ok(())
     }
 
+    /// Whether a prepared file is a patch rather than a whole index
+    fn is_patch(temp_path: &Path) -> Result<bool> {
+        let mut node_type = [0u8; 1];
+        let read = File::open(temp_path)?.read(&mut node_type)?;
+        Ok(read == 1 && node_type[0] == NODE_TYPE_PATCH)
+    }
+
+    /// Write the page images of a patch into the index file, then the stamp
+    /// pointing at the new root, and delete the patch
+    ///
+    /// The images go to runs the tree in the file does not use, so until the
+    /// stamp is written the file still holds that tree whole. The writes are
+    /// the same every time, so a patch whose application was cut short by a
+    /// crash is applied again as a whole.
+    fn apply_patch(temp_path: &Path, final_path: &Path) -> Result<()> {
+        let data = std::fs::read(temp_path)?;
+        let (header, start) = Self::patch_header(&data).ok_or_else(|| MongoLiteError::Corruption(
+            format!("Index patch {:?} is truncated", temp_path)
+        ))?;
+
+        let mut file = std::fs::OpenOptions::new().write(true).open(final_path)?;
+        let mut at = start;
+        for &(offset, len) in &header.pages {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&data[at..at + len])?;
+            at += len;
+        }
+        // The pages are on disk before the stamp that points at them
+        file.sync_data()?;
+        file.seek(SeekFrom::Start(0))?;
+        header.stamp.write(&mut file)?;
+        file.sync_data()?;
+
+        std::fs::remove_file(temp_path)?;
+        Ok(())
+    }
+
+    /// Header of a patch and where its page images start; None if the patch
+    /// is incomplete
+    fn patch_header(data: &[u8]) -> Option<(PatchHeader, usize)> {
+        let len = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?) as usize;
+        let header: PatchHeader = serde_json::from_slice(data.get(5..5 + len)?).ok()?;
+        let start = node_span(len);
+        let images: usize = header.pages.iter().map(|&(_, len)| len).sum();
+        (data.first() == Some(&NODE_TYPE_PATCH) && start + images <= data.len()).then_some((header, start))
+    }
+
+    /// Commit or discard a prepared file a crash left behind; returns
+    /// whether it was committed
+    ///
+    /// Prepared files are complete before their transaction commits, and
+    /// committed only after it did, so one whose transaction `committed`
+    /// reports committed is committed now - a patch applied again as a
+    /// whole - and any other is deleted. Neither kind writes over the tree
+    /// the index file holds before its stamp, so the file is left as it was
+    /// last committed.
+    pub fn recover_prepared_changes<F>(temp_path: &PathBuf, final_path: &PathBuf, committed: F) -> Result<bool>
+    where
+        F: Fn(u64) -> bool,
+    {
+        let data = std::fs::read(temp_path)?;
+        let stamp = match Self::patch_header(&data) {
+            Some((header, _)) => Some(header.stamp),
+            None => IndexFileStamp::parse(&data),
+        };
+        if stamp.and_then(|stamp| stamp.transaction).is_some_and(committed) {
+            Self::commit_prepared_changes(temp_path, final_path)?;
+            return Ok(true);
+        }
+
+        Self::rollback_prepared_changes(temp_path)?;
+        Ok(false)
+    }
+
     /// Rollback prepared changes by deleting the temp file
     pub fn rollback_prepared_changes(temp_path: &PathBuf) -> Result<()> {
         use std::fs;
@@ -475,6 +1627,21 @@ impl BPlusTree {
     }
 }
 
+/// A run of `capacity` bytes: cut from the first free one large enough, or
+/// a new one at `end`
+fn allocate_run(free: &mut Vec<(u64, usize)>, end: &mut u64, capacity: usize) -> (u64, usize) {
+    if let Some(pos) = free.iter().position(|&(_, size)| size >= capacity) {
+        let (offset, size) = free.swap_remove(pos);
+        if size > capacity {
+            free.push((offset + capacity as u64, size - capacity));
+        }
+        return (offset, capacity);
+    }
+    let run = (*end, capacity);
+    *end += capacity as u64;
+    run
+}
+
 // ===== Legacy HashMap-based Index (for compatibility) =====
 
 /// Index types
@@ -541,6 +1708,18 @@ impl Index {
     }
 }
 
+/// Progress of an index build, reported while existing documents are indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexBuildProgress {
+    /// Documents indexed so far
+    pub processed: u64,
+    /// Documents in the collection when the build started
+    pub total: u64,
+}
+
+/// Documents indexed between two progress reports
+pub const INDEX_BUILD_PROGRESS_INTERVAL: usize = 1000;
+
 /// Index Manager - manages all indexes for a collection
 pub struct IndexManager {
     btree_indexes: HashMap<String, BPlusTree>,
@@ -581,6 +1760,19 @@ impl IndexManager {
         Ok(())
     }
 
+    /// Create hashed B+ tree index
+    pub fn create_hashed_index(&mut self, name: String, field: String) -> Result<()> {
+        if self.btree_indexes.contains_key(&name) {
+            return Err(MongoLiteError::IndexError(
+                format!("Index already exists: {}", name)
+            ));
+        }
+
+        let tree = BPlusTree::new_hashed(name.clone(), field);
+        self.btree_indexes.insert(name, tree);
+        Ok(())
+    }
+
     /// Create legacy HashMap index
     pub fn create_index(&mut self, definition: IndexDefinition) -> Result<()> {
         let name = definition.name.clone();
@@ -617,6 +1809,11 @@ impl IndexManager {
         self.btree_indexes.get_mut(name)
     }
 
+    /// Put `tree` in place of the B+ tree index of the same name
+    pub fn replace_btree_index(&mut self, tree: BPlusTree) {
+        self.btree_indexes.insert(tree.metadata.name.clone(), tree);
+    }
+
     /// Get legacy index
     pub fn get_index(&self, name: &str) -> Option<&Index> {
         self.legacy_indexes.get(name)
@@ -667,10 +1864,29 @@ mod tests {
         tree.insert(IndexKey::Int(30), DocumentId::Int(2)).unwrap();
         tree.insert(IndexKey::Int(20), DocumentId::Int(3)).unwrap();
 
-        assert_eq!(tree.search(&IndexKey::Int(25)), Some(DocumentId::Int(1)));
-        assert_eq!(tree.search(&IndexKey::Int(30)), Some(DocumentId::Int(2)));
-        assert_eq!(tree.search(&IndexKey::Int(20)), Some(DocumentId::Int(3)));
-        assert_eq!(tree.search(&IndexKey::Int(99)), None);
+        assert_eq!(tree.search(&IndexKey::Int(25)).unwrap(), vec![DocumentId::Int(1)]);
+        assert_eq!(tree.search(&IndexKey::Int(30)).unwrap(), vec![DocumentId::Int(2)]);
+        assert_eq!(tree.search(&IndexKey::Int(20)).unwrap(), vec![DocumentId::Int(3)]);
+        assert_eq!(tree.search(&IndexKey::Int(99)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_hashed_index_keys() {
+        use serde_json::json;
+
+        let long = "x".repeat(500);
+        let tree = BPlusTree::new_hashed("users_bio_hashed".to_string(), "bio".to_string());
+
+        assert!(tree.metadata.hashed);
+        assert_eq!(tree.key_for(&json!(long)), IndexKey::hashed(&json!(long)));
+        assert!(matches!(tree.key_for(&json!(long)), IndexKey::Int(_)));
+
+        // Same type tag discipline as plain keys: 1 and "1" are different values
+        assert_ne!(IndexKey::hashed(&json!(1)), IndexKey::hashed(&json!("1")));
+        assert_ne!(IndexKey::hashed(&json!("a")), IndexKey::hashed(&json!("b")));
+
+        let plain = BPlusTree::new("users_bio".to_string(), "bio".to_string(), false);
+        assert_eq!(plain.key_for(&json!("a")), IndexKey::String("a".to_string()));
     }
 
     #[test]
@@ -696,9 +1912,426 @@ mod tests {
             &IndexKey::Int(20),
             true,  // inclusive start
             false, // exclusive end
-        );
+        ).unwrap();
 
         assert_eq!(results.len(), 10);  // 10..19
+        assert_eq!(results[0], DocumentId::Int(10));
+        assert_eq!(results[9], DocumentId::Int(19));
+    }
+
+    #[test]
+    fn test_btree_delete_duplicate_key() {
+        let mut tree = BPlusTree::new("city_idx".to_string(), "city".to_string(), false);
+
+        for i in 1..=3 {
+            tree.insert(IndexKey::String("NYC".to_string()), DocumentId::Int(i)).unwrap();
+        }
+
+        // Removes exactly the given document, wherever it sits among equal keys
+        tree.delete(&IndexKey::String("NYC".to_string()), &DocumentId::Int(2)).unwrap();
+
+        let key = IndexKey::String("NYC".to_string());
+        let mut ids = tree.range_scan(&key, &key, true, true).unwrap();
+        ids.sort_by_key(|id| format!("{:?}", id));
+        assert_eq!(ids, vec![DocumentId::Int(1), DocumentId::Int(3)]);
+        assert_eq!(tree.size(), 2);
+    }
+
+    #[test]
+    fn test_btree_range_scan_entries() {
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+
+        tree.insert(IndexKey::Int(30), DocumentId::Int(1)).unwrap();
+        tree.insert(IndexKey::Float(OrderedFloat(20.5)), DocumentId::Int(2)).unwrap();
+
+        let entries = tree.range_scan_entries(&IndexKey::Int(0), &IndexKey::Float(OrderedFloat(100.0)), true, true).unwrap();
+        assert_eq!(entries.len(), 2);
+        // Numbers sort by value whatever their kind, and keep their kind
+        assert_eq!(entries[0].0.to_value(), serde_json::json!(20.5));
+        assert_eq!(entries[1].0.to_value(), serde_json::json!(30));
+    }
+
+    /// Every node but the root holds MIN_KEYS..=MAX_KEYS entries, entries
+    /// are in order and all leaves sit at the tree's height
+    fn check_invariants(tree: &BPlusTree) {
+        fn walk(tree: &BPlusTree, id: NodeId, depth: u32, is_root: bool, leaves: &mut Vec<NodeId>) -> u32 {
+            let node = tree.node(id).unwrap();
+            assert!(node.len() <= MAX_KEYS);
+            assert!(is_root || node.len() >= MIN_KEYS, "underfull node: {}", node.len());
+            match node {
+                Node::Leaf { entries, .. } => {
+                    assert!(entries.windows(2).all(|pair| pair[0] <= pair[1]));
+                    leaves.push(id);
+                    depth
+                }
+                Node::Internal { separators, children } => {
+                    assert_eq!(children.len(), separators.len() + 1);
+                    let depths: Vec<u32> = children.iter()
+                        .map(|child| walk(tree, *child, depth + 1, false, leaves))
+                        .collect();
+                    assert!(depths.iter().all(|d| *d == depths[0]));
+                    depths[0]
+                }
+            }
+        }
+        let mut leaves = Vec::new();
+        assert_eq!(walk(tree, tree.root, 1, true, &mut leaves), tree.height());
+
+        // The leaf chain visits the leaves in tree order and ends
+        let mut chain = Vec::new();
+        let mut leaf = Some(tree.first_leaf(Bound::Unbounded).unwrap());
+        while let Some(id) = leaf {
+            chain.push(id);
+            leaf = tree.next_leaf(id).unwrap();
+        }
+        assert_eq!(chain, leaves);
+
+        let entries = tree.entries().unwrap();
+        assert_eq!(entries.len() as u64, tree.size());
+        assert!(entries.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_btree_split() {
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+
+        // Pseudo-random order, enough keys for three levels
+        for i in 0..2000 {
+            let key = (i * 7919) % 2000;
+            tree.insert(IndexKey::Int(key), DocumentId::Int(key)).unwrap();
+        }
+
+        assert_eq!(tree.size(), 2000);
+        assert_eq!(tree.height(), 3);
+        check_invariants(&tree);
+        for i in 0..2000 {
+            assert_eq!(tree.search(&IndexKey::Int(i)).unwrap(), vec![DocumentId::Int(i)], "key {}", i);
+        }
+        assert_eq!(tree.range_scan(&IndexKey::Int(500), &IndexKey::Int(1500), false, true).unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn test_btree_delete_rebalances() {
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        for i in 0..2000 {
+            tree.insert(IndexKey::Int(i), DocumentId::Int(i)).unwrap();
+        }
+
+        // Delete every key but every tenth, checking the shape as it shrinks
+        for i in (0..2000).filter(|i| i % 10 != 0) {
+            tree.delete(&IndexKey::Int(i), &DocumentId::Int(i)).unwrap();
+            if i % 97 == 0 {
+                check_invariants(&tree);
+            }
+        }
+        check_invariants(&tree);
+        assert_eq!(tree.size(), 200);
+        assert_eq!(tree.height(), 2);
+        assert_eq!(tree.search(&IndexKey::Int(990)).unwrap(), vec![DocumentId::Int(990)]);
+        assert_eq!(tree.search(&IndexKey::Int(991)).unwrap(), vec![]);
+
+        for i in (0..2000).step_by(10) {
+            tree.delete(&IndexKey::Int(i), &DocumentId::Int(i)).unwrap();
+        }
+        assert_eq!(tree.size(), 0);
+        assert_eq!(tree.height(), 1);
+        assert!(tree.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_btree_duplicate_run_spans_leaves() {
+        let mut tree = BPlusTree::new("city_idx".to_string(), "city".to_string(), false);
+        let nyc = IndexKey::String("NYC".to_string());
+        for i in 0..100 {
+            tree.insert(IndexKey::String("Boston".to_string()), DocumentId::Int(1000 + i)).unwrap();
+            tree.insert(nyc.clone(), DocumentId::Int(i)).unwrap();
+        }
+        check_invariants(&tree);
+        assert!(tree.height() > 1);
+
+        // Equal keys are ordered by document id and all found, across leaves
+        let ids = tree.range_scan(&nyc, &nyc, true, true).unwrap();
+        assert_eq!(ids, (0..100).map(DocumentId::Int).collect::<Vec<_>>());
+        assert_eq!(tree.search(&nyc).unwrap(), ids);
+
+        for i in (0..100).rev().step_by(2) {
+            tree.delete(&nyc, &DocumentId::Int(i)).unwrap();
+        }
+        check_invariants(&tree);
+        assert_eq!(tree.range_scan(&nyc, &nyc, true, true).unwrap().len(), 50);
+        assert_eq!(tree.size(), 150);
+    }
+
+    #[test]
+    fn test_btree_duplicates_delete_exact_entry() {
+        use std::fs::OpenOptions;
+
+        let temp_path = "test_duplicate_tree.tmp";
+        let mut tree = BPlusTree::new("status_idx".to_string(), "status".to_string(), false);
+        let active = IndexKey::String("active".to_string());
+        // One key over many leaves, inserted out of id order
+        for i in 0..2000 {
+            tree.insert(active.clone(), DocumentId::Int((i * 7919) % 2000)).unwrap();
+        }
+        check_invariants(&tree);
+        assert!(tree.height() > 2);
+
+        // Deleting an id that does not hold the key leaves the run alone
+        tree.delete(&active, &DocumentId::Int(2000)).unwrap();
+        tree.delete(&IndexKey::String("inactive".to_string()), &DocumentId::Int(7)).unwrap();
+        assert_eq!(tree.size(), 2000);
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(temp_path)
+            .unwrap();
+        tree.save_to_file(&mut file).unwrap();
+        let mut loaded = BPlusTree::load_from_file(&mut file, tree.metadata.clone()).unwrap();
+
+        // Separators carry their document id, so a loaded tree finds each entry too
+        for i in (0..2000).filter(|i| i % 3 != 0) {
+            loaded.delete(&active, &DocumentId::Int(i)).unwrap();
+        }
+        check_invariants(&loaded);
+        let expected: Vec<DocumentId> = (0..2000).step_by(3).map(DocumentId::Int).collect();
+        assert_eq!(loaded.search(&active).unwrap(), expected);
+        assert_eq!(loaded.size(), expected.len() as u64);
+
+        std::fs::remove_file(temp_path).ok();
+    }
+
+    #[test]
+    fn test_scan_entries_windows() {
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        // Three levels, every key twice
+        for i in 0..2000 {
+            tree.insert(IndexKey::Int((i * 7919) % 1000), DocumentId::Int(i)).unwrap();
+        }
+        assert_eq!(tree.height(), 3);
+        let all = tree.entries().unwrap();
+
+        let bounds = [
+            (Bound::Unbounded, Bound::Unbounded),
+            (Bound::Included(IndexKey::Int(250)), Bound::Excluded(IndexKey::Int(700))),
+            (Bound::Excluded(IndexKey::Int(250)), Bound::Included(IndexKey::Int(700))),
+            (Bound::Included(IndexKey::Int(999)), Bound::Unbounded),
+            (Bound::Unbounded, Bound::Excluded(IndexKey::Int(0))),
+        ];
+        for (start, end) in &bounds {
+            let inside: Vec<_> = all.iter()
+                .filter(|(key, _)| !before_start(key, start.as_ref()) && !after_end(key, end.as_ref()))
+                .cloned()
+                .collect();
+            for reverse in [false, true] {
+                let mut expected = inside.clone();
+                if reverse {
+                    expected.reverse();
+                }
+                for (skip, limit) in [(0, None), (0, Some(10)), (37, Some(100)), (5000, Some(1))] {
+                    let window = tree.scan_entries(start.as_ref(), end.as_ref(), reverse, skip, limit).unwrap();
+                    let wanted: Vec<_> = expected.iter().skip(skip).take(limit.unwrap_or(usize::MAX)).cloned().collect();
+                    assert_eq!(window, wanted, "{:?}..{:?} reverse={} skip={} limit={:?}", start, end, reverse, skip, limit);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_level_tree_loads_lazily() {
+        use std::fs::OpenOptions;
+
+        let temp_path = "test_lazy_tree.tmp";
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        for i in 0..1000 {
+            tree.insert(IndexKey::Int(i), DocumentId::Int(i)).unwrap();
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(temp_path)
+            .unwrap();
+        tree.save_to_file(&mut file).unwrap();
+
+        let mut loaded = BPlusTree::load_from_file(&mut file, tree.metadata.clone()).unwrap();
+        let loaded_nodes = |tree: &BPlusTree| tree.nodes.iter().filter(|slot| slot.get().is_some()).count();
+        assert_eq!(loaded_nodes(&loaded), 1);
+
+        // A point lookup reads one path, not the whole tree
+        assert_eq!(loaded.search(&IndexKey::Int(500)).unwrap(), vec![DocumentId::Int(500)]);
+        assert_eq!(loaded_nodes(&loaded), loaded.height() as usize);
+
+        // A range scan follows the leaf chain from where it starts, looking
+        // up each link through the parents already read
+        let ids = loaded.range_scan(&IndexKey::Int(100), &IndexKey::Int(199), true, true).unwrap();
+        assert_eq!(ids, (100..200).map(DocumentId::Int).collect::<Vec<_>>());
+        assert!(loaded_nodes(&loaded) < 20);
+
+        // Updates load what they touch; saving again loads the rest
+        loaded.insert(IndexKey::Int(1000), DocumentId::Int(1000)).unwrap();
+        loaded.delete(&IndexKey::Int(0), &DocumentId::Int(0)).unwrap();
+        check_invariants(&loaded);
+        let mut copy = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("test_lazy_tree_copy.tmp")
+            .unwrap();
+        loaded.save_to_file(&mut copy).unwrap();
+        assert!(loaded.source.is_none());
+
+        let reloaded = BPlusTree::load_from_file(&mut copy, loaded.metadata.clone()).unwrap();
+        check_invariants(&reloaded);
+        assert_eq!(reloaded.entries().unwrap().len(), 1000);
+
+        std::fs::remove_file(temp_path).ok();
+        std::fs::remove_file("test_lazy_tree_copy.tmp").ok();
+    }
+
+    #[test]
+    fn test_loaded_leaves_keep_the_chain_through_splits_and_merges() {
+        let path = std::env::temp_dir().join(format!("test_loaded_chain_{}.idx", std::process::id()));
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        for i in 0..1000 {
+            tree.insert(IndexKey::Int(i), DocumentId::Int(i)).unwrap();
+        }
+        let mut file = File::create(&path).unwrap();
+        tree.save_to_file(&mut file).unwrap();
+
+        // Leaves split and merged before any scan has looked up their links
+        let mut file = File::open(&path).unwrap();
+        let mut loaded = BPlusTree::load_from_file(&mut file, tree.metadata.clone()).unwrap();
+        for i in 0..200 {
+            loaded.insert(IndexKey::Int(600), DocumentId::Int(1000 + i)).unwrap();
+        }
+        for i in 200..400 {
+            loaded.delete(&IndexKey::Int(i), &DocumentId::Int(i)).unwrap();
+        }
+        check_invariants(&loaded);
+        let ids = loaded.range_scan(&IndexKey::Int(190), &IndexKey::Int(410), true, true).unwrap();
+        let expected: Vec<DocumentId> = (190..200).chain(400..411).map(DocumentId::Int).collect();
+        assert_eq!(ids, expected);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_incremental_saves_write_changed_nodes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.age_idx.idx");
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        for i in 0..2000 {
+            tree.insert(IndexKey::Int(i * 2), DocumentId::Int(i)).unwrap();
+        }
+        let save = |tree: &mut BPlusTree| -> Vec<u8> {
+            let temp_path = tree.prepare_changes(&path).unwrap();
+            let prepared = std::fs::read(&temp_path).unwrap();
+            BPlusTree::commit_prepared_changes(&temp_path, &path).unwrap();
+            assert!(!temp_path.exists());
+            prepared
+        };
+        let reload = |tree: &BPlusTree| {
+            let mut file = File::open(&path).unwrap();
+            let loaded = BPlusTree::load_from_file(&mut file, tree.metadata.clone()).unwrap();
+            check_invariants(&loaded);
+            assert_eq!(loaded.entries().unwrap(), tree.entries().unwrap());
+        };
+
+        assert_eq!(save(&mut tree)[0], NODE_TYPE_STAMP);
+        reload(&tree);
+
+        // One insert copies its path to new runs...
+        let size = std::fs::metadata(&path).unwrap().len();
+        tree.insert(IndexKey::Int(501), DocumentId::Int(5000)).unwrap();
+        let patch = save(&mut tree);
+        assert_eq!(patch[0], NODE_TYPE_PATCH);
+        assert_eq!(patch.len(), (1 + tree.height() as usize) * NODE_PAGE_SIZE);
+        let grown = size + tree.height() as u64 * NODE_PAGE_SIZE as u64;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), grown);
+        reload(&tree);
+
+        // ...and the next one into the runs the last one stopped using
+        tree.insert(IndexKey::Int(503), DocumentId::Int(5001)).unwrap();
+        assert_eq!(save(&mut tree)[0], NODE_TYPE_PATCH);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), grown);
+        reload(&tree);
+
+        // A patch cut short before its stamp leaves the committed tree whole
+        let committed = tree.entries().unwrap();
+        tree.insert(IndexKey::Int(505), DocumentId::Int(5002)).unwrap();
+        let temp_path = tree.prepare_changes(&path).unwrap();
+        let prepared = std::fs::read(&temp_path).unwrap();
+        let (header, mut at) = BPlusTree::patch_header(&prepared).unwrap();
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        for &(offset, len) in &header.pages {
+            assert!(offset >= NODE_PAGE_SIZE as u64);
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&prepared[at..at + len]).unwrap();
+            at += len;
+        }
+        drop(file);
+        let mut file = File::open(&path).unwrap();
+        let stamp = IndexFileStamp::parse(&std::fs::read(&path).unwrap()).unwrap();
+        let mut metadata = tree.metadata.clone();
+        metadata.root_offset = stamp.root_offset;
+        assert_eq!(BPlusTree::load_from_file(&mut file, metadata).unwrap().entries().unwrap(), committed);
+        BPlusTree::commit_prepared_changes(&temp_path, &path).unwrap();
+        reload(&tree);
+
+        // Splits and merges copy the nodes they touch too
+        for round in 0..4 {
+            for i in 0..500 {
+                tree.insert(IndexKey::Int(i * 8 + 1), DocumentId::Int(10_000 + round * 500 + i)).unwrap();
+                tree.delete(&IndexKey::Int(i * 8 + round * 2), &DocumentId::Int((i * 8 + round * 2) / 2)).unwrap();
+            }
+            assert_eq!(save(&mut tree)[0], NODE_TYPE_PATCH);
+            reload(&tree);
+        }
+
+        // A patch that was never committed leaves the file behind the tree
+        tree.insert(IndexKey::Int(-1), DocumentId::Int(-1)).unwrap();
+        let temp_path = tree.prepare_changes(&path).unwrap();
+        BPlusTree::rollback_prepared_changes(&temp_path).unwrap();
+        tree.insert(IndexKey::Int(-2), DocumentId::Int(-2)).unwrap();
+        assert_eq!(save(&mut tree)[0], NODE_TYPE_STAMP);
+        reload(&tree);
+    }
+
+    #[test]
+    fn test_read_from_file_reads_the_whole_tree() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.age_idx.idx");
+        let mut tree = BPlusTree::new("age_idx".to_string(), "age".to_string(), false);
+        for i in 0..3000 {
+            tree.insert(IndexKey::Int(i % 1000), DocumentId::Int(i)).unwrap();
+        }
+        let temp_path = tree.prepare_changes(&path).unwrap();
+        BPlusTree::commit_prepared_changes(&temp_path, &path).unwrap();
+        let save_id = tree.saved_stamp().unwrap().save_id.clone();
+
+        // The definition only: key count and height come from the file
+        let definition = tree.empty_like().metadata;
+        let (read, stamp) = BPlusTree::read_from_file(&path, definition).unwrap();
+        assert_eq!(stamp.save_id, save_id);
+        assert_eq!((read.size(), read.height()), (3000, tree.height()));
+        assert!(read.nodes.iter().filter(|slot| slot.get().is_some()).count() > 1);
+
+        // Nothing is read later, so the file may go
+        std::fs::remove_file(&path).unwrap();
+        check_invariants(&read);
+        assert_eq!(read.entries().unwrap(), tree.entries().unwrap());
+
+        // Every save gets its own id
+        tree.insert(IndexKey::Int(5), DocumentId::Int(5000)).unwrap();
+        let temp_path = tree.prepare_changes(&path).unwrap();
+        BPlusTree::commit_prepared_changes(&temp_path, &path).unwrap();
+        assert_ne!(tree.saved_stamp().unwrap().save_id, save_id);
     }
 
     #[test]
@@ -744,6 +2377,46 @@ mod tests {
         std::fs::remove_file(temp_path).ok();
     }
 
+    #[test]
+    fn test_multi_page_node_save_load() {
+        use std::fs::OpenOptions;
+
+        let temp_path = "test_multi_page_node.tmp";
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(temp_path)
+            .unwrap();
+
+        // Long string keys push the node well past one page
+        let keys: Vec<IndexKey> = (0..8).map(|i| IndexKey::String(format!("{}{}", i, "x".repeat(2000)))).collect();
+        let leaf = BTreeNode::Leaf(LeafNode {
+            document_ids: (0..8).map(DocumentId::Int).collect(),
+            keys: keys.clone(),
+            next_leaf_offset: 0,
+        });
+
+        let first = BPlusTree::save_node(&mut file, &leaf).unwrap();
+        let second = BPlusTree::save_node(&mut file, &leaf).unwrap();
+        assert_eq!(first, 0);
+        assert_eq!(second % NODE_PAGE_SIZE as u64, 0);
+        assert!(second > NODE_PAGE_SIZE as u64);
+
+        match BPlusTree::load_node(&mut file, second).unwrap() {
+            BTreeNode::Leaf(restored) => assert_eq!(restored.keys, keys),
+            _ => panic!("Expected leaf node"),
+        }
+
+        // A length running past the end of the file is corruption, not a panic
+        file.seek(SeekFrom::Start(second + 1)).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        assert!(matches!(BPlusTree::load_node(&mut file, second), Err(MongoLiteError::Corruption(_))));
+
+        std::fs::remove_file(temp_path).ok();
+    }
+
     #[test]
     fn test_tree_persistence() {
         use std::fs::OpenOptions;
@@ -775,10 +2448,10 @@ mod tests {
         let loaded_tree = BPlusTree::load_from_file(&mut file, metadata_clone).unwrap();
 
         // Verify search still works
-        assert_eq!(loaded_tree.search(&IndexKey::Int(0)), Some(DocumentId::Int(0)));
-        assert_eq!(loaded_tree.search(&IndexKey::Int(50)), Some(DocumentId::Int(5)));
-        assert_eq!(loaded_tree.search(&IndexKey::Int(90)), Some(DocumentId::Int(9)));
-        assert_eq!(loaded_tree.search(&IndexKey::Int(99)), None);
+        assert_eq!(loaded_tree.search(&IndexKey::Int(0)).unwrap(), vec![DocumentId::Int(0)]);
+        assert_eq!(loaded_tree.search(&IndexKey::Int(50)).unwrap(), vec![DocumentId::Int(5)]);
+        assert_eq!(loaded_tree.search(&IndexKey::Int(90)).unwrap(), vec![DocumentId::Int(9)]);
+        assert_eq!(loaded_tree.search(&IndexKey::Int(99)).unwrap(), vec![]);
 
         // Cleanup
         std::fs::remove_file(temp_path).ok();
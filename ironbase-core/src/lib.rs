@@ -4,18 +4,47 @@
 pub mod error;
 pub mod document;
 pub mod storage;
+pub mod snapshot_scan;
+pub mod cursor;
 pub mod query;
 pub mod query_cache;
+pub mod plan_cache;
 pub mod index;
-pub mod btree;
 pub mod query_planner;
 pub mod aggregation;
+pub mod expression;
 pub mod find_options;
+pub mod top_k;
 pub mod collection_core;
 pub mod database;
+pub mod directory;
+pub mod client;
 pub mod transaction;
 pub mod wal;
 pub mod catalog_serde;
+pub mod validation;
+pub mod replication;
+pub mod session;
+pub mod durable_fs;
+pub mod memory;
+pub mod operations;
+pub mod hooks;
+pub mod encryption;
+pub mod computed;
+pub mod auth;
+pub mod views;
+pub mod sequence;
+pub mod statistics;
+pub mod export;
+pub mod import;
+pub mod backup;
+pub mod typed;
+pub mod query_builder;
+pub mod lock_manager;
+pub mod contention;
+pub mod numeric;
+pub mod trace;
+pub mod testing;
 
 #[cfg(test)]
 mod transaction_property_tests;
@@ -25,13 +54,42 @@ mod transaction_integration_tests;
 mod transaction_benchmarks;
 
 // Public exports
-pub use error::{MongoLiteError, Result};
+pub use error::{ErrorContext, MongoLiteError, Result, ResultExt};
 pub use document::{Document, DocumentId};
-pub use storage::{StorageEngine, CompactionStats};
+pub use storage::{StorageEngine, StorageConfig, StorageBackend, StorageFile, FileSystem, CollectionQuota, OnDelete, Reference, CompactionStats, CollectionCompactionStats, RepairStats, InspectReport, SpaceReport, OplogConfig, FORMAT_VERSION, MvccStats, Snapshot, DocumentCatalog, ID_RESERVATION_BLOCK};
 pub use query::Query;
+pub use expression::Expression;
 pub use query_cache::{QueryCache, QueryHash, CacheStats};
-pub use find_options::FindOptions;
-pub use collection_core::{CollectionCore, InsertManyResult};
-pub use database::DatabaseCore;
-pub use transaction::{Transaction, TransactionId, TransactionState, Operation};
-pub use wal::{WriteAheadLog, WALEntry, WALEntryType};
+pub use plan_cache::{PlanCache, PlanCacheStats, QueryShape};
+pub use find_options::{FindOptions, Page, Populate, ReadConcern, ReturnDocument};
+pub use collection_core::{BulkUpsertResult, CollectionCore, DeleteResult, InsertManyResult, ReindexResult, UpdateResult};
+pub use index::IndexBuildProgress;
+pub use database::{DatabaseCore, TransactionGuard};
+pub use directory::DirectoryDatabase;
+pub use client::{Client, ClientOptions};
+pub use transaction::{Transaction, TransactionId, TransactionState, Operation, Durability};
+pub use wal::{WriteAheadLog, WALEntry, WALEntryType, SyncThread};
+pub use validation::{ValidationReport, ValidationIssue};
+pub use session::{Session, Lsn};
+pub use snapshot_scan::SnapshotScan;
+pub use cursor::{CursorBatch, CursorConfig, CursorRegistry};
+pub use memory::{CancellationToken, MemoryTracker};
+pub use operations::{CurrentOp, OpId, OperationRegistry};
+pub use hooks::{Hook, HookEvent, HookRegistry};
+pub use encryption::{EncryptionKey, EncryptionMode, FieldEncryptor};
+pub use computed::{ComputedField, ComputedFields};
+pub use auth::{Action, Grant, Role, User};
+pub use views::{View, ViewDefinition, ViewRefresh};
+pub use sequence::Sequence;
+pub use statistics::CollectionStatistics;
+pub use lock_manager::LockManager;
+pub use contention::{LockMetrics, LockStats, LockModeStats};
+pub use export::{ExportFormat, ExportSchema, ExportStats};
+pub use import::{ImportOptions, ImportStats};
+pub use backup::BackupStats;
+pub use trace::{ReplayStats, TraceEntry, TraceRecorder, TracedOp};
+pub use typed::Collection;
+pub use query_builder::{FilterBuilder, QueryBuilder, Update, UpdateBuilder};
+#[cfg(feature = "derive")]
+pub use ironbase_derive::Queryable;
+pub use replication::{ReplicationPosition, ReplicationSource, ReplicationBatch, ReplicatedTransaction, Follower};
@@ -0,0 +1,228 @@
+// ironbase-core/src/lock_manager.rs
+// Document-level write latches for transactions
+//
+// A transaction latches every document it writes and keeps the latches until
+// it commits or rolls back (strict two-phase locking), so transactions writing
+// different documents run side by side while writers of the same document
+// queue up. Waits are checked against a wait-for graph: a request that would
+// close a cycle fails with MongoLiteError::Deadlock instead of blocking.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use parking_lot::{Condvar, Mutex};
+
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::transaction::TransactionId;
+
+/// Default time a transaction waits for a latch before giving up
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A latched document: (collection, _id)
+pub type DocumentKey = (String, DocumentId);
+
+#[derive(Default)]
+struct LockTable {
+    /// Document -> transaction holding its latch
+    holders: HashMap<DocumentKey, TransactionId>,
+    /// Transaction -> documents it holds
+    held: HashMap<TransactionId, HashSet<DocumentKey>>,
+    /// Transaction -> document it is waiting for (wait-for graph edges)
+    waiting: HashMap<TransactionId, DocumentKey>,
+}
+
+impl LockTable {
+    /// Would `tx` waiting on a latch held by `holder` close a cycle?
+    fn closes_cycle(&self, tx: TransactionId, holder: TransactionId) -> bool {
+        let mut current = holder;
+        // Every transaction waits for at most one latch, so the chain is a
+        // simple path; its length is bounded by the number of waiters
+        for _ in 0..=self.waiting.len() {
+            if current == tx {
+                return true;
+            }
+            match self.waiting.get(&current).and_then(|key| self.holders.get(key)) {
+                Some(&next) => current = next,
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Per-document latch table shared by all transactions of a database
+pub struct LockManager {
+    table: Mutex<LockTable>,
+    released: Condvar,
+    timeout: Mutex<Duration>,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockManager {
+    pub fn new() -> Self {
+        LockManager {
+            table: Mutex::new(LockTable::default()),
+            released: Condvar::new(),
+            timeout: Mutex::new(DEFAULT_LOCK_TIMEOUT),
+        }
+    }
+
+    /// How long acquire() waits for a latch held by another transaction
+    pub fn set_timeout(&self, timeout: Duration) {
+        *self.timeout.lock() = timeout;
+    }
+
+    pub fn timeout(&self) -> Duration {
+        *self.timeout.lock()
+    }
+
+    /// Latch `doc_id` in `collection` for `tx`, waiting while another
+    /// transaction holds it
+    ///
+    /// Re-acquiring a latch the transaction already holds is a no-op.
+    /// Fails with Deadlock if waiting would close a cycle and with LockTimeout
+    /// after the lock timeout; the caller should then roll `tx` back.
+    pub fn acquire(&self, tx: TransactionId, collection: &str, doc_id: &DocumentId) -> Result<()> {
+        let key: DocumentKey = (collection.to_string(), doc_id.clone());
+        let deadline = Instant::now() + self.timeout();
+        let mut table = self.table.lock();
+
+        loop {
+            let holder = match table.holders.get(&key) {
+                None => {
+                    table.waiting.remove(&tx);
+                    table.holders.insert(key.clone(), tx);
+                    table.held.entry(tx).or_default().insert(key);
+                    return Ok(());
+                }
+                Some(&holder) if holder == tx => {
+                    table.waiting.remove(&tx);
+                    return Ok(());
+                }
+                Some(&holder) => holder,
+            };
+
+            if table.closes_cycle(tx, holder) {
+                table.waiting.remove(&tx);
+                return Err(MongoLiteError::Deadlock(format!(
+                    "transaction {} waiting for {:?} in '{}' held by transaction {}",
+                    tx, doc_id, collection, holder
+                )));
+            }
+
+            table.waiting.insert(tx, key.clone());
+            if self.released.wait_until(&mut table, deadline).timed_out() && table.holders.get(&key).is_some_and(|&h| h != tx) {
+                table.waiting.remove(&tx);
+                return Err(MongoLiteError::LockTimeout(format!(
+                    "transaction {} waited {:?} for {:?} in '{}'",
+                    tx, self.timeout(), doc_id, collection
+                )));
+            }
+        }
+    }
+
+    /// Release every latch `tx` holds (on commit or rollback)
+    pub fn release_all(&self, tx: TransactionId) {
+        let mut table = self.table.lock();
+        table.waiting.remove(&tx);
+        if let Some(keys) = table.held.remove(&tx) {
+            for key in keys {
+                table.holders.remove(&key);
+            }
+            drop(table);
+            self.released.notify_all();
+        }
+    }
+
+    /// Transaction holding the latch on a document, if any
+    pub fn holder(&self, collection: &str, doc_id: &DocumentId) -> Option<TransactionId> {
+        let key: DocumentKey = (collection.to_string(), doc_id.clone());
+        self.table.lock().holders.get(&key).copied()
+    }
+
+    /// Number of latches `tx` holds
+    pub fn held_count(&self, tx: TransactionId) -> usize {
+        self.table.lock().held.get(&tx).map_or(0, HashSet::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn id(n: i64) -> DocumentId {
+        DocumentId::Int(n)
+    }
+
+    #[test]
+    fn test_acquire_is_reentrant_and_released() {
+        let locks = LockManager::new();
+        locks.acquire(1, "users", &id(1)).unwrap();
+        locks.acquire(1, "users", &id(1)).unwrap();
+        locks.acquire(2, "users", &id(2)).unwrap();
+        locks.acquire(2, "orders", &id(1)).unwrap();
+
+        assert_eq!(locks.holder("users", &id(1)), Some(1));
+        assert_eq!(locks.held_count(1), 1);
+        assert_eq!(locks.held_count(2), 2);
+
+        locks.release_all(1);
+        assert_eq!(locks.holder("users", &id(1)), None);
+        assert_eq!(locks.held_count(1), 0);
+    }
+
+    #[test]
+    fn test_waiter_gets_latch_after_release() {
+        let locks = Arc::new(LockManager::new());
+        locks.acquire(1, "users", &id(1)).unwrap();
+
+        let waiter = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.acquire(2, "users", &id(1)))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(locks.holder("users", &id(1)), Some(1));
+
+        locks.release_all(1);
+        waiter.join().unwrap().unwrap();
+        assert_eq!(locks.holder("users", &id(1)), Some(2));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let locks = LockManager::new();
+        locks.set_timeout(Duration::from_millis(20));
+        locks.acquire(1, "users", &id(1)).unwrap();
+
+        assert!(matches!(locks.acquire(2, "users", &id(1)), Err(MongoLiteError::LockTimeout(_))));
+        // The failed waiter left no edge behind
+        locks.acquire(2, "users", &id(2)).unwrap();
+    }
+
+    #[test]
+    fn test_deadlock_detected() {
+        let locks = Arc::new(LockManager::new());
+        locks.acquire(1, "users", &id(1)).unwrap();
+        locks.acquire(2, "users", &id(2)).unwrap();
+
+        // 1 waits for 2 ...
+        let waiter = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.acquire(1, "users", &id(2)))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+
+        // ... so 2 waiting for 1 would deadlock: 2 is refused at once
+        assert!(matches!(locks.acquire(2, "users", &id(1)), Err(MongoLiteError::Deadlock(_))));
+
+        locks.release_all(2);
+        waiter.join().unwrap().unwrap();
+        assert_eq!(locks.holder("users", &id(2)), Some(1));
+    }
+}
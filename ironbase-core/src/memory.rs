@@ -0,0 +1,218 @@
+// ironbase-core/src/memory.rs
+// Per-operation memory and time accounting for queries, scans and aggregation
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::error::{Result, MongoLiteError};
+
+/// Memory accountant of a single operation
+///
+/// Scans and pipelines charge the documents they hold on to; once the total
+/// passes the limit the operation fails with `QueryExceededMemoryLimit`
+/// instead of growing until the embedding process is killed. Sizes are
+/// estimates of the in-memory `Value` trees, not exact allocator figures.
+///
+/// The tracker also carries the operation's deadline and cancellation token:
+/// every charge (and every document a filter looks at) checks them, so a
+/// runaway scan fails with `OperationTimedOut` or `Cancelled` instead of
+/// hanging its caller.
+#[derive(Debug, Clone)]
+pub struct MemoryTracker {
+    limit: Option<usize>,
+    used: usize,
+    peak: usize,
+    /// Deadline and the max_time_ms it was set from
+    deadline: Option<(Instant, u64)>,
+    /// The caller's token and the one kill_op() cancels
+    cancellation: Vec<CancellationToken>,
+}
+
+impl MemoryTracker {
+    /// Tracker failing once `limit` bytes are in use (None = no limit)
+    pub fn new(limit: Option<usize>) -> Self {
+        MemoryTracker {
+            limit,
+            used: 0,
+            peak: 0,
+            deadline: None,
+            cancellation: Vec::new(),
+        }
+    }
+
+    /// Fail the operation once `max_time_ms` milliseconds have passed from now
+    pub fn with_max_time_ms(mut self, max_time_ms: u64) -> Self {
+        self.deadline = Some((Instant::now() + Duration::from_millis(max_time_ms), max_time_ms));
+        self
+    }
+
+    /// Fail the operation once `token` is cancelled (in addition to any
+    /// tokens added before)
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation.push(token);
+        self
+    }
+
+    /// Err once the operation was cancelled or ran past its deadline
+    pub fn check_interrupt(&self) -> Result<()> {
+        if self.cancellation.iter().any(CancellationToken::is_cancelled) {
+            return Err(MongoLiteError::Cancelled("operation cancelled by its token".to_string()));
+        }
+        match self.deadline {
+            Some((deadline, max_time_ms)) if Instant::now() >= deadline => {
+                Err(MongoLiteError::OperationTimedOut { max_time_ms })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Tracker that only counts
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Account `bytes` more in use
+    pub fn charge(&mut self, bytes: usize) -> Result<()> {
+        self.check_interrupt()?;
+        self.used = self.used.saturating_add(bytes);
+        self.peak = self.peak.max(self.used);
+
+        match self.limit {
+            Some(limit) if self.used > limit => Err(MongoLiteError::QueryExceededMemoryLimit {
+                used: self.used,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Account a document held by the operation
+    pub fn charge_value(&mut self, value: &Value) -> Result<()> {
+        self.charge(estimate_size(value))
+    }
+
+    /// Account `bytes` given back
+    pub fn release(&mut self, bytes: usize) {
+        self.used = self.used.saturating_sub(bytes);
+    }
+
+    /// Replace the accounted total with `bytes` (e.g. after a pipeline stage
+    /// replaced its input with its output)
+    pub fn set_used(&mut self, bytes: usize) -> Result<()> {
+        if bytes >= self.used {
+            self.charge(bytes - self.used)
+        } else {
+            self.release(self.used - bytes);
+            Ok(())
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Highest total seen during the operation
+    pub fn peak(&self) -> usize {
+        self.peak
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+}
+
+/// Handle to cancel running operations from another thread
+///
+/// Clones share the flag: pass one clone in FindOptions and keep another to
+/// call cancel() on - e.g. from a GUI's stop button.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every operation holding this token fail at its next check
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Estimated heap + inline size of a JSON value
+pub fn estimate_size(value: &Value) -> usize {
+    let inline = std::mem::size_of::<Value>();
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => inline,
+        Value::String(s) => inline + s.len(),
+        Value::Array(items) => inline + items.iter().map(estimate_size).sum::<usize>(),
+        Value::Object(map) => inline + map.iter()
+            .map(|(key, value)| std::mem::size_of::<String>() + key.len() + estimate_size(value))
+            .sum::<usize>(),
+    }
+}
+
+/// Estimated size of a set of documents
+pub fn estimate_all(values: &[Value]) -> usize {
+    values.iter().map(estimate_size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimate_grows_with_content() {
+        let small = json!({"name": "a"});
+        let large = json!({"name": "a", "bio": "x".repeat(1000), "tags": ["a", "b", "c"]});
+
+        assert!(estimate_size(&large) > estimate_size(&small) + 1000);
+        assert_eq!(estimate_all(&[small.clone(), small.clone()]), 2 * estimate_size(&small));
+    }
+
+    #[test]
+    fn test_limit_enforced() {
+        let mut tracker = MemoryTracker::new(Some(100));
+        tracker.charge(60).unwrap();
+        tracker.release(20);
+        tracker.charge(60).unwrap();
+
+        let err = tracker.charge(1).unwrap_err();
+        assert!(matches!(err, MongoLiteError::QueryExceededMemoryLimit { used: 101, limit: 100 }));
+        assert_eq!(tracker.peak(), 101);
+    }
+
+    #[test]
+    fn test_set_used_and_unlimited() {
+        let mut tracker = MemoryTracker::new(Some(100));
+        tracker.set_used(80).unwrap();
+        tracker.set_used(10).unwrap();
+        assert_eq!(tracker.used(), 10);
+        assert!(tracker.set_used(200).is_err());
+
+        let mut unlimited = MemoryTracker::unlimited();
+        unlimited.charge(usize::MAX).unwrap();
+        assert_eq!(unlimited.limit(), None);
+    }
+
+    #[test]
+    fn test_deadline_and_cancellation() {
+        let mut tracker = MemoryTracker::unlimited().with_max_time_ms(0);
+        assert!(matches!(tracker.charge(1), Err(MongoLiteError::OperationTimedOut { max_time_ms: 0 })));
+
+        let token = CancellationToken::new();
+        let mut tracker = MemoryTracker::unlimited().with_max_time_ms(60_000).with_cancellation(token.clone());
+        tracker.charge(1).unwrap();
+        token.cancel();
+        assert!(matches!(tracker.check_interrupt(), Err(MongoLiteError::Cancelled(_))));
+        assert!(tracker.charge(1).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+// ironbase-core/src/numeric.rs
+// Numeric model shared by value conversion, query matching, updates and indexes
+//
+// - Integers are exact: every i64, plus the u64 values above i64::MAX
+// - Floats stay floats and integers stay integers (3.0 is never rewritten to 3)
+// - Comparison and equality go by mathematical value across the kinds:
+//   1 == 1.0, and 2^63 (a u64) sorts above i64::MAX instead of rounding to it
+// - Integer arithmetic is checked: a result outside both integer ranges is an
+//   error, never silently rounded through f64
+
+use std::cmp::Ordering;
+use serde_json::{Number, Value};
+
+/// A number reduced to the two kinds the model compares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+impl Num {
+    pub(crate) fn of(number: &Number) -> Num {
+        if let Some(i) = number.as_i64() {
+            Num::Int(i as i128)
+        } else if let Some(u) = number.as_u64() {
+            Num::Int(u as i128)
+        } else {
+            Num::Float(number.as_f64().unwrap_or(f64::NAN))
+        }
+    }
+
+    /// Total order by value; NaN sorts above every number
+    pub(crate) fn cmp(self, other: Num) -> Ordering {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => a.cmp(&b),
+            (Num::Int(a), Num::Float(b)) => cmp_int_float(a, b),
+            (Num::Float(a), Num::Int(b)) => cmp_int_float(b, a).reverse(),
+            (Num::Float(a), Num::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            },
+        }
+    }
+}
+
+/// Exact comparison of an integer with a float (no rounding of the integer)
+fn cmp_int_float(i: i128, f: f64) -> Ordering {
+    if f.is_nan() {
+        return Ordering::Less;
+    }
+    // Every i64/u64 lies strictly inside ±2^127
+    if f >= 2f64.powi(127) {
+        return Ordering::Less;
+    }
+    if f < -(2f64.powi(127)) {
+        return Ordering::Greater;
+    }
+    let whole = f.trunc();
+    match i.cmp(&(whole as i128)) {
+        Ordering::Equal => 0f64.partial_cmp(&(f - whole)).unwrap_or(Ordering::Equal),
+        ordering => ordering,
+    }
+}
+
+/// Compare two JSON numbers by value
+pub fn compare(a: &Number, b: &Number) -> Ordering {
+    Num::of(a).cmp(Num::of(b))
+}
+
+/// JSON equality with numbers compared by value (1 == 1.0), recursively
+/// through arrays and objects
+pub fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => compare(x, y) == Ordering::Equal,
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| values_equal(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| values_equal(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Sum as used by $inc: integers add exactly (i64, or u64 above i64::MAX),
+/// anything involving a float adds as f64. None when an integer sum leaves
+/// both ranges or a float sum is not finite.
+pub fn add(a: &Number, b: &Number) -> Option<Number> {
+    match (Num::of(a), Num::of(b)) {
+        (Num::Int(x), Num::Int(y)) => from_i128(x + y),
+        _ => Number::from_f64(a.as_f64()? + b.as_f64()?),
+    }
+}
+
+/// Integer as a JSON number, if it fits i64 or u64
+pub fn from_i128(i: i128) -> Option<Number> {
+    if let Ok(i) = i64::try_from(i) {
+        Some(Number::from(i))
+    } else {
+        u64::try_from(i).ok().map(Number::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn num(value: Value) -> Number {
+        match value {
+            Value::Number(n) => n,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_compare_across_kinds() {
+        assert_eq!(compare(&num(json!(1)), &num(json!(1.0))), Ordering::Equal);
+        assert_eq!(compare(&num(json!(1)), &num(json!(1.5))), Ordering::Less);
+        assert_eq!(compare(&num(json!(-2)), &num(json!(-1.5))), Ordering::Less);
+
+        // u64 above i64::MAX stays above it; 2^63 as a float equals it exactly
+        let above = num(json!(i64::MAX as u64 + 1));
+        assert_eq!(compare(&num(json!(i64::MAX)), &above), Ordering::Less);
+        assert_eq!(compare(&above, &num(json!(9223372036854775808.0))), Ordering::Equal);
+
+        // 2^53 + 1 is not rounded to 2^53
+        assert_eq!(compare(&num(json!(9007199254740993_i64)), &num(json!(9007199254740992.0))), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_values_equal_is_numeric() {
+        assert!(values_equal(&json!({"a": [1, 2.0]}), &json!({"a": [1.0, 2]})));
+        assert!(!values_equal(&json!([1]), &json!([1, 2])));
+        assert!(!values_equal(&json!("1"), &json!(1)));
+    }
+
+    #[test]
+    fn test_add_keeps_kinds() {
+        assert_eq!(add(&num(json!(1)), &num(json!(2))), Some(num(json!(3))));
+        assert!(add(&num(json!(1)), &num(json!(2))).unwrap().is_i64());
+        assert!(add(&num(json!(1.0)), &num(json!(2))).unwrap().is_f64());
+
+        // Crossing into the u64 range stays an exact integer
+        assert_eq!(add(&num(json!(i64::MAX)), &num(json!(1))), Some(num(json!(i64::MAX as u64 + 1))));
+        assert_eq!(add(&num(json!(u64::MAX)), &num(json!(1))), None);
+        assert_eq!(add(&num(json!(i64::MIN)), &num(json!(-1))), None);
+    }
+}
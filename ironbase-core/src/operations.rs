@@ -0,0 +1,171 @@
+// ironbase-core/src/operations.rs
+// Registry of in-flight operations: current_ops() and kill_op()
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use crate::memory::CancellationToken;
+
+/// Identifier of a running operation, unique while the database is open
+pub type OpId = u64;
+
+/// Snapshot of one running operation, as reported by current_ops()
+#[derive(Debug, Clone)]
+pub struct CurrentOp {
+    pub id: OpId,
+    /// "find" or "aggregate"
+    pub op: String,
+    pub collection: String,
+    /// Query filter (find) or pipeline (aggregate)
+    pub query: Value,
+    /// Wall-clock start, for display
+    pub started_at: SystemTime,
+    pub running_for: Duration,
+    /// kill_op() was called - the operation stops at its next check
+    pub killed: bool,
+}
+
+impl CurrentOp {
+    pub fn to_json(&self) -> Value {
+        let started_at = self.started_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        serde_json::json!({
+            "id": self.id,
+            "op": self.op,
+            "collection": self.collection,
+            "query": self.query,
+            "started_at_ms": started_at.as_millis() as u64,
+            "running_ms": self.running_for.as_secs_f64() * 1000.0,
+            "killed": self.killed,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    op: &'static str,
+    collection: String,
+    query: Value,
+    started_at: SystemTime,
+    started: Instant,
+    token: CancellationToken,
+}
+
+/// In-flight operations of one database, shared by all its collections
+///
+/// Operations register on start and get a cancellation token their scan loops
+/// check; kill_op() cancels that token, so killing is cooperative - the
+/// operation fails with Cancelled at its next check, not mid-write.
+#[derive(Debug, Default)]
+pub struct OperationRegistry {
+    next_id: AtomicU64,
+    running: Mutex<HashMap<OpId, Entry>>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an operation; it stays listed until the guard is dropped
+    pub fn register(self: &Arc<Self>, op: &'static str, collection: &str, query: &Value) -> OperationGuard {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = CancellationToken::new();
+        self.running.lock().insert(id, Entry {
+            op,
+            collection: collection.to_string(),
+            query: query.clone(),
+            started_at: SystemTime::now(),
+            started: Instant::now(),
+            token: token.clone(),
+        });
+        OperationGuard {
+            registry: Arc::clone(self),
+            id,
+            token,
+        }
+    }
+
+    /// Running operations, oldest first
+    pub fn current_ops(&self) -> Vec<CurrentOp> {
+        let mut ops: Vec<CurrentOp> = self.running.lock().iter()
+            .map(|(id, entry)| CurrentOp {
+                id: *id,
+                op: entry.op.to_string(),
+                collection: entry.collection.clone(),
+                query: entry.query.clone(),
+                started_at: entry.started_at,
+                running_for: entry.started.elapsed(),
+                killed: entry.token.is_cancelled(),
+            })
+            .collect();
+        ops.sort_by_key(|op| op.id);
+        ops
+    }
+
+    /// Cancel operation `id`; false if no such operation is running
+    pub fn kill(&self, id: OpId) -> bool {
+        match self.running.lock().get(&id) {
+            Some(entry) => {
+                entry.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Registration of a running operation - unregisters it on drop
+#[derive(Debug)]
+pub struct OperationGuard {
+    registry: Arc<OperationRegistry>,
+    id: OpId,
+    token: CancellationToken,
+}
+
+impl OperationGuard {
+    pub fn id(&self) -> OpId {
+        self.id
+    }
+
+    /// Token cancelled by kill_op()
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.registry.running.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_register_kill_and_unregister() {
+        let registry = Arc::new(OperationRegistry::new());
+        let first = registry.register("find", "users", &json!({"age": 1}));
+        let second = registry.register("aggregate", "orders", &json!([]));
+
+        let ops = registry.current_ops();
+        assert_eq!(ops.iter().map(|op| op.id).collect::<Vec<_>>(), vec![first.id(), second.id()]);
+        assert_eq!(ops[0].collection, "users");
+
+        assert!(registry.kill(first.id()));
+        assert!(first.token().is_cancelled());
+        assert!(!second.token().is_cancelled());
+
+        let first_id = first.id();
+        drop(first);
+        assert!(!registry.kill(first_id));
+        assert_eq!(registry.current_ops().len(), 1);
+    }
+}
@@ -0,0 +1,177 @@
+// ironbase-core/src/plan_cache.rs
+// Query plan caching by query shape with LRU eviction
+
+use lru::LruCache;
+use parking_lot::RwLock;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde_json::Value;
+use crate::query_planner::QueryPlan;
+
+/// Hash of a query's shape: its structure with every value replaced by a
+/// placeholder for its kind, so {"age": 25} and {"age": 40} share a shape
+/// (and a plan) while {"age": "25"} or {"age": {"$gt": 25}} do not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryShape(u64);
+
+impl QueryShape {
+    /// Create a shape from collection name and query JSON
+    pub fn new(collection: &str, query: &Value) -> Self {
+        let mut hasher = DefaultHasher::new();
+        collection.hash(&mut hasher);
+        Self::shape_of(query, false).hash(&mut hasher);
+
+        QueryShape(hasher.finish())
+    }
+
+    /// Placeholder string of a value. Branch lists of logical operators keep
+    /// every element - the planner handles each branch - while other arrays
+    /// (such as $in lists) only keep the set of their element shapes.
+    fn shape_of(value: &Value, positional: bool) -> String {
+        match value {
+            Value::Null => "null".to_string(),
+            Value::Bool(_) => "bool".to_string(),
+            Value::Number(_) => "number".to_string(),
+            Value::String(_) => "string".to_string(),
+            Value::Array(items) => {
+                let mut shapes: Vec<String> = items.iter().map(|item| Self::shape_of(item, false)).collect();
+                if !positional {
+                    shapes.sort();
+                    shapes.dedup();
+                }
+                format!("[{}]", shapes.join(","))
+            }
+            Value::Object(map) => {
+                let fields: Vec<String> = map.iter()
+                    .map(|(key, value)| {
+                        let positional = matches!(key.as_str(), "$or" | "$and" | "$nor");
+                        format!("{:?}:{}", key, Self::shape_of(value, positional))
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }
+        }
+    }
+}
+
+/// Planner result cached for a shape: the field and plan, or None for a
+/// collection scan
+pub type CachedPlan = Option<(String, QueryPlan)>;
+
+/// Query plan cache with LRU eviction
+///
+/// Caches the planner's choice per query shape so repeated queries skip
+/// index listing and selection. Plans name indexes, so the cache must be
+/// invalidated whenever indexes are created or dropped.
+pub struct PlanCache {
+    cache: RwLock<LruCache<QueryShape, CachedPlan>>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PlanCache {
+    /// Create a new plan cache with specified capacity
+    pub fn new(capacity: usize) -> Self {
+        let non_zero_capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(1000).unwrap());
+        PlanCache {
+            cache: RwLock::new(LruCache::new(non_zero_capacity)),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the cached plan for a shape (outer None if not cached)
+    pub fn get(&self, shape: &QueryShape) -> Option<CachedPlan> {
+        let cached = self.cache.read().peek(shape).cloned();
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Insert a plan into the cache
+    pub fn insert(&self, shape: QueryShape, plan: CachedPlan) {
+        self.cache.write().put(shape, plan);
+    }
+
+    /// Drop all cached plans (index created or dropped)
+    pub fn invalidate(&self) {
+        self.cache.write().clear();
+    }
+
+    /// Get cache statistics
+    pub fn stats(&self) -> PlanCacheStats {
+        PlanCacheStats {
+            capacity: self.capacity,
+            size: self.cache.read().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for PlanCache {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+/// Plan cache statistics
+#[derive(Debug, Clone)]
+pub struct PlanCacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_shape_ignores_values() {
+        let shape = |query: Value| QueryShape::new("users", &query);
+
+        assert_eq!(shape(json!({"age": 25})), shape(json!({"age": 40})));
+        assert_eq!(shape(json!({"age": {"$gte": 1, "$lt": 9}})), shape(json!({"age": {"$gte": 2.5, "$lt": 7}})));
+        assert_eq!(shape(json!({"city": {"$in": ["NYC"]}})), shape(json!({"city": {"$in": ["LA", "SF", "NYC"]}})));
+
+        // Kinds, operators, fields and collections all matter
+        assert_ne!(shape(json!({"age": 25})), shape(json!({"age": "25"})));
+        assert_ne!(shape(json!({"age": 25})), shape(json!({"age": null})));
+        assert_ne!(shape(json!({"age": {"$gt": 1}})), shape(json!({"age": {"$gte": 1}})));
+        assert_ne!(shape(json!({"city": {"$in": ["NYC"]}})), shape(json!({"city": {"$in": ["NYC", null]}})));
+        assert_ne!(shape(json!({"age": 25})), shape(json!({"name": 25})));
+        assert_ne!(shape(json!({"age": 25})), QueryShape::new("posts", &json!({"age": 25})));
+    }
+
+    #[test]
+    fn test_shape_keeps_logical_branches() {
+        let shape = |query: Value| QueryShape::new("users", &query);
+
+        assert_eq!(shape(json!({"$or": [{"a": 1}, {"b": 2}]})), shape(json!({"$or": [{"a": 5}, {"b": 6}]})));
+        assert_ne!(shape(json!({"$or": [{"a": 1}, {"b": 2}]})), shape(json!({"$or": [{"b": 2}, {"a": 1}]})));
+        assert_ne!(shape(json!({"$or": [{"a": 1}]})), shape(json!({"$or": [{"a": 1}, {"a": 2}]})));
+    }
+
+    #[test]
+    fn test_cache_insert_invalidate_and_stats() {
+        let cache = PlanCache::new(10);
+        let shape = QueryShape::new("users", &json!({"age": 25}));
+
+        assert!(cache.get(&shape).is_none());
+        cache.insert(shape, None);
+        assert!(matches!(cache.get(&shape), Some(None)));
+
+        cache.invalidate();
+        assert!(cache.get(&shape).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.size, stats.hits, stats.misses), (0, 1, 2));
+    }
+}
@@ -3,6 +3,8 @@ use serde_json::Value;
 use std::collections::HashMap;
 use crate::document::Document;
 use crate::error::{Result, MongoLiteError};
+use crate::expression::{self, Expression};
+use crate::numeric;
 
 /// Query típusok
 #[derive(Debug, Clone)]
@@ -27,6 +29,14 @@ pub enum QueryOperator {
     Exists(bool),        // $exists
     Type(String),        // $type
     Regex(String),       // $regex
+    Expr(Expression),    // $expr (top-level, aggregation expression)
+
+    // Számtani és bitenkénti
+    Mod(i64, i64),           // $mod [divisor, remainder]
+    BitsAllSet(Vec<u32>),    // $bitsAllSet (bit positions)
+    BitsAnySet(Vec<u32>),    // $bitsAnySet
+    BitsAllClear(Vec<u32>),  // $bitsAllClear
+    BitsAnyClear(Vec<u32>),  // $bitsAnyClear
 }
 
 /// Query - MongoDB-szerű lekérdezés
@@ -99,6 +109,11 @@ impl Query {
                     Err(MongoLiteError::InvalidQuery("$nor requires array".into()))
                 }
             }
+            "$expr" => {
+                Expression::from_json(value)
+                    .map(QueryOperator::Expr)
+                    .map_err(|e| MongoLiteError::InvalidQuery(format!("$expr: {}", e)))
+            }
             _ => Err(MongoLiteError::InvalidQuery(format!("Unknown logical operator: {}", op)))
         }
     }
@@ -158,6 +173,11 @@ impl Query {
                                 Err(MongoLiteError::InvalidQuery("$regex requires string".into()))
                             }
                         }
+                        "$mod" => Self::parse_mod(val),
+                        "$bitsAllSet" => Ok(QueryOperator::BitsAllSet(Self::parse_bit_positions(op, val)?)),
+                        "$bitsAnySet" => Ok(QueryOperator::BitsAnySet(Self::parse_bit_positions(op, val)?)),
+                        "$bitsAllClear" => Ok(QueryOperator::BitsAllClear(Self::parse_bit_positions(op, val)?)),
+                        "$bitsAnyClear" => Ok(QueryOperator::BitsAnyClear(Self::parse_bit_positions(op, val)?)),
                         _ => Err(MongoLiteError::InvalidQuery(format!("Unknown operator: {}", op)))
                     }
                 } else {
@@ -169,6 +189,81 @@ impl Query {
         }
     }
     
+    /// $mod: [divisor, remainder]
+    ///
+    /// As in MongoDB, both must be numbers (numeric strings are rejected) and
+    /// fractional parts are truncated toward zero; a zero divisor is an error.
+    fn parse_mod(value: &Value) -> Result<QueryOperator> {
+        let args = match value {
+            Value::Array(args) if args.len() == 2 => args,
+            _ => return Err(MongoLiteError::InvalidQuery("$mod requires an array of [divisor, remainder]".into())),
+        };
+        let divisor = Self::truncate_to_i64(&args[0])
+            .ok_or_else(|| MongoLiteError::InvalidQuery("$mod divisor must be a number".into()))?;
+        let remainder = Self::truncate_to_i64(&args[1])
+            .ok_or_else(|| MongoLiteError::InvalidQuery("$mod remainder must be a number".into()))?;
+        if divisor == 0 {
+            return Err(MongoLiteError::InvalidQuery("$mod divisor cannot be 0".into()));
+        }
+        Ok(QueryOperator::Mod(divisor, remainder))
+    }
+
+    /// Bitmask operand of the $bits* operators: a non-negative integer mask
+    /// or an array of non-negative bit positions (integral floats accepted)
+    fn parse_bit_positions(op: &str, value: &Value) -> Result<Vec<u32>> {
+        match value {
+            Value::Number(_) => {
+                let mask = Self::integral_i64(value)
+                    .filter(|mask| *mask >= 0)
+                    .ok_or_else(|| MongoLiteError::InvalidQuery(format!("{} bitmask must be a non-negative integer", op)))?;
+                Ok((0..64).filter(|bit| mask & (1 << bit) != 0).collect())
+            }
+            Value::Array(positions) => positions.iter()
+                .map(|position| {
+                    Self::integral_i64(position)
+                        .and_then(|position| u32::try_from(position).ok())
+                        .filter(|position| *position <= i32::MAX as u32)
+                        .ok_or_else(|| MongoLiteError::InvalidQuery(format!("{} bit positions must be non-negative integers", op)))
+                })
+                .collect(),
+            _ => Err(MongoLiteError::InvalidQuery(format!("{} requires a numeric bitmask or an array of bit positions", op))),
+        }
+    }
+
+    /// Number truncated toward zero; None for non-numbers, NaN and values
+    /// outside the i64 range
+    fn truncate_to_i64(value: &Value) -> Option<i64> {
+        match value {
+            Value::Number(n) => n.as_i64().or_else(|| {
+                let f = n.as_f64()?.trunc();
+                (f >= i64::MIN as f64 && f < i64::MAX as f64).then_some(f as i64)
+            }),
+            _ => None,
+        }
+    }
+
+    /// Number with no fractional part that fits an i64 (the values the
+    /// $bits* operators test); None otherwise
+    fn integral_i64(value: &Value) -> Option<i64> {
+        match value {
+            Value::Number(n) => n.as_i64().or_else(|| {
+                let f = n.as_f64()?;
+                (f.fract() == 0.0 && f >= i64::MIN as f64 && f < i64::MAX as f64).then_some(f as i64)
+            }),
+            _ => None,
+        }
+    }
+
+    /// Bit `position` of a two's complement i64; positions past 63 repeat
+    /// the sign bit
+    fn bit_is_set(value: i64, position: u32) -> bool {
+        if position >= 64 {
+            value < 0
+        } else {
+            (value >> position) & 1 == 1
+        }
+    }
+
     /// Dokumentum illeszkedik-e a query-re
     pub fn matches(&self, document: &Document) -> bool {
         for (field, operator) in &self.conditions {
@@ -208,6 +303,10 @@ impl Query {
                 // Query must not match
                 !query.matches(document)
             }
+            QueryOperator::Expr(expr) => {
+                // Evaluation errors (e.g. division by zero) count as no match
+                expr.evaluate_document(document).is_ok_and(|v| expression::is_truthy(&v))
+            }
             _ => false,
         }
     }
@@ -215,46 +314,74 @@ impl Query {
     /// Operátor illeszkedés ellenőrzése
     fn matches_operator(value: Option<&Value>, operator: &QueryOperator, document: &Document) -> bool {
         match operator {
+            // Numbers compare by value: 1 matches 1.0
             QueryOperator::Eq(target) => {
-                value.map_or(false, |v| v == target)
+                value.is_some_and(|v| numeric::values_equal(v, target))
             }
 
             QueryOperator::Ne(target) => {
-                value.map_or(true, |v| v != target)
+                value.is_none_or(|v| !numeric::values_equal(v, target))
             }
 
             QueryOperator::Gt(target) => {
-                value.map_or(false, |v| Self::compare_values(v, target) == Some(std::cmp::Ordering::Greater))
+                value.is_some_and(|v| Self::compare_values(v, target) == Some(std::cmp::Ordering::Greater))
             }
 
             QueryOperator::Gte(target) => {
-                value.map_or(false, |v| {
+                value.is_some_and(|v| {
                     matches!(Self::compare_values(v, target), Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal))
                 })
             }
 
             QueryOperator::Lt(target) => {
-                value.map_or(false, |v| Self::compare_values(v, target) == Some(std::cmp::Ordering::Less))
+                value.is_some_and(|v| Self::compare_values(v, target) == Some(std::cmp::Ordering::Less))
             }
 
             QueryOperator::Lte(target) => {
-                value.map_or(false, |v| {
+                value.is_some_and(|v| {
                     matches!(Self::compare_values(v, target), Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal))
                 })
             }
 
             QueryOperator::In(targets) => {
-                value.map_or(false, |v| targets.contains(v))
+                value.is_some_and(|v| targets.iter().any(|t| numeric::values_equal(v, t)))
             }
 
             QueryOperator::Nin(targets) => {
-                value.map_or(true, |v| !targets.contains(v))
+                value.is_none_or(|v| !targets.iter().any(|t| numeric::values_equal(v, t)))
             }
 
             QueryOperator::Exists(should_exist) => {
                 value.is_some() == *should_exist
             }
 
+            // Non-numeric values (including numeric strings) never match
+            QueryOperator::Mod(divisor, remainder) => {
+                value.and_then(Self::truncate_to_i64)
+                    .is_some_and(|v| v.wrapping_rem(*divisor) == *remainder)
+            }
+
+            // Only integral numbers representable as i64 are tested
+            QueryOperator::BitsAllSet(positions) => {
+                value.and_then(Self::integral_i64)
+                    .is_some_and(|v| positions.iter().all(|p| Self::bit_is_set(v, *p)))
+            }
+
+            QueryOperator::BitsAnySet(positions) => {
+                value.and_then(Self::integral_i64)
+                    .is_some_and(|v| positions.iter().any(|p| Self::bit_is_set(v, *p)))
+            }
+
+            QueryOperator::BitsAllClear(positions) => {
+                value.and_then(Self::integral_i64)
+                    .is_some_and(|v| positions.iter().all(|p| !Self::bit_is_set(v, *p)))
+            }
+
+            QueryOperator::BitsAnyClear(positions) => {
+                value.and_then(Self::integral_i64)
+                    .is_some_and(|v| positions.iter().any(|p| !Self::bit_is_set(v, *p)))
+            }
+
             QueryOperator::Not(query) => {
                 // For field-level $not - check if the inner operator matches
                 // The query contains a single dummy "_field_" condition with the real operator
@@ -273,11 +400,7 @@ impl Query {
     /// Értékek összehasonlítása
     fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
         match (a, b) {
-            (Value::Number(n1), Value::Number(n2)) => {
-                let f1 = n1.as_f64()?;
-                let f2 = n2.as_f64()?;
-                f1.partial_cmp(&f2)
-            }
+            (Value::Number(n1), Value::Number(n2)) => Some(numeric::compare(n1, n2)),
             (Value::String(s1), Value::String(s2)) => Some(s1.cmp(s2)),
             (Value::Bool(b1), Value::Bool(b2)) => Some(b1.cmp(b2)),
             _ => None,
@@ -602,4 +725,95 @@ mod tests {
         assert!(query.matches(&doc1));
         assert!(!query.matches(&doc2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_query_expr_operator() {
+        let query = Query::from_json(&json!({
+            "$expr": {"$gt": ["$spent", {"$multiply": ["$budget", 1.5]}]}
+        })).unwrap();
+
+        let over = create_test_document(1, serde_json::Map::from_iter(vec![
+            ("spent".to_string(), json!(200)),
+            ("budget".to_string(), json!(100)),
+        ]));
+        let under = create_test_document(2, serde_json::Map::from_iter(vec![
+            ("spent".to_string(), json!(120)),
+            ("budget".to_string(), json!(100)),
+        ]));
+
+        assert!(query.matches(&over));
+        assert!(!query.matches(&under));
+
+        // Combines with ordinary field conditions
+        let query = Query::from_json(&json!({
+            "budget": 100,
+            "$expr": {"$eq": [{"$subtract": ["$spent", "$budget"]}, 20]}
+        })).unwrap();
+        assert!(!query.matches(&over));
+        assert!(query.matches(&under));
+
+        assert!(Query::from_json(&json!({"$expr": {"$nope": 1}})).is_err());
+    }
+
+    fn n_doc(value: Value) -> Document {
+        create_test_document(1, serde_json::Map::from_iter(vec![("n".to_string(), value)]))
+    }
+
+    #[test]
+    fn test_query_mod_operator() {
+        let query = Query::from_json(&json!({"n": {"$mod": [4, 1]}})).unwrap();
+        assert!(query.matches(&n_doc(json!(5))));
+        assert!(!query.matches(&n_doc(json!(6))));
+        // Fractional values are truncated toward zero, like MongoDB
+        assert!(query.matches(&n_doc(json!(5.9))));
+        // Numeric strings are not coerced
+        assert!(!query.matches(&n_doc(json!("5"))));
+        assert!(!query.matches(&n_doc(json!(null))));
+
+        // Remainder takes the sign of the dividend
+        let negative = Query::from_json(&json!({"n": {"$mod": [4, -1]}})).unwrap();
+        assert!(negative.matches(&n_doc(json!(-5))));
+        assert!(!negative.matches(&n_doc(json!(5))));
+
+        // Arguments are truncated as well
+        let truncated = Query::from_json(&json!({"n": {"$mod": [4.7, 1.2]}})).unwrap();
+        assert!(truncated.matches(&n_doc(json!(9))));
+
+        assert!(Query::from_json(&json!({"n": {"$mod": [0, 1]}})).is_err());
+        assert!(Query::from_json(&json!({"n": {"$mod": ["4", 1]}})).is_err());
+        assert!(Query::from_json(&json!({"n": {"$mod": [4]}})).is_err());
+    }
+
+    #[test]
+    fn test_query_bits_operators() {
+        // 54 = 0b110110
+        let doc = n_doc(json!(54));
+        let matches = |filter: Value| Query::from_json(&json!({"n": filter})).unwrap().matches(&doc);
+
+        assert!(matches(json!({"$bitsAllSet": [1, 2]})));
+        assert!(matches(json!({"$bitsAllSet": 6})));
+        assert!(!matches(json!({"$bitsAllSet": [0, 1]})));
+        assert!(matches(json!({"$bitsAnySet": [0, 1]})));
+        assert!(!matches(json!({"$bitsAnySet": 9})));
+        assert!(matches(json!({"$bitsAllClear": [0, 3]})));
+        assert!(!matches(json!({"$bitsAllClear": [0, 1]})));
+        assert!(matches(json!({"$bitsAnyClear": [0, 1]})));
+        assert!(!matches(json!({"$bitsAnyClear": 6})));
+
+        // Positions past 63 repeat the sign bit (two's complement)
+        let negative = Query::from_json(&json!({"n": {"$bitsAllSet": [63, 200]}})).unwrap();
+        assert!(negative.matches(&n_doc(json!(-1))));
+        assert!(!negative.matches(&n_doc(json!(1))));
+
+        // Only integral numbers are tested: no strings, no fractions
+        let any = Query::from_json(&json!({"n": {"$bitsAnySet": [1]}})).unwrap();
+        assert!(any.matches(&n_doc(json!(2.0))));
+        assert!(!any.matches(&n_doc(json!(2.5))));
+        assert!(!any.matches(&n_doc(json!("2"))));
+
+        assert!(Query::from_json(&json!({"n": {"$bitsAllSet": -1}})).is_err());
+        assert!(Query::from_json(&json!({"n": {"$bitsAllSet": 1.5}})).is_err());
+        assert!(Query::from_json(&json!({"n": {"$bitsAllSet": [-1]}})).is_err());
+        assert!(Query::from_json(&json!({"n": {"$bitsAllSet": "6"}})).is_err());
+    }
+}
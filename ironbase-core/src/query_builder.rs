@@ -0,0 +1,466 @@
+// ironbase-core/src/query_builder.rs
+// Programmatic construction of JSON queries and updates
+//
+//   QueryBuilder::field("age").gt(18).and(QueryBuilder::field("city").eq("NYC")).build()
+//   UpdateBuilder::set("name", "alice").inc("count", 1).build()
+//
+// and the runtime side of #[derive(Queryable)]:
+//
+//   #[derive(Queryable)]
+//   struct User { name: String, age: u32, city: Option<String> }
+//
+//   let query = User::query().age().gte(18).city().eq("NYC").build();
+//   // {"age": {"$gte": 18}, "city": "NYC"}
+//
+// The derive generates a `UserQuery` builder with one accessor per field.
+// Each accessor returns a `Field` whose comparison methods only accept the
+// field's type (`Option<T>` and `Vec<T>` fields compare against `T`).
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use serde::Serialize;
+use serde_json::Map;
+
+/// Re-exported for generated code
+pub use serde_json::Value;
+
+/// Conditions collected by a query builder, in the order they were added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    conditions: Vec<(String, Value)>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `field <op> value`
+    pub fn add(&mut self, field: &str, op: &str, value: Value) {
+        let condition = if op == "$eq" && !is_operator_object(&value) {
+            // Plain form lets the planner use an index on the field
+            value
+        } else {
+            let mut ops = Map::new();
+            ops.insert(op.to_string(), value);
+            Value::Object(ops)
+        };
+        self.conditions.push((field.to_string(), condition));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// The JSON query accepted by find(), count_documents(), update_*() ...
+    ///
+    /// The query language reads one operator per field, so further conditions
+    /// on a field go into `$and`: {"age": {"$gte": 18}, "$and": [{"age": {"$lt": 65}}]}
+    pub fn to_json(&self) -> Value {
+        let mut query = Map::new();
+        let mut repeated = Vec::new();
+
+        for (field, condition) in &self.conditions {
+            if query.contains_key(field) {
+                let mut extra = Map::new();
+                extra.insert(field.clone(), condition.clone());
+                repeated.push(Value::Object(extra));
+            } else {
+                query.insert(field.clone(), condition.clone());
+            }
+        }
+
+        if !repeated.is_empty() {
+            query.insert("$and".to_string(), Value::Array(repeated));
+        }
+        Value::Object(query)
+    }
+}
+
+/// Objects whose keys all start with '$' would be read as operators
+fn is_operator_object(value: &Value) -> bool {
+    value.as_object().is_some_and(|map| !map.is_empty() && map.keys().all(|key| key.starts_with('$')))
+}
+
+/// A builder collecting a Filter: QueryBuilder and the generated
+/// `<Struct>Query` types implement this
+pub trait FilterBuilder: Sized {
+    fn filter(&self) -> &Filter;
+    fn filter_mut(&mut self) -> &mut Filter;
+
+    /// The JSON query
+    fn build(&self) -> Value {
+        self.filter().to_json()
+    }
+}
+
+/// A field of a query builder `Q` holding values of type `T`
+pub struct Field<Q, T> {
+    query: Q,
+    name: Cow<'static, str>,
+    _value: PhantomData<fn(T)>,
+}
+
+impl<Q: FilterBuilder, T: Serialize> Field<Q, T> {
+    pub fn new(query: Q, name: impl Into<Cow<'static, str>>) -> Self {
+        Field {
+            query,
+            name: name.into(),
+            _value: PhantomData,
+        }
+    }
+
+    /// Stored (serialized) field name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn condition(mut self, op: &str, value: Value) -> Q {
+        self.query.filter_mut().add(&self.name, op, value);
+        self.query
+    }
+
+    fn compare(self, op: &str, value: T) -> Q {
+        let value = to_json(&value);
+        self.condition(op, value)
+    }
+
+    pub fn eq(self, value: impl Into<T>) -> Q {
+        self.compare("$eq", value.into())
+    }
+
+    pub fn ne(self, value: impl Into<T>) -> Q {
+        self.compare("$ne", value.into())
+    }
+
+    pub fn gt(self, value: impl Into<T>) -> Q {
+        self.compare("$gt", value.into())
+    }
+
+    pub fn gte(self, value: impl Into<T>) -> Q {
+        self.compare("$gte", value.into())
+    }
+
+    pub fn lt(self, value: impl Into<T>) -> Q {
+        self.compare("$lt", value.into())
+    }
+
+    pub fn lte(self, value: impl Into<T>) -> Q {
+        self.compare("$lte", value.into())
+    }
+
+    /// Value is one of `values` ($in)
+    pub fn is_in<I, V>(self, values: I) -> Q
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<T>,
+    {
+        let values = values.into_iter().map(|value| to_json(&value.into())).collect();
+        self.condition("$in", Value::Array(values))
+    }
+
+    /// Value is none of `values` ($nin)
+    pub fn not_in<I, V>(self, values: I) -> Q
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<T>,
+    {
+        let values = values.into_iter().map(|value| to_json(&value.into())).collect();
+        self.condition("$nin", Value::Array(values))
+    }
+
+    pub fn exists(self, exists: bool) -> Q {
+        self.condition("$exists", Value::Bool(exists))
+    }
+}
+
+/// Query built field by field, without a struct to derive from
+///
+/// Field values are any JSON-convertible value (`impl Into<Value>`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryBuilder {
+    filter: Filter,
+}
+
+impl FilterBuilder for QueryBuilder {
+    fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    fn filter_mut(&mut self) -> &mut Filter {
+        &mut self.filter
+    }
+}
+
+impl QueryBuilder {
+    /// Empty query (matches every document)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a query with a condition on `name`
+    pub fn field(name: impl Into<String>) -> Field<QueryBuilder, Value> {
+        Field::new(QueryBuilder::new(), name.into())
+    }
+
+    /// Both queries must match
+    pub fn and(mut self, other: QueryBuilder) -> Self {
+        self.filter.conditions.extend(other.filter.conditions);
+        self
+    }
+
+    /// Either query must match
+    pub fn or(self, other: QueryBuilder) -> Self {
+        let mut query = QueryBuilder::new();
+        query.filter.conditions.push(("$or".to_string(), Value::Array(vec![self.build(), other.build()])));
+        query
+    }
+
+    /// Neither query may match
+    pub fn nor(self, other: QueryBuilder) -> Self {
+        let mut query = QueryBuilder::new();
+        query.filter.conditions.push(("$nor".to_string(), Value::Array(vec![self.build(), other.build()])));
+        query
+    }
+
+    /// The JSON query
+    pub fn build(&self) -> Value {
+        self.filter.to_json()
+    }
+}
+
+impl From<QueryBuilder> for Value {
+    fn from(query: QueryBuilder) -> Self {
+        query.build()
+    }
+}
+
+/// Entry points of an update: `UpdateBuilder::set("name", "alice").inc("count", 1)`
+///
+/// Each returns an [`Update`] that further operators chain onto.
+pub struct UpdateBuilder;
+
+impl UpdateBuilder {
+    pub fn set(field: impl Into<String>, value: impl Into<Value>) -> Update {
+        Update::new().set(field, value)
+    }
+
+    pub fn unset(field: impl Into<String>) -> Update {
+        Update::new().unset(field)
+    }
+
+    pub fn inc(field: impl Into<String>, delta: impl Into<Value>) -> Update {
+        Update::new().inc(field, delta)
+    }
+
+    pub fn push(field: impl Into<String>, value: impl Into<Value>) -> Update {
+        Update::new().push(field, value)
+    }
+
+    pub fn pull(field: impl Into<String>, value: impl Into<Value>) -> Update {
+        Update::new().pull(field, value)
+    }
+
+    pub fn add_to_set(field: impl Into<String>, value: impl Into<Value>) -> Update {
+        Update::new().add_to_set(field, value)
+    }
+
+    pub fn pop_first(field: impl Into<String>) -> Update {
+        Update::new().pop_first(field)
+    }
+
+    pub fn pop_last(field: impl Into<String>) -> Update {
+        Update::new().pop_last(field)
+    }
+
+    pub fn merge(field: impl Into<String>, patch: impl Into<Value>) -> Update {
+        Update::new().merge(field, patch)
+    }
+}
+
+/// Update operators collected by UpdateBuilder, grouped per operator
+/// ({"$set": {..}, "$inc": {..}}); a later value for the same field wins
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Update {
+    operators: Map<String, Value>,
+}
+
+impl Update {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn operator(mut self, op: &str, field: String, value: Value) -> Self {
+        let fields = self.operators.entry(op.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(fields) = fields {
+            fields.insert(field, value);
+        }
+        self
+    }
+
+    pub fn set(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.operator("$set", field.into(), value.into())
+    }
+
+    pub fn unset(self, field: impl Into<String>) -> Self {
+        self.operator("$unset", field.into(), Value::String(String::new()))
+    }
+
+    /// Add `delta` to a numeric field (a missing field is left alone)
+    pub fn inc(self, field: impl Into<String>, delta: impl Into<Value>) -> Self {
+        self.operator("$inc", field.into(), delta.into())
+    }
+
+    pub fn push(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.operator("$push", field.into(), value.into())
+    }
+
+    /// Append several values at once ($push with $each)
+    pub fn push_each<I, V>(self, field: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        let mut each = Map::new();
+        each.insert("$each".to_string(), Value::Array(values.into_iter().map(Into::into).collect()));
+        self.operator("$push", field.into(), Value::Object(each))
+    }
+
+    pub fn pull(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.operator("$pull", field.into(), value.into())
+    }
+
+    pub fn add_to_set(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.operator("$addToSet", field.into(), value.into())
+    }
+
+    pub fn pop_first(self, field: impl Into<String>) -> Self {
+        self.operator("$pop", field.into(), Value::from(-1))
+    }
+
+    pub fn pop_last(self, field: impl Into<String>) -> Self {
+        self.operator("$pop", field.into(), Value::from(1))
+    }
+
+    /// Deep-merge `patch` into the field ($mergeObjects): nested objects merge
+    /// key by key, a null removes a key
+    pub fn merge(self, field: impl Into<String>, patch: impl Into<Value>) -> Self {
+        self.operator("$mergeObjects", field.into(), patch.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty()
+    }
+
+    /// The JSON update accepted by update_one() / update_many()
+    pub fn build(&self) -> Value {
+        Value::Object(self.operators.clone())
+    }
+}
+
+impl From<Update> for Value {
+    fn from(update: Update) -> Self {
+        update.build()
+    }
+}
+
+/// Field values are plain data; a value serde cannot represent as JSON
+/// (e.g. a map with non-string keys) compares as null
+fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// What #[derive(Queryable)] generates, written out
+    #[derive(Default)]
+    struct UserQuery {
+        filter: Filter,
+    }
+
+    impl FilterBuilder for UserQuery {
+        fn filter(&self) -> &Filter {
+            &self.filter
+        }
+
+        fn filter_mut(&mut self) -> &mut Filter {
+            &mut self.filter
+        }
+    }
+
+    impl UserQuery {
+        fn age(self) -> Field<Self, u32> {
+            Field::new(self, "age")
+        }
+
+        fn city(self) -> Field<Self, String> {
+            Field::new(self, "city")
+        }
+    }
+
+    #[test]
+    fn test_conditions_build_query() {
+        let query = UserQuery::default().age().gte(18u32).city().eq("NYC").build();
+        assert_eq!(query, json!({"age": {"$gte": 18}, "city": "NYC"}));
+
+        let query = UserQuery::default()
+            .city().is_in(["NYC", "LA"])
+            .age().not_in([1u32, 2])
+            .build();
+        assert_eq!(query, json!({"city": {"$in": ["NYC", "LA"]}, "age": {"$nin": [1, 2]}}));
+    }
+
+    #[test]
+    fn test_repeated_field_goes_to_and() {
+        let query = UserQuery::default().age().gte(18u32).age().lt(65u32).age().ne(30u32).build();
+        assert_eq!(query, json!({
+            "age": {"$gte": 18},
+            "$and": [{"age": {"$lt": 65}}, {"age": {"$ne": 30}}]
+        }));
+
+        let parsed = crate::query::Query::from_json(&query).unwrap();
+        let doc = |age: u32| crate::document::Document::from_json(&json!({"_id": 1, "age": age}).to_string()).unwrap();
+        assert!(parsed.matches(&doc(40)));
+        assert!(!parsed.matches(&doc(30)));
+        assert!(!parsed.matches(&doc(70)));
+    }
+
+    #[test]
+    fn test_fluent_query_builder() {
+        let query = QueryBuilder::field("age").gt(18).and(QueryBuilder::field("city").eq("NYC")).build();
+        assert_eq!(query, json!({"age": {"$gt": 18}, "city": "NYC"}));
+
+        let query = QueryBuilder::field("age").lt(18)
+            .or(QueryBuilder::field("age").gt(65))
+            .and(QueryBuilder::field("active").eq(true))
+            .build();
+        assert_eq!(query, json!({
+            "$or": [{"age": {"$lt": 18}}, {"age": {"$gt": 65}}],
+            "active": true
+        }));
+
+        assert_eq!(QueryBuilder::new().build(), json!({}));
+        assert_eq!(QueryBuilder::field("tags").is_in(["a", "b"]).build(), json!({"tags": {"$in": ["a", "b"]}}));
+    }
+
+    #[test]
+    fn test_update_builder() {
+        let update = UpdateBuilder::set("name", "alice").inc("count", 1).set("age", 31).build();
+        assert_eq!(update, json!({"$set": {"name": "alice", "age": 31}, "$inc": {"count": 1}}));
+
+        let update = UpdateBuilder::unset("tmp").push_each("tags", ["a", "b"]).pop_first("queue").build();
+        assert_eq!(update, json!({
+            "$unset": {"tmp": ""},
+            "$push": {"tags": {"$each": ["a", "b"]}},
+            "$pop": {"queue": -1}
+        }));
+        assert_eq!(UpdateBuilder::merge("address", json!({"city": "Pécs"})).build(), json!({
+            "$mergeObjects": {"address": {"city": "Pécs"}}
+        }));
+        assert!(Update::new().is_empty());
+    }
+}
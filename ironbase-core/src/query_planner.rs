@@ -1,8 +1,11 @@
 // src/query_planner.rs
 // Query planner and optimizer - index selection
 
+use std::collections::HashMap;
+use std::ops::Bound;
 use serde_json::Value;
-use crate::index::IndexKey;
+use crate::index::{IndexKey, HASHED_INDEX_SUFFIX};
+use crate::statistics::CollectionStatistics;
 
 /// Query plan - describes how to execute a query
 #[derive(Debug, Clone)]
@@ -26,6 +29,40 @@ pub enum QueryPlan {
         inclusive_start: bool,
         inclusive_end: bool,
     },
+
+    /// Hashed index lookup for equality / $in (keys are value hashes)
+    HashedIndexScan {
+        index_name: String,
+        field: String,
+        keys: Vec<IndexKey>,
+    },
+
+    /// Index point lookups for $in, one per key
+    IndexInScan {
+        index_name: String,
+        field: String,
+        keys: Vec<IndexKey>,
+    },
+
+    /// $or whose every branch can use an index: the union of the branch
+    /// plans, each document once
+    IndexUnion {
+        plans: Vec<QueryPlan>,
+    },
+}
+
+impl QueryPlan {
+    /// Names of the indexes the plan reads, in branch order
+    pub fn index_names(&self) -> Vec<&str> {
+        match self {
+            QueryPlan::IndexScan { index_name, .. }
+            | QueryPlan::IndexRangeScan { index_name, .. }
+            | QueryPlan::HashedIndexScan { index_name, .. }
+            | QueryPlan::IndexInScan { index_name, .. } => vec![index_name.as_str()],
+            QueryPlan::IndexUnion { plans } => plans.iter().flat_map(|plan| plan.index_names()).collect(),
+            QueryPlan::CollectionScan => Vec::new(),
+        }
+    }
 }
 
 /// Query planner - analyzes queries and selects optimal execution plan
@@ -34,7 +71,41 @@ pub struct QueryPlanner;
 impl QueryPlanner {
     /// Analyze a query and determine if an index can be used
     /// Returns (field_name, QueryPlan) if an index opportunity is found
+    /// (field_name is "$or" for a union of index scans)
     pub fn analyze_query(query_json: &Value, available_indexes: &[String]) -> Option<(String, QueryPlan)> {
+        // Ordered indexes first - they also serve ranges and covered queries
+        Self::analyze_ordered_query(query_json, available_indexes)
+            .or_else(|| Self::analyze_hashed_query(query_json, available_indexes))
+            .or_else(|| Self::analyze_or_query(query_json, available_indexes))
+    }
+
+    /// analyze_query() choosing by collection statistics (see statistics.rs):
+    /// of the conditions of a plain filter an index can serve, the one
+    /// estimated to match the fewest documents
+    pub fn analyze_query_with_statistics(
+        query_json: &Value,
+        available_indexes: &[String],
+        statistics: Option<&CollectionStatistics>,
+    ) -> Option<(String, QueryPlan)> {
+        let (Some(statistics), Some(map)) = (statistics, query_json.as_object().filter(|map| map.len() > 1)) else {
+            return Self::analyze_query(query_json, available_indexes);
+        };
+
+        map.iter()
+            .filter(|(field, _)| !field.starts_with('$'))
+            .filter_map(|(field, condition)| {
+                let single = Value::Object(serde_json::Map::from_iter([(field.clone(), condition.clone())]));
+                let (field, plan) = Self::analyze_query(&single, available_indexes)?;
+                let selectivity = statistics.selectivity(&field, condition).unwrap_or(1.0);
+                Some((selectivity, field, plan))
+            })
+            .min_by(|(a, _, _), (b, _, _)| a.total_cmp(b))
+            .map(|(_, field, plan)| (field, plan))
+            .or_else(|| Self::analyze_query(query_json, available_indexes))
+    }
+
+    /// Analyze a query against the ordered (non-hashed) indexes
+    fn analyze_ordered_query(query_json: &Value, available_indexes: &[String]) -> Option<(String, QueryPlan)> {
         // Check for simple equality query: { "field": value }
         if let Value::Object(ref map) = query_json {
             // First try range query analysis (handles { "field": { "$gte": ... } })
@@ -42,6 +113,11 @@ impl QueryPlanner {
                 return Some((field, plan));
             }
 
+            // Then $in lists (handles { "field": { "$in": [...] } })
+            if let Some((field, plan)) = Self::analyze_in_query(query_json, available_indexes) {
+                return Some((field, plan));
+            }
+
             // Skip logical operators like $and, $or, $nor
             if map.keys().any(|k| k.starts_with('$')) {
                 return None;
@@ -85,45 +161,10 @@ impl QueryPlanner {
 
                 if let Value::Object(ref cond_map) = conditions {
                     // Check for range operators
-                    let has_gt = cond_map.contains_key("$gt");
-                    let has_gte = cond_map.contains_key("$gte");
-                    let has_lt = cond_map.contains_key("$lt");
-                    let has_lte = cond_map.contains_key("$lte");
-
-                    if has_gt || has_gte || has_lt || has_lte {
+                    if cond_map.keys().any(|op| matches!(op.as_str(), "$gt" | "$gte" | "$lt" | "$lte")) {
                         // We have a range query
                         let index_name = Self::find_index_for_field(field, available_indexes)?;
-
-                        let start = if has_gte {
-                            cond_map.get("$gte").map(IndexKey::from)
-                        } else if has_gt {
-                            cond_map.get("$gt").map(IndexKey::from)
-                        } else {
-                            None
-                        };
-
-                        let end = if has_lte {
-                            cond_map.get("$lte").map(IndexKey::from)
-                        } else if has_lt {
-                            cond_map.get("$lt").map(IndexKey::from)
-                        } else {
-                            None
-                        };
-
-                        let inclusive_start = has_gte || (!has_gt && !has_gte);
-                        let inclusive_end = has_lte || (!has_lt && !has_lte);
-
-                        return Some((
-                            field.clone(),
-                            QueryPlan::IndexRangeScan {
-                                index_name,
-                                field: field.clone(),
-                                start,
-                                end,
-                                inclusive_start,
-                                inclusive_end,
-                            }
-                        ));
+                        return Some((field.clone(), Self::range_plan(index_name, field.clone(), cond_map)));
                     }
                 }
             }
@@ -132,19 +173,321 @@ impl QueryPlanner {
         None
     }
 
+    /// Range scan on an ordered index for the $gt/$gte/$lt/$lte operators of a condition
+    fn range_plan(index_name: String, field: String, cond_map: &serde_json::Map<String, Value>) -> QueryPlan {
+        let has_gt = cond_map.contains_key("$gt");
+        let has_gte = cond_map.contains_key("$gte");
+        let has_lt = cond_map.contains_key("$lt");
+        let has_lte = cond_map.contains_key("$lte");
+
+        let start = if has_gte {
+            cond_map.get("$gte").map(IndexKey::from)
+        } else if has_gt {
+            cond_map.get("$gt").map(IndexKey::from)
+        } else {
+            None
+        };
+
+        let end = if has_lte {
+            cond_map.get("$lte").map(IndexKey::from)
+        } else if has_lt {
+            cond_map.get("$lt").map(IndexKey::from)
+        } else {
+            None
+        };
+
+        QueryPlan::IndexRangeScan {
+            index_name,
+            field,
+            start,
+            end,
+            inclusive_start: has_gte || !has_gt,
+            inclusive_end: has_lte || !has_lt,
+        }
+    }
+
+    /// Analyze query for $in on an ordered index: one point lookup per value
+    fn analyze_in_query(query_json: &Value, available_indexes: &[String]) -> Option<(String, QueryPlan)> {
+        let map = query_json.as_object()?;
+
+        for (field, condition) in map {
+            if field.starts_with('$') {
+                continue; // Skip logical operators at root level
+            }
+            let values = match condition {
+                Value::Object(ops) if ops.len() == 1 => match ops.get("$in") {
+                    Some(Value::Array(values)) => values,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            // Null also matches missing fields, which are not indexed, and
+            // arrays or objects in the list match by structure
+            if values.iter().any(|value| matches!(value, Value::Null | Value::Array(_) | Value::Object(_))) {
+                continue;
+            }
+            let index_name = match Self::find_index_for_field(field, available_indexes) {
+                Some(index_name) => index_name,
+                None => continue,
+            };
+
+            return Some((field.clone(), Self::in_plan(index_name, field.clone(), values)));
+        }
+
+        None
+    }
+
+    /// Point lookups on an ordered index for the values of an $in list
+    pub fn in_plan(index_name: String, field: String, values: &[Value]) -> QueryPlan {
+        let mut keys: Vec<IndexKey> = values.iter().map(IndexKey::from).collect();
+        // Array and object field values are indexed as Null - their elements
+        // may still match, which the query filter decides
+        keys.push(IndexKey::Null);
+        keys.sort();
+        keys.dedup();
+
+        QueryPlan::IndexInScan { index_name, field, keys }
+    }
+
+    /// Analyze a root-level $or: a union of index scans when every branch
+    /// can use an index, otherwise nothing (a single collection scan is
+    /// cheaper than scans plus lookups)
+    fn analyze_or_query(query_json: &Value, available_indexes: &[String]) -> Option<(String, QueryPlan)> {
+        let branches = query_json.as_object()?.get("$or")?.as_array()?;
+        if branches.is_empty() {
+            return None;
+        }
+
+        let plans = branches.iter()
+            .map(|branch| Self::analyze_query(branch, available_indexes).map(|(_, plan)| plan))
+            .collect::<Option<Vec<QueryPlan>>>()?;
+
+        Some(("$or".to_string(), QueryPlan::IndexUnion { plans }))
+    }
+
+    /// Analyze query for hashed index use: equality, $eq or $in on a field
+    /// with a hashed index. Never used for ranges - hash order is meaningless.
+    fn analyze_hashed_query(query_json: &Value, available_indexes: &[String]) -> Option<(String, QueryPlan)> {
+        let map = query_json.as_object()?;
+
+        // Skip logical operators like $and, $or, $nor
+        if map.keys().any(|k| k.starts_with('$')) {
+            return None;
+        }
+
+        for (field, condition) in map {
+            let index_name = match Self::find_hashed_index_for_field(field, available_indexes) {
+                Some(index_name) => index_name,
+                None => continue,
+            };
+            if let Some(plan) = Self::hashed_plan(index_name, field.clone(), condition) {
+                return Some((field.clone(), plan));
+            }
+        }
+
+        None
+    }
+
+    /// Hashed index lookups for an equality, $eq or $in condition, if its
+    /// values can be hashed
+    fn hashed_plan(index_name: String, field: String, condition: &Value) -> Option<QueryPlan> {
+        // Only scalars hash to the key a document was indexed under
+        // (null also matches missing fields, which are not indexed)
+        let hashable = |value: &Value| !matches!(value, Value::Null | Value::Array(_) | Value::Object(_));
+
+        let values: Vec<&Value> = match condition {
+            Value::Object(ops) if ops.len() == 1 => match (ops.get("$eq"), ops.get("$in")) {
+                (Some(value), _) => vec![value],
+                (_, Some(Value::Array(values))) => values.iter().collect(),
+                _ => return None,
+            },
+            value => vec![value],
+        };
+        if !values.iter().all(|value| hashable(value)) {
+            return None;
+        }
+
+        let mut keys: Vec<IndexKey> = values.into_iter().map(IndexKey::hashed).collect();
+        keys.sort();
+        keys.dedup();
+
+        Some(QueryPlan::HashedIndexScan { index_name, field, keys })
+    }
+
+    /// Re-create a plan for another query of the same shape (see
+    /// plan_cache::QueryShape): same indexes, keys taken from `query_json`.
+    /// None if the query does not fit the plan after all.
+    pub fn bind(plan: &QueryPlan, query_json: &Value) -> Option<QueryPlan> {
+        let condition = |field: &str| query_json.as_object()?.get(field);
+
+        match plan {
+            QueryPlan::IndexScan { index_name, field, .. } => Some(QueryPlan::IndexScan {
+                index_name: index_name.clone(),
+                field: field.clone(),
+                key: IndexKey::from(condition(field)?),
+            }),
+            QueryPlan::IndexRangeScan { index_name, field, .. } => {
+                Some(Self::range_plan(index_name.clone(), field.clone(), condition(field)?.as_object()?))
+            }
+            QueryPlan::HashedIndexScan { index_name, field, .. } => {
+                Self::hashed_plan(index_name.clone(), field.clone(), condition(field)?)
+            }
+            QueryPlan::IndexInScan { index_name, field, .. } => {
+                let values = condition(field)?.get("$in")?.as_array()?;
+                Some(Self::in_plan(index_name.clone(), field.clone(), values))
+            }
+            QueryPlan::IndexUnion { plans } => {
+                let branches = condition("$or")?.as_array()?;
+                if branches.len() != plans.len() {
+                    return None;
+                }
+                let plans = plans.iter().zip(branches)
+                    .map(|(plan, branch)| Self::bind(plan, branch))
+                    .collect::<Option<Vec<QueryPlan>>>()?;
+                Some(QueryPlan::IndexUnion { plans })
+            }
+            QueryPlan::CollectionScan => Some(QueryPlan::CollectionScan),
+        }
+    }
+
     /// Find an index for a given field
     fn find_index_for_field(field: &str, available_indexes: &[String]) -> Option<String> {
-        // Look for index ending with _{field}
+        // Look for index ending with _{field} (hashed indexes only serve equality)
         available_indexes.iter()
-            .find(|idx| idx.ends_with(&format!("_{}", field)))
+            .find(|idx| idx.ends_with(&format!("_{}", field)) && !idx.ends_with(HASHED_INDEX_SUFFIX))
             .cloned()
     }
 
+    /// Find a hashed index for a given field
+    fn find_hashed_index_for_field(field: &str, available_indexes: &[String]) -> Option<String> {
+        // Look for index ending with _{field}_hashed
+        available_indexes.iter()
+            .find(|idx| idx.ends_with(&format!("_{}{}", field, HASHED_INDEX_SUFFIX)))
+            .cloned()
+    }
+
+    /// Index key bounds holding exactly the documents that match a query
+    /// on `field`, so walking an index needs no filter: an empty query, or a
+    /// single equality (number, string or bool) or single range operator
+    /// (number or string) on `field`. An open range end stops at the edge of
+    /// the value's kind - other kinds never compare. None when documents
+    /// would still need the query filter. An empty query also matches
+    /// documents without the field, which the caller has to rule out.
+    pub fn exact_bounds(query_json: &Value, field: &str) -> Option<(Bound<IndexKey>, Bound<IndexKey>)> {
+        let map = query_json.as_object()?;
+        if map.is_empty() {
+            return Some((Bound::Unbounded, Bound::Unbounded));
+        }
+        if map.len() != 1 {
+            return None;
+        }
+
+        match map.get(field)? {
+            value @ (Value::Number(_) | Value::String(_) | Value::Bool(_)) => {
+                let key = IndexKey::from(value);
+                Some((Bound::Included(key.clone()), Bound::Included(key)))
+            }
+            Value::Object(ops) if ops.len() == 1 => {
+                let (op, value) = ops.iter().next()?;
+                let key = IndexKey::from(value);
+                // Numbers sort between the booleans and the strings, strings last
+                let (kind_start, kind_end) = match value {
+                    Value::Number(_) => (Bound::Excluded(IndexKey::Bool(true)), Bound::Excluded(IndexKey::String(String::new()))),
+                    Value::String(_) => (Bound::Included(IndexKey::String(String::new())), Bound::Unbounded),
+                    _ => return None,
+                };
+                match op.as_str() {
+                    "$gt" => Some((Bound::Excluded(key), kind_end)),
+                    "$gte" => Some((Bound::Included(key), kind_end)),
+                    "$lt" => Some((kind_start, Bound::Excluded(key))),
+                    "$lte" => Some((kind_start, Bound::Included(key))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Check whether an index plan covers the query: the filter
+    /// touches only the indexed field and the projection and sort need nothing
+    /// beyond the index key and `_id`, so documents never have to be fetched
+    pub fn is_covered(
+        query_json: &Value,
+        plan: &QueryPlan,
+        projection: &HashMap<String, i32>,
+        sort: Option<&[(String, i32)]>,
+    ) -> bool {
+        // Hashed keys can't be turned back into field values
+        let field = match plan {
+            QueryPlan::IndexScan { field, .. } | QueryPlan::IndexRangeScan { field, .. } => field.as_str(),
+            QueryPlan::HashedIndexScan { .. }
+            | QueryPlan::IndexInScan { .. }
+            | QueryPlan::IndexUnion { .. }
+            | QueryPlan::CollectionScan => return false,
+        };
+        // Rows are rebuilt as {_id, field}: a dotted path has no place there
+        if field.contains('.') {
+            return false;
+        }
+        let covered_field = |name: &str| name == field || name == "_id";
+
+        // Filter: a single scalar equality or range condition on the indexed field
+        let condition = match query_json {
+            Value::Object(map) if map.len() == 1 => match map.get(field) {
+                Some(condition) => condition,
+                None => return false,
+            },
+            _ => return false,
+        };
+        let filter_covered = match condition {
+            Value::Object(ops) => {
+                !ops.is_empty()
+                    && ops.iter().all(|(op, value)| {
+                        matches!(op.as_str(), "$gt" | "$gte" | "$lt" | "$lte")
+                            && !value.is_object()
+                            && !value.is_array()
+                    })
+            }
+            Value::Array(_) | Value::Null => false,
+            _ => true,
+        };
+        if !filter_covered {
+            return false;
+        }
+
+        // Projection: include mode with only covered fields
+        let include_mode = projection.iter().any(|(name, &action)| action == 1 && name != "_id");
+        let projection_covered = include_mode
+            && projection.keys().all(|name| covered_field(name));
+        if !projection_covered {
+            return false;
+        }
+
+        sort.unwrap_or(&[]).iter().all(|(name, _)| covered_field(name))
+    }
+
     /// Create a query plan description for explain output
     pub fn explain_query(query_json: &Value, available_indexes: &[String]) -> Value {
+        Self::explain_query_with_options(query_json, available_indexes, None, None, None)
+    }
+
+    /// Explain output for a find with projection and sort, reporting whether
+    /// the query is answered from the index alone; planned with `statistics`
+    /// when the collection has them
+    pub fn explain_query_with_options(
+        query_json: &Value,
+        available_indexes: &[String],
+        projection: Option<&HashMap<String, i32>>,
+        sort: Option<&[(String, i32)]>,
+        statistics: Option<&CollectionStatistics>,
+    ) -> Value {
         use serde_json::json;
 
-        if let Some((field, plan)) = Self::analyze_query(query_json, available_indexes) {
+        if let Some((field, plan)) = Self::analyze_query_with_statistics(query_json, available_indexes, statistics) {
+            let covered = projection
+                .is_some_and(|projection| Self::is_covered(query_json, &plan, projection, sort));
+            let stage = if covered { "INDEX_ONLY" } else { "FETCH_WITH_INDEX" };
+
             // Index-based plan
             match plan {
                 QueryPlan::IndexScan { ref index_name, ref key, .. } => {
@@ -152,7 +495,8 @@ impl QueryPlanner {
                         "queryPlan": "IndexScan",
                         "indexUsed": index_name,
                         "field": field,
-                        "stage": "FETCH_WITH_INDEX",
+                        "stage": stage,
+                        "coveredQuery": covered,
                         "indexType": "equality",
                         "searchKey": format!("{:?}", key),
                         "estimatedCost": "O(log n)",
@@ -163,7 +507,8 @@ impl QueryPlanner {
                         "queryPlan": "IndexRangeScan",
                         "indexUsed": index_name,
                         "field": field,
-                        "stage": "FETCH_WITH_INDEX",
+                        "stage": stage,
+                        "coveredQuery": covered,
                         "indexType": "range",
                         "range": {
                             "start": format!("{:?}", start),
@@ -174,11 +519,48 @@ impl QueryPlanner {
                         "estimatedCost": "O(log n + k)",
                     })
                 }
+                QueryPlan::HashedIndexScan { ref index_name, ref keys, .. } => {
+                    json!({
+                        "queryPlan": "HashedIndexScan",
+                        "indexUsed": index_name,
+                        "field": field,
+                        "stage": stage,
+                        "coveredQuery": covered,
+                        "indexType": "hashed",
+                        "keyCount": keys.len(),
+                        "estimatedCost": "O(k log n)",
+                    })
+                }
+                QueryPlan::IndexInScan { ref index_name, ref keys, .. } => {
+                    json!({
+                        "queryPlan": "IndexInScan",
+                        "indexUsed": index_name,
+                        "field": field,
+                        "stage": stage,
+                        "coveredQuery": covered,
+                        "indexType": "equality",
+                        "keyCount": keys.len(),
+                        "estimatedCost": "O(k log n)",
+                    })
+                }
+                QueryPlan::IndexUnion { ref plans } => {
+                    json!({
+                        "queryPlan": "IndexUnion",
+                        "indexesUsed": plan.index_names(),
+                        "field": field,
+                        "stage": stage,
+                        "coveredQuery": covered,
+                        "indexType": "union",
+                        "branchCount": plans.len(),
+                        "estimatedCost": "O(b log n + k)",
+                    })
+                }
                 QueryPlan::CollectionScan => {
                     json!({
                         "queryPlan": "CollectionScan",
                         "indexUsed": null,
                         "stage": "FULL_SCAN",
+                        "coveredQuery": false,
                         "reason": "No suitable index",
                         "estimatedCost": "O(n)",
                     })
@@ -190,6 +572,7 @@ impl QueryPlanner {
                 "queryPlan": "CollectionScan",
                 "indexUsed": null,
                 "stage": "FULL_SCAN",
+                "coveredQuery": false,
                 "reason": "No suitable index found for query",
                 "estimatedCost": "O(n)",
                 "availableIndexes": available_indexes,
@@ -256,6 +639,150 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_covered_query_detection() {
+        let projection: HashMap<String, i32> = [("age".to_string(), 1)].into_iter().collect();
+        let sort = vec![("age".to_string(), -1)];
+        let plan = QueryPlan::IndexScan {
+            index_name: "users_age".to_string(),
+            field: "age".to_string(),
+            key: IndexKey::Int(25),
+        };
+
+        assert!(QueryPlanner::is_covered(&json!({"age": 25}), &plan, &projection, None));
+        assert!(QueryPlanner::is_covered(&json!({"age": {"$gte": 18, "$lt": 65}}), &plan, &projection, Some(&sort)));
+
+        // Filter, projection or sort reaching outside the index
+        assert!(!QueryPlanner::is_covered(&json!({"age": 25, "name": "Alice"}), &plan, &projection, None));
+        assert!(!QueryPlanner::is_covered(&json!({"age": {"$in": [1, 2]}}), &plan, &projection, None));
+        let with_name: HashMap<String, i32> = [("age".to_string(), 1), ("name".to_string(), 1)].into_iter().collect();
+        assert!(!QueryPlanner::is_covered(&json!({"age": 25}), &plan, &with_name, None));
+        let exclusion: HashMap<String, i32> = [("name".to_string(), 0)].into_iter().collect();
+        assert!(!QueryPlanner::is_covered(&json!({"age": 25}), &plan, &exclusion, None));
+        let by_name = vec![("name".to_string(), 1)];
+        assert!(!QueryPlanner::is_covered(&json!({"age": 25}), &plan, &projection, Some(&by_name)));
+
+        // Hashed keys never cover a projection
+        let hashed = QueryPlan::HashedIndexScan {
+            index_name: "users_age_hashed".to_string(),
+            field: "age".to_string(),
+            keys: vec![IndexKey::hashed(&json!(25))],
+        };
+        assert!(!QueryPlanner::is_covered(&json!({"age": 25}), &hashed, &projection, None));
+
+        let indexes = vec!["users_age".to_string()];
+        let plan = QueryPlanner::explain_query_with_options(&json!({"age": 25}), &indexes, Some(&projection), None, None);
+        assert_eq!(plan["coveredQuery"], true);
+        assert_eq!(plan["stage"], "INDEX_ONLY");
+        assert_eq!(QueryPlanner::explain_query(&json!({"age": 25}), &indexes)["coveredQuery"], false);
+    }
+
+    #[test]
+    fn test_hashed_index_equality_only() {
+        let indexes = vec!["users_email_hashed".to_string()];
+
+        match QueryPlanner::analyze_query(&json!({"email": "a@example.com"}), &indexes) {
+            Some((field, QueryPlan::HashedIndexScan { index_name, keys, .. })) => {
+                assert_eq!(field, "email");
+                assert_eq!(index_name, "users_email_hashed");
+                assert_eq!(keys, vec![IndexKey::hashed(&json!("a@example.com"))]);
+            }
+            other => panic!("Expected HashedIndexScan, got {:?}", other),
+        }
+
+        match QueryPlanner::analyze_query(&json!({"email": {"$in": ["a", "b", "a"]}}), &indexes) {
+            Some((_, QueryPlan::HashedIndexScan { keys, .. })) => assert_eq!(keys.len(), 2),
+            other => panic!("Expected HashedIndexScan, got {:?}", other),
+        }
+        assert!(matches!(
+            QueryPlanner::analyze_query(&json!({"email": {"$eq": "a"}}), &indexes),
+            Some((_, QueryPlan::HashedIndexScan { .. }))
+        ));
+
+        // Ranges, nulls and other operators never use the hashed index
+        assert!(QueryPlanner::analyze_query(&json!({"email": {"$gt": "a"}}), &indexes).is_none());
+        assert!(QueryPlanner::analyze_query(&json!({"email": null}), &indexes).is_none());
+        assert!(QueryPlanner::analyze_query(&json!({"email": {"$ne": "a"}}), &indexes).is_none());
+
+        // An ordered index on the same field wins
+        let both = vec!["users_email_hashed".to_string(), "users_email".to_string()];
+        assert!(matches!(
+            QueryPlanner::analyze_query(&json!({"email": "a"}), &both),
+            Some((_, QueryPlan::IndexScan { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_in_query_analysis() {
+        let indexes = vec!["users_age".to_string()];
+
+        match QueryPlanner::analyze_query(&json!({"age": {"$in": [30, 25, 30]}}), &indexes) {
+            Some((field, QueryPlan::IndexInScan { index_name, keys, .. })) => {
+                assert_eq!(field, "age");
+                assert_eq!(index_name, "users_age");
+                // Null keys stand for array and object values
+                assert_eq!(keys, vec![IndexKey::Null, IndexKey::Int(25), IndexKey::Int(30)]);
+            }
+            other => panic!("Expected IndexInScan, got {:?}", other),
+        }
+
+        // Null (missing fields) and structured values can't be looked up
+        assert!(QueryPlanner::analyze_query(&json!({"age": {"$in": [1, null]}}), &indexes).is_none());
+        assert!(QueryPlanner::analyze_query(&json!({"age": {"$in": [[1, 2]]}}), &indexes).is_none());
+        assert!(QueryPlanner::analyze_query(&json!({"age": {"$nin": [1]}}), &indexes).is_none());
+    }
+
+    #[test]
+    fn test_or_query_union() {
+        let indexes = vec!["users_age".to_string(), "users_email_hashed".to_string()];
+
+        let query = json!({"$or": [{"age": {"$gte": 65}}, {"email": "a@example.com"}, {"age": {"$in": [1, 2]}}]});
+        match QueryPlanner::analyze_query(&query, &indexes) {
+            Some((field, QueryPlan::IndexUnion { plans })) => {
+                assert_eq!(field, "$or");
+                assert!(matches!(plans[0], QueryPlan::IndexRangeScan { .. }));
+                assert!(matches!(plans[1], QueryPlan::HashedIndexScan { .. }));
+                assert!(matches!(plans[2], QueryPlan::IndexInScan { .. }));
+            }
+            other => panic!("Expected IndexUnion, got {:?}", other),
+        }
+
+        // Any unindexable branch (or no branches at all) means a collection scan
+        assert!(QueryPlanner::analyze_query(&json!({"$or": [{"age": 1}, {"name": "Alice"}]}), &indexes).is_none());
+        assert!(QueryPlanner::analyze_query(&json!({"$or": []}), &indexes).is_none());
+
+        let explain = QueryPlanner::explain_query(&query, &indexes);
+        assert_eq!(explain["queryPlan"], "IndexUnion");
+        assert_eq!(explain["branchCount"], 3);
+        assert_eq!(explain["indexesUsed"], json!(["users_age", "users_email_hashed", "users_age"]));
+    }
+
+    #[test]
+    fn test_exact_bounds() {
+        let bounds = |query: Value| QueryPlanner::exact_bounds(&query, "age");
+        let int = |i: i64| IndexKey::Int(i);
+
+        assert_eq!(bounds(json!({})), Some((Bound::Unbounded, Bound::Unbounded)));
+        assert_eq!(bounds(json!({"age": 5})), Some((Bound::Included(int(5)), Bound::Included(int(5)))));
+        assert_eq!(
+            bounds(json!({"age": {"$gte": 5}})),
+            Some((Bound::Included(int(5)), Bound::Excluded(IndexKey::String(String::new()))))
+        );
+        assert_eq!(bounds(json!({"age": {"$lt": "m"}})), Some((
+            Bound::Included(IndexKey::String(String::new())),
+            Bound::Excluded(IndexKey::String("m".to_string())),
+        )));
+
+        // Anything the index can't decide alone
+        assert_eq!(bounds(json!({"age": null})), None);
+        assert_eq!(bounds(json!({"age": [1, 2]})), None);
+        assert_eq!(bounds(json!({"age": {"$gte": 5, "$lt": 9}})), None);
+        assert_eq!(bounds(json!({"age": {"$gt": true}})), None);
+        assert_eq!(bounds(json!({"age": {"$in": [1, 2]}})), None);
+        assert_eq!(bounds(json!({"age": 5, "name": "Alice"})), None);
+        assert_eq!(bounds(json!({"name": "Alice"})), None);
+    }
+
     #[test]
     fn test_complex_query_no_optimization() {
         let query = json!({"$and": [{"age": 25}, {"name": "Alice"}]});
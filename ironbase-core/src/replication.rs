@@ -0,0 +1,245 @@
+// ironbase-core/src/replication.rs
+// Pull-based WAL replication (single leader -> follower)
+//
+// The leader's WAL is the replication stream: a follower tails the file from a
+// byte position, receives whole committed transactions and applies them to its
+// own database. The position is a plain WAL offset, so it can be persisted and
+// handed back later to resume.
+//
+// LIMITATIONS:
+// - Only writes that go through the WAL (transactions) are replicated
+// - The leader truncates its WAL on open() (recovery); a follower whose position
+//   is past the new end of the log gets ReplicationError and must be re-seeded
+//   from a copy of the leader file
+
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::storage::StorageEngine;
+use crate::transaction::{Operation, TransactionId};
+use crate::wal::{WALEntry, WALEntryType};
+
+/// Size of a WAL entry without its payload: 8 (tx_id) + 1 (type) + 4 (len) + 4 (crc)
+const WAL_ENTRY_OVERHEAD: usize = 17;
+
+/// Resumable position in the leader's WAL
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ReplicationPosition {
+    /// Byte offset of the next unread WAL entry
+    pub offset: u64,
+}
+
+impl ReplicationPosition {
+    /// Start of the log
+    pub fn start() -> Self {
+        ReplicationPosition { offset: 0 }
+    }
+
+    /// Opaque string form for persisting
+    pub fn to_token(&self) -> String {
+        format!("wal:{}", self.offset)
+    }
+
+    /// Parse a token produced by to_token()
+    pub fn from_token(token: &str) -> Result<Self> {
+        token.strip_prefix("wal:")
+            .and_then(|offset| offset.parse().ok())
+            .map(|offset| ReplicationPosition { offset })
+            .ok_or_else(|| MongoLiteError::ReplicationError(format!("Invalid position token: {}", token)))
+    }
+}
+
+/// One committed transaction read from the leader's WAL
+#[derive(Debug, Clone)]
+pub struct ReplicatedTransaction {
+    pub tx_id: TransactionId,
+    pub operations: Vec<Operation>,
+}
+
+/// Batch of committed transactions and the position after them
+#[derive(Debug, Clone)]
+pub struct ReplicationBatch {
+    pub transactions: Vec<ReplicatedTransaction>,
+    pub next_position: ReplicationPosition,
+}
+
+/// Leader side: tails the WAL file
+/// Opens the file read-only on every call, so it never interferes with the writer
+#[derive(Debug, Clone)]
+pub struct ReplicationSource {
+    wal_path: PathBuf,
+}
+
+impl ReplicationSource {
+    pub fn new(wal_path: impl AsRef<Path>) -> Self {
+        ReplicationSource { wal_path: wal_path.as_ref().to_path_buf() }
+    }
+
+    pub fn wal_path(&self) -> &Path {
+        &self.wal_path
+    }
+
+    /// Read up to `max_transactions` committed transactions starting at `position`
+    ///
+    /// Aborted transactions are skipped. An incomplete transaction at the tail
+    /// (still being written) is left for the next call.
+    pub fn read_batch(&self, position: ReplicationPosition, max_transactions: usize) -> Result<ReplicationBatch> {
+        let data = match std::fs::read(&self.wal_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        if position.offset > data.len() as u64 {
+            return Err(MongoLiteError::ReplicationError(format!(
+                "Position {} is beyond the end of the leader WAL ({} bytes) - the log was truncated, re-seed the follower",
+                position.offset, data.len()
+            )));
+        }
+
+        let mut transactions = Vec::new();
+        let mut next_position = position;
+        let mut offset = position.offset as usize;
+        let mut pending: Vec<WALEntry> = Vec::new();
+
+        while transactions.len() < max_transactions {
+            let entry = match Self::entry_at(&data, offset)? {
+                Some(entry) => entry,
+                None => break, // tail not fully written yet
+            };
+            offset += WAL_ENTRY_OVERHEAD + entry.data.len();
+
+            match entry.entry_type {
+                WALEntryType::Commit => {
+                    let tx_id = entry.transaction_id;
+                    let operations = pending.drain(..)
+                        .filter(|e| e.transaction_id == tx_id && e.entry_type == WALEntryType::Operation)
+                        .map(|e| serde_json::from_slice::<Operation>(&e.data))
+                        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+                    transactions.push(ReplicatedTransaction { tx_id, operations });
+                    next_position = ReplicationPosition { offset: offset as u64 };
+                }
+                WALEntryType::Abort => {
+                    let tx_id = entry.transaction_id;
+                    pending.retain(|e| e.transaction_id != tx_id);
+                    if pending.is_empty() {
+                        next_position = ReplicationPosition { offset: offset as u64 };
+                    }
+                }
+                _ => pending.push(entry),
+            }
+        }
+
+        Ok(ReplicationBatch { transactions, next_position })
+    }
+
+    /// Parse the entry at `offset`; None if it is not completely written yet
+    fn entry_at(data: &[u8], offset: usize) -> Result<Option<WALEntry>> {
+        if offset + WAL_ENTRY_OVERHEAD > data.len() {
+            return Ok(None);
+        }
+
+        let data_len = u32::from_le_bytes(data[offset + 9..offset + 13].try_into().unwrap()) as usize;
+        let end = offset + WAL_ENTRY_OVERHEAD + data_len;
+        if end > data.len() {
+            return Ok(None);
+        }
+
+        // A checksum mismatch here usually means the position does not land on an entry boundary
+        WALEntry::deserialize(&data[offset..end])
+            .map(Some)
+            .map_err(|_| MongoLiteError::ReplicationError(format!(
+                "Invalid WAL entry at offset {} - position does not match the leader log", offset
+            )))
+    }
+}
+
+/// Follower side: pulls from a leader WAL and applies to a local database
+pub struct Follower {
+    source: ReplicationSource,
+    position: ReplicationPosition,
+}
+
+impl Follower {
+    /// Create a follower resuming at `position` (use ReplicationPosition::start() for a fresh copy)
+    pub fn new(leader_wal_path: impl AsRef<Path>, position: ReplicationPosition) -> Self {
+        Follower {
+            source: ReplicationSource::new(leader_wal_path),
+            position,
+        }
+    }
+
+    /// Current position - persist this to resume after restart
+    pub fn position(&self) -> ReplicationPosition {
+        self.position
+    }
+
+    /// Pull and apply up to `max_transactions` transactions
+    /// Returns the number of transactions applied
+    pub fn pull(&mut self, db: &crate::database::DatabaseCore, max_transactions: usize) -> Result<usize> {
+        let batch = self.source.read_batch(self.position, max_transactions)?;
+
+        for tx in &batch.transactions {
+            db.apply_replicated(tx)?;
+        }
+
+        self.position = batch.next_position;
+        Ok(batch.transactions.len())
+    }
+
+    /// Pull until the follower has caught up with the leader's WAL
+    pub fn catch_up(&mut self, db: &crate::database::DatabaseCore) -> Result<usize> {
+        let mut total = 0;
+        loop {
+            let applied = self.pull(db, 100)?;
+            if applied == 0 {
+                return Ok(total);
+            }
+            total += applied;
+        }
+    }
+}
+
+/// Apply one replicated operation with catalog tracking
+/// Unlike WAL recovery this goes through write_document, so documents are visible to queries
+pub(crate) fn apply_operation(storage: &mut StorageEngine, operation: &Operation) -> Result<()> {
+    let (collection, doc_id, doc) = match operation {
+        Operation::Insert { collection, doc_id, doc } => (collection, doc_id, doc.clone()),
+        Operation::Update { collection, doc_id, new_doc, .. } => (collection, doc_id, new_doc.clone()),
+        Operation::Delete { collection, doc_id, .. } => {
+            let tombstone = serde_json::json!({
+                "_id": doc_id,
+                "_collection": collection,
+                "_tombstone": true
+            });
+            (collection, doc_id, tombstone)
+        }
+    };
+
+    if storage.get_collection_meta(collection).is_none() {
+        storage.create_collection(collection)?;
+    }
+
+    let mut doc = doc;
+    if let Value::Object(ref mut map) = doc {
+        map.entry("_id".to_string()).or_insert_with(|| serde_json::json!(doc_id));
+        map.insert("_collection".to_string(), Value::String(collection.clone()));
+    }
+
+    let doc_json = serde_json::to_vec(&doc)?;
+    storage.write_document(collection, doc_id, &doc_json)?;
+
+    // Keep auto-increment ids ahead of replicated ones
+    if let DocumentId::Int(i) = doc_id {
+        storage.claim_ids(collection, (*i).max(0) as u64)?;
+    }
+
+    // No index maintenance here - the handles' indexes are out of date
+    storage.mark_indexes_stale(collection);
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+// ironbase-core/src/sequence.rs
+// Named sequences: monotonic counters for application numbers (invoice
+// numbers, ticket ids) without a find-max-then-insert race
+
+use std::sync::Arc;
+
+use crate::contention::TimedRwLock;
+use crate::error::Result;
+use crate::storage::StorageEngine;
+
+/// Collection holding one document per sequence (hidden from list_collections)
+pub const SEQUENCES_COLLECTION: &str = "_sequences";
+
+/// Handle on a named sequence (see DatabaseCore::get_sequence)
+///
+/// Values are allocated like auto-increment _ids (see storage/ids.rs): they
+/// only ever go up and are never handed out twice, but a crash leaves a gap.
+#[derive(Clone)]
+pub struct Sequence {
+    name: String,
+    storage: Arc<TimedRwLock<StorageEngine>>,
+}
+
+impl Sequence {
+    pub(crate) fn new(name: &str, storage: Arc<TimedRwLock<StorageEngine>>) -> Self {
+        Sequence { name: name.to_string(), storage }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Allocate the next value (the first one is 1)
+    pub fn next(&self) -> Result<u64> {
+        self.storage.write().next_sequence_value(&self.name)
+    }
+
+    /// Last value allocated, 0 if none
+    pub fn current(&self) -> Result<u64> {
+        self.storage.write().sequence_value(&self.name)
+    }
+}
@@ -0,0 +1,159 @@
+// ironbase-core/src/session.rs
+// Causal consistency: logical timestamps (LSN) and sessions
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::{Result, MongoLiteError};
+
+/// Log sequence number - advances by one for every committed document write
+/// (all writes of a transaction share one)
+pub type Lsn = u64;
+
+/// Monotonic LSN clock shared by the storage engine and sessions
+/// Readers can wait for it without touching the storage lock
+#[derive(Debug, Default)]
+pub struct LsnClock {
+    current: AtomicU64,
+    lock: Mutex<()>,
+    advanced: Condvar,
+}
+
+impl LsnClock {
+    pub fn new(start: Lsn) -> Self {
+        LsnClock {
+            current: AtomicU64::new(start),
+            ..Default::default()
+        }
+    }
+
+    pub fn current(&self) -> Lsn {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    /// Advance by one and wake waiters; returns the new LSN
+    pub fn advance(&self) -> Lsn {
+        let _guard = self.lock.lock();
+        let lsn = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.advanced.notify_all();
+        lsn
+    }
+
+    /// Block until the clock reaches `lsn` or `timeout` expires
+    pub fn wait_for(&self, lsn: Lsn, timeout: Duration) -> Result<Lsn> {
+        let deadline = Instant::now() + timeout;
+        let mut guard = self.lock.lock();
+
+        loop {
+            let current = self.current();
+            if current >= lsn {
+                return Ok(current);
+            }
+            if self.advanced.wait_until(&mut guard, deadline).timed_out() && self.current() < lsn {
+                return Err(MongoLiteError::Timeout(format!(
+                    "waiting for LSN {} (current {})", lsn, self.current()
+                )));
+            }
+        }
+    }
+}
+
+/// Client session with read-your-writes guarantees
+///
+/// Every write run through the session records the LSN it committed at
+/// (the session's operation time). Reads run through the session first wait
+/// until the database has reached that LSN. Operation times can be passed
+/// between threads/processes with `advance_operation_time()`.
+pub struct Session {
+    clock: Arc<LsnClock>,
+    operation_time: AtomicU64,
+    read_timeout: Duration,
+}
+
+impl Session {
+    pub(crate) fn new(clock: Arc<LsnClock>) -> Self {
+        Session {
+            clock,
+            operation_time: AtomicU64::new(0),
+            read_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// How long reads wait for the database to catch up (default: 5s)
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Highest LSN this session has written or observed
+    pub fn operation_time(&self) -> Lsn {
+        self.operation_time.load(Ordering::SeqCst)
+    }
+
+    /// Adopt an LSN obtained elsewhere (e.g. from another session)
+    pub fn advance_operation_time(&self, lsn: Lsn) {
+        self.operation_time.fetch_max(lsn, Ordering::SeqCst);
+    }
+
+    /// Run a write and return its result with the LSN it committed at
+    ///
+    /// NOTE: under concurrent writers the returned LSN may be slightly higher
+    /// than the write's own - that is safe, it only makes later reads wait for
+    /// writes that are already committed.
+    pub fn write<T, F>(&self, f: F) -> Result<(T, Lsn)>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        let result = f()?;
+        let lsn = self.clock.current();
+        self.advance_operation_time(lsn);
+        Ok((result, lsn))
+    }
+
+    /// Run a read once the database has reached the session's operation time
+    pub fn read<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        self.read_after(self.operation_time(), f)
+    }
+
+    /// Run a read once the database has reached `lsn`
+    pub fn read_after<T, F>(&self, lsn: Lsn, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        self.clock.wait_for(lsn, self.read_timeout)?;
+        self.advance_operation_time(lsn);
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_advance_and_wait() {
+        let clock = Arc::new(LsnClock::new(10));
+        assert_eq!(clock.advance(), 11);
+        assert_eq!(clock.wait_for(11, Duration::from_millis(10)).unwrap(), 11);
+
+        let waiter = {
+            let clock = Arc::clone(&clock);
+            std::thread::spawn(move || clock.wait_for(13, Duration::from_secs(5)))
+        };
+        clock.advance();
+        clock.advance();
+        assert_eq!(waiter.join().unwrap().unwrap(), 13);
+    }
+
+    #[test]
+    fn test_clock_wait_timeout() {
+        let clock = LsnClock::new(0);
+        let result = clock.wait_for(1, Duration::from_millis(20));
+        assert!(matches!(result, Err(MongoLiteError::Timeout(_))));
+    }
+}
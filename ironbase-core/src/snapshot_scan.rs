@@ -0,0 +1,105 @@
+// ironbase-core/src/snapshot_scan.rs
+// Snapshot scans: a collection read as of one LSN, a batch at a time
+//
+// A scan pins a Snapshot and the _ids the collection holds when it starts,
+// then reads the documents SNAPSHOT_SCAN_BATCH at a time, taking the storage
+// lock once per batch. Writers go on in between: the snapshot keeps every
+// version they replace, so the scan still sees each document as of its LSN,
+// and a transaction committed meanwhile is invisible as a whole. Compaction
+// in between is fine too - it moves the kept versions into memory.
+//
+// Exports, backups (see backup.rs) and $out read through snapshot scans.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use serde_json::Value;
+
+use crate::contention::TimedRwLock;
+use crate::document::DocumentId;
+use crate::encryption::FieldEncryptor;
+use crate::error::Result;
+use crate::storage::{Snapshot, StorageEngine};
+
+/// Documents read per storage lock acquisition
+pub const SNAPSHOT_SCAN_BATCH: usize = 256;
+
+/// A document as stored: _id and serialized form
+pub type StoredDocument = (DocumentId, Vec<u8>);
+
+/// Documents of one collection as of a snapshot (see snapshot_scan.rs)
+///
+/// Iterates over the documents in plaintext; next_batch() hands out the
+/// stored form instead. Ends with an error if the collection is dropped
+/// part-way.
+pub struct SnapshotScan {
+    storage: Arc<TimedRwLock<StorageEngine>>,
+    collection: String,
+    snapshot: Snapshot,
+    ids: std::vec::IntoIter<DocumentId>,
+    encryptor: FieldEncryptor,
+    /// Read but not yet returned by next()
+    pending: VecDeque<StoredDocument>,
+}
+
+impl SnapshotScan {
+    /// Scan `collection` as of `snapshot`
+    pub fn new(storage: Arc<TimedRwLock<StorageEngine>>, collection: &str, snapshot: Snapshot) -> Result<Self> {
+        let (ids, encryptor) = {
+            let engine = storage.read();
+            (engine.snapshot_ids(collection)?, engine.field_encryptor(collection))
+        };
+        Ok(SnapshotScan {
+            storage,
+            collection: collection.to_string(),
+            snapshot,
+            ids: ids.into_iter(),
+            encryptor,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// The snapshot the scan reads at
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// The next documents as stored (encrypted fields encrypted), None once
+    /// the scan is done; batches may come out empty
+    pub fn next_batch(&mut self) -> Result<Option<Vec<StoredDocument>>> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.drain(..).collect()));
+        }
+        let ids: Vec<DocumentId> = self.ids.by_ref().take(SNAPSHOT_SCAN_BATCH).collect();
+        if ids.is_empty() {
+            return Ok(None);
+        }
+
+        let mut storage = self.storage.write();
+        let mut batch = Vec::with_capacity(ids.len());
+        for doc_id in ids {
+            if let Some(data) = storage.read_version_at(&self.collection, &doc_id, self.snapshot.lsn())? {
+                batch.push((doc_id, data));
+            }
+        }
+        Ok(Some(batch))
+    }
+}
+
+impl Iterator for SnapshotScan {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            match self.next_batch() {
+                Ok(Some(batch)) => self.pending.extend(batch),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let (_, data) = self.pending.pop_front()?;
+        let decoded = serde_json::from_slice::<Value>(&data)
+            .map_err(Into::into)
+            .and_then(|mut doc| self.encryptor.decrypt_document(&mut doc).map(|_| doc));
+        Some(decoded)
+    }
+}
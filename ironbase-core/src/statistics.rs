@@ -0,0 +1,336 @@
+// ironbase-core/src/statistics.rs
+// Collection statistics for the query planner ($analyze)
+//
+// CollectionCore::analyze() reads a sample of the collection - every k-th
+// document in natural order, at most ANALYZE_SAMPLE - and keeps per
+// top-level field how many documents hold it, how many distinct values it
+// has and an equi-depth histogram of its scalar values (in index key order,
+// a value never split across buckets). The result goes into the
+// collection's metadata and, for reading it like any other document, into
+// the STATS_COLLECTION system collection.
+//
+// The planner uses the statistics to pick the index of the most selective
+// condition when a query could use several (see
+// QueryPlanner::analyze_query_with_statistics), and explain reports the
+// estimated result size. Fields are assumed independent. Statistics are
+// not maintained by writes: analyze again after large changes - estimates
+// scale with the current document count meanwhile. Encrypted fields are
+// described by their stored ciphertexts, which is what queries compare.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::index::IndexKey;
+
+/// System collection holding the statistics of each analyzed collection,
+/// one document per collection with its name as _id (hidden from
+/// list_collections)
+pub const STATS_COLLECTION: &str = "_stats";
+
+/// Documents analyze() samples at most
+pub const ANALYZE_SAMPLE: usize = 1000;
+
+/// Buckets per histogram at most
+pub const HISTOGRAM_BUCKETS: usize = 32;
+
+/// Statistics of one collection (see statistics.rs)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionStatistics {
+    /// Documents in the collection when it was analyzed
+    pub documents: u64,
+    /// Documents sampled
+    pub sampled: u64,
+    /// Per top-level field, _id excluded
+    pub fields: BTreeMap<String, FieldStatistics>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldStatistics {
+    /// Sampled documents holding the field (null included)
+    pub present: u64,
+    /// Sampled documents holding null
+    pub nulls: u64,
+    /// Estimated distinct values in the whole collection
+    pub distinct: u64,
+    /// Equi-depth histogram of the sampled scalar values, in index key order
+    pub histogram: Vec<Bucket>,
+}
+
+/// Histogram bucket: the sampled values above the previous bucket's upper
+/// bound, up to and including its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub upper: Value,
+    /// Sampled values in the bucket
+    pub count: u64,
+    /// Distinct sampled values in the bucket
+    pub distinct: u64,
+}
+
+/// A field in the sample: documents holding it, nulls, scalar values
+type FieldSample<'a> = (u64, u64, Vec<(IndexKey, &'a Value)>);
+
+impl CollectionStatistics {
+    /// Statistics of a collection of `documents` documents from a sample of them
+    pub fn build(sample: &[Value], documents: u64) -> Self {
+        let mut present: BTreeMap<&str, FieldSample> = BTreeMap::new();
+        for doc in sample {
+            let Some(map) = doc.as_object() else { continue };
+            for (field, value) in map {
+                if field == "_id" || field == "_tombstone" {
+                    continue;
+                }
+                let (count, nulls, values) = present.entry(field).or_default();
+                *count += 1;
+                match value {
+                    Value::Null => *nulls += 1,
+                    Value::Array(_) | Value::Object(_) => {}
+                    scalar => values.push((IndexKey::from(scalar), scalar)),
+                }
+            }
+        }
+
+        let sampled = sample.len() as u64;
+        let fields = present.into_iter()
+            .map(|(field, (present, nulls, mut values))| {
+                values.sort_by(|(a, _), (b, _)| a.cmp(b));
+                let groups = group_values(&values);
+                let stats = FieldStatistics {
+                    present,
+                    nulls,
+                    distinct: estimate_distinct(&groups, sampled, documents),
+                    histogram: histogram(&groups),
+                };
+                (field.to_string(), stats)
+            })
+            .collect();
+
+        CollectionStatistics { documents, sampled, fields }
+    }
+
+    /// Estimated fraction of the documents whose `field` satisfies
+    /// `condition` (a value or an operator object), None if the field was
+    /// not analyzed or the condition is not an equality or range
+    pub fn selectivity(&self, field: &str, condition: &Value) -> Option<f64> {
+        let stats = self.fields.get(field)?;
+        let sampled = self.sampled.max(1) as f64;
+
+        let fraction = match condition {
+            Value::Object(ops) if ops.keys().any(|op| op.starts_with('$')) => {
+                let mut fraction = 1.0f64;
+                let (mut lower, mut upper) = (None, None);
+                for (op, operand) in ops {
+                    match op.as_str() {
+                        "$eq" => fraction = fraction.min(stats.equal(operand, sampled)),
+                        "$in" => {
+                            let values = operand.as_array()?;
+                            fraction = fraction.min(values.iter().map(|value| stats.equal(value, sampled)).sum());
+                        }
+                        "$gt" | "$gte" => lower = Some((IndexKey::from(operand), op == "$gte")),
+                        "$lt" | "$lte" => upper = Some((IndexKey::from(operand), op == "$lte")),
+                        _ => return None,
+                    }
+                }
+                if lower.is_some() || upper.is_some() {
+                    fraction = fraction.min(stats.range(lower.as_ref(), upper.as_ref()) / sampled);
+                }
+                fraction
+            }
+            Value::Array(_) | Value::Object(_) => return None,
+            value => stats.equal(value, sampled),
+        };
+        Some(fraction.clamp(0.0, 1.0))
+    }
+
+    /// Estimated documents of `documents` matching `query`: the conditions
+    /// of a plain filter (and $and) multiply, $or branches add up;
+    /// conditions without statistics count as matching everything
+    pub fn estimate(&self, query: &Value, documents: u64) -> u64 {
+        (self.query_selectivity(query) * documents as f64).round() as u64
+    }
+
+    fn query_selectivity(&self, query: &Value) -> f64 {
+        let Some(map) = query.as_object() else {
+            return 1.0;
+        };
+        map.iter()
+            .map(|(key, condition)| match key.as_str() {
+                "$and" => condition.as_array()
+                    .map_or(1.0, |clauses| clauses.iter().map(|clause| self.query_selectivity(clause)).product()),
+                "$or" => condition.as_array()
+                    .map_or(1.0, |clauses| clauses.iter().map(|clause| self.query_selectivity(clause)).sum::<f64>().min(1.0)),
+                operator if operator.starts_with('$') => 1.0,
+                field => self.selectivity(field, condition).unwrap_or(1.0),
+            })
+            .product()
+    }
+
+    /// Document stored in STATS_COLLECTION for `collection`
+    pub fn to_document(&self, collection: &str) -> Value {
+        let mut doc = serde_json::to_value(self).unwrap_or_default();
+        if let Value::Object(map) = &mut doc {
+            map.insert("_id".to_string(), Value::from(collection));
+            map.insert("_collection".to_string(), Value::from(STATS_COLLECTION));
+        }
+        doc
+    }
+}
+
+impl FieldStatistics {
+    /// Fraction of sampled documents (of `sampled`) holding `value`
+    fn equal(&self, value: &Value, sampled: f64) -> f64 {
+        match value {
+            // Missing fields match null too
+            Value::Null => (self.nulls as f64 + sampled - self.present as f64) / sampled,
+            Value::Array(_) | Value::Object(_) => 1.0,
+            value => {
+                let key = IndexKey::from(value);
+                match self.histogram.iter().find(|bucket| IndexKey::from(&bucket.upper) >= key) {
+                    // Values of a bucket are taken as equally frequent
+                    Some(bucket) => bucket.count as f64 / bucket.distinct.max(1) as f64 / sampled,
+                    None => 0.0,
+                }
+            }
+        }
+    }
+
+    /// Sampled values between the bounds (key, inclusive); a one-sided
+    /// range only covers values of its bound's type, as queries compare
+    fn range(&self, lower: Option<&(IndexKey, bool)>, upper: Option<&(IndexKey, bool)>) -> f64 {
+        let kind = lower.or(upper).map(|(key, _)| key_kind(key));
+        let mut previous: Option<IndexKey> = None;
+        let mut count = 0.0;
+        for bucket in &self.histogram {
+            let top = IndexKey::from(&bucket.upper);
+            let bottom = previous.replace(top.clone());
+            if kind.is_some_and(|kind| key_kind(&top) != kind) {
+                continue;
+            }
+            let above = lower.is_none_or(|(bound, inclusive)| if *inclusive { top >= *bound } else { top > *bound });
+            let below = upper.is_none_or(|(bound, _)| bottom.as_ref().is_none_or(|bottom| bottom < bound));
+            if !above || !below {
+                continue;
+            }
+            // Whole bucket inside the range, or the part a bound cuts off - taken as half
+            let inside_lower = lower.is_none_or(|(bound, _)| bottom.as_ref().is_some_and(|bottom| bottom >= bound));
+            let inside_upper = upper.is_none_or(|(bound, inclusive)| if *inclusive { top <= *bound } else { top < *bound });
+            count += if inside_lower && inside_upper { bucket.count as f64 } else { bucket.count as f64 / 2.0 };
+        }
+        count
+    }
+}
+
+/// Type class of a key: ranges only compare within one
+fn key_kind(key: &IndexKey) -> u8 {
+    match key {
+        IndexKey::Null => 0,
+        IndexKey::Bool(_) => 1,
+        IndexKey::Int(_) | IndexKey::UInt(_) | IndexKey::Float(_) => 2,
+        IndexKey::String(_) => 3,
+    }
+}
+
+/// Sorted values as (value, occurrences)
+fn group_values<'a>(values: &[(IndexKey, &'a Value)]) -> Vec<(&'a Value, u64)> {
+    let mut groups: Vec<(&IndexKey, &Value, u64)> = Vec::new();
+    for (key, value) in values {
+        match groups.last_mut() {
+            Some((last, _, count)) if *last == key => *count += 1,
+            _ => groups.push((key, value, 1)),
+        }
+    }
+    groups.into_iter().map(|(_, value, count)| (value, count)).collect()
+}
+
+/// Distinct values of the collection from those of the sample: the GEE
+/// estimator - values seen once scale with sqrt(documents / sampled),
+/// values seen more often are taken as all there are
+fn estimate_distinct(groups: &[(&Value, u64)], sampled: u64, documents: u64) -> u64 {
+    let distinct = groups.len() as u64;
+    if sampled == 0 || sampled >= documents {
+        return distinct;
+    }
+    let singles = groups.iter().filter(|(_, count)| *count == 1).count() as f64;
+    let scale = (documents as f64 / sampled as f64).sqrt();
+    let estimate = (scale * singles + (distinct as f64 - singles)).round() as u64;
+    estimate.clamp(distinct, documents)
+}
+
+/// Equi-depth buckets over sorted (value, occurrences) groups
+fn histogram(groups: &[(&Value, u64)]) -> Vec<Bucket> {
+    let total: u64 = groups.iter().map(|(_, count)| count).sum();
+    let depth = total.div_ceil(HISTOGRAM_BUCKETS as u64).max(1);
+
+    let mut buckets: Vec<Bucket> = Vec::new();
+    let mut open = false;
+    for (value, count) in groups {
+        match buckets.last_mut() {
+            Some(bucket) if open => {
+                bucket.upper = (*value).clone();
+                bucket.count += count;
+                bucket.distinct += 1;
+            }
+            _ => buckets.push(Bucket { upper: (*value).clone(), count: *count, distinct: 1 }),
+        }
+        open = buckets.last().is_some_and(|bucket| bucket.count < depth);
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Vec<Value> {
+        (0..1000).map(|n| json!({"_id": n, "n": n, "status": if n % 100 == 0 { "rare" } else { "common" }, "tags": [1]}))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_describes_fields() {
+        let stats = CollectionStatistics::build(&sample(), 1000);
+        assert_eq!((stats.documents, stats.sampled), (1000, 1000));
+        assert!(!stats.fields.contains_key("_id"));
+
+        let n = &stats.fields["n"];
+        assert_eq!((n.present, n.nulls, n.distinct), (1000, 0, 1000));
+        assert!(n.histogram.len() <= HISTOGRAM_BUCKETS);
+        assert_eq!(n.histogram.iter().map(|bucket| bucket.count).sum::<u64>(), 1000);
+        assert_eq!(n.histogram.last().unwrap().upper, json!(999));
+
+        assert_eq!(stats.fields["status"].distinct, 2);
+        assert!(stats.fields["tags"].histogram.is_empty());
+    }
+
+    #[test]
+    fn test_selectivity_estimates() {
+        let stats = CollectionStatistics::build(&sample(), 1000);
+        let close = |estimate: Option<f64>, expected: f64| {
+            let estimate = estimate.unwrap();
+            assert!((estimate - expected).abs() < 0.05, "{} vs {}", estimate, expected);
+        };
+
+        close(stats.selectivity("n", &json!(5)), 0.001);
+        close(stats.selectivity("n", &json!({"$gte": 900})), 0.1);
+        close(stats.selectivity("n", &json!({"$gt": 100, "$lt": 300})), 0.2);
+        close(stats.selectivity("n", &json!({"$gt": "a"})), 0.0);
+        close(stats.selectivity("status", &json!("rare")), 0.01);
+        close(stats.selectivity("status", &json!({"$in": ["rare", "common"]})), 1.0);
+        assert_eq!(stats.selectivity("missing", &json!(1)), None);
+        assert_eq!(stats.selectivity("n", &json!({"$regex": "1"})), None);
+
+        // Twice the documents since: estimates scale
+        assert_eq!(stats.estimate(&json!({"status": "rare"}), 2000), 20);
+        assert_eq!(stats.estimate(&json!({"status": "rare", "n": {"$lt": 500}}), 1000), 5);
+    }
+
+    #[test]
+    fn test_distinct_scales_from_sample() {
+        let sample: Vec<Value> = (0..100).map(|n| json!({"unique": n, "flag": n % 2 == 0})).collect();
+        let stats = CollectionStatistics::build(&sample, 10_000);
+        assert_eq!(stats.fields["unique"].distinct, 1000);
+        assert_eq!(stats.fields["flag"].distinct, 2);
+    }
+}
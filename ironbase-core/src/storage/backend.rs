@@ -0,0 +1,126 @@
+// storage/backend.rs
+// Where the storage engine's files live: the file system, or a test double
+//
+// The data file, the WAL and the metadata journal are all opened through a
+// StorageBackend (see StorageConfig::with_backend). FileSystem is the real
+// thing; testing::SimulatedBackend keeps the files in memory and injects
+// faults. Backups, exports, replication and persisted index files always use
+// the file system.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+/// An open file of a StorageBackend, positioned like std::fs::File
+pub trait StorageFile: Read + Write + Seek + Send + Sync {
+    /// Current length in bytes
+    fn size(&self) -> io::Result<u64>;
+
+    /// Truncate or extend (with zeros) to `len` bytes
+    fn set_len(&self, len: u64) -> io::Result<()>;
+
+    /// Make the contents durable
+    fn sync_data(&self) -> io::Result<()>;
+
+    /// Make the contents and the length durable
+    fn sync_all(&self) -> io::Result<()>;
+
+    /// Another handle on the same file (with its own position)
+    fn try_clone(&self) -> io::Result<Box<dyn StorageFile>>;
+
+    /// Reserve disk space for the first `len` bytes without changing the
+    /// length, so appends up to there need not allocate blocks. A hint:
+    /// files that cannot do it do nothing
+    fn preallocate(&self, _len: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The OS file underneath, if there is one (memory mapping needs it)
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+/// Opens, replaces and removes the storage engine's files
+pub trait StorageBackend: fmt::Debug + Send + Sync {
+    /// Open `path` for reading and writing, creating it empty if missing
+    fn open(&self, path: &Path) -> io::Result<Box<dyn StorageFile>>;
+
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Atomically and durably replace `to` with `from` (see durable_fs::atomic_replace)
+    fn replace(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The operating system's file system (the default backend)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystem;
+
+impl StorageBackend for FileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn replace(&self, from: &Path, to: &Path) -> io::Result<()> {
+        crate::durable_fs::atomic_replace(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+}
+
+impl StorageFile for File {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn StorageFile>> {
+        Ok(Box::new(File::try_clone(self)?))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn preallocate(&self, len: u64) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+        let len = libc::off_t::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        // Safety: plain syscall on a descriptor this File owns
+        let result = unsafe { libc::fallocate(self.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, len) };
+        if result == 0 {
+            return Ok(());
+        }
+        match io::Error::last_os_error() {
+            // File systems without fallocate just allocate as they go
+            e if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(()),
+            e => Err(e),
+        }
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(self)
+    }
+}
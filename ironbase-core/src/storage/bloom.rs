@@ -0,0 +1,211 @@
+// storage/bloom.rs
+// Per-segment Bloom filters on unindexed fields
+//
+// The data file is cut into fixed-size segments. For each field a collection
+// lists in `CollectionMeta::bloom_fields`, every segment gets a Bloom filter
+// of the scalar values its records hold. An equality query on such a field
+// skips the segments whose filter says the value cannot be there - a cheap
+// middle ground between a full scan and an index.
+//
+// Filters are runtime only: they are built by the first query that can use
+// them (one pass over the collection's records) and kept up to date by
+// write_document. Superseded values stay in their segment's filter until the
+// filters are rebuilt, which only costs false positives.
+
+use std::collections::{HashMap, HashSet};
+use serde_json::Value;
+use crate::error::Result;
+use crate::index::IndexKey;
+use crate::query::{Query, QueryOperator};
+use super::StorageEngine;
+
+/// Bytes of the data file covered by one filter
+pub const BLOOM_SEGMENT_SIZE: u64 = 1 << 20;
+
+/// Bits per filter (8 KiB)
+const BLOOM_BITS: usize = 1 << 16;
+/// Bit positions set per value
+const BLOOM_HASHES: u64 = 4;
+
+/// Segment holding the record at `offset`
+pub fn bloom_segment(offset: u64) -> u64 {
+    offset / BLOOM_SEGMENT_SIZE
+}
+
+/// Fixed-size Bloom filter over JSON scalars
+#[derive(Clone)]
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new() -> Self {
+        BloomFilter { bits: vec![0; BLOOM_BITS / 64] }
+    }
+
+    /// Bit positions of a value (double hashing of its hashed index key,
+    /// so numbers that compare equal - 1 and 1.0 - set the same bits)
+    fn positions(value: &Value) -> impl Iterator<Item = usize> {
+        let IndexKey::Int(hash) = IndexKey::hashed(value) else {
+            unreachable!("hashed keys are integers")
+        };
+        let h1 = hash as u64;
+        let h2 = (h1.rotate_left(32) ^ 0x9e37_79b9_7f4a_7c15).wrapping_mul(0xbf58_476d_1ce4_e5b9) | 1;
+        (0..BLOOM_HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % BLOOM_BITS as u64) as usize)
+    }
+
+    fn insert(&mut self, value: &Value) {
+        for bit in Self::positions(value) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn may_contain(&self, value: &Value) -> bool {
+        Self::positions(value).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// Only scalars go into filters: equality never matches an array or object
+/// field against a scalar (see Query::matches_operator)
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+/// Filters of one collection, built for a given set of fields
+#[derive(Default)]
+pub(super) struct CollectionBlooms {
+    /// Sorted, to compare with the collection's current bloom_fields
+    fields: Vec<String>,
+    /// field -> segment -> filter; a segment without a filter holds no
+    /// scalar value of the field
+    filters: HashMap<String, HashMap<u64, BloomFilter>>,
+}
+
+impl CollectionBlooms {
+    fn new(mut fields: Vec<String>) -> Self {
+        fields.sort();
+        CollectionBlooms { fields, filters: HashMap::new() }
+    }
+
+    /// Add the scalar field values of the record at `offset`
+    fn add_record(&mut self, offset: u64, record: &Value) {
+        let segment = bloom_segment(offset);
+        for field in &self.fields {
+            if let Some(value) = record.get(field).filter(|value| is_scalar(value)) {
+                self.filters.entry(field.clone()).or_default()
+                    .entry(segment).or_insert_with(BloomFilter::new)
+                    .insert(value);
+            }
+        }
+    }
+
+    fn may_contain(&self, field: &str, segment: u64, value: &Value) -> bool {
+        self.filters.get(field)
+            .and_then(|segments| segments.get(&segment))
+            .is_some_and(|filter| filter.may_contain(value))
+    }
+}
+
+/// Values a bloom field must equal for a document to match: one list per
+/// constraint (top-level equality or $in on the field, also inside $and)
+fn equality_constraints<'q>(query: &'q Query, fields: &[String], constraints: &mut Vec<(&'q str, Vec<&'q Value>)>) {
+    for (field, operator) in &query.conditions {
+        match operator {
+            QueryOperator::And(queries) if field == "$and" => {
+                for query in queries {
+                    equality_constraints(query, fields, constraints);
+                }
+            }
+            _ if !fields.contains(field) => {}
+            QueryOperator::Eq(value) if is_scalar(value) => constraints.push((field, vec![value])),
+            QueryOperator::In(values) if values.iter().all(is_scalar) => {
+                constraints.push((field, values.iter().collect()))
+            }
+            _ => {}
+        }
+    }
+}
+
+impl StorageEngine {
+    /// Segments of the data file no document of `collection` matching
+    /// `query` can be in, going by the collection's Bloom filters (see
+    /// bloom.rs); empty if the query has no equality on a bloom field
+    ///
+    /// Builds the filters first if they are missing or the collection's
+    /// bloom fields changed since.
+    pub fn bloom_skipped_segments(&mut self, collection: &str, query: &Query) -> Result<HashSet<u64>> {
+        let Some(meta) = self.collections.get(collection) else {
+            return Ok(HashSet::new());
+        };
+        let mut constraints = Vec::new();
+        equality_constraints(query, &meta.bloom_fields, &mut constraints);
+        if constraints.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut segments: Vec<u64> = meta.document_catalog.values().map(|&offset| bloom_segment(offset)).collect();
+        segments.sort_unstable();
+        segments.dedup();
+        self.build_blooms(collection)?;
+
+        let Some(blooms) = self.blooms.get(collection) else {
+            return Ok(HashSet::new());
+        };
+        Ok(segments.into_iter()
+            .filter(|&segment| constraints.iter().any(|(field, values)| {
+                !values.iter().any(|value| blooms.may_contain(field, segment, value))
+            }))
+            .collect())
+    }
+
+    /// Build the filters of `collection` unless they match its bloom fields
+    fn build_blooms(&mut self, collection: &str) -> Result<()> {
+        let Some(meta) = self.collections.get(collection) else {
+            return Ok(());
+        };
+        let mut blooms = CollectionBlooms::new(meta.bloom_fields.clone());
+        if self.blooms.get(collection).is_some_and(|built| built.fields == blooms.fields) {
+            return Ok(());
+        }
+
+        let fields: HashSet<String> = blooms.fields.iter().cloned().collect();
+        let offsets: Vec<u64> = meta.document_catalog.values().copied().collect();
+        for offset in offsets {
+            // Unreadable records are skipped, as scans skip them
+            let Ok(bytes) = self.read_data(offset) else {
+                continue;
+            };
+            if let Ok(record) = crate::find_options::decode_fields(&bytes, &fields) {
+                blooms.add_record(offset, &record);
+            }
+        }
+        self.blooms.insert(collection.to_string(), blooms);
+        Ok(())
+    }
+
+    /// Keep built filters current with a record just written (see write_document)
+    pub(super) fn bloom_record(&mut self, collection: &str, offset: u64, data: &[u8]) {
+        let Some(blooms) = self.blooms.get_mut(collection) else {
+            return;
+        };
+        let fields: HashSet<String> = blooms.fields.iter().cloned().collect();
+        match crate::find_options::decode_fields(data, &fields) {
+            Ok(record) => blooms.add_record(offset, &record),
+            // Not a JSON object: the next query rebuilds the filters
+            Err(_) => {
+                self.blooms.remove(collection);
+            }
+        }
+    }
+
+    /// Forget the built filters of `collection`, or of every collection
+    /// (records moved); the next query rebuilds them
+    pub(super) fn drop_blooms(&mut self, collection: Option<&str>) {
+        match collection {
+            Some(collection) => {
+                self.blooms.remove(collection);
+            }
+            None => self.blooms.clear(),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+// storage/catalog.rs
+// Document catalog persisted as a paged hash of shard records
+//
+// Ids are spread over a power-of-two number of shards by a stable hash. Each
+// shard is one record in the data file (`{"_catalog":..,"shard":..,"entries":..}`,
+// no `_collection`, so document scanners skip it) and the collection metadata
+// only holds the shard offsets. A flush rewrites the shards whose entries
+// changed and releases their old records to the free-space map, so the
+// metadata region stays small and the cost of a flush follows the number of
+// changed shards, not the size of the catalog.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::SeekFrom;
+use serde::{Serialize, Deserialize};
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use super::{StorageEngine, StorageFile};
+
+/// Entries per shard before the shard count doubles
+pub const CATALOG_SHARD_CAPACITY: usize = 4096;
+
+/// Document catalog of a collection: DocumentId -> file offset
+#[derive(Debug, Clone)]
+pub struct DocumentCatalog {
+    shards: Vec<HashMap<DocumentId, u64>>,
+    /// (offset, size) of each shard's record, (0, 0) if it has none
+    records: Vec<(u64, u64)>,
+    /// Shards changed since their record was written
+    dirty: BTreeSet<usize>,
+    len: usize,
+}
+
+impl Default for DocumentCatalog {
+    fn default() -> Self {
+        DocumentCatalog {
+            shards: vec![HashMap::new()],
+            records: vec![(0, 0)],
+            dirty: BTreeSet::new(),
+            len: 0,
+        }
+    }
+}
+
+/// Shards to rewrite: (shard, record payload), None for a shard now empty
+type ShardPayloads = Vec<(usize, Option<Vec<u8>>)>;
+
+/// Shard record payload
+#[derive(Serialize, Deserialize)]
+struct ShardRecord {
+    #[serde(rename = "_catalog")]
+    collection: String,
+    shard: usize,
+    entries: Vec<(String, String, u64)>,
+}
+
+/// FNV-1a over the id's type tag and value: shard membership must not change
+/// between runs, so the std hasher (randomly seeded) will not do
+fn stable_hash(doc_id: &DocumentId) -> u64 {
+    let (tag, bytes): (u8, std::borrow::Cow<[u8]>) = match doc_id {
+        DocumentId::Int(i) => (b'i', i.to_le_bytes().to_vec().into()),
+        DocumentId::String(s) => (b's', s.as_bytes().into()),
+        DocumentId::ObjectId(oid) => (b'o', oid.as_bytes().into()),
+    };
+    std::iter::once(tag).chain(bytes.iter().copied()).fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Shard count for `len` entries (a power of two)
+fn shard_count_for(len: usize) -> usize {
+    len.div_ceil(CATALOG_SHARD_CAPACITY).max(1).next_power_of_two()
+}
+
+impl DocumentCatalog {
+    /// Catalog whose shard records are at `records`; entries are filled in by
+    /// `load_shards`
+    pub(crate) fn with_records(records: Vec<(u64, u64)>) -> Self {
+        let count = records.len().max(1).next_power_of_two();
+        let mut catalog = DocumentCatalog {
+            shards: vec![HashMap::new(); count],
+            records,
+            dirty: BTreeSet::new(),
+            len: 0,
+        };
+        // A shard count that is not a power of two is rewritten from scratch
+        if catalog.records.len() != count {
+            catalog.dirty.extend(0..count);
+        }
+        catalog.records.resize(count, (0, 0));
+        catalog
+    }
+
+    fn shard_of(&self, doc_id: &DocumentId) -> usize {
+        (stable_hash(doc_id) as usize) & (self.shards.len() - 1)
+    }
+
+    pub fn get(&self, doc_id: &DocumentId) -> Option<&u64> {
+        self.shards[self.shard_of(doc_id)].get(doc_id)
+    }
+
+    pub fn contains_key(&self, doc_id: &DocumentId) -> bool {
+        self.get(doc_id).is_some()
+    }
+
+    pub fn insert(&mut self, doc_id: DocumentId, offset: u64) -> Option<u64> {
+        let shard = self.shard_of(&doc_id);
+        self.dirty.insert(shard);
+        let previous = self.shards[shard].insert(doc_id, offset);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    pub fn remove(&mut self, doc_id: &DocumentId) -> Option<u64> {
+        let shard = self.shard_of(doc_id);
+        let removed = self.shards[shard].remove(doc_id);
+        if removed.is_some() {
+            self.dirty.insert(shard);
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        for shard in &mut self.shards {
+            shard.clear();
+        }
+        self.dirty.extend(0..self.shards.len());
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&DocumentId, &u64)> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &DocumentId> {
+        self.iter().map(|(doc_id, _)| doc_id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &u64> {
+        self.iter().map(|(_, offset)| offset)
+    }
+
+    pub fn into_keys(self) -> impl Iterator<Item = DocumentId> {
+        self.shards.into_iter().flat_map(|shard| shard.into_keys())
+    }
+
+    /// Number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// (offset, size) of each shard's record in the data file
+    pub fn shard_records(&self) -> &[(u64, u64)] {
+        &self.records
+    }
+
+    /// True if some shard changed since its record was written
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// Payloads of the shards to rewrite (None for a shard now empty), and
+    /// the records no longer used by any shard
+    ///
+    /// Re-shards first when the catalog outgrew its shards (or shrank to a
+    /// quarter of them) - every shard is rewritten then.
+    pub(crate) fn take_dirty_shards(&mut self, collection: &str) -> Result<(ShardPayloads, Vec<(u64, u64)>)> {
+        let mut released = Vec::new();
+        let wanted = shard_count_for(self.len);
+        if wanted > self.shards.len() || wanted * 4 <= self.shards.len() {
+            let entries: Vec<(DocumentId, u64)> = self.shards.drain(..).flatten().collect();
+            released.extend(self.records.drain(..).filter(|&(offset, _)| offset != 0));
+            self.shards = vec![HashMap::new(); wanted];
+            self.records = vec![(0, 0); wanted];
+            for (doc_id, offset) in entries {
+                let shard = self.shard_of(&doc_id);
+                self.shards[shard].insert(doc_id, offset);
+            }
+            self.dirty = (0..wanted).collect();
+        }
+
+        let mut payloads = Vec::with_capacity(self.dirty.len());
+        for shard in std::mem::take(&mut self.dirty) {
+            let entries = &self.shards[shard];
+            if entries.is_empty() {
+                payloads.push((shard, None));
+                continue;
+            }
+            let record = ShardRecord {
+                collection: collection.to_string(),
+                shard,
+                entries: entries.iter().map(|(doc_id, &offset)| crate::catalog_serde::encode_entry(doc_id, offset)).collect(),
+            };
+            payloads.push((shard, Some(serde_json::to_vec(&record)?)));
+        }
+        Ok((payloads, released))
+    }
+
+    /// Point `shard` at its new record; returns the one it replaces
+    pub(crate) fn set_shard_record(&mut self, shard: usize, record: (u64, u64)) -> (u64, u64) {
+        std::mem::replace(&mut self.records[shard], record)
+    }
+
+    /// Fill the shards in from their records in `file`
+    fn load_shards(&mut self, collection: &str, file: &mut dyn StorageFile) -> Result<()> {
+        for shard in 0..self.records.len() {
+            let (offset, _) = self.records[shard];
+            if offset == 0 {
+                continue;
+            }
+
+            file.seek(SeekFrom::Start(offset))?;
+            let mut len_bytes = [0u8; 4];
+            file.read_exact(&mut len_bytes)?;
+            let mut payload = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+            file.read_exact(&mut payload)?;
+
+            let record: ShardRecord = serde_json::from_slice(&payload)
+                .map_err(|e| MongoLiteError::Corruption(format!("catalog shard {} of '{}': {}", shard, collection, e)))?;
+            if record.collection != collection || record.shard != shard {
+                return Err(MongoLiteError::Corruption(format!(
+                    "catalog shard {} of '{}' points at shard {} of '{}'",
+                    shard, collection, record.shard, record.collection
+                )));
+            }
+
+            for (tag, value, doc_offset) in record.entries {
+                let doc_id = crate::catalog_serde::decode_entry(&tag, value)
+                    .map_err(MongoLiteError::Corruption)?;
+                // Loading does not make a shard dirty - unless an entry is
+                // in the wrong one, then both are rewritten
+                let target = self.shard_of(&doc_id);
+                if target != shard {
+                    self.dirty.insert(target);
+                    self.dirty.insert(shard);
+                }
+                if self.shards[target].insert(doc_id, doc_offset).is_none() {
+                    self.len += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::Index<&DocumentId> for DocumentCatalog {
+    type Output = u64;
+
+    fn index(&self, doc_id: &DocumentId) -> &u64 {
+        self.get(doc_id).expect("no entry for document id")
+    }
+}
+
+impl<'a> IntoIterator for &'a DocumentCatalog {
+    type Item = (&'a DocumentId, &'a u64);
+    type IntoIter = Box<dyn Iterator<Item = (&'a DocumentId, &'a u64)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl FromIterator<(DocumentId, u64)> for DocumentCatalog {
+    /// A catalog with every shard dirty (nothing written yet)
+    fn from_iter<I: IntoIterator<Item = (DocumentId, u64)>>(entries: I) -> Self {
+        let mut catalog = DocumentCatalog::default();
+        for (doc_id, offset) in entries {
+            catalog.insert(doc_id, offset);
+        }
+        let wanted = shard_count_for(catalog.len);
+        if wanted > catalog.shards.len() {
+            // Re-shard now rather than on the first flush
+            let entries: Vec<(DocumentId, u64)> = catalog.shards.drain(..).flatten().collect();
+            catalog.shards = vec![HashMap::new(); wanted];
+            catalog.records = vec![(0, 0); wanted];
+            for (doc_id, offset) in entries {
+                let shard = catalog.shard_of(&doc_id);
+                catalog.shards[shard].insert(doc_id, offset);
+            }
+        }
+        catalog.dirty = (0..catalog.shards.len()).collect();
+        catalog
+    }
+}
+
+impl StorageEngine {
+    /// Write the changed catalog shards of every collection
+    ///
+    /// Runs before the metadata is written: the replaced records are released
+    /// and only stamped free once metadata pointing at the new ones is on disk.
+    pub(super) fn write_catalog_shards(&mut self) -> Result<()> {
+        let changed: Vec<String> = self.collections.iter()
+            .filter(|(_, meta)| meta.document_catalog.is_dirty())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in changed {
+            let meta = self.get_collection_meta_mut(&name).expect("collection exists");
+            let (payloads, released) = meta.document_catalog.take_dirty_shards(&name)?;
+            for (offset, size) in released {
+                self.free_space.release_unowned(offset, size);
+            }
+
+            for (shard, payload) in payloads {
+                let record = match payload {
+                    Some(payload) => self.place_record(&payload)?,
+                    None => (0, 0),
+                };
+                let meta = self.get_collection_meta_mut(&name).expect("collection exists");
+                let (offset, size) = meta.document_catalog.set_shard_record(shard, record);
+                if offset != 0 {
+                    self.free_space.release_unowned(offset, size);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read every collection's catalog shards (after load_metadata parsed the metadata)
+    pub(super) fn load_catalogs(file: &mut dyn StorageFile, collections: &mut HashMap<String, super::CollectionMeta>) -> Result<()> {
+        for (name, meta) in collections.iter_mut() {
+            meta.document_catalog.load_shards(name, file)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_hash_is_fixed() {
+        // Shard membership is persisted - the hash must never change
+        assert_eq!(stable_hash(&DocumentId::Int(1)), stable_hash(&DocumentId::Int(1)));
+        assert_ne!(stable_hash(&DocumentId::Int(1)), stable_hash(&DocumentId::String("1".into())));
+        assert_eq!(stable_hash(&DocumentId::String(String::new())), 0xaf63ee4c86020b22);
+    }
+
+    #[test]
+    fn test_only_changed_shards_are_rewritten() {
+        let mut catalog: DocumentCatalog = (0..CATALOG_SHARD_CAPACITY as i64 * 3)
+            .map(|i| (DocumentId::Int(i), 1000 + i as u64))
+            .collect();
+        assert_eq!(catalog.shard_count(), 4);
+
+        let (payloads, released) = catalog.take_dirty_shards("items").unwrap();
+        assert_eq!(payloads.len(), 4);
+        assert!(released.is_empty());
+        for (shard, _) in payloads {
+            catalog.set_shard_record(shard, (5000 + shard as u64, 10));
+        }
+        assert!(!catalog.is_dirty());
+
+        catalog.insert(DocumentId::Int(7), 1);
+        catalog.remove(&DocumentId::Int(8));
+        let (payloads, _) = catalog.take_dirty_shards("items").unwrap();
+        let mut shards: Vec<usize> = payloads.iter().map(|(shard, _)| *shard).collect();
+        shards.dedup();
+        let mut expected = vec![catalog.shard_of(&DocumentId::Int(7)), catalog.shard_of(&DocumentId::Int(8))];
+        expected.sort();
+        expected.dedup();
+        assert_eq!(shards, expected);
+        assert_eq!(catalog.len(), CATALOG_SHARD_CAPACITY * 3 - 1);
+        assert_eq!(catalog[&DocumentId::Int(7)], 1);
+    }
+
+    #[test]
+    fn test_reshard_releases_old_records() {
+        let mut catalog = DocumentCatalog::default();
+        catalog.insert(DocumentId::Int(1), 100);
+        let (payloads, _) = catalog.take_dirty_shards("items").unwrap();
+        assert_eq!(payloads.len(), 1);
+        catalog.set_shard_record(0, (5000, 40));
+
+        for i in 0..CATALOG_SHARD_CAPACITY as i64 {
+            catalog.insert(DocumentId::Int(i + 2), 100);
+        }
+        let (payloads, released) = catalog.take_dirty_shards("items").unwrap();
+        assert_eq!(catalog.shard_count(), 2);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(released, vec![(5000, 40)]);
+
+        // Shrinking back takes dropping to a quarter
+        catalog.clear();
+        let (payloads, _) = catalog.take_dirty_shards("items").unwrap();
+        assert_eq!(catalog.shard_count(), 2);
+        assert!(payloads.iter().all(|(_, payload)| payload.is_none()));
+    }
+}
@@ -2,10 +2,11 @@
 // Storage compaction functionality
 
 use std::collections::HashMap;
-use std::fs::{self, OpenOptions};
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{IoSlice, Seek, SeekFrom, Write};
 use serde_json::Value;
-use crate::error::{Result};
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use super::free_space::MIN_FREE_REGION;
 use super::StorageEngine;
 
 /// Compaction configuration
@@ -32,9 +33,43 @@ pub struct CompactionStats {
     pub documents_kept: u64,
     pub tombstones_removed: u64,
     pub peak_memory_mb: u64,  // Peak memory usage during compaction
+    /// Breakdown by collection (only the compacted one for compact_collection)
+    pub collections: HashMap<String, CollectionCompactionStats>,
+}
+
+/// Compaction statistics of one collection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectionCompactionStats {
+    pub documents_scanned: u64,
+    pub documents_kept: u64,
+    pub tombstones_removed: u64,
+    /// Dead record bytes removed or released for reuse
+    pub bytes_reclaimed: u64,
 }
 
 impl CompactionStats {
+    pub fn to_json(&self) -> serde_json::Value {
+        let collections: serde_json::Map<String, serde_json::Value> = self.collections.iter()
+            .map(|(name, stats)| (name.clone(), serde_json::json!({
+                "documents_scanned": stats.documents_scanned,
+                "documents_kept": stats.documents_kept,
+                "tombstones_removed": stats.tombstones_removed,
+                "bytes_reclaimed": stats.bytes_reclaimed,
+            })))
+            .collect();
+        serde_json::json!({
+            "size_before": self.size_before,
+            "size_after": self.size_after,
+            "space_saved": self.space_saved(),
+            "documents_scanned": self.documents_scanned,
+            "documents_kept": self.documents_kept,
+            "tombstones_removed": self.tombstones_removed,
+            "peak_memory_mb": self.peak_memory_mb,
+            "compression_ratio": self.compression_ratio(),
+            "collections": collections,
+        })
+    }
+
     pub fn space_saved(&self) -> u64 {
         self.size_before.saturating_sub(self.size_after)
     }
@@ -57,23 +92,31 @@ impl StorageEngine {
 
     /// Storage compaction with custom configuration
     pub fn compact_with_config(&mut self, config: &CompactionConfig) -> Result<CompactionStats> {
+        if self.dropped {
+            return Err(MongoLiteError::InvalidConfig("cannot compact a dropped database".to_string()));
+        }
+        self.flush_writes()?;
         let temp_path = format!("{}.compact", self.file_path);
         let mut stats = CompactionStats::default();
 
+        // Versions older than the oldest open snapshot are dropped; the ones
+        // still visible to a snapshot move out of the file being rewritten
+        self.inline_versions()?;
+
+        // The compacted file is dense - no free regions to carry over
+        let mut header = self.header.clone();
+        header.free_list_head = 0;
+
         // Get current file size
-        stats.size_before = self.file.metadata()?.len();
+        stats.size_before = self.file.size()?;
 
         // Clone collections to avoid borrow conflicts
         let collections_snapshot = self.collections.clone();
         let file_len = self.file_len()?;
 
         // Create temporary new file
-        let mut new_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&temp_path)?;
+        let mut new_file = self.backend.open(temp_path.as_ref())?;
+        new_file.set_len(0)?;
 
         // Prepare new collections metadata
         let mut new_collections = self.collections.clone();
@@ -85,7 +128,7 @@ impl StorageEngine {
 
         // Write placeholder metadata
         new_file.seek(SeekFrom::Start(0))?;
-        Self::write_metadata(&mut new_file, &self.header, &new_collections)?;
+        Self::write_metadata(&mut *new_file, &header, &new_collections)?;
 
         // Write documents starting at DATA_START_OFFSET
         new_file.seek(SeekFrom::Start(super::DATA_START_OFFSET))?;
@@ -97,6 +140,7 @@ impl StorageEngine {
             let mut docs_by_id: HashMap<crate::document::DocumentId, Value> = HashMap::new();
             let mut current_offset = coll_meta.data_offset;
             let mut chunk_count = 0;
+            stats.collections.entry(coll_name.clone()).or_default();
             // Scan all documents in this collection with chunked processing
             while current_offset < file_len {
                 match self.read_data(current_offset) {
@@ -110,7 +154,11 @@ impl StorageEngine {
                                 .unwrap_or("");
 
                             if doc_collection == coll_name {
-                                if let Some(id_value) = doc.get("_id") {
+                                let coll_stats = stats.collections.entry(coll_name.clone()).or_default();
+                                coll_stats.documents_scanned += 1;
+                                coll_stats.bytes_reclaimed += 4 + doc_bytes.len() as u64;
+
+                                if let Some(id_value) = doc.get("_id").filter(|id| !coll_meta.is_superseded(id, current_offset)) {
                                     // Deserialize directly to DocumentId
                                     if let Ok(doc_id) = serde_json::from_value::<crate::document::DocumentId>(id_value.clone()) {
                                         // Track memory usage (estimate: document size + HashMap overhead)
@@ -127,7 +175,7 @@ impl StorageEngine {
                                         // If chunk is full, flush non-tombstones to new file
                                         if chunk_count >= config.chunk_size {
                                             write_offset = self.flush_compaction_chunk(
-                                                &mut new_file,
+                                                &mut *new_file,
                                                 &mut new_collections,
                                                 coll_name,
                                                 &mut docs_by_id,
@@ -151,7 +199,7 @@ impl StorageEngine {
             // Flush remaining documents in the final chunk
             if !docs_by_id.is_empty() {
                 write_offset = self.flush_compaction_chunk(
-                    &mut new_file,
+                    &mut *new_file,
                     &mut new_collections,
                     coll_name,
                     &mut docs_by_id,
@@ -161,39 +209,66 @@ impl StorageEngine {
             }
         }
 
+        // Catalog shards go after the documents (the old ones were in the old file)
+        for (coll_name, coll_meta) in new_collections.iter_mut() {
+            let (payloads, _) = coll_meta.document_catalog.take_dirty_shards(coll_name)?;
+            for (shard, payload) in payloads {
+                let record = match payload {
+                    Some(payload) => {
+                        new_file.write_all(&(payload.len() as u32).to_le_bytes())?;
+                        new_file.write_all(&payload)?;
+                        let record = (write_offset, 4 + payload.len() as u64);
+                        write_offset += record.1;
+                        record
+                    }
+                    None => (0, 0),
+                };
+                coll_meta.document_catalog.set_shard_record(shard, record);
+            }
+        }
+
         new_file.sync_all()?;
 
         // Now rewrite metadata with the populated document_catalog
         new_file.seek(SeekFrom::Start(0))?;
-        Self::write_metadata(&mut new_file, &self.header, &new_collections)?;
+        Self::write_metadata(&mut new_file, &header, &new_collections)?;
         new_file.sync_all()?;
 
         // Get new file size
-        stats.size_after = new_file.metadata()?.len();
+        stats.size_after = new_file.size()?;
 
-        // Close new file before renaming
-        drop(new_file);
-
-        // Close old file and mmap
+        // Close old file and mmap, keeping the handles on the new file: on
+        // Windows the old file cannot be replaced while it is still open
         drop(self.mmap.take());
+        let old_file = std::mem::replace(&mut self.file, new_file);
+        self.data_sync = self.journal.group_sync(&*self.file, self.sync_thread.as_ref())?;
+        drop(old_file);
 
         // Replace old file with new file
-        fs::rename(&temp_path, &self.file_path)?;
-
-        // Reopen the compacted file
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&self.file_path)?;
+        if let Err(e) = self.backend.replace(temp_path.as_ref(), self.file_path.as_ref()) {
+            // The original file is untouched - go back to it
+            self.file = self.backend.open(self.file_path.as_ref())?;
+            self.data_sync = self.journal.group_sync(&*self.file, self.sync_thread.as_ref())?;
+            let _ = self.backend.remove(temp_path.as_ref());
+            return Err(e.into());
+        }
+        // The new file's metadata is complete; the journal points into the old one
+        self.clear_journal()?;
 
         // Reload metadata
-        let (header, collections) = Self::load_metadata(&mut file)?;
+        let (header, collections, layout) = Self::load_metadata(&mut *self.file)?;
 
         // Update self
-        self.file = file;
         self.header = header;
         self.collections = collections;
-        self.mmap = None; // Reset mmap
+        self.layout = layout;
+        self.remap();
+        self.write_buffer.forget_preallocation();
+        self.free_space.clear();
+        self.drop_blooms(None);
+
+        // Old versions and tombstones are gone - count what is left
+        self.recount_garbage()?;
 
         Ok(stats)
     }
@@ -201,17 +276,22 @@ impl StorageEngine {
     /// Helper function to flush a chunk of documents to the compacted file
     fn flush_compaction_chunk(
         &self,
-        new_file: &mut std::fs::File,
+        new_file: &mut dyn super::StorageFile,
         new_collections: &mut HashMap<String, super::CollectionMeta>,
         coll_name: &str,
         docs_by_id: &mut HashMap<crate::document::DocumentId, Value>,
         mut write_offset: u64,
         stats: &mut CompactionStats,
     ) -> Result<u64> {
+        let mut coll_stats = stats.collections.remove(coll_name).unwrap_or_default();
+        // The chunk goes out in as few writes as the file takes: length
+        // prefixes and documents side by side, not copied together
+        let mut records: Vec<([u8; 4], Vec<u8>)> = Vec::new();
         for (doc_id, doc) in docs_by_id.iter() {
             // Skip tombstones (deleted documents)
             if doc.get("_tombstone").and_then(|v| v.as_bool()).unwrap_or(false) {
                 stats.tombstones_removed += 1;
+                coll_stats.tombstones_removed += 1;
                 continue;
             }
 
@@ -220,11 +300,11 @@ impl StorageEngine {
             let doc_bytes = serde_json::to_vec(&doc)?;
             let len = doc_bytes.len() as u32;
 
-            new_file.write_all(&len.to_le_bytes())?;
-            new_file.write_all(&doc_bytes)?;
-
             write_offset += 4 + doc_bytes.len() as u64;
             stats.documents_kept += 1;
+            coll_stats.documents_kept += 1;
+            coll_stats.bytes_reclaimed = coll_stats.bytes_reclaimed.saturating_sub(4 + doc_bytes.len() as u64);
+            records.push((len.to_le_bytes(), doc_bytes));
 
             // Update document_catalog and document_count
             if let Some(coll_meta) = new_collections.get_mut(coll_name) {
@@ -232,7 +312,129 @@ impl StorageEngine {
                 coll_meta.document_count += 1;
             }
         }
+        let mut slices: Vec<IoSlice> = records.iter()
+            .flat_map(|(len, doc_bytes)| [IoSlice::new(len), IoSlice::new(doc_bytes)])
+            .collect();
+        Self::write_all_vectored(new_file, &mut slices)?;
 
+        stats.collections.insert(coll_name.to_string(), coll_stats);
         Ok(write_offset)
     }
+
+    /// Compact a single collection in place
+    ///
+    /// The collection's dead records (old versions, tombstones) become free
+    /// regions that later writes reuse. Live records stay where they are and
+    /// no other collection is rewritten, so a large, rarely touched
+    /// collection costs nothing when a small, hot one is compacted. The file
+    /// only shrinks by the free space left at its end - compact() rewrites it
+    /// densely.
+    pub fn compact_collection(&mut self, collection: &str) -> Result<CompactionStats> {
+        let catalog = self.get_collection_meta(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?
+            .document_catalog
+            .clone();
+
+        // Settle earlier releases: afterwards every record of the collection
+        // that is not stamped free is either live, pinned or dead
+        self.prune_versions();
+        self.flush()?;
+
+        let mut stats = CompactionStats {
+            size_before: self.file_len()?,
+            ..Default::default()
+        };
+        let pinned = self.pinned_records(collection);
+        let mut coll_stats = CollectionCompactionStats::default();
+
+        // Every record of the collection by _id, in file order - only its
+        // extents hold any (see extents.rs)
+        let mut records: HashMap<DocumentId, Vec<(u64, u64, bool)>> = HashMap::new();
+        self.for_each_record_of(collection, |offset, data| {
+            let doc: Value = match serde_json::from_slice(data) {
+                Ok(doc) => doc,
+                Err(_) => return Ok(()),
+            };
+            if doc.get("_collection").and_then(|c| c.as_str()) != Some(collection) {
+                return Ok(());
+            }
+            coll_stats.documents_scanned += 1;
+
+            let tombstone = doc.get("_tombstone").and_then(|t| t.as_bool()).unwrap_or(false);
+            match doc.get("_id").and_then(|id| serde_json::from_value::<DocumentId>(id.clone()).ok()) {
+                Some(doc_id) => records.entry(doc_id).or_default().push((offset, 4 + data.len() as u64, tombstone)),
+                None => coll_stats.documents_kept += 1,
+            }
+            Ok(())
+        })?;
+
+        // (offset, size, tombstone) of the records to release
+        let mut dead = Vec::new();
+        let mut deleted = Vec::new();
+        for (doc_id, mut versions) in records {
+            // The catalog names the current record; ids outside it (raw
+            // writes) go by file order like in compact()
+            let current = match catalog.get(&doc_id) {
+                Some(&offset) => versions.iter().position(|&(record, _, _)| record == offset),
+                None => Some(versions.len() - 1),
+            };
+            let current = match current {
+                Some(index) => versions.remove(index),
+                None => {
+                    dead.extend(versions);
+                    continue;
+                }
+            };
+
+            let (offset, _, tombstone) = current;
+            if !tombstone {
+                coll_stats.documents_kept += 1;
+            } else if versions.iter().all(|&(record, size, _)| record < offset && size >= MIN_FREE_REGION && !pinned.contains(&record))
+                && !pinned.contains(&offset)
+            {
+                // Regions are stamped in offset order, so a crash part-way
+                // never leaves an old version behind without its tombstone
+                dead.push(current);
+                deleted.push(doc_id);
+            }
+            dead.extend(versions.into_iter().filter(|(record, _, _)| !pinned.contains(record)));
+        }
+
+        // A deleted document leaves the catalog along with its tombstone
+        if let Some(meta) = self.get_collection_meta_mut(collection) {
+            for doc_id in &deleted {
+                meta.document_catalog.remove(doc_id);
+            }
+        }
+
+        for (offset, size, tombstone) in dead {
+            if size < MIN_FREE_REGION {
+                continue;
+            }
+            self.free_space.release(collection, offset, size, tombstone);
+            coll_stats.bytes_reclaimed += size;
+            if tombstone {
+                coll_stats.tombstones_removed += 1;
+            }
+        }
+        self.flush()?;
+
+        // Free space at the end of the file is given back
+        if let Some(len) = self.free_space.trim_tail(self.file_len()?) {
+            drop(self.mmap.take());
+            self.file.set_len(len)?;
+            self.clip_extents(len);
+            self.remap();
+            self.write_buffer.forget_preallocation();
+            self.layout.unsynced = true;
+            self.reclaim_free_space()?;
+        }
+
+        stats.size_after = self.file_len()?;
+        stats.documents_scanned = coll_stats.documents_scanned;
+        stats.documents_kept = coll_stats.documents_kept;
+        stats.tombstones_removed = coll_stats.tombstones_removed;
+        stats.collections.insert(collection.to_string(), coll_stats);
+        Ok(stats)
+    }
 }
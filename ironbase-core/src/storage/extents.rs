@@ -0,0 +1,159 @@
+// storage/extents.rs
+// Per-collection extents: the parts of the data file a collection's records are in
+//
+// Every record of a collection - current, superseded or tombstone - lies in
+// one of its extents: sorted, non-overlapping offset ranges kept in
+// CollectionMeta::extents. Walks over one collection's records read only
+// those ranges instead of the whole data region, so a huge collection does
+// not slow down the small ones next to it.
+//
+// An extent starts at a record boundary and may cover records of other
+// collections in between. Extents closest to each other are merged to keep
+// the list short, and a free region merged over an extent's start moves the
+// start back to its own (the old start is no longer a record boundary).
+
+use crate::error::Result;
+use super::StorageEngine;
+
+/// Most extents kept per collection
+pub const MAX_EXTENTS: usize = 16;
+
+/// Add `[start, end)` to a sorted extent list
+pub(super) fn add_extent(extents: &mut Vec<(u64, u64)>, start: u64, end: u64) {
+    // Appends extend the last extent
+    if let Some(last) = extents.last_mut() {
+        if last.1 == start {
+            last.1 = end;
+            return;
+        }
+    }
+    let at = extents.partition_point(|&(existing, _)| existing < start);
+    extents.insert(at, (start, end));
+    normalize(extents);
+}
+
+/// Merge overlapping and touching extents, then the closest ones while
+/// there are more than MAX_EXTENTS
+fn normalize(extents: &mut Vec<(u64, u64)>) {
+    extents.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(extents.len());
+    for &(start, end) in extents.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    while merged.len() > MAX_EXTENTS {
+        let closest = (0..merged.len() - 1)
+            .min_by_key(|&i| merged[i + 1].0 - merged[i].1)
+            .expect("more than one extent");
+        merged[closest].1 = merged[closest + 1].1;
+        merged.remove(closest + 1);
+    }
+    *extents = merged;
+}
+
+impl StorageEngine {
+    /// Record that `collection` has a record at `[offset, offset + size)`
+    pub(super) fn extend_extents(&mut self, collection: &str, offset: u64, size: u64) {
+        if let Some(extents) = self.get_collection_meta_mut(collection).and_then(|meta| meta.extents.as_mut()) {
+            add_extent(extents, offset, offset + size);
+        }
+    }
+
+    /// Move extent starts inside `regions` (free regions about to be stamped,
+    /// sorted by offset) back to the region's start
+    pub(super) fn widen_extents(&mut self, regions: &[(u64, u64)]) {
+        if regions.is_empty() {
+            return;
+        }
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        for name in names {
+            let Some(extents) = self.collections[&name].extents.as_ref() else {
+                continue;
+            };
+            let mut widened = extents.clone();
+            for extent in widened.iter_mut() {
+                let at = regions.partition_point(|&(offset, _)| offset < extent.0);
+                if let Some(&(offset, size)) = at.checked_sub(1).map(|i| &regions[i]) {
+                    if extent.0 < offset + size {
+                        extent.0 = offset;
+                    }
+                }
+            }
+            if &widened != extents {
+                normalize(&mut widened);
+                if let Some(meta) = self.get_collection_meta_mut(&name) {
+                    meta.extents = Some(widened);
+                }
+            }
+        }
+    }
+
+    /// Cut every extent at `len` (the data file was truncated)
+    pub(super) fn clip_extents(&mut self, len: u64) {
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        for name in names {
+            let beyond = self.collections[&name].extents.as_ref()
+                .is_some_and(|extents| extents.last().is_some_and(|&(_, end)| end > len));
+            if !beyond {
+                continue;
+            }
+            if let Some(extents) = self.get_collection_meta_mut(&name).and_then(|meta| meta.extents.as_mut()) {
+                extents.retain(|&(start, _)| start < len);
+                for extent in extents.iter_mut() {
+                    extent.1 = extent.1.min(len);
+                }
+            }
+        }
+    }
+
+    /// Extents of `collection`, finding them first in files written before
+    /// they were kept (see recount_garbage)
+    pub fn collection_extents(&mut self, collection: &str) -> Result<Vec<(u64, u64)>> {
+        if self.collections.get(collection).is_some_and(|meta| meta.extents.is_none()) {
+            self.recount_garbage()?;
+        }
+        Ok(self.collections.get(collection)
+            .and_then(|meta| meta.extents.clone())
+            .unwrap_or_default())
+    }
+
+    /// for_each_record() over the extents of `collection` only
+    ///
+    /// Records of other collections inside the extents are passed on too;
+    /// callers still check `_collection`.
+    pub fn for_each_record_of<F>(&mut self, collection: &str, mut f: F) -> Result<()>
+    where
+        F: FnMut(u64, &[u8]) -> Result<()>,
+    {
+        for (start, end) in self.collection_extents(collection)? {
+            self.for_each_record_between(start, end, &mut f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_extent_merges_and_caps() {
+        let mut extents = Vec::new();
+        add_extent(&mut extents, 100, 200);
+        add_extent(&mut extents, 200, 300);
+        assert_eq!(extents, vec![(100, 300)]);
+
+        add_extent(&mut extents, 50, 60);
+        add_extent(&mut extents, 60, 100);
+        assert_eq!(extents, vec![(50, 300)]);
+
+        for i in 0..MAX_EXTENTS as u64 + 4 {
+            add_extent(&mut extents, 1000 + i * 100 + i, 1050 + i * 100 + i);
+        }
+        assert_eq!(extents.len(), MAX_EXTENTS);
+        assert_eq!(extents[0], (50, 300));
+        assert!(extents.windows(2).all(|pair| pair[0].1 < pair[1].0));
+    }
+}
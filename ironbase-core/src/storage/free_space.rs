@@ -0,0 +1,379 @@
+// storage/free_space.rs
+// Free-space map: reuse dead record regions between compactions
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use crate::error::{Result, MongoLiteError};
+use super::StorageEngine;
+
+/// Payload written at the start of a free region: `{"_free":<next>}` with the
+/// next offset space-padded to a fixed width, so links can be rewritten in place
+const FREE_STAMP_LEN: usize = 30;
+
+/// Smallest region that can hold a free record (length prefix + stamp)
+pub const MIN_FREE_REGION: u64 = 4 + FREE_STAMP_LEN as u64;
+
+/// Free regions of the data file
+///
+/// A free region is still a well-formed `[u32 len][JSON]` record - a stamp
+/// followed by spaces - so every scanner walks over it and skips it (it has
+/// no `_collection`). The regions form a chain in offset order starting at
+/// `Header::free_list_head`.
+///
+/// Regions released by a write are only *pending*: the catalog on disk may
+/// still point at them until the next metadata flush, so they become
+/// reusable (and get stamped) only after that flush.
+#[derive(Debug, Default)]
+pub struct FreeSpaceMap {
+    /// offset -> region size (length prefix included)
+    available: BTreeMap<u64, u64>,
+    /// Released since the last flush
+    pending: Vec<ReleasedRecord>,
+    /// Accounted in the metadata being flushed, stamped once it is on disk
+    ready: Vec<(u64, u64)>,
+    /// Regions allocated since the chain on disk was last linked
+    relink: bool,
+}
+
+/// A superseded record waiting for the next flush
+#[derive(Debug, Clone)]
+pub(super) struct ReleasedRecord {
+    /// Collection whose dead space it was (None for catalog shards)
+    pub collection: Option<String>,
+    pub offset: u64,
+    pub size: u64,
+    pub tombstone: bool,
+}
+
+impl FreeSpaceMap {
+    /// Mark a dead record as free once the catalog no longer references it on disk
+    /// Records too small to hold a free record stay dead space until compaction
+    pub(super) fn release(&mut self, collection: &str, offset: u64, size: u64, tombstone: bool) {
+        if size >= MIN_FREE_REGION {
+            self.pending.push(ReleasedRecord {
+                collection: Some(collection.to_string()),
+                offset,
+                size,
+                tombstone,
+            });
+        }
+    }
+
+    /// Release a record no collection accounts as dead space (a catalog shard)
+    pub(super) fn release_unowned(&mut self, offset: u64, size: u64) {
+        if size >= MIN_FREE_REGION {
+            self.pending.push(ReleasedRecord {
+                collection: None,
+                offset,
+                size,
+                tombstone: false,
+            });
+        }
+    }
+
+    /// Hand out the records released since the last flush for accounting;
+    /// they are promoted by the next `promote_ready`
+    pub(super) fn take_pending(&mut self) -> Vec<ReleasedRecord> {
+        let pending = std::mem::take(&mut self.pending);
+        self.ready.extend(pending.iter().map(|record| (record.offset, record.size)));
+        pending
+    }
+
+    /// First-fit region of at least `size` bytes
+    ///
+    /// Returns (offset, region size used). The remainder is split off as a new
+    /// region when it can hold a free record, otherwise it goes with the
+    /// allocation (and the record is padded to fill it).
+    pub fn allocate(&mut self, size: u64) -> Option<(u64, u64)> {
+        let (offset, region) = self.available
+            .iter()
+            .find(|(_, &region)| region >= size)
+            .map(|(&offset, &region)| (offset, region))?;
+        self.available.remove(&offset);
+        self.relink = true;
+
+        if region - size >= MIN_FREE_REGION {
+            self.available.insert(offset + size, region - size);
+            Some((offset, size))
+        } else {
+            Some((offset, region))
+        }
+    }
+
+    /// Region starting at `offset`, if free
+    pub fn region_at(&self, offset: u64) -> Option<u64> {
+        self.available.get(&offset).copied()
+    }
+
+    /// First free region after `offset` (the chain link of the region at `offset`)
+    pub fn next_after(&self, offset: u64) -> u64 {
+        self.available.range(offset + 1..).next().map_or(0, |(&next, _)| next)
+    }
+
+    /// Free regions in offset order
+    pub fn regions(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.available.iter().map(|(&offset, &size)| (offset, size))
+    }
+
+    /// Total reusable bytes
+    pub fn free_bytes(&self) -> u64 {
+        self.available.values().sum()
+    }
+
+    /// Remove the region ending at `file_len`, if any; returns its offset (the
+    /// length the file can be cut to)
+    pub(super) fn trim_tail(&mut self, file_len: u64) -> Option<u64> {
+        let (&offset, &size) = self.available.iter().next_back()?;
+        if offset + size != file_len {
+            return None;
+        }
+        self.available.remove(&offset);
+        self.relink = true;
+        Some(offset)
+    }
+
+    /// Drop everything (after compaction the file has no holes)
+    pub fn clear(&mut self) {
+        self.available.clear();
+        self.pending.clear();
+        self.ready.clear();
+    }
+
+    /// The regions promote_ready() will stamp, leaving the map as it is
+    pub(super) fn ready_regions(&self) -> Vec<(u64, u64)> {
+        let mut ready = self.ready.clone();
+        ready.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::new();
+        for (offset, size) in ready {
+            let region = match merged.last_mut() {
+                Some(last) if last.0 + last.1 == offset => {
+                    last.1 += size;
+                    last
+                }
+                _ => {
+                    let start = match self.available.range(..offset).next_back() {
+                        Some((&prev, &prev_size)) if prev + prev_size == offset => prev,
+                        _ => offset,
+                    };
+                    merged.push((start, offset + size - start));
+                    merged.last_mut().expect("just pushed")
+                }
+            };
+            if let Some(next_size) = self.available.get(&(region.0 + region.1)) {
+                region.1 += next_size;
+            }
+        }
+        merged
+    }
+
+    /// Move ready regions into the map, merging neighbours
+    /// Returns the resulting regions that need a full stamp
+    fn promote_ready(&mut self) -> Vec<(u64, u64)> {
+        let mut ready = std::mem::take(&mut self.ready);
+        ready.sort_unstable();
+
+        let mut touched = Vec::new();
+        for (mut offset, mut size) in ready {
+            // Merge with the region ending where this one starts
+            if let Some((&prev, &prev_size)) = self.available.range(..offset).next_back() {
+                if prev + prev_size == offset {
+                    self.available.remove(&prev);
+                    offset = prev;
+                    size += prev_size;
+                }
+            }
+            // ... and with the one starting where it ends
+            if let Some(next_size) = self.available.remove(&(offset + size)) {
+                size += next_size;
+            }
+            self.available.insert(offset, size);
+            touched.retain(|&start| start < offset || start >= offset + size);
+            touched.push(offset);
+        }
+
+        touched.into_iter().map(|offset| (offset, self.available[&offset])).collect()
+    }
+}
+
+impl StorageEngine {
+    /// Load the free-region chain starting at `head`
+    ///
+    /// Stops at the first link that is not a free record: the chain is only
+    /// relinked on flush, so after a crash it may end early (that space just
+    /// waits for compaction).
+    pub(super) fn load_free_space(&mut self, head: u64) -> Result<()> {
+        let file_len = self.file_len()?;
+        let mut offset = head;
+
+        while offset >= super::DATA_START_OFFSET && offset + MIN_FREE_REGION <= file_len {
+            self.file.seek(SeekFrom::Start(offset))?;
+            let mut record = [0u8; MIN_FREE_REGION as usize];
+            self.file.read_exact(&mut record)?;
+
+            let len = u32::from_le_bytes(record[..4].try_into().unwrap()) as u64;
+            let next = match Self::parse_free_stamp(&record[4..]) {
+                Some(next) if offset + 4 + len <= file_len && 4 + len >= MIN_FREE_REGION => next,
+                _ => break,
+            };
+
+            self.free_space.available.insert(offset, 4 + len);
+
+            // Links always point forward - anything else is stale
+            if next <= offset {
+                break;
+            }
+            offset = next;
+        }
+
+        Ok(())
+    }
+
+    /// Take the records released since the last flush out of the dead-space
+    /// statistics - called before the metadata is written, as they are
+    /// stamped free right after
+    pub(super) fn account_reclaimed(&mut self) {
+        let released = self.free_space.take_pending();
+        // Extent starts inside a merged region stop being record boundaries
+        let regions = self.free_space.ready_regions();
+        self.widen_extents(&regions);
+
+        for record in released {
+            let collection = match &record.collection {
+                Some(collection) => collection,
+                None => continue,
+            };
+            if let Some(meta) = self.get_collection_meta_mut(collection) {
+                meta.garbage
+                    .get_or_insert_with(Default::default)
+                    .reclaim(record.size, record.tombstone);
+            }
+        }
+    }
+
+    /// Stamp regions released before the last metadata flush and relink the chain
+    pub(super) fn reclaim_free_space(&mut self) -> Result<()> {
+        let stamped = self.free_space.promote_ready();
+        if stamped.is_empty() && !self.free_space.relink {
+            return Ok(());
+        }
+        self.free_space.relink = false;
+
+        for (offset, size) in stamped {
+            self.write_free_region(offset, size, true)?;
+        }
+
+        // Relink every region (allocations since the last flush broke links)
+        let regions: Vec<(u64, u64)> = self.free_space.regions().collect();
+        for (offset, size) in regions {
+            self.write_free_region(offset, size, false)?;
+        }
+
+        self.header.free_list_head = self.free_space.regions().next().map_or(0, |(offset, _)| offset);
+        let header_bytes = bincode::serialize(&self.header)
+            .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header_bytes)?;
+        self.file.sync_all()?;
+        self.layout.unsynced = false;
+        self.layout.header = header_bytes;
+
+        Ok(())
+    }
+
+    /// Write a free record over `size` bytes at `offset`
+    /// `blank` also overwrites the rest of the region with spaces
+    pub(super) fn write_free_region(&mut self, offset: u64, size: u64, blank: bool) -> Result<()> {
+        let next = self.free_space.next_after(offset);
+        let stamp = format!("{{\"_free\":{:<20}}}", next);
+        debug_assert_eq!(stamp.len(), FREE_STAMP_LEN);
+        self.layout.unsynced = true;
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&((size - 4) as u32).to_le_bytes())?;
+        self.file.write_all(stamp.as_bytes())?;
+
+        if blank {
+            Self::write_padding(&mut self.file, size - MIN_FREE_REGION)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write `count` spaces (valid trailing JSON whitespace)
+    pub(super) fn write_padding<W: Write + ?Sized>(writer: &mut W, mut count: u64) -> Result<()> {
+        let spaces = [b' '; 4096];
+        while count > 0 {
+            let chunk = count.min(spaces.len() as u64) as usize;
+            writer.write_all(&spaces[..chunk])?;
+            count -= chunk as u64;
+        }
+        Ok(())
+    }
+
+    /// Next offset of a free stamp, None if `payload` is not one
+    fn parse_free_stamp(payload: &[u8]) -> Option<u64> {
+        let stamp: serde_json::Value = serde_json::from_slice(payload).ok()?;
+        stamp.get("_free")?.as_u64()
+    }
+
+    /// Free-space map of the data file
+    pub fn free_space(&self) -> &FreeSpaceMap {
+        &self.free_space
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_regions_merge_on_promote() {
+        let mut map = FreeSpaceMap::default();
+        map.release("users", 1000, 100, false);
+        map.release("users", 1100, 50, true);
+        // Too small to become a free record
+        map.release("users", 2000, 10, false);
+
+        // Nothing is reusable before the flush
+        assert_eq!(map.take_pending().len(), 2);
+        assert!(map.allocate(10).is_none());
+
+        let stamped = map.promote_ready();
+        assert_eq!(stamped, vec![(1000, 150)]);
+        assert_eq!(map.regions().collect::<Vec<_>>(), vec![(1000, 150)]);
+    }
+
+    #[test]
+    fn test_allocate_first_fit_and_split() {
+        let mut map = FreeSpaceMap::default();
+        map.release("users", 1000, 40, false);
+        map.release("users", 5000, 200, false);
+        map.take_pending();
+        map.promote_ready();
+
+        // Too small regions are skipped
+        assert_eq!(map.allocate(100), Some((5000, 100)));
+
+        // Remainder big enough for a free record is split off
+        assert_eq!(map.region_at(5100), Some(100));
+
+        // Remainder too small goes with the allocation
+        assert_eq!(map.allocate(80), Some((5100, 100)));
+        assert_eq!(map.allocate(40), Some((1000, 40)));
+        assert_eq!(map.free_bytes(), 0);
+    }
+
+    #[test]
+    fn test_trim_tail_only_at_file_end() {
+        let mut map = FreeSpaceMap::default();
+        map.release("users", 1000, 100, false);
+        map.release("users", 2000, 100, false);
+        map.take_pending();
+        map.promote_ready();
+
+        assert_eq!(map.trim_tail(3000), None);
+        assert_eq!(map.trim_tail(2100), Some(2000));
+        assert_eq!(map.regions().collect::<Vec<_>>(), vec![(1000, 100)]);
+    }
+}
@@ -0,0 +1,226 @@
+// storage/garbage.rs
+// Dead-record accounting: live vs dead space per collection
+
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use super::StorageEngine;
+
+/// Live and dead space of a collection
+///
+/// A record is live if the catalog points at it and it is not a tombstone;
+/// every other record of the collection (old versions, tombstones) is dead
+/// space that free-space reuse or compaction reclaims. Maintained on every
+/// write and persisted with the collection metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GarbageStats {
+    pub live_records: u64,
+    pub live_bytes: u64,
+    pub dead_records: u64,
+    pub dead_bytes: u64,
+    /// Tombstone records (counted in dead_records as well)
+    pub tombstones: u64,
+}
+
+impl GarbageStats {
+    /// Share of the collection's bytes that is dead (0.0 - 1.0)
+    pub fn dead_ratio(&self) -> f64 {
+        let total = self.live_bytes + self.dead_bytes;
+        if total == 0 {
+            0.0
+        } else {
+            self.dead_bytes as f64 / total as f64
+        }
+    }
+
+    fn add(&mut self, size: u64, live: bool, tombstone: bool) {
+        if live {
+            self.live_records += 1;
+            self.live_bytes += size;
+        } else {
+            self.dead_records += 1;
+            self.dead_bytes += size;
+        }
+        if tombstone {
+            self.tombstones += 1;
+        }
+    }
+
+    /// A live record was superseded
+    fn kill(&mut self, size: u64) {
+        self.live_records = self.live_records.saturating_sub(1);
+        self.live_bytes = self.live_bytes.saturating_sub(size);
+        self.dead_records += 1;
+        self.dead_bytes += size;
+    }
+
+    /// A dead record was reclaimed (stamped free or compacted away)
+    pub(super) fn reclaim(&mut self, size: u64, tombstone: bool) {
+        self.dead_records = self.dead_records.saturating_sub(1);
+        self.dead_bytes = self.dead_bytes.saturating_sub(size);
+        if tombstone {
+            self.tombstones = self.tombstones.saturating_sub(1);
+        }
+    }
+}
+
+/// Just enough of a record to tell a tombstone apart
+#[derive(Deserialize)]
+struct TombstoneProbe {
+    #[serde(default, rename = "_tombstone")]
+    tombstone: bool,
+}
+
+/// True if the record payload is a tombstone
+pub(super) fn is_tombstone(data: &[u8]) -> bool {
+    serde_json::from_slice::<TombstoneProbe>(data).is_ok_and(|probe| probe.tombstone)
+}
+
+impl StorageEngine {
+    /// Dead-record statistics of a collection
+    pub fn garbage_stats(&self, collection: &str) -> Option<GarbageStats> {
+        self.collections.get(collection).map(|meta| meta.garbage.unwrap_or_default())
+    }
+
+    /// Account a record written for `collection`
+    pub(super) fn account_write(&mut self, collection: &str, size: u64, live: bool, tombstone: bool) {
+        if let Some(meta) = self.get_collection_meta_mut(collection) {
+            meta.garbage.get_or_insert_with(GarbageStats::default).add(size, live, tombstone);
+        }
+    }
+
+    /// Account the current record of `doc_id` going dead; returns (offset, size, tombstone)
+    pub(super) fn account_superseded(&mut self, collection: &str, doc_id: &DocumentId) -> Result<Option<(u64, u64, bool)>> {
+        let offset = match self.collections.get(collection).and_then(|meta| meta.document_catalog.get(doc_id)) {
+            Some(&offset) if offset >= super::DATA_START_OFFSET => offset,
+            _ => return Ok(None),
+        };
+
+        let data = self.read_data(offset)?;
+        let size = 4 + data.len() as u64;
+        let tombstone = is_tombstone(&data);
+
+        if !tombstone {
+            if let Some(meta) = self.get_collection_meta_mut(collection) {
+                meta.garbage.get_or_insert_with(GarbageStats::default).kill(size);
+            }
+        }
+
+        Ok(Some((offset, size, tombstone)))
+    }
+
+    /// Append a record of `collection` the catalog does not point at
+    /// (e.g. a transaction's delete tombstone) - it is dead from the start
+    pub fn write_dead_record(&mut self, collection: &str, data: &[u8]) -> Result<u64> {
+        let offset = self.write_data(data)?;
+        self.account_write(collection, 4 + data.len() as u64, false, is_tombstone(data));
+        self.extend_extents(collection, offset, 4 + data.len() as u64);
+        Ok(offset)
+    }
+
+    /// Delete `doc_id` by appending a tombstone and dropping its catalog entry
+    ///
+    /// Keeps the catalog at the live set; the tombstone keeps compaction and
+    /// file-order scans from resurrecting the old record.
+    pub fn write_tombstone(&mut self, collection: &str, doc_id: &DocumentId) -> Result<u64> {
+        let tombstone = serde_json::json!({
+            "_id": doc_id,
+            "_collection": collection,
+            "_tombstone": true
+        });
+
+        let previous = self.account_superseded(collection, doc_id)?;
+        let offset = self.write_dead_record(collection, &serde_json::to_vec(&tombstone)?)?;
+
+        let meta = self.get_collection_meta_mut(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?;
+        meta.document_catalog.remove(doc_id);
+        self.journal_remove(collection, doc_id, offset)?;
+
+        self.retire_version(collection, doc_id, previous);
+        if self.batch_lsn.is_none() {
+            self.advance_lsn(collection);
+        }
+
+        Ok(offset)
+    }
+
+    /// Rebuild every collection's statistics - and extents (see extents.rs) -
+    /// from a scan of the data region
+    ///
+    /// Used for files written before the statistics were kept and after
+    /// compaction rewrote the file.
+    pub fn recount_garbage(&mut self) -> Result<()> {
+        let catalogs: HashMap<String, super::DocumentCatalog> = self.collections.iter()
+            .map(|(name, meta)| (name.clone(), meta.document_catalog.clone()))
+            .collect();
+        let mut counts: HashMap<String, GarbageStats> = catalogs.keys()
+            .map(|name| (name.clone(), GarbageStats::default()))
+            .collect();
+        let mut extents: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+
+        self.for_each_record(|offset, data| {
+            // Not every record in the data region is a document (e.g. raw write_data)
+            let doc: serde_json::Value = match serde_json::from_slice(data) {
+                Ok(doc) => doc,
+                Err(_) => return Ok(()),
+            };
+            let collection = match doc.get("_collection").and_then(|c| c.as_str()) {
+                Some(collection) => collection,
+                None => return Ok(()),
+            };
+            let (stats, catalog) = match (counts.get_mut(collection), catalogs.get(collection)) {
+                (Some(stats), Some(catalog)) => (stats, catalog),
+                _ => return Ok(()),
+            };
+
+            let tombstone = doc.get("_tombstone").and_then(|t| t.as_bool()).unwrap_or(false);
+            let current = doc.get("_id")
+                .and_then(|id| serde_json::from_value::<DocumentId>(id.clone()).ok())
+                .and_then(|id| catalog.get(&id))
+                .is_some_and(|&catalog_offset| catalog_offset == offset);
+
+            stats.add(4 + data.len() as u64, current && !tombstone, tombstone);
+            super::extents::add_extent(extents.entry(collection.to_string()).or_default(), offset, offset + 4 + data.len() as u64);
+            Ok(())
+        })?;
+
+        for (name, stats) in counts {
+            let collection_extents = extents.remove(&name).unwrap_or_default();
+            if let Some(meta) = self.get_collection_meta_mut(&name) {
+                meta.garbage = Some(stats);
+                meta.extents = Some(collection_extents);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tombstone_probe() {
+        assert!(is_tombstone(br#"{"_id":1,"_tombstone":true,"name":"a"}"#));
+        assert!(!is_tombstone(br#"{"_id":1,"name":"a"}"#));
+        assert!(!is_tombstone(br#"{"_free":0}   "#));
+        assert!(!is_tombstone(b"not json"));
+    }
+
+    #[test]
+    fn test_dead_ratio() {
+        let mut stats = GarbageStats::default();
+        assert_eq!(stats.dead_ratio(), 0.0);
+
+        stats.add(300, true, false);
+        stats.add(100, false, true);
+        assert_eq!(stats.dead_ratio(), 0.25);
+
+        stats.kill(300);
+        assert_eq!(stats.live_records, 0);
+        assert_eq!(stats.dead_ratio(), 1.0);
+    }
+}
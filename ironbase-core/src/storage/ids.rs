@@ -0,0 +1,124 @@
+// storage/ids.rs
+// Crash-safe auto-increment _ids
+//
+// last_id reaches the data file only when the metadata is flushed. Ids are
+// therefore claimed against a reservation: the first id past it journals a
+// new high-water mark ID_RESERVATION_BLOCK ahead and fsyncs the journal, and
+// replaying the journal after a crash moves last_id past every reservation.
+// An id handed out is never handed out again - a crash only leaves a gap, like
+// a cached SQL sequence. A metadata flush stores the exact last_id and drops
+// the reservations, so a clean restart leaves none.
+//
+// Named sequences (sequence.rs) work the same way, with the reservation kept
+// as the `value` of the sequence's document in SEQUENCES_COLLECTION: the
+// document is only rewritten (and synced) once per block, and flush() writes
+// back the exact value.
+
+use serde_json::{json, Value};
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::sequence::SEQUENCES_COLLECTION;
+use super::StorageEngine;
+
+/// Ids reserved by one journal entry
+pub const ID_RESERVATION_BLOCK: u64 = 1000;
+
+impl StorageEngine {
+    /// Advance `collection`'s last_id to at least `through`, reserving a new
+    /// block first if `through` is past the current reservation
+    pub fn claim_ids(&mut self, collection: &str, through: u64) -> Result<()> {
+        let last_id = self.get_collection_meta(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?
+            .last_id;
+        if through <= last_id {
+            return Ok(());
+        }
+
+        if self.id_reservations.get(collection).is_none_or(|&reserved| through > reserved) {
+            let reserved = through + ID_RESERVATION_BLOCK;
+            self.journal_reservation(collection, reserved)?;
+            self.id_reservations.insert(collection.to_string(), reserved);
+        }
+
+        if let Some(meta) = self.get_collection_meta_mut(collection) {
+            meta.last_id = through;
+        }
+        Ok(())
+    }
+
+    /// Next auto-increment id of `collection` (claimed)
+    pub fn next_id(&mut self, collection: &str) -> Result<u64> {
+        let next = self.get_collection_meta(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?
+            .last_id + 1;
+        self.claim_ids(collection, next)?;
+        Ok(next)
+    }
+
+    /// Allocate the next value of sequence `name`
+    pub fn next_sequence_value(&mut self, name: &str) -> Result<u64> {
+        let (value, mut reserved) = self.cached_sequence(name)?;
+        let next = value + 1;
+        if next > reserved {
+            reserved = next + ID_RESERVATION_BLOCK;
+            self.write_sequence(name, reserved)?;
+            // The new reservation has to be on disk before a value of it is handed out
+            self.flush_writes()?;
+            self.file.sync_data()?;
+            self.journal.sync()?;
+        }
+        self.sequences.insert(name.to_string(), (next, reserved));
+        Ok(next)
+    }
+
+    /// Last value allocated from sequence `name`, 0 if none
+    pub fn sequence_value(&mut self, name: &str) -> Result<u64> {
+        Ok(self.cached_sequence(name)?.0)
+    }
+
+    /// Write back the exact value of every sequence holding a reservation
+    /// (called by flush(), so a clean restart continues without a gap)
+    pub(super) fn release_sequence_reservations(&mut self) -> Result<()> {
+        let reserved: Vec<(String, u64)> = self.sequences.iter()
+            .filter(|(_, &(value, reserved))| value < reserved)
+            .map(|(name, &(value, _))| (name.clone(), value))
+            .collect();
+        for (name, value) in reserved {
+            self.write_sequence(&name, value)?;
+            self.sequences.insert(name, (value, value));
+        }
+        Ok(())
+    }
+
+    /// (last value allocated, reserved through) of sequence `name`
+    fn cached_sequence(&mut self, name: &str) -> Result<(u64, u64)> {
+        if name.is_empty() {
+            return Err(MongoLiteError::InvalidConfig("sequence name cannot be empty".to_string()));
+        }
+        if let Some(&cached) = self.sequences.get(name) {
+            return Ok(cached);
+        }
+
+        // A stored value may have been handed out before a crash
+        let offset = self.get_collection_meta(SEQUENCES_COLLECTION)
+            .and_then(|meta| meta.document_catalog.get(&DocumentId::String(name.to_string())).copied());
+        let stored = match offset {
+            Some(offset) => {
+                let doc: Value = serde_json::from_slice(&self.read_data(offset)?)?;
+                doc.get("value").and_then(Value::as_u64).unwrap_or(0)
+            }
+            None => 0,
+        };
+        self.sequences.insert(name.to_string(), (stored, stored));
+        Ok((stored, stored))
+    }
+
+    fn write_sequence(&mut self, name: &str, value: u64) -> Result<()> {
+        if self.get_collection_meta(SEQUENCES_COLLECTION).is_none() {
+            self.create_collection(SEQUENCES_COLLECTION)?;
+        }
+        let doc = json!({"_id": name, "_collection": SEQUENCES_COLLECTION, "value": value});
+        self.write_document(SEQUENCES_COLLECTION, &DocumentId::String(name.to_string()), &serde_json::to_vec(&doc)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+// storage/in_place.rs
+// In-place updates: a new version that fits its record overwrites it
+//
+// An update normally writes the new version to a free region or the end of
+// the file and leaves the old record as dead space until compaction - a
+// whole record written, and later copied again, for a $inc of one counter.
+// With StorageConfig::in_place_updates a new version that fits in the
+// payload of the document's current record overwrites it, padded with
+// spaces like a record placed in a larger free region. The catalog does not
+// change and no dead space is left behind.
+//
+// Overwriting is not atomic, so the new payload is journaled - and the
+// journal fsynced - before the record is touched: after a crash that tore
+// the record, replay_journal writes it again. Records are only overwritten
+// when no one can still want the old version: no snapshot is open (see
+// mvcc.rs), the old record is not a tombstone, and it is not in the write
+// buffer.
+//
+// A record rarely has room for a version that grew, so the records of
+// updated documents that do not fit are written with their payload rounded
+// up to a multiple of IN_PLACE_SIZE_CLASS: a counter gaining a digit still
+// fits the next time.
+
+use std::io::{Seek, SeekFrom, Write};
+use crate::document::DocumentId;
+use crate::error::Result;
+use super::garbage::is_tombstone;
+use super::StorageEngine;
+
+/// Payload sizes of updated documents' records are rounded up to a multiple
+/// of this (with in_place_updates)
+pub const IN_PLACE_SIZE_CLASS: usize = 32;
+
+impl StorageEngine {
+    /// Overwrite the current record of `doc_id` with `data` if it fits (see
+    /// in_place.rs); returns the record's offset, or None when a new record
+    /// has to be written
+    pub(super) fn try_write_in_place(&mut self, collection: &str, doc_id: &DocumentId, data: &[u8]) -> Result<Option<u64>> {
+        if !self.in_place_updates || is_tombstone(data) {
+            return Ok(None);
+        }
+        let offset = match self.collections.get(collection).and_then(|meta| meta.document_catalog.get(doc_id)) {
+            Some(&offset) if offset >= super::DATA_START_OFFSET => offset,
+            _ => return Ok(None),
+        };
+        self.prune_versions();
+        if self.mvcc_stats().open_snapshots > 0 || self.write_buffer.record(offset).is_some() {
+            return Ok(None);
+        }
+
+        let current = self.read_data(offset)?;
+        if data.len() > current.len() || is_tombstone(&current) {
+            return Ok(None);
+        }
+
+        self.journal_patch(collection, doc_id, offset, data)?;
+        self.overwrite_payload(offset, current.len(), data)?;
+        self.bloom_record(collection, offset, data);
+        Ok(Some(offset))
+    }
+
+    /// `data` padded with spaces to a multiple of IN_PLACE_SIZE_CLASS
+    pub(super) fn pad_to_size_class(data: &[u8]) -> Vec<u8> {
+        let size = data.len().div_ceil(IN_PLACE_SIZE_CLASS) * IN_PLACE_SIZE_CLASS;
+        let mut padded = data.to_vec();
+        padded.resize(size.min(super::MAX_RECORD_DOCUMENT_SIZE), b' ');
+        padded
+    }
+
+    /// Write `data` over the payload of the record at `offset`, which holds
+    /// `capacity` bytes; the rest is padded with spaces
+    pub(super) fn overwrite_payload(&mut self, offset: u64, capacity: usize, data: &[u8]) -> Result<()> {
+        self.layout.unsynced = true;
+        self.file.seek(SeekFrom::Start(offset + 4))?;
+        self.file.write_all(data)?;
+        Self::write_padding(&mut self.file, (capacity - data.len()) as u64)
+    }
+}
@@ -0,0 +1,660 @@
+// storage/inspect.rs
+// Read-only dump of a database's on-disk structures (ironbase-inspect)
+//
+// Reads the data file, the WAL, the journal and the index files directly,
+// without opening the database: nothing is recovered, replayed or flushed,
+// so a file can be inspected exactly as a user sent it. Whatever does not
+// parse is listed under `problems` and the rest is still reported.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::Value;
+use crate::error::{Result, MongoLiteError};
+use crate::index::{IndexFileStamp, IndexFileStatus, IndexMetadata, NODE_PAGE_SIZE};
+use crate::wal::{WALEntry, WALEntryType};
+use super::{CollectionMeta, GarbageStats, Header, StorageEngine, DATA_START_OFFSET};
+
+/// Bincode size of the header (see load_metadata)
+const SERIALIZED_HEADER_SIZE: usize = 36;
+
+/// Everything ironbase-inspect prints
+#[derive(Serialize, Debug, Clone)]
+pub struct InspectReport {
+    pub path: String,
+    pub file_size: u64,
+    pub header: HeaderReport,
+    pub metadata: MetadataReport,
+    pub collections: Vec<CollectionReport>,
+    /// Every record of the data region, in file order
+    pub records: Vec<RecordReport>,
+    /// Records and bytes per kind
+    pub record_summary: BTreeMap<RecordKind, KindSummary>,
+    pub journal: JournalReport,
+    pub wal: WalReport,
+    pub index_files: Vec<IndexFileReport>,
+    /// `.idx` files of this database no collection references
+    pub orphan_index_files: Vec<String>,
+    /// Anything that did not parse or does not add up
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HeaderReport {
+    pub magic: String,
+    pub version: u32,
+    pub page_size: u32,
+    pub collection_count: u32,
+    pub free_list_head: u64,
+    pub index_section_offset: u64,
+}
+
+/// The reserved metadata region between the header and DATA_START_OFFSET
+#[derive(Serialize, Debug, Clone)]
+pub struct MetadataReport {
+    /// (collection, offset, size) of each section, in region order
+    pub sections: Vec<(String, u64, u64)>,
+    /// Header and sections
+    pub used_bytes: u64,
+    pub reserved_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CollectionReport {
+    pub name: String,
+    pub document_count: u64,
+    pub last_id: u64,
+    pub last_lsn: u64,
+    /// Catalog entries, journal applied
+    pub catalog_entries: usize,
+    /// (offset, size) of each catalog shard record
+    pub catalog_shards: Vec<(u64, u64)>,
+    pub garbage: Option<GarbageStats>,
+    pub indexes: Vec<IndexMetadata>,
+}
+
+/// What a record of the data region holds
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    /// Current version of a document (the catalog points at it)
+    Live,
+    /// Older version of a document
+    Dead,
+    Tombstone,
+    /// Free region (see free_space.rs)
+    Free,
+    /// Catalog shard (see catalog.rs)
+    Catalog,
+    /// Anything else: raw data or an unparseable payload
+    Unknown,
+}
+
+impl RecordKind {
+    fn label(self) -> &'static str {
+        match self {
+            RecordKind::Live => "live",
+            RecordKind::Dead => "dead",
+            RecordKind::Tombstone => "tombstone",
+            RecordKind::Free => "free",
+            RecordKind::Catalog => "catalog",
+            RecordKind::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordReport {
+    pub offset: u64,
+    /// Length prefix included
+    pub size: u64,
+    pub kind: RecordKind,
+    pub collection: Option<String>,
+    pub id: Option<Value>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+pub struct KindSummary {
+    pub records: u64,
+    pub bytes: u64,
+}
+
+/// Catalog changes since the last metadata flush (`<db>.journal`)
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct JournalReport {
+    pub bytes: u64,
+    /// Entries per op (put, remove, reserve, applied)
+    pub entries: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct WalReport {
+    pub bytes: u64,
+    pub entries: Vec<WalEntryReport>,
+    /// Transactions with a commit marker - replayed on the next open
+    pub committed: Vec<u64>,
+    /// Transactions without one - discarded on the next open
+    pub uncommitted: Vec<u64>,
+    /// Bytes after the last well-formed entry
+    pub torn_tail_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WalEntryReport {
+    pub offset: u64,
+    pub transaction: u64,
+    pub kind: String,
+    pub data_len: usize,
+    /// The payload (operations and index changes are JSON)
+    pub data: Option<Value>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IndexFileReport {
+    pub collection: String,
+    pub index: String,
+    pub path: String,
+    /// Indexes without a file are rebuilt from the documents on open
+    pub exists: bool,
+    pub size: u64,
+    pub leaf_nodes: u64,
+    pub internal_nodes: u64,
+    /// Keys in the leaves
+    pub keys: u64,
+    /// Root node offset recorded in the stamp, else in the metadata (0 = none)
+    pub root_offset: u64,
+    /// A node starts at root_offset
+    pub root_ok: bool,
+    /// Format version and index uuid on the first page (None before stamps)
+    pub stamp: Option<IndexFileStamp>,
+    /// The metadata lists the file as holding the index as of the documents,
+    /// so it is read on open rather than rebuilt (see CollectionMeta::index_files)
+    pub current: bool,
+}
+
+impl StorageEngine {
+    /// Inspect the database at `path` without opening it (see inspect.rs)
+    ///
+    /// Fails only if the file cannot be read or its header does not parse.
+    pub fn inspect<P: AsRef<Path>>(path: P) -> Result<InspectReport> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let mut problems = Vec::new();
+
+        let header: Header = data.get(..SERIALIZED_HEADER_SIZE)
+            .and_then(|bytes| bincode::deserialize(bytes).ok())
+            .ok_or_else(|| MongoLiteError::Corruption("Invalid header".into()))?;
+        if &header.magic != b"MONGOLTE" {
+            problems.push("invalid magic number".to_string());
+        }
+
+        let (metadata, mut collections) = Self::inspect_metadata(&data, &header, &mut problems);
+        if let Err(e) = Self::load_catalogs(&mut File::open(path)?, &mut collections) {
+            problems.push(format!("catalog: {}", e));
+        }
+        let journal = Self::inspect_journal(&path.with_extension("journal"), &mut collections, &mut problems)?;
+        let records = Self::inspect_records(&data, &collections, &mut problems);
+        let wal = Self::inspect_wal(&path.with_extension("wal"), &mut problems)?;
+        let (index_files, orphan_index_files) = Self::inspect_index_files(path, &collections, &mut problems)?;
+
+        let mut record_summary: BTreeMap<RecordKind, KindSummary> = BTreeMap::new();
+        for record in &records {
+            let summary = record_summary.entry(record.kind).or_default();
+            summary.records += 1;
+            summary.bytes += record.size;
+        }
+
+        let mut collection_reports: Vec<CollectionReport> = collections.into_values()
+            .map(|meta| CollectionReport {
+                catalog_entries: meta.document_catalog.len(),
+                catalog_shards: meta.document_catalog.shard_records().to_vec(),
+                name: meta.name,
+                document_count: meta.document_count,
+                last_id: meta.last_id,
+                last_lsn: meta.last_lsn,
+                garbage: meta.garbage,
+                indexes: meta.indexes,
+            })
+            .collect();
+        collection_reports.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(InspectReport {
+            path: path.display().to_string(),
+            file_size: data.len() as u64,
+            header: HeaderReport {
+                magic: String::from_utf8_lossy(&header.magic).into_owned(),
+                version: header.version,
+                page_size: header.page_size,
+                collection_count: header.collection_count,
+                free_list_head: header.free_list_head,
+                index_section_offset: header.index_section_offset,
+            },
+            metadata,
+            collections: collection_reports,
+            records,
+            record_summary,
+            journal,
+            wal,
+            index_files,
+            orphan_index_files,
+            problems,
+        })
+    }
+
+    /// Metadata sections `[u32 len][JSON]` right after the header
+    fn inspect_metadata(
+        data: &[u8],
+        header: &Header,
+        problems: &mut Vec<String>,
+    ) -> (MetadataReport, HashMap<String, CollectionMeta>) {
+        let mut sections = Vec::new();
+        let mut collections = HashMap::new();
+        let mut offset = SERIALIZED_HEADER_SIZE;
+
+        for section in 0..header.collection_count {
+            let Some(len_bytes) = data.get(offset..offset + 4) else {
+                problems.push(format!("metadata section {} at offset {} is truncated", section, offset));
+                break;
+            };
+            let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            let parsed = data.get(offset + 4..offset + 4 + len)
+                .ok_or_else(|| "truncated".to_string())
+                .and_then(|bytes| serde_json::from_slice::<CollectionMeta>(bytes).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(meta) => {
+                    sections.push((meta.name.clone(), offset as u64, 4 + len as u64));
+                    collections.insert(meta.name.clone(), meta);
+                }
+                Err(e) => {
+                    problems.push(format!("metadata section {} at offset {}: {}", section, offset, e));
+                    break;
+                }
+            }
+            offset += 4 + len;
+        }
+
+        if offset as u64 > DATA_START_OFFSET {
+            problems.push(format!("metadata ends at {}, past the reserved region ({})", offset, DATA_START_OFFSET));
+        }
+        let report = MetadataReport {
+            sections,
+            used_bytes: offset as u64,
+            reserved_bytes: DATA_START_OFFSET,
+        };
+        (report, collections)
+    }
+
+    /// Count the journal entries and apply their catalog changes, as the next open would
+    fn inspect_journal(
+        path: &Path,
+        collections: &mut HashMap<String, CollectionMeta>,
+        problems: &mut Vec<String>,
+    ) -> Result<JournalReport> {
+        let mut report = JournalReport::default();
+        let Some(text) = read_optional(path)? else {
+            return Ok(report);
+        };
+        report.bytes = text.len() as u64;
+
+        for (number, line) in text.split(|&byte| byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let Ok(entry) = serde_json::from_slice::<Value>(line) else {
+                problems.push(format!("journal line {} does not parse", number + 1));
+                continue;
+            };
+            let op = entry["op"].as_str().unwrap_or("?").to_string();
+            *report.entries.entry(op.clone()).or_default() += 1;
+
+            let removed = match op.as_str() {
+                "put" => false,
+                "remove" => true,
+                _ => continue,
+            };
+            let Some(meta) = entry["collection"].as_str().and_then(|name| collections.get_mut(name)) else {
+                continue;
+            };
+            let Ok((tag, value, offset)) = serde_json::from_value::<(String, String, u64)>(entry["entry"].clone()) else {
+                continue;
+            };
+            if let Ok(doc_id) = crate::catalog_serde::decode_entry(&tag, value) {
+                // As on open: the index files were written before these writes
+                meta.index_files.clear();
+                if removed {
+                    meta.document_catalog.remove(&doc_id);
+                } else {
+                    meta.document_catalog.insert(doc_id, offset);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Classify every record of the data region
+    fn inspect_records(
+        data: &[u8],
+        collections: &HashMap<String, CollectionMeta>,
+        problems: &mut Vec<String>,
+    ) -> Vec<RecordReport> {
+        let current: HashSet<u64> = collections.values()
+            .flat_map(|meta| meta.document_catalog.values().copied())
+            .collect();
+        let mut records = Vec::new();
+        let mut offset = DATA_START_OFFSET as usize;
+
+        while offset + 4 <= data.len() {
+            let len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let Some(payload) = data.get(offset + 4..offset + 4 + len) else {
+                break;
+            };
+
+            // Documents repeat _id (see Document::from_json), so no derived struct
+            let record = serde_json::from_slice::<Value>(payload).unwrap_or_default();
+            let text = |key: &str| record.get(key).and_then(Value::as_str).map(str::to_string);
+            let (kind, collection, id) = if record.get("_free").is_some() {
+                (RecordKind::Free, None, None)
+            } else if let Some(catalog) = text("_catalog") {
+                (RecordKind::Catalog, Some(catalog), None)
+            } else if let Some(collection) = text("_collection") {
+                let kind = if record.get("_tombstone").and_then(Value::as_bool).unwrap_or(false) {
+                    RecordKind::Tombstone
+                } else if current.contains(&(offset as u64)) {
+                    RecordKind::Live
+                } else {
+                    RecordKind::Dead
+                };
+                (kind, Some(collection), record.get("_id").cloned())
+            } else {
+                (RecordKind::Unknown, None, None)
+            };
+            records.push(RecordReport { offset: offset as u64, size: 4 + len as u64, kind, collection, id });
+            offset += 4 + len;
+        }
+
+        if offset < data.len() {
+            problems.push(format!("torn record at offset {} ({} bytes to the end of the file)", offset, data.len() - offset));
+        }
+        records
+    }
+
+    /// Every well-formed WAL entry, as the next open would read them
+    fn inspect_wal(path: &Path, problems: &mut Vec<String>) -> Result<WalReport> {
+        let mut report = WalReport::default();
+        let Some(data) = read_optional(path)? else {
+            return Ok(report);
+        };
+        report.bytes = data.len() as u64;
+
+        // transaction -> last entry type, in order of first appearance
+        let mut transactions: Vec<(u64, WALEntryType)> = Vec::new();
+        let mut offset = 0usize;
+        while offset + 17 <= data.len() {
+            let data_len = u32::from_le_bytes(data[offset + 9..offset + 13].try_into().unwrap()) as usize;
+            let entry_len = 17usize.saturating_add(data_len);
+            let Some(entry) = data.get(offset..offset.saturating_add(entry_len)).and_then(|bytes| WALEntry::deserialize(bytes).ok()) else {
+                break;
+            };
+
+            match transactions.iter_mut().find(|(tx, _)| *tx == entry.transaction_id) {
+                Some((_, last)) => *last = entry.entry_type,
+                None => transactions.push((entry.transaction_id, entry.entry_type)),
+            }
+            report.entries.push(WalEntryReport {
+                offset: offset as u64,
+                transaction: entry.transaction_id,
+                kind: format!("{:?}", entry.entry_type),
+                data_len: entry.data.len(),
+                data: serde_json::from_slice(&entry.data).ok(),
+            });
+            offset += entry_len;
+        }
+
+        // A torn last entry or a zero-filled tail is normal after a crash;
+        // a bad entry with more behind it is corruption (see WriteAheadLog::recover)
+        let tail = &data[offset..];
+        report.torn_tail_bytes = tail.len() as u64;
+        if tail.len() >= 17 && tail.iter().any(|&byte| byte != 0) {
+            let entry_len = 17 + u32::from_le_bytes(tail[9..13].try_into().unwrap()) as u64;
+            if entry_len < tail.len() as u64 {
+                problems.push(format!("WAL entry at offset {} is corrupt", offset));
+            }
+        }
+        for (transaction, last) in transactions {
+            if last == WALEntryType::Commit {
+                report.committed.push(transaction);
+            } else {
+                report.uncommitted.push(transaction);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Summaries of the `<db>.<index>.idx` files, and the unreferenced ones
+    fn inspect_index_files(
+        path: &Path,
+        collections: &HashMap<String, CollectionMeta>,
+        problems: &mut Vec<String>,
+    ) -> Result<(Vec<IndexFileReport>, Vec<String>)> {
+        let index_path = |index: &str| super::index_file_path(path, index);
+
+        let mut names: Vec<&String> = collections.keys().collect();
+        names.sort();
+        let mut reports = Vec::new();
+        let mut referenced = HashSet::new();
+        for name in names {
+            // The _id index is not in the metadata, but transactions write its file too
+            let meta = &collections[name];
+            let id_index = (format!("{}_id", name), 0, &meta.uuid);
+            let indexes = std::iter::once(id_index)
+                .chain(meta.indexes.iter().map(|index| (index.name.clone(), index.root_offset, &index.uuid)));
+            for (index, root_offset, uuid) in indexes {
+                let file_path = index_path(&index);
+                referenced.insert(file_path.clone());
+                let mut report = IndexFileReport {
+                    collection: name.clone(),
+                    index,
+                    path: file_path.display().to_string(),
+                    exists: false,
+                    size: 0,
+                    leaf_nodes: 0,
+                    internal_nodes: 0,
+                    keys: 0,
+                    root_offset,
+                    root_ok: false,
+                    stamp: None,
+                    current: false,
+                };
+                if let Some(data) = read_optional(&file_path)? {
+                    report.exists = true;
+                    report.size = data.len() as u64;
+                    report.stamp = IndexFileStamp::parse(&data);
+                    match IndexFileStatus::of(report.stamp.as_ref(), uuid) {
+                        IndexFileStatus::Stale(reason) => {
+                            problems.push(format!("index {}: stale file ({}), rewritten on open", report.index, reason));
+                        }
+                        _ => {
+                            report.current = report.stamp.as_ref()
+                                .is_some_and(|stamp| meta.index_files.get(&report.index) == Some(&stamp.save_id));
+                        }
+                    }
+                    Self::scan_index_nodes(&data, &mut report, problems);
+                }
+                reports.push(report);
+            }
+        }
+
+        // Other .idx files with this database's prefix
+        let base = if path.extension().is_some_and(|extension| extension == "mlite") {
+            path.with_extension("")
+        } else {
+            path.to_path_buf()
+        };
+        let prefix = format!("{}.", base.file_name().map(|name| name.to_string_lossy()).unwrap_or_default());
+        let directory = base.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut orphans = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let entry_path = entry?.path();
+            let file_name = entry_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            if file_name.starts_with(&prefix) && file_name.ends_with(".idx") && !referenced.contains(&entry_path) {
+                orphans.push(entry_path.display().to_string());
+            }
+        }
+        orphans.sort();
+        Ok((reports, orphans))
+    }
+
+    /// Walk the nodes of an index file: `[u8 type][u32 len][JSON]`, padded to
+    /// whole pages. A file whose stamp records the root is walked from it -
+    /// runs left behind by nodes that moved are not part of the tree - older
+    /// ones page by page after the stamp page.
+    fn scan_index_nodes(data: &[u8], report: &mut IndexFileReport, problems: &mut Vec<String>) {
+        if let Some(root) = report.stamp.as_ref().map(|stamp| stamp.root_offset).filter(|&root| root != 0) {
+            report.root_offset = root;
+            let mut pending = vec![root as usize];
+            let mut visited = HashSet::new();
+            while let Some(offset) = pending.pop() {
+                if !visited.insert(offset) {
+                    continue;
+                }
+                let Some((node, _)) = Self::index_node_at(data, offset) else {
+                    problems.push(format!("index {}: no node at offset {}", report.index, offset));
+                    continue;
+                };
+                report.root_ok |= offset as u64 == root;
+                Self::count_index_node(&node, report);
+                if let Some(children) = node.pointer("/Internal/children_offsets").and_then(Value::as_array) {
+                    pending.extend(children.iter().filter_map(Value::as_u64).map(|child| child as usize));
+                }
+            }
+        } else {
+            let mut offset = if report.stamp.is_some() { NODE_PAGE_SIZE } else { 0 };
+            while offset + 5 <= data.len() {
+                let Some((node, span)) = Self::index_node_at(data, offset) else {
+                    problems.push(format!("index {}: no node at offset {}", report.index, offset));
+                    return;
+                };
+                if offset as u64 == report.root_offset {
+                    report.root_ok = true;
+                }
+                Self::count_index_node(&node, report);
+                offset += span;
+            }
+        }
+        if report.root_offset != 0 && !report.root_ok {
+            problems.push(format!("index {}: no node at the root offset {}", report.index, report.root_offset));
+        }
+    }
+
+    /// Node at `offset` of an index file, and the bytes it spans
+    fn index_node_at(data: &[u8], offset: usize) -> Option<(Value, usize)> {
+        let len = u32::from_le_bytes(data.get(offset + 1..offset + 5)?.try_into().unwrap()) as usize;
+        let span = (5 + len).div_ceil(NODE_PAGE_SIZE) * NODE_PAGE_SIZE;
+        let node = serde_json::from_slice::<Value>(data.get(offset + 5..offset + 5 + len)?).ok()?;
+        Some((node, span))
+    }
+
+    fn count_index_node(node: &Value, report: &mut IndexFileReport) {
+        if let Some(leaf) = node.get("Leaf") {
+            report.leaf_nodes += 1;
+            report.keys += leaf["keys"].as_array().map_or(0, Vec::len) as u64;
+        } else {
+            report.internal_nodes += 1;
+        }
+    }
+}
+
+/// Contents of a file, None if it does not exist
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl fmt::Display for InspectReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({} bytes)", self.path, self.file_size)?;
+
+        let header = &self.header;
+        writeln!(f, "\nHeader")?;
+        writeln!(f, "  magic {}  version {}  page size {}", header.magic, header.version, header.page_size)?;
+        writeln!(f, "  collections {}  free list head {}  index section {}",
+            header.collection_count, header.free_list_head, header.index_section_offset)?;
+
+        let metadata = &self.metadata;
+        writeln!(f, "\nMetadata region: {} of {} bytes used ({:.1}%)", metadata.used_bytes, metadata.reserved_bytes,
+            metadata.used_bytes as f64 * 100.0 / metadata.reserved_bytes as f64)?;
+        for (name, offset, size) in &metadata.sections {
+            writeln!(f, "  {:>10}  {:>8} B  {}", offset, size, name)?;
+        }
+
+        writeln!(f, "\nCollections")?;
+        for collection in &self.collections {
+            writeln!(f, "  {}: {} documents, {} catalog entries in {} shards, last_id {}, last_lsn {}",
+                collection.name, collection.document_count, collection.catalog_entries,
+                collection.catalog_shards.len(), collection.last_id, collection.last_lsn)?;
+            if let Some(garbage) = &collection.garbage {
+                writeln!(f, "    live {} records / {} B, dead {} records / {} B, {} tombstones",
+                    garbage.live_records, garbage.live_bytes, garbage.dead_records, garbage.dead_bytes, garbage.tombstones)?;
+            }
+            for index in &collection.indexes {
+                writeln!(f, "    index {} on {}{}{}", index.name, index.field,
+                    if index.unique { " unique" } else { "" }, if index.hashed { " hashed" } else { "" })?;
+            }
+        }
+
+        writeln!(f, "\nRecords")?;
+        for (kind, summary) in &self.record_summary {
+            writeln!(f, "  {:<10} {:>8} records {:>12} B", kind.label(), summary.records, summary.bytes)?;
+        }
+        for record in &self.records {
+            let id = record.id.as_ref().map(Value::to_string).unwrap_or_default();
+            writeln!(f, "  {:>10}  {:>8} B  {:<10} {} {}", record.offset, record.size, record.kind.label(),
+                record.collection.as_deref().unwrap_or("-"), id)?;
+        }
+
+        writeln!(f, "\nJournal: {} bytes", self.journal.bytes)?;
+        for (op, count) in &self.journal.entries {
+            writeln!(f, "  {:<8} {}", op, count)?;
+        }
+
+        let wal = &self.wal;
+        writeln!(f, "\nWAL: {} bytes, {} entries, committed {:?}, uncommitted {:?}, torn tail {} bytes",
+            wal.bytes, wal.entries.len(), wal.committed, wal.uncommitted, wal.torn_tail_bytes)?;
+        for entry in &wal.entries {
+            let data = entry.data.as_ref().map(Value::to_string).unwrap_or_default();
+            writeln!(f, "  {:>10}  tx {:<6} {:<12} {:>6} B  {}", entry.offset, entry.transaction, entry.kind, entry.data_len, data)?;
+        }
+
+        writeln!(f, "\nIndex files")?;
+        for index in &self.index_files {
+            if index.exists {
+                let version = match &index.stamp {
+                    Some(stamp) => format!("format {}", stamp.version),
+                    None => "unstamped".to_string(),
+                };
+                writeln!(f, "  {}: {}, {} B, {} leaves, {} internal nodes, {} keys, root {}{}, {}",
+                    index.path, version, index.size, index.leaf_nodes, index.internal_nodes, index.keys,
+                    index.root_offset, if index.root_ok || index.root_offset == 0 { "" } else { " (missing)" },
+                    if index.current { "read on open" } else { "rebuilt on open" })?;
+            } else {
+                writeln!(f, "  {}: no file (rebuilt on open)", index.path)?;
+            }
+        }
+        for orphan in &self.orphan_index_files {
+            writeln!(f, "  {}: not referenced", orphan)?;
+        }
+
+        if !self.problems.is_empty() {
+            writeln!(f, "\nProblems")?;
+            for problem in &self.problems {
+                writeln!(f, "  {}", problem)?;
+            }
+        }
+        Ok(())
+    }
+}
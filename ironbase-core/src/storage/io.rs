@@ -1,48 +1,167 @@
 // storage/io.rs
 // Low-level I/O operations for storage engine
 
-use std::io::{Read, Write, Seek, SeekFrom};
-use crate::error::Result;
+use std::io::{IoSlice, Read, Write, Seek, SeekFrom};
+use crate::error::{Result, ResultExt};
 use super::StorageEngine;
 
 impl StorageEngine {
     /// Write data to end of file
     /// Returns the offset where data was written
+    ///
+    /// Always appends: raw records have no catalog entry, so file order is the
+    /// only thing saying which record is newest (see write_document)
     pub fn write_data(&mut self, data: &[u8]) -> Result<u64> {
-        let offset = self.file.seek(SeekFrom::End(0))?;
-
-        // Méret + adat írása
-        let len = (data.len() as u32).to_le_bytes();
-        self.file.write_all(&len)?;
-        self.file.write_all(data)?;
-
-        Ok(offset)
+        self.layout.unsynced = true;
+        // Méret + adat írása (see write_buffer.rs)
+        self.append_record(data, 0)
     }
 
     /// Read data from specified offset
     pub fn read_data(&mut self, offset: u64) -> Result<Vec<u8>> {
-        self.file.seek(SeekFrom::Start(offset))?;
+        if let Some(data) = self.mapped_record(offset) {
+            return Ok(data);
+        }
+        if let Some(data) = self.write_buffer.record(offset) {
+            return Ok(data);
+        }
+        self.file.seek(SeekFrom::Start(offset)).at_offset(offset)?;
 
         // Méret olvasása
         let mut len_bytes = [0u8; 4];
-        self.file.read_exact(&mut len_bytes)?;
+        self.file.read_exact(&mut len_bytes).at_offset(offset)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
 
         // Adat olvasása
         let mut data = vec![0u8; len];
+        self.file.read_exact(&mut data).at_offset(offset)?;
+
+        Ok(data)
+    }
+
+    /// Read data from specified offset, refusing records that run past end of file
+    /// Use this when the offset itself is untrusted (validation, repair)
+    pub fn read_data_checked(&mut self, offset: u64) -> Result<Vec<u8>> {
+        use crate::error::MongoLiteError;
+
+        self.flush_writes()?;
+        let file_len = self.file_len()?;
+        if offset + 4 > file_len {
+            return Err(MongoLiteError::Corruption(
+                format!("record offset {} beyond end of file ({})", offset, file_len)
+            ));
+        }
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut len_bytes = [0u8; 4];
+        self.file.read_exact(&mut len_bytes)?;
+        let len = u32::from_le_bytes(len_bytes) as u64;
+
+        if offset + 4 + len > file_len {
+            return Err(MongoLiteError::Corruption(
+                format!("record at offset {} truncated ({} bytes declared)", offset, len)
+            ));
+        }
+
+        let mut data = vec![0u8; len as usize];
         self.file.read_exact(&mut data)?;
 
         Ok(data)
     }
 
-    /// Get file length
+    /// Walk every length-prefixed record in the data region, in file order
+    /// The callback receives the ABSOLUTE offset and the record payload
+    /// Stops quietly at a truncated tail record
+    /// Reads through a buffer of one page (Header::page_size)
+    pub fn for_each_record<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnMut(u64, &[u8]) -> Result<()>,
+    {
+        self.for_each_record_between(super::DATA_START_OFFSET, u64::MAX, f)
+    }
+
+    /// Walk the records starting in `[start, end)`; `start` must be a record boundary
+    pub(super) fn for_each_record_between<F>(&mut self, start: u64, end: u64, mut f: F) -> Result<()>
+    where
+        F: FnMut(u64, &[u8]) -> Result<()>,
+    {
+        self.flush_writes()?;
+        let file_len = self.file_len()?;
+        let end = end.min(file_len);
+        let mut offset = start.max(super::DATA_START_OFFSET);
+
+        let mut reader = std::io::BufReader::with_capacity(self.header.page_size as usize, &mut *self.file);
+        reader.seek(SeekFrom::Start(offset))?;
+
+        while offset < end && offset + 4 <= file_len {
+            let mut len_bytes = [0u8; 4];
+            reader.read_exact(&mut len_bytes)?;
+            let len = u32::from_le_bytes(len_bytes) as u64;
+
+            if offset + 4 + len > file_len {
+                break;
+            }
+
+            let mut data = vec![0u8; len as usize];
+            reader.read_exact(&mut data)?;
+
+            f(offset, &data)?;
+            offset += 4 + len;
+        }
+
+        Ok(())
+    }
+
+    /// Cut a record left incomplete by a crash off the end of the data region,
+    /// so the next append does not land behind it
+    pub(super) fn drop_torn_tail(&mut self) -> Result<()> {
+        let file_len = self.file_len()?;
+        if file_len <= super::DATA_START_OFFSET {
+            return Ok(());
+        }
+
+        let mut end = super::DATA_START_OFFSET;
+        self.for_each_record(|offset, data| {
+            end = offset + 4 + data.len() as u64;
+            Ok(())
+        })?;
+        if end < file_len {
+            drop(self.mmap.take());
+            self.file.set_len(end)?;
+            self.file.sync_data()?;
+            self.clip_extents(end);
+        }
+        Ok(())
+    }
+
+    /// write_all() of several buffers in as few (vectored) writes as the
+    /// file takes
+    pub(super) fn write_all_vectored<W: Write + ?Sized>(writer: &mut W, mut slices: &mut [IoSlice]) -> Result<()> {
+        while !slices.is_empty() {
+            match writer.write_vectored(slices) {
+                Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()),
+                Ok(written) => IoSlice::advance_slices(&mut slices, written),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Get file length, appends still buffered included (see write_buffer.rs)
     pub fn file_len(&self) -> Result<u64> {
-        Ok(self.file.metadata()?.len())
+        Ok(self.file.size()? + self.write_buffer.len())
     }
 
     /// Write document and update catalog
     /// This is the new persistent write method that tracks document offsets
     /// Stores ABSOLUTE offsets in catalog for simplicity and correctness
+    ///
+    /// Reuses a free region when one fits, so the newest version of a document
+    /// is not necessarily the last one in the file - readers go by the catalog
+    /// (see CollectionMeta::is_superseded). The superseded record is released
+    /// to the free-space map, or kept as an old version while snapshots are
+    /// open (see mvcc.rs).
     pub fn write_document(
         &mut self,
         collection: &str,
@@ -51,15 +170,33 @@ impl StorageEngine {
     ) -> Result<u64> {
         use crate::error::MongoLiteError;
 
-        // Ensure we write AFTER the reserved metadata space
-        let file_end = self.file.seek(SeekFrom::End(0))?;
-        let write_pos = std::cmp::max(file_end, super::DATA_START_OFFSET);
-        let absolute_offset = self.file.seek(SeekFrom::Start(write_pos))?;
-
-        // Write length + data (same format as write_data)
-        let len = (data.len() as u32).to_le_bytes();
-        self.file.write_all(&len)?;
-        self.file.write_all(data)?;
+        if self.get_collection_meta(collection).is_none() {
+            return Err(MongoLiteError::CollectionNotFound(collection.to_string()));
+        }
+        self.check_document_size(data.len())?;
+        if let Some(offset) = self.try_write_in_place(collection, doc_id, data)? {
+            // Same record, same catalog entry (see in_place.rs)
+            if self.batch_lsn.is_none() {
+                self.advance_lsn(collection);
+            }
+            return Ok(offset);
+        }
+
+        let tombstone = super::garbage::is_tombstone(data);
+        let previous = self.account_superseded(collection, doc_id)?;
+        // The new record of an updated document gets room to grow in place
+        let padded = (self.in_place_updates && !tombstone && matches!(previous, Some((_, _, false))))
+            .then(|| Self::pad_to_size_class(data));
+        let (absolute_offset, written) = self.place_record(padded.as_deref().unwrap_or(data))?;
+
+        // The superseded version goes to open snapshots or the free-space map
+        self.retire_version(collection, doc_id, previous);
+
+        self.account_write(collection, written, !tombstone, tombstone);
+        self.extend_extents(collection, absolute_offset, written);
+        if !tombstone {
+            self.bloom_record(collection, absolute_offset, data);
+        }
 
         // Update catalog in metadata with ABSOLUTE offset
         // Direct insert using DocumentId (no serialization overhead!)
@@ -67,10 +204,48 @@ impl StorageEngine {
             .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?;
 
         meta.document_catalog.insert(doc_id.clone(), absolute_offset);
+        self.journal_put(collection, doc_id, absolute_offset)?;
+
+        // A transaction's writes share its LSN (advanced once by apply_transaction)
+        if self.batch_lsn.is_none() {
+            self.advance_lsn(collection);
+        }
 
         Ok(absolute_offset)
     }
 
+    /// Write a record into the first free region it fits, or at the end of the file
+    /// Returns (offset, region size used)
+    pub(super) fn place_record(&mut self, data: &[u8]) -> Result<(u64, u64)> {
+        let record_size = 4 + data.len() as u64;
+        let region = self.free_space.allocate(record_size);
+        self.layout.unsynced = true;
+
+        let placed = match region {
+            Some((offset, region_size)) => {
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.write_all(&((region_size - 4) as u32).to_le_bytes())?;
+                self.file.write_all(data)?;
+                // Pad a region too small to split (JSON allows trailing whitespace)
+                Self::write_padding(&mut self.file, region_size - record_size)?;
+
+                // Split-off remainder becomes a free record of its own
+                if let Some(rest) = self.free_space.region_at(offset + region_size) {
+                    self.write_free_region(offset + region_size, rest, false)?;
+                }
+                (offset, region_size)
+            }
+            None => {
+                // Ensure we write AFTER the reserved metadata space
+                // (same format as write_data)
+                let offset = self.append_record(data, super::DATA_START_OFFSET)?;
+                (offset, record_size)
+            }
+        };
+
+        Ok(placed)
+    }
+
     /// Read document by offset (catalog-based retrieval)
     /// Takes an ABSOLUTE offset directly from catalog
     pub fn read_document_at(&mut self, _collection: &str, absolute_offset: u64) -> Result<Vec<u8>> {
@@ -0,0 +1,251 @@
+// storage/journal.rs
+// Metadata journal: catalog changes and id reservations since the last metadata flush
+//
+// The catalog and last_id only reach the data file when the metadata is
+// flushed, so after a crash the documents written since would be unreachable
+// and their _ids handed out again. Every catalog change therefore appends a
+// line to `<db>.journal`, which is fsynced together with the data file (see
+// StorageEngine::data_sync). Opening the database replays the journal; a
+// metadata flush empties it.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::document::DocumentId;
+use crate::error::Result;
+use crate::wal::{GroupSync, SyncThread};
+use super::{StorageBackend, StorageEngine, StorageFile};
+
+/// One catalog change; the id is a catalog_serde entry (type tag, value, offset)
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalEntry {
+    /// The document's current record is at the offset
+    Put { collection: String, entry: (String, String, u64) },
+    /// The document was deleted by the tombstone at the offset
+    Remove { collection: String, entry: (String, String, u64) },
+    /// Auto-increment ids up to `through` may have been handed out (see ids.rs)
+    Reserve { collection: String, through: u64 },
+    /// Every write of the transaction is journaled (see replay_wal)
+    Applied { transaction: u64 },
+    /// The document's record at the offset was overwritten with `data`
+    /// (see in_place.rs)
+    Patch { collection: String, entry: (String, String, u64), data: String },
+}
+
+/// Append-only file of catalog changes not yet in the flushed metadata
+pub(super) struct MetadataJournal {
+    file: Box<dyn StorageFile>,
+    /// Bytes in the file (0 once the metadata caught up)
+    len: u64,
+}
+
+impl MetadataJournal {
+    pub(super) fn open(backend: &dyn StorageBackend, path: &Path) -> Result<Self> {
+        let file = backend.open(path)?;
+        let len = file.size()?;
+        Ok(MetadataJournal { file, len })
+    }
+
+    fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        // One write per entry: a crash tears at most the last line
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&line)?;
+        self.len += line.len() as u64;
+        Ok(())
+    }
+
+    /// Entries in order, up to the first line that does not parse (a torn tail)
+    fn entries(&mut self) -> Result<Vec<JournalEntry>> {
+        let mut content = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut content)?;
+        Ok(content.split(|&byte| byte == b'\n')
+            .map_while(|line| serde_json::from_slice(line).ok())
+            .collect())
+    }
+
+    pub(super) fn sync(&self) -> Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Empty the journal - only once the metadata it covers is on disk
+    fn clear(&mut self) -> Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Group commit over the data file and the journal
+    pub(super) fn group_sync(&self, data_file: &dyn StorageFile, thread: Option<&Arc<SyncThread>>) -> Result<Arc<GroupSync>> {
+        Ok(Arc::new(GroupSync::with_files_on(vec![data_file.try_clone()?, self.file.try_clone()?], thread)))
+    }
+}
+
+impl StorageEngine {
+    /// Journal that `doc_id`'s current record is at `offset`
+    pub(super) fn journal_put(&mut self, collection: &str, doc_id: &DocumentId, offset: u64) -> Result<()> {
+        self.journal.append(&JournalEntry::Put {
+            collection: collection.to_string(),
+            entry: crate::catalog_serde::encode_entry(doc_id, offset),
+        })
+    }
+
+    /// Journal that `doc_id` was deleted by the tombstone at `offset`
+    pub(super) fn journal_remove(&mut self, collection: &str, doc_id: &DocumentId, offset: u64) -> Result<()> {
+        self.journal.append(&JournalEntry::Remove {
+            collection: collection.to_string(),
+            entry: crate::catalog_serde::encode_entry(doc_id, offset),
+        })
+    }
+
+    /// Journal (durably) that ids of `collection` up to `through` may be handed out
+    pub(super) fn journal_reservation(&mut self, collection: &str, through: u64) -> Result<()> {
+        self.journal.append(&JournalEntry::Reserve {
+            collection: collection.to_string(),
+            through,
+        })?;
+        self.journal.sync()
+    }
+
+    /// Journal (durably) the payload about to overwrite `doc_id`'s record at
+    /// `offset`, so a torn overwrite can be redone
+    pub(super) fn journal_patch(&mut self, collection: &str, doc_id: &DocumentId, offset: u64, data: &[u8]) -> Result<()> {
+        self.journal.append(&JournalEntry::Patch {
+            collection: collection.to_string(),
+            entry: crate::catalog_serde::encode_entry(doc_id, offset),
+            data: String::from_utf8_lossy(data).into_owned(),
+        })?;
+        self.journal.sync()
+    }
+
+    /// Journal that every write of `transaction` is journaled
+    pub(super) fn journal_applied(&mut self, transaction: crate::transaction::TransactionId) -> Result<()> {
+        self.journal.append(&JournalEntry::Applied { transaction })
+    }
+
+    /// Empty the journal once the metadata on disk covers it; id reservations
+    /// go with it (the flushed last_id is exact)
+    ///
+    /// The WAL goes first: its applied transactions are covered too, and
+    /// without their Applied markers replay_wal would apply them again.
+    pub(super) fn clear_journal(&mut self) -> Result<()> {
+        self.wal.clear()?;
+        self.journal.clear()?;
+        self.id_reservations.clear();
+        self.applied_transactions.clear();
+        Ok(())
+    }
+
+    /// Bring the catalogs and last_ids up to the journal after an unclean shutdown
+    /// (last_id moves past every id reservation)
+    ///
+    /// The data file is trusted over the journal: an entry whose record did
+    /// not make it to disk is skipped. Records are only written into regions
+    /// that were free on disk, and writing one breaks the free chain there
+    /// (see load_free_space), so no replayed record is in the free-space map.
+    /// Returns the number of entries applied.
+    pub(super) fn replay_journal(&mut self) -> Result<usize> {
+        if self.journal.len == 0 {
+            return Ok(0);
+        }
+
+        let entries = self.journal.entries()?;
+
+        // Overwrites first: the records they tore must parse again before
+        // the catalog entries pointing at them are checked
+        let mut applied = 0;
+        for entry in &entries {
+            if let JournalEntry::Patch { entry: (_, _, offset), data, .. } = entry {
+                if self.redo_patch(*offset, data.as_bytes()) {
+                    applied += 1;
+                }
+            }
+        }
+
+        for entry in entries {
+            let (collection, (tag, value, offset), removed) = match entry {
+                JournalEntry::Put { collection, entry } => (collection, entry, false),
+                JournalEntry::Remove { collection, entry } => (collection, entry, true),
+                JournalEntry::Reserve { collection, through } => {
+                    // Ids of the reservation may be in use anywhere - skip past all of them
+                    if let Some(meta) = self.get_collection_meta_mut(&collection) {
+                        meta.last_id = meta.last_id.max(through);
+                        applied += 1;
+                    }
+                    continue;
+                }
+                JournalEntry::Applied { transaction } => {
+                    self.applied_transactions.insert(transaction);
+                    continue;
+                }
+                JournalEntry::Patch { .. } => continue,
+            };
+            let Ok(doc_id) = crate::catalog_serde::decode_entry(&tag, value) else {
+                continue;
+            };
+            if !self.collections.contains_key(&collection)
+                || !self.journaled_record_matches(&collection, &doc_id, offset, removed)
+            {
+                continue;
+            }
+
+            let meta = self.get_collection_meta_mut(&collection).expect("collection checked above");
+            // The index files were written before these writes
+            meta.index_files.clear();
+            if removed {
+                meta.document_catalog.remove(&doc_id);
+            } else {
+                meta.document_catalog.insert(doc_id.clone(), offset);
+            }
+            if let DocumentId::Int(id) = doc_id {
+                meta.last_id = meta.last_id.max(id.max(0) as u64);
+            }
+            applied += 1;
+        }
+
+        if applied > 0 {
+            // Cheaper to recount than to journal every statistics change
+            self.recount_garbage()?;
+        }
+        Ok(applied)
+    }
+
+    /// Write a journaled overwrite again; false if the record at `offset` did
+    /// not make it to disk or cannot hold `data`
+    fn redo_patch(&mut self, offset: u64, data: &[u8]) -> bool {
+        if offset < super::DATA_START_OFFSET {
+            return false;
+        }
+        match self.read_data_checked(offset) {
+            Ok(current) if current.len() >= data.len() => self.overwrite_payload(offset, current.len(), data).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// True if the record at `offset` is `doc_id` of `collection` (a tombstone
+    /// for a removal)
+    fn journaled_record_matches(&mut self, collection: &str, doc_id: &DocumentId, offset: u64, removed: bool) -> bool {
+        if offset < super::DATA_START_OFFSET {
+            return false;
+        }
+        let Ok(data) = self.read_data_checked(offset) else {
+            return false;
+        };
+        let Ok(record) = serde_json::from_slice::<Value>(&data) else {
+            return false;
+        };
+        record.get("_collection").and_then(Value::as_str) == Some(collection)
+            && record.get("_id") == serde_json::to_value(doc_id).ok().as_ref()
+            && (!removed || super::garbage::is_tombstone(&data))
+    }
+}
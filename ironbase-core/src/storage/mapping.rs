@@ -0,0 +1,91 @@
+// storage/mapping.rs
+// Memory map of the data file for record reads
+//
+// The map covers a prefix of the data file: all of it up to
+// StorageConfig::mmap_limit, the first mmap_limit bytes of a larger file.
+// Records inside the map are read from it, everything else with plain file
+// I/O. Appends grow the file past the map; it is remapped once the unmapped
+// tail is large enough to be worth it (a remap per append would cost more
+// than the reads it saves). The map is dropped before the file shrinks and
+// rebuilt afterwards.
+
+use memmap2::{Mmap, MmapOptions};
+use serde::Serialize;
+use super::StorageEngine;
+
+/// Default StorageConfig::mmap_limit
+pub const DEFAULT_MMAP_LIMIT: u64 = 1_000_000_000;
+
+/// Smallest unmapped tail that triggers a remap (see grow_mmap)
+pub const MMAP_REMAP_MIN_GROWTH: u64 = 1 << 20;
+
+/// How record reads reach the data file
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MapMode {
+    /// The whole file is mapped
+    Full,
+    /// A prefix is mapped; the tail is read with file I/O until the next
+    /// remap, or for good past mmap_limit
+    Partial,
+    /// No map: mmap_limit is 0, the backend has no OS file, or mapping failed
+    Off,
+}
+
+impl StorageEngine {
+    /// Map the data file afresh, up to mmap_limit bytes
+    pub(super) fn remap(&mut self) {
+        drop(self.mmap.take());
+        // Buffered appends are not in the file yet
+        let len = match self.file.size() {
+            Ok(file_len) => file_len.min(self.mmap_limit),
+            Err(_) => return,
+        };
+        let Some(os_file) = self.file.as_file() else {
+            return;
+        };
+        if len == 0 {
+            return;
+        }
+        // Safety: the file is only changed through this engine, which drops
+        // the map before shrinking the file
+        self.mmap = unsafe { MmapOptions::new().len(len as usize).map(os_file) }.ok();
+        self.remaps += u64::from(self.mmap.is_some());
+    }
+
+    /// The file now ends at `file_end`: remap once the unmapped part is at
+    /// least an eighth of the map (and MMAP_REMAP_MIN_GROWTH), or the file
+    /// reached mmap_limit
+    pub(super) fn grow_mmap(&mut self, file_end: u64) {
+        if self.mmap_limit == 0 || self.file.as_file().is_none() {
+            return;
+        }
+        let mapped = self.mapped_len();
+        let target = file_end.min(self.mmap_limit);
+        if target > mapped && (target - mapped >= (mapped / 8).max(MMAP_REMAP_MIN_GROWTH) || target == self.mmap_limit) {
+            self.remap();
+        }
+    }
+
+    /// Payload of the record at `offset`, if the map holds all of it
+    pub(super) fn mapped_record(&self, offset: u64) -> Option<Vec<u8>> {
+        let map: &Mmap = self.mmap.as_ref()?;
+        let start = usize::try_from(offset).ok()?.checked_add(4)?;
+        let len = u32::from_le_bytes(map.get(start - 4..start)?.try_into().ok()?) as usize;
+        map.get(start..start.checked_add(len)?).map(<[u8]>::to_vec)
+    }
+
+    /// Bytes of the data file the map covers
+    pub fn mapped_len(&self) -> u64 {
+        self.mmap.as_ref().map_or(0, |map| map.len() as u64)
+    }
+
+    /// How record reads reach the data file (see mapping.rs)
+    pub fn map_mode(&self) -> MapMode {
+        match self.mmap.as_ref() {
+            None => MapMode::Off,
+            Some(map) if self.file_len().is_ok_and(|len| len <= map.len() as u64) => MapMode::Full,
+            Some(_) => MapMode::Partial,
+        }
+    }
+}
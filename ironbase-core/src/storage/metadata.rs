@@ -1,15 +1,53 @@
 // storage/metadata.rs
 // Metadata management for storage engine
 
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Write, Seek, SeekFrom};
+use std::collections::{HashMap, HashSet};
+use super::StorageFile;
+use std::io::{Write, Seek, SeekFrom};
 use crate::error::{Result, MongoLiteError};
 use super::{StorageEngine, Header, CollectionMeta};
 
+/// Where each collection's metadata section sits on disk, and what changed
+/// since it was written
+///
+/// Sections are `[u32 len][JSON]` records packed right after the header, so a
+/// section that changes size moves every one behind it. A flush rewrites from
+/// the first changed section on and puts the changed ones last: collections
+/// written often drift to the end of the region, and flushing them leaves the
+/// sections of the quiet ones alone.
+#[derive(Debug, Default)]
+pub(super) struct MetadataLayout {
+    /// (collection, offset) in region order
+    sections: Vec<(String, u64)>,
+    /// End of the last section
+    end: u64,
+    /// Collections changed since their section was written
+    dirty: HashSet<String>,
+    /// Header as last written
+    pub(super) header: Vec<u8>,
+    /// Data file written since the last fsync
+    pub(super) unsynced: bool,
+}
+
+impl MetadataLayout {
+    /// Layout of a region holding only the header
+    pub(super) fn empty(end: u64) -> Self {
+        MetadataLayout {
+            end,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn mark_dirty(&mut self, collection: &str) {
+        if !self.dirty.contains(collection) {
+            self.dirty.insert(collection.to_string());
+        }
+    }
+}
+
 impl StorageEngine {
     /// Load metadata from file
-    pub(super) fn load_metadata(file: &mut File) -> Result<(Header, HashMap<String, CollectionMeta>)> {
+    pub(super) fn load_metadata(file: &mut dyn StorageFile) -> Result<(Header, HashMap<String, CollectionMeta>, MetadataLayout)> {
         file.seek(SeekFrom::Start(0))?;
 
         // Header beolvasása
@@ -27,11 +65,22 @@ impl StorageEngine {
             return Err(MongoLiteError::Corruption("Invalid magic number".into()));
         }
 
+        // A newer build's file may not even parse past the header
+        super::migration::check_version(header.version)?;
+        if !super::valid_page_size(header.page_size) {
+            return Err(MongoLiteError::Corruption(format!("Invalid page size {}", header.page_size)));
+        }
+
         // Collection-ök metaadatainak beolvasása
         // FONTOS: JSON serialization használja a custom catalog_serde modult,
         // ami megőrzi a DocumentId típusinformációt [type_tag, value, offset] formátumban
         let mut collections = HashMap::new();
+        let mut layout = MetadataLayout {
+            header: header_bytes,
+            ..Default::default()
+        };
         for _ in 0..header.collection_count {
+            let offset = file.stream_position()?;
             let mut len_bytes = [0u8; 4];
             file.read_exact(&mut len_bytes)?;
             let len = u32::from_le_bytes(len_bytes) as usize;
@@ -40,15 +89,18 @@ impl StorageEngine {
             file.read_exact(&mut meta_bytes)?;
 
             let meta: CollectionMeta = serde_json::from_slice(&meta_bytes)?;
+            layout.sections.push((meta.name.clone(), offset));
             collections.insert(meta.name.clone(), meta);
         }
+        layout.end = file.stream_position()?;
+        Self::load_catalogs(file, &mut collections)?;
 
-        Ok((header, collections))
+        Ok((header, collections, layout))
     }
 
     /// Write metadata to writer
     /// Returns the offset at the end of metadata section
-    pub(super) fn write_metadata<W: Write + Seek>(
+    pub(super) fn write_metadata<W: Write + Seek + ?Sized>(
         writer: &mut W,
         header: &Header,
         collections: &HashMap<String, CollectionMeta>,
@@ -77,35 +129,181 @@ impl StorageEngine {
     }
 
     /// Flush metadata to disk with RESERVED SPACE approach
+    /// Only the header and sections that changed are written (see MetadataLayout)
     pub(super) fn flush_metadata(&mut self) -> Result<()> {
         // Use FIXED data offset = HEADER + RESERVED_METADATA_SIZE
         // This prevents documents from being overwritten when metadata grows
         let data_offset = super::DATA_START_OFFSET;
+        self.flush_writes()?;
+
+        // Changed catalog shards first: the metadata written below points at them
+        self.write_catalog_shards()?;
+
+        // Records released since the last flush are stamped free once this metadata is on disk
+        self.account_reclaimed();
 
         // Update all collection data_offset to the FIXED start position
-        for meta in self.collections.values_mut() {
-            meta.data_offset = data_offset;
-            meta.index_offset = data_offset;
+        for (name, meta) in self.collections.iter_mut() {
+            if meta.data_offset != data_offset || meta.index_offset != data_offset {
+                meta.data_offset = data_offset;
+                meta.index_offset = data_offset;
+                self.layout.mark_dirty(name);
+            }
         }
 
-        // Write metadata (will fit in reserved space or error if too large)
-        let metadata_end = Self::write_metadata(&mut self.file, &self.header, &self.collections)?;
+        let mut written = self.write_changed_metadata()?;
+
+        // Ensure file is at least DATA_START_OFFSET long (fills reserved space with zeros if needed)
+        let current_size = self.file.size()?;
+        if current_size < data_offset {
+            self.file.set_len(data_offset)?;
+            written = true;
+        }
+
+        if written || self.layout.unsynced {
+            self.file.sync_all()?;
+            self.layout.unsynced = false;
+        }
+        // The metadata on disk covers everything journaled so far
+        self.clear_journal()?;
+
+        // The catalog on disk no longer references regions released before now
+        self.reclaim_free_space()?;
+
+        Ok(())
+    }
+
+    /// Write the header and the collection sections that changed
+    /// Returns false if nothing had changed
+    fn write_changed_metadata(&mut self) -> Result<bool> {
+        let header_bytes = bincode::serialize(&self.header)
+            .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
+
+        // First section that changed or belongs to a dropped collection
+        let start = self.layout.sections.iter()
+            .position(|(name, _)| self.layout.dirty.contains(name) || !self.collections.contains_key(name))
+            .unwrap_or(self.layout.sections.len());
+        let placed: HashSet<&str> = self.layout.sections.iter().map(|(name, _)| name.as_str()).collect();
+        let mut created: Vec<String> = self.collections.keys()
+            .filter(|name| !placed.contains(name.as_str()))
+            .cloned()
+            .collect();
+
+        if start == self.layout.sections.len() && created.is_empty() && header_bytes == self.layout.header {
+            return Ok(false);
+        }
+
+        // Rewritten sections: unchanged ones first, then changed and new ones
+        let (mut order, changed): (Vec<String>, Vec<String>) = self.layout.sections[start..].iter()
+            .map(|(name, _)| name.clone())
+            .filter(|name| self.collections.contains_key(name))
+            .partition(|name| !self.layout.dirty.contains(name));
+        created.sort();
+        order.extend(changed);
+        order.extend(created);
+
+        let region_start = self.layout.sections.get(start).map_or(self.layout.end, |&(_, offset)| offset);
+        let mut buffer = Vec::new();
+        let mut sections = Vec::with_capacity(order.len());
+        for name in order {
+            // FONTOS: JSON serialization használja a custom catalog_serde modult
+            let meta_bytes = serde_json::to_vec(&self.collections[&name])?;
+            sections.push((name, region_start + buffer.len() as u64));
+            buffer.extend_from_slice(&(meta_bytes.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(&meta_bytes);
+        }
 
         // Verify metadata fits in reserved space
-        if metadata_end > data_offset {
+        let metadata_end = region_start + buffer.len() as u64;
+        if metadata_end > super::DATA_START_OFFSET {
             return Err(MongoLiteError::Corruption(
-                format!("Metadata size {} exceeds reserved space {}", metadata_end, data_offset)
+                format!("Metadata size {} exceeds reserved space {}", metadata_end, super::DATA_START_OFFSET)
             ));
         }
 
-        // Ensure file is at least DATA_START_OFFSET long (fills reserved space with zeros if needed)
-        let current_size = self.file.metadata()?.len();
-        if current_size < data_offset {
-            self.file.set_len(data_offset)?;
+        self.file.seek(SeekFrom::Start(region_start))?;
+        self.file.write_all(&buffer)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header_bytes)?;
+
+        self.layout.sections.truncate(start);
+        self.layout.sections.extend(sections);
+        self.layout.end = metadata_end;
+        self.layout.dirty.clear();
+        self.layout.header = header_bytes;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocumentId;
+    use tempfile::TempDir;
+
+    fn section_offset(storage: &StorageEngine, name: &str) -> u64 {
+        storage.layout.sections.iter().find(|(section, _)| section == name).unwrap().1
+    }
+
+    fn write(storage: &mut StorageEngine, collection: &str, id: i64) {
+        let doc = serde_json::json!({"_id": id, "_collection": collection});
+        storage.write_document(collection, &DocumentId::Int(id), &serde_json::to_vec(&doc).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_flush_rewrites_only_changed_sections() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.mlite");
+        let mut storage = StorageEngine::open(&db_path).unwrap();
+        for name in ["a", "b", "c"] {
+            storage.create_collection(name).unwrap();
+            write(&mut storage, name, 1);
         }
+        storage.flush().unwrap();
 
-        self.file.sync_all()?;
+        // Nothing changed: nothing to write
+        assert!(!storage.write_changed_metadata().unwrap());
 
-        Ok(())
+        // A changed section moves behind the unchanged ones after it
+        let a = section_offset(&storage, "a");
+        write(&mut storage, "b", 2);
+        storage.flush().unwrap();
+        let order: Vec<String> = storage.layout.sections.iter().map(|(name, _)| name.clone()).collect();
+        assert_eq!(order.last().unwrap(), "b");
+        assert_eq!(section_offset(&storage, "a"), a);
+
+        // ... and the next change to it rewrites only its own section
+        let before: Vec<(String, u64)> = storage.layout.sections.clone();
+        write(&mut storage, "b", 3);
+        storage.flush().unwrap();
+        assert_eq!(storage.layout.sections, before);
+
+        storage.drop_collection(&order[0]).unwrap();
+        storage.create_collection("d").unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = StorageEngine::open(&db_path).unwrap();
+        let mut names = storage.list_collections();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"d".to_string()));
+        assert_eq!(storage.get_collection_meta("b").unwrap().document_catalog.len(), 3);
+    }
+
+    #[test]
+    fn test_flush_syncs_only_after_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = StorageEngine::open(temp_dir.path().join("test.mlite")).unwrap();
+        storage.create_collection("a").unwrap();
+        storage.flush().unwrap();
+        assert!(!storage.layout.unsynced);
+
+        write(&mut storage, "a", 1);
+        assert!(storage.layout.unsynced);
+        assert!(storage.layout.dirty.contains("a"));
+        storage.flush().unwrap();
+        assert!(!storage.layout.unsynced);
+        assert!(storage.layout.dirty.is_empty());
     }
 }
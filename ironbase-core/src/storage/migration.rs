@@ -0,0 +1,78 @@
+// storage/migration.rs
+// On-disk format versioning and upgrades of older files
+
+use crate::error::{Result, MongoLiteError};
+use super::StorageEngine;
+
+/// On-disk format version written by this build
+///
+/// History:
+/// 1 - initial format
+/// 2 - per-collection live/dead space statistics in the metadata
+/// 3 - document catalogs in shard records instead of inline in the metadata
+pub const FORMAT_VERSION: u32 = 3;
+
+/// One step of the upgrade path: turns a version `from` file into `from + 1`
+///
+/// Steps run in order on open and the header is flushed after each one, so
+/// a step interrupted by a crash simply runs again - keep them re-runnable.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    pub apply: fn(&mut StorageEngine) -> Result<()>,
+}
+
+/// Registered migrations, one per version step
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "count live and dead space per collection",
+        apply: StorageEngine::recount_garbage,
+    },
+    Migration {
+        from: 2,
+        description: "move document catalogs into shard records",
+        apply: StorageEngine::write_catalog_shards,
+    },
+];
+
+/// Refuse files written by a newer build before anything else is read
+pub(super) fn check_version(version: u32) -> Result<()> {
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(MongoLiteError::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    Ok(())
+}
+
+impl StorageEngine {
+    /// Bring an older file up to FORMAT_VERSION
+    /// Returns the descriptions of the migrations that ran
+    pub(super) fn migrate(&mut self) -> Result<Vec<&'static str>> {
+        let mut applied = Vec::new();
+
+        while self.header.version < FORMAT_VERSION {
+            let version = self.header.version;
+            let migration = MIGRATIONS.iter()
+                .find(|migration| migration.from == version)
+                .ok_or_else(|| MongoLiteError::Corruption(
+                    format!("no migration from format version {}", version)
+                ))?;
+
+            (migration.apply)(self)?;
+
+            self.header.version = version + 1;
+            self.flush_metadata()?;
+            applied.push(migration.description);
+        }
+
+        Ok(applied)
+    }
+
+    /// On-disk format version of the open file
+    pub fn format_version(&self) -> u32 {
+        self.header.version
+    }
+}
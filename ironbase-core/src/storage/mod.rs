@@ -4,18 +4,60 @@
 mod compaction;
 mod metadata;
 mod io;
+mod repair;
+mod oplog;
+mod free_space;
+mod garbage;
+mod quota;
+mod references;
+mod migration;
+mod mvcc;
+mod catalog;
+mod journal;
+mod ids;
+mod backend;
+mod inspect;
+mod space;
+mod bloom;
+mod extents;
+mod mapping;
+mod write_buffer;
+mod in_place;
 
-use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
-use memmap2::{MmapMut, MmapOptions};
+use memmap2::Mmap;
 use serde::{Serialize, Deserialize};
 use crate::error::{Result, MongoLiteError};
-use crate::wal::WriteAheadLog;
+use crate::wal::{GroupSync, SyncThread, WriteAheadLog};
 use crate::transaction::Transaction;
+use crate::session::LsnClock;
+use std::sync::Arc;
+use metadata::MetadataLayout;
+use journal::MetadataJournal;
 
 // Re-export compaction types
-pub use compaction::{CompactionStats, CompactionConfig};
+pub use compaction::{CompactionStats, CompactionConfig, CollectionCompactionStats};
+pub use repair::RepairStats;
+pub use oplog::{OplogConfig, OPLOG_COLLECTION};
+pub use free_space::{FreeSpaceMap, MIN_FREE_REGION};
+pub use garbage::GarbageStats;
+pub use quota::CollectionQuota;
+pub use references::{OnDelete, Reference};
+pub use migration::{Migration, MIGRATIONS, FORMAT_VERSION};
+pub use mvcc::{MvccStats, Snapshot, SnapshotRegistry};
+pub use catalog::{DocumentCatalog, CATALOG_SHARD_CAPACITY};
+pub use ids::ID_RESERVATION_BLOCK;
+pub use backend::{FileSystem, StorageBackend, StorageFile};
+pub use space::{CollectionSpace, FieldSpace, SpaceReport, TOP_FIELDS};
+pub use bloom::{bloom_segment, BLOOM_SEGMENT_SIZE};
+pub use extents::MAX_EXTENTS;
+pub use mapping::{MapMode, DEFAULT_MMAP_LIMIT, MMAP_REMAP_MIN_GROWTH};
+pub use write_buffer::{DEFAULT_PREALLOCATION, WRITE_BUFFER_LIMIT};
+pub use inspect::{
+    CollectionReport, HeaderReport, IndexFileReport, InspectReport, JournalReport, KindSummary,
+    MetadataReport, RecordKind, RecordReport, WalEntryReport, WalReport,
+};
 
 /// Recovered index change from WAL (for higher-level replay)
 #[derive(Debug, Clone)]
@@ -29,8 +71,9 @@ pub struct RecoveredIndexChange {
 
 /// RESERVED SPACE for metadata at the beginning of file (after header)
 /// This ensures documents ALWAYS start at a fixed offset (HEADER_SIZE + RESERVED_METADATA_SIZE)
-/// preventing corruption during metadata growth when document_catalog grows
-pub const RESERVED_METADATA_SIZE: u64 = 256 * 1024; // 256KB reserved for metadata (supports 10K+ docs)
+/// preventing corruption during metadata growth. Document catalogs live in
+/// shard records in the data region, so their size does not count here
+pub const RESERVED_METADATA_SIZE: u64 = 256 * 1024; // 256KB reserved for metadata
 pub const HEADER_SIZE: u64 = 256; // Fixed header size
 pub const DATA_START_OFFSET: u64 = HEADER_SIZE + RESERVED_METADATA_SIZE; // Documents start here
 
@@ -50,8 +93,8 @@ impl Default for Header {
     fn default() -> Self {
         Header {
             magic: *b"MONGOLTE",
-            version: 1,
-            page_size: 4096,
+            version: FORMAT_VERSION,
+            page_size: DEFAULT_PAGE_SIZE,
             collection_count: 0,
             free_list_head: 0,
             index_section_offset: 0,
@@ -59,6 +102,121 @@ impl Default for Header {
     }
 }
 
+pub const DEFAULT_PAGE_SIZE: u32 = 4096;
+
+/// Default limit of a serialized document (the same 16 MB as MongoDB)
+pub const DEFAULT_MAX_DOCUMENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Largest document a record can hold at all (records are `[u32 len][JSON]`)
+pub const MAX_RECORD_DOCUMENT_SIZE: usize = u32::MAX as usize - 4;
+
+/// Storage configuration
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    /// I/O unit for sequential scans; power of two, 512 bytes - 1 MB (default: 4KB)
+    /// Only used when the file is created - an existing file keeps its own
+    pub page_size: u32,
+    /// Largest serialized (JSON) document accepted by writes, in bytes
+    /// (default: 16 MB). Runtime only - not stored in the file
+    pub max_document_size: usize,
+    /// Where the data file, the WAL and the journal live (default: FileSystem)
+    pub backend: Arc<dyn StorageBackend>,
+    /// Bytes of the data file reads go through a memory map for (default:
+    /// 1 GB); the rest of a larger file is read with file I/O, 0 turns the
+    /// map off. Runtime only
+    pub mmap_limit: u64,
+    /// Disk space reserved ahead of appends at a time (default: 4 MB), more
+    /// once an eighth of the file is larger; 0 turns it off. Runtime only
+    pub preallocation: u64,
+    /// Thread the WAL and data file fsyncs run on, shared with other
+    /// databases (default: None - threads of the database's own). Runtime only
+    pub sync_thread: Option<Arc<SyncThread>>,
+    /// Overwrite a document's record when its new version fits, at the cost
+    /// of a journal fsync per update (default: false - see in_place.rs).
+    /// Runtime only
+    pub in_place_updates: bool,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            page_size: DEFAULT_PAGE_SIZE,
+            max_document_size: DEFAULT_MAX_DOCUMENT_SIZE,
+            backend: Arc::new(FileSystem),
+            mmap_limit: DEFAULT_MMAP_LIMIT,
+            preallocation: DEFAULT_PREALLOCATION,
+            sync_thread: None,
+            in_place_updates: false,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_max_document_size(mut self, max_document_size: usize) -> Self {
+        self.max_document_size = max_document_size;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_mmap_limit(mut self, mmap_limit: u64) -> Self {
+        self.mmap_limit = mmap_limit;
+        self
+    }
+
+    pub fn with_preallocation(mut self, preallocation: u64) -> Self {
+        self.preallocation = preallocation;
+        self
+    }
+
+    pub fn with_sync_thread(mut self, sync_thread: Arc<SyncThread>) -> Self {
+        self.sync_thread = Some(sync_thread);
+        self
+    }
+
+    pub fn with_in_place_updates(mut self, in_place_updates: bool) -> Self {
+        self.in_place_updates = in_place_updates;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !valid_page_size(self.page_size) {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "page size {} must be a power of two between 512 and 1048576", self.page_size
+            )));
+        }
+        if self.max_document_size == 0 || self.max_document_size > MAX_RECORD_DOCUMENT_SIZE {
+            return Err(MongoLiteError::InvalidConfig(format!(
+                "max document size {} must be between 1 and {}", self.max_document_size, MAX_RECORD_DOCUMENT_SIZE
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn valid_page_size(page_size: u32) -> bool {
+    page_size.is_power_of_two() && (512..=1 << 20).contains(&page_size)
+}
+
+/// Index file of a database: `{db_path_without_.mlite}.{index_name}.idx`
+///
+/// Example: "/data/myapp.mlite" + "users_age" → "/data/myapp.users_age.idx"
+pub fn index_file_path<P: AsRef<Path>>(db_path: P, index_name: &str) -> PathBuf {
+    let mut path = db_path.as_ref().to_path_buf();
+    if path.extension().is_some_and(|extension| extension == "mlite") {
+        path.set_extension("");
+    }
+    PathBuf::from(format!("{}.{}.idx", path.display(), index_name))
+}
+
 /// Collection metaadatok
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CollectionMeta {
@@ -70,14 +228,102 @@ pub struct CollectionMeta {
 
     /// Document catalog: DocumentId -> file offset mapping
     /// This enables persistent document storage and fast retrieval
-    /// BREAKING CHANGE: Changed from HashMap<String, u64> to HashMap<DocumentId, u64>
-    /// Custom serialization preserves DocumentId type information in JSON metadata
+    /// Persisted as shard records in the data file - the metadata only says
+    /// where they are (see storage/catalog.rs and catalog_serde)
     #[serde(default, with = "crate::catalog_serde")]
-    pub document_catalog: HashMap<crate::document::DocumentId, u64>,
+    pub document_catalog: DocumentCatalog,
 
     /// Persisted index metadata for this collection
     #[serde(default)]
     pub indexes: Vec<crate::index::IndexMetadata>,
+
+    /// LSN of the last committed write to this collection
+    #[serde(default)]
+    pub last_lsn: u64,
+
+    /// Live/dead space accounting (None in files written before it was kept)
+    #[serde(default)]
+    pub garbage: Option<GarbageStats>,
+
+    /// Fields stored encrypted, and how (see encryption.rs)
+    #[serde(default)]
+    pub encrypted_fields: HashMap<String, crate::encryption::EncryptionMode>,
+
+    /// Document count / size limits (see quota.rs)
+    #[serde(default)]
+    pub quota: CollectionQuota,
+
+    /// Set if this collection is a view (see views.rs); a materialized view
+    /// stores its results as the collection's documents
+    #[serde(default)]
+    pub view: Option<crate::views::ViewDefinition>,
+
+    /// Fields the engine computes on every write (see computed.rs)
+    #[serde(default)]
+    pub computed_fields: Vec<crate::computed::ComputedField>,
+
+    /// Fields holding _ids of other collections' documents (see references.rs)
+    #[serde(default)]
+    pub references: Vec<Reference>,
+
+    /// Identity of this collection, stamped on its _id index file (empty in
+    /// files written before it was kept)
+    #[serde(default)]
+    pub uuid: String,
+
+    /// Fields with per-segment Bloom filters (see bloom.rs)
+    #[serde(default)]
+    pub bloom_fields: Vec<String>,
+
+    /// Offset ranges holding every record of this collection (see
+    /// extents.rs; None in files written before they were kept)
+    #[serde(default)]
+    pub extents: Option<Vec<(u64, u64)>>,
+
+    /// Planner statistics from the last analyze() (see statistics.rs)
+    #[serde(default)]
+    pub statistics: Option<crate::statistics::CollectionStatistics>,
+
+    /// Index files that hold their index as of the documents: index name ->
+    /// save id of the file (see IndexFileStamp::save_id). Every write
+    /// clears it; indexes without an entry are rebuilt on open.
+    #[serde(default)]
+    pub index_files: HashMap<String, String>,
+
+    /// The transaction that made the last write (kept in memory only)
+    #[serde(skip)]
+    pub last_transaction: Option<LastTransaction>,
+
+    /// Moved by writes that bypass index maintenance (replicated operations,
+    /// $out / $merge targets): open handles rebuild their indexes when it
+    /// changes. Kept in memory only - handles opened later build from the
+    /// documents anyway.
+    #[serde(skip)]
+    pub index_epoch: u64,
+}
+
+/// A transaction that wrote to a collection, and the collection before it
+/// (see CollectionMeta::last_transaction)
+#[derive(Debug, Clone)]
+pub struct LastTransaction {
+    pub id: crate::transaction::TransactionId,
+    /// LSN of the collection's last write before the transaction
+    pub previous_lsn: crate::session::Lsn,
+    /// CollectionMeta::index_files before the transaction
+    pub index_files: HashMap<String, String>,
+}
+
+impl CollectionMeta {
+    /// True if the catalog points at another record for this `_id`
+    ///
+    /// File order alone does not say which version is newest once free regions
+    /// are reused; ids outside the catalog (raw writes) still go by file order.
+    pub fn is_superseded(&self, id_value: &serde_json::Value, offset: u64) -> bool {
+        serde_json::from_value::<crate::document::DocumentId>(id_value.clone())
+            .ok()
+            .and_then(|doc_id| self.document_catalog.get(&doc_id))
+            .is_some_and(|&current| current != offset)
+    }
 }
 
 /// Index record for persistence
@@ -89,60 +335,160 @@ pub struct IndexRecord {
 
 /// Storage engine - fájl alapú tárolás
 pub struct StorageEngine {
-    file: File,
-    mmap: Option<MmapMut>,
+    file: Box<dyn StorageFile>,
+    backend: Arc<dyn StorageBackend>,
+    /// Read-only map of a prefix of the data file (see mapping.rs)
+    mmap: Option<Mmap>,
+    /// Largest prefix mapped (StorageConfig::mmap_limit)
+    mmap_limit: u64,
+    /// Times the file was mapped since open
+    remaps: u64,
     header: Header,
     collections: HashMap<String, CollectionMeta>,
     file_path: String,
     wal: WriteAheadLog,
+    /// Group commit for data file fsyncs (see wal::GroupSync)
+    data_sync: Arc<GroupSync>,
+    /// Shared thread the fsyncs run on (see StorageConfig::sync_thread)
+    sync_thread: Option<Arc<SyncThread>>,
+    oplog: OplogConfig,
+    /// Changes every time the oplog is switched on (None while it is off):
+    /// readers that saw the same epoch know no write went unlogged since
+    oplog_epoch: Option<String>,
+    /// Logical clock for causal consistency (see session.rs)
+    lsn: Arc<LsnClock>,
+    /// Reusable dead regions of the data file
+    free_space: FreeSpaceMap,
+    /// In-flight finds and aggregations (see operations.rs)
+    operations: Arc<crate::operations::OperationRegistry>,
+    /// Write hooks of every collection (see hooks.rs)
+    hooks: Arc<crate::hooks::HookRegistry>,
+    /// Operation capture (see trace.rs)
+    trace: Arc<crate::trace::TraceRecorder>,
+    /// Key for encrypted fields (runtime only - never persisted)
+    encryption_key: Option<crate::encryption::EncryptionKey>,
+    /// Default per-operation memory limit in bytes (runtime only, None = unlimited)
+    query_memory_limit: Option<usize>,
+    /// Largest serialized document writes accept (see StorageConfig)
+    max_document_size: usize,
+    /// Old document versions kept for open snapshots (see mvcc.rs)
+    versions: mvcc::VersionStore,
+    /// LSN shared by every write of the transaction being applied
+    batch_lsn: Option<crate::session::Lsn>,
+    /// On-disk metadata sections and what changed since (see metadata.rs)
+    layout: MetadataLayout,
+    /// Catalog changes since the last metadata flush (see journal.rs)
+    journal: MetadataJournal,
+    /// Highest auto-increment id reserved per collection since the last
+    /// metadata flush (see ids.rs)
+    id_reservations: HashMap<String, u64>,
+    /// Named sequences used so far: (last value allocated, reserved through)
+    sequences: HashMap<String, (u64, u64)>,
+    /// Transactions the journal marks applied (see replay_wal)
+    applied_transactions: std::collections::HashSet<crate::transaction::TransactionId>,
+    /// What replay_wal recovered, until recover_from_wal() takes it
+    wal_recovery: (Vec<Vec<crate::wal::WALEntry>>, Vec<RecoveredIndexChange>),
+    /// Built Bloom filters per collection (runtime only - see bloom.rs)
+    blooms: HashMap<String, bloom::CollectionBlooms>,
+    /// Appends of the current write batch (see write_buffer.rs)
+    write_buffer: write_buffer::WriteBuffer,
+    /// Updates overwrite records they fit in (see in_place.rs)
+    in_place_updates: bool,
+    /// The files were deleted (see remove_files)
+    dropped: bool,
 }
 
 impl StorageEngine {
     /// Adatbázis megnyitása vagy létrehozása
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, &StorageConfig::default())
+    }
+
+    /// Open or create with a configuration
+    ///
+    /// Files from an older format version are migrated in place; files from a
+    /// newer one are refused without being touched.
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: &StorageConfig) -> Result<Self> {
+        config.validate()?;
+
         let path_str = path.as_ref().to_string_lossy().to_string();
-        let exists = path.as_ref().exists();
+        let backend = Arc::clone(&config.backend);
+        let exists = backend.exists(path.as_ref());
         
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&path)?;
+        let mut file = backend.open(path.as_ref())?;
         
-        let (header, collections) = if exists && file.metadata()?.len() > 0 {
+        let (header, collections, layout) = if exists && file.size()? > 0 {
             // Meglévő adatbázis betöltése
-            Self::load_metadata(&mut file)?
+            Self::load_metadata(&mut *file)?
         } else {
             // Új adatbázis inicializálása
-            let header = Header::default();
+            let header = Header {
+                page_size: config.page_size,
+                ..Header::default()
+            };
             let collections = HashMap::new();
-            let _ = Self::write_metadata(&mut file, &header, &collections)?;
-            (header, collections)
+            let end = Self::write_metadata(&mut *file, &header, &collections)?;
+            (header, collections, MetadataLayout::empty(end))
         };
         
-        // Memory-mapped fájl (ha elég kicsi a fájl)
-        let mmap = if file.metadata()?.len() < 1_000_000_000 {  // 1GB alatt használjuk az mmap-et
-            let mmap = unsafe { MmapOptions::new().map_mut(&file).ok() };
-            mmap
-        } else {
-            None
-        };
-
         // WAL fájl megnyitása
         let wal_path = PathBuf::from(&path_str).with_extension("wal");
-        let wal = WriteAheadLog::open(wal_path)?;
+        let wal = WriteAheadLog::open_on(Arc::clone(&backend), wal_path, config.sync_thread.clone())?;
 
-        let storage = StorageEngine {
+        // LSN continues from the highest one persisted in collection metadata
+        let last_lsn = collections.values().map(|meta| meta.last_lsn).max().unwrap_or(0);
+
+        // Metadata journal: fsynced along with the data file
+        let journal = MetadataJournal::open(&*backend, &PathBuf::from(&path_str).with_extension("journal"))?;
+        let data_sync = journal.group_sync(&*file, config.sync_thread.as_ref())?;
+
+        let free_list_head = header.free_list_head;
+        let mut storage = StorageEngine {
             file,
-            mmap,
+            backend,
+            mmap: None,
+            mmap_limit: config.mmap_limit,
+            remaps: 0,
             header,
             collections,
             file_path: path_str,
             wal,
+            data_sync,
+            sync_thread: config.sync_thread.clone(),
+            oplog: OplogConfig::default(),
+            oplog_epoch: None,
+            lsn: Arc::new(LsnClock::new(last_lsn)),
+            free_space: FreeSpaceMap::default(),
+            operations: Arc::new(crate::operations::OperationRegistry::new()),
+            hooks: Arc::new(crate::hooks::HookRegistry::new()),
+            trace: Arc::new(crate::trace::TraceRecorder::new()),
+            encryption_key: None,
+            query_memory_limit: None,
+            max_document_size: config.max_document_size,
+            versions: mvcc::VersionStore::default(),
+            batch_lsn: None,
+            layout,
+            journal,
+            id_reservations: HashMap::new(),
+            sequences: HashMap::new(),
+            applied_transactions: std::collections::HashSet::new(),
+            wal_recovery: Default::default(),
+            blooms: HashMap::new(),
+            write_buffer: write_buffer::WriteBuffer::new(config.preallocation),
+            in_place_updates: config.in_place_updates,
+            dropped: false,
         };
-
-        // NOTE: WAL recovery is now handled by DatabaseCore::open() for index atomicity
-        // This allows Database to coordinate index recovery across all collections
+        storage.drop_torn_tail()?;
+        storage.load_free_space(free_list_head)?;
+        storage.replay_journal()?;
+        storage.replay_wal()?;
+        // The metadata now covers the journal and the WAL
+        storage.flush_metadata()?;
+        storage.migrate()?;
+        // Map the file as recovery left it (see mapping.rs)
+        storage.remap();
+
+        // NOTE: recovered index changes are applied by DatabaseCore::open() (see recover_from_wal)
 
         Ok(storage)
     }
@@ -161,11 +507,26 @@ impl StorageEngine {
             data_offset: 0,  // Will be set correctly by flush_metadata
             index_offset: 0,
             last_id: 0,
-            document_catalog: HashMap::new(),  // Initialize empty catalog
+            document_catalog: DocumentCatalog::default(),  // Initialize empty catalog
             indexes: Vec::new(),  // Initialize empty index list
+            last_lsn: 0,
+            garbage: Some(GarbageStats::default()),
+            encrypted_fields: HashMap::new(),
+            quota: CollectionQuota::default(),
+            view: None,
+            computed_fields: Vec::new(),
+            references: Vec::new(),
+            uuid: uuid::Uuid::new_v4().to_string(),
+            bloom_fields: Vec::new(),
+            extents: Some(Vec::new()),
+            statistics: None,
+            index_files: HashMap::new(),
+            last_transaction: None,
+            index_epoch: 0,
         };
 
         self.collections.insert(name.to_string(), meta);
+        self.layout.mark_dirty(name);
         self.header.collection_count += 1;
 
         // Flush metadata with proper convergence
@@ -174,6 +535,29 @@ impl StorageEngine {
         Ok(())
     }
     
+    /// Create collection `meta.name` with the settings of `meta` - indexes,
+    /// encryption, quota, view, computed fields, references, id counter -
+    /// but none of its documents (copies of a collection, see backup.rs)
+    pub fn create_collection_like(&mut self, meta: &CollectionMeta) -> Result<()> {
+        self.create_collection(&meta.name)?;
+        if let Some(created) = self.get_collection_meta_mut(&meta.name) {
+            *created = CollectionMeta {
+                document_count: 0,
+                data_offset: created.data_offset,
+                index_offset: created.index_offset,
+                document_catalog: DocumentCatalog::default(),
+                garbage: Some(GarbageStats::default()),
+                uuid: created.uuid.clone(),
+                extents: Some(Vec::new()),
+                index_files: HashMap::new(),
+                last_transaction: None,
+                index_epoch: 0,
+                ..meta.clone()
+            };
+        }
+        self.flush_metadata()
+    }
+
     /// Collection törlése
     pub fn drop_collection(&mut self, name: &str) -> Result<()> {
         if !self.collections.contains_key(name) {
@@ -181,7 +565,12 @@ impl StorageEngine {
         }
 
         self.collections.remove(name);
+        self.drop_blooms(Some(name));
         self.header.collection_count -= 1;
+        if name == crate::sequence::SEQUENCES_COLLECTION {
+            self.sequences.clear();
+        }
+        self.remove_statistics_document(name)?;
 
         // Flush metadata with proper convergence
         self.flush_metadata()?;
@@ -189,6 +578,61 @@ impl StorageEngine {
         Ok(())
     }
     
+    /// Store the planner statistics of `collection` (see statistics.rs): in
+    /// its metadata and as its document of STATS_COLLECTION
+    pub fn set_statistics(&mut self, collection: &str, statistics: crate::statistics::CollectionStatistics) -> Result<()> {
+        use crate::statistics::STATS_COLLECTION;
+
+        let doc = statistics.to_document(collection);
+        self.get_collection_meta_mut(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?
+            .statistics = Some(statistics);
+        if self.get_collection_meta(STATS_COLLECTION).is_none() {
+            self.create_collection(STATS_COLLECTION)?;
+        }
+        self.write_document(STATS_COLLECTION, &crate::document::DocumentId::String(collection.to_string()), &serde_json::to_vec(&doc)?)?;
+        self.flush()
+    }
+
+    /// Drop the STATS_COLLECTION document of a dropped collection
+    fn remove_statistics_document(&mut self, collection: &str) -> Result<()> {
+        use crate::statistics::STATS_COLLECTION;
+
+        let doc_id = crate::document::DocumentId::String(collection.to_string());
+        let stored = self.get_collection_meta(STATS_COLLECTION)
+            .is_some_and(|meta| meta.document_catalog.get(&doc_id).is_some());
+        if stored {
+            self.write_tombstone(STATS_COLLECTION, &doc_id)?;
+        }
+        Ok(())
+    }
+
+    /// Delete the database's files - data file, WAL, journal and index
+    /// files - once everything pending is flushed. Afterwards flush() does
+    /// nothing and compaction is refused, so no file is written under the
+    /// database's name again.
+    pub fn remove_files(&mut self) -> Result<()> {
+        self.flush()?;
+        self.dropped = true;
+
+        let path = PathBuf::from(&self.file_path);
+        for file in [path.with_extension("wal"), path.with_extension("journal"), path] {
+            if self.backend.exists(&file) {
+                self.backend.remove(&file)?;
+            }
+        }
+        // The _id index is not in the metadata, but transactions write its file too
+        let index_names = self.collections.iter()
+            .flat_map(|(name, meta)| std::iter::once(format!("{}_id", name)).chain(meta.indexes.iter().map(|index| index.name.clone())));
+        for index_name in index_names {
+            match std::fs::remove_file(index_file_path(&self.file_path, &index_name)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     /// Collection-ök listája
     pub fn list_collections(&self) -> Vec<String> {
         self.collections.keys().cloned().collect()
@@ -200,36 +644,160 @@ impl StorageEngine {
     }
 
     /// Collection metaadatok lekérése (mutable)
-    /// Metadata changes are persisted only when flush() is called (typically on database close)
+    /// Metadata changes are persisted only when flush() is called (typically on database close);
+    /// catalog changes made through write_document / write_tombstone are journaled until then
+    /// The collection counts as changed: the next flush rewrites its metadata
     pub fn get_collection_meta_mut(&mut self, name: &str) -> Option<&mut CollectionMeta> {
-        self.collections.get_mut(name)
+        let meta = self.collections.get_mut(name)?;
+        self.layout.mark_dirty(name);
+        Some(meta)
     }
 
     /// Flush - változások lemezre írása (beleértve a metadata-t is)
+    /// Writes only the metadata that changed, and syncs only if anything was written
     pub fn flush(&mut self) -> Result<()> {
+        if self.dropped {
+            return Ok(());
+        }
+        self.release_sequence_reservations()?;
         // Flush metadata to disk with proper convergence
         self.flush_metadata()?;
-        self.file.sync_all()?;
+        self.flush_writes()?;
+        if self.layout.unsynced {
+            self.file.sync_all()?;
+            self.layout.unsynced = false;
+        }
         Ok(())
     }
 
     /// Get mutable reference to the database file (for index persistence)
-    pub fn get_file_mut(&mut self) -> &mut File {
-        &mut self.file
+    pub fn get_file_mut(&mut self) -> &mut dyn StorageFile {
+        self.layout.unsynced = true;
+        &mut *self.file
+    }
+
+    /// Shared LSN clock
+    pub fn lsn_clock(&self) -> Arc<LsnClock> {
+        Arc::clone(&self.lsn)
+    }
+
+    /// LSN of the last committed write to `collection` (0 if none / unknown)
+    pub fn collection_lsn(&self, collection: &str) -> u64 {
+        self.collections.get(collection).map_or(0, |meta| meta.last_lsn)
+    }
+
+    /// Advance the LSN for a committed write to `collection`
+    pub fn advance_lsn(&mut self, collection: &str) -> u64 {
+        let lsn = self.lsn.advance();
+        if let Some(meta) = self.get_collection_meta_mut(collection) {
+            meta.last_lsn = lsn;
+            meta.last_transaction = None;
+            meta.index_files.clear();
+        }
+        lsn
+    }
+
+    /// Mark the open handles' indexes of `collection` stale, after a write
+    /// that did not go through them (see CollectionMeta::index_epoch)
+    pub fn mark_indexes_stale(&mut self, collection: &str) {
+        if let Some(meta) = self.get_collection_meta_mut(collection) {
+            meta.index_epoch += 1;
+        }
+    }
+
+    /// Set the default memory limit of queries, scans and aggregations
+    pub fn set_query_memory_limit(&mut self, limit: Option<usize>) {
+        self.query_memory_limit = limit;
+    }
+
+    pub fn query_memory_limit(&self) -> Option<usize> {
+        self.query_memory_limit
+    }
+
+    /// Registry of running operations, shared with every collection handle
+    pub fn operations(&self) -> Arc<crate::operations::OperationRegistry> {
+        Arc::clone(&self.operations)
+    }
+
+    /// Write hooks, shared with every collection handle
+    pub fn hooks(&self) -> Arc<crate::hooks::HookRegistry> {
+        Arc::clone(&self.hooks)
+    }
+
+    /// Operation capture, shared with every collection handle
+    pub fn trace(&self) -> Arc<crate::trace::TraceRecorder> {
+        Arc::clone(&self.trace)
+    }
+
+    pub fn set_encryption_key(&mut self, key: Option<crate::encryption::EncryptionKey>) {
+        self.encryption_key = key;
+    }
+
+    /// Encryptor for the encrypted fields of `collection` with the current key
+    pub fn field_encryptor(&self, collection: &str) -> crate::encryption::FieldEncryptor {
+        let fields = self.collections.get(collection)
+            .map(|meta| meta.encrypted_fields.clone())
+            .unwrap_or_default();
+        crate::encryption::FieldEncryptor::new(fields, self.encryption_key.clone())
+    }
+
+    /// Parsed computed fields of `collection` (definitions were validated
+    /// when added, so a parse failure here means a damaged catalog)
+    pub fn computed_fields(&self, collection: &str) -> Result<crate::computed::ComputedFields> {
+        self.collections.get(collection)
+            .map(|meta| crate::computed::ComputedFields::new(&meta.computed_fields))
+            .unwrap_or_else(|| Ok(Default::default()))
+    }
+
+    pub fn max_document_size(&self) -> usize {
+        self.max_document_size
+    }
+
+    /// DocumentTooLarge if a serialized document of `size` bytes may not be
+    /// written; callers check before touching indexes
+    pub fn check_document_size(&self, size: usize) -> Result<()> {
+        if size > self.max_document_size {
+            return Err(MongoLiteError::DocumentTooLarge { size, max: self.max_document_size });
+        }
+        Ok(())
+    }
+
+    /// Path of the `.idx` file of an index of this database
+    pub fn index_file_path(&self, index_name: &str) -> PathBuf {
+        index_file_path(&self.file_path, index_name)
     }
 
     /// Statisztikák
     pub fn stats(&self) -> serde_json::Value {
         serde_json::json!({
             "file_path": self.file_path,
-            "file_size": self.file.metadata().map(|m| m.len()).unwrap_or(0),
+            "file_size": self.file.size().unwrap_or(0),
             "page_size": self.header.page_size,
+            "max_document_size": self.max_document_size,
+            "format_version": self.header.version,
             "collection_count": self.header.collection_count,
+            "wal_fsyncs": self.wal.group_sync().fsync_count(),
+            "data_fsyncs": self.data_sync.fsync_count(),
+            "data_writes": self.data_writes(),
+            "free_bytes": self.free_space.free_bytes(),
+            "free_regions": self.free_space.regions().count(),
+            "mmap": {
+                "mode": self.map_mode(),
+                "mapped_bytes": self.mapped_len(),
+                "limit": self.mmap_limit,
+                "remaps": self.remaps,
+            },
             "collections": self.collections.iter().map(|(name, meta)| {
+                let garbage = meta.garbage.unwrap_or_default();
                 serde_json::json!({
                     "name": name,
                     "document_count": meta.document_count,
                     "last_id": meta.last_id,
+                    "tombstone_count": garbage.tombstones,
+                    "live_bytes": garbage.live_bytes,
+                    "dead_records": garbage.dead_records,
+                    "dead_bytes": garbage.dead_bytes,
+                    "dead_ratio": garbage.dead_ratio(),
                 })
             }).collect::<Vec<_>>(),
         })
@@ -237,7 +805,37 @@ impl StorageEngine {
 
     /// Commit a transaction (9-step atomic operation)
     /// This is the core of ACD guarantee
+    ///
+    /// Runs every step under the caller's lock. Concurrent committers should
+    /// use the split form instead (see DatabaseCore::commit_transaction):
+    /// `log_transaction`, WAL `sync_to` outside the lock, `apply_transaction`,
+    /// data `sync_to` outside the lock, then `mark_committed`.
     pub fn commit_transaction(&mut self, transaction: &mut Transaction) -> Result<()> {
+        let durable = transaction.durability() == crate::transaction::Durability::Durable;
+        let wal_ticket = self.log_transaction(transaction)?;
+
+        // Step 4: Fsync WAL (durability guarantee; relaxed commits leave it to the sync thread)
+        if durable {
+            self.wal.group_sync().sync_to(wal_ticket)?;
+        }
+
+        let data_ticket = self.apply_transaction(transaction)?;
+
+        // Step 8: Fsync storage file
+        if durable {
+            self.data_sync.sync_to(data_ticket)?;
+        }
+
+        // Step 9: Mark transaction as committed
+        transaction.mark_committed()?;
+
+        Ok(())
+    }
+
+    /// Commit steps 1-3: append the transaction to the WAL (not yet fsynced)
+    ///
+    /// Returns the WAL group-commit ticket to wait on with `wal_sync().sync_to()`
+    pub fn log_transaction(&mut self, transaction: &Transaction) -> Result<u64> {
         use crate::wal::{WALEntry, WALEntryType};
 
         if !transaction.is_active() {
@@ -257,8 +855,8 @@ impl StorageEngine {
         }
 
         // Step 2.5: Write index changes to WAL (for two-phase commit recovery)
-        // Each index change is written as an IndexChange entry
-        // Format: {collection: string, index_name: string, operation: Insert|Delete, key: IndexKey, doc_id: DocumentId}
+        // One IndexChange entry per index, holding all of its changes
+        // Format: {collection: string, index_name: string, changes: [{operation: Insert|Delete, key: IndexKey, doc_id: DocumentId}]}
         // Extract collection name from first operation (all operations in a transaction are for the same collection)
         let collection_name = transaction.operations()
             .first()
@@ -269,40 +867,52 @@ impl StorageEngine {
             });
 
         for (index_name, changes) in transaction.index_changes() {
-            for change in changes {
-                // Serialize index change to JSON (now includes collection name)
-                let change_data = serde_json::json!({
-                    "collection": collection_name.as_ref().unwrap_or(&"unknown".to_string()),
-                    "index_name": index_name,
+            let changes: Vec<serde_json::Value> = changes.iter()
+                .map(|change| serde_json::json!({
                     "operation": match change.operation {
                         crate::transaction::IndexOperation::Insert => "Insert",
                         crate::transaction::IndexOperation::Delete => "Delete",
                     },
                     "key": change.key,
                     "doc_id": change.doc_id,
-                });
+                }))
+                .collect();
+            let change_data = serde_json::json!({
+                "collection": collection_name.as_ref().unwrap_or(&"unknown".to_string()),
+                "index_name": index_name,
+                "changes": changes,
+            });
 
-                let change_json = serde_json::to_string(&change_data)
-                    .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
+            let change_json = serde_json::to_string(&change_data)
+                .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
 
-                let index_entry = WALEntry::new(
-                    transaction.id,
-                    WALEntryType::IndexChange,
-                    change_json.as_bytes().to_vec()
-                );
-                self.wal.append(&index_entry)?;
-            }
+            let index_entry = WALEntry::new(
+                transaction.id,
+                WALEntryType::IndexChange,
+                change_json.as_bytes().to_vec()
+            );
+            self.wal.append(&index_entry)?;
         }
 
         // Step 3: Write COMMIT marker to WAL
         let commit_entry = WALEntry::new(transaction.id, WALEntryType::Commit, vec![]);
         self.wal.append(&commit_entry)?;
 
-        // Step 4: Fsync WAL (durability guarantee)
-        self.wal.flush()?;
+        Ok(self.wal.group_sync().register())
+    }
 
-        // Step 5: Apply operations to storage
-        self.apply_operations(transaction)?;
+    /// Commit steps 5-7: apply a logged transaction to the data file
+    ///
+    /// Call only once the WAL ticket is durable. Returns the data group-commit
+    /// ticket to wait on with `data_sync().sync_to()`.
+    pub fn apply_transaction(&mut self, transaction: &Transaction) -> Result<u64> {
+        // Step 5: Apply operations to storage - all of them commit at one LSN,
+        // so a snapshot sees either the whole transaction or none of it
+        self.batch_lsn = Some(self.lsn.current() + 1);
+        let applied = self.write_batch(|storage| storage.apply_operations(transaction));
+        self.batch_lsn = None;
+        applied?;
+        self.journal_applied(transaction.id)?;
 
         // Step 6: Two-Phase Commit for Index Changes
         // NOTE: Index changes are written to WAL in Step 2.5 above.
@@ -319,28 +929,68 @@ impl StorageEngine {
         //
         // Phase 2 (COMMIT): Atomic rename temp → final
         //   - For each temp: BPlusTree::commit_prepared_changes(temp_path, final_path)
-        //   - POSIX rename() guarantees atomicity
+        //   - durable_fs::atomic_replace guarantees atomicity on every platform
         //
         // CRASH RECOVERY (implemented in Step 4):
         // - WAL recovery replays IndexChange entries
-        // - Detects uncommitted temp files and cleans up
+        // - Temp files of committed transactions are committed on open, the
+        //   others deleted (see recover_prepared_indexes)
         //
         // TODO (Steps 4-6): Implement full two-phase commit at Database/CollectionCore level
 
+        // Step 6.5: Record operations in the oplog (no-op when disabled)
+        if self.oplog_enabled() {
+            for operation in transaction.operations() {
+                let (op, collection, doc_id, delta) = match operation {
+                    crate::transaction::Operation::Insert { collection, doc_id, doc } =>
+                        ("insert", collection, doc_id, doc.clone()),
+                    crate::transaction::Operation::Update { collection, doc_id, new_doc, .. } =>
+                        ("update", collection, doc_id, new_doc.clone()),
+                    crate::transaction::Operation::Delete { collection, doc_id, .. } =>
+                        ("delete", collection, doc_id, serde_json::Value::Null),
+                };
+                self.log_operation(op, collection, doc_id, delta)?;
+            }
+        }
+
+        // Step 6.6: One LSN for the whole transaction
+        let touched: std::collections::HashSet<&str> = transaction.operations().iter().map(|op| match op {
+            crate::transaction::Operation::Insert { collection, .. } => collection.as_str(),
+            crate::transaction::Operation::Update { collection, .. } => collection.as_str(),
+            crate::transaction::Operation::Delete { collection, .. } => collection.as_str(),
+        }).collect();
+        if !touched.is_empty() {
+            let lsn = self.lsn.advance();
+            for collection in touched {
+                if let Some(meta) = self.get_collection_meta_mut(collection) {
+                    meta.last_transaction = Some(LastTransaction {
+                        id: transaction.id,
+                        previous_lsn: meta.last_lsn,
+                        index_files: std::mem::take(&mut meta.index_files),
+                    });
+                    meta.last_lsn = lsn;
+                }
+            }
+        }
+
         // Step 7: Apply metadata changes
         for metadata_change in transaction.metadata_changes() {
-            if let Some(meta) = self.collections.get_mut(&metadata_change.collection) {
-                meta.last_id = metadata_change.last_id as u64;
+            if self.get_collection_meta(&metadata_change.collection).is_some() {
+                self.claim_ids(&metadata_change.collection, metadata_change.last_id.max(0) as u64)?;
             }
         }
 
-        // Step 8: Fsync storage file
-        self.file.sync_all()?;
+        Ok(self.data_sync.register())
+    }
 
-        // Step 9: Mark transaction as committed
-        transaction.mark_committed()?;
+    /// Group-commit handle for WAL fsyncs
+    pub fn wal_sync(&self) -> Arc<GroupSync> {
+        self.wal.group_sync()
+    }
 
-        Ok(())
+    /// Group-commit handle for data file fsyncs
+    pub fn data_sync(&self) -> Arc<GroupSync> {
+        Arc::clone(&self.data_sync)
     }
 
     /// Rollback a transaction (discard all buffered operations)
@@ -366,30 +1016,24 @@ impl StorageEngine {
     fn apply_operations(&mut self, transaction: &Transaction) -> Result<()> {
         use crate::transaction::Operation;
 
+        // Same primitives as the non-transactional writes, so committed
+        // documents enter the catalog and are visible to finds
         for operation in transaction.operations() {
             match operation {
-                Operation::Insert { collection: _, doc_id: _, doc } => {
-                    // Serialize and write document to storage
+                Operation::Insert { collection, doc_id, doc } => {
                     let doc_json = serde_json::to_string(doc)
                         .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                    self.write_data(doc_json.as_bytes())?;
+                    self.write_document(collection, doc_id, doc_json.as_bytes())?;
                 }
-                Operation::Update { collection: _, doc_id: _, old_doc: _, new_doc } => {
-                    // Write new version of document (append-only)
+                Operation::Update { collection, doc_id, old_doc: _, new_doc } => {
+                    // The new version supersedes the old one in the catalog
                     let doc_json = serde_json::to_string(new_doc)
                         .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                    self.write_data(doc_json.as_bytes())?;
+                    self.write_document(collection, doc_id, doc_json.as_bytes())?;
                 }
                 Operation::Delete { collection, doc_id, old_doc: _ } => {
-                    // Write tombstone marker with collection info
-                    let tombstone = serde_json::json!({
-                        "_id": doc_id,
-                        "_collection": collection,
-                        "_tombstone": true
-                    });
-                    let tombstone_json = serde_json::to_string(&tombstone)
-                        .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                    self.write_data(tombstone_json.as_bytes())?;
+                    // Tombstone + catalog removal
+                    self.write_tombstone(collection, doc_id)?;
                 }
             }
         }
@@ -397,51 +1041,49 @@ impl StorageEngine {
         Ok(())
     }
 
-    /// Recover from WAL after crash
+    /// Committed transactions and index changes recovered from the WAL when
+    /// the database was opened (see replay_wal), for higher-level recovery
     ///
-    /// Returns (committed_transactions, index_changes) for higher-level recovery
+    /// Returns (committed_transactions, index_changes); empty on later calls.
     pub fn recover_from_wal(&mut self) -> Result<(Vec<Vec<crate::wal::WALEntry>>, Vec<RecoveredIndexChange>)> {
-        let recovered = self.wal.recover()?;
-
-        if recovered.is_empty() {
-            return Ok((vec![], vec![]));
-        }
+        Ok(std::mem::take(&mut self.wal_recovery))
+    }
 
+    /// Apply the committed transactions in the WAL that the journal does not
+    /// mark applied - a crash between the WAL fsync and the data fsync
+    ///
+    /// The others are in the catalog already (replayed from the journal or
+    /// flushed), and replaying them would undo later writes. A collection
+    /// dropped since is skipped. The WAL is emptied by the next metadata flush.
+    fn replay_wal(&mut self) -> Result<()> {
+        let recovered = self.wal.recover()?;
         let mut all_index_changes = Vec::new();
 
-        // Replay each committed transaction
         for tx_entries in &recovered {
-            // Deserialize operations from WAL entries
+            let Some(tx_id) = tx_entries.first().map(|entry| entry.transaction_id) else {
+                continue;
+            };
+            if self.applied_transactions.contains(&tx_id) {
+                continue;
+            }
+
+            let mut transaction = Transaction::new(tx_id);
             for entry in tx_entries {
                 match entry.entry_type {
                     crate::wal::WALEntryType::Operation => {
                         let op_str = std::str::from_utf8(&entry.data)
                             .map_err(|e| MongoLiteError::Serialization(format!("UTF-8 error: {}", e)))?;
                         let operation: crate::transaction::Operation = serde_json::from_str(op_str)?;
-
-                        // Apply operation to storage
-                        match operation {
-                            crate::transaction::Operation::Insert { collection: _, doc_id: _, doc } => {
-                                let doc_json = serde_json::to_string(&doc)
-                                    .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                                self.write_data(doc_json.as_bytes())?;
-                            }
-                            crate::transaction::Operation::Update { collection: _, doc_id: _, old_doc: _, new_doc } => {
-                                let doc_json = serde_json::to_string(&new_doc)
-                                    .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                                self.write_data(doc_json.as_bytes())?;
-                            }
-                            crate::transaction::Operation::Delete { collection, doc_id, old_doc: _ } => {
-                                let tombstone = serde_json::json!({
-                                    "_id": doc_id,
-                                    "_collection": collection,
-                                    "_tombstone": true
-                                });
-                                let tombstone_json = serde_json::to_string(&tombstone)
-                                    .map_err(|e| MongoLiteError::Serialization(e.to_string()))?;
-                                self.write_data(tombstone_json.as_bytes())?;
-                            }
+                        let (crate::transaction::Operation::Insert { collection, doc_id, .. }
+                            | crate::transaction::Operation::Update { collection, doc_id, .. }
+                            | crate::transaction::Operation::Delete { collection, doc_id, .. }) = &operation;
+                        let Some(meta) = self.collections.get_mut(collection) else {
+                            continue;
+                        };
+                        if let crate::document::DocumentId::Int(id) = doc_id {
+                            meta.last_id = meta.last_id.max((*id).max(0) as u64);
                         }
+                        transaction.add_operation(operation)?;
                     }
                     crate::wal::WALEntryType::IndexChange => {
                         // Parse index change from JSON
@@ -460,32 +1102,86 @@ impl StorageEngine {
                             .ok_or_else(|| MongoLiteError::Serialization("Missing index_name".to_string()))?
                             .to_string();
 
-                        let operation = match change_json["operation"].as_str() {
-                            Some("Insert") => crate::transaction::IndexOperation::Insert,
-                            Some("Delete") => crate::transaction::IndexOperation::Delete,
-                            _ => return Err(MongoLiteError::Serialization("Invalid operation".to_string())),
+                        // A batch of changes, or a single one (WALs written before batching)
+                        let changes = match change_json["changes"].as_array() {
+                            Some(changes) => changes.iter().collect(),
+                            None => vec![&change_json],
                         };
-
-                        let key: crate::transaction::IndexKey = serde_json::from_value(change_json["key"].clone())?;
-                        let doc_id: crate::document::DocumentId = serde_json::from_value(change_json["doc_id"].clone())?;
-
-                        all_index_changes.push(RecoveredIndexChange {
-                            collection,
-                            index_name,
-                            operation,
-                            key,
-                            doc_id,
-                        });
+                        for change in changes {
+                            let operation = match change["operation"].as_str() {
+                                Some("Insert") => crate::transaction::IndexOperation::Insert,
+                                Some("Delete") => crate::transaction::IndexOperation::Delete,
+                                _ => return Err(MongoLiteError::Serialization("Invalid operation".to_string())),
+                            };
+
+                            let key: crate::transaction::IndexKey = serde_json::from_value(change["key"].clone())?;
+                            let doc_id: crate::document::DocumentId = serde_json::from_value(change["doc_id"].clone())?;
+
+                            all_index_changes.push(RecoveredIndexChange {
+                                collection: collection.clone(),
+                                index_name: index_name.clone(),
+                                operation,
+                                key,
+                                doc_id,
+                            });
+                        }
                     }
                     _ => {}  // Skip Begin, Commit, Abort markers
                 }
             }
+            self.apply_operations(&transaction)?;
         }
 
-        // Clear WAL after successful recovery
-        self.wal.clear()?;
+        // Transactions the WAL or the journal shows committed
+        let committed: std::collections::HashSet<u64> = recovered.iter()
+            .filter_map(|tx_entries| tx_entries.first().map(|entry| entry.transaction_id))
+            .chain(self.applied_transactions.iter().copied())
+            .collect();
+        self.recover_prepared_indexes(&committed)?;
+
+        self.wal_recovery = (recovered, all_index_changes);
+        Ok(())
+    }
+
+    /// Commit or delete the index files a crash left prepared (`.idx.tmp`),
+    /// by whether their transaction is in `committed` (see
+    /// BPlusTree::recover_prepared_changes)
+    fn recover_prepared_indexes(&self, committed: &std::collections::HashSet<u64>) -> Result<()> {
+        // The index file of an empty index name ends in the prefix: "<db>..idx"
+        let template = index_file_path(&self.file_path, "");
+        let Some(prefix) = template.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".idx"))
+            .map(str::to_string)
+        else {
+            return Ok(());
+        };
+        let directory = template.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
 
-        Ok((recovered, all_index_changes))
+        for entry in entries {
+            let temp_path = entry?.path();
+            let prepared = temp_path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".idx.tmp"));
+            if !prepared {
+                continue;
+            }
+            let final_path = temp_path.with_extension("");
+            match crate::index::BPlusTree::recover_prepared_changes(&temp_path, &final_path, |tx| committed.contains(&tx)) {
+                Ok(true) => eprintln!("WARN: Committed prepared index file {:?} left by a crash", temp_path),
+                Ok(false) => eprintln!("WARN: Deleted prepared index file {:?} of an uncommitted transaction", temp_path),
+                Err(e) => {
+                    eprintln!("WARN: Could not recover prepared index file {:?}: {:?} - deleting it", temp_path, e);
+                    let _ = std::fs::remove_file(&temp_path);
+                }
+            }
+        }
+        Ok(())
     }
 
 }
@@ -516,8 +1212,8 @@ mod tests {
         let (_temp, storage) = setup_test_db();
 
         assert_eq!(storage.header.magic, *b"MONGOLTE");
-        assert_eq!(storage.header.version, 1);
-        assert_eq!(storage.header.page_size, 4096);
+        assert_eq!(storage.header.version, FORMAT_VERSION);
+        assert_eq!(storage.header.page_size, DEFAULT_PAGE_SIZE);
         assert_eq!(storage.header.collection_count, 0);
         assert_eq!(storage.collections.len(), 0);
     }
@@ -834,8 +1530,8 @@ mod tests {
         let header = Header::default();
 
         assert_eq!(header.magic, *b"MONGOLTE");
-        assert_eq!(header.version, 1);
-        assert_eq!(header.page_size, 4096);
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert_eq!(header.page_size, DEFAULT_PAGE_SIZE);
         assert_eq!(header.collection_count, 0);
         assert_eq!(header.free_list_head, 0);
     }
@@ -0,0 +1,323 @@
+// storage/mvcc.rs
+// Multi-version concurrency control over the append-only data file
+//
+// A write never touches the record it supersedes - the catalog simply points
+// at the new one. While read snapshots are open, the superseded record is kept
+// as an old version stamped with the LSN of the commit that replaced it,
+// instead of going to the free-space map. A snapshot taken at LSN S sees, for
+// every document, the oldest version replaced after S (or the current one if
+// none was). Versions replaced at or before the oldest open snapshot are
+// visible to nobody; they are pruned and their regions released.
+//
+// Old versions live in memory only: snapshots do not survive a restart, so
+// neither do the versions kept for them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use parking_lot::Mutex;
+
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::session::Lsn;
+use super::StorageEngine;
+
+/// Open read snapshots, by LSN (with a count for snapshots sharing one)
+#[derive(Debug, Default)]
+pub struct SnapshotRegistry {
+    active: Mutex<BTreeMap<Lsn, usize>>,
+}
+
+impl SnapshotRegistry {
+    fn register(&self, lsn: Lsn) {
+        *self.active.lock().entry(lsn).or_insert(0) += 1;
+    }
+
+    fn release(&self, lsn: Lsn) {
+        let mut active = self.active.lock();
+        if let Some(count) = active.get_mut(&lsn) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&lsn);
+            }
+        }
+    }
+
+    /// LSN of the oldest open snapshot
+    pub fn oldest(&self) -> Option<Lsn> {
+        self.active.lock().keys().next().copied()
+    }
+
+    /// Number of open snapshots
+    pub fn len(&self) -> usize {
+        self.active.lock().values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.lock().is_empty()
+    }
+}
+
+/// A consistent point-in-time view of the database
+///
+/// Reads through a snapshot (`CollectionCore::find_at`) see exactly the
+/// writes committed at or before its LSN. Old versions are kept for as long
+/// as the snapshot is alive - drop it when done.
+#[derive(Debug)]
+pub struct Snapshot {
+    lsn: Lsn,
+    registry: Arc<SnapshotRegistry>,
+}
+
+impl Snapshot {
+    pub fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        self.registry.register(self.lsn);
+        Snapshot {
+            lsn: self.lsn,
+            registry: Arc::clone(&self.registry),
+        }
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.registry.release(self.lsn);
+    }
+}
+
+/// Where a superseded version's bytes are
+#[derive(Debug, Clone)]
+enum VersionData {
+    /// The document did not exist (it was inserted by the superseding write)
+    Absent,
+    /// A record in the data file, pinned until the version is pruned
+    Record { offset: u64, size: u64, tombstone: bool },
+    /// Copied out of the file by compaction
+    Inline(Vec<u8>),
+}
+
+/// A superseded document version
+#[derive(Debug, Clone)]
+struct OldVersion {
+    /// LSN of the commit that replaced it (visible to snapshots < end)
+    end: Lsn,
+    data: VersionData,
+}
+
+/// Old versions kept for open snapshots
+#[derive(Debug, Default)]
+pub(super) struct VersionStore {
+    /// collection -> _id -> superseded versions, oldest first
+    history: HashMap<String, HashMap<DocumentId, Vec<OldVersion>>>,
+    snapshots: Arc<SnapshotRegistry>,
+    /// Oldest snapshot at the last prune (None = none open)
+    pruned_for: Option<Lsn>,
+}
+
+impl VersionStore {
+    fn version_count(&self) -> usize {
+        self.history.values().flat_map(|docs| docs.values()).map(Vec::len).sum()
+    }
+}
+
+/// Version counts for stats()
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MvccStats {
+    pub open_snapshots: usize,
+    pub oldest_snapshot: Option<Lsn>,
+    pub retained_versions: usize,
+}
+
+impl StorageEngine {
+    /// Open a snapshot at the current LSN
+    ///
+    /// Writes happen under the storage lock, so holding `&self` here means no
+    /// write is half-applied.
+    pub fn snapshot(&self) -> Snapshot {
+        let lsn = self.lsn.current();
+        self.versions.snapshots.register(lsn);
+        Snapshot {
+            lsn,
+            registry: Arc::clone(&self.versions.snapshots),
+        }
+    }
+
+    pub fn mvcc_stats(&self) -> MvccStats {
+        MvccStats {
+            open_snapshots: self.versions.snapshots.len(),
+            oldest_snapshot: self.versions.snapshots.oldest(),
+            retained_versions: self.versions.version_count(),
+        }
+    }
+
+    /// LSN the write in progress commits at: the transaction's, or the next one
+    pub(super) fn commit_lsn(&self) -> Lsn {
+        self.batch_lsn.unwrap_or_else(|| self.lsn.current() + 1)
+    }
+
+    /// Retire the version a write supersedes (`previous` as returned by
+    /// account_superseded): keep it for open snapshots, or free its region
+    pub(super) fn retire_version(
+        &mut self,
+        collection: &str,
+        doc_id: &DocumentId,
+        previous: Option<(u64, u64, bool)>,
+    ) {
+        self.prune_versions();
+
+        if self.versions.snapshots.is_empty() {
+            if let Some((offset, size, tombstone)) = previous {
+                self.free_space.release(collection, offset, size, tombstone);
+            }
+            return;
+        }
+
+        let data = match previous {
+            Some((offset, size, tombstone)) => VersionData::Record { offset, size, tombstone },
+            None => VersionData::Absent,
+        };
+        let end = self.commit_lsn();
+        self.versions.history
+            .entry(collection.to_string())
+            .or_default()
+            .entry(doc_id.clone())
+            .or_default()
+            .push(OldVersion { end, data });
+    }
+
+    /// Drop the versions no open snapshot can see and free their regions
+    pub fn prune_versions(&mut self) {
+        let oldest = self.versions.snapshots.oldest();
+        if oldest == self.versions.pruned_for && oldest.is_some() {
+            return;
+        }
+        self.versions.pruned_for = oldest;
+        if self.versions.history.is_empty() {
+            return;
+        }
+
+        let mut released = Vec::new();
+        self.versions.history.retain(|collection, docs| {
+            docs.retain(|_, versions| {
+                versions.retain(|version| {
+                    let visible = oldest.is_some_and(|oldest| version.end > oldest);
+                    if !visible {
+                        if let VersionData::Record { offset, size, tombstone } = version.data {
+                            released.push((collection.clone(), offset, size, tombstone));
+                        }
+                    }
+                    visible
+                });
+                !versions.is_empty()
+            });
+            !docs.is_empty()
+        });
+
+        for (collection, offset, size, tombstone) in released {
+            self.free_space.release(&collection, offset, size, tombstone);
+        }
+    }
+
+    /// Offsets of `collection`'s records kept as old versions for open snapshots
+    pub(super) fn pinned_records(&self, collection: &str) -> HashSet<u64> {
+        self.versions.history.get(collection)
+            .into_iter()
+            .flat_map(|docs| docs.values().flatten())
+            .filter_map(|version| match version.data {
+                VersionData::Record { offset, .. } => Some(offset),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Copy the versions still needed out of the data file (before compaction
+    /// rewrites it)
+    pub(super) fn inline_versions(&mut self) -> Result<()> {
+        self.prune_versions();
+
+        let mut pinned = Vec::new();
+        for (collection, docs) in &self.versions.history {
+            for (doc_id, versions) in docs {
+                for (index, version) in versions.iter().enumerate() {
+                    if let VersionData::Record { offset, .. } = version.data {
+                        pinned.push((collection.clone(), doc_id.clone(), index, offset));
+                    }
+                }
+            }
+        }
+
+        for (collection, doc_id, index, offset) in pinned {
+            let data = self.read_data(offset)?;
+            if let Some(version) = self.versions.history.get_mut(&collection)
+                .and_then(|docs| docs.get_mut(&doc_id))
+                .and_then(|versions| versions.get_mut(index))
+            {
+                version.data = VersionData::Inline(data);
+            }
+        }
+        Ok(())
+    }
+
+    /// The document `doc_id` as of `lsn` (None if it did not exist then)
+    pub fn read_version_at(&mut self, collection: &str, doc_id: &DocumentId, lsn: Lsn) -> Result<Option<Vec<u8>>> {
+        let old = self.versions.history.get(collection)
+            .and_then(|docs| docs.get(doc_id))
+            .and_then(|versions| versions.iter().find(|version| version.end > lsn))
+            .map(|version| version.data.clone());
+
+        let data = match old {
+            Some(VersionData::Absent) => return Ok(None),
+            Some(VersionData::Record { tombstone: true, .. }) => return Ok(None),
+            Some(VersionData::Record { offset, .. }) => self.read_data(offset)?,
+            Some(VersionData::Inline(data)) => data,
+            None => {
+                let meta = self.get_collection_meta(collection)
+                    .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?;
+                match meta.document_catalog.get(doc_id) {
+                    Some(&offset) => self.read_data(offset)?,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        Ok(if super::garbage::is_tombstone(&data) { None } else { Some(data) })
+    }
+
+    /// Every document of `collection` as of `lsn`
+    ///
+    /// Current documents come in natural (file) order, those deleted since
+    /// the snapshot after them in _id order.
+    pub fn scan_at(&mut self, collection: &str, lsn: Lsn) -> Result<Vec<(DocumentId, Vec<u8>)>> {
+        let ids = self.snapshot_ids(collection)?;
+        let mut documents = Vec::with_capacity(ids.len());
+        for doc_id in ids {
+            if let Some(data) = self.read_version_at(collection, &doc_id, lsn)? {
+                documents.push((doc_id, data));
+            }
+        }
+        Ok(documents)
+    }
+
+    /// The _ids scan_at() reads, in its order: every document an open
+    /// snapshot can see is among them
+    pub fn snapshot_ids(&self, collection: &str) -> Result<Vec<DocumentId>> {
+        let meta = self.get_collection_meta(collection)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(collection.to_string()))?;
+
+        // Current documents plus the ones deleted after the snapshot
+        let mut current: Vec<(u64, DocumentId)> = meta.document_catalog.iter()
+            .map(|(id, offset)| (*offset, id.clone()))
+            .collect();
+        current.sort_unstable();
+        let mut deleted: Vec<DocumentId> = self.versions.history.get(collection)
+            .map(|docs| docs.keys().filter(|id| !meta.document_catalog.contains_key(id)).cloned().collect())
+            .unwrap_or_default();
+        deleted.sort_unstable();
+        Ok(current.into_iter().map(|(_, id)| id).chain(deleted).collect())
+    }
+}
@@ -0,0 +1,141 @@
+// storage/oplog.rs
+// Optional capped operation history (_oplog collection)
+
+use serde_json::Value;
+use crate::document::DocumentId;
+use crate::error::Result;
+use super::StorageEngine;
+
+/// Name of the oplog collection
+pub const OPLOG_COLLECTION: &str = "_oplog";
+
+/// Oplog configuration (runtime only - not persisted, disabled by default)
+#[derive(Debug, Clone)]
+pub struct OplogConfig {
+    pub enabled: bool,
+    /// Entries kept; older ones are tombstoned as new ones arrive
+    /// NOTE: the catalog lives in the reserved metadata area, keep this moderate
+    pub max_entries: u64,
+}
+
+impl Default for OplogConfig {
+    fn default() -> Self {
+        OplogConfig {
+            enabled: false,
+            max_entries: 1000,
+        }
+    }
+}
+
+impl OplogConfig {
+    /// Enabled oplog with the given retention
+    pub fn with_retention(max_entries: u64) -> Self {
+        OplogConfig {
+            enabled: true,
+            max_entries,
+        }
+    }
+}
+
+impl StorageEngine {
+    pub fn set_oplog_config(&mut self, config: OplogConfig) {
+        if !config.enabled {
+            self.oplog_epoch = None;
+        } else if self.oplog_epoch.is_none() {
+            self.oplog_epoch = Some(uuid::Uuid::new_v4().to_string());
+        }
+        self.oplog = config;
+    }
+
+    /// See StorageEngine::oplog_epoch
+    pub fn oplog_epoch(&self) -> Option<&str> {
+        self.oplog_epoch.as_deref()
+    }
+
+    /// Sequence numbers of the oldest retained entry (None if there is none)
+    /// and of the newest entry ever written
+    pub fn oplog_range(&self) -> (Option<u64>, u64) {
+        match self.collections.get(OPLOG_COLLECTION) {
+            Some(meta) => {
+                let oldest = meta.document_catalog.keys()
+                    .filter_map(|id| match id {
+                        DocumentId::Int(i) => Some(*i as u64),
+                        _ => None,
+                    })
+                    .min();
+                (oldest, meta.last_id)
+            }
+            None => (None, 0),
+        }
+    }
+
+    pub fn oplog_config(&self) -> &OplogConfig {
+        &self.oplog
+    }
+
+    /// Cheap check so callers can skip building the delta
+    pub fn oplog_enabled(&self) -> bool {
+        self.oplog.enabled
+    }
+
+    /// Record a committed operation in the oplog
+    /// `op` is "insert", "update" or "delete"; `delta` is the inserted document,
+    /// the update spec / new version, or null for deletes
+    pub fn log_operation(&mut self, op: &str, collection: &str, doc_id: &DocumentId, delta: Value) -> Result<()> {
+        if !self.oplog.enabled || collection == OPLOG_COLLECTION {
+            return Ok(());
+        }
+
+        if self.get_collection_meta(OPLOG_COLLECTION).is_none() {
+            self.create_collection(OPLOG_COLLECTION)?;
+        }
+
+        let seq = self.next_id(OPLOG_COLLECTION)?;
+
+        let entry = serde_json::json!({
+            "_id": seq,
+            "_collection": OPLOG_COLLECTION,
+            "ts": chrono::Utc::now().timestamp_millis(),
+            "op": op,
+            "collection": collection,
+            "doc_id": doc_id,
+            "delta": delta,
+        });
+        let entry_json = serde_json::to_vec(&entry)?;
+        self.write_document(OPLOG_COLLECTION, &DocumentId::Int(seq as i64), &entry_json)?;
+
+        self.trim_oplog(seq)
+    }
+
+    /// Drop entries older than the retention window
+    /// Tombstones are written without catalog tracking and the catalog entry is removed,
+    /// so the catalog (and the metadata area) stays bounded
+    fn trim_oplog(&mut self, newest: u64) -> Result<()> {
+        let max_entries = self.oplog.max_entries;
+        let expired: Vec<i64> = {
+            let meta = match self.collections.get(OPLOG_COLLECTION) {
+                Some(meta) => meta,
+                None => return Ok(()),
+            };
+            if meta.document_catalog.len() as u64 <= max_entries {
+                return Ok(());
+            }
+
+            let cutoff = newest.saturating_sub(max_entries) as i64;
+            let mut expired: Vec<i64> = meta.document_catalog.keys()
+                .filter_map(|id| match id {
+                    DocumentId::Int(i) if *i <= cutoff => Some(*i),
+                    _ => None,
+                })
+                .collect();
+            expired.sort_unstable();
+            expired
+        };
+
+        for id in expired {
+            self.write_tombstone(OPLOG_COLLECTION, &DocumentId::Int(id))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+// storage/quota.rs
+// Per-collection document count and size limits
+
+use serde::{Serialize, Deserialize};
+use crate::error::{Result, MongoLiteError};
+use super::{GarbageStats, StorageEngine};
+
+/// Limits on a collection's live documents (persisted with its metadata)
+///
+/// Checked when documents are inserted; updates that grow documents and
+/// aggregation output ($out/$merge) are not limited. Bytes are record bytes
+/// (serialized document plus its 4-byte length), as in GarbageStats::live_bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollectionQuota {
+    pub max_documents: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl CollectionQuota {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_documents.is_none() && self.max_bytes.is_none()
+    }
+}
+
+impl StorageEngine {
+    /// QuotaExceeded if inserting `documents` more documents, serialized to
+    /// `bytes` bytes in total, would take `collection` past its quota; callers
+    /// check before touching indexes
+    pub fn check_quota(&self, collection: &str, documents: u64, bytes: u64) -> Result<()> {
+        let Some(meta) = self.collections.get(collection) else {
+            return Ok(());
+        };
+        let quota = meta.quota;
+        // Files from before live/dead accounting: fall back to the catalog
+        let live = meta.garbage.unwrap_or(GarbageStats {
+            live_records: meta.document_catalog.len() as u64,
+            ..GarbageStats::default()
+        });
+
+        if let Some(max) = quota.max_documents {
+            let total = live.live_records + documents;
+            if total > max {
+                return Err(MongoLiteError::QuotaExceeded(format!(
+                    "collection '{}' would hold {} documents (max {})", collection, total, max
+                )));
+            }
+        }
+        if let Some(max) = quota.max_bytes {
+            let total = live.live_bytes + bytes + 4 * documents;
+            if total > max {
+                return Err(MongoLiteError::QuotaExceeded(format!(
+                    "collection '{}' would hold {} bytes (max {})", collection, total, max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
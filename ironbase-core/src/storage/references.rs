@@ -0,0 +1,123 @@
+// storage/references.rs
+// Reference constraints: a field that must hold the _id of a live document
+// in another collection
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use super::StorageEngine;
+
+/// What deleting a referenced document does to the documents referencing it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    /// The delete fails while any document references it
+    Restrict,
+    /// Referencing documents are deleted too
+    Cascade,
+}
+
+impl std::str::FromStr for OnDelete {
+    type Err = MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "restrict" => Ok(OnDelete::Restrict),
+            "cascade" => Ok(OnDelete::Cascade),
+            other => Err(MongoLiteError::InvalidConfig(format!(
+                "unknown on_delete '{}' (expected restrict or cascade)", other
+            ))),
+        }
+    }
+}
+
+/// `field` holds the _id of a document in `collection` (persisted with the
+/// referencing collection's metadata)
+///
+/// A missing or null field references nothing; anything else must match a
+/// live document when it is written.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub field: String,
+    pub collection: String,
+    pub on_delete: OnDelete,
+}
+
+impl StorageEngine {
+    /// ReferenceViolation if a document of `collection` with the given field
+    /// values references a document that does not exist; callers check before
+    /// touching indexes
+    pub fn check_references<'a, F>(&mut self, collection: &str, field: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<&'a Value>,
+    {
+        let references = match self.collections.get(collection) {
+            Some(meta) if !meta.references.is_empty() => meta.references.clone(),
+            _ => return Ok(()),
+        };
+        for reference in &references {
+            let Some(value) = field(&reference.field).filter(|value| !value.is_null()) else {
+                continue;
+            };
+            if !self.document_exists(&reference.collection, value)? {
+                return Err(MongoLiteError::ReferenceViolation(format!(
+                    "{}.{} = {} matches no document in '{}'",
+                    collection, reference.field, value, reference.collection
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// (collection, reference) for every reference pointing at `collection`
+    pub fn referrers(&self, collection: &str) -> Vec<(String, Reference)> {
+        self.collections.iter()
+            .flat_map(|(name, meta)| meta.references.iter().map(move |reference| (name.clone(), reference.clone())))
+            .filter(|(_, reference)| reference.collection == collection)
+            .collect()
+    }
+
+    /// Live documents of `collection` whose `field` is one of `ids`
+    pub fn find_referencing(&mut self, collection: &str, field: &str, ids: &[Value]) -> Result<Vec<Value>> {
+        let offsets: Vec<u64> = match self.collections.get(collection) {
+            Some(meta) => meta.document_catalog.values().copied().collect(),
+            None => return Ok(Vec::new()),
+        };
+        let mut found = Vec::new();
+        for offset in offsets {
+            let doc: Value = serde_json::from_slice(&self.read_data(offset)?)?;
+            if is_tombstone(&doc) {
+                continue;
+            }
+            if doc.get(field).is_some_and(|value| ids.contains(value)) {
+                found.push(doc);
+            }
+        }
+        Ok(found)
+    }
+
+    fn document_exists(&mut self, collection: &str, id: &Value) -> Result<bool> {
+        let Ok(id) = serde_json::from_value::<DocumentId>(id.clone()) else {
+            return Ok(false);
+        };
+        // A string _id may be an ObjectId, which serializes the same way
+        let object_id = match &id {
+            DocumentId::String(s) => Some(DocumentId::ObjectId(s.clone())),
+            _ => None,
+        };
+        let offset = self.collections.get(collection).and_then(|meta| {
+            meta.document_catalog.get(&id)
+                .or_else(|| object_id.as_ref().and_then(|id| meta.document_catalog.get(id)))
+                .copied()
+        });
+        let Some(offset) = offset else {
+            return Ok(false);
+        };
+        let doc: Value = serde_json::from_slice(&self.read_data(offset)?)?;
+        Ok(!is_tombstone(&doc))
+    }
+}
+
+fn is_tombstone(doc: &Value) -> bool {
+    doc.get("_tombstone").and_then(Value::as_bool).unwrap_or(false)
+}
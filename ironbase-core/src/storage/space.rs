@@ -0,0 +1,202 @@
+// storage/space.rs
+// Space-usage analysis: what the data file is made of
+//
+// Walks every record once and attributes its bytes to a collection (live
+// documents, superseded versions, tombstones) or to the engine (catalog
+// shards, free regions). For the live documents of each collection it keeps
+// the size distribution and the bytes of every top-level field, so users can
+// see which collections and fields are worth pruning or compressing.
+
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use serde_json::Value;
+use crate::error::Result;
+use super::{StorageEngine, DATA_START_OFFSET};
+
+/// Fields listed per collection in `CollectionSpace::top_fields`
+pub const TOP_FIELDS: usize = 10;
+
+/// Space used by the data file, the WAL, the journal and the index files
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SpaceReport {
+    pub file_size: u64,
+    /// Header and reserved metadata region
+    pub metadata_bytes: u64,
+    /// Document catalog shard records
+    pub catalog_bytes: u64,
+    /// Free regions waiting for reuse
+    pub free_bytes: u64,
+    /// Records that are neither documents nor engine records
+    pub other_bytes: u64,
+    pub wal_bytes: u64,
+    pub journal_bytes: u64,
+    /// Largest first (live + dead + tombstone bytes)
+    pub collections: Vec<CollectionSpace>,
+}
+
+/// Space used by one collection
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CollectionSpace {
+    pub name: String,
+    pub documents: u64,
+    /// Records of current document versions (length prefixes included)
+    pub live_bytes: u64,
+    /// Superseded document versions
+    pub dead_records: u64,
+    pub dead_bytes: u64,
+    pub tombstones: u64,
+    pub tombstone_bytes: u64,
+    /// Serialized document sizes of the live documents
+    pub average_document_size: f64,
+    pub p50_document_size: u64,
+    pub p90_document_size: u64,
+    pub p99_document_size: u64,
+    pub max_document_size: u64,
+    /// Heaviest top-level fields of the live documents, heaviest first
+    pub top_fields: Vec<FieldSpace>,
+    /// `.idx` files of the collection's indexes, _id included
+    pub index_bytes: u64,
+}
+
+impl CollectionSpace {
+    /// Bytes of all the collection's records
+    pub fn total_bytes(&self) -> u64 {
+        self.live_bytes + self.dead_bytes + self.tombstone_bytes
+    }
+}
+
+/// Bytes a field takes over all live documents of a collection
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpace {
+    pub field: String,
+    /// `"field":value,` as serialized
+    pub bytes: u64,
+    /// Documents that have the field
+    pub documents: u64,
+}
+
+/// Accumulated while walking the records
+#[derive(Default)]
+struct CollectionTally {
+    space: CollectionSpace,
+    sizes: Vec<u64>,
+    fields: HashMap<String, (u64, u64)>,
+}
+
+impl StorageEngine {
+    /// Walk the data file and report what takes its space (see space.rs)
+    ///
+    /// Reads every record, so it takes as long as a full scan of all
+    /// collections.
+    pub fn analyze_space(&mut self) -> Result<SpaceReport> {
+        let current: HashSet<u64> = self.collections.values()
+            .flat_map(|meta| meta.document_catalog.values().copied())
+            .collect();
+        let mut tallies: HashMap<String, CollectionTally> = HashMap::new();
+        let mut report = SpaceReport {
+            file_size: self.file_len()?,
+            metadata_bytes: DATA_START_OFFSET,
+            ..Default::default()
+        };
+
+        self.for_each_record(|offset, payload| {
+            let size = 4 + payload.len() as u64;
+            // Documents repeat _id (see Document::from_json), so no derived struct
+            let Ok(Value::Object(record)) = serde_json::from_slice::<Value>(payload) else {
+                report.other_bytes += size;
+                return Ok(());
+            };
+            let Some(collection) = record.get("_collection").and_then(Value::as_str) else {
+                if record.contains_key("_free") {
+                    report.free_bytes += size;
+                } else if record.contains_key("_catalog") {
+                    report.catalog_bytes += size;
+                } else {
+                    report.other_bytes += size;
+                }
+                return Ok(());
+            };
+
+            let tally = tallies.entry(collection.to_string()).or_default();
+            if record.get("_tombstone").and_then(Value::as_bool).unwrap_or(false) {
+                tally.space.tombstones += 1;
+                tally.space.tombstone_bytes += size;
+            } else if !current.contains(&offset) {
+                tally.space.dead_records += 1;
+                tally.space.dead_bytes += size;
+            } else {
+                tally.space.documents += 1;
+                tally.space.live_bytes += size;
+                // Reused regions are padded with trailing spaces
+                tally.sizes.push(payload.trim_ascii_end().len() as u64);
+                for (field, value) in &record {
+                    let bytes = field.len() as u64 + 4 + serde_json::to_vec(value)?.len() as u64;
+                    let entry = tally.fields.entry(field.clone()).or_default();
+                    entry.0 += bytes;
+                    entry.1 += 1;
+                }
+            }
+            Ok(())
+        })?;
+
+        let wal_path = std::path::Path::new(&self.file_path).with_extension("wal");
+        let journal_path = std::path::Path::new(&self.file_path).with_extension("journal");
+        report.wal_bytes = file_size(&wal_path);
+        report.journal_bytes = file_size(&journal_path);
+
+        // Collections without records still show up
+        for name in self.collections.keys() {
+            tallies.entry(name.clone()).or_default();
+        }
+        for (name, tally) in tallies {
+            let mut space = tally.finish(name);
+            if let Some(meta) = self.collections.get(&space.name) {
+                let index_names = std::iter::once(format!("{}_id", space.name))
+                    .chain(meta.indexes.iter().map(|index| index.name.clone()));
+                space.index_bytes = index_names
+                    .map(|index| file_size(&super::index_file_path(&self.file_path, &index)))
+                    .sum();
+            }
+            report.collections.push(space);
+        }
+        report.collections.sort_by(|a, b| b.total_bytes().cmp(&a.total_bytes()).then_with(|| a.name.cmp(&b.name)));
+        Ok(report)
+    }
+}
+
+impl CollectionTally {
+    fn finish(mut self, name: String) -> CollectionSpace {
+        let mut space = self.space;
+        space.name = name;
+
+        self.sizes.sort_unstable();
+        // Nearest rank
+        let percentile = |p: f64| -> u64 {
+            if self.sizes.is_empty() {
+                return 0;
+            }
+            let rank = ((p * self.sizes.len() as f64).ceil() as usize).clamp(1, self.sizes.len());
+            self.sizes[rank - 1]
+        };
+        space.p50_document_size = percentile(0.50);
+        space.p90_document_size = percentile(0.90);
+        space.p99_document_size = percentile(0.99);
+        space.max_document_size = self.sizes.last().copied().unwrap_or(0);
+        if !self.sizes.is_empty() {
+            space.average_document_size = self.sizes.iter().sum::<u64>() as f64 / self.sizes.len() as f64;
+        }
+
+        let mut fields: Vec<FieldSpace> = self.fields.into_iter()
+            .map(|(field, (bytes, documents))| FieldSpace { field, bytes, documents })
+            .collect();
+        fields.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.field.cmp(&b.field)));
+        fields.truncate(TOP_FIELDS);
+        space.top_fields = fields;
+        space
+    }
+}
+
+/// Size of a file, 0 if it does not exist
+fn file_size(path: &std::path::Path) -> u64 {
+    std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
@@ -0,0 +1,148 @@
+// storage/write_buffer.rs
+// Write batching: record appends coalesced into few file writes
+//
+// Outside a write batch every append is a single write - length prefix and
+// payload together. Inside one (insert_many, bulk_upsert, applying a
+// transaction, see write_batch) appends to the end of the data file collect
+// in memory and reach the file in one write when the batch ends or the
+// buffer passes WRITE_BUFFER_LIMIT. Buffered records are read from the
+// buffer; anything else that touches the end of the file - a record walk,
+// a metadata flush, an fsync - flushes the buffer first. A batch
+// always ends flushed, so nothing is held back once the storage lock is
+// released, and the data group commit (see journal.rs) syncs all of it.
+//
+// Disk space is reserved ahead of the appends (StorageFile::preallocate) a
+// step at a time, so a large import does not extend the file's allocation
+// write after write.
+
+use std::io::{Seek, SeekFrom, Write};
+use crate::error::Result;
+use super::StorageEngine;
+
+/// Buffered bytes that trigger a write in the middle of a batch
+pub const WRITE_BUFFER_LIMIT: usize = 1 << 20;
+
+/// Default StorageConfig::preallocation
+pub const DEFAULT_PREALLOCATION: u64 = 4 << 20;
+
+/// Appends not yet written to the data file
+#[derive(Debug, Default)]
+pub(super) struct WriteBuffer {
+    /// File offset of the first buffered byte
+    start: u64,
+    bytes: Vec<u8>,
+    /// Nesting depth of write_batch (0 = appends are written at once)
+    batches: u32,
+    /// Record writes issued to the data file since open
+    writes: u64,
+    /// Smallest preallocation step (0 = none)
+    preallocation: u64,
+    /// End of the space preallocated so far
+    preallocated: u64,
+}
+
+impl WriteBuffer {
+    pub(super) fn new(preallocation: u64) -> Self {
+        WriteBuffer { preallocation, ..Default::default() }
+    }
+
+    /// The file was truncated or replaced: its preallocated space is gone
+    pub(super) fn forget_preallocation(&mut self) {
+        self.preallocated = 0;
+    }
+
+    /// Bytes waiting to be written
+    pub(super) fn len(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    /// Payload of the buffered record at `offset`, if it is one
+    pub(super) fn record(&self, offset: u64) -> Option<Vec<u8>> {
+        let start = usize::try_from(offset.checked_sub(self.start)?).ok()?.checked_add(4)?;
+        let len = u32::from_le_bytes(self.bytes.get(start - 4..start)?.try_into().ok()?) as usize;
+        self.bytes.get(start..start.checked_add(len)?).map(<[u8]>::to_vec)
+    }
+}
+
+impl StorageEngine {
+    /// Run `f` as one write batch: its appends are written together (see
+    /// write_buffer.rs)
+    ///
+    /// The buffer is flushed when the outermost batch ends, also when `f`
+    /// fails - whatever it wrote is journaled already.
+    pub fn write_batch<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.write_buffer.batches += 1;
+        let result = f(self);
+        self.write_buffer.batches -= 1;
+        if self.write_buffer.batches > 0 {
+            return result;
+        }
+        let flushed = self.flush_writes();
+        let value = result?;
+        flushed?;
+        Ok(value)
+    }
+
+    /// Append a record at the end of the data file, but not before
+    /// `min_offset`; returns its offset
+    pub(super) fn append_record(&mut self, data: &[u8], min_offset: u64) -> Result<u64> {
+        let record_size = 4 + data.len();
+        if self.write_buffer.batches > 0 && self.write_buffer.bytes.len() + record_size > WRITE_BUFFER_LIMIT {
+            self.flush_writes()?;
+        }
+
+        if self.write_buffer.bytes.is_empty() {
+            self.write_buffer.start = self.file.seek(SeekFrom::End(0))?.max(min_offset);
+        }
+        let offset = self.write_buffer.start + self.write_buffer.len();
+        self.write_buffer.bytes.reserve(record_size);
+        self.write_buffer.bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.write_buffer.bytes.extend_from_slice(data);
+
+        if self.write_buffer.batches == 0 {
+            self.flush_writes()?;
+        }
+        Ok(offset)
+    }
+
+    /// Write out buffered appends
+    pub(super) fn flush_writes(&mut self) -> Result<()> {
+        if self.write_buffer.bytes.is_empty() {
+            return Ok(());
+        }
+        let start = self.write_buffer.start;
+        let end = start + self.write_buffer.len();
+        self.preallocate(end);
+        // A failed write is not retried: the records it held are lost like
+        // records of a failed unbuffered write
+        let written = self.file.seek(SeekFrom::Start(start))
+            .and_then(|_| self.file.write_all(&self.write_buffer.bytes));
+        self.write_buffer.bytes.clear();
+        self.write_buffer.writes += 1;
+        written?;
+        self.grow_mmap(end);
+        Ok(())
+    }
+
+    /// Reserve space past `end` once appends reach the end of the last
+    /// reservation: the larger of the step and an eighth of the file
+    fn preallocate(&mut self, end: u64) {
+        let step = self.write_buffer.preallocation;
+        if step == 0 || end <= self.write_buffer.preallocated {
+            return;
+        }
+        let target = end + step.max(end / 8);
+        // Only a hint - a file system out of space fails the write itself.
+        // Not retried before the next step either way
+        let _ = self.file.preallocate(target);
+        self.write_buffer.preallocated = target;
+    }
+
+    /// Record writes issued to the data file since open
+    pub fn data_writes(&self) -> u64 {
+        self.write_buffer.writes
+    }
+}
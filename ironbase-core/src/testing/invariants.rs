@@ -0,0 +1,177 @@
+// testing/invariants.rs
+// Expected state of a crash workload and the checks against it
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use serde_json::{json, Value};
+use crate::collection_core::CollectionCore;
+use crate::error::MongoLiteError;
+
+/// What a workload has written, as far as the database has acknowledged it
+///
+/// Documents are `{"key": <0..KEYS>, "value": <i64>}`, one per key. Every
+/// value written is distinct, so a value identifies the write that made it.
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    /// State after every acknowledged write
+    current: BTreeMap<u8, i64>,
+    /// Keys written since the last durable point: every value (None for
+    /// deleted) the key may have after a crash, starting with its durable one
+    unsynced: BTreeMap<u8, Vec<Option<i64>>>,
+    /// Values written by transactions that never committed
+    uncommitted: BTreeSet<i64>,
+}
+
+impl Model {
+    pub fn new() -> Self {
+        Model::default()
+    }
+
+    /// Current value of `key`
+    pub fn get(&self, key: u8) -> Option<i64> {
+        self.current.get(&key).copied()
+    }
+
+    /// An acknowledged write that is not durable yet
+    pub fn write(&mut self, key: u8, value: Option<i64>) {
+        let before = self.get(key);
+        self.unsynced.entry(key).or_insert_with(|| vec![before]).push(value);
+        match value {
+            Some(value) => self.current.insert(key, value),
+            None => self.current.remove(&key),
+        };
+    }
+
+    /// Everything written so far is on disk
+    pub fn sync(&mut self) {
+        self.unsynced.clear();
+    }
+
+    /// Values of a transaction that rolled back or was lost in a crash
+    pub fn discard(&mut self, values: impl IntoIterator<Item = i64>) {
+        self.uncommitted.extend(values);
+    }
+
+    /// Continue from the state found after a crash
+    pub fn recovered(&mut self, state: BTreeMap<u8, i64>) {
+        self.current = state;
+        self.unsynced.clear();
+    }
+
+    /// Check the state of a running database: exactly the acknowledged writes
+    pub fn check_live(&self, state: &BTreeMap<u8, i64>) -> Result<(), Violation> {
+        for key in self.keys(state) {
+            let found = state.get(&key).copied();
+            self.check_committed(key, found)?;
+            if found != self.get(key) {
+                return Err(Violation::LostWrite { key, allowed: vec![self.get(key)], found });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the state found after a crash: every key has its durable value
+    /// or one written since
+    pub fn check_recovered(&self, state: &BTreeMap<u8, i64>) -> Result<(), Violation> {
+        for key in self.keys(state) {
+            let found = state.get(&key).copied();
+            self.check_committed(key, found)?;
+            let allowed = self.unsynced.get(&key).cloned().unwrap_or_else(|| vec![self.get(key)]);
+            if !allowed.contains(&found) {
+                return Err(Violation::LostWrite { key, allowed, found });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_committed(&self, key: u8, found: Option<i64>) -> Result<(), Violation> {
+        match found {
+            Some(value) if self.uncommitted.contains(&value) => Err(Violation::UncommittedVisible { key, value }),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self, state: &BTreeMap<u8, i64>) -> BTreeSet<u8> {
+        self.current.keys().chain(self.unsynced.keys()).chain(state.keys()).copied().collect()
+    }
+}
+
+/// An invariant a workload broke
+#[derive(Debug)]
+pub enum Violation {
+    /// `key` has a value it cannot have: a committed write was lost or
+    /// replaced by one never made
+    LostWrite { key: u8, allowed: Vec<Option<i64>>, found: Option<i64> },
+    /// A transaction that never committed wrote `value`
+    UncommittedVisible { key: u8, value: i64 },
+    /// A document is unreadable, duplicated, or missing from an index
+    Inconsistent(String),
+    /// An operation the workload expects to succeed failed (recovery included)
+    Database(MongoLiteError),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::LostWrite { key, allowed, found } =>
+                write!(f, "key {} is {:?}, expected one of {:?}", key, found, allowed),
+            Violation::UncommittedVisible { key, value } =>
+                write!(f, "key {} shows value {} of an uncommitted transaction", key, value),
+            Violation::Inconsistent(message) => write!(f, "inconsistent collection: {}", message),
+            Violation::Database(error) => write!(f, "database error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+impl From<MongoLiteError> for Violation {
+    fn from(error: MongoLiteError) -> Self {
+        Violation::Database(error)
+    }
+}
+
+/// Read the workload's documents as key -> value
+pub fn read_state(collection: &CollectionCore) -> Result<BTreeMap<u8, i64>, Violation> {
+    let mut state = BTreeMap::new();
+    for doc in collection.find(&json!({}))? {
+        let (key, value) = parse(&doc)?;
+        if state.insert(key, value).is_some() {
+            return Err(Violation::Inconsistent(format!("two documents for key {}", key)));
+        }
+    }
+    Ok(state)
+}
+
+/// Indexes agree with the documents: validate() reports no issue and each
+/// document is found through each index
+pub fn check_indexes(collection: &CollectionCore, state: &BTreeMap<u8, i64>) -> Result<(), Violation> {
+    let report = collection.validate(false)?;
+    if let Some(issue) = report.issues.first() {
+        return Err(Violation::Inconsistent(format!("{:?}", issue)));
+    }
+
+    for field in ["key", "value"] {
+        let index = format!("{}_{}", collection.name, field);
+        for (&key, &value) in state {
+            let wanted = if field == "key" { json!(key) } else { json!(value) };
+            let found = collection.find_with_hint(&json!({field: wanted}), &index)?;
+            let found: Vec<(u8, i64)> = found.iter().map(parse).collect::<Result<_, _>>()?;
+            if found != [(key, value)] {
+                return Err(Violation::Inconsistent(format!(
+                    "{} lookup of {} returned {:?}, expected key {} value {}", index, wanted, found, key, value
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse(doc: &Value) -> Result<(u8, i64), Violation> {
+    let key = doc.get("key").and_then(Value::as_u64).and_then(|key| u8::try_from(key).ok());
+    let value = doc.get("value").and_then(Value::as_i64);
+    match (key, value) {
+        (Some(key), Some(value)) => Ok((key, value)),
+        _ => Err(Violation::Inconsistent(format!("malformed document {}", doc))),
+    }
+}
@@ -0,0 +1,31 @@
+// testing/mod.rs
+// Test support: crash-recovery workloads, the invariants checked after them,
+// and a simulated storage backend with fault injection
+//
+// A workload is a sequence of Ops against one collection, interleaved with
+// simulated crashes: the database is dropped without a clean shutdown and the
+// data file and the WAL each lose a random part of what was written since
+// their last fsync. CrashHarness runs the workload next to a Model of
+// what must, may and must not be visible, and checks after every step:
+//
+// - no committed data lost: everything durable before a crash is still there
+// - no uncommitted data visible: writes of transactions that never committed
+//   never show up, before or after a crash
+// - indexes consistent with documents: validate() finds nothing and every
+//   document is found through its index
+//
+// Drive it with proptest (tests/crash_property_tests.rs) or with cargo-fuzz
+// (fuzz/fuzz_targets/crash_recovery.rs, which decodes raw bytes with
+// Op::decode).
+//
+// SimulatedBackend goes further: the database runs on in-memory files (see
+// StorageConfig::with_backend) whose writes and syncs can fail, tear or lie,
+// and a crash drops exactly what was not synced.
+
+mod invariants;
+mod simulation;
+mod workload;
+
+pub use invariants::{check_indexes, read_state, Model, Violation};
+pub use simulation::{Fault, SimulatedBackend};
+pub use workload::{CrashHarness, CrashPoint, Op, KEYS};
@@ -0,0 +1,297 @@
+// testing/simulation.rs
+// Simulated storage: in-memory files, crashes and injected I/O faults
+
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use crate::storage::{StorageBackend, StorageFile};
+
+/// A fault armed on a SimulatedBackend
+///
+/// Writes and syncs are counted over every file from the moment the fault is
+/// armed: `nth: 1` hits the very next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The write fails without writing anything
+    FailWrite { nth: u64 },
+    /// The write stores its first `keep` bytes, then fails
+    TornWrite { nth: u64, keep: usize },
+    /// The sync fails without making anything durable
+    FailSync { nth: u64 },
+}
+
+/// In-memory StorageBackend for deterministic crash testing
+///
+/// Every file has its current contents and its durable contents (as of its
+/// last sync); `crash()` throws away everything that was not synced and
+/// invalidates every open handle, so the database that was using them can be
+/// dropped and a new one opened on the same backend. Directory changes
+/// (create, replace, remove) are durable at once, like durable_fs makes them.
+/// Clones share the same files.
+///
+/// Faults are injected with `inject()`; `lie_about_fsync(true)` makes syncs
+/// succeed without making anything durable, like a disk with a volatile
+/// write cache. Nothing is random: the same workload hits the same faults.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedBackend {
+    state: Arc<Mutex<SimState>>,
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    /// Path -> inode
+    paths: HashMap<PathBuf, u64>,
+    inodes: HashMap<u64, SimFile>,
+    next_inode: u64,
+    /// Bumped by every crash: handles opened before it are dead
+    epoch: u64,
+    writes: u64,
+    syncs: u64,
+    /// Faults with the write or sync count they fire at
+    faults: Vec<(Fault, u64)>,
+    fsync_lies: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+struct SimFile {
+    data: Vec<u8>,
+    durable: Vec<u8>,
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        SimulatedBackend::default()
+    }
+
+    /// Arm a fault (see Fault)
+    pub fn inject(&self, fault: Fault) {
+        let mut state = self.state.lock();
+        let at = match fault {
+            Fault::FailWrite { nth } | Fault::TornWrite { nth, .. } => state.writes + nth,
+            Fault::FailSync { nth } => state.syncs + nth,
+        };
+        state.faults.push((fault, at));
+    }
+
+    /// Disarm every fault not fired yet
+    pub fn clear_faults(&self) {
+        self.state.lock().faults.clear();
+    }
+
+    /// Make syncs succeed without making anything durable
+    pub fn lie_about_fsync(&self, lie: bool) {
+        self.state.lock().fsync_lies = lie;
+    }
+
+    /// Lose power: every file goes back to what was synced, and every open
+    /// handle fails from now on
+    pub fn crash(&self) {
+        let mut state = self.state.lock();
+        state.epoch += 1;
+        let SimState { paths, inodes, .. } = &mut *state;
+        inodes.retain(|inode, _| paths.values().any(|linked| linked == inode));
+        for file in inodes.values_mut() {
+            file.data = file.durable.clone();
+        }
+    }
+
+    /// Writes performed so far (failed ones included)
+    pub fn write_count(&self) -> u64 {
+        self.state.lock().writes
+    }
+
+    /// Syncs performed so far (failed ones included)
+    pub fn sync_count(&self) -> u64 {
+        self.state.lock().syncs
+    }
+
+    /// Current contents of `path`, if it exists
+    pub fn contents(&self, path: &Path) -> Option<Vec<u8>> {
+        let state = self.state.lock();
+        let inode = state.paths.get(path)?;
+        Some(state.inodes[inode].data.clone())
+    }
+}
+
+impl SimState {
+    /// Count a write; the fault it fires, if any
+    fn next_write(&mut self) -> Option<Fault> {
+        self.writes += 1;
+        self.fire(self.writes, |fault| matches!(fault, Fault::FailWrite { .. } | Fault::TornWrite { .. }))
+    }
+
+    /// Count a sync; the fault it fires, if any
+    fn next_sync(&mut self) -> Option<Fault> {
+        self.syncs += 1;
+        self.fire(self.syncs, |fault| matches!(fault, Fault::FailSync { .. }))
+    }
+
+    fn fire(&mut self, count: u64, kind: impl Fn(&Fault) -> bool) -> Option<Fault> {
+        let index = self.faults.iter().position(|(fault, at)| kind(fault) && *at == count)?;
+        Some(self.faults.remove(index).0)
+    }
+}
+
+impl StorageBackend for SimulatedBackend {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn StorageFile>> {
+        let mut state = self.state.lock();
+        let inode = match state.paths.get(path) {
+            Some(&inode) => inode,
+            None => {
+                state.next_inode += 1;
+                let inode = state.next_inode;
+                state.paths.insert(path.to_path_buf(), inode);
+                state.inodes.insert(inode, SimFile::default());
+                inode
+            }
+        };
+        Ok(Box::new(SimHandle {
+            state: Arc::clone(&self.state),
+            inode,
+            epoch: state.epoch,
+            position: 0,
+        }))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.state.lock().paths.contains_key(path)
+    }
+
+    fn replace(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.state.lock();
+        let inode = state.paths.remove(from).ok_or_else(|| not_found(from))?;
+        state.paths.insert(to.to_path_buf(), inode);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.state.lock().paths.remove(path).map(|_| ()).ok_or_else(|| not_found(path))
+    }
+}
+
+/// An open file of a SimulatedBackend
+struct SimHandle {
+    state: Arc<Mutex<SimState>>,
+    inode: u64,
+    /// Epoch the handle was opened in (see SimState::epoch)
+    epoch: u64,
+    position: u64,
+}
+
+impl SimHandle {
+    /// Run `f` on the file, unless a crash happened since the handle was opened
+    fn with_file<T>(&self, f: impl FnOnce(&mut SimState, u64) -> io::Result<T>) -> io::Result<T> {
+        let mut state = self.state.lock();
+        if state.epoch != self.epoch {
+            return Err(io::Error::other("simulated crash: handle opened before it"));
+        }
+        f(&mut state, self.inode)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.with_file(|state, inode| {
+            if state.next_sync().is_some() {
+                return Err(io::Error::other("simulated sync failure"));
+            }
+            if !state.fsync_lies {
+                let file = state.inodes.get_mut(&inode).expect("open file has an inode");
+                file.durable = file.data.clone();
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Read for SimHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.position as usize;
+        let read = self.with_file(|state, inode| {
+            let data = &state.inodes[&inode].data;
+            let available = data.get(position..).unwrap_or_default();
+            let read = available.len().min(buf.len());
+            buf[..read].copy_from_slice(&available[..read]);
+            Ok(read)
+        })?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for SimHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let position = self.position as usize;
+        let written = self.with_file(|state, inode| {
+            let (len, error) = match state.next_write() {
+                None => (buf.len(), None),
+                Some(Fault::TornWrite { keep, .. }) => (keep.min(buf.len()), Some("simulated torn write")),
+                Some(_) => (0, Some("simulated write failure")),
+            };
+            let data = &mut state.inodes.get_mut(&inode).expect("open file has an inode").data;
+            if len > 0 {
+                if data.len() < position + len {
+                    data.resize(position + len, 0);
+                }
+                data[position..position + len].copy_from_slice(&buf[..len]);
+            }
+            match error {
+                Some(message) => Err(io::Error::other(message)),
+                None => Ok(len),
+            }
+        })?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SimHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.size()?.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file"))?;
+        Ok(self.position)
+    }
+}
+
+impl StorageFile for SimHandle {
+    fn size(&self) -> io::Result<u64> {
+        self.with_file(|state, inode| Ok(state.inodes[&inode].data.len() as u64))
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        self.with_file(|state, inode| {
+            state.inodes.get_mut(&inode).expect("open file has an inode").data.resize(len as usize, 0);
+            Ok(())
+        })
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        self.sync()
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        self.sync()
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn StorageFile>> {
+        self.with_file(|_, _| Ok(()))?;
+        Ok(Box::new(SimHandle {
+            state: Arc::clone(&self.state),
+            inode: self.inode,
+            epoch: self.epoch,
+            position: self.position,
+        }))
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display()))
+}
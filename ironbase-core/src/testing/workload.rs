@@ -0,0 +1,309 @@
+// testing/workload.rs
+// Crash workloads: operations, simulated crashes and the harness running them
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::collection_core::CollectionCore;
+use crate::database::DatabaseCore;
+use crate::transaction::TransactionId;
+use super::invariants::{check_indexes, read_state, Model, Violation};
+
+/// Documents are keyed 0..KEYS, so a workload keeps hitting the same ones
+pub const KEYS: u8 = 16;
+
+const COLLECTION: &str = "crash";
+
+/// One step of a crash workload
+///
+/// Writes that do not apply (an insert of an existing key, an update of a
+/// missing one) are skipped, so any sequence of Ops is a valid workload.
+/// While a transaction is open, writes go to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Insert(u8),
+    Update(u8),
+    Delete(u8),
+    /// Begin a (durable) transaction
+    Begin,
+    Commit,
+    Rollback,
+    Flush,
+    Compact,
+    Crash(CrashPoint),
+}
+
+/// How much of what the data file and the WAL got since their last fsync
+/// survives a crash, in 255ths (0: truncated back to the fsync, 255: all of it)
+///
+/// The metadata journal is kept whole: it is fsynced with every id
+/// reservation, which the harness cannot see from outside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashPoint {
+    pub data: u8,
+    pub wal: u8,
+}
+
+impl Op {
+    /// Decode a workload from raw bytes (for fuzzers)
+    ///
+    /// Every byte string decodes to something; a truncated trailing Op is dropped.
+    pub fn decode(bytes: &[u8]) -> Vec<Op> {
+        let mut ops = Vec::new();
+        let mut bytes = bytes.iter().copied();
+        while let Some(tag) = bytes.next() {
+            let op = match tag % 16 {
+                0..=3 => bytes.next().map(|key| Op::Insert(key % KEYS)),
+                4..=6 => bytes.next().map(|key| Op::Update(key % KEYS)),
+                7..=8 => bytes.next().map(|key| Op::Delete(key % KEYS)),
+                9 => Some(Op::Begin),
+                10 => Some(Op::Commit),
+                11 => Some(Op::Rollback),
+                12 => Some(Op::Flush),
+                13 => Some(Op::Compact),
+                _ => match (bytes.next(), bytes.next()) {
+                    (Some(data), Some(wal)) => Some(Op::Crash(CrashPoint { data, wal })),
+                    _ => None,
+                },
+            };
+            match op {
+                Some(op) => ops.push(op),
+                None => break,
+            }
+        }
+        ops
+    }
+}
+
+/// Lengths of the data file and the WAL
+#[derive(Debug, Clone, Copy, Default)]
+struct FileLengths {
+    data: u64,
+    wal: u64,
+}
+
+/// A transaction in progress: its id and the writes it holds
+struct OpenTransaction {
+    id: TransactionId,
+    writes: Vec<(u8, Option<i64>)>,
+}
+
+/// Runs a crash workload against a database and its Model
+pub struct CrashHarness {
+    path: PathBuf,
+    db: Option<DatabaseCore>,
+    model: Model,
+    transaction: Option<OpenTransaction>,
+    /// File lengths at the last fsync of everything
+    synced: FileLengths,
+    /// Every write gets a new value
+    next_value: i64,
+}
+
+impl CrashHarness {
+    /// Create the database at `path` (which must not exist yet)
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Violation> {
+        let path = path.as_ref().to_path_buf();
+        let db = DatabaseCore::open(&path)?;
+        let collection = db.collection(COLLECTION)?;
+        collection.create_index("key".to_string(), true)?;
+        collection.create_index("value".to_string(), false)?;
+        db.flush()?;
+
+        let mut harness = CrashHarness {
+            path,
+            db: Some(db),
+            model: Model::new(),
+            transaction: None,
+            synced: FileLengths::default(),
+            next_value: 0,
+        };
+        harness.synced = harness.file_lengths();
+        Ok(harness)
+    }
+
+    /// Run `ops` on a new database at `path`, checking the invariants after each
+    pub fn run<P: AsRef<Path>>(path: P, ops: &[Op]) -> Result<(), Violation> {
+        let mut harness = CrashHarness::new(path)?;
+        for op in ops {
+            harness.apply(*op)?;
+        }
+        Ok(())
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    pub fn db(&self) -> &DatabaseCore {
+        self.db.as_ref().expect("database is open between operations")
+    }
+
+    /// Apply one Op, then check the invariants
+    pub fn apply(&mut self, op: Op) -> Result<(), Violation> {
+        match op {
+            Op::Insert(key) => self.insert(key)?,
+            Op::Update(key) => self.update(key)?,
+            Op::Delete(key) => self.delete(key)?,
+            Op::Begin => {
+                if self.transaction.is_none() {
+                    let id = self.db().begin_transaction();
+                    self.transaction = Some(OpenTransaction { id, writes: Vec::new() });
+                }
+            }
+            Op::Commit => {
+                if let Some(transaction) = self.transaction.take() {
+                    self.db().commit_transaction_with_indexes(transaction.id)?;
+                    for (key, value) in transaction.writes {
+                        self.model.write(key, value);
+                    }
+                    self.synced();
+                }
+            }
+            Op::Rollback => {
+                if let Some(transaction) = self.transaction.take() {
+                    self.db().rollback_transaction(transaction.id)?;
+                    self.model.discard(transaction.writes.into_iter().filter_map(|(_, value)| value));
+                }
+            }
+            Op::Flush => {
+                self.db().flush()?;
+                self.synced();
+            }
+            Op::Compact => {
+                if self.transaction.is_none() {
+                    self.db().compact()?;
+                    self.synced();
+                }
+            }
+            Op::Crash(point) => return self.crash(point),
+        }
+        self.check()
+    }
+
+    /// Check the running database against the model
+    pub fn check(&self) -> Result<(), Violation> {
+        let collection = self.collection()?;
+        let state = read_state(&collection)?;
+        self.model.check_live(&state)?;
+        check_indexes(&collection, &state)
+    }
+
+    fn insert(&mut self, key: u8) -> Result<(), Violation> {
+        if self.model.get(key).is_some() || self.in_transaction(key) {
+            return Ok(());
+        }
+        let value = self.next_value();
+        let doc: HashMap<String, Value> = [("key".to_string(), json!(key)), ("value".to_string(), json!(value))].into();
+        match &mut self.transaction {
+            Some(transaction) => {
+                self.db.as_ref().unwrap().insert_one_tx(COLLECTION, doc, transaction.id)?;
+                transaction.writes.push((key, Some(value)));
+            }
+            None => {
+                self.collection()?.insert_one(doc)?;
+                self.model.write(key, Some(value));
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, key: u8) -> Result<(), Violation> {
+        if self.model.get(key).is_none() || self.in_transaction(key) {
+            return Ok(());
+        }
+        let value = self.next_value();
+        match &mut self.transaction {
+            Some(transaction) => {
+                let doc = json!({"key": key, "value": value});
+                self.db.as_ref().unwrap().update_one_tx(COLLECTION, &json!({"key": key}), doc, transaction.id)?;
+                transaction.writes.push((key, Some(value)));
+            }
+            None => {
+                self.collection()?.update_one(&json!({"key": key}), &json!({"$set": {"value": value}}))?;
+                self.model.write(key, Some(value));
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, key: u8) -> Result<(), Violation> {
+        if self.model.get(key).is_none() || self.in_transaction(key) {
+            return Ok(());
+        }
+        match &mut self.transaction {
+            Some(transaction) => {
+                self.db.as_ref().unwrap().delete_one_tx(COLLECTION, &json!({"key": key}), transaction.id)?;
+                transaction.writes.push((key, None));
+            }
+            None => {
+                self.collection()?.delete_one(&json!({"key": key}))?;
+                self.model.write(key, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the database without a clean shutdown, cut the data file and the
+    /// WAL somewhere past their last fsync, then recover and check what survived
+    fn crash(&mut self, point: CrashPoint) -> Result<(), Violation> {
+        if let Some(transaction) = self.transaction.take() {
+            self.model.discard(transaction.writes.into_iter().filter_map(|(_, value)| value));
+        }
+        std::mem::forget(self.db.take());
+
+        let written = self.file_lengths();
+        cut(&self.path, self.synced.data, written.data, point.data)?;
+        cut(&self.path.with_extension("wal"), self.synced.wal, written.wal, point.wal)?;
+
+        self.db = Some(DatabaseCore::open(&self.path)?);
+        let collection = self.collection()?;
+        let state = read_state(&collection)?;
+        self.model.check_recovered(&state)?;
+        check_indexes(&collection, &state)?;
+
+        // Whatever recovery kept is the new starting point
+        self.model.recovered(state);
+        self.db().flush()?;
+        self.synced();
+        Ok(())
+    }
+
+    fn collection(&self) -> Result<CollectionCore, Violation> {
+        Ok(self.db().collection(COLLECTION)?)
+    }
+
+    fn in_transaction(&self, key: u8) -> bool {
+        self.transaction.as_ref().is_some_and(|transaction| transaction.writes.iter().any(|&(k, _)| k == key))
+    }
+
+    fn next_value(&mut self) -> i64 {
+        self.next_value += 1;
+        self.next_value
+    }
+
+    fn synced(&mut self) {
+        self.model.sync();
+        self.synced = self.file_lengths();
+    }
+
+    fn file_lengths(&self) -> FileLengths {
+        let len = |path: PathBuf| std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+        FileLengths {
+            data: len(self.path.clone()),
+            wal: len(self.path.with_extension("wal")),
+        }
+    }
+}
+
+/// Truncate `path` to `synced` plus `keep`/255 of what was written after it
+fn cut(path: &Path, synced: u64, written: u64, keep: u8) -> Result<(), Violation> {
+    if written <= synced {
+        return Ok(());
+    }
+    let len = synced + (written - synced) * keep as u64 / 255;
+    let file = OpenOptions::new().write(true).open(path).map_err(crate::error::MongoLiteError::from)?;
+    file.set_len(len).map_err(crate::error::MongoLiteError::from)?;
+    Ok(())
+}
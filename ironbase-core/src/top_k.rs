@@ -0,0 +1,132 @@
+// ironbase-core/src/top_k.rs
+// Top-k selection: the first k documents of a sort without sorting them all
+//
+// A sort followed by a limit only returns k documents. TopK keeps them in a
+// binary max-heap whose top is the last of them: each further document
+// either replaces the top or is dropped, so n documents cost O(n log k)
+// time and O(k) memory instead of a full sort's O(n log n) and O(n). Ties
+// keep their input order - the result is what a stable sort followed by
+// the limit returns.
+//
+// find_with_options selects through it when a limit comes with a sort, and
+// so does the $sort + $limit stage the aggregation optimizer fuses.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use serde_json::Value;
+
+/// Order a TopK selects documents by
+pub type DocumentOrder<'a> = &'a dyn Fn(&Value, &Value) -> Ordering;
+
+/// The first `limit` documents pushed, in `order`
+pub struct TopK<'a> {
+    heap: BinaryHeap<Entry<'a>>,
+    limit: usize,
+    order: DocumentOrder<'a>,
+    /// Documents pushed so far (the input position of the next one)
+    pushed: usize,
+}
+
+impl<'a> TopK<'a> {
+    pub fn new(limit: usize, order: DocumentOrder<'a>) -> Self {
+        TopK {
+            heap: BinaryHeap::with_capacity(limit.saturating_add(1).min(1024)),
+            limit,
+            order,
+            pushed: 0,
+        }
+    }
+
+    /// Offer a document; returns the one this drops from the first `limit`
+    /// (possibly `doc` itself)
+    pub fn push(&mut self, doc: Value) -> Option<Value> {
+        if self.limit == 0 {
+            return Some(doc);
+        }
+        let entry = Entry { order: self.order, doc, seq: self.pushed };
+        self.pushed += 1;
+        if self.heap.len() < self.limit {
+            self.heap.push(entry);
+            return None;
+        }
+
+        // Full: the new document replaces the last kept one if it sorts before it
+        let mut last = self.heap.peek_mut()?;
+        if entry < *last {
+            Some(std::mem::replace(&mut *last, entry).doc)
+        } else {
+            Some(entry.doc)
+        }
+    }
+
+    /// The kept documents, in order
+    pub fn into_sorted_vec(self) -> Vec<Value> {
+        self.heap.into_sorted_vec().into_iter().map(|entry| entry.doc).collect()
+    }
+}
+
+/// The first `limit` of `docs` in `order`
+pub fn top_k(docs: impl IntoIterator<Item = Value>, limit: usize, order: DocumentOrder<'_>) -> Vec<Value> {
+    let mut top = TopK::new(limit, order);
+    for doc in docs {
+        top.push(doc);
+    }
+    top.into_sorted_vec()
+}
+
+/// A kept document: in `order`, ties in input order
+struct Entry<'a> {
+    order: DocumentOrder<'a>,
+    doc: Value,
+    seq: usize,
+}
+
+impl Ord for Entry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.order)(&self.doc, &other.doc).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for Entry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_top_k_matches_stable_sort() {
+        let docs: Vec<Value> = (0..100).map(|n| json!({"n": n, "key": (n * 37) % 10})).collect();
+        let order = |a: &Value, b: &Value| a["key"].as_i64().cmp(&b["key"].as_i64());
+
+        let mut sorted = docs.clone();
+        sorted.sort_by(order);
+        for limit in [0, 1, 7, 10, 100, 150] {
+            let expected: Vec<Value> = sorted.iter().take(limit).cloned().collect();
+            assert_eq!(top_k(docs.clone(), limit, &order), expected);
+        }
+    }
+
+    #[test]
+    fn test_push_returns_dropped_document() {
+        let order = |a: &Value, b: &Value| a.as_i64().cmp(&b.as_i64());
+        let mut top = TopK::new(2, &order);
+        assert_eq!(top.push(json!(5)), None);
+        assert_eq!(top.push(json!(3)), None);
+        assert_eq!(top.push(json!(4)), Some(json!(5)));
+        assert_eq!(top.push(json!(9)), Some(json!(9)));
+        assert_eq!(top.into_sorted_vec(), vec![json!(3), json!(4)]);
+    }
+}
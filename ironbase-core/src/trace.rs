@@ -0,0 +1,222 @@
+// ironbase-core/src/trace.rs
+// Operation capture and replay
+//
+// While a capture is running, every collection operation is appended to a
+// trace file as one JSON line: the logical operation and its arguments (not
+// the bytes it wrote), the collection, when it finished and whether it
+// succeeded. replay() runs a trace against another database - usually a
+// fresh one - to reproduce a reported problem or to benchmark a realistic
+// workload. Captured: insert_one/many, update_one/many, delete_one/many,
+// find, find_one, count_documents, aggregate, create_index and drop_index.
+// Transactions and the other variants (find_with_options, ...) are not.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::collection_core::CollectionCore;
+use crate::database::DatabaseCore;
+use crate::error::{MongoLiteError, Result};
+
+/// A captured operation and its arguments
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TracedOp {
+    InsertOne { doc: Value },
+    InsertMany { docs: Vec<Value> },
+    UpdateOne { filter: Value, update: Value },
+    UpdateMany { filter: Value, update: Value },
+    DeleteOne { filter: Value },
+    DeleteMany { filter: Value },
+    Find { filter: Value },
+    FindOne { filter: Value },
+    CountDocuments { filter: Value },
+    Aggregate { pipeline: Value },
+    CreateIndex { field: String, unique: bool },
+    DropIndex { name: String },
+}
+
+/// One line of a trace file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Position in the trace, from 1
+    pub seq: u64,
+    /// Microseconds since the capture started, when the operation finished
+    pub at_us: u64,
+    pub collection: String,
+    #[serde(flatten)]
+    pub op: TracedOp,
+    /// The operation succeeded when it was captured
+    pub ok: bool,
+}
+
+/// Result of replaying a trace
+#[derive(Debug, Clone, Default)]
+pub struct ReplayStats {
+    /// Operations run
+    pub operations: u64,
+    /// Operations that failed during the replay
+    pub failed: u64,
+    /// Operations whose success differs from the capture (first one: `first_divergence`)
+    pub diverged: u64,
+    /// seq of the first diverging operation
+    pub first_divergence: Option<u64>,
+    pub elapsed: Duration,
+}
+
+/// A running capture
+struct Capture {
+    writer: BufWriter<File>,
+    started: Instant,
+    seq: u64,
+    /// First write error; the capture stops recording at it
+    error: Option<std::io::Error>,
+}
+
+/// Operation capture of a database, shared with every collection handle
+#[derive(Default)]
+pub struct TraceRecorder {
+    capture: Mutex<Option<Capture>>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        TraceRecorder::default()
+    }
+
+    /// Start capturing into `path` (created or truncated)
+    pub fn start<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut capture = self.capture.lock();
+        if capture.is_some() {
+            return Err(MongoLiteError::InvalidQuery("an operation trace is already being captured".to_string()));
+        }
+        *capture = Some(Capture {
+            writer: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+            seq: 0,
+            error: None,
+        });
+        Ok(())
+    }
+
+    /// Stop capturing; returns the number of operations captured, or the
+    /// first error writing the trace
+    pub fn stop(&self) -> Result<u64> {
+        let Some(mut capture) = self.capture.lock().take() else {
+            return Ok(0);
+        };
+        if let Some(error) = capture.error.take() {
+            return Err(error.into());
+        }
+        capture.writer.flush()?;
+        capture.writer.get_ref().sync_all()?;
+        Ok(capture.seq)
+    }
+
+    /// Cheap check so callers can skip building the TracedOp
+    pub fn is_active(&self) -> bool {
+        self.capture.lock().is_some()
+    }
+
+    /// Append an operation to the trace, if a capture is running
+    ///
+    /// Never fails the operation: a write error is kept for stop().
+    pub fn record(&self, collection: &str, op: TracedOp, ok: bool) {
+        let mut capture = self.capture.lock();
+        let Some(capture) = capture.as_mut().filter(|capture| capture.error.is_none()) else {
+            return;
+        };
+        capture.seq += 1;
+        let entry = TraceEntry {
+            seq: capture.seq,
+            at_us: capture.started.elapsed().as_micros() as u64,
+            collection: collection.to_string(),
+            op,
+            ok,
+        };
+        let written = serde_json::to_writer(&mut capture.writer, &entry)
+            .map_err(std::io::Error::from)
+            .and_then(|_| capture.writer.write_all(b"\n"));
+        if let Err(error) = written {
+            capture.error = Some(error);
+        }
+    }
+}
+
+/// Read every entry of a trace file
+pub fn read_trace<P: AsRef<Path>>(path: P) -> Result<Vec<TraceEntry>> {
+    let mut entries = Vec::new();
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            MongoLiteError::Corruption(format!("trace line {}: {}", number + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Run the operations of a trace file against `db`, in order
+///
+/// Failing operations do not stop the replay: they are counted, and so are
+/// the ones whose outcome differs from the capture.
+pub fn replay<P: AsRef<Path>>(db: &DatabaseCore, path: P) -> Result<ReplayStats> {
+    let entries = read_trace(path)?;
+    let started = Instant::now();
+    let mut stats = ReplayStats::default();
+    let mut collections: HashMap<String, CollectionCore> = HashMap::new();
+
+    for entry in entries {
+        if !collections.contains_key(&entry.collection) {
+            collections.insert(entry.collection.clone(), db.collection(&entry.collection)?);
+        }
+        let ok = run(&collections[&entry.collection], &entry.op).is_ok();
+
+        stats.operations += 1;
+        if !ok {
+            stats.failed += 1;
+        }
+        if ok != entry.ok {
+            stats.diverged += 1;
+            stats.first_divergence.get_or_insert(entry.seq);
+        }
+    }
+    stats.elapsed = started.elapsed();
+    Ok(stats)
+}
+
+fn run(collection: &CollectionCore, op: &TracedOp) -> Result<()> {
+    match op {
+        TracedOp::InsertOne { doc } => collection.insert_one(fields(doc)?).map(drop),
+        TracedOp::InsertMany { docs } => {
+            collection.insert_many(docs.iter().map(fields).collect::<Result<_>>()?).map(drop)
+        }
+        TracedOp::UpdateOne { filter, update } => collection.update_one(filter, update).map(drop),
+        TracedOp::UpdateMany { filter, update } => collection.update_many(filter, update).map(drop),
+        TracedOp::DeleteOne { filter } => collection.delete_one(filter).map(drop),
+        TracedOp::DeleteMany { filter } => collection.delete_many(filter).map(drop),
+        TracedOp::Find { filter } => collection.find(filter).map(drop),
+        TracedOp::FindOne { filter } => collection.find_one(filter).map(drop),
+        TracedOp::CountDocuments { filter } => collection.count_documents(filter).map(drop),
+        TracedOp::Aggregate { pipeline } => collection.aggregate(pipeline).map(drop),
+        TracedOp::CreateIndex { field, unique } => collection.create_index(field.clone(), *unique).map(drop),
+        TracedOp::DropIndex { name } => collection.drop_index(name),
+    }
+}
+
+/// A captured document back as insert fields
+fn fields(doc: &Value) -> Result<HashMap<String, Value>> {
+    match doc {
+        Value::Object(map) => Ok(map.clone().into_iter().collect()),
+        other => Err(MongoLiteError::Corruption(format!("traced document is not an object: {}", other))),
+    }
+}
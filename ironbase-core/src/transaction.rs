@@ -22,6 +22,17 @@ pub enum TransactionState {
     Aborted,
 }
 
+/// When commit acknowledges a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Durability {
+    /// Return once the WAL and data are fsynced - survives a crash
+    #[default]
+    Durable,
+    /// Return once written; the sync thread fsyncs shortly after. A crash may
+    /// lose the latest relaxed commits but never tears older ones.
+    Relaxed,
+}
+
 /// A single operation within a transaction
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Operation {
@@ -69,6 +80,8 @@ pub enum IndexKey {
     Float(OrderedFloat),
     Bool(bool),
     Null,
+    /// Integers above i64::MAX
+    UInt(u64),
 }
 
 /// Ordered float wrapper for IndexKey
@@ -114,6 +127,8 @@ impl From<&Value> for IndexKey {
             Value::Number(n) => {
                 if let Some(i) = n.as_i64() {
                     IndexKey::Int(i)
+                } else if let Some(u) = n.as_u64() {
+                    IndexKey::UInt(u)
                 } else {
                     IndexKey::Float(OrderedFloat(n.as_f64().unwrap_or(0.0)))
                 }
@@ -150,6 +165,9 @@ pub struct Transaction {
 
     /// Current state
     state: TransactionState,
+
+    /// Commit acknowledgment level
+    durability: Durability,
 }
 
 impl Transaction {
@@ -161,6 +179,7 @@ impl Transaction {
             index_changes: HashMap::new(),
             metadata_changes: Vec::new(),
             state: TransactionState::Active,
+            durability: Durability::Durable,
         }
     }
 
@@ -174,6 +193,20 @@ impl Transaction {
         self.state == TransactionState::Active
     }
 
+    /// Commit acknowledgment level
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Choose the commit acknowledgment level (before commit)
+    pub fn set_durability(&mut self, durability: Durability) -> Result<()> {
+        if !self.is_active() {
+            return Err(MongoLiteError::TransactionCommitted);
+        }
+        self.durability = durability;
+        Ok(())
+    }
+
     /// Add an operation to the transaction buffer
     pub fn add_operation(&mut self, op: Operation) -> Result<()> {
         if !self.is_active() {
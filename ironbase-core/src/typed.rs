@@ -0,0 +1,110 @@
+// ironbase-core/src/typed.rs
+// Typed collection API on top of CollectionCore (serde)
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::collection_core::{CollectionCore, DeleteResult, UpdateResult};
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::find_options::FindOptions;
+
+/// Collection of documents of type `T`
+///
+/// Documents are stored as their serde JSON representation. `_id` handling:
+/// - on insert the database assigns the _id; an `_id` field of `T` is ignored
+/// - on reads `_id` is passed to `T` (declare it as
+///   `#[serde(rename = "_id", default)] id: Option<DocumentId>` to receive it);
+///   the internal `_collection` field is not
+///
+/// Queries and updates stay JSON - they address fields by their serialized names.
+pub struct Collection<T> {
+    core: CollectionCore,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Collection<T> {
+    pub fn new(core: CollectionCore) -> Self {
+        Collection {
+            core,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The untyped collection underneath
+    pub fn core(&self) -> &CollectionCore {
+        &self.core
+    }
+
+    pub fn name(&self) -> &str {
+        &self.core.name
+    }
+
+    /// Insert a document; returns the _id assigned to it
+    pub fn insert_one(&self, doc: &T) -> Result<DocumentId> {
+        self.core.insert_one(to_fields(doc)?)
+    }
+
+    /// Find documents matching a JSON query
+    pub fn find(&self, query: &Value) -> Result<Vec<T>> {
+        self.core.find(query)?.into_iter().map(from_document).collect()
+    }
+
+    /// find() with projection, sort, limit and skip
+    /// A projection must keep every field `T` requires
+    pub fn find_with_options(&self, query: &Value, options: FindOptions) -> Result<Vec<T>> {
+        self.core.find_with_options(query, options)?.into_iter().map(from_document).collect()
+    }
+
+    pub fn find_one(&self, query: &Value) -> Result<Option<T>> {
+        self.core.find_one(query)?.map(from_document).transpose()
+    }
+
+    pub fn find_by_id(&self, id: &DocumentId) -> Result<Option<T>> {
+        let found = self.core.find_by_ids(std::slice::from_ref(id))?.pop().flatten();
+        found.map(from_document).transpose()
+    }
+
+    pub fn count_documents(&self, query: &Value) -> Result<u64> {
+        self.core.count_documents(query)
+    }
+
+    /// Apply update operators ({"$set": ...}) to the first match
+    pub fn update_one(&self, query: &Value, update: &Value) -> Result<UpdateResult> {
+        self.core.update_one(query, update)
+    }
+
+    pub fn update_many(&self, query: &Value, update: &Value) -> Result<UpdateResult> {
+        self.core.update_many(query, update)
+    }
+
+    pub fn delete_one(&self, query: &Value) -> Result<DeleteResult> {
+        self.core.delete_one(query)
+    }
+
+    pub fn delete_many(&self, query: &Value) -> Result<DeleteResult> {
+        self.core.delete_many(query)
+    }
+}
+
+/// Serialize `doc` into insertable fields (the database assigns _id)
+fn to_fields<T: Serialize>(doc: &T) -> Result<HashMap<String, Value>> {
+    match serde_json::to_value(doc)? {
+        Value::Object(map) => Ok(map.into_iter()
+            .filter(|(key, _)| key != "_id")
+            .collect()),
+        other => Err(MongoLiteError::Serialization(format!(
+            "typed documents must serialize to a JSON object, got {}", other
+        ))),
+    }
+}
+
+/// Stored document -> `T`
+fn from_document<T: DeserializeOwned>(mut doc: Value) -> Result<T> {
+    if let Value::Object(ref mut map) = doc {
+        map.remove("_collection");
+    }
+    Ok(serde_json::from_value(doc)?)
+}
@@ -0,0 +1,62 @@
+// src/validation.rs
+// Collection integrity report types (see CollectionCore::validate)
+
+use serde::Serialize;
+use crate::document::DocumentId;
+
+/// A single inconsistency found by validate()
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationIssue {
+    /// Catalog entry points outside the data region or at a truncated record
+    UnreadableRecord { doc_id: DocumentId, offset: u64, error: String },
+    /// Record exists but is not a valid JSON document
+    MalformedDocument { doc_id: DocumentId, offset: u64, error: String },
+    /// Record's _id differs from the catalog key
+    IdMismatch { doc_id: DocumentId, offset: u64, found: Option<DocumentId> },
+    /// Record belongs to another collection
+    CollectionMismatch { doc_id: DocumentId, offset: u64, found: Option<String> },
+    /// Live document has a value for the indexed field but no index entry
+    MissingIndexEntry { index: String, doc_id: DocumentId },
+    /// Index entry points to a deleted or unknown document
+    StaleIndexEntry { index: String, doc_id: DocumentId },
+    /// Index entry key differs from the document's current field value
+    IndexKeyMismatch { index: String, doc_id: DocumentId },
+    /// Catalog offset does not start a record in the data region (full mode)
+    MisalignedOffset { doc_id: DocumentId, offset: u64 },
+    /// Data region does not parse cleanly to end of file (full mode)
+    TrailingGarbage { offset: u64, bytes: u64 },
+}
+
+/// Result of CollectionCore::validate()
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationReport {
+    pub collection: String,
+    pub full: bool,
+    /// True if no issues were found
+    pub valid: bool,
+    pub documents_checked: u64,
+    pub tombstones_checked: u64,
+    pub index_entries_checked: u64,
+    /// Records whose checksum was verified (data records carry no checksum yet)
+    pub checksums_verified: u64,
+    /// Records walked in the data region (full mode only)
+    pub records_scanned: u64,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn new(collection: &str, full: bool) -> Self {
+        ValidationReport {
+            collection: collection.to_string(),
+            full,
+            valid: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_issue(&mut self, issue: ValidationIssue) {
+        self.valid = false;
+        self.issues.push(issue);
+    }
+}
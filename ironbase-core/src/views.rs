@@ -0,0 +1,239 @@
+// ironbase-core/src/views.rs
+// Read-only views: a pipeline over a source collection, plain or materialized
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::aggregation::{MergeStage, OutputStage, Pipeline, WhenMatched, WhenNotMatched};
+use crate::collection_core::CollectionCore;
+use crate::contention::TimedRwLock;
+use crate::document::DocumentId;
+use crate::error::{Result, MongoLiteError};
+use crate::storage::{StorageEngine, OPLOG_COLLECTION};
+
+/// When a materialized view's stored results catch up with its source
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewRefresh {
+    /// Only on View::refresh() / DatabaseCore::refresh_view()
+    OnDemand,
+    /// Before every read. Changes are applied one source document at a time
+    /// from the oplog when the pipeline allows it (Pipeline::is_per_document)
+    /// and the oplog has been on since the last refresh; otherwise the
+    /// pipeline is rerun
+    Incremental,
+}
+
+impl std::str::FromStr for ViewRefresh {
+    type Err = MongoLiteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "on_demand" => Ok(ViewRefresh::OnDemand),
+            "incremental" => Ok(ViewRefresh::Incremental),
+            other => Err(MongoLiteError::InvalidConfig(format!(
+                "unknown view refresh '{}' (expected on_demand or incremental)", other
+            ))),
+        }
+    }
+}
+
+/// A view as stored in its collection's metadata
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ViewDefinition {
+    pub source: String,
+    pub pipeline: Value,
+    /// None: the pipeline runs on every read and nothing is stored
+    pub materialized: Option<ViewRefresh>,
+    /// Source LSN the stored results reflect
+    #[serde(default)]
+    pub refreshed_lsn: u64,
+    /// Oplog epoch (see StorageEngine::oplog_epoch) and last entry the stored
+    /// results reflect
+    #[serde(default)]
+    pub oplog_epoch: Option<String>,
+    #[serde(default)]
+    pub oplog_seq: u64,
+}
+
+impl ViewDefinition {
+    pub fn new(source: &str, pipeline: Value, materialized: Option<ViewRefresh>) -> Result<Self> {
+        if Pipeline::from_json(&pipeline)?.output().is_some() {
+            return Err(MongoLiteError::AggregationError("views cannot use $out / $merge".to_string()));
+        }
+        Ok(ViewDefinition {
+            source: source.to_string(),
+            pipeline,
+            materialized,
+            refreshed_lsn: 0,
+            oplog_epoch: None,
+            oplog_seq: 0,
+        })
+    }
+
+    /// The view's pipeline followed by `stages`
+    fn then(&self, stages: &Value) -> Result<Value> {
+        let stages = stages.as_array()
+            .ok_or_else(|| MongoLiteError::AggregationError("Pipeline must be an array".to_string()))?;
+        let mut pipeline = self.pipeline.as_array().cloned().unwrap_or_default();
+        pipeline.extend(stages.iter().cloned());
+        Ok(Value::Array(pipeline))
+    }
+}
+
+/// Handle on a view (see DatabaseCore::create_view)
+///
+/// Reads of a plain view run its pipeline on the source, with the query or
+/// pipeline of the read appended. A materialized view is read from its stored
+/// results, which `db.collection(name)` can read as well - without catching up.
+pub struct View {
+    name: String,
+    storage: Arc<TimedRwLock<StorageEngine>>,
+}
+
+impl View {
+    pub(crate) fn new(name: &str, storage: Arc<TimedRwLock<StorageEngine>>) -> Self {
+        View { name: name.to_string(), storage }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn definition(&self) -> Result<ViewDefinition> {
+        self.storage.read().get_collection_meta(&self.name)
+            .ok_or_else(|| MongoLiteError::CollectionNotFound(self.name.clone()))?
+            .view.clone()
+            .ok_or_else(|| MongoLiteError::InvalidQuery(format!("'{}' is a collection, not a view", self.name)))
+    }
+
+    pub fn find(&self, query_json: &Value) -> Result<Vec<Value>> {
+        let definition = self.definition()?;
+        match definition.materialized {
+            None => self.source(&definition)?.aggregate(&definition.then(&json!([{"$match": query_json}]))?),
+            Some(_) => {
+                self.catch_up(&definition)?;
+                self.stored()?.find(query_json)
+            }
+        }
+    }
+
+    pub fn count_documents(&self, query_json: &Value) -> Result<u64> {
+        let definition = self.definition()?;
+        match definition.materialized {
+            None => Ok(self.find(query_json)?.len() as u64),
+            Some(_) => {
+                self.catch_up(&definition)?;
+                self.stored()?.count_documents(query_json)
+            }
+        }
+    }
+
+    /// Run `pipeline_json` on the view's documents
+    pub fn aggregate(&self, pipeline_json: &Value) -> Result<Vec<Value>> {
+        let definition = self.definition()?;
+        match definition.materialized {
+            None => self.source(&definition)?.aggregate(&definition.then(pipeline_json)?),
+            Some(_) => {
+                self.catch_up(&definition)?;
+                self.stored()?.aggregate(pipeline_json)
+            }
+        }
+    }
+
+    /// Recompute a materialized view's results (no-op for a plain view)
+    pub fn refresh(&self) -> Result<()> {
+        let definition = self.definition()?;
+        if definition.materialized.is_none() {
+            return Ok(());
+        }
+        self.recompute(&definition)
+    }
+
+    fn source(&self, definition: &ViewDefinition) -> Result<CollectionCore> {
+        CollectionCore::new(definition.source.clone(), Arc::clone(&self.storage))
+    }
+
+    fn stored(&self) -> Result<CollectionCore> {
+        CollectionCore::new(self.name.clone(), Arc::clone(&self.storage))
+    }
+
+    /// Source LSN, oplog epoch and newest oplog entry - read together, so a
+    /// write after this point is caught by the next refresh
+    fn position(&self, definition: &ViewDefinition) -> (u64, Option<String>, Option<u64>, u64) {
+        let storage = self.storage.read();
+        let (oldest, newest) = storage.oplog_range();
+        (storage.collection_lsn(&definition.source), storage.oplog_epoch().map(str::to_string), oldest, newest)
+    }
+
+    fn recompute(&self, definition: &ViewDefinition) -> Result<()> {
+        let (lsn, epoch, _, newest) = self.position(definition);
+        let source = self.source(definition)?;
+        let results = source.aggregate(&definition.pipeline)?;
+        source.write_aggregation_output(&OutputStage::Out(self.name.clone()), results, Vec::new())?;
+        self.mark_refreshed(lsn, epoch, newest);
+        Ok(())
+    }
+
+    /// Bring an incremental view up to date with its source
+    fn catch_up(&self, definition: &ViewDefinition) -> Result<()> {
+        if definition.materialized != Some(ViewRefresh::Incremental) {
+            return Ok(());
+        }
+        let (lsn, epoch, oldest, newest) = self.position(definition);
+        if lsn == definition.refreshed_lsn {
+            return Ok(());
+        }
+
+        // Every write since the last refresh must still be in the oplog
+        let pipeline = Pipeline::from_json(&definition.pipeline)?;
+        let logged = epoch.is_some()
+            && epoch == definition.oplog_epoch
+            && (newest == definition.oplog_seq || oldest.is_some_and(|oldest| oldest <= definition.oplog_seq + 1));
+        if !pipeline.is_per_document() || !logged {
+            return self.recompute(definition);
+        }
+
+        let oplog = CollectionCore::new(OPLOG_COLLECTION.to_string(), Arc::clone(&self.storage))?;
+        let entries = oplog.find(&json!({
+            "_id": {"$gt": definition.oplog_seq, "$lte": newest},
+            "collection": definition.source,
+        }))?;
+        let mut seen = HashSet::new();
+        let ids: Vec<DocumentId> = entries.iter()
+            .filter_map(|entry| serde_json::from_value(entry.get("doc_id")?.clone()).ok())
+            .filter(|id: &DocumentId| seen.insert(id.clone()))
+            .collect();
+
+        // Rerun the pipeline on the current version of each changed document
+        let source = self.source(definition)?;
+        let (mut upserts, mut removals) = (Vec::new(), Vec::new());
+        for (id, doc) in ids.iter().zip(source.find_by_ids(&ids)?) {
+            match doc.map(|doc| pipeline.execute(vec![doc])).transpose()?.and_then(|mut out| out.pop()) {
+                Some(result) => upserts.push(result),
+                None => removals.push(id.clone()),
+            }
+        }
+        let merge = OutputStage::Merge(MergeStage {
+            into: self.name.clone(),
+            when_matched: WhenMatched::Replace,
+            when_not_matched: WhenNotMatched::Insert,
+        });
+        source.write_aggregation_output(&merge, upserts, removals)?;
+        self.mark_refreshed(lsn, epoch, newest);
+        Ok(())
+    }
+
+    /// Record what the stored results reflect (persisted with the next flush;
+    /// until then a reopened database recomputes)
+    fn mark_refreshed(&self, lsn: u64, epoch: Option<String>, oplog_seq: u64) {
+        let mut storage = self.storage.write();
+        if let Some(view) = storage.get_collection_meta_mut(&self.name).and_then(|meta| meta.view.as_mut()) {
+            view.refreshed_lsn = lsn;
+            view.oplog_epoch = epoch;
+            view.oplog_seq = oplog_seq;
+        }
+    }
+}
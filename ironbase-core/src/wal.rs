@@ -1,11 +1,14 @@
 // ironbase-core/src/wal.rs
 // Write-Ahead Log (WAL) for transaction durability
 
-use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::{Condvar, Mutex};
 
 use crate::error::{Result, MongoLiteError};
+use crate::storage::{FileSystem, StorageBackend, StorageFile};
 use crate::transaction::TransactionId;
 
 /// Entry type in the WAL
@@ -141,25 +144,307 @@ impl WALEntry {
     }
 }
 
+/// Group commit: many writers, one fsync
+///
+/// A dedicated sync thread owns the fsyncs. A writer calls `register()` once
+/// its bytes are written - that queues a sync request and returns a ticket -
+/// and then either `sync_to()`s the ticket (durable acknowledgment) or just
+/// carries on (relaxed acknowledgment). Each fsync of the thread covers every
+/// ticket queued before it started, so N concurrent commits cost far fewer
+/// than N fsyncs and relaxed callers never wait on the disk at all.
+///
+/// The sync thread is the GroupSync's own, or a SyncThread several of them
+/// share (`with_files_on`).
+pub struct GroupSync {
+    shared: Arc<GroupSyncShared>,
+    worker: Worker,
+}
+
+enum Worker {
+    Own(Option<std::thread::JoinHandle<()>>),
+    Shared(Arc<SyncThread>),
+}
+
+struct GroupSyncShared {
+    /// Files each sync covers
+    files: Vec<Box<dyn StorageFile>>,
+    state: Mutex<GroupSyncState>,
+    /// Signalled when tickets are queued (or on shutdown)
+    queued: Condvar,
+    /// Signalled when an fsync finishes
+    synced: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct GroupSyncState {
+    /// Last ticket handed out
+    registered: u64,
+    /// Every ticket up to this one is durable
+    synced: u64,
+    /// Ticket range (first, last) of the last failed fsync, with its error
+    failed: Option<(u64, u64, String)>,
+    /// fsyncs performed (for stats)
+    fsyncs: u64,
+    shutdown: bool,
+    /// Waiting in a shared SyncThread's queue
+    on_thread: bool,
+}
+
+impl GroupSync {
+    /// Group fsyncs of `file` (usually a `try_clone` of the file being written)
+    pub fn new(file: Box<dyn StorageFile>) -> Self {
+        Self::with_files(vec![file])
+    }
+
+    /// Group fsyncs of several files: a ticket is durable once all of them are
+    pub fn with_files(files: Vec<Box<dyn StorageFile>>) -> Self {
+        Self::with_files_on(files, None)
+    }
+
+    /// with_files() whose fsyncs run on `thread` if given, else on a thread
+    /// of its own
+    pub fn with_files_on(files: Vec<Box<dyn StorageFile>>, thread: Option<&Arc<SyncThread>>) -> Self {
+        let shared = Arc::new(GroupSyncShared {
+            files,
+            state: Mutex::new(GroupSyncState::default()),
+            queued: Condvar::new(),
+            synced: Condvar::new(),
+        });
+
+        let worker = match thread {
+            Some(thread) => Worker::Shared(Arc::clone(thread)),
+            None => {
+                let worker_shared = Arc::clone(&shared);
+                let worker = std::thread::Builder::new()
+                    .name("ironbase-sync".to_string())
+                    .spawn(move || Self::run(&worker_shared))
+                    .expect("failed to spawn sync thread");
+                Worker::Own(Some(worker))
+            }
+        };
+
+        GroupSync { shared, worker }
+    }
+
+    /// Sync thread: fsync whenever tickets are queued, drain on shutdown
+    fn run(shared: &GroupSyncShared) {
+        let mut state = shared.state.lock();
+
+        loop {
+            if state.synced >= state.registered {
+                if state.shutdown {
+                    return;
+                }
+                shared.queued.wait(&mut state);
+                continue;
+            }
+
+            state = Self::sync_round(shared, state);
+        }
+    }
+
+    /// One fsync for every ticket registered until now
+    fn sync_round<'a>(
+        shared: &'a GroupSyncShared,
+        state: parking_lot::MutexGuard<'a, GroupSyncState>,
+    ) -> parking_lot::MutexGuard<'a, GroupSyncState> {
+        let target = state.registered;
+        drop(state);
+
+        let result = shared.files.iter().try_for_each(|file| file.sync_all());
+
+        let mut state = shared.state.lock();
+        match result {
+            Ok(()) => {
+                state.synced = state.synced.max(target);
+                state.fsyncs += 1;
+            }
+            Err(e) => {
+                // Fail the waiters of this batch; later tickets get a fresh attempt
+                state.failed = Some((state.synced + 1, target, e.to_string()));
+                state.synced = state.synced.max(target);
+            }
+        }
+        shared.synced.notify_all();
+        state
+    }
+
+    /// Queue a sync covering everything written so far; returns its ticket
+    pub fn register(&self) -> u64 {
+        let mut state = self.shared.state.lock();
+        state.registered += 1;
+        match &self.worker {
+            Worker::Own(_) => {
+                self.shared.queued.notify_one();
+            }
+            Worker::Shared(thread) if !state.on_thread => {
+                state.on_thread = true;
+                thread.enqueue(Arc::clone(&self.shared));
+            }
+            Worker::Shared(_) => {}
+        }
+        state.registered
+    }
+
+    /// Block until everything up to `ticket` is on disk
+    pub fn sync_to(&self, ticket: u64) -> Result<()> {
+        let mut state = self.shared.state.lock();
+
+        while state.synced < ticket {
+            self.shared.synced.wait(&mut state);
+        }
+
+        match &state.failed {
+            Some((first, last, message)) if (*first..=*last).contains(&ticket) => {
+                Err(MongoLiteError::Io(std::io::Error::other(message.clone())))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of fsyncs performed so far
+    pub fn fsync_count(&self) -> u64 {
+        self.shared.state.lock().fsyncs
+    }
+}
+
+impl Drop for GroupSync {
+    fn drop(&mut self) {
+        // Relaxed commits still queued get their fsync before the thread exits
+        let mut state = self.shared.state.lock();
+        state.shutdown = true;
+        match &mut self.worker {
+            Worker::Own(worker) => {
+                drop(state);
+                self.shared.queued.notify_one();
+                if let Some(worker) = worker.take() {
+                    let _ = worker.join();
+                }
+            }
+            Worker::Shared(_) => {
+                while state.synced < state.registered {
+                    self.shared.synced.wait(&mut state);
+                }
+            }
+        }
+    }
+}
+
+/// A sync thread GroupSyncs share (GroupSync::with_files_on), so many open
+/// databases do not each keep threads of their own: it takes the groups
+/// with queued tickets in turn, one fsync round each. Lives as long as the
+/// last GroupSync on it; queued rounds are done before it exits.
+pub struct SyncThread {
+    shared: Arc<SyncThreadShared>,
+    worker: Option<std::thread::JoinHandle<()>>,
+}
+
+struct SyncThreadShared {
+    state: Mutex<SyncThreadState>,
+    /// Signalled when a group is queued (or on shutdown)
+    queued: Condvar,
+}
+
+#[derive(Default)]
+struct SyncThreadState {
+    pending: std::collections::VecDeque<Arc<GroupSyncShared>>,
+    shutdown: bool,
+}
+
+impl SyncThread {
+    pub fn new() -> Arc<Self> {
+        let shared = Arc::new(SyncThreadShared {
+            state: Mutex::new(SyncThreadState::default()),
+            queued: Condvar::new(),
+        });
+
+        let worker_shared = Arc::clone(&shared);
+        let worker = std::thread::Builder::new()
+            .name("ironbase-sync".to_string())
+            .spawn(move || Self::run(&worker_shared))
+            .expect("failed to spawn sync thread");
+
+        Arc::new(SyncThread { shared, worker: Some(worker) })
+    }
+
+    fn enqueue(&self, group: Arc<GroupSyncShared>) {
+        self.shared.state.lock().pending.push_back(group);
+        self.shared.queued.notify_one();
+    }
+
+    fn run(shared: &SyncThreadShared) {
+        let mut state = shared.state.lock();
+
+        loop {
+            let Some(group) = state.pending.pop_front() else {
+                if state.shutdown {
+                    return;
+                }
+                shared.queued.wait(&mut state);
+                continue;
+            };
+            drop(state);
+
+            // Tickets registered from here on queue the group again
+            let mut group_state = group.state.lock();
+            group_state.on_thread = false;
+            if group_state.synced < group_state.registered {
+                group_state = GroupSync::sync_round(&group, group_state);
+            }
+            drop(group_state);
+
+            state = shared.state.lock();
+        }
+    }
+}
+
+impl Drop for SyncThread {
+    fn drop(&mut self) {
+        self.shared.state.lock().shutdown = true;
+        self.shared.queued.notify_one();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for SyncThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncThread")
+            .field("pending", &self.shared.state.lock().pending.len())
+            .finish()
+    }
+}
+
 /// Write-Ahead Log file manager
 pub struct WriteAheadLog {
-    file: File,
+    file: Box<dyn StorageFile>,
     path: PathBuf,
+    sync: Arc<GroupSync>,
+    backend: Arc<dyn StorageBackend>,
+    /// Shared thread the fsyncs run on, if any (see SyncThread)
+    thread: Option<Arc<SyncThread>>,
 }
 
 impl WriteAheadLog {
     /// Open or create a WAL file
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_backend(Arc::new(FileSystem), path)
+    }
+
+    /// Open or create a WAL file of `backend`
+    pub fn open_with_backend(backend: Arc<dyn StorageBackend>, path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_on(backend, path, None)
+    }
+
+    /// open_with_backend() with the fsyncs on a shared `thread` if given
+    pub fn open_on(backend: Arc<dyn StorageBackend>, path: impl AsRef<Path>, thread: Option<Arc<SyncThread>>) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
 
-        let file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .append(true)
-            .open(&path)?;
+        let file = backend.open(&path)?;
+        let sync = Arc::new(GroupSync::with_files_on(vec![file.try_clone()?], thread.as_ref()));
 
-        Ok(WriteAheadLog { file, path })
+        Ok(WriteAheadLog { file, path, sync, backend, thread })
     }
 
     /// Append an entry to the WAL
@@ -172,26 +457,30 @@ impl WriteAheadLog {
 
     /// Flush WAL to disk (fsync)
     pub fn flush(&mut self) -> Result<()> {
-        self.file.sync_all()?;
-        Ok(())
+        let ticket = self.sync.register();
+        self.sync.sync_to(ticket)
+    }
+
+    /// Group-commit handle: `register()` after appending, then `sync_to()`
+    /// without holding the storage lock
+    pub fn group_sync(&self) -> Arc<GroupSync> {
+        Arc::clone(&self.sync)
     }
 
     /// Recover transactions from WAL
     /// Returns grouped transactions (only committed ones)
+    ///
+    /// A torn entry at the tail (crash in the middle of an append) is not an
+    /// error: recovery stops at the last valid entry and the garbage is truncated
+    /// so later appends start on a clean boundary.
     pub fn recover(&mut self) -> Result<Vec<Vec<WALEntry>>> {
-        self.file.seek(SeekFrom::Start(0))?;
-
-        let mut entries = Vec::new();
+        let (entries, valid_len) = self.read_all_entries()?;
 
-        // Read all entries
-        loop {
-            match self.read_next_entry() {
-                Ok(entry) => entries.push(entry),
-                Err(MongoLiteError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    break;  // End of file
-                }
-                Err(e) => return Err(e),
-            }
+        let file_len = self.file.size()?;
+        if valid_len < file_len {
+            eprintln!("WARN: WAL has a torn tail entry, truncating {} bytes", file_len - valid_len);
+            self.file.set_len(valid_len)?;
+            self.file.sync_all()?;
         }
 
         // Group entries by transaction ID
@@ -218,42 +507,58 @@ impl WriteAheadLog {
         Ok(committed)
     }
 
-    /// Read next entry from current position
-    fn read_next_entry(&mut self) -> Result<WALEntry> {
-        // Read header: 8 (tx_id) + 1 (type) + 4 (len) = 13 bytes
-        let mut header = [0u8; 13];
-        self.file.read_exact(&mut header)?;
+    /// Read every well-formed entry from the start of the file
+    ///
+    /// Returns the entries and the byte length of the valid prefix.
+    /// The scan ends quietly at a torn tail: a partial entry, a bad entry that is
+    /// the last one in the file, or a zero-filled tail (preallocated blocks).
+    /// A bad entry followed by more data is real corruption -> WALCorruption.
+    fn read_all_entries(&mut self) -> Result<(Vec<WALEntry>, u64)> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        self.file.read_to_end(&mut data)?;
 
-        let tx_id = u64::from_le_bytes(header[0..8].try_into().unwrap());
-        let entry_type = WALEntryType::from_u8(header[8])?;
-        let data_len = u32::from_le_bytes(header[9..13].try_into().unwrap()) as usize;
+        let mut entries = Vec::new();
+        let mut offset = 0usize;
 
-        // Read data
-        let mut data = vec![0u8; data_len];
-        self.file.read_exact(&mut data)?;
+        while offset < data.len() {
+            let remaining = &data[offset..];
 
-        // Read checksum
-        let mut checksum_bytes = [0u8; 4];
-        self.file.read_exact(&mut checksum_bytes)?;
-        let checksum = u32::from_le_bytes(checksum_bytes);
+            // Header: 8 (tx_id) + 1 (type) + 4 (len), plus 4 (checksum)
+            if remaining.len() < 17 {
+                break; // partial header
+            }
 
-        let entry = WALEntry {
-            transaction_id: tx_id,
-            entry_type,
-            data,
-            checksum,
-        };
+            let data_len = u32::from_le_bytes(remaining[9..13].try_into().unwrap()) as usize;
+            let entry_len = 17usize.saturating_add(data_len);
+            if entry_len > remaining.len() {
+                break; // partial payload
+            }
 
-        // Verify checksum
-        if entry.compute_checksum() != checksum {
-            return Err(MongoLiteError::WALCorruption);
+            match WALEntry::deserialize(&remaining[..entry_len]) {
+                Ok(entry) => {
+                    entries.push(entry);
+                    offset += entry_len;
+                }
+                Err(_) => {
+                    let is_last = entry_len == remaining.len();
+                    let zero_tail = remaining.iter().all(|&b| b == 0);
+                    if is_last || zero_tail {
+                        break;
+                    }
+                    return Err(MongoLiteError::WALCorruption);
+                }
+            }
         }
 
-        Ok(entry)
+        Ok((entries, offset as u64))
     }
 
     /// Clear WAL file (after successful recovery)
     pub fn clear(&mut self) -> Result<()> {
+        if self.file.size()? == 0 {
+            return Ok(());
+        }
         self.file.set_len(0)?;
         self.file.seek(SeekFrom::Start(0))?;
         self.file.sync_all()?;  // Ensure truncation is persisted to disk
@@ -262,19 +567,8 @@ impl WriteAheadLog {
 
     /// Checkpoint: remove committed transactions from WAL
     pub fn checkpoint(&mut self, committed_tx_ids: &[TransactionId]) -> Result<()> {
-        // Read all entries
-        self.file.seek(SeekFrom::Start(0))?;
-        let mut all_entries = Vec::new();
-
-        loop {
-            match self.read_next_entry() {
-                Ok(entry) => all_entries.push(entry),
-                Err(MongoLiteError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    break;
-                }
-                Err(e) => return Err(e),
-            }
-        }
+        // Read all entries (a torn tail is dropped by the rewrite below)
+        let (all_entries, _) = self.read_all_entries()?;
 
         // Keep only uncommitted transactions
         let active_entries: Vec<_> = all_entries
@@ -284,27 +578,25 @@ impl WriteAheadLog {
 
         // Rewrite WAL file
         let temp_path = self.path.with_extension("wal.tmp");
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&temp_path)?;
+        let mut temp_file = self.backend.open(&temp_path)?;
+        temp_file.set_len(0)?;
 
         for entry in active_entries {
             temp_file.write_all(&entry.serialize())?;
         }
         temp_file.sync_all()?;
-        drop(temp_file);
 
-        // Atomic rename
-        std::fs::rename(&temp_path, &self.path)?;
+        // Let go of the old WAL before replacing it (Windows refuses to
+        // replace a file with open handles)
+        self.sync = Arc::new(GroupSync::with_files_on(vec![temp_file.try_clone()?], self.thread.as_ref()));
+        self.file = temp_file;
+
+        // Atomic replace
+        self.backend.replace(&temp_path, &self.path)?;
 
         // Reopen file
-        self.file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .append(true)
-            .open(&self.path)?;
+        self.file = self.backend.open(&self.path)?;
+        self.sync = Arc::new(GroupSync::with_files_on(vec![self.file.try_clone()?], self.thread.as_ref()));
 
         Ok(())
     }
@@ -313,6 +605,91 @@ impl WriteAheadLog {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_group_sync_batches_concurrent_writers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = File::create(temp_dir.path().join("data")).unwrap();
+        let sync = Arc::new(GroupSync::new(Box::new(file)));
+
+        // Tickets already covered by an earlier fsync return without syncing
+        let first = sync.register();
+        let second = sync.register();
+        sync.sync_to(second).unwrap();
+        let fsyncs = sync.fsync_count();
+        sync.sync_to(first).unwrap();
+        assert_eq!(sync.fsync_count(), fsyncs);
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let sync = Arc::clone(&sync);
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let ticket = sync.register();
+                        sync.sync_to(ticket).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Never more fsyncs than commits
+        assert!(sync.fsync_count() <= 2 + 8 * 20);
+        assert_eq!(sync.shared.state.lock().synced, 2 + 8 * 20);
+    }
+
+    #[test]
+    fn test_group_sync_relaxed_tickets_synced_in_background() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = File::create(temp_dir.path().join("data")).unwrap();
+        let sync = GroupSync::new(Box::new(file));
+
+        // Nobody waits - the sync thread still gets to every ticket
+        let last = (0..10).map(|_| sync.register()).max().unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while sync.shared.state.lock().synced < last {
+            assert!(std::time::Instant::now() < deadline, "sync thread never caught up");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(sync.fsync_count() >= 1);
+    }
+
+    #[test]
+    fn test_group_syncs_share_a_sync_thread() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let thread = SyncThread::new();
+        let syncs: Vec<Arc<GroupSync>> = (0..3)
+            .map(|n| {
+                let file = File::create(temp_dir.path().join(format!("data{}", n))).unwrap();
+                Arc::new(GroupSync::with_files_on(vec![Box::new(file)], Some(&thread)))
+            })
+            .collect();
+
+        let threads: Vec<_> = syncs.iter()
+            .map(|sync| {
+                let sync = Arc::clone(sync);
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        let ticket = sync.register();
+                        sync.sync_to(ticket).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(syncs.iter().all(|sync| sync.fsync_count() >= 1));
+
+        // Dropping a group waits for its relaxed tickets
+        let relaxed = Arc::clone(&syncs[0].shared);
+        let last = (0..10).map(|_| syncs[0].register()).max().unwrap();
+        drop(syncs);
+        assert!(relaxed.state.lock().synced >= last);
+    }
 
     #[test]
     fn test_wal_entry_type_conversion() {
@@ -434,4 +811,99 @@ mod tests {
             assert_eq!(recovered.len(), 0);
         }
     }
+
+    /// Two committed transactions followed by a third one, serialized
+    fn write_three_transactions(wal_path: &Path) -> (u64, u64) {
+        let mut wal = WriteAheadLog::open(wal_path).unwrap();
+        for tx_id in 1..=2 {
+            wal.append(&WALEntry::new(tx_id, WALEntryType::Begin, vec![])).unwrap();
+            wal.append(&WALEntry::new(tx_id, WALEntryType::Operation, b"op".to_vec())).unwrap();
+            wal.append(&WALEntry::new(tx_id, WALEntryType::Commit, vec![])).unwrap();
+        }
+        let two_tx_len = std::fs::metadata(wal_path).unwrap().len();
+
+        wal.append(&WALEntry::new(3, WALEntryType::Begin, vec![])).unwrap();
+        wal.append(&WALEntry::new(3, WALEntryType::Operation, b"third op".to_vec())).unwrap();
+        wal.append(&WALEntry::new(3, WALEntryType::Commit, vec![])).unwrap();
+        wal.flush().unwrap();
+
+        (two_tx_len, std::fs::metadata(wal_path).unwrap().len())
+    }
+
+    #[test]
+    fn test_wal_recover_torn_write_at_every_byte() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (two_tx_len, full_len) = write_three_transactions(&wal_path);
+        let full = std::fs::read(&wal_path).unwrap();
+
+        for cut in two_tx_len..full_len {
+            std::fs::write(&wal_path, &full[..cut as usize]).unwrap();
+
+            let mut wal = WriteAheadLog::open(&wal_path).unwrap();
+            let recovered = wal.recover()
+                .unwrap_or_else(|e| panic!("recover failed with tail cut at {}: {:?}", cut, e));
+
+            // Third transaction never committed (commit marker is the last entry)
+            assert_eq!(recovered.len(), 2, "cut at {}", cut);
+
+            // Garbage truncated to the last whole entry
+            let len = std::fs::metadata(&wal_path).unwrap().len();
+            assert!(len >= two_tx_len && len <= cut, "cut at {}: len {}", cut, len);
+
+            // Appending after recovery yields a readable log
+            wal.append(&WALEntry::new(9, WALEntryType::Begin, vec![])).unwrap();
+            wal.append(&WALEntry::new(9, WALEntryType::Commit, vec![])).unwrap();
+            wal.flush().unwrap();
+            drop(wal);
+
+            let mut wal = WriteAheadLog::open(&wal_path).unwrap();
+            assert_eq!(wal.recover().unwrap().len(), 3, "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn test_wal_recover_corrupted_last_entry() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (_, full_len) = write_three_transactions(&wal_path);
+
+        // Flip a bit in the final checksum - torn, not fatal
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data[full_len as usize - 1] ^= 0x01;
+        std::fs::write(&wal_path, &data).unwrap();
+
+        let mut wal = WriteAheadLog::open(&wal_path).unwrap();
+        assert_eq!(wal.recover().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_wal_recover_zero_filled_tail() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        let (_, full_len) = write_three_transactions(&wal_path);
+
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data.extend_from_slice(&[0u8; 4096]);
+        std::fs::write(&wal_path, &data).unwrap();
+
+        let mut wal = WriteAheadLog::open(&wal_path).unwrap();
+        assert_eq!(wal.recover().unwrap().len(), 3);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), full_len);
+    }
+
+    #[test]
+    fn test_wal_recover_mid_log_corruption_is_fatal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wal_path = temp_dir.path().join("test.wal");
+        write_three_transactions(&wal_path);
+
+        // Corrupt the checksum of the very first entry (Begin, 17 bytes)
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data[16] ^= 0xFF;
+        std::fs::write(&wal_path, &data).unwrap();
+
+        let mut wal = WriteAheadLog::open(&wal_path).unwrap();
+        assert!(matches!(wal.recover(), Err(MongoLiteError::WALCorruption)));
+    }
 }
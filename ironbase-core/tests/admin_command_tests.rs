@@ -0,0 +1,76 @@
+// Administrative commands (DatabaseCore::command) and dropping a whole database
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_command_dispatches_admin_commands() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.insert_many((0..100).map(|n| doc(json!({"n": n}))).collect()).unwrap();
+    users.delete_many(&json!({"n": {"$lt": 50}})).unwrap();
+
+    assert_eq!(db.command(&json!({"ping": 1})).unwrap(), json!({"ok": 1}));
+    assert_eq!(db.command(&json!({"listCollections": 1})).unwrap()["collections"], json!(["users"]));
+
+    let stats = db.command(&json!({"collStats": "users"})).unwrap();
+    assert_eq!(stats["document_count"], 50);
+    assert_eq!(stats["ok"], 1);
+
+    let compacted = db.command(&json!({"compact": "users"})).unwrap();
+    assert_eq!(compacted["collections"]["users"]["documents_kept"], 50);
+    assert!(db.command(&json!({"compact": 1})).unwrap()["size_after"].as_u64().unwrap() > 0);
+
+    let report = db.command(&json!({"validate": "users", "full": true})).unwrap();
+    assert_eq!((report["valid"].clone(), report["full"].clone()), (json!(true), json!(true)));
+    assert_eq!(report["documents_checked"], 50);
+
+    let status = db.command(&json!({"serverStatus": 1})).unwrap();
+    assert!(status["storage"]["collections"].is_array());
+    assert_eq!(status["mvcc"]["open_snapshots"], 0);
+    assert!(status["metrics"]["locks"].is_object());
+
+    assert_eq!(db.command(&json!({"analyze": "users"})).unwrap()["documents"], 50);
+    assert_eq!(db.command(&json!({"killOp": 1, "op": 12345})).unwrap()["killed"], false);
+    db.command(&json!({"drop": "users"})).unwrap();
+    assert!(db.list_collections().is_empty());
+
+    assert!(matches!(db.command(&json!({"shutdown": 1})), Err(MongoLiteError::InvalidQuery(_))));
+    assert!(matches!(db.command(&json!({"collStats": 1})), Err(MongoLiteError::InvalidQuery(_))));
+    assert!(matches!(db.command(&json!({})), Err(MongoLiteError::InvalidQuery(_))));
+}
+
+#[test]
+fn test_drop_database_removes_its_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("app.mlite");
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    users.insert_one(doc(json!({"email": "a@x"}))).unwrap();
+    let tx_id = db.begin_transaction();
+    db.insert_one_tx("users", doc(json!({"email": "b@x"})), tx_id).unwrap();
+    db.commit_transaction(tx_id).unwrap();
+
+    // Another database's files in the same directory stay
+    let other = DatabaseCore::open(temp_dir.path().join("app2.mlite")).unwrap();
+    other.collection("users").unwrap().create_index("email".to_string(), false).unwrap();
+
+    db.drop_database().unwrap();
+    let left: Vec<String> = std::fs::read_dir(temp_dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert!(left.iter().all(|name| !name.starts_with("app.")), "{:?}", left);
+    assert!(left.iter().any(|name| name.starts_with("app2.")));
+
+    // Nothing the dropped handle does brings the files back
+    users.insert_one(doc(json!({"email": "c@x"}))).unwrap();
+    assert!(db.compact().is_err());
+    drop((users, db));
+    assert!(!path.exists());
+    assert_eq!(DatabaseCore::open(&path).unwrap().list_collections().len(), 0);
+}
@@ -0,0 +1,171 @@
+// $out / $merge aggregation stage tests
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+/// insert_one always assigns its own _id, so documents with chosen ids are staged through $out
+fn seed_with_ids(db: &DatabaseCore, collection: &str, docs: Vec<serde_json::Value>) {
+    let staging = db.collection("staging").unwrap();
+    for d in docs {
+        staging.insert_one(doc(d)).unwrap();
+    }
+    staging.aggregate(&json!([
+        {"$addFields": {"_id": "$key"}},
+        {"$project": {"key": 0}},
+        {"$out": collection}
+    ])).unwrap();
+    db.drop_collection("staging").unwrap();
+}
+
+fn seed_sales(db: &DatabaseCore) {
+    let sales = db.collection("sales").unwrap();
+    sales.insert_one(doc(json!({"region": "eu", "amount": 10}))).unwrap();
+    sales.insert_one(doc(json!({"region": "eu", "amount": 5}))).unwrap();
+    sales.insert_one(doc(json!({"region": "us", "amount": 7}))).unwrap();
+}
+
+#[test]
+fn test_out_replaces_target_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed_sales(&db);
+
+    seed_with_ids(&db, "totals", vec![json!({"key": "stale", "total": 0})]);
+    assert_eq!(db.collection("totals").unwrap().count_documents(&json!({"_id": "stale"})).unwrap(), 1);
+
+    let returned = db.collection("sales").unwrap().aggregate(&json!([
+        {"$group": {"_id": "$region", "total": {"$sum": "$amount"}}},
+        {"$out": "totals"}
+    ])).unwrap();
+    assert!(returned.is_empty());
+
+    let totals = db.collection("totals").unwrap();
+    assert_eq!(totals.count_documents(&json!({})).unwrap(), 2);
+    assert_eq!(totals.find_one(&json!({"_id": "eu"})).unwrap().unwrap()["total"], 15);
+    assert_eq!(totals.find_one(&json!({"_id": "us"})).unwrap().unwrap()["total"], 7);
+    assert!(totals.find_one(&json!({"_id": "stale"})).unwrap().is_none());
+
+    // Old contents stay gone after compaction and reopen
+    db.compact().unwrap();
+    drop(totals);
+    drop(db);
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    assert_eq!(db.collection("totals").unwrap().count_documents(&json!({})).unwrap(), 2);
+}
+
+#[test]
+fn test_merge_upserts_by_id() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed_sales(&db);
+
+    seed_with_ids(&db, "totals", vec![
+        json!({"key": "eu", "total": 0, "owner": "ana"}),
+        json!({"key": "apac", "total": 3}),
+    ]);
+
+    db.collection("sales").unwrap().aggregate(&json!([
+        {"$group": {"_id": "$region", "total": {"$sum": "$amount"}}},
+        {"$merge": {"into": "totals"}}
+    ])).unwrap();
+
+    let totals = db.collection("totals").unwrap();
+    assert_eq!(totals.count_documents(&json!({})).unwrap(), 3);
+
+    // Default whenMatched is "merge": untouched fields survive
+    let eu = totals.find_one(&json!({"_id": "eu"})).unwrap().unwrap();
+    assert_eq!(eu["total"], 15);
+    assert_eq!(eu["owner"], "ana");
+    assert_eq!(totals.find_one(&json!({"_id": "us"})).unwrap().unwrap()["total"], 7);
+    assert_eq!(totals.find_one(&json!({"_id": "apac"})).unwrap().unwrap()["total"], 3);
+
+    // whenMatched: replace drops the extra field
+    db.collection("sales").unwrap().aggregate(&json!([
+        {"$group": {"_id": "$region", "total": {"$sum": 1}}},
+        {"$merge": {"into": "totals", "whenMatched": "replace", "whenNotMatched": "discard"}}
+    ])).unwrap();
+    let eu = db.collection("totals").unwrap().find_one(&json!({"_id": "eu"})).unwrap().unwrap();
+    assert_eq!(eu["total"], 2);
+    assert!(eu.get("owner").is_none());
+}
+
+#[test]
+fn test_failed_merge_leaves_target_untouched() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed_sales(&db);
+
+    seed_with_ids(&db, "totals", vec![json!({"key": "us", "total": 1})]);
+
+    // "eu" would be inserted first, then "us" fails - nothing may be written
+    let err = db.collection("sales").unwrap().aggregate(&json!([
+        {"$group": {"_id": "$region", "total": {"$sum": "$amount"}}},
+        {"$sort": {"_id": 1}},
+        {"$merge": {"into": "totals", "whenMatched": "fail"}}
+    ]));
+    assert!(err.is_err());
+
+    let totals = db.collection("totals").unwrap();
+    assert_eq!(totals.count_documents(&json!({})).unwrap(), 1);
+    assert_eq!(totals.find_one(&json!({"_id": "us"})).unwrap().unwrap()["total"], 1);
+
+    // A failing stage never creates the target either
+    let err = db.collection("sales").unwrap().aggregate(&json!([
+        {"$project": {"ratio": {"$divide": ["$amount", 0]}}},
+        {"$out": "ratios"}
+    ]));
+    assert!(err.is_err());
+    assert!(!db.list_collections().contains(&"ratios".to_string()));
+}
+
+#[test]
+fn test_out_rejects_unusable_ids() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed_sales(&db);
+
+    let result = db.collection("sales").unwrap().aggregate(&json!([
+        {"$group": {"_id": null, "total": {"$sum": "$amount"}}},
+        {"$out": "grand_total"}
+    ]));
+    assert!(result.is_err());
+    assert!(!db.list_collections().contains(&"grand_total".to_string()));
+}
+
+#[test]
+fn test_indexed_finds_see_the_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed_sales(&db);
+
+    // Handles of the target open before the aggregation
+    let totals = db.collection("totals").unwrap();
+    totals.insert_one(doc(json!({"total": 99}))).unwrap();
+    totals.create_index("total".to_string(), false).unwrap();
+    assert_eq!(totals.find(&json!({"total": 99})).unwrap().len(), 1);
+
+    let sales = db.collection("sales").unwrap();
+    sales.aggregate(&json!([
+        {"$group": {"_id": "$region", "total": {"$sum": "$amount"}}},
+        {"$out": "totals"}
+    ])).unwrap();
+
+    let found = totals.find(&json!({"total": 15})).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["_id"], "eu");
+    assert!(totals.find(&json!({"total": 99})).unwrap().is_empty());
+    assert_eq!(totals.explain(&json!({"total": 15})).unwrap()["queryPlan"], "IndexScan");
+
+    // $merge into the aggregated collection itself replaces indexed values
+    sales.create_index("amount".to_string(), false).unwrap();
+    sales.aggregate(&json!([
+        {"$match": {"region": "us"}},
+        {"$set": {"amount": 70}},
+        {"$merge": "sales"}
+    ])).unwrap();
+    assert_eq!(sales.find(&json!({"amount": 70})).unwrap().len(), 1);
+    assert!(sales.find(&json!({"amount": 7})).unwrap().is_empty());
+}
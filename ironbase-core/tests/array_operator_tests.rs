@@ -1,22 +1,17 @@
 // array_operator_tests.rs
 // Comprehensive tests for array update operators: $push, $pull, $addToSet, $pop
 
-use ironbase_core::{DatabaseCore, Document};
+use ironbase_core::{DatabaseCore, Document, UpdateResult};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::fs;
-
-/// Helper to create a test database
-fn setup_test_db(name: &str) -> DatabaseCore {
-    let path = format!("test_{}.mlite", name);
-    let _ = fs::remove_file(&path); // Clean up if exists
-    DatabaseCore::open(&path).expect("Failed to open database")
-}
-
-/// Helper to cleanup test database
-fn cleanup_test_db(name: &str) {
-    let path = format!("test_{}.mlite", name);
-    let _ = fs::remove_file(&path);
+use tempfile::TempDir;
+
+/// Helper to create a test database in its own temporary directory
+fn setup_test_db(name: &str) -> (TempDir, DatabaseCore) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(format!("test_{}.mlite", name));
+    let db = DatabaseCore::open(&path).expect("Failed to open database");
+    (dir, db)
 }
 
 /// Helper to convert JSON to HashMap for insert_one
@@ -32,14 +27,14 @@ fn json_to_hashmap(json: Value) -> HashMap<String, Value> {
 
 #[test]
 fn test_push_simple() {
-    let db = setup_test_db("push_simple");
+    let (_dir, db) = setup_test_db("push_simple");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["rust"]}))).unwrap();
 
     // Push single element
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"tags": "mongodb"}})
     ).unwrap();
@@ -50,20 +45,18 @@ fn test_push_simple() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["rust", "mongodb"]));
-
-    cleanup_test_db("push_simple");
 }
 
 #[test]
 fn test_push_to_nonexistent_field() {
-    let db = setup_test_db("push_nonexistent");
+    let (_dir, db) = setup_test_db("push_nonexistent");
     let coll = db.collection("test").unwrap();
 
     // Insert document without array field
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "name": "test"}))).unwrap();
 
     // Push to nonexistent field (should create array)
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"tags": "new"}})
     ).unwrap();
@@ -74,20 +67,18 @@ fn test_push_to_nonexistent_field() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["new"]));
-
-    cleanup_test_db("push_nonexistent");
 }
 
 #[test]
 fn test_push_each() {
-    let db = setup_test_db("push_each");
+    let (_dir, db) = setup_test_db("push_each");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "scores": [10, 20]}))).unwrap();
 
     // Push multiple elements with $each
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"scores": {"$each": [30, 40, 50]}}})
     ).unwrap();
@@ -98,20 +89,18 @@ fn test_push_each() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["scores"], json!([10, 20, 30, 40, 50]));
-
-    cleanup_test_db("push_each");
 }
 
 #[test]
 fn test_push_position() {
-    let db = setup_test_db("push_position");
+    let (_dir, db) = setup_test_db("push_position");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": ["a", "b", "c"]}))).unwrap();
 
     // Push at position 1
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"items": {"$each": ["x", "y"], "$position": 1}}})
     ).unwrap();
@@ -122,20 +111,18 @@ fn test_push_position() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!(["a", "x", "y", "b", "c"]));
-
-    cleanup_test_db("push_position");
 }
 
 #[test]
 fn test_push_slice_positive() {
-    let db = setup_test_db("push_slice_pos");
+    let (_dir, db) = setup_test_db("push_slice_pos");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": [1, 2, 3]}))).unwrap();
 
     // Push and keep only first 3 elements
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"items": {"$each": [4, 5], "$slice": 3}}})
     ).unwrap();
@@ -146,20 +133,18 @@ fn test_push_slice_positive() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!([1, 2, 3])); // Only first 3 kept
-
-    cleanup_test_db("push_slice_pos");
 }
 
 #[test]
 fn test_push_slice_negative() {
-    let db = setup_test_db("push_slice_neg");
+    let (_dir, db) = setup_test_db("push_slice_neg");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": [1, 2, 3]}))).unwrap();
 
     // Push and keep only last 3 elements
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$push": {"items": {"$each": [4, 5], "$slice": -3}}})
     ).unwrap();
@@ -170,13 +155,11 @@ fn test_push_slice_negative() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!([3, 4, 5])); // Only last 3 kept
-
-    cleanup_test_db("push_slice_neg");
 }
 
 #[test]
 fn test_push_to_non_array_field_should_error() {
-    let db = setup_test_db("push_error");
+    let (_dir, db) = setup_test_db("push_error");
     let coll = db.collection("test").unwrap();
 
     // Insert document with non-array field
@@ -189,22 +172,20 @@ fn test_push_to_non_array_field_should_error() {
     );
 
     assert!(result.is_err());
-
-    cleanup_test_db("push_error");
 }
 
 // ========== $pull TESTS ==========
 
 #[test]
 fn test_pull_simple_equality() {
-    let db = setup_test_db("pull_simple");
+    let (_dir, db) = setup_test_db("pull_simple");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["rust", "python", "rust", "java"]}))).unwrap();
 
     // Pull all "rust" elements
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pull": {"tags": "rust"}})
     ).unwrap();
@@ -215,20 +196,18 @@ fn test_pull_simple_equality() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["python", "java"]));
-
-    cleanup_test_db("pull_simple");
 }
 
 #[test]
 fn test_pull_with_condition() {
-    let db = setup_test_db("pull_condition");
+    let (_dir, db) = setup_test_db("pull_condition");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array of numbers
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "scores": [5, 10, 15, 20, 25]}))).unwrap();
 
     // Pull elements less than 15
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pull": {"scores": {"$lt": 15}}})
     ).unwrap();
@@ -239,20 +218,18 @@ fn test_pull_with_condition() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["scores"], json!([15, 20, 25]));
-
-    cleanup_test_db("pull_condition");
 }
 
 #[test]
 fn test_pull_with_in_operator() {
-    let db = setup_test_db("pull_in");
+    let (_dir, db) = setup_test_db("pull_in");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["a", "b", "c", "d", "e"]}))).unwrap();
 
     // Pull elements in ["b", "d"]
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pull": {"tags": {"$in": ["b", "d"]}}})
     ).unwrap();
@@ -263,33 +240,29 @@ fn test_pull_with_in_operator() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["a", "c", "e"]));
-
-    cleanup_test_db("pull_in");
 }
 
 #[test]
 fn test_pull_from_nonexistent_field() {
-    let db = setup_test_db("pull_nonexistent");
+    let (_dir, db) = setup_test_db("pull_nonexistent");
     let coll = db.collection("test").unwrap();
 
     // Insert document without array field
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "name": "test"}))).unwrap();
 
     // Pull from nonexistent field (should be no-op)
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pull": {"tags": "value"}})
     ).unwrap();
 
     assert_eq!(matched, 1);
     assert_eq!(modified, 0); // No modification since field doesn't exist
-
-    cleanup_test_db("pull_nonexistent");
 }
 
 #[test]
 fn test_pull_from_non_array_field_should_error() {
-    let db = setup_test_db("pull_error");
+    let (_dir, db) = setup_test_db("pull_error");
     let coll = db.collection("test").unwrap();
 
     // Insert document with non-array field
@@ -302,22 +275,20 @@ fn test_pull_from_non_array_field_should_error() {
     );
 
     assert!(result.is_err());
-
-    cleanup_test_db("pull_error");
 }
 
 // ========== $addToSet TESTS ==========
 
 #[test]
 fn test_addtoset_simple() {
-    let db = setup_test_db("addtoset_simple");
+    let (_dir, db) = setup_test_db("addtoset_simple");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["rust", "python"]}))).unwrap();
 
     // Add unique element
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$addToSet": {"tags": "java"}})
     ).unwrap();
@@ -328,20 +299,18 @@ fn test_addtoset_simple() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["rust", "python", "java"]));
-
-    cleanup_test_db("addtoset_simple");
 }
 
 #[test]
 fn test_addtoset_duplicate() {
-    let db = setup_test_db("addtoset_dup");
+    let (_dir, db) = setup_test_db("addtoset_dup");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["rust", "python"]}))).unwrap();
 
     // Try to add duplicate element
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$addToSet": {"tags": "rust"}})
     ).unwrap();
@@ -352,20 +321,18 @@ fn test_addtoset_duplicate() {
     // Verify (array unchanged)
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["rust", "python"]));
-
-    cleanup_test_db("addtoset_dup");
 }
 
 #[test]
 fn test_addtoset_each() {
-    let db = setup_test_db("addtoset_each");
+    let (_dir, db) = setup_test_db("addtoset_each");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["rust", "python"]}))).unwrap();
 
     // Add multiple unique elements with $each
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$addToSet": {"tags": {"$each": ["java", "rust", "go"]}}})
     ).unwrap();
@@ -381,20 +348,18 @@ fn test_addtoset_each() {
     assert!(tags.contains(&json!("python")));
     assert!(tags.contains(&json!("java")));
     assert!(tags.contains(&json!("go")));
-
-    cleanup_test_db("addtoset_each");
 }
 
 #[test]
 fn test_addtoset_to_nonexistent_field() {
-    let db = setup_test_db("addtoset_nonexistent");
+    let (_dir, db) = setup_test_db("addtoset_nonexistent");
     let coll = db.collection("test").unwrap();
 
     // Insert document without array field
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "name": "test"}))).unwrap();
 
     // AddToSet to nonexistent field (should create array)
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$addToSet": {"tags": "new"}})
     ).unwrap();
@@ -405,13 +370,11 @@ fn test_addtoset_to_nonexistent_field() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["new"]));
-
-    cleanup_test_db("addtoset_nonexistent");
 }
 
 #[test]
 fn test_addtoset_to_non_array_field_should_error() {
-    let db = setup_test_db("addtoset_error");
+    let (_dir, db) = setup_test_db("addtoset_error");
     let coll = db.collection("test").unwrap();
 
     // Insert document with non-array field
@@ -424,22 +387,20 @@ fn test_addtoset_to_non_array_field_should_error() {
     );
 
     assert!(result.is_err());
-
-    cleanup_test_db("addtoset_error");
 }
 
 // ========== $pop TESTS ==========
 
 #[test]
 fn test_pop_first() {
-    let db = setup_test_db("pop_first");
+    let (_dir, db) = setup_test_db("pop_first");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": [1, 2, 3, 4, 5]}))).unwrap();
 
     // Pop first element
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pop": {"items": -1}})
     ).unwrap();
@@ -450,20 +411,18 @@ fn test_pop_first() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!([2, 3, 4, 5]));
-
-    cleanup_test_db("pop_first");
 }
 
 #[test]
 fn test_pop_last() {
-    let db = setup_test_db("pop_last");
+    let (_dir, db) = setup_test_db("pop_last");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": [1, 2, 3, 4, 5]}))).unwrap();
 
     // Pop last element
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pop": {"items": 1}})
     ).unwrap();
@@ -474,20 +433,18 @@ fn test_pop_last() {
     // Verify
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!([1, 2, 3, 4]));
-
-    cleanup_test_db("pop_last");
 }
 
 #[test]
 fn test_pop_empty_array() {
-    let db = setup_test_db("pop_empty");
+    let (_dir, db) = setup_test_db("pop_empty");
     let coll = db.collection("test").unwrap();
 
     // Insert document with empty array
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "items": []}))).unwrap();
 
     // Pop from empty array (should be no-op)
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({"$pop": {"items": 1}})
     ).unwrap();
@@ -498,13 +455,11 @@ fn test_pop_empty_array() {
     // Verify (array still empty)
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["items"], json!([]));
-
-    cleanup_test_db("pop_empty");
 }
 
 #[test]
 fn test_pop_invalid_direction() {
-    let db = setup_test_db("pop_invalid");
+    let (_dir, db) = setup_test_db("pop_invalid");
     let coll = db.collection("test").unwrap();
 
     // Insert document with array
@@ -517,13 +472,11 @@ fn test_pop_invalid_direction() {
     );
 
     assert!(result.is_err());
-
-    cleanup_test_db("pop_invalid");
 }
 
 #[test]
 fn test_pop_from_non_array_field_should_error() {
-    let db = setup_test_db("pop_error");
+    let (_dir, db) = setup_test_db("pop_error");
     let coll = db.collection("test").unwrap();
 
     // Insert document with non-array field
@@ -536,22 +489,20 @@ fn test_pop_from_non_array_field_should_error() {
     );
 
     assert!(result.is_err());
-
-    cleanup_test_db("pop_error");
 }
 
 // ========== COMBINED OPERATIONS TESTS ==========
 
 #[test]
 fn test_combined_array_operations() {
-    let db = setup_test_db("combined");
+    let (_dir, db) = setup_test_db("combined");
     let coll = db.collection("test").unwrap();
 
     // Insert document
     coll.insert_one(json_to_hashmap(json!({"_id": 1, "tags": ["a", "b"], "scores": [10, 20]}))).unwrap();
 
     // Apply multiple array operations at once
-    let (matched, modified) = coll.update_one(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_one(
         &json!({"_id": 1}),
         &json!({
             "$push": {"tags": "c"},
@@ -566,13 +517,11 @@ fn test_combined_array_operations() {
     let docs = coll.find(&json!({"_id": 1})).unwrap();
     assert_eq!(docs[0]["tags"], json!(["a", "b", "c"]));
     assert_eq!(docs[0]["scores"], json!([10, 20, 30]));
-
-    cleanup_test_db("combined");
 }
 
 #[test]
 fn test_update_many_with_array_operators() {
-    let db = setup_test_db("update_many_array");
+    let (_dir, db) = setup_test_db("update_many_array");
     let coll = db.collection("test").unwrap();
 
     // Insert multiple documents
@@ -581,7 +530,7 @@ fn test_update_many_with_array_operators() {
     coll.insert_one(json_to_hashmap(json!({"_id": 3, "category": "B", "tags": ["old"]}))).unwrap();
 
     // Update all category A documents
-    let (matched, modified) = coll.update_many(
+    let UpdateResult { matched_count: matched, modified_count: modified, .. } = coll.update_many(
         &json!({"category": "A"}),
         &json!({"$push": {"tags": "new"}})
     ).unwrap();
@@ -595,6 +544,4 @@ fn test_update_many_with_array_operators() {
     for doc in docs {
         assert_eq!(doc["tags"], json!(["old", "new"]));
     }
-
-    cleanup_test_db("update_many_array");
 }
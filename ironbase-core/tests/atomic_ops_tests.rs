@@ -0,0 +1,97 @@
+// find_one_and_replace / increment: find and write under one storage lock
+use ironbase_core::{DatabaseCore, MongoLiteError, ReturnDocument};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn insert(db: &DatabaseCore, collection: &str, doc: serde_json::Value) {
+    let fields: HashMap<String, serde_json::Value> = serde_json::from_value(doc).unwrap();
+    db.collection(collection).unwrap().insert_one(fields).unwrap();
+}
+
+#[test]
+fn test_increment_counters() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "counters", json!({"name": "hits", "value": 10}));
+    insert(&db, "counters", json!({"name": "ratio", "value": 0.5, "label": "x"}));
+    let counters = db.collection("counters").unwrap();
+
+    assert_eq!(counters.increment(&json!({"name": "hits"}), "value", &json!(5)).unwrap(), Some(json!(15)));
+    assert_eq!(counters.increment(&json!({"name": "ratio"}), "value", &json!(1)).unwrap(), Some(json!(1.5)));
+
+    // A missing field starts from zero; no match changes nothing
+    assert_eq!(counters.increment(&json!({"name": "hits"}), "misses", &json!(-2)).unwrap(), Some(json!(-2)));
+    assert_eq!(counters.increment(&json!({"name": "nope"}), "value", &json!(1)).unwrap(), None);
+
+    let hits = counters.find_one(&json!({"name": "hits"})).unwrap().unwrap();
+    assert_eq!((hits["value"].clone(), hits["misses"].clone()), (json!(15), json!(-2)));
+
+    assert!(matches!(
+        counters.increment(&json!({"name": "ratio"}), "label", &json!(1)),
+        Err(MongoLiteError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        counters.increment(&json!({"name": "hits"}), "value", &json!("1")),
+        Err(MongoLiteError::InvalidQuery(_))
+    ));
+}
+
+#[test]
+fn test_concurrent_increments_do_not_lose_updates() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap());
+    insert(&db, "counters", json!({"name": "seq", "value": 0}));
+
+    let threads: Vec<_> = (0..4).map(|_| {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            let counters = db.collection("counters").unwrap();
+            for _ in 0..25 {
+                counters.increment(&json!({"name": "seq"}), "value", &json!(1)).unwrap();
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let seq = db.collection("counters").unwrap().find_one(&json!({"name": "seq"})).unwrap().unwrap();
+    assert_eq!(seq["value"], 100);
+}
+
+#[test]
+fn test_find_one_and_replace() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "users", json!({"name": "alice", "age": 30, "city": "NYC"}));
+    let users = db.collection("users").unwrap();
+
+    let before = users.find_one_and_replace(&json!({"name": "alice"}), &json!({"name": "alice", "age": 31}), ReturnDocument::Before)
+        .unwrap()
+        .unwrap();
+    assert_eq!(before["age"], 30);
+    let id = before["_id"].clone();
+
+    let after = users.find_one_and_replace(&json!({"age": 31}), &json!({"name": "alicia"}), ReturnDocument::After)
+        .unwrap()
+        .unwrap();
+    assert_eq!((after["_id"].clone(), after["name"].clone()), (id.clone(), json!("alicia")));
+
+    // The replacement is the whole document: old fields are gone
+    let stored = users.find_one(&json!({"_id": id})).unwrap().unwrap();
+    assert_eq!(stored["name"], "alicia");
+    assert!(stored.get("age").is_none() && stored.get("city").is_none());
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 1);
+
+    assert_eq!(users.find_one_and_replace(&json!({"name": "bob"}), &json!({"name": "x"}), ReturnDocument::After).unwrap(), None);
+    assert!(matches!(
+        users.find_one_and_replace(&json!({}), &json!({"_id": 999, "name": "x"}), ReturnDocument::After),
+        Err(MongoLiteError::InvalidQuery(_))
+    ));
+    assert!(matches!(
+        users.find_one_and_replace(&json!({}), &json!({"$set": {"name": "x"}}), ReturnDocument::After),
+        Err(MongoLiteError::InvalidQuery(_))
+    ));
+}
@@ -0,0 +1,49 @@
+// Users, roles and authentication for server/CLI front ends
+use ironbase_core::{Action, DatabaseCore, Grant, MongoLiteError, Role};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_users_persist_and_authenticate() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        assert!(!db.auth_enabled());
+        db.create_user("admin", "root-pw", vec![Grant::new(Role::DbAdmin, "*")]).unwrap();
+        db.create_user("app", "app-pw", vec![Grant::new(Role::ReadWrite, "orders")]).unwrap();
+        assert!(db.create_user("app", "other", Vec::new()).is_err());
+        assert!(db.create_user("", "pw", Vec::new()).is_err());
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    assert!(db.auth_enabled());
+    // The users collection is hidden and holds no plaintext passwords
+    assert!(db.list_collections().is_empty());
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!raw.windows(6).any(|window| window == b"app-pw"));
+
+    let app = db.authenticate("app", "app-pw").unwrap();
+    assert!(app.is_allowed("orders", Action::Write));
+    assert!(matches!(app.authorize("customers", Action::Read), Err(MongoLiteError::Unauthorized(_))));
+    assert!(matches!(db.authenticate("app", "wrong"), Err(MongoLiteError::AuthenticationFailed)));
+    assert!(matches!(db.authenticate("nobody", "app-pw"), Err(MongoLiteError::AuthenticationFailed)));
+
+    db.set_user_grants("app", vec![Grant::new(Role::Read, "*")]).unwrap();
+    db.change_password("app", "new-pw").unwrap();
+    assert!(db.authenticate("app", "app-pw").is_err());
+    let app = db.authenticate("app", "new-pw").unwrap();
+    assert!(app.is_allowed("customers", Action::Read) && !app.is_allowed("orders", Action::Write));
+    assert!(db.set_user_grants("nobody", Vec::new()).is_err());
+
+    let names: Vec<String> = db.users().unwrap().into_iter().map(|user| user.name).collect();
+    assert_eq!(names, vec!["admin", "app"]);
+    assert!(db.drop_user("app").unwrap());
+    assert!(!db.drop_user("app").unwrap());
+
+    // $out cannot overwrite the users
+    let orders = db.collection("orders").unwrap();
+    assert!(orders.aggregate(&json!([{"$out": "_users"}])).is_err());
+    assert!(db.authenticate("admin", "root-pw").unwrap().is_allowed("_users", Action::Admin));
+}
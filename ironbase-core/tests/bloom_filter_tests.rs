@@ -0,0 +1,68 @@
+// Per-segment Bloom filters: scans for equality on unindexed fields skip file regions
+mod common;
+
+use common::doc;
+use ironbase_core::storage::BLOOM_SEGMENT_SIZE;
+use ironbase_core::{DatabaseCore, Query};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[test]
+fn test_bloom_filters_skip_segments_without_matches() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let padding = "x".repeat(2000);
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let events = db.collection("events").unwrap();
+        // ~4 MiB: each region lands in its own stretch of the file
+        let documents: Vec<_> = (0..2000)
+            .map(|i| doc(json!({"n": i, "region": format!("r{}", i / 500), "padding": padding})))
+            .collect();
+        events.insert_many(documents).unwrap();
+        assert!(events.add_bloom_filter("_id").is_err());
+        assert!(events.add_bloom_filter("a.b").is_err());
+        events.add_bloom_filter("region").unwrap();
+        events.add_bloom_filter("n").unwrap();
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let events = db.collection("events").unwrap();
+    assert_eq!(events.bloom_filters(), vec!["region".to_string(), "n".to_string()]);
+    let segments = |filter: Value| {
+        events.storage.write().bloom_skipped_segments("events", &Query::from_json(&filter).unwrap()).unwrap().len()
+    };
+    let file_segments = (std::fs::metadata(&path).unwrap().len() / BLOOM_SEGMENT_SIZE) as usize;
+    assert!(file_segments >= 3);
+
+    assert!(segments(json!({"region": "r0"})) >= 2);
+    assert!(segments(json!({"$and": [{"n": {"$gt": 5}}, {"n": 1999}]})) >= 2);
+    assert!(segments(json!({"region": {"$in": ["r0", "r3"]}})) >= 1);
+    assert!(segments(json!({"region": "nowhere"})) >= file_segments);
+    // Ranges, $or and fields without filters scan everything
+    assert_eq!(segments(json!({"n": {"$gte": 1999}})), 0);
+    assert_eq!(segments(json!({"$or": [{"region": "r0"}]})), 0);
+    assert_eq!(segments(json!({"padding": "x"})), 0);
+
+    // Skipping changes nothing about the results
+    assert_eq!(events.count_documents(&json!({"region": "r1"})).unwrap(), 500);
+    assert_eq!(events.find(&json!({"region": "r2", "n": 1200})).unwrap().len(), 1);
+    assert_eq!(events.find_one(&json!({"n": 1.0})).unwrap().unwrap()["region"], "r0");
+
+    // Writes after the filters were built are found
+    events.insert_one(doc(json!({"n": 5000, "region": "r0"}))).unwrap();
+    events.update_one(&json!({"n": 0}), &json!({"$set": {"region": "moved"}})).unwrap();
+    assert_eq!(events.count_documents(&json!({"region": "r0"})).unwrap(), 500);
+    assert_eq!(events.count_documents(&json!({"region": "moved"})).unwrap(), 1);
+
+    // Compaction moves records - the filters are rebuilt
+    db.compact().unwrap();
+    let events = db.collection("events").unwrap();
+    assert_eq!(events.count_documents(&json!({"region": "r3"})).unwrap(), 500);
+    assert_eq!(events.find(&json!({"n": {"$in": [1, 1999, 5000]}})).unwrap().len(), 3);
+
+    assert!(events.drop_bloom_filter("region").unwrap());
+    assert!(!events.drop_bloom_filter("region").unwrap());
+    assert_eq!(events.storage.write().bloom_skipped_segments("events", &Query::from_json(&json!({"region": "r0"})).unwrap()).unwrap().len(), 0);
+}
@@ -0,0 +1,70 @@
+// bulk_upsert: batch insert-or-replace by key fields
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_bulk_upsert_by_key() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let products = db.collection("products").unwrap();
+        products.insert_one(doc(json!({"shop": "a", "sku": "x1", "price": 10, "old": true}))).unwrap();
+
+        let result = products.bulk_upsert(vec![
+            doc(json!({"shop": "a", "sku": "x1", "price": 12})),
+            doc(json!({"shop": "b", "sku": "x1", "price": 20})),
+            doc(json!({"shop": "a", "sku": "y2", "price": 5})),
+            doc(json!({"shop": "a", "sku": "y2", "price": 6})),
+        ], &["shop", "sku"]).unwrap();
+        assert_eq!(result.matched_count, 1);
+        assert_eq!(result.upserted_count, 2);
+        assert_eq!(result.upserted_ids.len(), 2);
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let products = db.collection("products").unwrap();
+    assert_eq!(products.count_documents(&json!({})).unwrap(), 3);
+    // Replaced, not merged; the _id is kept
+    let x1 = products.find_one(&json!({"shop": "a", "sku": "x1"})).unwrap().unwrap();
+    assert_eq!(x1["price"], json!(12));
+    assert!(x1.get("old").is_none());
+    assert_eq!(x1["_id"], json!(1));
+    assert_eq!(products.find_one(&json!({"sku": "y2"})).unwrap().unwrap()["price"], json!(6));
+
+    // New _ids continue after the upserted ones
+    let id = products.insert_one(doc(json!({"shop": "c"}))).unwrap();
+    assert_eq!(serde_json::to_value(id).unwrap(), json!(4));
+    assert!(products.bulk_upsert(vec![doc(json!({"shop": "a"}))], &[]).is_err());
+}
+
+#[test]
+fn test_bulk_upsert_is_all_or_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    users.insert_one(doc(json!({"name": "ann", "email": "ann@x"}))).unwrap();
+
+    // Bob takes ann's email: nothing is written
+    let result = users.bulk_upsert(vec![
+        doc(json!({"name": "cy", "email": "cy@x"})),
+        doc(json!({"name": "bob", "email": "ann@x"})),
+    ], &["name"]);
+    assert!(matches!(result, Err(MongoLiteError::IndexError(_))), "{:?}", result);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 1);
+
+    // The index was restored; within a batch, ann can hand her email on
+    users.bulk_upsert(vec![
+        doc(json!({"name": "cy", "email": "cy@x"})),
+        doc(json!({"name": "ann", "email": "ann@y"})),
+        doc(json!({"name": "bob", "email": "ann@x"})),
+    ], &["name"]).unwrap();
+    assert_eq!(users.find(&json!({"email": "ann@x"})).unwrap()[0]["name"], json!("bob"));
+    assert!(users.insert_one(doc(json!({"email": "cy@x"}))).is_err());
+}
@@ -0,0 +1,78 @@
+// Document catalog stored as shard records in the data file
+use ironbase_core::{DatabaseCore, DocumentId, StorageEngine};
+use ironbase_core::storage::CATALOG_SHARD_CAPACITY;
+use serde_json::json;
+use tempfile::TempDir;
+
+fn write_doc(storage: &mut StorageEngine, id: i64, value: i64) {
+    let doc = json!({"_id": id, "_collection": "items", "value": value});
+    storage.write_document("items", &DocumentId::Int(id), &serde_json::to_vec(&doc).unwrap()).unwrap();
+}
+
+#[test]
+fn test_large_catalog_outgrows_metadata_region() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let count = CATALOG_SHARD_CAPACITY as i64 * 5;
+
+    // Far more entries than an inline catalog could fit in the metadata region
+    {
+        let mut storage = StorageEngine::open(&db_path).unwrap();
+        storage.create_collection("items").unwrap();
+        for id in 0..count {
+            write_doc(&mut storage, id, id);
+        }
+        storage.flush().unwrap();
+        assert_eq!(storage.get_collection_meta("items").unwrap().document_catalog.shard_count(), 8);
+    }
+
+    let mut storage = StorageEngine::open(&db_path).unwrap();
+    let catalog = &storage.get_collection_meta("items").unwrap().document_catalog;
+    assert_eq!(catalog.len(), count as usize);
+    let before = catalog.shard_records().to_vec();
+
+    // One changed document rewrites one shard
+    write_doc(&mut storage, 42, -1);
+    storage.flush().unwrap();
+    let after = storage.get_collection_meta("items").unwrap().document_catalog.shard_records().to_vec();
+    assert_eq!(before.iter().zip(&after).filter(|(a, b)| a != b).count(), 1);
+    drop(storage);
+
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let items = db.collection("items").unwrap();
+    assert_eq!(items.find_one(&json!({"_id": 42})).unwrap().unwrap()["value"], -1);
+    assert_eq!(items.count_documents(&json!({})).unwrap(), count as u64);
+}
+
+#[test]
+fn test_catalog_shards_survive_compaction() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    {
+        let mut storage = StorageEngine::open(&db_path).unwrap();
+        storage.create_collection("items").unwrap();
+        for id in 0..CATALOG_SHARD_CAPACITY as i64 * 3 {
+            write_doc(&mut storage, id, id);
+        }
+        storage.flush().unwrap();
+        assert_eq!(storage.get_collection_meta("items").unwrap().document_catalog.shard_count(), 4);
+
+        // Shrunk to a quarter: the shards are merged on the next flush
+        for id in 0..CATALOG_SHARD_CAPACITY as i64 * 3 - 10 {
+            storage.write_tombstone("items", &DocumentId::Int(id)).unwrap();
+        }
+        storage.flush().unwrap();
+        assert_eq!(storage.get_collection_meta("items").unwrap().document_catalog.shard_count(), 1);
+
+        storage.compact().unwrap();
+        let catalog = &storage.get_collection_meta("items").unwrap().document_catalog;
+        assert_eq!(catalog.len(), 10);
+        assert!(catalog.shard_records()[0].0 > 0);
+    }
+
+    let last = CATALOG_SHARD_CAPACITY as i64 * 3 - 1;
+    let mut storage = StorageEngine::open(&db_path).unwrap();
+    let offset = storage.get_collection_meta("items").unwrap().document_catalog[&DocumentId::Int(last)];
+    let doc: serde_json::Value = serde_json::from_slice(&storage.read_data(offset).unwrap()).unwrap();
+    assert_eq!(doc["value"], last);
+}
@@ -0,0 +1,72 @@
+// Client: several named databases under one root directory, sharing background threads
+mod common;
+
+use common::doc;
+use ironbase_core::{Client, ClientOptions, MongoLiteError};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn no_maintenance() -> ClientOptions {
+    ClientOptions { compaction_interval: None, ..Default::default() }
+}
+
+#[test]
+fn test_client_manages_named_databases() {
+    let temp_dir = TempDir::new().unwrap();
+    let client = Client::open_with_options(temp_dir.path(), &no_maintenance()).unwrap();
+
+    let analytics = client.database("analytics").unwrap();
+    let events = analytics.collection("events").unwrap();
+    events.create_index("kind".to_string(), false).unwrap();
+    events.insert_many((0..50).map(|n| doc(json!({"kind": n % 5}))).collect()).unwrap();
+    let tx_id = analytics.begin_transaction();
+    analytics.insert_one_tx("events", doc(json!({"kind": 9})), tx_id).unwrap();
+    analytics.commit_transaction(tx_id).unwrap();
+    client.database("app").unwrap().collection("users").unwrap().insert_one(doc(json!({"name": "a"}))).unwrap();
+
+    // One handle per database; databases do not see each other's collections
+    assert!(std::sync::Arc::ptr_eq(&analytics, &client.database("analytics").unwrap()));
+    assert_eq!(client.database("app").unwrap().list_collections(), vec!["users".to_string()]);
+    assert_eq!(client.list_databases().unwrap(), vec!["analytics".to_string(), "app".to_string()]);
+    assert!(matches!(client.database("../etc"), Err(MongoLiteError::InvalidConfig(_))));
+    assert!(matches!(client.database("a.b"), Err(MongoLiteError::InvalidConfig(_))));
+    drop((events, analytics));
+    drop(client);
+
+    // Reopened, the data is there; dropping removes every file of the database
+    let client = Client::open_with_options(temp_dir.path(), &no_maintenance()).unwrap();
+    let events = client.database("analytics").unwrap().collection("events").unwrap();
+    assert_eq!(events.count_documents(&json!({"kind": 9})).unwrap(), 1);
+    assert_eq!(events.count_documents(&json!({"kind": 3})).unwrap(), 10);
+    drop(events);
+    assert!(client.drop_database("analytics").unwrap());
+    assert!(!client.drop_database("analytics").unwrap());
+    let left: Vec<String> = std::fs::read_dir(temp_dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert!(left.iter().all(|name| !name.starts_with("analytics.")), "{:?}", left);
+    assert_eq!(client.list_databases().unwrap(), vec!["app".to_string()]);
+    assert_eq!(client.database("analytics").unwrap().list_collections().len(), 0);
+}
+
+#[test]
+fn test_client_compacts_dead_collections_in_background() {
+    let temp_dir = TempDir::new().unwrap();
+    let options = ClientOptions {
+        compaction_interval: Some(Duration::from_millis(20)),
+        compaction_threshold: 0.5,
+        ..Default::default()
+    };
+    let client = Client::open_with_options(temp_dir.path(), &options).unwrap();
+    let logs = client.database("logs").unwrap().collection("entries").unwrap();
+    logs.insert_many((0..200).map(|n| doc(json!({"n": n, "text": "x".repeat(100)}))).collect()).unwrap();
+    logs.delete_many(&json!({"n": {"$gte": 20}})).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while logs.stats().unwrap()["dead_ratio"].as_f64().unwrap() >= 0.5 {
+        assert!(Instant::now() < deadline, "collection never compacted");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(logs.count_documents(&json!({})).unwrap(), 20);
+}
@@ -0,0 +1,136 @@
+// Collection-level storage statistics tests
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_stats_empty_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+
+    let stats = collection.stats().unwrap();
+    assert_eq!(stats["collection"], "users");
+    assert_eq!(stats["document_count"], 0);
+    assert_eq!(stats["tombstone_count"], 0);
+    assert_eq!(stats["avg_document_size"], 0);
+    assert_eq!(stats["dead_bytes"], 0);
+    assert_eq!(stats["index_sizes"]["users_id"], 0);
+}
+
+#[test]
+fn test_stats_reports_dead_space() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+
+    for i in 0..10 {
+        collection.insert_one(doc(json!({"name": format!("user{}", i), "age": i}))).unwrap();
+    }
+
+    let stats = collection.stats().unwrap();
+    assert_eq!(stats["document_count"], 10);
+    assert_eq!(stats["dead_bytes"], 0);
+    assert_eq!(stats["catalog_size"], 10);
+    assert!(stats["avg_document_size"].as_u64().unwrap() > 0);
+    assert_eq!(stats["total_bytes"], stats["live_bytes"]);
+
+    collection.update_one(&json!({"name": "user1"}), &json!({"$set": {"age": 100}})).unwrap();
+    collection.delete_many(&json!({"age": {"$lt": 3}})).unwrap();
+
+    let stats = collection.stats().unwrap();
+    assert_eq!(stats["document_count"], 8);
+    assert!(stats["tombstone_count"].as_u64().unwrap() >= 2);
+    assert!(stats["dead_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(
+        stats["total_bytes"].as_u64().unwrap(),
+        stats["live_bytes"].as_u64().unwrap() + stats["dead_bytes"].as_u64().unwrap()
+    );
+}
+
+#[test]
+fn test_stats_ignores_other_collections() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    let posts = db.collection("posts").unwrap();
+
+    users.insert_one(doc(json!({"name": "alice"}))).unwrap();
+    posts.insert_one(doc(json!({"title": "hello"}))).unwrap();
+    posts.insert_one(doc(json!({"title": "world"}))).unwrap();
+
+    assert_eq!(users.stats().unwrap()["document_count"], 1);
+    assert_eq!(posts.stats().unwrap()["document_count"], 2);
+}
+
+#[test]
+fn test_stats_index_sizes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+
+    for i in 0..5 {
+        collection.insert_one(doc(json!({"email": format!("u{}@x.com", i)}))).unwrap();
+    }
+    collection.create_index("email".to_string(), true).unwrap();
+
+    let stats = collection.stats().unwrap();
+    assert_eq!(stats["index_sizes"]["users_email"], 5);
+    assert_eq!(stats["index_sizes"]["users_id"], 5);
+}
+
+#[test]
+fn test_incremental_stats_match_a_full_recount() {
+    use ironbase_core::StorageEngine;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let before = {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+
+        for i in 0..30 {
+            users.insert_one(doc(json!({"seq": i, "bio": "x".repeat(60)}))).unwrap();
+        }
+        users.update_many(&json!({"seq": {"$lt": 10}}), &json!({"$set": {"bio": "y".repeat(80)}})).unwrap();
+        users.delete_many(&json!({"seq": {"$gte": 25}})).unwrap();
+
+        // Flushing reclaims the old versions; the next updates reuse their space
+        db.flush().unwrap();
+        users.update_many(&json!({"seq": {"$lt": 5}}), &json!({"$set": {"bio": "z"}})).unwrap();
+        users.delete_one(&json!({"seq": 12})).unwrap();
+
+        let stats = users.stats().unwrap();
+        assert_eq!(stats["document_count"], 24);
+        assert_eq!(stats["tombstone_count"], 6);
+        let ratio = stats["dead_ratio"].as_f64().unwrap();
+        assert!(ratio > 0.0 && ratio < 1.0);
+
+        // Reclaiming on flush takes the old versions out of the dead space
+        db.flush().unwrap();
+        let flushed = users.stats().unwrap();
+        assert!(flushed["dead_bytes"].as_u64() < stats["dead_bytes"].as_u64());
+        assert_eq!(flushed["tombstone_count"], 6);
+        flushed
+    };
+
+    // Persisted with the metadata, and the same numbers a scan arrives at
+    let mut storage = StorageEngine::open(&db_path).unwrap();
+    let persisted = storage.garbage_stats("users").unwrap();
+    assert_eq!(before["dead_bytes"], persisted.dead_bytes);
+    assert_eq!(before["tombstone_count"], persisted.tombstones);
+
+    storage.recount_garbage().unwrap();
+    assert_eq!(storage.garbage_stats("users").unwrap(), persisted);
+
+    // Compaction leaves only live records
+    storage.compact().unwrap();
+    let compacted = storage.garbage_stats("users").unwrap();
+    assert_eq!(compacted.live_records, 24);
+    assert_eq!(compacted.dead_bytes, 0);
+    assert_eq!(compacted.tombstones, 0);
+}
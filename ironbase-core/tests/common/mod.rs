@@ -0,0 +1,8 @@
+// Helpers shared by the integration tests (`mod common;`)
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Fields of a document to insert, from a JSON object
+pub fn doc(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
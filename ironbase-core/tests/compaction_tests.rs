@@ -1,5 +1,5 @@
 // Storage compaction tests
-use ironbase_core::{StorageEngine, Document, DocumentId};
+use ironbase_core::{DatabaseCore, StorageEngine, Document, DocumentId, MongoLiteError};
 use serde_json::json;
 use std::collections::HashMap;
 use tempfile::TempDir;
@@ -285,3 +285,153 @@ fn test_compaction_persistence() {
         assert_eq!(count, 5);
     }
 }
+
+fn insert_items(db: &DatabaseCore, collection: &str, count: i64) {
+    let coll = db.collection(collection).unwrap();
+    for seq in 0..count {
+        let mut fields = HashMap::new();
+        fields.insert("seq".to_string(), json!(seq));
+        fields.insert("payload".to_string(), json!("x".repeat(100)));
+        coll.insert_one(fields).unwrap();
+    }
+}
+
+#[test]
+fn test_compact_collection_leaves_others_alone() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("compact_collection.mlite");
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        insert_items(&db, "cold", 50);
+        insert_items(&db, "hot", 10);
+        let cold = db.collection("cold").unwrap();
+        cold.delete_many(&json!({"seq": {"$gte": 40}})).unwrap();
+        let hot = db.collection("hot").unwrap();
+        hot.update_many(&json!({}), &json!({"$set": {"payload": "y"}})).unwrap();
+        hot.delete_many(&json!({"seq": {"$lt": 4}})).unwrap();
+        // Flushing frees old versions of every collection; compaction does the rest
+        db.flush().unwrap();
+        let cold_before = cold.stats().unwrap();
+
+        let stats = db.compact_collection("hot").unwrap();
+        assert_eq!(stats.collections.keys().collect::<Vec<_>>(), vec!["hot"]);
+        let hot_stats = &stats.collections["hot"];
+        assert_eq!(hot_stats.documents_kept, 6);
+        assert_eq!(hot_stats.tombstones_removed, 4);
+        // The old versions were freed by the flush before the scan
+        assert_eq!(hot_stats.documents_scanned, 10);
+        assert!(hot_stats.bytes_reclaimed > 0);
+        assert_eq!((stats.documents_kept, stats.tombstones_removed), (6, 4));
+
+        // The hot collection has no dead space left; the cold one is untouched
+        let hot_after = hot.stats().unwrap();
+        assert_eq!((hot_after["dead_bytes"].clone(), hot_after["tombstone_count"].clone()), (json!(0), json!(0)));
+        assert_eq!(cold.stats().unwrap(), cold_before);
+        assert_eq!(hot.count_documents(&json!({"payload": "y"})).unwrap(), 6);
+        assert_eq!(cold.count_documents(&json!({})).unwrap(), 40);
+
+        // The freed regions are reused by later writes
+        let size = db.compact_collection("hot").unwrap().size_after;
+        insert_items(&db, "hot", 3);
+        assert_eq!(std::fs::metadata(&db_path).unwrap().len(), size);
+
+        assert!(matches!(db.compact_collection("missing"), Err(MongoLiteError::CollectionNotFound(_))));
+    }
+
+    // Deleted documents stay deleted after reopening and a full compaction
+    let db = DatabaseCore::open(&db_path).unwrap();
+    assert_eq!(db.collection("hot").unwrap().count_documents(&json!({})).unwrap(), 9);
+    let stats = db.compact().unwrap();
+    assert_eq!(stats.collections["cold"].documents_kept, 40);
+    assert_eq!(stats.collections["cold"].tombstones_removed, 10);
+    assert_eq!(stats.collections["hot"].documents_kept, 9);
+    assert_eq!(db.collection("hot").unwrap().count_documents(&json!({"seq": {"$lt": 4}})).unwrap(), 3);
+}
+
+#[test]
+fn test_compact_collection_trims_file_tail() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("compact_tail.mlite");
+    let db = DatabaseCore::open(&db_path).unwrap();
+    insert_items(&db, "items", 20);
+    db.flush().unwrap();
+    let size = std::fs::metadata(&db_path).unwrap().len();
+
+    // Rewriting every document appends a second copy of each
+    let items = db.collection("items").unwrap();
+    items.update_many(&json!({}), &json!({"$set": {"payload": "z".repeat(100)}})).unwrap();
+    items.delete_many(&json!({})).unwrap();
+
+    let stats = db.compact_collection("items").unwrap();
+    assert!(stats.size_after < stats.size_before);
+    assert!(stats.size_after <= size);
+    assert_eq!(stats.collections["items"].tombstones_removed, 20);
+    assert_eq!(items.count_documents(&json!({})).unwrap(), 0);
+}
+
+
+/// Offsets of `collection`'s records: walking its extents finds the same as
+/// walking the whole file
+fn assert_extents_cover(storage: &mut StorageEngine, collection: &str) -> u64 {
+    let mut all = Vec::new();
+    storage.for_each_record(|offset, data| {
+        all.push((offset, data.to_vec()));
+        Ok(())
+    }).unwrap();
+    let mut pruned = Vec::new();
+    storage.for_each_record_of(collection, |offset, data| {
+        pruned.push((offset, data.to_vec()));
+        Ok(())
+    }).unwrap();
+    let of_collection = |records: Vec<(u64, Vec<u8>)>| -> Vec<u64> {
+        records.into_iter()
+            .filter(|(_, data)| serde_json::from_slice::<serde_json::Value>(data).unwrap_or_default()["_collection"] == collection)
+            .map(|(offset, _)| offset)
+            .collect()
+    };
+    assert_eq!(of_collection(pruned), of_collection(all));
+
+    let extents = storage.collection_extents(collection).unwrap();
+    assert!(extents.len() <= ironbase_core::storage::MAX_EXTENTS);
+    extents.iter().map(|(start, end)| end - start).sum()
+}
+
+#[test]
+fn test_collection_extents_prune_record_walks() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("extents.mlite");
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        insert_items(&db, "small", 5);
+        insert_items(&db, "bulk", 300);
+        insert_items(&db, "small", 5);
+        insert_items(&db, "bulk", 300);
+        let small = db.collection("small").unwrap();
+        let bulk = db.collection("bulk").unwrap();
+
+        // Updates, deletes and reused free regions keep the extents exact
+        small.update_many(&json!({"seq": {"$lt": 3}}), &json!({"$set": {"payload": "y"}})).unwrap();
+        bulk.delete_many(&json!({"seq": {"$lt": 100}})).unwrap();
+        db.flush().unwrap();
+        insert_items(&db, "small", 5);
+        db.flush().unwrap();
+        let covered = assert_extents_cover(&mut small.storage.write(), "small");
+        assert!(covered * 4 < std::fs::metadata(&db_path).unwrap().len());
+        assert_extents_cover(&mut small.storage.write(), "bulk");
+
+        db.compact_collection("bulk").unwrap();
+        let stats = db.compact_collection("small").unwrap();
+        assert_eq!(stats.collections["small"].documents_kept, 15);
+        assert_extents_cover(&mut small.storage.write(), "small");
+        assert_extents_cover(&mut small.storage.write(), "bulk");
+    }
+
+    // Extents are persisted, and rebuilt by a full compaction
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let small = db.collection("small").unwrap();
+    assert_extents_cover(&mut small.storage.write(), "small");
+    db.compact().unwrap();
+    assert_eq!(small.storage.write().collection_extents("small").unwrap().len(), 1);
+    assert_extents_cover(&mut small.storage.write(), "small");
+    assert_eq!(small.count_documents(&json!({})).unwrap(), 15);
+}
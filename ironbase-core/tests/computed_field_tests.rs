@@ -0,0 +1,69 @@
+// Computed fields: stored expression results maintained on insert and update
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, EncryptionKey, EncryptionMode};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_computed_fields_follow_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let people = db.collection("people").unwrap();
+        people.insert_one(doc(json!({"first": "Ada", "last": "Lovelace"}))).unwrap();
+
+        // Existing documents are backfilled
+        people.add_computed_field("full_name", &json!({"$concat": ["$first", " ", "$last"]})).unwrap();
+        assert_eq!(people.find_one(&json!({})).unwrap().unwrap()["full_name"], json!("Ada Lovelace"));
+
+        // Inserts and updates recompute; a written value is overwritten
+        people.insert_one(doc(json!({"first": "Alan", "last": "Turing", "full_name": "x"}))).unwrap();
+        assert_eq!(people.count_documents(&json!({"full_name": "Alan Turing"})).unwrap(), 1);
+        people.update_one(&json!({"first": "Ada"}), &json!({"$set": {"last": "King"}})).unwrap();
+        assert_eq!(people.count_documents(&json!({"full_name": "Ada King"})).unwrap(), 1);
+        people.update_many(&json!({}), &json!({"$unset": {"last": ""}})).unwrap();
+        assert_eq!(people.count_documents(&json!({"full_name": {"$exists": true}})).unwrap(), 0);
+
+        assert!(people.add_computed_field("_id", &json!("$first")).is_err());
+        assert!(people.add_computed_field("n", &json!({"$bogus": 1})).is_err());
+        assert!(people.increment(&json!({}), "full_name", &json!(1)).is_err());
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let people = db.collection("people").unwrap();
+    assert_eq!(people.computed_fields().len(), 1);
+    people.update_one(&json!({"first": "Alan"}), &json!({"$set": {"last": "T"}})).unwrap();
+    assert_eq!(people.count_documents(&json!({"full_name": "Alan T"})).unwrap(), 1);
+
+    // Dropped: the stored value stays but is no longer maintained
+    assert!(people.drop_computed_field("full_name").unwrap());
+    assert!(!people.drop_computed_field("full_name").unwrap());
+    people.update_one(&json!({"first": "Alan"}), &json!({"$set": {"last": "Turing"}})).unwrap();
+    assert_eq!(people.count_documents(&json!({"full_name": "Alan T"})).unwrap(), 1);
+}
+
+#[test]
+fn test_computed_field_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.add_computed_field("email_key", &json!({"$toLower": "$email"})).unwrap();
+    users.create_index("email_key".to_string(), true).unwrap();
+
+    users.insert_one(doc(json!({"email": "Ann@Example.com"}))).unwrap();
+    assert!(users.insert_one(doc(json!({"email": "ann@example.COM"}))).is_err());
+    users.insert_one(doc(json!({"email": "bob@example.com"}))).unwrap();
+    assert!(users.update_one(&json!({"email": "bob@example.com"}), &json!({"$set": {"email": "ANN@example.com"}})).is_err());
+
+    let found = users.find(&json!({"email_key": "ann@example.com"})).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["email"], json!("Ann@Example.com"));
+
+    // Computed and encrypted fields don't mix in one collection
+    db.set_encryption_key(Some(EncryptionKey::new([7; 32])));
+    assert!(users.encrypt_field("ssn", EncryptionMode::Deterministic).is_err());
+}
@@ -0,0 +1,55 @@
+// Lock contention metrics
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_metrics_count_lock_acquisitions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    db.reset_metrics();
+
+    let mut fields = HashMap::new();
+    fields.insert("name".to_string(), json!("alice"));
+    users.insert_one(fields).unwrap();
+    users.find(&json!({"name": "alice"})).unwrap();
+
+    let (storage, indexes) = db.lock_stats();
+    assert!(storage.write.acquisitions >= 1);
+    assert!(storage.read.acquisitions >= 1);
+    assert!(indexes.write.acquisitions >= 1);
+
+    let metrics = db.metrics();
+    assert_eq!(metrics["locks"]["storage"]["write"]["acquisitions"], json!(storage.write.acquisitions));
+    assert!(metrics["contention"]["total_wait_ms"].is_number());
+
+    db.reset_metrics();
+    let (storage, indexes) = db.lock_stats();
+    assert_eq!(storage.read.acquisitions + storage.write.acquisitions, 0);
+    assert_eq!(indexes.read.acquisitions + indexes.write.acquisitions, 0);
+    assert_eq!(db.metrics()["contention"]["most_contended"], json!(null));
+}
+
+#[test]
+fn test_blocked_storage_lock_is_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    db.reset_metrics();
+
+    std::thread::scope(|scope| {
+        let guard = users.storage.write();
+        let reader = scope.spawn(|| db.stats());
+        std::thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        reader.join().unwrap();
+    });
+
+    let (storage, _) = db.lock_stats();
+    assert_eq!(storage.read.contended, 1);
+    assert!(storage.read.max_wait >= Duration::from_millis(40));
+    assert_eq!(db.metrics()["contention"]["most_contended"], json!("storage"));
+}
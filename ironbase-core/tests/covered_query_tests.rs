@@ -0,0 +1,107 @@
+// Covered queries: filter + projection answered from the index alone
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, FindOptions};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn projection(fields: &[(&str, i32)]) -> HashMap<String, i32> {
+    fields.iter().map(|(field, action)| (field.to_string(), *action)).collect()
+}
+
+#[test]
+fn test_covered_query_matches_fetch_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    for (name, age) in [("a", 20), ("b", 35), ("c", 42), ("d", 35), ("e", 61)] {
+        users.insert_one(doc(json!({"name": name, "age": age}))).unwrap();
+    }
+    users.create_index("age".to_string(), false).unwrap();
+
+    let options = FindOptions::new()
+        .with_projection(projection(&[("age", 1), ("_id", 0)]))
+        .with_sort(vec![("age".to_string(), 1)]);
+    let query = json!({"age": {"$gte": 30, "$lt": 60}});
+
+    let covered = users.find_with_options(&query, options.clone()).unwrap();
+    assert_eq!(covered, vec![json!({"age": 35}), json!({"age": 35}), json!({"age": 42})]);
+
+    // Same answer as fetching full documents and projecting them
+    let mut fetched = users.find(&query).unwrap();
+    fetched.sort_by_key(|doc| doc["age"].as_i64());
+    let fetched: Vec<_> = fetched.iter().map(|doc| json!({"age": doc["age"]})).collect();
+    assert_eq!(covered, fetched);
+
+    let plan = users.explain_with_options(&query, &options).unwrap();
+    assert_eq!(plan["coveredQuery"], true);
+    assert_eq!(plan["stage"], "INDEX_ONLY");
+}
+
+#[test]
+fn test_uncovered_projection_fetches_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    users.insert_one(doc(json!({"name": "a", "age": 20}))).unwrap();
+    users.create_index("age".to_string(), false).unwrap();
+
+    let options = FindOptions::new().with_projection(projection(&[("name", 1), ("_id", 0)]));
+    let results = users.find_with_options(&json!({"age": 20}), options.clone()).unwrap();
+    assert_eq!(results, vec![json!({"name": "a"})]);
+
+    let plan = users.explain_with_options(&json!({"age": 20}), &options).unwrap();
+    assert_eq!(plan["coveredQuery"], false);
+    assert_eq!(plan["stage"], "FETCH_WITH_INDEX");
+}
+
+#[test]
+fn test_covered_query_sees_updates_and_deletes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    users.create_index("age".to_string(), false).unwrap();
+    for (name, age) in [("a", 20), ("b", 20), ("c", 30)] {
+        users.insert_one(doc(json!({"name": name, "age": age}))).unwrap();
+    }
+
+    users.update_one(&json!({"name": "a"}), &json!({"$set": {"age": 30}})).unwrap();
+    users.delete_one(&json!({"name": "c"})).unwrap();
+
+    let options = FindOptions::new().with_projection(projection(&[("age", 1), ("_id", 0)]));
+    let twenty = users.find_with_options(&json!({"age": 20}), options.clone()).unwrap();
+    assert_eq!(twenty, vec![json!({"age": 20})]);
+    let thirty = users.find_with_options(&json!({"age": 30}), options.clone()).unwrap();
+    assert_eq!(thirty, vec![json!({"age": 30})]);
+
+    users.delete_many(&json!({})).unwrap();
+    assert!(users.find_with_options(&json!({"age": 30}), options).unwrap().is_empty());
+}
+
+#[test]
+fn test_nested_field_index_fetches_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    users.create_index("address.city".to_string(), false).unwrap();
+    users.insert_one(doc(json!({"name": "a", "address": {"city": "Pécs"}}))).unwrap();
+    users.insert_one(doc(json!({"name": "b", "address": {"city": "Győr"}}))).unwrap();
+
+    // Index keys can't be put back at a dotted path, so the rows are fetched
+    let query = json!({"address.city": "Pécs"});
+    let options = FindOptions::new().with_projection(projection(&[("address.city", 1), ("_id", 0)]));
+    let plan = users.explain_with_options(&query, &options).unwrap();
+    assert_eq!(plan["coveredQuery"], false);
+    assert_eq!(plan["stage"], "FETCH_WITH_INDEX");
+
+    let projected: Vec<_> = users.find(&query).unwrap().iter()
+        .map(|doc| ironbase_core::find_options::apply_projection(doc, &projection(&[("address.city", 1), ("_id", 0)])))
+        .collect();
+    assert_eq!(users.find_with_options(&query, options).unwrap(), projected);
+}
@@ -0,0 +1,62 @@
+// Crash-recovery property tests: random workloads with injected crashes
+// (see ironbase_core::testing)
+use ironbase_core::testing::{CrashHarness, CrashPoint, Op, KEYS};
+use proptest::prelude::*;
+use tempfile::TempDir;
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (0..KEYS).prop_map(Op::Insert),
+        3 => (0..KEYS).prop_map(Op::Update),
+        2 => (0..KEYS).prop_map(Op::Delete),
+        1 => Just(Op::Begin),
+        1 => Just(Op::Commit),
+        1 => Just(Op::Rollback),
+        1 => Just(Op::Flush),
+        1 => Just(Op::Compact),
+        1 => any::<(u8, u8)>().prop_map(|(data, wal)| Op::Crash(CrashPoint { data, wal })),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn prop_crash_recovery_keeps_invariants(ops in prop::collection::vec(op(), 1..60)) {
+        let temp_dir = TempDir::new().unwrap();
+        if let Err(violation) = CrashHarness::run(temp_dir.path().join("test.mlite"), &ops) {
+            panic!("{}", violation);
+        }
+    }
+}
+
+#[test]
+fn test_crash_losing_unsynced_tail() {
+    let temp_dir = TempDir::new().unwrap();
+    let ops = [
+        Op::Insert(1), Op::Insert(2), Op::Flush,
+        Op::Update(1), Op::Begin, Op::Insert(3), Op::Delete(2), Op::Commit,
+        Op::Insert(4), Op::Begin, Op::Update(1),
+        Op::Crash(CrashPoint { data: 0, wal: 0 }),
+        Op::Insert(5), Op::Begin, Op::Delete(5), Op::Rollback,
+        Op::Crash(CrashPoint { data: 128, wal: 64 }),
+        Op::Compact, Op::Update(3),
+        Op::Crash(CrashPoint { data: 255, wal: 255 }),
+    ];
+    let mut harness = CrashHarness::new(temp_dir.path().join("test.mlite")).unwrap();
+    for op in ops {
+        harness.apply(op).unwrap_or_else(|violation| panic!("{:?}: {}", op, violation));
+    }
+
+    // The committed transaction survived, the one open at the crash did not
+    assert!(harness.model().get(3).is_some());
+    assert!(harness.model().get(2).is_none());
+    assert!(harness.model().get(5).is_some());
+}
+
+#[test]
+fn test_decode_accepts_any_bytes() {
+    assert_eq!(Op::decode(&[]), vec![]);
+    assert_eq!(Op::decode(&[0, 17, 9, 10, 12]), vec![Op::Insert(1), Op::Begin, Op::Commit, Op::Flush]);
+    assert_eq!(Op::decode(&[14, 1, 2, 15, 1]), vec![Op::Crash(CrashPoint { data: 1, wal: 2 })]);
+}
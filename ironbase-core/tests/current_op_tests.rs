@@ -0,0 +1,43 @@
+// Registry of running operations: current_ops() and kill_op()
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[test]
+fn test_kill_running_aggregation() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap());
+    let orders = db.collection("orders").unwrap();
+    let documents = (0..2000)
+        .map(|i| HashMap::from([("amount".to_string(), json!(i)), ("region".to_string(), json!(i % 7))]))
+        .collect();
+    orders.insert_many(documents).unwrap();
+
+    // Rerun the aggregation until a kill lands while it is scanning
+    let pipeline = json!([{"$group": {"_id": "$region", "total": {"$sum": "$amount"}}}]);
+    let worker = std::thread::spawn(move || {
+        for _ in 0..1000 {
+            if let Err(e) = orders.aggregate(&pipeline) {
+                return Some(e);
+            }
+        }
+        None
+    });
+
+    while !worker.is_finished() {
+        for op in db.current_ops() {
+            assert_eq!(op.op, "aggregate");
+            assert_eq!(op.collection, "orders");
+            db.kill_op(op.id);
+        }
+    }
+    let error = worker.join().unwrap();
+    assert!(matches!(error, Some(MongoLiteError::Cancelled(_))), "{:?}", error);
+
+    // Finished operations leave the registry
+    assert!(db.current_ops().is_empty());
+    assert!(!db.kill_op(u64::MAX));
+    assert_eq!(db.collection("orders").unwrap().find(&json!({"region": 3})).unwrap().len(), 286);
+}
@@ -0,0 +1,79 @@
+// Server-side cursors: batches, getMore, per-connection limits and idle timeouts
+mod common;
+
+use common::doc;
+use ironbase_core::{CursorConfig, CursorRegistry, DatabaseCore, MongoLiteError};
+use serde_json::json;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_cursor_returns_batches_until_exhausted() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+    items.insert_many((0..250).map(|n| doc(json!({"n": n, "even": n % 2 == 0}))).collect()).unwrap();
+
+    let cursors = CursorRegistry::default();
+    let source = Box::new(items.find_iter(&json!({"even": true})).unwrap());
+    let first = cursors.open(1, source, Some(50)).unwrap();
+    assert_eq!(first.documents.len(), 50);
+    assert_ne!(first.cursor_id, 0);
+    assert_eq!(first.to_json()["cursor"]["firstBatch"].as_array().unwrap().len(), 50);
+
+    // Writes after the cursor opened are not in it
+    items.delete_many(&json!({"n": {"$gte": 200}})).unwrap();
+
+    // Another connection does not see the cursor
+    assert!(matches!(cursors.get_more(2, first.cursor_id, None), Err(MongoLiteError::CursorNotFound(_))));
+
+    let mut seen = first.documents;
+    let second = cursors.get_more(1, first.cursor_id, Some(50)).unwrap();
+    assert_eq!(second.cursor_id, first.cursor_id);
+    seen.extend(second.documents);
+    let last = cursors.get_more(1, first.cursor_id, Some(50)).unwrap();
+    assert!(last.exhausted());
+    assert_eq!(last.documents.len(), 25);
+    assert!(last.to_json()["cursor"]["nextBatch"].is_array());
+    seen.extend(last.documents);
+    assert_eq!(seen.len(), 125);
+    assert!(seen.iter().all(|doc| doc["even"] == true));
+    assert_eq!(cursors.open_cursors(), 0);
+
+    // A result that fits the first batch opens no cursor
+    let small = cursors.open(1, Box::new(items.find_iter(&json!({"n": 3})).unwrap()), None).unwrap();
+    assert!(small.exhausted());
+    assert_eq!(small.documents.len(), 1);
+    assert!(matches!(cursors.get_more(1, first.cursor_id, None), Err(MongoLiteError::CursorNotFound(_))));
+}
+
+#[test]
+fn test_cursor_limits_and_timeouts() {
+    let cursors = CursorRegistry::new(CursorConfig {
+        batch_size: 2,
+        idle_timeout: Duration::from_millis(100),
+        max_per_connection: 2,
+    });
+    let numbers = || Box::new((0..10).map(|n| Ok(json!(n))));
+
+    let a = cursors.open(1, numbers(), None).unwrap();
+    let b = cursors.open(1, numbers(), None).unwrap();
+    assert_eq!(a.documents, vec![json!(0), json!(1)]);
+    assert!(matches!(cursors.open(1, numbers(), None), Err(MongoLiteError::TooManyCursors { limit: 2 })));
+    let other = cursors.open(2, numbers(), None).unwrap();
+
+    assert!(!cursors.kill(2, a.cursor_id));
+    assert!(cursors.kill(1, a.cursor_id));
+    assert!(cursors.open(1, numbers(), None).is_ok());
+    assert_eq!(cursors.close_connection(1), 2);
+    assert!(matches!(cursors.get_more(1, b.cursor_id, None), Err(MongoLiteError::CursorNotFound(_))));
+
+    // An idle cursor expires, one in use does not
+    let busy = cursors.open(3, numbers(), None).unwrap();
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(40));
+        cursors.get_more(3, busy.cursor_id, None).unwrap();
+    }
+    assert!(matches!(cursors.get_more(2, other.cursor_id, None), Err(MongoLiteError::CursorNotFound(_))));
+    assert_eq!(cursors.open_cursors(), 1);
+}
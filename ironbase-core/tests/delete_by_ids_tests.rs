@@ -0,0 +1,53 @@
+// delete_many_by_ids and find_and_delete_many
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, DocumentId, EncryptionKey, EncryptionMode, MongoLiteError, OnDelete};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[test]
+fn test_delete_many_by_ids() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    let ids: Vec<DocumentId> = (0..4)
+        .map(|i| users.insert_one(doc(json!({"n": i, "email": format!("{}@x", i)}))).unwrap())
+        .collect();
+
+    let deleted = users.delete_many_by_ids(&[ids[0].clone(), ids[2].clone(), ids[2].clone(), DocumentId::Int(99)]).unwrap().deleted_count;
+    assert_eq!(deleted, 2);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 2);
+    assert_eq!(users.delete_many_by_ids(&[ids[0].clone()]).unwrap().deleted_count, 0);
+
+    // Index entries went with them
+    users.insert_one(doc(json!({"email": "0@x"}))).unwrap();
+
+    // Restricting references veto the whole batch
+    let orders = db.collection("orders").unwrap();
+    orders.add_reference("user_id", "users", OnDelete::Restrict).unwrap();
+    orders.insert_one(doc(json!({"user_id": ids[3].clone()}))).unwrap();
+    let result = users.delete_many_by_ids(&[ids[1].clone(), ids[3].clone()]);
+    assert!(matches!(result, Err(MongoLiteError::ReferenceViolation(_))));
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 3);
+}
+
+#[test]
+fn test_find_and_delete_many_returns_deleted_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_encryption_key(Some(EncryptionKey::new([7; 32])));
+    let logs = db.collection("logs").unwrap();
+    logs.encrypt_field("message", EncryptionMode::Randomized).unwrap();
+    for (level, message) in [("debug", "a"), ("info", "b"), ("debug", "c")] {
+        logs.insert_one(doc(json!({"level": level, "message": message}))).unwrap();
+    }
+
+    let mut removed = logs.find_and_delete_many(&json!({"level": "debug"})).unwrap();
+    removed.sort_by_key(|doc| doc["message"].as_str().map(str::to_string));
+    let messages: Vec<&Value> = removed.iter().map(|doc| &doc["message"]).collect();
+    assert_eq!(messages, vec![&json!("a"), &json!("c")]);
+    assert_eq!(logs.count_documents(&json!({})).unwrap(), 1);
+    assert!(logs.find_and_delete_many(&json!({"level": "debug"})).unwrap().is_empty());
+}
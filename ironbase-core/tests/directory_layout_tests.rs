@@ -0,0 +1,60 @@
+// File-per-collection layout: one data file per collection under a database directory
+mod common;
+
+use common::doc;
+use ironbase_core::directory::MANIFEST_FILE;
+use ironbase_core::{DirectoryDatabase, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn file_names(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_directory_layout_keeps_collections_apart() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("db");
+    {
+        let db = DirectoryDatabase::open(&dir).unwrap();
+        let users = db.collection("users").unwrap();
+        users.create_index("email".to_string(), true).unwrap();
+        users.insert_one(doc(json!({"email": "ann@x"}))).unwrap();
+        let logs = db.collection("order items").unwrap();
+        for i in 0..200 {
+            logs.insert_one(doc(json!({"n": i, "padding": "x".repeat(200)}))).unwrap();
+        }
+        db.flush().unwrap();
+    }
+
+    let db = DirectoryDatabase::open(&dir).unwrap();
+    assert_eq!(db.list_collections(), vec!["order items".to_string(), "users".to_string()]);
+    let users = db.collection("users").unwrap();
+    assert!(users.insert_one(doc(json!({"email": "ann@x"}))).is_err());
+    assert_eq!(db.collection("order items").unwrap().count_documents(&json!({})).unwrap(), 200);
+
+    // Compacting one collection rewrites only its file
+    let users_size = std::fs::metadata(dir.join("c1.mlite")).unwrap().len();
+    db.collection("order items").unwrap().delete_many(&json!({"n": {"$gte": 10}})).unwrap();
+    let stats = db.compact_collection("order items").unwrap();
+    assert!(stats.size_after < stats.size_before);
+    assert_eq!(std::fs::metadata(dir.join("c1.mlite")).unwrap().len(), users_size);
+    assert!(matches!(db.compact_collection("missing"), Err(MongoLiteError::CollectionNotFound(_))));
+
+    // Dropping a collection deletes its files
+    assert!(file_names(&dir).iter().filter(|name| name.starts_with("c1.")).count() >= 2);
+    db.drop_collection("users").unwrap();
+    assert!(!file_names(&dir).iter().any(|name| name.starts_with("c1.")));
+    assert!(file_names(&dir).contains(&MANIFEST_FILE.to_string()));
+    assert_eq!(db.list_collections(), vec!["order items".to_string()]);
+    assert!(matches!(db.drop_collection("users"), Err(MongoLiteError::CollectionNotFound(_))));
+
+    // A new collection of the same name starts empty in a new file
+    assert_eq!(db.collection("users").unwrap().count_documents(&json!({})).unwrap(), 0);
+    assert!(dir.join("c3.mlite").exists());
+    assert_eq!(db.collection("order items").unwrap().count_documents(&json!({})).unwrap(), 10);
+}
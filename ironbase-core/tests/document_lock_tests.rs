@@ -0,0 +1,169 @@
+// Document-level write latches between transactions
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn open_with_accounts(temp_dir: &TempDir) -> Arc<DatabaseCore> {
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let accounts = db.collection("accounts").unwrap();
+    for name in ["a", "b"] {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), json!(name));
+        fields.insert("balance".to_string(), json!(100));
+        accounts.insert_one(fields).unwrap();
+    }
+    Arc::new(db)
+}
+
+fn balance(db: &DatabaseCore, name: &str) -> serde_json::Value {
+    db.collection("accounts").unwrap().find_one(&json!({"name": name})).unwrap().unwrap()["balance"].clone()
+}
+
+#[test]
+fn test_transactions_on_different_documents_do_not_block() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+    db.set_lock_timeout(Duration::from_millis(100));
+
+    let tx1 = db.begin_transaction();
+    let tx2 = db.begin_transaction();
+    db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 50}), tx1).unwrap();
+    db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 150}), tx2).unwrap();
+    assert_eq!(db.lock_manager().held_count(tx1), 1);
+
+    db.commit_transaction(tx2).unwrap();
+    db.commit_transaction(tx1).unwrap();
+    assert_eq!((balance(&db, "a"), balance(&db, "b")), (json!(50), json!(150)));
+    assert_eq!(db.lock_manager().held_count(tx1), 0);
+}
+
+#[test]
+fn test_writer_of_same_document_waits_for_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+
+    let tx1 = db.begin_transaction();
+    db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 10}), tx1).unwrap();
+
+    let tx2 = db.begin_transaction();
+    let waiter = {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 20}), tx2).unwrap();
+            db.commit_transaction(tx2).unwrap();
+        })
+    };
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!waiter.is_finished(), "second writer must wait for the latch");
+
+    db.commit_transaction(tx1).unwrap();
+    waiter.join().unwrap();
+    assert_eq!(balance(&db, "a"), json!(20));
+}
+
+#[test]
+fn test_deadlock_rolls_back_one_transaction() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+
+    let tx1 = db.begin_transaction();
+    let tx2 = db.begin_transaction();
+    db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 1}), tx1).unwrap();
+    db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 2}), tx2).unwrap();
+
+    // tx1 waits for b ...
+    let waiter = {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 3}), tx1).unwrap();
+            db.commit_transaction(tx1).unwrap();
+        })
+    };
+    std::thread::sleep(Duration::from_millis(100));
+
+    // ... so tx2 asking for a closes the cycle and is rolled back
+    let result = db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 4}), tx2);
+    assert!(matches!(result, Err(MongoLiteError::Deadlock(_))));
+    assert!(result.unwrap_err().is_transient());
+    assert!(db.get_transaction(tx2).is_none());
+
+    waiter.join().unwrap();
+    assert_eq!((balance(&db, "a"), balance(&db, "b")), (json!(1), json!(3)));
+}
+
+#[test]
+fn test_lock_wait_timeout() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+    db.set_lock_timeout(Duration::from_millis(50));
+
+    let tx1 = db.begin_transaction();
+    db.delete_one_tx("accounts", &json!({"name": "a"}), tx1).unwrap();
+
+    let tx2 = db.begin_transaction();
+    let result = db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 0}), tx2);
+    assert!(matches!(result, Err(MongoLiteError::LockTimeout(_))));
+    assert!(result.unwrap_err().is_transient());
+    assert!(db.get_transaction(tx2).is_none());
+
+    db.commit_transaction(tx1).unwrap();
+    assert!(db.collection("accounts").unwrap().find_one(&json!({"name": "a"})).unwrap().is_none());
+}
+
+#[test]
+fn test_deadlock_victim_succeeds_when_retried() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+
+    let tx1 = db.begin_transaction();
+    db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 1}), tx1).unwrap();
+    let (latched_b, latch_b) = std::sync::mpsc::channel();
+    let (go, waiting) = std::sync::mpsc::channel();
+
+    // tx1 holds a and waits for b; whoever holds b then asks for a
+    let waiter = {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            latch_b.recv().unwrap();
+            go.send(()).unwrap();
+            db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 1}), tx1).unwrap();
+            db.commit_transaction(tx1).unwrap();
+        })
+    };
+
+    // Retried from the start while the error is transient, backing off as
+    // with_retry does so the winner can finish first
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let tx = db.begin_transaction();
+        let result = db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 2}), tx)
+            .and_then(|_| {
+                if attempts == 1 {
+                    latched_b.send(()).unwrap();
+                    waiting.recv().unwrap();
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 2}), tx)
+            })
+            .and_then(|_| db.commit_transaction(tx));
+        match result {
+            Ok(()) => break,
+            Err(e) if e.is_transient() && attempts < 8 => {
+                assert!(db.get_transaction(tx).is_none());
+                std::thread::sleep(Duration::from_millis(10 << attempts));
+            }
+            Err(e) => panic!("attempt {} failed: {}", attempts, e),
+        }
+    }
+    waiter.join().unwrap();
+
+    assert!(attempts >= 2);
+    assert_eq!((balance(&db, "a"), balance(&db, "b")), (json!(2), json!(2)));
+    // A timeout that is not a latch wait rolls nothing back
+    assert!(!MongoLiteError::Timeout("waiting for LSN 9 (current 3)".to_string()).is_transient());
+}
@@ -0,0 +1,111 @@
+// Document size limits and index nodes larger than one page
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError, StorageConfig};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn too_large<T: std::fmt::Debug>(result: ironbase_core::Result<T>) -> bool {
+    matches!(result, Err(MongoLiteError::DocumentTooLarge { .. }))
+}
+
+#[test]
+fn test_oversized_writes_are_rejected_cleanly() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::default().with_max_document_size(1024);
+    let db = DatabaseCore::open_with_config(temp_dir.path().join("test.mlite"), &config).unwrap();
+    let coll = db.collection("docs").unwrap();
+    coll.create_index("name".to_string(), true).unwrap();
+
+    let id = coll.insert_one(doc(json!({"name": "small"}))).unwrap();
+    let err = coll.insert_one(doc(json!({"name": "big", "body": "x".repeat(2000)}))).unwrap_err();
+    match err {
+        MongoLiteError::DocumentTooLarge { size, max } => {
+            assert!(size > 2000);
+            assert_eq!(max, 1024);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert!(too_large(coll.insert_many(vec![
+        doc(json!({"name": "ok"})),
+        doc(json!({"name": "big", "body": "x".repeat(2000)})),
+    ])));
+    assert!(too_large(coll.update_one(&json!({"name": "small"}), &json!({"$set": {"body": "x".repeat(2000)}}))));
+    assert!(too_large(coll.update_many(&json!({}), &json!({"$set": {"body": "x".repeat(2000)}}))));
+
+    // Neither the collection nor the unique index saw the rejected documents
+    assert_eq!(coll.count_documents(&json!({})).unwrap(), 1);
+    coll.insert_one(doc(json!({"name": "big"}))).unwrap();
+    coll.insert_one(doc(json!({"name": "ok"}))).unwrap();
+    assert_eq!(coll.count_documents(&json!({"body": {"$exists": true}})).unwrap(), 0);
+
+    // Rejected inserts do not consume ids
+    let next = coll.insert_one(doc(json!({"name": "next"}))).unwrap();
+    assert_eq!(serde_json::to_value(&next).unwrap(), json!(serde_json::to_value(&id).unwrap().as_i64().unwrap() + 3));
+}
+
+#[test]
+fn test_transactions_reject_oversized_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::default().with_max_document_size(1024);
+    let db = DatabaseCore::open_with_config(temp_dir.path().join("test.mlite"), &config).unwrap();
+    db.collection("docs").unwrap().insert_one(doc(json!({"name": "a"}))).unwrap();
+
+    let tx = db.begin_transaction();
+    assert!(too_large(db.insert_one_tx("docs", doc(json!({"body": "x".repeat(2000)})), tx)));
+    assert!(too_large(db.update_one_tx("docs", &json!({"name": "a"}), json!({"body": "x".repeat(2000)}), tx)));
+    db.commit_transaction(tx).unwrap();
+    assert_eq!(db.collection("docs").unwrap().count_documents(&json!({})).unwrap(), 1);
+}
+
+#[test]
+fn test_invalid_max_document_size_is_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = StorageConfig::default().with_max_document_size(0);
+    let result = DatabaseCore::open_with_config(temp_dir.path().join("test.mlite"), &config);
+    assert!(matches!(result, Err(MongoLiteError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_large_documents_and_index_keys_persist() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let body = "y".repeat(100_000);
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let coll = db.collection("docs").unwrap();
+        coll.create_index("key".to_string(), false).unwrap();
+        // Keys long enough that single index nodes exceed the 4KB node page
+        for i in 0..20 {
+            coll.insert_one(doc(json!({"key": format!("{:02}{}", i, "k".repeat(3000)), "body": body}))).unwrap();
+        }
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let coll = db.collection("docs").unwrap();
+    let key = format!("07{}", "k".repeat(3000));
+    let doc = coll.find_one(&json!({"key": key})).unwrap().unwrap();
+    assert_eq!(doc["body"].as_str().unwrap().len(), 100_000);
+    assert_eq!(coll.count_documents(&json!({"key": {"$gte": "10"}})).unwrap(), 10);
+}
+
+#[test]
+fn test_indexed_commit_writes_multi_page_nodes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let coll = db.collection("docs").unwrap();
+    coll.create_index("key".to_string(), false).unwrap();
+    for i in 0..5 {
+        coll.insert_one(doc(json!({"key": format!("{}{}", i, "k".repeat(3000))}))).unwrap();
+    }
+
+    // Preparing the index file used to fail once a node outgrew its 4KB page
+    let tx = db.begin_transaction();
+    db.insert_one_tx("docs", doc(json!({"key": format!("9{}", "k".repeat(3000))})), tx).unwrap();
+    db.commit_transaction_with_indexes(tx).unwrap();
+
+    assert_eq!(coll.count_documents(&json!({"key": {"$gte": "3"}})).unwrap(), 3);
+    assert!(temp_dir.path().join("test.docs_key.idx").exists());
+}
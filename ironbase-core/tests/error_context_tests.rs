@@ -0,0 +1,51 @@
+// Storage errors say which operation, collection, document and offset failed
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use std::io::{Seek, SeekFrom, Write};
+use tempfile::TempDir;
+
+#[test]
+fn test_corrupted_document_error_has_context() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let users = db.collection("users").unwrap();
+    let id = users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+    users.insert_one(doc(json!({"name": "bob"}))).unwrap();
+    db.flush().unwrap();
+
+    let offset = users.storage.read().get_collection_meta("users").unwrap().document_catalog[&id];
+    // Keep the length prefix, garble the JSON payload
+    let mut file = std::fs::OpenOptions::new().write(true).open(&db_path).unwrap();
+    file.seek(SeekFrom::Start(offset + 4)).unwrap();
+    file.write_all(b"!!!!").unwrap();
+    drop(file);
+
+    let err = users.update_one(&json!({"_id": id}), &json!({"$set": {"age": 1}})).unwrap_err();
+    assert!(matches!(err.root(), MongoLiteError::Deserialization(_)));
+    let context = err.context().unwrap();
+    assert_eq!(context.operation.as_deref(), Some("update_one"));
+    assert_eq!(context.collection.as_deref(), Some("users"));
+    assert_eq!(context.doc_id.as_deref(), Some("1"));
+    assert_eq!(context.offset, Some(offset));
+
+    let message = err.to_string();
+    assert!(message.starts_with("Deserialization error"));
+    assert!(message.contains(&format!("operation update_one, collection 'users', _id 1, offset {}", offset)));
+}
+
+#[test]
+fn test_caller_errors_are_not_wrapped() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    users.insert_one(doc(json!({"email": "a@x"}))).unwrap();
+
+    let err = users.insert_one(doc(json!({"email": "a@x"}))).unwrap_err();
+    assert!(err.context().is_none());
+    assert!(std::ptr::eq(err.root(), &err));
+}
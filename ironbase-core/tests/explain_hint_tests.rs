@@ -206,3 +206,59 @@ fn test_explain_and_hint_consistency() {
     assert_eq!(results_auto.len(), results_hint.len());
     assert_eq!(results_auto.len(), 1);
 }
+
+#[test]
+fn test_explain_aggregate() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let collection = db.collection("users").unwrap();
+    collection.create_index("age".to_string(), false).unwrap();
+    for i in 0..100 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!(i % 50));
+        fields.insert("city".to_string(), json!(if i % 2 == 0 { "NYC" } else { "LA" }));
+        collection.insert_one(fields).unwrap();
+    }
+
+    // Leading $match on an indexed field is pushed down; $limit bounds the index walk
+    let pipeline = json!([
+        {"$match": {"age": {"$gte": 40}}},
+        {"$project": {"_id": 1, "city": 1}},
+        {"$limit": 5}
+    ]);
+    let plan = collection.explain_aggregate(&pipeline).unwrap();
+    assert_eq!(plan["input"], "INDEX");
+    assert_eq!(plan["stages"][0]["stage"], "$match");
+    assert_eq!(plan["stages"][0]["pushedDown"], true);
+    assert_eq!(plan["stages"][0]["inputPlan"]["indexUsed"], "users_age");
+    assert_eq!(plan["limitPushdown"], 5);
+    assert_eq!(plan["stages"][2]["maxDocuments"], 5);
+    assert_eq!(plan["blockingStages"], json!([]));
+    assert_eq!(plan["documentCount"], 100);
+    let average_size = plan["averageDocumentSize"].as_u64().unwrap();
+    assert_eq!(plan["estimatedMemory"].as_u64().unwrap(), 5 * average_size);
+
+    // Running it reads the same documents as the unindexed form
+    let results = collection.aggregate(&pipeline).unwrap();
+    assert_eq!(results.len(), 5);
+    let expected = collection.find(&json!({"age": {"$gte": 40}})).unwrap();
+    assert!(results.iter().all(|doc| expected.iter().any(|e| e["_id"] == doc["_id"])));
+
+    // Unindexed input is scanned; $group and $sort block and hold it all
+    let pipeline = json!([
+        {"$match": {"city": "NYC"}},
+        {"$group": {"_id": "$age", "count": {"$sum": 1}}},
+        {"$sort": {"count": -1}},
+        {"$out": "by_age"}
+    ]);
+    let plan = collection.explain_aggregate(&pipeline).unwrap();
+    assert_eq!(plan["input"], "SNAPSHOT_SCAN");
+    assert_eq!(plan["stages"][0]["usesIndex"], false);
+    assert_eq!(plan["blockingStages"], json!([1, 2]));
+    assert_eq!(plan["limitPushdown"], serde_json::Value::Null);
+    assert_eq!(plan["output"], "$out");
+    assert_eq!(plan["estimatedMemory"].as_u64().unwrap(), 100 * average_size);
+    assert!(collection.explain_aggregate(&json!([{"$bogus": {}}])).is_err());
+}
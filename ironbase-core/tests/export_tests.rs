@@ -0,0 +1,114 @@
+// Arrow IPC / Parquet export (requires the `arrow` feature)
+#![cfg(feature = "arrow")]
+
+use ironbase_core::export::arrow_array::{Array, Float64Array, Int64Array, RecordBatch, StringArray};
+use ironbase_core::export::{arrow_ipc, parquet};
+use ironbase_core::{DatabaseCore, ExportFormat, FindOptions, MongoLiteError};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn populate(db: &DatabaseCore) -> ironbase_core::CollectionCore {
+    let users = db.collection("users").unwrap();
+    for (name, age, city) in [("alice", json!(30), "Budapest"), ("bob", json!(25.5), "Szeged"), ("carol", json!(null), "Budapest")] {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), json!(name));
+        fields.insert("age".to_string(), age);
+        fields.insert("city".to_string(), json!(city));
+        users.insert_one(fields).unwrap();
+    }
+    users
+}
+
+fn read_ipc(path: &std::path::Path) -> RecordBatch {
+    let file = std::fs::File::open(path).unwrap();
+    let mut reader = arrow_ipc::reader::FileReader::try_new(file, None).unwrap();
+    reader.next().unwrap().unwrap()
+}
+
+fn read_parquet(path: &std::path::Path) -> RecordBatch {
+    let file = std::fs::File::open(path).unwrap();
+    let mut reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    reader.next().unwrap().unwrap()
+}
+
+#[test]
+fn test_export_collection_to_ipc_and_parquet() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let ipc_path = temp_dir.path().join("users.arrow");
+    let stats = users.export(&json!({}), &ipc_path, ExportFormat::ArrowIpc).unwrap();
+    assert_eq!(stats.rows, 3);
+    assert_eq!(stats.columns, 4); // _id, age, city, name
+
+    let parquet_path = temp_dir.path().join("users.parquet");
+    users.export(&json!({}), &parquet_path, ExportFormat::Parquet).unwrap();
+
+    for batch in [read_ipc(&ipc_path), read_parquet(&parquet_path)] {
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema().field(0).name(), "_id");
+        assert!(batch.schema().index_of("_collection").is_err());
+
+        // Integers and floats widen to one float column, nulls stay null
+        let age = batch.column_by_name("age").unwrap().as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(age.null_count(), 1);
+        let city = batch.column_by_name("city").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((0..3).filter(|&i| city.value(i) == "Budapest").count(), 2);
+    }
+
+    assert!(!temp_dir.path().join("users.arrow.tmp").exists());
+}
+
+#[test]
+fn test_export_aggregation_results() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let path = temp_dir.path().join("cities.parquet");
+    let pipeline = json!([
+        {"$group": {"_id": "$city", "count": {"$sum": 1}}},
+        {"$sort": {"_id": 1}}
+    ]);
+    let stats = users.export_aggregation(&pipeline, &path, ExportFormat::Parquet).unwrap();
+    assert_eq!(stats.rows, 2);
+
+    let batch = read_parquet(&path);
+    let ids = batch.column_by_name("_id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+    let counts = batch.column_by_name("count").unwrap().as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!((ids.value(0), counts.value(0)), ("Budapest", 2));
+    assert_eq!((ids.value(1), counts.value(1)), ("Szeged", 1));
+
+    // Pipelines writing to a collection have nothing to export
+    let out = json!([{"$match": {}}, {"$out": "copy"}]);
+    let result = users.export_aggregation(&out, temp_dir.path().join("copy.arrow"), ExportFormat::ArrowIpc);
+    assert!(matches!(result, Err(MongoLiteError::ExportError(_))));
+    assert!(!db.list_collections().contains(&"copy".to_string()));
+}
+
+#[test]
+fn test_find_arrow_builds_columns_from_the_results() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let options = FindOptions::new().with_sort(vec![("name".to_string(), 1)]);
+    let batch = users.find_arrow(&json!({"city": "Budapest"}), options).unwrap();
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(batch.schema().field(0).name(), "_id");
+
+    let names = batch.column_by_name("name").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!((names.value(0), names.value(1)), ("alice", "carol"));
+    let ages = batch.column_by_name("age").unwrap().as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(ages.value(0), 30);
+    assert!(ages.is_null(1));
+
+    // No results: an empty batch without columns
+    let batch = users.find_arrow(&json!({"city": "Pécs"}), FindOptions::new()).unwrap();
+    assert_eq!((batch.num_rows(), batch.num_columns()), (0, 0));
+}
@@ -0,0 +1,63 @@
+// Aggregation expressions and $expr query tests
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_find_with_expr_compares_fields() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let budgets = db.collection("budgets").unwrap();
+
+    budgets.insert_one(doc(json!({"dept": "ops", "budget": 100, "spent": 150}))).unwrap();
+    budgets.insert_one(doc(json!({"dept": "dev", "budget": 300, "spent": 120}))).unwrap();
+    budgets.insert_one(doc(json!({"dept": "hr", "budget": 50, "spent": 50}))).unwrap();
+
+    let over = budgets.find(&json!({"$expr": {"$gt": ["$spent", "$budget"]}})).unwrap();
+    assert_eq!(over.len(), 1);
+    assert_eq!(over[0]["dept"], "ops");
+
+    let count = budgets.count_documents(&json!({
+        "$expr": {"$lte": [{"$divide": ["$spent", "$budget"]}, 1]}
+    })).unwrap();
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn test_aggregate_with_computed_fields() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let orders = db.collection("orders").unwrap();
+
+    orders.insert_one(doc(json!({"item": "Pen", "price": 2, "qty": 10, "region": "eu"}))).unwrap();
+    orders.insert_one(doc(json!({"item": "Ink", "price": 15, "qty": 1, "region": "eu"}))).unwrap();
+    orders.insert_one(doc(json!({"item": "Pad", "price": 4, "qty": 5, "region": "us"}))).unwrap();
+
+    let results = orders.aggregate(&json!([
+        {"$addFields": {"total": {"$multiply": ["$price", "$qty"]}}},
+        {"$project": {
+            "_id": 0,
+            "label": {"$concat": [{"$toLower": "$item"}, "-", "$region"]},
+            "total": 1
+        }},
+        {"$sort": {"total": -1}}
+    ])).unwrap();
+
+    // pen and pad tie on total, so only the last position is fixed
+    assert_eq!(results.len(), 3);
+    assert!(results.contains(&json!({"label": "pen-eu", "total": 20})));
+    assert!(results.contains(&json!({"label": "pad-us", "total": 20})));
+    assert_eq!(results[2], json!({"label": "ink-eu", "total": 15}));
+
+    let revenue = orders.aggregate(&json!([
+        {"$group": {"_id": "$region", "revenue": {"$sum": {"$multiply": ["$price", "$qty"]}}}},
+        {"$sort": {"_id": 1}}
+    ])).unwrap();
+    assert_eq!(revenue, vec![
+        json!({"_id": "eu", "revenue": 35}),
+        json!({"_id": "us", "revenue": 20}),
+    ]);
+}
@@ -0,0 +1,101 @@
+// Field-level encryption: ciphertext at rest, plaintext to key holders
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, EncryptionKey, EncryptionMode, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn open_with_key(path: &std::path::Path) -> DatabaseCore {
+    let db = DatabaseCore::open(path).unwrap();
+    db.set_encryption_key(Some(EncryptionKey::new([42; 32])));
+    db
+}
+
+#[test]
+fn test_encrypted_fields_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = open_with_key(&path);
+        let people = db.collection("people").unwrap();
+        people.encrypt_field("ssn", EncryptionMode::Deterministic).unwrap();
+        people.encrypt_field("notes", EncryptionMode::Randomized).unwrap();
+        people.create_index("ssn".to_string(), true).unwrap();
+
+        people.insert_one(doc(json!({"name": "Ann", "ssn": "111", "notes": "likes tea"}))).unwrap();
+        people.insert_many(vec![
+            doc(json!({"name": "Bob", "ssn": "222", "notes": "likes tea"})),
+            doc(json!({"name": "Cy", "ssn": "333"})),
+        ]).unwrap();
+
+        // Equality queries (index included) on deterministic fields
+        let ann = people.find_one(&json!({"ssn": "111"})).unwrap().unwrap();
+        assert_eq!((ann["name"].clone(), ann["notes"].clone()), (json!("Ann"), json!("likes tea")));
+        assert_eq!(people.find(&json!({"ssn": {"$in": ["222", "333"]}})).unwrap().len(), 2);
+        assert_eq!(people.count_documents(&json!({"notes": {"$exists": true}})).unwrap(), 2);
+        assert!(matches!(people.find(&json!({"notes": "likes tea"})), Err(MongoLiteError::InvalidQuery(_))));
+        assert!(people.find(&json!({"ssn": {"$gt": "1"}})).is_err());
+        assert_eq!(people.distinct("notes", &json!({})).unwrap(), vec![json!("likes tea")]);
+
+        // Updates encrypt $set values; arithmetic on encrypted fields is refused
+        people.update_one(&json!({"ssn": "333"}), &json!({"$set": {"ssn": "444"}})).unwrap();
+        assert_eq!(people.find_one(&json!({"ssn": "444"})).unwrap().unwrap()["name"], json!("Cy"));
+        assert!(people.update_many(&json!({}), &json!({"$inc": {"ssn": 1}})).is_err());
+        assert!(people.create_index("notes".to_string(), false).is_err());
+
+        // A plaintext made to look like a ciphertext is not stored as one
+        assert!(matches!(
+            people.insert_one(doc(json!({"name": "Mal", "ssn": "$enc:d:00"}))),
+            Err(MongoLiteError::EncryptionError(_))
+        ));
+        assert!(people.update_one(&json!({"ssn": "111"}), &json!({"$set": {"notes": "$enc:r:plain"}})).is_err());
+
+        let grouped = people.aggregate(&json!([{"$match": {"notes": "likes tea"}}, {"$group": {"_id": null, "n": {"$sum": 1}}}])).unwrap();
+        assert_eq!(grouped, vec![json!({"_id": null, "n": 2})]);
+        db.flush().unwrap();
+    }
+
+    // Nothing readable lands in the file
+    let raw = std::fs::read(&path).unwrap();
+    let contains = |needle: &str| raw.windows(needle.len()).any(|window| window == needle.as_bytes());
+    assert!(contains("Ann") && !contains("likes tea") && !contains("\"111\""));
+
+    // Without the key: ciphertexts out, no writes to encrypted fields
+    let db = DatabaseCore::open(&path).unwrap();
+    let people = db.collection("people").unwrap();
+    assert_eq!(people.encrypted_fields()["ssn"], EncryptionMode::Deterministic);
+    let ann = people.find_one(&json!({"name": "Ann"})).unwrap().unwrap();
+    assert!(ann["ssn"].as_str().unwrap().starts_with("$enc:"));
+    assert!(matches!(
+        people.insert_one(doc(json!({"name": "Dee", "ssn": "555"}))),
+        Err(MongoLiteError::EncryptionError(_))
+    ));
+    people.insert_one(doc(json!({"name": "Eve"}))).unwrap();
+    // Stored ciphertexts are written back as they are
+    people.update_one(&json!({"name": "Ann"}), &json!({"$set": {"age": 40}})).unwrap();
+
+    // A wrong key fails loudly instead of returning garbage
+    db.set_encryption_key(Some(EncryptionKey::new([1; 32])));
+    assert!(matches!(people.find(&json!({"name": "Ann"})), Err(MongoLiteError::EncryptionError(_))));
+}
+
+#[test]
+fn test_encrypt_field_preconditions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_key(&temp_dir.path().join("test.mlite"));
+    let people = db.collection("people").unwrap();
+    people.insert_one(doc(json!({"email": "a@example.com"}))).unwrap();
+    people.create_index("phone".to_string(), false).unwrap();
+
+    // Existing plaintext, indexed randomized fields and nested paths are refused
+    assert!(people.encrypt_field("email", EncryptionMode::Deterministic).is_err());
+    assert!(people.encrypt_field("phone", EncryptionMode::Randomized).is_err());
+    assert!(people.encrypt_field("address.zip", EncryptionMode::Deterministic).is_err());
+    assert!(people.encrypt_field("_id", EncryptionMode::Deterministic).is_err());
+
+    people.encrypt_field("phone", EncryptionMode::Deterministic).unwrap();
+    people.encrypt_field("phone", EncryptionMode::Deterministic).unwrap();
+    assert!(people.encrypt_field("phone", EncryptionMode::Randomized).is_err());
+    assert_eq!(people.encrypted_fields().len(), 1);
+}
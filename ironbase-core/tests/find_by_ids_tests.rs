@@ -0,0 +1,56 @@
+// Batch fetch by _id list tests
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, DocumentId};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_find_by_ids_preserves_input_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    let ids: Vec<DocumentId> = (0..5)
+        .map(|i| users.insert_one(doc(json!({"n": i}))).unwrap())
+        .collect();
+
+    let wanted = vec![ids[3].clone(), ids[0].clone(), ids[4].clone()];
+    let results = users.find_by_ids(&wanted).unwrap();
+
+    let ns: Vec<i64> = results.iter().map(|d| d.as_ref().unwrap()["n"].as_i64().unwrap()).collect();
+    assert_eq!(ns, vec![3, 0, 4]);
+}
+
+#[test]
+fn test_find_by_ids_gaps_for_missing_and_deleted() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    let a = users.insert_one(doc(json!({"name": "a"}))).unwrap();
+    let b = users.insert_one(doc(json!({"name": "b"}))).unwrap();
+    users.delete_one(&json!({"name": "b"})).unwrap();
+
+    let results = users.find_by_ids(&[b, DocumentId::Int(999), a.clone(), a]).unwrap();
+    assert_eq!(results.len(), 4);
+    assert!(results[0].is_none());
+    assert!(results[1].is_none());
+    assert_eq!(results[2].as_ref().unwrap()["name"], "a");
+    assert_eq!(results[3].as_ref().unwrap()["name"], "a");
+}
+
+#[test]
+fn test_find_by_ids_returns_latest_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    let id = users.insert_one(doc(json!({"name": "a", "v": 1}))).unwrap();
+    users.update_one(&json!({"name": "a"}), &json!({"$set": {"v": 2}})).unwrap();
+
+    let results = users.find_by_ids(&[id]).unwrap();
+    assert_eq!(results[0].as_ref().unwrap()["v"], 2);
+    assert!(users.find_by_ids(&[]).unwrap().is_empty());
+}
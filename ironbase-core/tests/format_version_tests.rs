@@ -0,0 +1,190 @@
+// File format versioning: page size configuration, migrations, future versions
+use ironbase_core::{DatabaseCore, DocumentId, MongoLiteError, StorageConfig, StorageEngine, FORMAT_VERSION};
+use ironbase_core::storage::DATA_START_OFFSET;
+use ironbase_core::index::{IndexFileStamp, INDEX_FILE_VERSION};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom, Write};
+use tempfile::TempDir;
+
+/// Overwrite the header's version field (after the 8-byte magic)
+fn set_format_version(path: &std::path::Path, version: u32) {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(8)).unwrap();
+    file.write_all(&version.to_le_bytes()).unwrap();
+    file.sync_all().unwrap();
+}
+
+#[test]
+fn test_page_size_fixed_at_creation() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let config = StorageConfig::default().with_page_size(16384);
+    {
+        let db = DatabaseCore::open_with_config(&db_path, &config).unwrap();
+        let users = db.collection("users").unwrap();
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), json!("alice"));
+        users.insert_one(fields).unwrap();
+        assert_eq!(db.stats()["page_size"], 16384);
+    }
+
+    // An existing file keeps the page size it was created with
+    let db = DatabaseCore::open_with_config(&db_path, &StorageConfig::default()).unwrap();
+    assert_eq!(db.stats()["page_size"], 16384);
+    assert_eq!(db.collection("users").unwrap().stats().unwrap()["document_count"], 1);
+
+    let invalid = StorageConfig::default().with_page_size(3000);
+    let result = DatabaseCore::open_with_config(temp_dir.path().join("other.mlite"), &invalid);
+    assert!(matches!(result, Err(MongoLiteError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_older_version_is_migrated() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        for i in 0..3 {
+            let mut fields = HashMap::new();
+            fields.insert("seq".to_string(), json!(i));
+            users.insert_one(fields).unwrap();
+        }
+        users.delete_one(&json!({"seq": 0})).unwrap();
+    }
+    set_format_version(&db_path, 1);
+
+    let storage = StorageEngine::open(&db_path).unwrap();
+    assert_eq!(storage.format_version(), FORMAT_VERSION);
+
+    let garbage = storage.garbage_stats("users").unwrap();
+    assert_eq!(garbage.live_records, 2);
+    assert_eq!(garbage.tombstones, 1);
+    drop(storage);
+
+    // The upgrade is persisted
+    let storage = StorageEngine::open(&db_path).unwrap();
+    assert_eq!(storage.format_version(), FORMAT_VERSION);
+}
+
+#[test]
+fn test_inline_catalog_moves_to_shards() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    // A version 2 file: one collection, its catalog inline in the metadata
+    let meta = json!({
+        "name": "users", "document_count": 1, "data_offset": DATA_START_OFFSET,
+        "index_offset": DATA_START_OFFSET, "last_id": 1,
+        "document_catalog": [["i", "1", DATA_START_OFFSET]]
+    });
+    let doc = json!({"_id": 1, "_collection": "users", "name": "alice"});
+    let mut bytes = b"MONGOLTE".to_vec();
+    for field in [2u32, 4096, 1] {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0u8; 16]);
+    for record in [&meta, &doc] {
+        if record == &doc {
+            bytes.resize(DATA_START_OFFSET as usize, 0);
+        }
+        let payload = serde_json::to_vec(record).unwrap();
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+    }
+    std::fs::write(&db_path, bytes).unwrap();
+
+    {
+        let storage = StorageEngine::open(&db_path).unwrap();
+        assert_eq!(storage.format_version(), FORMAT_VERSION);
+        let catalog = &storage.get_collection_meta("users").unwrap().document_catalog;
+        assert_eq!(catalog[&DocumentId::Int(1)], DATA_START_OFFSET);
+        assert!(catalog.shard_records()[0].0 >= DATA_START_OFFSET);
+    }
+
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let alice = db.collection("users").unwrap().find_one(&json!({"_id": 1})).unwrap().unwrap();
+    assert_eq!(alice["name"], "alice");
+}
+
+#[test]
+fn test_newer_version_is_refused_untouched() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        db.collection("users").unwrap();
+    }
+    set_format_version(&db_path, FORMAT_VERSION + 1);
+    let before = std::fs::read(&db_path).unwrap();
+
+    let result = DatabaseCore::open(&db_path);
+    assert!(matches!(
+        result,
+        Err(MongoLiteError::UnsupportedVersion { found, supported }) if found == FORMAT_VERSION + 1 && supported == FORMAT_VERSION
+    ));
+    assert_eq!(std::fs::read(&db_path).unwrap(), before);
+}
+
+#[test]
+fn test_stale_index_files_are_rewritten_on_open() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let idx_path = temp_dir.path().join("test.users_age.idx");
+    let stamp = || IndexFileStamp::parse(&std::fs::read(&idx_path).unwrap());
+
+    let write_ages = |db: &DatabaseCore, ages: &[i64]| {
+        let tx = db.begin_transaction();
+        for age in ages {
+            let mut fields = HashMap::new();
+            fields.insert("age".to_string(), json!(age));
+            db.insert_one_tx("users", fields, tx).unwrap();
+        }
+        db.commit_transaction_with_indexes(tx).unwrap();
+    };
+
+    let first = {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        db.collection("users").unwrap().create_index("age".to_string(), false).unwrap();
+        write_ages(&db, &[30, 40]);
+        stamp().unwrap()
+    };
+    assert_eq!(first.version, INDEX_FILE_VERSION);
+    assert!(!first.uuid.is_empty());
+
+    // A file from before stamps, then one from a future format
+    for stale in [vec![1u8; 64], {
+        let mut data = std::fs::read(&idx_path).unwrap();
+        let future = serde_json::to_vec(&json!({"version": INDEX_FILE_VERSION + 1, "uuid": first.uuid})).unwrap();
+        data[1..5].copy_from_slice(&(future.len() as u32).to_le_bytes());
+        data[5..5 + future.len()].copy_from_slice(&future);
+        data
+    }] {
+        std::fs::write(&idx_path, stale).unwrap();
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        assert_eq!(stamp().map(|stamp| (stamp.version, stamp.uuid)), Some((first.version, first.uuid.clone())));
+        assert_eq!(users.find(&json!({"age": 40})).unwrap().len(), 1);
+    }
+
+    // Dropping the index leaves its file; a new index of the same name replaces it
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let users = db.collection("users").unwrap();
+    users.drop_index("users_age").unwrap();
+    assert_eq!(stamp().map(|stamp| stamp.uuid), Some(first.uuid.clone()));
+    users.create_index("age".to_string(), false).unwrap();
+    let second = stamp().unwrap();
+    assert_ne!(second.uuid, first.uuid);
+
+    // A current file is left alone
+    let modified = std::fs::metadata(&idx_path).unwrap().modified().unwrap();
+    drop(users);
+    drop(db);
+    let db = DatabaseCore::open(&db_path).unwrap();
+    db.collection("users").unwrap();
+    assert_eq!(std::fs::metadata(&idx_path).unwrap().modified().unwrap(), modified);
+}
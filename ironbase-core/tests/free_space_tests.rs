@@ -0,0 +1,52 @@
+// Free-space reuse: dead record regions are recycled between compactions
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_updates_reuse_released_regions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+
+        for i in 0..20 {
+            users.insert_one(doc(json!({"seq": i, "payload": "x".repeat(200)}))).unwrap();
+        }
+
+        // First round appends; the old versions are freed once the flush persists the catalog
+        users.update_many(&json!({}), &json!({"$set": {"payload": "y".repeat(200)}})).unwrap();
+        db.flush().unwrap();
+        assert!(db.stats()["free_regions"].as_u64().unwrap() > 0);
+
+        // Second round fits into the freed regions - the file does not grow
+        let size_before = db.stats()["file_size"].as_u64().unwrap();
+        users.update_many(&json!({}), &json!({"$set": {"payload": "z".repeat(150)}})).unwrap();
+        assert_eq!(db.stats()["file_size"].as_u64().unwrap(), size_before);
+
+        assert_eq!(users.find(&json!({"payload": "z".repeat(150)})).unwrap().len(), 20);
+        assert!(users.validate(true).unwrap().valid);
+    }
+
+    // The free list and the reused regions survive reopening
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert!(db.stats()["free_regions"].as_u64().unwrap() > 0);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 20);
+    assert_eq!(users.find(&json!({"seq": 7})).unwrap()[0]["payload"], "z".repeat(150));
+
+    // Scans that keep the last record per _id still see the newest versions
+    assert_eq!(users.distinct("payload", &json!({})).unwrap(), vec![json!("z".repeat(150))]);
+    users.delete_many(&json!({"seq": {"$lt": 5}})).unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 15);
+
+    // Compaction leaves a dense file with nothing to reuse
+    db.compact().unwrap();
+    assert_eq!(db.stats()["free_regions"], 0);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 15);
+}
@@ -0,0 +1,90 @@
+// Per-collection write hooks: mutate, veto and audit
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, HookEvent, MongoLiteError, ReturnDocument};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+#[test]
+fn test_before_hooks_mutate_and_veto() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+
+    users.add_hook(HookEvent::BeforeInsert, |doc| {
+        let email = doc.get("email").and_then(Value::as_str)
+            .ok_or_else(|| MongoLiteError::HookRejected("email is required".to_string()))?;
+        doc["email"] = json!(email.to_lowercase());
+        Ok(())
+    });
+    users.add_hook(HookEvent::BeforeUpdate, |doc| {
+        doc["version"] = json!(doc.get("version").and_then(Value::as_i64).unwrap_or(0) + 1);
+        Ok(())
+    });
+    users.add_hook(HookEvent::BeforeDelete, |doc| match doc.get("admin") {
+        Some(Value::Bool(true)) => Err(MongoLiteError::HookRejected("admins cannot be deleted".to_string())),
+        _ => Ok(()),
+    });
+
+    // Mutations are stored - and indexed
+    users.insert_one(doc(json!({"email": "Ann@Example.com", "admin": true}))).unwrap();
+    users.insert_many(vec![doc(json!({"email": "BOB@example.com"}))]).unwrap();
+    assert_eq!(users.find(&json!({"email": "ann@example.com"})).unwrap().len(), 1);
+    assert!(matches!(
+        users.insert_many(vec![doc(json!({"email": "c@example.com"})), doc(json!({"name": "no email"}))]),
+        Err(MongoLiteError::HookRejected(_))
+    ));
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 2);
+
+    users.update_one(&json!({"email": "bob@example.com"}), &json!({"$set": {"age": 30}})).unwrap();
+    users.update_many(&json!({}), &json!({"$set": {"active": true}})).unwrap();
+    let replaced = users.find_one_and_replace(
+        &json!({"email": "ann@example.com"}), &json!({"email": "ann@example.com", "admin": true}), ReturnDocument::After,
+    ).unwrap().unwrap();
+    assert_eq!(replaced["version"], json!(1));
+    let bob = users.find_one(&json!({"email": "bob@example.com"})).unwrap().unwrap();
+    assert_eq!((bob["age"].clone(), bob["version"].clone()), (json!(30), json!(2)));
+
+    // Vetoed deletes leave the document in place
+    assert!(matches!(users.delete_one(&json!({"admin": true})), Err(MongoLiteError::HookRejected(_))));
+    assert!(users.delete_many(&json!({"admin": true})).is_err());
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 2);
+
+    // Hooks belong to the database - new handles run them, clear_hooks() removes them
+    let again = db.collection("users").unwrap();
+    assert!(again.insert_one(doc(json!({"name": "no email"}))).is_err());
+    again.clear_hooks();
+    assert_eq!(users.delete_many(&json!({})).unwrap().deleted_count, 2);
+}
+
+#[test]
+fn test_after_hooks_see_stored_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let orders = db.collection("orders").unwrap();
+
+    let audit = Arc::new(Mutex::new(Vec::new()));
+    for (event, name) in [(HookEvent::AfterInsert, "insert"), (HookEvent::AfterUpdate, "update"), (HookEvent::AfterDelete, "delete")] {
+        let audit = Arc::clone(&audit);
+        orders.add_hook(event, move |doc| {
+            audit.lock().push((name, doc["_id"].clone(), doc.get("qty").cloned()));
+            Ok(())
+        });
+    }
+
+    let id = orders.insert_one(doc(json!({"qty": 1}))).unwrap();
+    orders.increment(&json!({"_id": id}), "qty", &json!(2)).unwrap();
+    orders.delete_one(&json!({"_id": id})).unwrap();
+    orders.update_one(&json!({"_id": id}), &json!({"$set": {"qty": 9}})).unwrap();
+
+    let id = json!(id);
+    assert_eq!(*audit.lock(), vec![
+        ("insert", id.clone(), Some(json!(1))),
+        ("update", id.clone(), Some(json!(3))),
+        ("delete", id, Some(json!(3))),
+    ]);
+}
@@ -0,0 +1,50 @@
+// Writes selecting a document by _id look it up in the catalog instead of scanning
+use ironbase_core::{DatabaseCore, MongoLiteError, ReturnDocument};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn exceeded<T: std::fmt::Debug>(result: ironbase_core::Result<T>) -> bool {
+    matches!(result.as_ref().err().map(|e| e.root()), Some(MongoLiteError::QueryExceededMemoryLimit { .. }))
+}
+
+#[test]
+fn test_id_equality_writes_do_not_scan() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.insert_many((0..50).map(|i| {
+        let mut fields = HashMap::new();
+        fields.insert("_id".to_string(), json!(i));
+        fields.insert("bio".to_string(), json!("x".repeat(1000)));
+        fields
+    }).collect()).unwrap();
+
+    // Room for a few documents, not for a scan of the collection
+    db.set_query_memory_limit(Some(10_000));
+    assert!(exceeded(users.update_one(&json!({"bio": "y"}), &json!({"$set": {"n": 1}}))));
+    assert!(exceeded(users.delete_many(&json!({"bio": "y"}))));
+
+    assert_eq!(users.update_one(&json!({"_id": 1}), &json!({"$set": {"n": 1}})).unwrap().modified_count, 1);
+    assert_eq!(users.update_many(&json!({"_id": {"$eq": 2}}), &json!({"$set": {"n": 2}})).unwrap().modified_count, 1);
+    assert_eq!(users.increment(&json!({"_id": 1}), "n", &json!(10)).unwrap(), Some(json!(11)));
+    let replaced = users.find_one_and_replace(&json!({"_id": 3}), &json!({"n": 3}), ReturnDocument::After).unwrap();
+    assert_eq!(replaced.unwrap()["n"], 3);
+
+    // Conditions next to the _id still have to match
+    assert_eq!(users.update_one(&json!({"_id": 4, "n": 5}), &json!({"$set": {"n": 4}})).unwrap().matched_count, 0);
+    assert_eq!(users.delete_one(&json!({"_id": 2, "n": 2})).unwrap().deleted_count, 1);
+    assert_eq!(users.delete_many(&json!({"_id": 2})).unwrap().deleted_count, 0);
+    assert_eq!(users.find_one(&json!({"_id": 3})).unwrap().unwrap()["n"], 3);
+    assert!(users.find_one(&json!({"_id": 999})).unwrap().is_none());
+
+    let tx = db.begin();
+    tx.update_one("users", &json!({"_id": 5}), json!({"n": 5})).unwrap();
+    tx.delete_one("users", &json!({"_id": {"$eq": 6}})).unwrap();
+    tx.commit().unwrap();
+
+    db.set_query_memory_limit(None);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 48);
+    assert_eq!(users.find_one(&json!({"_id": 5})).unwrap().unwrap()["n"], 5);
+    assert_eq!(users.count_documents(&json!({"n": {"$exists": true}})).unwrap(), 3);
+}
@@ -0,0 +1,196 @@
+// Import from mongodump / mongoexport directories and SQLite databases
+use ironbase_core::import::from_extended_json;
+use ironbase_core::{DatabaseCore, ImportOptions};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_extended_json_type_mapping() {
+    let canonical = json!({
+        "_id": {"$oid": "5f1d7a3b9c1e4a2b3c4d5e6f"},
+        "count": {"$numberInt": "7"},
+        "big": {"$numberLong": "9007199254740993"},
+        "ratio": {"$numberDouble": "0.5"},
+        "nan": {"$numberDouble": "NaN"},
+        "price": {"$numberDecimal": "19.99"},
+        "created": {"$date": {"$numberLong": "1600000000000"}},
+        "updated": {"$date": "2020-09-13T12:26:40+02:00"},
+        "blob": {"$binary": {"base64": "AQID", "subType": "00"}},
+        "pattern": {"$regularExpression": {"pattern": "^a", "options": "i"}},
+        "ts": {"$timestamp": {"t": 10, "i": 2}},
+        "low": {"$minKey": 1},
+        "nested": {"tags": [{"$numberInt": "1"}, "x"], "plain": {"a": 1}},
+    });
+
+    assert_eq!(from_extended_json(canonical), json!({
+        "_id": "5f1d7a3b9c1e4a2b3c4d5e6f",
+        "count": 7,
+        "big": 9007199254740993i64,
+        "ratio": 0.5,
+        "nan": null,
+        "price": 19.99,
+        "created": "2020-09-13T12:26:40.000Z",
+        "updated": "2020-09-13T10:26:40.000Z",
+        "blob": "AQID",
+        "pattern": "/^a/i",
+        "ts": {"t": 10, "i": 2},
+        "low": null,
+        "nested": {"tags": [1, "x"], "plain": {"a": 1}},
+    }));
+}
+
+#[test]
+fn test_import_mongoexport_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let dump = temp_dir.path().join("dump");
+    std::fs::create_dir(&dump).unwrap();
+    std::fs::write(
+        dump.join("users.json"),
+        "{\"_id\":{\"$oid\":\"aaaaaaaaaaaaaaaaaaaaaaaa\"},\"email\":\"ann@x\",\"age\":{\"$numberInt\":\"31\"}}\n\
+         {\"_id\":{\"$oid\":\"bbbbbbbbbbbbbbbbbbbbbbbb\"},\"email\":\"bob@x\",\"age\":{\"$numberInt\":\"25\"}}\n",
+    ).unwrap();
+    std::fs::write(
+        dump.join("users.metadata.json"),
+        json!({"indexes": [
+            {"v": {"$numberInt": "2"}, "key": {"_id": {"$numberInt": "1"}}, "name": "_id_"},
+            {"v": {"$numberInt": "2"}, "key": {"email": {"$numberInt": "1"}}, "name": "email_1", "unique": true},
+            {"v": {"$numberInt": "2"}, "key": {"age": 1, "email": 1}, "name": "age_1_email_1"},
+        ]}).to_string(),
+    ).unwrap();
+    // mongoexport --jsonArray
+    std::fs::write(dump.join("tags.json"), "[{\"name\": \"a\"}, {\"name\": \"b\"}, {\"name\": \"c\"}]").unwrap();
+    std::fs::write(dump.join("notes.txt"), "not a dump file").unwrap();
+
+    let db = DatabaseCore::open(temp_dir.path().join("imported.mlite")).unwrap();
+    let stats = db.import_mongodump(&dump, &ImportOptions::default()).unwrap();
+    assert_eq!(stats.collections.get("users"), Some(&2));
+    assert_eq!(stats.collections.get("tags"), Some(&3));
+    assert_eq!(stats.documents(), 5);
+    // _id and email: the compound index has no equivalent
+    assert_eq!(stats.indexes, 2);
+
+    let users = db.collection("users").unwrap();
+    let ann = users.find_one(&json!({"email": "ann@x"})).unwrap().unwrap();
+    assert_eq!(ann["source_id"], "aaaaaaaaaaaaaaaaaaaaaaaa");
+    assert_eq!(ann["age"], 31);
+    assert!(users.list_indexes().contains(&"users_email".to_string()));
+    assert!(users.list_indexes().contains(&"users_source_id".to_string()));
+    assert!(users.insert_one(serde_json::from_value(json!({"email": "ann@x"})).unwrap()).is_err());
+
+    // Importing again keeps the existing indexes - and fails on the unique email
+    assert!(db.import_mongodump(&dump, &ImportOptions::default()).is_err());
+}
+
+#[test]
+fn test_import_rejects_compressed_and_malformed_dumps() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("imported.mlite")).unwrap();
+
+    let gzipped = temp_dir.path().join("gzipped");
+    std::fs::create_dir(&gzipped).unwrap();
+    std::fs::write(gzipped.join("users.bson.gz"), [0u8; 8]).unwrap();
+    assert!(db.import_mongodump(&gzipped, &ImportOptions::default()).is_err());
+
+    let malformed = temp_dir.path().join("malformed");
+    std::fs::create_dir(&malformed).unwrap();
+    std::fs::write(malformed.join("users.json"), "{\"a\": 1}\n42\n").unwrap();
+    assert!(db.import_mongodump(&malformed, &ImportOptions::default()).is_err());
+}
+
+#[cfg(feature = "bson")]
+#[test]
+fn test_import_mongodump_bson() {
+    use ironbase_core::import::bson::{doc, oid::ObjectId, Binary, DateTime, Decimal128};
+
+    let temp_dir = TempDir::new().unwrap();
+    let dump = temp_dir.path().join("dump");
+    std::fs::create_dir(&dump).unwrap();
+
+    let id = ObjectId::new();
+    let mut bytes = Vec::new();
+    for i in 0..2500i64 {
+        let document = doc! {
+            "_id": if i == 0 { id } else { ObjectId::new() },
+            "n": i,
+            "small": i as i32,
+            "at": DateTime::from_millis(1_600_000_000_000 + i),
+            "raw": Binary { subtype: ironbase_core::import::bson::spec::BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+            "price": "12.50".parse::<Decimal128>().unwrap(),
+            "inner": {"flag": true, "list": [1.5, "x"]},
+        };
+        document.to_writer(&mut bytes).unwrap();
+    }
+    std::fs::write(dump.join("events.bson"), &bytes).unwrap();
+
+    let db = DatabaseCore::open(temp_dir.path().join("imported.mlite")).unwrap();
+    let stats = db.import_mongodump(&dump, &ImportOptions::default()).unwrap();
+    assert_eq!(stats.documents(), 2500);
+
+    let events = db.collection("events").unwrap();
+    let first = events.find_one(&json!({"n": 0})).unwrap().unwrap();
+    assert_eq!(first["source_id"], id.to_hex());
+    assert_eq!(first["small"], 0);
+    assert_eq!(first["at"], "2020-09-13T12:26:40.000Z");
+    assert_eq!(first["raw"], "AQID");
+    assert_eq!(first["price"], 12.5);
+    assert_eq!(first["inner"], json!({"flag": true, "list": [1.5, "x"]}));
+
+    // A truncated last document is reported, not skipped
+    std::fs::write(dump.join("events.bson"), &bytes[..bytes.len() - 3]).unwrap();
+    let other = DatabaseCore::open(temp_dir.path().join("other.mlite")).unwrap();
+    assert!(other.import_mongodump(&dump, &ImportOptions::default()).is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_import_sqlite() {
+    use ironbase_core::import::rusqlite::Connection;
+
+    let temp_dir = TempDir::new().unwrap();
+    let sqlite_path = temp_dir.path().join("source.sqlite");
+    let connection = Connection::open(&sqlite_path).unwrap();
+    connection.execute_batch("
+        CREATE TABLE customers (
+            customer_id INTEGER PRIMARY KEY,
+            email TEXT NOT NULL UNIQUE,
+            name TEXT,
+            balance REAL,
+            active BOOLEAN,
+            prefs JSON,
+            avatar BLOB
+        );
+        CREATE INDEX customers_name ON customers(name);
+        CREATE INDEX customers_name_balance ON customers(name, balance);
+        INSERT INTO customers VALUES (1, 'ann@x', 'Ann', 10.5, 1, '{\"theme\": \"dark\"}', x'010203');
+        INSERT INTO customers VALUES (2, 'bob@x', NULL, 0, 0, 'not json', NULL);
+        CREATE TABLE \"order items\" (sku TEXT, qty INTEGER);
+        INSERT INTO \"order items\" VALUES ('A-1', 3);
+    ").unwrap();
+    drop(connection);
+
+    let db = DatabaseCore::open(temp_dir.path().join("imported.mlite")).unwrap();
+    let stats = db.import_sqlite(&sqlite_path, &ImportOptions::default()).unwrap();
+    assert_eq!(stats.collections.get("customers"), Some(&2));
+    assert_eq!(stats.collections.get("order items"), Some(&1));
+    // customer_id (primary key), email (UNIQUE) and name; not the two-column index
+    assert_eq!(stats.indexes, 3);
+
+    let customers = db.collection("customers").unwrap();
+    let ann = customers.find_one(&json!({"customer_id": 1})).unwrap().unwrap();
+    assert_eq!(ann["email"], "ann@x");
+    assert_eq!(ann["balance"], 10.5);
+    assert_eq!(ann["active"], true);
+    assert_eq!(ann["prefs"], json!({"theme": "dark"}));
+    assert_eq!(ann["avatar"], "AQID");
+
+    let bob = customers.find_one(&json!({"email": "bob@x"})).unwrap().unwrap();
+    assert_eq!(bob["name"], serde_json::Value::Null);
+    assert_eq!(bob["active"], false);
+    assert_eq!(bob["prefs"], "not json");
+
+    let indexes = customers.list_indexes();
+    assert!(indexes.contains(&"customers_customer_id".to_string()));
+    assert!(indexes.contains(&"customers_email".to_string()));
+    assert!(indexes.contains(&"customers_name".to_string()));
+    assert!(customers.insert_one(serde_json::from_value(json!({"email": "ann@x"})).unwrap()).is_err());
+}
@@ -0,0 +1,83 @@
+// In-place updates: new versions that fit overwrite their record (StorageConfig::in_place_updates)
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, StorageConfig};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn in_place() -> StorageConfig {
+    StorageConfig::default().with_in_place_updates(true)
+}
+
+fn dead_records(db: &DatabaseCore, name: &str) -> u64 {
+    db.collection(name).unwrap().stats().unwrap()["dead_records"].as_u64().unwrap()
+}
+
+#[test]
+fn test_counter_updates_leave_no_dead_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open_with_config(temp_dir.path().join("test.mlite"), &in_place()).unwrap();
+    let counters = db.collection("counters").unwrap();
+    counters.insert_one(doc(json!({"name": "hits", "count": 0}))).unwrap();
+    counters.insert_one(doc(json!({"name": "misses", "count": 0}))).unwrap();
+
+    for _ in 0..200 {
+        counters.increment(&json!({"name": "hits"}), "count", &json!(1)).unwrap();
+        counters.update_one(&json!({"name": "misses"}), &json!({"$inc": {"count": 1}})).unwrap();
+    }
+    // Only the versions that outgrew their record moved: 9 -> 10 and 99 -> 100 at most
+    assert!(dead_records(&db, "counters") <= 4, "{}", dead_records(&db, "counters"));
+    assert_eq!(counters.find_one(&json!({"name": "hits"})).unwrap().unwrap()["count"], 200);
+
+    // A version that does not fit is written elsewhere
+    let before = dead_records(&db, "counters");
+    counters.update_one(&json!({"name": "misses"}), &json!({"$set": {"note": "x".repeat(100)}})).unwrap();
+    assert_eq!(dead_records(&db, "counters"), before + 1);
+
+    // An open snapshot keeps the old version, so the record is not overwritten
+    let snapshot = db.snapshot();
+    counters.increment(&json!({"name": "hits"}), "count", &json!(1)).unwrap();
+    assert_eq!(dead_records(&db, "counters"), before + 2);
+    assert_eq!(counters.find_at(&json!({"name": "hits"}), &snapshot).unwrap()[0]["count"], 200);
+    drop(snapshot);
+
+    // Transactions overwrite in place too
+    let tx = db.begin();
+    tx.update_one("counters", &json!({"name": "hits"}), json!({"name": "hits", "count": 300})).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(dead_records(&db, "counters"), before + 2);
+
+    db.flush().unwrap();
+    drop((counters, db));
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let counters = db.collection("counters").unwrap();
+    assert_eq!(counters.find_one(&json!({"name": "hits"})).unwrap().unwrap()["count"], 300);
+    assert_eq!(counters.find_one(&json!({"name": "misses"})).unwrap().unwrap()["count"], 200);
+    assert_eq!(counters.count_documents(&json!({"count": {"$gte": 200}})).unwrap(), 2);
+}
+
+#[test]
+fn test_torn_overwrite_is_redone_from_the_journal() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open_with_config(&path, &in_place()).unwrap();
+        let counters = db.collection("counters").unwrap();
+        counters.insert_one(doc(json!({"name": "hits", "count": 1000, "tag": "aaaa"}))).unwrap();
+        db.flush().unwrap();
+        counters.update_one(&json!({"name": "hits"}), &json!({"$set": {"count": 2000, "tag": "bbbb"}})).unwrap();
+        assert_eq!(dead_records(&db, "counters"), 0);
+        std::mem::forget((counters, db));
+    }
+
+    // Tear the overwritten record: only its first bytes reached the disk
+    let mut data = std::fs::read(&path).unwrap();
+    let at = data.windows(6).position(|window| window == b"\"bbbb\"").unwrap();
+    data[at..at + 6].copy_from_slice(b"\"aa\x00\x00\x00");
+    std::fs::write(&path, &data).unwrap();
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let hits = db.collection("counters").unwrap().find_one(&json!({"name": "hits"})).unwrap().unwrap();
+    assert_eq!((hits["count"].clone(), hits["tag"].clone()), (json!(2000), json!("bbbb")));
+}
@@ -0,0 +1,93 @@
+// A transaction's index changes are applied and logged as one batch per index
+mod common;
+
+use common::doc;
+use ironbase_core::transaction::{IndexKey, Operation};
+use ironbase_core::wal::{WALEntry, WALEntryType};
+use ironbase_core::document::DocumentId;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_transaction_logs_one_index_entry_per_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    users.create_index("age".to_string(), false).unwrap();
+    users.insert_one(doc(json!({"email": "a@x", "age": 30}))).unwrap();
+    users.insert_one(doc(json!({"email": "b@x", "age": 40}))).unwrap();
+
+    let id = db.begin_transaction();
+    for i in 0..3 {
+        db.insert_one_tx("users", doc(json!({"email": format!("new{}@x", i), "age": 20 + i})), id).unwrap();
+    }
+    // The unique email moves between documents: applied one change at a
+    // time, b@x would briefly be held twice
+    db.update_one_tx("users", &json!({"email": "a@x"}), json!({"email": "b@x", "age": 30}), id).unwrap();
+    db.update_one_tx("users", &json!({"age": 40}), json!({"email": "a@x", "age": 40}), id).unwrap();
+    db.commit_transaction_with_indexes(id).unwrap();
+
+    let report = DatabaseCore::inspect(&path).unwrap();
+    let batches: Vec<_> = report.wal.entries.iter()
+        .filter(|entry| entry.transaction == id && entry.kind == "IndexChange")
+        .map(|entry| entry.data.clone().unwrap())
+        .collect();
+    // users_id, users_email and users_age
+    assert_eq!(batches.len(), 3);
+    for batch in &batches {
+        assert_eq!(batch["collection"], "users");
+        assert_eq!(batch["changes"].as_array().unwrap().len(), 7);
+    }
+
+    assert_eq!(users.find_one(&json!({"email": "b@x"})).unwrap().unwrap()["age"], 30);
+    assert_eq!(users.find_one(&json!({"email": "a@x"})).unwrap().unwrap()["age"], 40);
+    assert_eq!(users.count_documents(&json!({"age": {"$lt": 30}})).unwrap(), 3);
+
+    // A batch that breaks the unique constraint leaves the index as it was
+    let id = db.begin_transaction();
+    db.insert_one_tx("users", doc(json!({"email": "c@x", "age": 50})), id).unwrap();
+    db.insert_one_tx("users", doc(json!({"email": "a@x", "age": 60})), id).unwrap();
+    assert!(db.commit_transaction_with_indexes(id).is_err());
+    db.rollback_transaction(id).unwrap();
+    assert!(users.find_one(&json!({"email": "c@x"})).unwrap().is_none());
+    assert_eq!(users.count_documents(&json!({"email": "a@x"})).unwrap(), 1);
+}
+
+#[test]
+fn test_single_change_index_entries_are_still_replayed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        db.collection("users").unwrap().create_index("email".to_string(), true).unwrap();
+        db.flush().unwrap();
+    }
+
+    // A committed transaction as logged before index changes were batched
+    let insert = Operation::Insert {
+        collection: "users".to_string(),
+        doc_id: DocumentId::Int(1),
+        doc: json!({"_id": 1, "email": "a@x"}),
+    };
+    let change = json!({
+        "collection": "users",
+        "index_name": "users_email",
+        "operation": "Insert",
+        "key": IndexKey::String("a@x".to_string()),
+        "doc_id": DocumentId::Int(1),
+    });
+    let wal = [
+        WALEntry::new(9, WALEntryType::Begin, vec![]),
+        WALEntry::new(9, WALEntryType::Operation, serde_json::to_vec(&insert).unwrap()),
+        WALEntry::new(9, WALEntryType::IndexChange, serde_json::to_vec(&change).unwrap()),
+        WALEntry::new(9, WALEntryType::Commit, vec![]),
+    ].iter().flat_map(|entry| entry.serialize()).collect::<Vec<u8>>();
+    std::fs::write(path.with_extension("wal"), wal).unwrap();
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.find_one(&json!({"email": "a@x"})).unwrap().unwrap()["_id"], 1);
+}
@@ -0,0 +1,118 @@
+// Indexes are read from their index files on open while the files hold them
+// as of the documents, and rebuilt from the documents otherwise
+mod common;
+
+use common::doc;
+use ironbase_core::index::IndexKey;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn name_keys(db: &DatabaseCore) -> Vec<IndexKey> {
+    let users = db.collection("users").unwrap();
+    let indexes = users.indexes.read();
+    indexes.get_btree_index("users_name").unwrap().entries().unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Whether inspect reports the index file as read on open
+fn file_is_current(path: &Path, index: &str) -> bool {
+    let report = DatabaseCore::inspect(path).unwrap();
+    report.index_files.iter().find(|file| file.index == index).unwrap().current
+}
+
+fn strings(names: &[&str]) -> Vec<IndexKey> {
+    names.iter().map(|name| IndexKey::String(name.to_string())).collect()
+}
+
+/// Rename a key inside the index file, keeping its stamp - a tree read from
+/// the file shows the new name, a rebuilt one the documents'
+fn tamper(path: &Path, from: &str, to: &str) {
+    let mut data = std::fs::read(path).unwrap();
+    let at = data.windows(from.len()).position(|window| window == from.as_bytes()).unwrap();
+    data[at..at + to.len()].copy_from_slice(to.as_bytes());
+    std::fs::write(path, data).unwrap();
+}
+
+/// Users alice and bob, committed with their index files
+fn populate(db: &DatabaseCore) {
+    db.collection("users").unwrap().create_index("name".to_string(), false).unwrap();
+    let tx = db.begin_transaction();
+    db.insert_one_tx("users", doc(json!({"name": "alice"})), tx).unwrap();
+    db.insert_one_tx("users", doc(json!({"name": "bob"})), tx).unwrap();
+    db.commit_transaction_with_indexes(tx).unwrap();
+}
+
+#[test]
+fn test_index_files_are_read_until_the_next_write() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let idx_path = temp_dir.path().join("test.users_name.idx");
+    populate(&DatabaseCore::open(&path).unwrap());
+    assert!(file_is_current(&path, "users_name") && file_is_current(&path, "users_id"));
+    tamper(&idx_path, "alice", "alicx");
+
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        assert_eq!(name_keys(&db), strings(&["alicx", "bob"]));
+
+        // A write outside a transaction leaves the file behind
+        db.collection("users").unwrap().insert_one(doc(json!({"name": "carol"}))).unwrap();
+        assert_eq!(name_keys(&db), strings(&["alice", "bob", "carol"]));
+    }
+
+    assert!(!file_is_current(&path, "users_name"));
+    let db = DatabaseCore::open(&path).unwrap();
+    assert_eq!(name_keys(&db), strings(&["alice", "bob", "carol"]));
+
+    // A reindex writes the files again, as of the documents
+    db.collection("users").unwrap().reindex(None).unwrap();
+    drop(db);
+    tamper(&idx_path, "carol", "carox");
+    let db = DatabaseCore::open(&path).unwrap();
+    assert_eq!(name_keys(&db), strings(&["alice", "bob", "carox"]));
+}
+
+#[test]
+fn test_index_files_are_rebuilt_after_writes_lost_from_the_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        populate(&db);
+        db.flush().unwrap();
+
+        // Killed after a write the catalog journal has, but the metadata
+        // listing the index files as current does not
+        let users = db.collection("users").unwrap();
+        users.insert_one(doc(json!({"name": "carol"}))).unwrap();
+        std::mem::forget((users, db));
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    assert_eq!(name_keys(&db), strings(&["alice", "bob", "carol"]));
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({"name": "carol"})).unwrap(), 1);
+}
+
+#[test]
+fn test_index_file_of_a_dropped_namesake_is_not_read() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let idx_path = temp_dir.path().join("test.users_name.idx");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        populate(&db);
+        let users = db.collection("users").unwrap();
+        users.drop_index("users_name").unwrap();
+        users.create_index("name".to_string(), false).unwrap();
+    }
+    assert!(idx_path.exists());
+    tamper(&idx_path, "alice", "alicx");
+
+    let db = DatabaseCore::open(&path).unwrap();
+    assert_eq!(name_keys(&db), strings(&["alice", "bob"]));
+}
@@ -107,3 +107,377 @@ fn test_drop_index() {
     let indexes = collection.list_indexes();
     assert!(!indexes.contains(&index_name));
 }
+
+#[test]
+fn test_hashed_index_equality_lookups() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let urls: Vec<String> = (0..5)
+        .map(|i| format!("https://example.com/{}/{}", "segment/".repeat(50), i))
+        .collect();
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let pages = db.collection("pages").unwrap();
+
+        for (i, url) in urls.iter().enumerate() {
+            let mut fields = std::collections::HashMap::new();
+            fields.insert("url".to_string(), json!(url));
+            fields.insert("rank".to_string(), json!(i));
+            pages.insert_one(fields).unwrap();
+        }
+
+        let index_name = pages.create_hashed_index("url".to_string()).unwrap();
+        assert_eq!(index_name, "pages_url_hashed");
+    }
+
+    // Index definition survives reopening and is rebuilt as hashed
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let pages = db.collection("pages").unwrap();
+
+    let found = pages.find(&json!({"url": urls[3]})).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["rank"], 3);
+
+    let found = pages.find(&json!({"url": {"$in": [urls[1], urls[4], "missing"]}})).unwrap();
+    assert_eq!(found.len(), 2);
+
+    let plan = pages.explain(&json!({"url": {"$eq": urls[0]}})).unwrap();
+    assert_eq!(plan["queryPlan"], "HashedIndexScan");
+    assert_eq!(plan["indexUsed"], "pages_url_hashed");
+
+    // Ranges cannot use a hashed index
+    let plan = pages.explain(&json!({"url": {"$gte": urls[0]}})).unwrap();
+    assert_eq!(plan["queryPlan"], "CollectionScan");
+    assert_eq!(pages.find(&json!({"url": {"$gte": urls[3]}})).unwrap().len(), 2);
+
+    // Updates and deletes move the hashed entries
+    pages.update_one(&json!({"url": urls[0]}), &json!({"$set": {"url": "short"}})).unwrap();
+    pages.delete_one(&json!({"url": urls[2]})).unwrap();
+    assert!(pages.find(&json!({"url": urls[0]})).unwrap().is_empty());
+    assert!(pages.find(&json!({"url": urls[2]})).unwrap().is_empty());
+    assert_eq!(pages.find(&json!({"url": "short"})).unwrap().len(), 1);
+
+    let hinted = pages.find_with_hint(&json!({"url": urls[4]}), "pages_url_hashed").unwrap();
+    assert_eq!(hinted.len(), 1);
+}
+
+#[test]
+fn test_index_build_progress_and_cancellation() {
+    use ironbase_core::{IndexBuildProgress, MongoLiteError};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let collection = db.collection("events").unwrap();
+
+    for i in 0..2500 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("seq".to_string(), json!(i));
+        collection.insert_one(fields).unwrap();
+    }
+
+    // Cancel at the first report - nothing is left behind
+    let result = collection.create_index_with_progress("seq".to_string(), false, |_| false);
+    assert!(matches!(result, Err(MongoLiteError::Cancelled(_))));
+    assert!(!collection.list_indexes().contains(&"events_seq".to_string()));
+
+    let mut reports = Vec::new();
+    let index_name = collection.create_index_with_progress("seq".to_string(), false, |progress| {
+        reports.push(progress);
+        true
+    }).unwrap();
+
+    assert_eq!(index_name, "events_seq");
+    let processed: Vec<u64> = reports.iter().map(|p| p.processed).collect();
+    assert_eq!(processed, vec![1000, 2000, 2500]);
+    assert!(reports.iter().all(|p: &IndexBuildProgress| p.total == 2500));
+    assert_eq!(collection.find(&json!({"seq": 1234})).unwrap().len(), 1);
+}
+
+#[test]
+fn test_index_definitions_shared_between_handles() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let creator = db.collection("users").unwrap();
+        let other = db.collection("users").unwrap();
+
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!(30));
+        creator.insert_one(fields).unwrap();
+
+        // A handle opened before the index was created still sees and uses it
+        creator.create_index("age".to_string(), false).unwrap();
+        assert!(other.list_indexes().contains(&"users_age".to_string()));
+        assert_eq!(other.explain(&json!({"age": 30})).unwrap()["indexUsed"], "users_age");
+        assert_eq!(other.find(&json!({"age": 30})).unwrap().len(), 1);
+
+        // Creating it again through the other handle is a duplicate
+        assert!(other.create_index("age".to_string(), false).is_err());
+
+        // Dropping through either handle removes it everywhere
+        other.drop_index("users_age").unwrap();
+        assert!(!creator.list_indexes().contains(&"users_age".to_string()));
+        assert_eq!(creator.explain(&json!({"age": 30})).unwrap()["queryPlan"], "CollectionScan");
+
+        creator.create_index("age".to_string(), false).unwrap();
+    }
+
+    // Definitions survive a restart and the tree is rebuilt on open
+    let db = DatabaseCore::open(&db_path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.list_indexes(), vec!["users_age".to_string(), "users_id".to_string()]);
+    assert_eq!(users.explain(&json!({"age": 30})).unwrap()["indexUsed"], "users_age");
+    assert_eq!(users.find(&json!({"age": 30})).unwrap().len(), 1);
+}
+
+#[test]
+fn test_range_scans_span_many_leaves() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+    collection.create_index("age".to_string(), false).unwrap();
+
+    // Far more keys than one leaf holds, in scattered order, with duplicates
+    for i in 0..1000 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!((i * 37) % 500));
+        collection.insert_one(fields).unwrap();
+    }
+    collection.delete_many(&json!({"age": {"$lt": 100}})).unwrap();
+
+    assert_eq!(collection.count_documents(&json!({"age": {"$gte": 400}})).unwrap(), 200);
+    assert_eq!(collection.count_documents(&json!({"age": {"$lt": 150}})).unwrap(), 100);
+    assert_eq!(collection.count_documents(&json!({"age": 250})).unwrap(), 2);
+    assert!(collection.validate(false).unwrap().valid);
+}
+
+#[test]
+fn test_sort_follows_index_order() {
+    use ironbase_core::FindOptions;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+    collection.create_index("score".to_string(), false).unwrap();
+    for i in 0..300 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("score".to_string(), if i % 3 == 0 { json!(i as f64 / 2.0) } else { json!((i * 7) % 300) });
+        fields.insert("group".to_string(), json!(i % 2));
+        collection.insert_one(fields).unwrap();
+    }
+
+    let scores = |docs: Vec<serde_json::Value>| -> Vec<f64> {
+        docs.iter().map(|doc| doc["score"].as_f64().unwrap()).collect()
+    };
+    let sorted = |mut values: Vec<f64>, descending: bool| {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        if descending {
+            values.reverse();
+        }
+        values
+    };
+
+    // Ascending with skip and limit, filtered
+    let options = FindOptions::new().with_sort(vec![("score".to_string(), 1)]).with_skip(5).with_limit(20);
+    let page = scores(collection.find_with_options(&json!({"group": 1}), options).unwrap());
+    let all = sorted(scores(collection.find(&json!({"group": 1})).unwrap()), false);
+    assert_eq!(page, all[5..25].to_vec());
+
+    // Descending, unlimited
+    let options = FindOptions::new().with_sort(vec![("score".to_string(), -1)]);
+    let docs = scores(collection.find_with_options(&json!({}), options).unwrap());
+    assert_eq!(docs.len(), 300);
+    assert_eq!(docs, sorted(docs.clone(), true));
+
+    // Booleans sort after strings outside the index - the regular sort takes over
+    let mut fields = std::collections::HashMap::new();
+    fields.insert("score".to_string(), json!(true));
+    collection.insert_one(fields).unwrap();
+    let options = FindOptions::new().with_sort(vec![("score".to_string(), 1)]);
+    let docs = collection.find_with_options(&json!({}), options).unwrap();
+    assert_eq!(docs.last().unwrap()["score"], json!(true));
+}
+
+#[test]
+fn test_in_and_or_use_index_scans() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let collection = db.collection("users").unwrap();
+    collection.create_index("age".to_string(), false).unwrap();
+    collection.create_index("city".to_string(), false).unwrap();
+    for i in 0..200 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!(i % 50));
+        fields.insert("city".to_string(), json!(["NYC", "LA", "SF", "Boston"][i % 4]));
+        fields.insert("name".to_string(), json!(format!("user{}", i)));
+        collection.insert_one(fields).unwrap();
+    }
+    // Array values are indexed as Null - the filter decides whether they match
+    let mut fields = std::collections::HashMap::new();
+    fields.insert("age".to_string(), json!([7, 99]));
+    fields.insert("city".to_string(), json!("Paris"));
+    collection.insert_one(fields).unwrap();
+
+    let query = json!({"age": {"$in": [1, 2, 99, 1]}});
+    let plan = collection.explain(&query).unwrap();
+    assert_eq!(plan["queryPlan"], "IndexInScan");
+    assert_eq!(plan["indexUsed"], "users_age");
+    // count_documents scans, find goes through the index
+    assert_eq!(collection.find(&query).unwrap().len() as u64, collection.count_documents(&query).unwrap());
+    assert_eq!(collection.find(&query).unwrap().len(), 8);
+
+    // Overlapping branches: every document once
+    let query = json!({"$or": [{"age": {"$lt": 10}}, {"city": "NYC"}, {"age": 3}]});
+    let plan = collection.explain(&query).unwrap();
+    assert_eq!(plan["queryPlan"], "IndexUnion");
+    assert_eq!(plan["indexesUsed"], json!(["users_age", "users_city", "users_age"]));
+    let docs = collection.find(&query).unwrap();
+    let mut ids: Vec<String> = docs.iter().map(|doc| doc["_id"].to_string()).collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), docs.len());
+    // 40 ages under 10, 50 NYC, of which 10 are both
+    assert_eq!(docs.len(), 80);
+    assert_eq!(collection.count_documents(&query).unwrap(), 80);
+
+    // One unindexed branch sends the whole $or to a collection scan
+    let query = json!({"$or": [{"age": 3}, {"name": "user5"}]});
+    assert_eq!(collection.explain(&query).unwrap()["queryPlan"], "CollectionScan");
+    assert_eq!(collection.count_documents(&query).unwrap(), 5);
+}
+
+#[test]
+fn test_plan_cache_reuses_plans_by_shape() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    for i in 0..100 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!(i % 20));
+        users.insert_one(fields).unwrap();
+    }
+
+    // Same shape, different values: planned once, bound to each query's values
+    assert_eq!(users.find(&json!({"age": 3})).unwrap().len(), 5);
+    assert_eq!(users.find(&json!({"age": 4})).unwrap().len(), 5);
+    assert_eq!(users.find(&json!({"age": {"$gte": 15}})).unwrap().len(), 25);
+    assert_eq!(users.find(&json!({"age": {"$gte": 18}})).unwrap().len(), 10);
+    let stats = users.plan_cache.stats();
+    assert_eq!((stats.size, stats.hits, stats.misses), (2, 2, 2));
+
+    // Creating an index replaces the cached collection scan
+    users.create_index("age".to_string(), false).unwrap();
+    assert_eq!(users.plan_cache.stats().size, 0);
+    assert_eq!(users.find(&json!({"age": 5})).unwrap().len(), 5);
+    assert_eq!(users.find(&json!({"$or": [{"age": 1}, {"age": {"$in": [2, 3]}}]})).unwrap().len(), 15);
+    assert_eq!(users.find(&json!({"$or": [{"age": 7}, {"age": {"$in": [8]}}]})).unwrap().len(), 10);
+    assert_eq!(users.plan_cache.stats().size, 2);
+
+    // An index dropped through another handle is noticed before the cached plan is used
+    db.collection("users").unwrap().drop_index("users_age").unwrap();
+    assert_eq!(users.find(&json!({"age": 6})).unwrap().len(), 5);
+    assert_eq!(users.plan_cache.stats().size, 1);
+    assert_eq!(users.explain(&json!({"age": 6})).unwrap()["queryPlan"], "CollectionScan");
+}
+
+#[test]
+fn test_skip_and_limit_walk_the_index() {
+    use ironbase_core::FindOptions;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let events = db.collection("events").unwrap();
+    events.create_index("ts".to_string(), false).unwrap();
+    for i in 0..2000 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("ts".to_string(), json!((i * 7) % 2000));
+        fields.insert("payload".to_string(), json!("x".repeat(200)));
+        events.insert_one(fields).unwrap();
+    }
+    let ts = |docs: Vec<serde_json::Value>| -> Vec<i64> {
+        docs.iter().map(|doc| doc["ts"].as_i64().unwrap()).collect()
+    };
+
+    // Latest 10: only those documents are read - all 2000 would blow the memory limit
+    let latest = FindOptions::new()
+        .with_sort(vec![("ts".to_string(), -1)])
+        .with_limit(10)
+        .with_max_memory(100_000);
+    assert_eq!(ts(events.find_with_options(&json!({}), latest).unwrap()), (1990..2000).rev().collect::<Vec<_>>());
+
+    // Skip happens in the index too, ascending and descending, with a range filter
+    let page = FindOptions::new().with_sort(vec![("ts".to_string(), 1)]).with_skip(100).with_limit(5).with_max_memory(100_000);
+    assert_eq!(ts(events.find_with_options(&json!({"ts": {"$gte": 500}}), page).unwrap()), (600..605).collect::<Vec<_>>());
+    let page = FindOptions::new().with_sort(vec![("ts".to_string(), -1)]).with_skip(3).with_limit(4);
+    assert_eq!(ts(events.find_with_options(&json!({"ts": {"$lt": 50}}), page).unwrap()), vec![46, 45, 44, 43]);
+
+    // No sort: index order, windowed the same way
+    let page = FindOptions::new().with_skip(10).with_limit(3).with_max_memory(100_000);
+    assert_eq!(ts(events.find_with_options(&json!({"ts": {"$gt": 1000}}), page).unwrap()), vec![1011, 1012, 1013]);
+    let past_end = FindOptions::new().with_skip(1).with_limit(3);
+    assert!(events.find_with_options(&json!({"ts": 5}), past_end).unwrap().is_empty());
+
+    // A document without the field can't be found through the index - the
+    // empty filter goes back to sorting every document
+    let mut fields = std::collections::HashMap::new();
+    fields.insert("payload".to_string(), json!("no ts"));
+    events.insert_one(fields).unwrap();
+    let latest = FindOptions::new().with_sort(vec![("ts".to_string(), 1)]).with_limit(2);
+    let docs = events.find_with_options(&json!({}), latest).unwrap();
+    assert_eq!(docs[0]["payload"], "no ts");
+    assert_eq!(docs[1]["ts"], 0);
+}
+
+#[test]
+fn test_reindex_rebuilds_damaged_indexes() {
+    use ironbase_core::index::IndexKey;
+    use ironbase_core::{DocumentId, MongoLiteError};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    for age in 0..2500 {
+        let mut fields = std::collections::HashMap::new();
+        fields.insert("age".to_string(), json!(age % 100));
+        users.insert_one(fields).unwrap();
+    }
+    users.create_index("age".to_string(), false).unwrap();
+    users.create_hashed_index("email".to_string()).unwrap();
+
+    // Lose an entry: the index no longer finds its document
+    let lost = users.find_one(&json!({"age": 42})).unwrap().unwrap();
+    let lost_id: DocumentId = serde_json::from_value(lost["_id"].clone()).unwrap();
+    users.indexes.write().get_btree_index_mut("users_age").unwrap().delete(&IndexKey::Int(42), &lost_id).unwrap();
+    assert_eq!(users.find(&json!({"age": 42})).unwrap().len(), 24);
+
+    let mut reports = Vec::new();
+    let rebuilt = users.reindex_with_progress(Some("users_age"), |progress| {
+        reports.push(progress.processed);
+        true
+    }).unwrap();
+    assert_eq!(rebuilt.len(), 1);
+    assert_eq!(rebuilt[0].index, "users_age");
+    assert_eq!(rebuilt[0].keys, 2500);
+    assert_eq!(reports, vec![1000, 2000, 2500]);
+    assert_eq!(users.find(&json!({"age": 42})).unwrap().len(), 25);
+
+    // All of them; no document has an email, so the hashed index stays empty
+    let keys: Vec<(String, u64)> = users.reindex(None).unwrap().into_iter().map(|index| (index.index, index.keys)).collect();
+    assert_eq!(keys, vec![
+        ("users_age".to_string(), 2500),
+        ("users_email_hashed".to_string(), 0),
+        ("users_id".to_string(), 2500),
+    ]);
+    assert_eq!(users.list_indexes().len(), 3);
+
+    // A cancelled rebuild leaves the current indexes alone
+    assert!(matches!(users.reindex_with_progress(None, |_| false), Err(MongoLiteError::Cancelled(_))));
+    assert_eq!(users.find(&json!({"age": 42})).unwrap().len(), 25);
+    assert!(users.reindex(Some("users_missing")).is_err());
+}
@@ -0,0 +1,137 @@
+// ironbase-inspect: read-only dump of the on-disk structures
+mod common;
+
+use common::doc;
+use ironbase_core::storage::RecordKind;
+use ironbase_core::wal::{WALEntry, WALEntryType};
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use std::io::Write;
+use tempfile::TempDir;
+
+fn populate(path: &std::path::Path) {
+    let db = DatabaseCore::open(path).unwrap();
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    for i in 0..5 {
+        users.insert_one(doc(json!({"email": format!("u{}@x", i), "n": i}))).unwrap();
+    }
+    users.update_one(&json!({"n": 1}), &json!({"$set": {"n": 100, "padding": "x".repeat(200)}})).unwrap();
+    users.delete_one(&json!({"n": 2})).unwrap();
+    db.collection("events").unwrap().insert_one(doc(json!({"kind": "login"}))).unwrap();
+    db.flush().unwrap();
+}
+
+#[test]
+fn test_inspect_reports_layout_without_modifying_the_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("inspect.mlite");
+    populate(&path);
+    let before = std::fs::read(&path).unwrap();
+
+    let report = DatabaseCore::inspect(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+
+    assert_eq!(report.header.magic, "MONGOLTE");
+    assert_eq!(report.header.collection_count, 2);
+    assert_eq!(report.metadata.sections.len(), 2);
+    assert!(report.metadata.used_bytes < report.metadata.reserved_bytes);
+
+    let users = report.collections.iter().find(|c| c.name == "users").unwrap();
+    // The deleted document's entry points at its tombstone
+    assert_eq!(users.catalog_entries, 5);
+    assert_eq!(users.indexes.len(), 1);
+
+    let count = |kind: RecordKind, collection: &str| report.records.iter()
+        .filter(|record| record.kind == kind && record.collection.as_deref() == Some(collection))
+        .count();
+    assert_eq!(count(RecordKind::Live, "users"), 4);
+    assert_eq!(count(RecordKind::Live, "events"), 1);
+    assert_eq!(count(RecordKind::Tombstone, "users"), 1);
+    assert!(report.records.iter().any(|record| record.kind == RecordKind::Catalog));
+    // Records tile the data region
+    assert!(report.records.windows(2).all(|pair| pair[0].offset + pair[0].size == pair[1].offset));
+    assert_eq!(report.records.last().map(|record| record.offset + record.size), Some(report.file_size));
+
+    let text = report.to_string();
+    assert!(text.contains("Metadata region"));
+    assert!(text.contains("index users_email on email unique"));
+}
+
+#[test]
+fn test_inspect_lists_problems_of_a_damaged_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("damaged.mlite");
+    populate(&path);
+
+    // A record torn by a crash, and a WAL entry followed by garbage
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&500u32.to_le_bytes()).unwrap();
+    file.write_all(b"{\"_id\":").unwrap();
+    let entry = WALEntry::new(7, WALEntryType::Begin, b"{}".to_vec()).serialize();
+    let entry_len = entry.len();
+    let mut wal = [entry.clone(), entry.clone(), entry].concat();
+    // Payload of the second entry: its checksum no longer matches
+    wal[entry_len + 13] ^= 0xFF;
+    std::fs::write(path.with_extension("wal"), &wal).unwrap();
+
+    let report = DatabaseCore::inspect(&path).unwrap();
+    assert!(report.problems.iter().any(|problem| problem.starts_with("torn record")), "{:?}", report.problems);
+    assert!(report.problems.iter().any(|problem| problem.starts_with("WAL entry")), "{:?}", report.problems);
+    assert_eq!(report.wal.entries.len(), 1);
+    assert_eq!(report.wal.uncommitted, vec![7]);
+
+    std::fs::write(&path, b"not a database").unwrap();
+    assert!(DatabaseCore::inspect(&path).is_err());
+}
+
+#[test]
+fn test_inspect_binary_prints_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("cli.mlite");
+    populate(&path);
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_ironbase-inspect"))
+        .arg(&path)
+        .args(["--json", "--no-records"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["header"]["collection_count"], 2);
+    assert_eq!(report["records"], json!([]));
+    assert_eq!(report["record_summary"]["live"]["records"], 5);
+
+    let missing = std::process::Command::new(env!("CARGO_BIN_EXE_ironbase-inspect"))
+        .arg(temp_dir.path().join("missing.mlite"))
+        .output()
+        .unwrap();
+    assert!(!missing.status.success());
+}
+
+#[test]
+fn test_inspect_summarizes_index_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("indexed.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        db.collection("users").unwrap().create_index("age".to_string(), false).unwrap();
+        // Transactions persist the touched indexes to .idx files
+        let tx = db.begin_transaction();
+        for age in [30, 40, 50] {
+            db.insert_one_tx("users", doc(json!({"age": age})), tx).unwrap();
+        }
+        db.commit_transaction_with_indexes(tx).unwrap();
+    }
+    std::fs::write(temp_dir.path().join("indexed.stale.idx"), [0u8; 16]).unwrap();
+
+    let report = DatabaseCore::inspect(&path).unwrap();
+    let age = report.index_files.iter().find(|index| index.index == "users_age").unwrap();
+    assert!(age.exists);
+    assert_eq!(age.keys, 3);
+    assert_eq!(age.leaf_nodes, 1);
+    assert_eq!(age.stamp.as_ref().unwrap().version, ironbase_core::index::INDEX_FILE_VERSION);
+    assert_eq!(report.orphan_index_files.len(), 1);
+    assert!(report.orphan_index_files[0].ends_with("indexed.stale.idx"));
+}
@@ -1,5 +1,5 @@
 // Integration tests for MongoLite Core
-use ironbase_core::{StorageEngine, Document, DocumentId};
+use ironbase_core::{DatabaseCore, StorageEngine, Document, DocumentId};
 use std::collections::HashMap;
 use serde_json::json;
 use tempfile::TempDir;
@@ -280,3 +280,22 @@ fn test_stats_with_collections() {
     assert!(names.contains(&"users".to_string()));
     assert!(names.contains(&"posts".to_string()));
 }
+
+#[test]
+fn test_insert_many_continues_id_sequence() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    let fields = |name: &str| HashMap::from([("name".to_string(), json!(name))]);
+
+    // First batch on an empty collection
+    let first = users.insert_many(vec![fields("a"), fields("b")]).unwrap();
+    assert_eq!(first.inserted_ids, vec![DocumentId::Int(1), DocumentId::Int(2)]);
+
+    let single = users.insert_one(fields("c")).unwrap();
+    assert_eq!(single, DocumentId::Int(3));
+
+    let second = users.insert_many(vec![fields("d")]).unwrap();
+    assert_eq!(second.inserted_ids, vec![DocumentId::Int(4)]);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 4);
+}
@@ -0,0 +1,79 @@
+// Metadata journal: catalog changes and last_id survive a crash before flush
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, ID_RESERVATION_BLOCK};
+use serde_json::json;
+use tempfile::TempDir;
+
+/// Drop the database without the flush a clean shutdown does
+fn crash(db: DatabaseCore) {
+    std::mem::forget(db);
+}
+
+#[test]
+fn test_writes_since_last_flush_survive_crash() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+        users.insert_one(doc(json!({"name": "bob"}))).unwrap();
+        db.flush().unwrap();
+
+        // Reuses the region of ann's first version after the flush
+        users.update_one(&json!({"name": "ann"}), &json!({"$set": {"age": 30}})).unwrap();
+        db.flush().unwrap();
+        users.insert_one(doc(json!({"name": "cy"}))).unwrap();
+        users.delete_one(&json!({"name": "bob"})).unwrap();
+        users.update_one(&json!({"name": "ann"}), &json!({"$set": {"age": 31}})).unwrap();
+        crash(db);
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 2);
+    assert_eq!(users.find_one(&json!({"name": "ann"})).unwrap().unwrap()["age"], json!(31));
+    assert!(users.find_one(&json!({"name": "bob"})).unwrap().is_none());
+
+    // last_id was not lost: the next insert does not reuse cy's _id
+    let id = users.insert_one(doc(json!({"name": "dan"}))).unwrap();
+    assert!(serde_json::to_value(id).unwrap().as_u64().unwrap() > 3);
+    db.flush().unwrap();
+    drop(db);
+
+    // A clean shutdown leaves nothing to replay
+    assert_eq!(std::fs::metadata(path.with_extension("journal")).unwrap().len(), 0);
+}
+
+#[test]
+fn test_ids_are_not_reissued_after_crash() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+        db.flush().unwrap();
+
+        // Handed out, never written
+        let tx_id = db.begin_transaction();
+        let id = db.insert_one_tx("users", doc(json!({"name": "bob"})), tx_id).unwrap();
+        assert_eq!(serde_json::to_value(id).unwrap(), json!(2));
+        crash(db);
+    }
+    {
+        // Recovery skips the whole reservation
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        let id = users.insert_one(doc(json!({"name": "cy"}))).unwrap();
+        assert_eq!(serde_json::to_value(id).unwrap(), json!(2 + ID_RESERVATION_BLOCK + 1));
+    }
+
+    // A clean shutdown leaves no gap
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    let id = users.insert_one(doc(json!({"name": "dan"}))).unwrap();
+    assert_eq!(serde_json::to_value(id).unwrap(), json!(2 + ID_RESERVATION_BLOCK + 2));
+}
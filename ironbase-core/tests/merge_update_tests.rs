@@ -0,0 +1,42 @@
+// $mergeObjects: deep-merging a partial document into an existing one
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, UpdateBuilder};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_merge_objects_update() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    users.insert_one(doc(json!({
+        "name": "ann",
+        "profile": {"address": {"city": "Pécs", "zip": "7621"}, "phone": "1", "tags": ["a"]},
+    }))).unwrap();
+
+    let result = users.update_one(&json!({"name": "ann"}), &json!({"$mergeObjects": {
+        "profile": {"address": {"zip": "7622", "street": "Fő u."}, "phone": null, "tags": ["b"]},
+        "settings": {"theme": "dark"},
+    }})).unwrap();
+    assert_eq!((result.matched_count, result.modified_count), (1, 1));
+
+    let ann = users.find_one(&json!({"name": "ann"})).unwrap().unwrap();
+    assert_eq!(ann["profile"], json!({
+        "address": {"city": "Pécs", "zip": "7622", "street": "Fő u."},
+        "tags": ["b"],
+    }));
+    assert_eq!(ann["settings"], json!({"theme": "dark"}));
+
+    // Nothing changes: not modified
+    let update = UpdateBuilder::merge("settings", json!({"theme": "dark"})).build();
+    let result = users.update_one(&json!({"name": "ann"}), &update).unwrap();
+    assert_eq!((result.matched_count, result.modified_count), (1, 0));
+
+    // A non-object patch replaces, a null patch removes the field
+    users.update_many(&json!({}), &json!({"$mergeObjects": {"settings": null, "name": "ann b"}})).unwrap();
+    let ann = users.find_one(&json!({})).unwrap().unwrap();
+    assert_eq!(ann["name"], json!("ann b"));
+    assert!(ann.get("settings").is_none());
+}
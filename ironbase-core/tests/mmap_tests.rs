@@ -0,0 +1,63 @@
+// Memory map of the data file: remapping as the file grows, and the map mode in stats
+use ironbase_core::storage::MMAP_REMAP_MIN_GROWTH;
+use ironbase_core::{DatabaseCore, StorageConfig};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn insert_batch(db: &DatabaseCore, start: i64, count: i64) {
+    let items = db.collection("items").unwrap();
+    let documents = (start..start + count)
+        .map(|n| serde_json::from_value::<HashMap<String, Value>>(json!({"n": n, "padding": "x".repeat(1000)})).unwrap())
+        .collect();
+    items.insert_many(documents).unwrap();
+}
+
+#[test]
+fn test_map_follows_file_growth_up_to_the_limit() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let limit = 4 * MMAP_REMAP_MIN_GROWTH;
+    let db = DatabaseCore::open_with_config(&path, &StorageConfig::default().with_mmap_limit(limit)).unwrap();
+    assert_eq!(db.stats()["mmap"]["mode"], "full");
+    let remaps = db.stats()["mmap"]["remaps"].as_u64().unwrap();
+
+    // Small appends stay in the unmapped tail; a large enough one remaps
+    insert_batch(&db, 0, 10);
+    assert_eq!(db.stats()["mmap"]["mode"], "partial");
+    assert_eq!(db.stats()["mmap"]["remaps"].as_u64().unwrap(), remaps);
+    insert_batch(&db, 10, 1500);
+    assert!(db.stats()["mmap"]["remaps"].as_u64().unwrap() > remaps);
+
+    // Past the limit only the first `limit` bytes are mapped
+    insert_batch(&db, 1510, 3000);
+    let stats = db.stats();
+    assert!(stats["file_size"].as_u64().unwrap() > limit);
+    assert_eq!(stats["mmap"]["mode"], "partial");
+    assert_eq!(stats["mmap"]["mapped_bytes"].as_u64().unwrap(), limit);
+
+    // Reads inside and past the map see every write, updates in place included
+    let items = db.collection("items").unwrap();
+    assert_eq!(items.count_documents(&json!({})).unwrap(), 4510);
+    items.update_many(&json!({"n": {"$lt": 100}}), &json!({"$set": {"padding": "y"}})).unwrap();
+    db.flush().unwrap();
+    items.update_many(&json!({"n": {"$lt": 100}}), &json!({"$set": {"padding": "z"}})).unwrap();
+    assert_eq!(items.count_documents(&json!({"padding": "z"})).unwrap(), 100);
+    assert_eq!(items.find_one(&json!({"n": 4509})).unwrap().unwrap()["padding"], "x".repeat(1000));
+
+    // Compaction shrinks the file under the limit: mapped whole again
+    items.delete_many(&json!({"n": {"$gte": 1000}})).unwrap();
+    db.compact().unwrap();
+    assert_eq!(db.stats()["mmap"]["mode"], "full");
+    assert_eq!(db.collection("items").unwrap().count_documents(&json!({})).unwrap(), 1000);
+}
+
+#[test]
+fn test_map_can_be_turned_off() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open_with_config(temp_dir.path().join("test.mlite"), &StorageConfig::default().with_mmap_limit(0)).unwrap();
+    insert_batch(&db, 0, 100);
+    assert_eq!(db.stats()["mmap"]["mode"], "off");
+    assert_eq!(db.stats()["mmap"]["mapped_bytes"], 0);
+    assert_eq!(db.collection("items").unwrap().count_documents(&json!({})).unwrap(), 100);
+}
@@ -0,0 +1,142 @@
+// MVCC: snapshot reads over retained document versions
+use ironbase_core::{DatabaseCore, DocumentId};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn insert(db: &DatabaseCore, name: &str, value: i64) -> DocumentId {
+    let mut fields = HashMap::new();
+    fields.insert("name".to_string(), json!(name));
+    fields.insert("value".to_string(), json!(value));
+    db.collection("items").unwrap().insert_one(fields).unwrap()
+}
+
+fn names(mut docs: Vec<Value>) -> Vec<(String, i64)> {
+    docs.sort_by_key(|doc| doc["name"].as_str().unwrap().to_string());
+    docs.iter().map(|doc| (doc["name"].as_str().unwrap().to_string(), doc["value"].as_i64().unwrap())).collect()
+}
+
+fn pair(name: &str, value: i64) -> (String, i64) {
+    (name.to_string(), value)
+}
+
+#[test]
+fn test_snapshot_sees_state_at_its_lsn() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let a = insert(&db, "a", 1);
+    insert(&db, "b", 2);
+    let items = db.collection("items").unwrap();
+
+    let snapshot = db.snapshot();
+    assert_eq!(snapshot.lsn(), db.current_lsn());
+
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 10}})).unwrap();
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 20}})).unwrap();
+    items.delete_one(&json!({"name": "b"})).unwrap();
+    insert(&db, "c", 3);
+
+    assert_eq!(names(items.find_at(&json!({}), &snapshot).unwrap()), vec![pair("a", 1), pair("b", 2)]);
+    assert_eq!(names(items.find(&json!({})).unwrap()), vec![pair("a", 20), pair("c", 3)]);
+    assert_eq!(names(items.find_at(&json!({"value": {"$gte": 2}}), &snapshot).unwrap()), vec![pair("b", 2)]);
+    assert_eq!(items.find_by_id_at(&a, &snapshot).unwrap().unwrap()["value"], 1);
+
+    // A later snapshot sees the later state
+    let later = db.snapshot();
+    assert_eq!(names(items.find_at(&json!({}), &later).unwrap()), vec![pair("a", 20), pair("c", 3)]);
+}
+
+#[test]
+fn test_versions_pruned_after_snapshots_close() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "a", 1);
+    let items = db.collection("items").unwrap();
+
+    // No snapshot: nothing is retained
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 2}})).unwrap();
+    assert_eq!(db.mvcc_stats().retained_versions, 0);
+
+    let first = db.snapshot();
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 3}})).unwrap();
+    let second = db.snapshot();
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 4}})).unwrap();
+
+    let stats = db.mvcc_stats();
+    assert_eq!((stats.open_snapshots, stats.oldest_snapshot, stats.retained_versions), (2, Some(first.lsn()), 2));
+
+    // Only the version the oldest snapshot alone needed goes
+    drop(first);
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 5}})).unwrap();
+    assert_eq!(db.mvcc_stats().retained_versions, 2);
+    assert_eq!(items.find_at(&json!({}), &second).unwrap()[0]["value"], 3);
+
+    drop(second);
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 6}})).unwrap();
+    assert_eq!(db.mvcc_stats(), Default::default());
+}
+
+#[test]
+fn test_compaction_keeps_versions_of_open_snapshots() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "a", 1);
+    insert(&db, "b", 2);
+    let items = db.collection("items").unwrap();
+
+    let snapshot = db.snapshot();
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 10}})).unwrap();
+    items.delete_one(&json!({"name": "b"})).unwrap();
+
+    db.compact().unwrap();
+    let items = db.collection("items").unwrap();
+    assert_eq!(names(items.find_at(&json!({}), &snapshot).unwrap()), vec![pair("a", 1), pair("b", 2)]);
+    assert_eq!(names(items.find(&json!({})).unwrap()), vec![pair("a", 10)]);
+
+    drop(snapshot);
+    db.compact().unwrap();
+    assert_eq!(db.mvcc_stats().retained_versions, 0);
+}
+
+#[test]
+fn test_transaction_commits_at_one_lsn() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "a", 1);
+    insert(&db, "b", 2);
+    let items = db.collection("items").unwrap();
+
+    let snapshot = db.snapshot();
+    let tx = db.begin_transaction();
+    db.update_one_tx("items", &json!({"name": "a"}), json!({"name": "a", "value": 0}), tx).unwrap();
+    db.update_one_tx("items", &json!({"name": "b"}), json!({"name": "b", "value": 3}), tx).unwrap();
+    db.commit_transaction(tx).unwrap();
+
+    assert_eq!(db.current_lsn(), snapshot.lsn() + 1);
+    assert_eq!(names(items.find_at(&json!({}), &snapshot).unwrap()), vec![pair("a", 1), pair("b", 2)]);
+    assert_eq!(names(items.find_at(&json!({}), &db.snapshot()).unwrap()), vec![pair("a", 0), pair("b", 3)]);
+}
+
+#[test]
+fn test_collection_compaction_keeps_versions_of_open_snapshots() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    insert(&db, "a", 1);
+    insert(&db, "b", 2);
+    let items = db.collection("items").unwrap();
+
+    let snapshot = db.snapshot();
+    items.update_one(&json!({"name": "a"}), &json!({"$set": {"value": 10}})).unwrap();
+    items.delete_one(&json!({"name": "b"})).unwrap();
+
+    // b's tombstone stays while the snapshot still needs its old version
+    let stats = db.compact_collection("items").unwrap();
+    assert_eq!(stats.tombstones_removed, 0);
+    assert_eq!(names(items.find_at(&json!({}), &snapshot).unwrap()), vec![pair("a", 1), pair("b", 2)]);
+
+    drop(snapshot);
+    let stats = db.compact_collection("items").unwrap();
+    assert_eq!(stats.tombstones_removed, 1);
+    assert_eq!(db.mvcc_stats().retained_versions, 0);
+    assert_eq!(names(items.find(&json!({})).unwrap()), vec![pair("a", 10)]);
+}
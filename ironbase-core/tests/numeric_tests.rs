@@ -0,0 +1,111 @@
+// Numeric type fidelity: i64, u64 and f64 values through storage, queries,
+// updates, indexes and transactions
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const BIG: u64 = u64::MAX - 1;
+
+#[test]
+fn test_numbers_round_trip_with_their_kind() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let coll = db.collection("nums").unwrap();
+        coll.insert_one(doc(json!({"big": BIG, "float": 3.0, "int": 3, "min": i64::MIN}))).unwrap();
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let doc = db.collection("nums").unwrap().find_one(&json!({})).unwrap().unwrap();
+    assert_eq!(doc["big"].as_u64(), Some(BIG));
+    assert!(doc["float"].is_f64());
+    assert!(doc["int"].is_i64());
+    assert_eq!(doc["min"].as_i64(), Some(i64::MIN));
+}
+
+#[test]
+fn test_queries_compare_numbers_by_value() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let coll = db.collection("nums").unwrap();
+    coll.insert_one(doc(json!({"n": 1}))).unwrap();
+    coll.insert_one(doc(json!({"n": 1.5}))).unwrap();
+    coll.insert_one(doc(json!({"n": BIG}))).unwrap();
+    coll.insert_one(doc(json!({"n": BIG - 1}))).unwrap();
+
+    assert_eq!(coll.count_documents(&json!({"n": 1.0})).unwrap(), 1);
+    assert_eq!(coll.count_documents(&json!({"n": {"$in": [1.0, 2]}})).unwrap(), 1);
+    assert_eq!(coll.count_documents(&json!({"n": {"$gt": 1}})).unwrap(), 3);
+    // No rounding through f64: the two large values stay distinct
+    assert_eq!(coll.count_documents(&json!({"n": BIG})).unwrap(), 1);
+    assert_eq!(coll.count_documents(&json!({"n": {"$gt": BIG - 1}})).unwrap(), 1);
+
+    let sorted = coll.find_with_options(&json!({}), ironbase_core::FindOptions::new().with_sort(vec![("n".to_string(), 1)])).unwrap();
+    let order: Vec<&Value> = sorted.iter().map(|doc| &doc["n"]).collect();
+    assert_eq!(order, vec![&json!(1), &json!(1.5), &json!(BIG - 1), &json!(BIG)]);
+}
+
+#[test]
+fn test_inc_keeps_integers_exact() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let coll = db.collection("nums").unwrap();
+    coll.insert_one(doc(json!({"name": "a", "n": i64::MAX, "f": 1.0}))).unwrap();
+
+    coll.update_one(&json!({"name": "a"}), &json!({"$inc": {"n": 1, "f": 1}})).unwrap();
+    let doc = coll.find_one(&json!({"name": "a"})).unwrap().unwrap();
+    assert_eq!(doc["n"].as_u64(), Some(i64::MAX as u64 + 1));
+    assert!(doc["f"].is_f64());
+
+    let overflow = coll.update_one(&json!({"name": "a"}), &json!({"$inc": {"n": u64::MAX}}));
+    assert!(overflow.is_err());
+
+    let value = coll.increment(&json!({"name": "a"}), "n", &json!(-1)).unwrap();
+    assert_eq!(value, Some(json!(i64::MAX)));
+}
+
+#[test]
+fn test_index_keys_order_numbers_by_value() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let coll = db.collection("nums").unwrap();
+    coll.create_index("n".to_string(), false).unwrap();
+    for n in [json!(1), json!(2.5), json!(3), json!(BIG)] {
+        coll.insert_one(doc(json!({"n": n}))).unwrap();
+    }
+
+    // Range scans mix integers and floats, equality finds 3 as 3.0
+    assert_eq!(coll.count_documents(&json!({"n": {"$gte": 2}})).unwrap(), 3);
+    assert_eq!(coll.count_documents(&json!({"n": {"$lt": 3}})).unwrap(), 2);
+    assert_eq!(coll.count_documents(&json!({"n": 3.0})).unwrap(), 1);
+    assert_eq!(coll.count_documents(&json!({"n": {"$gt": i64::MAX}})).unwrap(), 1);
+
+    // A unique index treats 1 and 1.0 as the same key
+    let unique = db.collection("unique").unwrap();
+    unique.create_index("n".to_string(), true).unwrap();
+    unique.insert_one(doc(json!({"n": 1}))).unwrap();
+    assert!(unique.insert_one(doc(json!({"n": 1.0}))).is_err());
+}
+
+#[test]
+fn test_transaction_update_keeps_u64() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let coll = db.collection("nums").unwrap();
+    coll.create_index("n".to_string(), false).unwrap();
+    coll.insert_one(doc(json!({"name": "a", "n": 1}))).unwrap();
+
+    let tx = db.begin_transaction();
+    db.update_one_tx("nums", &json!({"name": "a"}), json!({"name": "a", "n": BIG}), tx).unwrap();
+    db.commit_transaction(tx).unwrap();
+
+    let coll = db.collection("nums").unwrap();
+    let doc = coll.find_one(&json!({"name": "a"})).unwrap().unwrap();
+    assert_eq!(doc["n"].as_u64(), Some(BIG));
+    assert_eq!(coll.count_documents(&json!({"n": BIG})).unwrap(), 1);
+}
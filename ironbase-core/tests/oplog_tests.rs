@@ -0,0 +1,110 @@
+// Oplog (_oplog collection) tests
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, OplogConfig};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_oplog_disabled_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    users.insert_one(doc(json!({"name": "alice"}))).unwrap();
+
+    assert!(!db.oplog_config().enabled);
+    assert!(!db.list_collections().contains(&"_oplog".to_string()));
+    assert!(db.read_oplog(0, 100).unwrap().is_empty());
+}
+
+#[test]
+fn test_oplog_records_crud_operations() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_oplog_config(OplogConfig::with_retention(100));
+    let users = db.collection("users").unwrap();
+
+    let id = users.insert_one(doc(json!({"name": "alice", "age": 30}))).unwrap();
+    users.update_one(&json!({"name": "alice"}), &json!({"$set": {"age": 31}})).unwrap();
+    users.delete_one(&json!({"name": "alice"})).unwrap();
+
+    let entries = db.read_oplog(0, 100).unwrap();
+    assert_eq!(entries.len(), 3);
+
+    let ops: Vec<&str> = entries.iter().map(|e| e["op"].as_str().unwrap()).collect();
+    assert_eq!(ops, vec!["insert", "update", "delete"]);
+
+    for entry in &entries {
+        assert_eq!(entry["collection"], "users");
+        assert_eq!(entry["doc_id"], json!(id));
+        assert!(entry["ts"].as_i64().unwrap() > 0);
+    }
+    assert_eq!(entries[0]["delta"]["name"], "alice");
+    assert_eq!(entries[1]["delta"], json!({"$set": {"age": 31}}));
+    assert!(entries[2]["delta"].is_null());
+
+    // Tailing from the last seen sequence number
+    let last_seen = entries[1]["_id"].as_u64().unwrap();
+    let tail = db.read_oplog(last_seen, 100).unwrap();
+    assert_eq!(tail.len(), 1);
+    assert_eq!(tail[0]["op"], "delete");
+}
+
+#[test]
+fn test_oplog_records_transactions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_oplog_config(OplogConfig::with_retention(100));
+    db.collection("users").unwrap();
+
+    let tx_id = db.begin_transaction();
+    db.insert_one_tx("users", doc(json!({"name": "bob"})), tx_id).unwrap();
+    db.insert_one_tx("users", doc(json!({"name": "carol"})), tx_id).unwrap();
+    db.commit_transaction(tx_id).unwrap();
+
+    let aborted = db.begin_transaction();
+    db.insert_one_tx("users", doc(json!({"name": "ghost"})), aborted).unwrap();
+    db.rollback_transaction(aborted).unwrap();
+
+    let entries = db.read_oplog(0, 100).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|e| e["op"] == "insert"));
+}
+
+#[test]
+fn test_oplog_retention_caps_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_oplog_config(OplogConfig::with_retention(5));
+    let users = db.collection("users").unwrap();
+
+    for i in 0..12 {
+        users.insert_one(doc(json!({"n": i}))).unwrap();
+    }
+
+    let entries = db.read_oplog(0, 100).unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0]["_id"], 8);
+    assert_eq!(entries[4]["_id"], 12);
+    assert_eq!(entries[4]["delta"]["n"], 11);
+
+    // Trimmed entries stay gone after compaction
+    db.compact().unwrap();
+    assert_eq!(db.read_oplog(0, 100).unwrap().len(), 5);
+}
+
+#[test]
+fn test_oplog_can_be_disabled() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_oplog_config(OplogConfig::with_retention(100));
+    let users = db.collection("users").unwrap();
+
+    users.insert_one(doc(json!({"n": 1}))).unwrap();
+    db.set_oplog_config(OplogConfig::default());
+    users.insert_one(doc(json!({"n": 2}))).unwrap();
+
+    assert_eq!(db.read_oplog(0, 100).unwrap().len(), 1);
+}
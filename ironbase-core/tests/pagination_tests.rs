@@ -0,0 +1,187 @@
+// Keyset pagination (find_page) and natural order tests
+mod common;
+
+use common::doc;
+use ironbase_core::{CollectionCore, DatabaseCore, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_find_page_walks_all_documents_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+
+    // Duplicate scores exercise the _id tie-breaker
+    for i in 0..25 {
+        items.insert_one(doc(json!({"n": i, "score": i % 4}))).unwrap();
+    }
+
+    let sort = vec![("score".to_string(), -1)];
+    let mut seen = Vec::new();
+    let mut token: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let page = items.find_page(&json!({}), &sort, token.as_deref(), 10).unwrap();
+        pages += 1;
+        seen.extend(page.documents.iter().map(|d| (d["score"].as_i64().unwrap(), d["_id"].as_i64().unwrap())));
+        match page.next_page_token {
+            Some(next) => token = Some(next),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(seen.len(), 25);
+    let mut expected = seen.clone();
+    expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn test_find_page_is_stable_under_concurrent_inserts() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+
+    for i in 0..6 {
+        items.insert_one(doc(json!({"rank": i * 10}))).unwrap();
+    }
+
+    let sort = vec![("rank".to_string(), 1)];
+    let first = items.find_page(&json!({}), &sort, None, 3).unwrap();
+    let ranks: Vec<i64> = first.documents.iter().map(|d| d["rank"].as_i64().unwrap()).collect();
+    assert_eq!(ranks, vec![0, 10, 20]);
+
+    // An insert before the cursor must not shift the next page
+    items.insert_one(doc(json!({"rank": 5}))).unwrap();
+
+    let second = items.find_page(&json!({}), &sort, first.next_page_token.as_deref(), 3).unwrap();
+    let ranks: Vec<i64> = second.documents.iter().map(|d| d["rank"].as_i64().unwrap()).collect();
+    assert_eq!(ranks, vec![30, 40, 50]);
+    assert!(second.next_page_token.is_none());
+}
+
+/// All pages of `query` in `sort` order, as (score, _id) pairs
+fn all_pages(items: &CollectionCore, query: &serde_json::Value, sort: &[(String, i32)], page_size: usize) -> Vec<(i64, i64)> {
+    let mut seen = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        let page = items.find_page(query, sort, token.as_deref(), page_size).unwrap();
+        assert!(page.documents.len() <= page_size);
+        seen.extend(page.documents.iter().map(|d| (d["score"].as_i64().unwrap(), d["_id"].as_i64().unwrap())));
+        match page.next_page_token {
+            Some(next) => token = Some(next),
+            None => return seen,
+        }
+    }
+}
+
+#[test]
+fn test_find_page_through_an_index_matches_the_scan() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let scanned = db.collection("scanned").unwrap();
+    let indexed = db.collection("indexed").unwrap();
+    indexed.create_index("score".to_string(), false).unwrap();
+
+    // Long runs of equal scores span several pages and index batches
+    for i in 0..60 {
+        let fields = json!({"score": (i * 7) % 5, "even": i % 2 == 0});
+        scanned.insert_one(doc(fields.clone())).unwrap();
+        indexed.insert_one(doc(fields)).unwrap();
+    }
+
+    for (query, direction, page_size) in [
+        (json!({}), 1, 7),
+        (json!({}), -1, 4),
+        (json!({"even": true}), 1, 5),
+        (json!({"score": {"$gte": 2}}), -1, 1),
+    ] {
+        let sort = vec![("score".to_string(), direction)];
+        let expected = all_pages(&scanned, &query, &sort, page_size);
+        assert!(!expected.is_empty());
+        assert_eq!(all_pages(&indexed, &query, &sort, page_size), expected);
+    }
+}
+
+#[test]
+fn test_find_page_through_an_index_reads_one_page() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+    items.create_index("score".to_string(), false).unwrap();
+
+    let padding = "x".repeat(1000);
+    for i in 0..200 {
+        items.insert_one(doc(json!({"score": i, "padding": padding}))).unwrap();
+    }
+    let sort = vec![("score".to_string(), 1)];
+    let mut page = items.find_page(&json!({}), &sort, None, 5).unwrap();
+    for _ in 0..30 {
+        page = items.find_page(&json!({}), &sort, page.next_page_token.as_deref(), 5).unwrap();
+    }
+
+    // Room for a page, far from all documents
+    db.set_query_memory_limit(Some(20_000));
+    let next = items.find_page(&json!({}), &sort, page.next_page_token.as_deref(), 5).unwrap();
+    let scores: Vec<i64> = next.documents.iter().map(|d| d["score"].as_i64().unwrap()).collect();
+    assert_eq!(scores, vec![155, 156, 157, 158, 159]);
+
+    // Without the index every page reads every document
+    items.drop_index("items_score").unwrap();
+    let scanned = items.find_page(&json!({}), &sort, page.next_page_token.as_deref(), 5);
+    assert!(matches!(scanned, Err(MongoLiteError::QueryExceededMemoryLimit { .. })));
+}
+
+#[test]
+fn test_find_page_rejects_bad_tokens() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+
+    for i in 0..3 {
+        items.insert_one(doc(json!({"n": i}))).unwrap();
+    }
+
+    let page = items.find_page(&json!({}), &[("n".to_string(), 1)], None, 1).unwrap();
+    let token = page.next_page_token.unwrap();
+
+    // Token from a different sort, garbage token, zero page size
+    assert!(items.find_page(&json!({}), &[("n".to_string(), -1)], Some(&token), 1).is_err());
+    assert!(items.find_page(&json!({}), &[], Some("not-a-token"), 1).is_err());
+    assert!(items.find_page(&json!({}), &[], None, 0).is_err());
+}
+
+#[test]
+fn test_unsorted_scans_return_natural_order() {
+    use ironbase_core::FindOptions;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let items = db.collection("items").unwrap();
+    for i in 0..300 {
+        items.insert_one(doc(json!({"n": i, "group": i % 3}))).unwrap();
+    }
+    let numbers = |docs: Vec<serde_json::Value>| -> Vec<i64> {
+        docs.iter().map(|d| d["n"].as_i64().unwrap()).collect()
+    };
+
+    // Records are appended, so natural order starts out as insertion order -
+    // for every handle, scan and page
+    assert_eq!(numbers(items.find(&json!({})).unwrap()), (0..300).collect::<Vec<_>>());
+    let other = db.collection("items").unwrap();
+    assert_eq!(numbers(other.find(&json!({"group": 1})).unwrap()), (1..300).step_by(3).collect::<Vec<_>>());
+    let page = FindOptions::new().with_skip(100).with_limit(50);
+    assert_eq!(numbers(other.find_with_options(&json!({"n": {"$gte": 0}}), page).unwrap()), (100..150).collect::<Vec<_>>());
+    assert_eq!(other.find_one(&json!({"group": 2})).unwrap().unwrap()["n"], 2);
+    let snapshot = db.snapshot();
+    assert_eq!(numbers(items.find_at(&json!({}), &snapshot).unwrap()), (0..300).collect::<Vec<_>>());
+
+    // A grown document moves, and then stays where it moved to
+    items.update_one(&json!({"n": 5}), &json!({"$set": {"pad": "x".repeat(500)}})).unwrap();
+    let after = numbers(other.find(&json!({})).unwrap());
+    assert_eq!(after.len(), 300);
+    assert_eq!(numbers(items.find(&json!({})).unwrap()), after);
+    assert_eq!(numbers(db.collection("items").unwrap().find(&json!({})).unwrap()), after);
+}
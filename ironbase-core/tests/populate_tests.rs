@@ -0,0 +1,42 @@
+// find_with_populate: resolving _id references from other collections
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, Populate};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[test]
+fn test_find_with_populate() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    let tags = db.collection("tags").unwrap();
+    let orders = db.collection("orders").unwrap();
+
+    let ann = users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+    let red = tags.insert_one(doc(json!({"label": "red"}))).unwrap();
+    let blue = tags.insert_one(doc(json!({"label": "blue"}))).unwrap();
+    orders.insert_one(doc(json!({"n": 1, "user_id": ann, "tags": [red, 99, blue]}))).unwrap();
+    orders.insert_one(doc(json!({"n": 2, "user_id": 42}))).unwrap();
+    orders.insert_one(doc(json!({"n": 3}))).unwrap();
+
+    let populate: Vec<Populate> = serde_json::from_value(json!([
+        {"field": "user_id", "from": "users", "as": "user"},
+        {"field": "tags", "from": "tags"},
+    ])).unwrap();
+    let mut docs = orders.find_with_populate(&json!({}), &populate).unwrap();
+    docs.sort_by_key(|doc| doc["n"].as_i64());
+
+    assert_eq!(docs[0]["user"]["name"], json!("ann"));
+    assert_eq!(docs[0]["user_id"], json!(1));
+    let labels: Vec<&Value> = docs[0]["tags"].as_array().unwrap().iter().map(|tag| &tag["label"]).collect();
+    assert_eq!(labels, vec![&json!("red"), &json!("blue")]);
+    assert_eq!(docs[1]["user"], Value::Null);
+    assert!(docs[2].get("user").is_none());
+
+    let filtered = orders.find_with_populate(&json!({"n": 1}), &[Populate::new("user_id", "users", "user")]).unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert!(orders.find_with_populate(&json!({}), &[Populate::new("user_id", "nope", "user")]).is_err());
+    assert!(!db.list_collections().contains(&"nope".to_string()));
+}
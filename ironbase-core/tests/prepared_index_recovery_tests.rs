@@ -0,0 +1,110 @@
+// Index files a crash left prepared (.idx.tmp) are committed or deleted on open
+use ironbase_core::index::{BPlusTree, IndexFileStamp, IndexKey};
+use ironbase_core::{CollectionCore, DatabaseCore};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn age(value: i64) -> HashMap<String, serde_json::Value> {
+    HashMap::from([("age".to_string(), json!(value))])
+}
+
+/// The first half of commit_transaction_with_indexes, on the index of
+/// `users`: the transaction's insert applied and its file prepared
+fn insert_prepared(db: &DatabaseCore, users: &CollectionCore, value: i64, idx_path: &Path) -> u64 {
+    let tx = db.begin_transaction();
+    let id = db.insert_one_tx("users", age(value), tx).unwrap();
+    let mut indexes = users.indexes.write();
+    let index = indexes.get_btree_index_mut("users_age").unwrap();
+    index.insert(IndexKey::from(&json!(value)), id).unwrap();
+    index.prepare_transaction_changes(&idx_path.to_path_buf(), tx).unwrap();
+    tx
+}
+
+/// Both halves, on the index of `users`
+fn insert_committed(db: &DatabaseCore, users: &CollectionCore, value: i64, idx_path: &Path) {
+    let tx = insert_prepared(db, users, value, idx_path);
+    db.commit_transaction(tx).unwrap();
+    let idx_path = idx_path.to_path_buf();
+    BPlusTree::commit_prepared_changes(&idx_path.with_extension("idx.tmp"), &idx_path).unwrap();
+}
+
+fn index_keys(path: &Path) -> u64 {
+    let report = DatabaseCore::inspect(path).unwrap();
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+    report.index_files.iter().find(|index| index.index == "users_age").unwrap().keys
+}
+
+#[test]
+fn test_prepared_index_of_a_committed_transaction_is_committed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let idx_path = temp_dir.path().join("test.users_age.idx");
+    let temp_path = temp_dir.path().join("test.users_age.idx.tmp");
+    let tx = {
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        users.create_index("age".to_string(), false).unwrap();
+        insert_committed(&db, &users, 30, &idx_path);
+
+        // Killed between the commit and the rename
+        let tx = insert_prepared(&db, &users, 40, &idx_path);
+        db.commit_transaction(tx).unwrap();
+        std::mem::forget((users, db));
+        tx
+    };
+    assert!(temp_path.exists());
+
+    let db = DatabaseCore::open(&path).unwrap();
+    assert!(!temp_path.exists());
+    let stamp = IndexFileStamp::parse(&std::fs::read(&idx_path).unwrap()).unwrap();
+    assert_eq!(stamp.transaction, Some(tx));
+    assert_eq!(index_keys(&path), 2);
+    assert_eq!(db.collection("users").unwrap().count_documents(&json!({"age": {"$gte": 30}})).unwrap(), 2);
+}
+
+#[test]
+fn test_prepared_index_of_an_uncommitted_transaction_is_deleted() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let idx_path = temp_dir.path().join("test.users_age.idx");
+    let temp_path = temp_dir.path().join("test.users_age.idx.tmp");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        users.create_index("age".to_string(), false).unwrap();
+        insert_committed(&db, &users, 30, &idx_path);
+
+        // Killed between the prepare and the commit; the prepared file is a
+        // patch of the index file
+        insert_prepared(&db, &users, 40, &idx_path);
+        std::mem::forget((users, db));
+    }
+    let committed = std::fs::read(&idx_path).unwrap();
+
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        assert!(!temp_path.exists());
+        assert_eq!(std::fs::read(&idx_path).unwrap(), committed);
+        assert_eq!(index_keys(&path), 1);
+        let users = db.collection("users").unwrap();
+        assert_eq!(users.count_documents(&json!({"age": {"$gte": 30}})).unwrap(), 1);
+
+        insert_committed(&db, &users, 50, &idx_path);
+
+        // A whole index file prepared (the first save of a tree) leaves the
+        // index file as it was
+        let fresh = db.collection("users").unwrap();
+        insert_prepared(&db, &fresh, 60, &idx_path);
+        std::mem::forget((users, fresh, db));
+    }
+    let before = std::fs::read(&idx_path).unwrap();
+    assert!(temp_path.exists());
+
+    let db = DatabaseCore::open(&path).unwrap();
+    assert!(!temp_path.exists());
+    assert_eq!(std::fs::read(&idx_path).unwrap(), before);
+    assert_eq!(index_keys(&path), 2);
+    assert_eq!(db.collection("users").unwrap().count_documents(&json!({"age": {"$gte": 30}})).unwrap(), 2);
+}
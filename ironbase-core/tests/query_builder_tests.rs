@@ -0,0 +1,36 @@
+// Fluent query / update builders against a real collection
+use ironbase_core::{DatabaseCore, QueryBuilder, UpdateBuilder};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+#[test]
+fn test_builders_drive_find_and_update() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    for (name, age, city) in [("alice", 30, "NYC"), ("bob", 17, "NYC"), ("carol", 40, "LA")] {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), json!(name));
+        fields.insert("age".to_string(), json!(age));
+        fields.insert("city".to_string(), json!(city));
+        fields.insert("visits".to_string(), json!(0));
+        users.insert_one(fields).unwrap();
+    }
+
+    let adults_in_nyc = QueryBuilder::field("age").gt(18).and(QueryBuilder::field("city").eq("NYC")).build();
+    let found = users.find(&adults_in_nyc).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["name"], "alice");
+
+    let young_or_la = QueryBuilder::field("age").lt(18).or(QueryBuilder::field("city").eq("LA")).build();
+    assert_eq!(users.count_documents(&young_or_la).unwrap(), 2);
+
+    let update = UpdateBuilder::set("status", "vip").inc("visits", 1).build();
+    assert_eq!(users.update_many(&young_or_la, &update).unwrap().modified_count, 2);
+
+    let vips = users.find(&QueryBuilder::field("status").eq("vip").build()).unwrap();
+    assert_eq!(vips.len(), 2);
+    assert!(vips.iter().all(|doc| doc["visits"] == 1));
+}
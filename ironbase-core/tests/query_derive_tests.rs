@@ -0,0 +1,63 @@
+// #[derive(Queryable)] typed query builders (requires the `derive` feature)
+#![cfg(feature = "derive")]
+
+use ironbase_core::{Collection, DatabaseCore, DocumentId, Queryable};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+struct User {
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    id: Option<DocumentId>,
+    name: String,
+    age: u32,
+    city: Option<String>,
+    #[serde(rename = "labels", default)]
+    tags: Vec<String>,
+    #[query(skip)]
+    #[serde(default)]
+    notes: String,
+}
+
+#[test]
+fn test_generated_builder_produces_json() {
+    let query = User::query().age().gte(18u32).city().eq("NYC").build();
+    assert_eq!(query, json!({"age": {"$gte": 18}, "city": "NYC"}));
+
+    // Stored names follow serde renames; Vec<T> fields compare against T
+    let query: Value = User::query().tags().eq("admin").id().is_in([DocumentId::Int(1), DocumentId::Int(2)]).into();
+    assert_eq!(query, json!({"labels": "admin", "_id": {"$in": [1, 2]}}));
+
+    assert_eq!(User::query().build(), json!({}));
+}
+
+#[test]
+fn test_generated_builder_queries_a_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users: Collection<User> = db.typed_collection("users").unwrap();
+
+    for (name, age, city) in [("alice", 30, Some("NYC")), ("bob", 17, Some("NYC")), ("carol", 45, None)] {
+        users.insert_one(&User {
+            id: None,
+            name: name.to_string(),
+            age,
+            city: city.map(str::to_string),
+            tags: vec![],
+            notes: String::new(),
+        }).unwrap();
+    }
+
+    let adults_in_nyc = users.find(&User::query().age().gte(18u32).city().eq("NYC").build()).unwrap();
+    assert_eq!(adults_in_nyc.len(), 1);
+    assert_eq!(adults_in_nyc[0].name, "alice");
+
+    let count = users.count_documents(&User::query().name().is_in(["alice", "carol"]).build()).unwrap();
+    assert_eq!(count, 2);
+
+    // Range on one field
+    let between = users.find(&User::query().age().gt(20u32).age().lt(40u32).build()).unwrap();
+    assert_eq!(between.len(), 1);
+    assert_eq!(between[0].name, "alice");
+}
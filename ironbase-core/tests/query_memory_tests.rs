@@ -0,0 +1,146 @@
+// Per-operation memory limits: scans, finds and aggregations
+use ironbase_core::{DatabaseCore, FindOptions, MongoLiteError};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn populate(db: &DatabaseCore) -> ironbase_core::CollectionCore {
+    let users = db.collection("users").unwrap();
+    for i in 0..50 {
+        let mut fields = HashMap::new();
+        fields.insert("seq".to_string(), json!(i));
+        fields.insert("group".to_string(), json!(i % 5));
+        fields.insert("bio".to_string(), json!("x".repeat(1000)));
+        users.insert_one(fields).unwrap();
+    }
+    users
+}
+
+fn exceeded<T: std::fmt::Debug>(result: ironbase_core::Result<T>) -> bool {
+    matches!(result, Err(MongoLiteError::QueryExceededMemoryLimit { .. }))
+}
+
+#[test]
+fn test_database_limit_aborts_scans() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    db.set_query_memory_limit(Some(10_000));
+    assert!(exceeded(users.find(&json!({"seq": {"$gte": 0}}))));
+    assert!(exceeded(users.count_documents(&json!({}))));
+    assert!(exceeded(users.update_many(&json!({}), &json!({"$set": {"seen": true}}))));
+    assert!(exceeded(users.delete_many(&json!({"seq": {"$lt": 10}}))));
+
+    // Nothing was written by the aborted operations
+    db.set_query_memory_limit(None);
+    assert_eq!(users.count_documents(&json!({"seen": true})).unwrap(), 0);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 50);
+}
+
+#[test]
+fn test_per_query_limit_overrides_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let small = FindOptions::new().with_max_memory(10_000);
+    assert!(exceeded(users.find_with_options(&json!({"seq": {"$gte": 0}}), small)));
+
+    db.set_query_memory_limit(Some(10_000));
+    let large = FindOptions::new().with_max_memory(10_000_000).with_limit(5);
+    assert_eq!(users.find_with_options(&json!({"seq": {"$gte": 0}}), large).unwrap().len(), 5);
+}
+
+#[test]
+fn test_aggregation_limit() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let pipeline = json!([
+        {"$group": {"_id": "$group", "count": {"$sum": 1}}}
+    ]);
+
+    db.set_query_memory_limit(Some(10_000));
+    assert!(exceeded(users.aggregate(&pipeline)));
+
+    db.set_query_memory_limit(Some(10_000_000));
+    assert_eq!(users.aggregate(&pipeline).unwrap().len(), 5);
+}
+
+#[test]
+fn test_streaming_aggregation_holds_only_results() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+    db.set_query_memory_limit(Some(10_000));
+
+    // 50 KB of bios stream past $match / $project / $limit
+    let streamed = json!([
+        {"$match": {"group": 3}},
+        {"$project": {"seq": 1}},
+        {"$limit": 4}
+    ]);
+    let results = users.aggregate(&streamed).unwrap();
+    let seqs: Vec<_> = results.iter().map(|doc| doc["seq"].as_i64().unwrap()).collect();
+    assert_eq!(seqs, vec![3, 8, 13, 18]);
+
+    // $sort has to hold every document it sorts
+    let sorted = json!([{"$sort": {"seq": -1}}, {"$project": {"seq": 1}}, {"$limit": 4}]);
+    assert!(exceeded(users.aggregate(&sorted)));
+}
+
+#[test]
+fn test_projection_pushdown_holds_only_projected_fields() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    // 50 bios take 50 KB; the scan keeps only seq, group and _id
+    let projection = HashMap::from([("seq".to_string(), 1)]);
+    let options = FindOptions::new()
+        .with_projection(projection.clone())
+        .with_sort(vec![("seq".to_string(), -1)])
+        .with_max_memory(10_000);
+    let docs = users.find_with_options(&json!({"group": 2}), options).unwrap();
+    assert_eq!(docs.len(), 10);
+    assert_eq!(docs[0], json!({"_id": docs[0]["_id"], "seq": 47}));
+    assert_eq!(docs[9]["seq"], 2);
+
+    // Without an inclusion projection the whole documents are needed
+    let full = FindOptions::new().with_max_memory(10_000);
+    assert!(exceeded(users.find_with_options(&json!({"group": 2}), full)));
+    let filter_on_bio = FindOptions::new().with_projection(projection).with_max_memory(10_000);
+    assert!(exceeded(users.find_with_options(&json!({"bio": {"$exists": true}}), filter_on_bio)));
+}
+
+#[test]
+fn test_sort_with_limit_holds_only_first_documents() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+    db.set_query_memory_limit(Some(10_000));
+
+    // The latest 3 of 50 full documents: a top-k scan, not a sort of all of them
+    let latest = FindOptions::new().with_sort(vec![("seq".to_string(), -1)]).with_skip(1).with_limit(3);
+    let docs = users.find_with_options(&json!({"seq": {"$gte": 0}}), latest).unwrap();
+    let seqs: Vec<_> = docs.iter().map(|doc| doc["seq"].as_i64().unwrap()).collect();
+    assert_eq!(seqs, vec![48, 47, 46]);
+    assert_eq!(docs[0]["bio"], "x".repeat(1000));
+
+    // Ties keep natural order, as a full sort leaves them
+    let first = FindOptions::new().with_sort(vec![("group".to_string(), 1)]).with_limit(4);
+    let docs = users.find_with_options(&json!({}), first).unwrap();
+    let seqs: Vec<_> = docs.iter().map(|doc| doc["seq"].as_i64().unwrap()).collect();
+    assert_eq!(seqs, vec![0, 5, 10, 15]);
+
+    // $sort directly followed by $limit fuses into the same selection
+    let pipeline = json!([{"$sort": {"seq": 1}}, {"$limit": 2}]);
+    let results = users.aggregate(&pipeline).unwrap();
+    assert_eq!((results[0]["seq"].clone(), results[1]["seq"].clone()), (json!(0), json!(1)));
+
+    // Without a limit every match is sorted
+    let all = FindOptions::new().with_sort(vec![("seq".to_string(), -1)]);
+    assert!(exceeded(users.find_with_options(&json!({}), all)));
+}
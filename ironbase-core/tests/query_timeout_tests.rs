@@ -0,0 +1,58 @@
+// Per-operation time limits and cancellation: finds and aggregations
+use ironbase_core::{CancellationToken, DatabaseCore, FindOptions, MongoLiteError, ReadConcern};
+use serde_json::json;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn populate(db: &DatabaseCore) -> ironbase_core::CollectionCore {
+    let users = db.collection("users").unwrap();
+    for i in 0..200 {
+        let mut fields = HashMap::new();
+        fields.insert("seq".to_string(), json!(i));
+        fields.insert("group".to_string(), json!(i % 5));
+        users.insert_one(fields).unwrap();
+    }
+    users
+}
+
+fn timed_out<T: std::fmt::Debug>(result: ironbase_core::Result<T>) -> bool {
+    matches!(result, Err(MongoLiteError::OperationTimedOut { max_time_ms: 0 }))
+}
+
+#[test]
+fn test_expired_time_limit_aborts_queries() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+    users.create_index("seq".to_string(), false).unwrap();
+
+    // Scans, index plans, index-ordered reads and snapshot reads all check the deadline
+    let expired = || FindOptions::new().with_max_time_ms(0);
+    assert!(timed_out(users.find_with_options(&json!({"group": 1}), expired())));
+    assert!(timed_out(users.find_with_options(&json!({"seq": {"$gte": 10}}), expired())));
+    assert!(timed_out(users.find_with_options(&json!({"group": 1}), expired().with_sort(vec![("seq".to_string(), -1)]))));
+    assert!(timed_out(users.find_with_options(&json!({}), expired().with_read_concern(ReadConcern::Snapshot))));
+    assert!(timed_out(users.aggregate_with_options(&json!([{"$group": {"_id": "$group"}}]), &expired())));
+
+    // A generous limit changes nothing
+    let generous = FindOptions::new().with_max_time_ms(60_000);
+    assert_eq!(users.find_with_options(&json!({"group": 1}), generous).unwrap().len(), 40);
+}
+
+#[test]
+fn test_cancellation_token() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = populate(&db);
+
+    let token = CancellationToken::new();
+    let options = FindOptions::new().with_cancellation(token.clone());
+    assert_eq!(users.find_with_options(&json!({"group": 2}), options.clone()).unwrap().len(), 40);
+
+    // Cancelled from another thread: every later check fails
+    std::thread::spawn(move || token.cancel()).join().unwrap();
+    let result = users.find_with_options(&json!({"group": 2}), options.clone());
+    assert!(matches!(result, Err(MongoLiteError::Cancelled(_))));
+    let result = users.aggregate_with_options(&json!([{"$match": {"group": 2}}]), &options);
+    assert!(matches!(result, Err(MongoLiteError::Cancelled(_))));
+}
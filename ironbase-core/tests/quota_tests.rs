@@ -0,0 +1,63 @@
+// Per-collection quotas: document count and live bytes
+mod common;
+
+use common::doc;
+use ironbase_core::{CollectionQuota, DatabaseCore, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_document_quota_persists_and_frees_up() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let logs = db.collection("logs").unwrap();
+        logs.set_quota(CollectionQuota { max_documents: Some(3), max_bytes: None }).unwrap();
+        logs.insert_many(vec![doc(json!({"n": 1})), doc(json!({"n": 2}))]).unwrap();
+
+        // A batch that does not fit is rejected as a whole
+        let result = logs.insert_many(vec![doc(json!({"n": 3})), doc(json!({"n": 4}))]);
+        assert!(matches!(result, Err(MongoLiteError::QuotaExceeded(_))));
+        assert_eq!(logs.count_documents(&json!({})).unwrap(), 2);
+        logs.insert_one(doc(json!({"n": 3}))).unwrap();
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let logs = db.collection("logs").unwrap();
+    assert_eq!(logs.quota().max_documents, Some(3));
+    assert!(matches!(logs.insert_one(doc(json!({"n": 4}))), Err(MongoLiteError::QuotaExceeded(_))));
+
+    // Transactions are checked when the insert is staged
+    let tx_id = db.begin_transaction();
+    assert!(db.insert_one_tx("logs", doc(json!({"n": 4})), tx_id).is_err());
+    db.rollback_transaction(tx_id).unwrap();
+
+    // Deletes make room, updates are not limited, other collections are unaffected
+    logs.delete_one(&json!({"n": 1})).unwrap();
+    logs.insert_one(doc(json!({"n": 4}))).unwrap();
+    logs.update_many(&json!({}), &json!({"$set": {"tag": "x"}})).unwrap();
+    db.collection("other").unwrap().insert_one(doc(json!({"n": 1}))).unwrap();
+
+    logs.set_quota(CollectionQuota::default()).unwrap();
+    assert!(logs.quota().is_unlimited());
+    logs.insert_one(doc(json!({"n": 5}))).unwrap();
+}
+
+#[test]
+fn test_byte_quota() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let blobs = db.collection("blobs").unwrap();
+    blobs.set_quota(CollectionQuota { max_documents: None, max_bytes: Some(1_500) }).unwrap();
+
+    let blob = || doc(json!({"data": "x".repeat(500)}));
+    blobs.insert_one(blob()).unwrap();
+    blobs.insert_one(blob()).unwrap();
+    assert!(matches!(blobs.insert_one(blob()), Err(MongoLiteError::QuotaExceeded(_))));
+    blobs.insert_one(doc(json!({"small": true}))).unwrap();
+
+    let stats = blobs.stats().unwrap();
+    assert!(stats["live_bytes"].as_u64().unwrap() <= 1_500);
+}
@@ -0,0 +1,111 @@
+// Read concern levels on find / aggregate and reads inside transactions
+use ironbase_core::{DatabaseCore, FindOptions, MongoLiteError, ReadConcern};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn open_with_accounts(temp_dir: &TempDir) -> Arc<DatabaseCore> {
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let accounts = db.collection("accounts").unwrap();
+    for name in ["a", "b"] {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_string(), json!(name));
+        fields.insert("balance".to_string(), json!(100));
+        accounts.insert_one(fields).unwrap();
+    }
+    Arc::new(db)
+}
+
+fn total(docs: &[Value]) -> i64 {
+    docs.iter().map(|doc| doc["balance"].as_i64().unwrap()).sum()
+}
+
+#[test]
+fn test_read_concern_levels_return_committed_data() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+    let accounts = db.collection("accounts").unwrap();
+
+    for level in ["local", "snapshot", "linearizable"] {
+        let read_concern: ReadConcern = level.parse().unwrap();
+        let options = FindOptions::new()
+            .with_read_concern(read_concern)
+            .with_sort(vec![("name".to_string(), -1)])
+            .with_limit(1);
+        let docs = accounts.find_with_options(&json!({"balance": 100}), options).unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0]["name"], "b", "read concern {}", level);
+
+        let options = FindOptions::new().with_read_concern(read_concern);
+        let grouped = accounts.aggregate_with_options(
+            &json!([{"$group": {"_id": null, "total": {"$sum": "$balance"}}}]),
+            &options,
+        ).unwrap();
+        assert_eq!(grouped[0]["total"], 200);
+    }
+
+    assert!(matches!("majority".parse::<ReadConcern>(), Err(MongoLiteError::InvalidQuery(_))));
+}
+
+#[test]
+fn test_snapshot_reads_see_whole_transactions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+
+    // Transfers keep the total at 200; a snapshot never sees half of one
+    let writer = {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            for i in 0..50 {
+                let tx = db.begin_transaction();
+                db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 100 - i}), tx).unwrap();
+                db.update_one_tx("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 100 + i}), tx).unwrap();
+                db.commit_transaction(tx).unwrap();
+            }
+        })
+    };
+
+    let accounts = db.collection("accounts").unwrap();
+    let snapshot = FindOptions::new().with_read_concern(ReadConcern::Snapshot);
+    for _ in 0..50 {
+        assert_eq!(total(&accounts.find_with_options(&json!({}), snapshot.clone()).unwrap()), 200);
+    }
+    writer.join().unwrap();
+    assert_eq!(total(&accounts.find_with_options(&json!({}), snapshot).unwrap()), 200);
+}
+
+#[test]
+fn test_find_tx_sees_own_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = open_with_accounts(&temp_dir);
+    let accounts = db.collection("accounts").unwrap();
+
+    let tx = db.begin_transaction();
+    db.update_one_tx("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 40}), tx).unwrap();
+    db.delete_one_tx("accounts", &json!({"name": "b"}), tx).unwrap();
+    let mut fields = HashMap::new();
+    fields.insert("name".to_string(), json!("c"));
+    fields.insert("balance".to_string(), json!(60));
+    db.insert_one_tx("accounts", fields, tx).unwrap();
+
+    let linearizable = FindOptions::new()
+        .with_read_concern(ReadConcern::Linearizable)
+        .with_sort(vec![("name".to_string(), 1)]);
+    let inside = db.find_tx("accounts", &json!({}), linearizable.clone(), tx).unwrap();
+    let names: Vec<&str> = inside.iter().map(|doc| doc["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["a", "c"]);
+    assert_eq!(total(&inside), 100);
+
+    // The query applies to the transaction's versions too
+    let rich = db.find_tx("accounts", &json!({"balance": {"$gt": 50}}), FindOptions::new(), tx).unwrap();
+    assert_eq!(rich.len(), 1);
+    assert_eq!(rich[0]["name"], "c");
+
+    // Nobody else sees them before commit
+    assert_eq!(total(&accounts.find(&json!({})).unwrap()), 200);
+
+    db.commit_transaction(tx).unwrap();
+    let after = accounts.find_with_options(&json!({}), linearizable).unwrap();
+    assert_eq!(after.iter().map(|doc| doc["name"].as_str().unwrap()).collect::<Vec<_>>(), vec!["a", "c"]);
+}
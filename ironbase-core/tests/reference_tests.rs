@@ -0,0 +1,87 @@
+// Reference constraints: checked on write, restrict or cascade on delete
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError, OnDelete};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn is_violation<T: std::fmt::Debug>(result: ironbase_core::Result<T>) -> bool {
+    matches!(result, Err(MongoLiteError::ReferenceViolation(_)))
+}
+
+#[test]
+fn test_reference_checked_on_write_and_restricts_delete() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let users = db.collection("users").unwrap();
+        let orders = db.collection("orders").unwrap();
+        let ann = users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+        orders.insert_one(doc(json!({"user_id": 99}))).unwrap();
+
+        // Existing documents must already satisfy a new reference
+        assert!(is_violation(orders.add_reference("user_id", "users", OnDelete::Restrict)));
+        orders.delete_many(&json!({})).unwrap();
+        assert!(orders.add_reference("user_id", "nope", OnDelete::Restrict).is_err());
+        orders.add_reference("user_id", "users", OnDelete::Restrict).unwrap();
+
+        orders.insert_one(doc(json!({"user_id": ann, "total": 5}))).unwrap();
+        orders.insert_one(doc(json!({"note": "no user"}))).unwrap();
+        assert!(is_violation(orders.insert_one(doc(json!({"user_id": 2})))));
+        assert!(is_violation(orders.update_one(&json!({"total": 5}), &json!({"$set": {"user_id": 2}}))));
+        assert_eq!(orders.count_documents(&json!({"user_id": 1})).unwrap(), 1);
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let users = db.collection("users").unwrap();
+    let orders = db.collection("orders").unwrap();
+    assert_eq!(orders.references().len(), 1);
+    assert!(is_violation(users.delete_one(&json!({"name": "ann"}))));
+    assert!(is_violation(users.delete_many(&json!({}))));
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 1);
+
+    let tx_id = db.begin_transaction();
+    assert!(db.insert_one_tx("orders", doc(json!({"user_id": 7})), tx_id).is_err());
+    db.rollback_transaction(tx_id).unwrap();
+
+    // Once nothing references it, the document can go
+    orders.delete_many(&json!({"user_id": 1})).unwrap();
+    users.delete_one(&json!({"name": "ann"})).unwrap();
+
+    assert!(orders.drop_reference("user_id").unwrap());
+    orders.insert_one(doc(json!({"user_id": 42}))).unwrap();
+}
+
+#[test]
+fn test_cascading_delete() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    let orders = db.collection("orders").unwrap();
+    let lines = db.collection("lines").unwrap();
+    orders.add_reference("user_id", "users", OnDelete::Cascade).unwrap();
+    lines.add_reference("order_id", "orders", OnDelete::Cascade).unwrap();
+
+    let ann = users.insert_one(doc(json!({"name": "ann"}))).unwrap();
+    let bob = users.insert_one(doc(json!({"name": "bob"}))).unwrap();
+    let first = orders.insert_one(doc(json!({"user_id": ann}))).unwrap();
+    orders.insert_one(doc(json!({"user_id": bob}))).unwrap();
+    lines.insert_one(doc(json!({"order_id": first, "sku": "a"}))).unwrap();
+    lines.insert_one(doc(json!({"order_id": first, "sku": "b"}))).unwrap();
+
+    // Deleting ann takes her order and its lines with it
+    users.delete_one(&json!({"name": "ann"})).unwrap();
+    assert_eq!(orders.count_documents(&json!({})).unwrap(), 1);
+    assert_eq!(lines.count_documents(&json!({})).unwrap(), 0);
+
+    // Self references: deleting a manager and their reports together is fine
+    let staff = db.collection("staff").unwrap();
+    staff.add_reference("manager", "staff", OnDelete::Restrict).unwrap();
+    let boss = staff.insert_one(doc(json!({"name": "boss"}))).unwrap();
+    staff.insert_one(doc(json!({"name": "dev", "manager": boss}))).unwrap();
+    assert!(is_violation(staff.delete_one(&json!({"name": "boss"}))));
+    assert_eq!(staff.delete_many(&json!({})).unwrap().deleted_count, 2);
+}
@@ -0,0 +1,130 @@
+// Database repair (salvage) tests
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, DocumentId};
+use serde_json::json;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use tempfile::TempDir;
+
+fn overwrite(path: &Path, offset: u64, bytes: &[u8]) {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+#[test]
+fn test_repair_healthy_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let repaired_path = temp_dir.path().join("repaired.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        for i in 0..10 {
+            users.insert_one(doc(json!({"name": format!("user{}", i), "age": i}))).unwrap();
+        }
+        users.create_index("age".to_string(), false).unwrap();
+        users.update_one(&json!({"name": "user1"}), &json!({"$set": {"age": 100}})).unwrap();
+        users.delete_one(&json!({"name": "user2"})).unwrap();
+        db.collection("empty").unwrap();
+        db.flush().unwrap();
+    }
+
+    let stats = DatabaseCore::repair(&db_path, &repaired_path).unwrap();
+    assert!(stats.metadata_recovered);
+    assert_eq!(stats.documents_recovered, 9);
+    assert_eq!(stats.collections_recovered, 2);
+    assert_eq!(stats.corrupted_blocks, 0);
+
+    let db = DatabaseCore::open(&repaired_path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 9);
+    assert_eq!(users.find_one(&json!({"name": "user1"})).unwrap().unwrap()["age"], 100);
+    assert!(users.find_one(&json!({"name": "user2"})).unwrap().is_none());
+    assert!(users.list_indexes().contains(&"users_age".to_string()));
+    assert!(users.validate(true).unwrap().valid);
+
+    // last_id survives, so new inserts don't reuse ids
+    let new_id = users.insert_one(doc(json!({"name": "new"}))).unwrap();
+    assert_eq!(new_id, DocumentId::Int(11));
+
+    assert!(db.list_collections().contains(&"empty".to_string()));
+}
+
+#[test]
+fn test_repair_corrupted_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let repaired_path = temp_dir.path().join("repaired.mlite");
+
+    {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        for i in 0..5 {
+            users.insert_one(doc(json!({"n": i}))).unwrap();
+        }
+        db.flush().unwrap();
+    }
+
+    overwrite(&db_path, 0, b"GARBAGE!GARBAGE!");
+    assert!(DatabaseCore::open(&db_path).is_err());
+
+    let stats = DatabaseCore::repair(&db_path, &repaired_path).unwrap();
+    assert!(!stats.metadata_recovered);
+    assert_eq!(stats.documents_recovered, 5);
+
+    let db = DatabaseCore::open(&repaired_path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 5);
+}
+
+#[test]
+fn test_repair_skips_corrupted_record() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let repaired_path = temp_dir.path().join("repaired.mlite");
+
+    let corrupt_offset = {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        let mut ids = Vec::new();
+        for i in 0..5 {
+            ids.push(users.insert_one(doc(json!({"n": i}))).unwrap());
+        }
+        db.flush().unwrap();
+
+        let storage = users.storage.read();
+        let meta = storage.get_collection_meta("users").unwrap();
+        meta.document_catalog[&ids[2]]
+    };
+
+    // Smash the length prefix and the start of the JSON payload
+    overwrite(&db_path, corrupt_offset, &[0xFF; 8]);
+
+    let stats = DatabaseCore::repair(&db_path, &repaired_path).unwrap();
+    assert_eq!(stats.documents_recovered, 4);
+    assert_eq!(stats.corrupted_blocks, 1);
+    assert!(stats.bytes_skipped > 0);
+
+    let db = DatabaseCore::open(&repaired_path).unwrap();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 4);
+    assert!(users.find_one(&json!({"n": 2})).unwrap().is_none());
+    assert!(users.find_one(&json!({"n": 3})).unwrap().is_some());
+}
+
+#[test]
+fn test_repair_refuses_existing_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+    let repaired_path = temp_dir.path().join("repaired.mlite");
+
+    DatabaseCore::open(&db_path).unwrap().flush().unwrap();
+    std::fs::write(&repaired_path, b"keep me").unwrap();
+
+    assert!(DatabaseCore::repair(&db_path, &repaired_path).is_err());
+    assert_eq!(std::fs::read(&repaired_path).unwrap(), b"keep me");
+}
@@ -0,0 +1,145 @@
+// WAL replication (leader -> follower) tests
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, Follower, ReplicationPosition, MongoLiteError};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn insert_tx(db: &DatabaseCore, collection: &str, value: serde_json::Value) {
+    db.collection(collection).unwrap();
+    let tx_id = db.begin_transaction();
+    db.insert_one_tx(collection, doc(value), tx_id).unwrap();
+    db.commit_transaction(tx_id).unwrap();
+}
+
+#[test]
+fn test_follower_applies_committed_transactions() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let follower_db = DatabaseCore::open(temp_dir.path().join("follower.mlite")).unwrap();
+
+    insert_tx(&leader, "users", json!({"name": "alice"}));
+    insert_tx(&leader, "users", json!({"name": "bob"}));
+
+    let mut follower = Follower::new(leader.replication_source().wal_path(), ReplicationPosition::start());
+    assert_eq!(follower.catch_up(&follower_db).unwrap(), 2);
+
+    let users = follower_db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 2);
+    assert!(users.find_one(&json!({"name": "bob"})).unwrap().is_some());
+
+    // Nothing new
+    assert_eq!(follower.pull(&follower_db, 10).unwrap(), 0);
+}
+
+#[test]
+fn test_open_handles_see_replicated_writes_through_indexes() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let follower_db = DatabaseCore::open(temp_dir.path().join("follower.mlite")).unwrap();
+    let mut follower = Follower::new(leader.replication_source().wal_path(), ReplicationPosition::start());
+
+    insert_tx(&leader, "users", json!({"name": "alice", "age": 30}));
+    insert_tx(&leader, "users", json!({"name": "bob", "age": 40}));
+    follower.catch_up(&follower_db).unwrap();
+
+    // Opened before the update and delete are applied
+    let users = follower_db.collection("users").unwrap();
+    users.create_index("age".to_string(), false).unwrap();
+    assert_eq!(users.find(&json!({"age": 30})).unwrap().len(), 1);
+
+    let tx_id = leader.begin_transaction();
+    leader.update_one_tx("users", &json!({"name": "alice"}), json!({"name": "alice", "age": 31}), tx_id).unwrap();
+    leader.delete_one_tx("users", &json!({"name": "bob"}), tx_id).unwrap();
+    leader.commit_transaction(tx_id).unwrap();
+    insert_tx(&leader, "users", json!({"name": "carol", "age": 40}));
+    assert_eq!(follower.catch_up(&follower_db).unwrap(), 2);
+
+    assert_eq!(users.explain(&json!({"age": 31})).unwrap()["queryPlan"], "IndexScan");
+    let found = users.find(&json!({"age": 31})).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["name"], "alice");
+    assert!(users.find(&json!({"age": 30})).unwrap().is_empty());
+    let found = users.find(&json!({"age": 40})).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["name"], "carol");
+}
+
+#[test]
+fn test_follower_skips_aborted_transactions() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let follower_db = DatabaseCore::open(temp_dir.path().join("follower.mlite")).unwrap();
+
+    leader.collection("users").unwrap();
+    let tx_id = leader.begin_transaction();
+    leader.insert_one_tx("users", doc(json!({"name": "ghost"})), tx_id).unwrap();
+    leader.rollback_transaction(tx_id).unwrap();
+    insert_tx(&leader, "users", json!({"name": "alice"}));
+
+    let mut follower = Follower::new(leader.replication_source().wal_path(), ReplicationPosition::start());
+    assert_eq!(follower.catch_up(&follower_db).unwrap(), 1);
+
+    let users = follower_db.collection("users").unwrap();
+    assert!(users.find_one(&json!({"name": "ghost"})).unwrap().is_none());
+    assert!(users.find_one(&json!({"name": "alice"})).unwrap().is_some());
+}
+
+#[test]
+fn test_follower_resumes_from_token() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let follower_db = DatabaseCore::open(temp_dir.path().join("follower.mlite")).unwrap();
+    let wal_path = leader.replication_source().wal_path().to_path_buf();
+
+    insert_tx(&leader, "users", json!({"n": 1}));
+
+    let token = {
+        let mut follower = Follower::new(&wal_path, ReplicationPosition::start());
+        follower.catch_up(&follower_db).unwrap();
+        follower.position().to_token()
+    };
+
+    insert_tx(&leader, "users", json!({"n": 2}));
+    insert_tx(&leader, "users", json!({"n": 3}));
+
+    let position = ReplicationPosition::from_token(&token).unwrap();
+    let mut follower = Follower::new(&wal_path, position);
+    assert_eq!(follower.pull(&follower_db, 1).unwrap(), 1);
+    assert_eq!(follower.pull(&follower_db, 10).unwrap(), 1);
+
+    let users = follower_db.collection("users").unwrap();
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 3);
+}
+
+#[test]
+fn test_incomplete_tail_is_not_applied() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let source = leader.replication_source();
+
+    insert_tx(&leader, "users", json!({"n": 1}));
+    let full_len = std::fs::metadata(source.wal_path()).unwrap().len();
+
+    // Simulate a transaction still being written: chop off the commit marker
+    insert_tx(&leader, "users", json!({"n": 2}));
+    let wal = std::fs::read(source.wal_path()).unwrap();
+    std::fs::write(source.wal_path(), &wal[..wal.len() - 5]).unwrap();
+
+    let batch = source.read_batch(ReplicationPosition::start(), 10).unwrap();
+    assert_eq!(batch.transactions.len(), 1);
+    assert_eq!(batch.next_position.offset, full_len);
+}
+
+#[test]
+fn test_position_beyond_truncated_wal() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader = DatabaseCore::open(temp_dir.path().join("leader.mlite")).unwrap();
+    let source = leader.replication_source();
+
+    let result = source.read_batch(ReplicationPosition { offset: 1_000_000 }, 10);
+    assert!(matches!(result, Err(MongoLiteError::ReplicationError(_))));
+
+    assert!(ReplicationPosition::from_token("bogus").is_err());
+}
@@ -0,0 +1,98 @@
+// Causal consistency session (LSN) tests
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_writes_advance_lsn() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+
+    let start = db.current_lsn();
+    users.insert_one(doc(json!({"n": 1}))).unwrap();
+    let after_insert = db.current_lsn();
+    assert!(after_insert > start);
+
+    users.update_one(&json!({"n": 1}), &json!({"$set": {"n": 2}})).unwrap();
+    assert!(db.current_lsn() > after_insert);
+
+    // Reads don't advance the clock
+    let before_read = db.current_lsn();
+    users.find(&json!({})).unwrap();
+    assert_eq!(db.current_lsn(), before_read);
+}
+
+#[test]
+fn test_session_read_your_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users = db.collection("users").unwrap();
+    let session = db.start_session();
+
+    let (_id, lsn) = session.write(|| users.insert_one(doc(json!({"name": "alice"})))).unwrap();
+    assert_eq!(session.operation_time(), lsn);
+
+    let found = session.read(|| users.find_one(&json!({"name": "alice"}))).unwrap();
+    assert!(found.is_some());
+}
+
+#[test]
+fn test_read_after_waits_for_other_thread() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap());
+    db.collection("users").unwrap();
+
+    let target = db.current_lsn() + 1;
+
+    let writer = {
+        let db = Arc::clone(&db);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            db.collection("users").unwrap().insert_one(doc(json!({"name": "bob"}))).unwrap();
+        })
+    };
+
+    let reader = db.start_session();
+    let users = db.collection("users").unwrap();
+    let count = reader.read_after(target, || users.count_documents(&json!({}))).unwrap();
+    assert_eq!(count, 1);
+    assert!(reader.operation_time() >= target);
+
+    writer.join().unwrap();
+}
+
+#[test]
+fn test_read_after_times_out() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let session = db.start_session().with_read_timeout(Duration::from_millis(20));
+
+    let result = session.read_after(db.current_lsn() + 100, || Ok(()));
+    assert!(matches!(result, Err(MongoLiteError::Timeout(_))));
+}
+
+#[test]
+fn test_lsn_survives_reopen() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.mlite");
+
+    let lsn = {
+        let db = DatabaseCore::open(&db_path).unwrap();
+        let users = db.collection("users").unwrap();
+        for i in 0..3 {
+            users.insert_one(doc(json!({"n": i}))).unwrap();
+        }
+        db.flush().unwrap();
+        db.current_lsn()
+    };
+
+    let db = DatabaseCore::open(&db_path).unwrap();
+    assert_eq!(db.current_lsn(), lsn);
+    assert!(db.wait_for_lsn(lsn, Duration::from_millis(10)).is_ok());
+}
@@ -0,0 +1,103 @@
+// Durability under injected faults, on the simulated backend (see ironbase_core::testing)
+use ironbase_core::testing::{Fault, SimulatedBackend};
+use ironbase_core::{DatabaseCore, StorageConfig};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+const DOCS: i64 = 5;
+
+fn open(backend: &SimulatedBackend) -> DatabaseCore {
+    let config = StorageConfig::default().with_backend(Arc::new(backend.clone()));
+    DatabaseCore::open_with_config("sim.mlite", &config).unwrap()
+}
+
+fn setup() -> (SimulatedBackend, DatabaseCore) {
+    let backend = SimulatedBackend::new();
+    let db = open(&backend);
+    db.collection("items").unwrap();
+    db.flush().unwrap();
+    (backend, db)
+}
+
+/// Insert n = 0..DOCS, one durable transaction each, until something fails;
+/// returns the n that committed
+fn run_workload(db: &DatabaseCore) -> BTreeSet<i64> {
+    let mut committed = BTreeSet::new();
+    for n in 0..DOCS {
+        let tx = db.begin_transaction();
+        let doc: HashMap<String, serde_json::Value> = [("n".to_string(), json!(n))].into();
+        let result = db.insert_one_tx("items", doc, tx).and_then(|_| db.commit_transaction(tx));
+        if result.is_err() {
+            break;
+        }
+        committed.insert(n);
+    }
+    committed
+}
+
+/// Crash, reopen and return the n found
+fn crash_and_recover(backend: &SimulatedBackend, db: DatabaseCore) -> BTreeSet<i64> {
+    backend.crash();
+    drop(db);
+    let db = open(backend);
+    let items = db.collection("items").unwrap();
+    assert!(items.validate(false).unwrap().issues.is_empty());
+    items.find(&json!({})).unwrap().iter().map(|doc| doc["n"].as_i64().unwrap()).collect()
+}
+
+/// Writes the workload makes after setup()
+fn workload_writes() -> u64 {
+    let (backend, db) = setup();
+    let before = backend.write_count();
+    assert_eq!(run_workload(&db).len() as i64, DOCS);
+    backend.write_count() - before
+}
+
+/// Inject `fault(nth)` at every write of the workload in turn: whatever
+/// committed survives the crash, and nothing but the failed commit is extra
+fn check_every_write(fault: impl Fn(u64) -> Fault) {
+    for nth in 1..=workload_writes() {
+        let (backend, db) = setup();
+        backend.inject(fault(nth));
+        let committed = run_workload(&db);
+        let found = crash_and_recover(&backend, db);
+
+        assert!(found.is_superset(&committed), "write {}: committed {:?}, found {:?}", nth, committed, found);
+        assert!(found.len() <= committed.len() + 1, "write {}: committed {:?}, found {:?}", nth, committed, found);
+    }
+}
+
+#[test]
+fn test_crash_keeps_durable_commits() {
+    let (backend, db) = setup();
+    let committed = run_workload(&db);
+    assert_eq!(crash_and_recover(&backend, db), committed);
+}
+
+#[test]
+fn test_failed_write_at_any_point_recovers() {
+    check_every_write(|nth| Fault::FailWrite { nth });
+}
+
+#[test]
+fn test_torn_write_at_any_point_recovers() {
+    check_every_write(|nth| Fault::TornWrite { nth, keep: 3 });
+}
+
+#[test]
+fn test_failed_sync_fails_the_commit() {
+    let (backend, db) = setup();
+    let tx = db.begin_transaction();
+    db.insert_one_tx("items", [("n".to_string(), json!(0))].into(), tx).unwrap();
+    backend.inject(Fault::FailSync { nth: 1 });
+    assert!(db.commit_transaction(tx).is_err());
+}
+
+#[test]
+fn test_lying_fsync_loses_acknowledged_commits() {
+    let (backend, db) = setup();
+    backend.lie_about_fsync(true);
+    assert_eq!(run_workload(&db).len() as i64, DOCS);
+    assert!(crash_and_recover(&backend, db).is_empty());
+}
@@ -0,0 +1,50 @@
+// analyze_space: bytes by collection and field
+mod common;
+
+use common::doc;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_analyze_space_by_collection_and_field() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("space.mlite")).unwrap();
+
+    let articles = db.collection("articles").unwrap();
+    for i in 0..100 {
+        let body = "x".repeat(if i == 99 { 5000 } else { 500 });
+        articles.insert_one(doc(json!({"title": format!("t{}", i), "body": body}))).unwrap();
+    }
+    articles.update_one(&json!({"title": "t0"}), &json!({"$set": {"title": "first"}})).unwrap();
+    articles.delete_many(&json!({"title": {"$in": ["t1", "t2"]}})).unwrap();
+    db.collection("tags").unwrap().insert_one(doc(json!({"name": "rust"}))).unwrap();
+    db.collection("empty").unwrap();
+
+    let report = db.analyze_space().unwrap();
+    assert_eq!(report.collections.len(), 3);
+    assert_eq!(report.collections[0].name, "articles");
+    assert_eq!(report.collections[2].name, "empty");
+
+    let articles = &report.collections[0];
+    assert_eq!(articles.documents, 98);
+    assert_eq!(articles.tombstones, 2);
+    assert!(articles.tombstone_bytes > 0);
+    // Superseded versions are released to the free-space map
+    assert!(report.free_bytes > 0);
+    assert!(articles.p50_document_size < 600);
+    assert!(articles.max_document_size > 5000);
+    assert!(articles.p50_document_size <= articles.p90_document_size);
+    assert!(articles.average_document_size > articles.p50_document_size as f64);
+    assert_eq!(articles.top_fields[0].field, "body");
+    assert_eq!(articles.top_fields[0].documents, 98);
+    assert!(articles.top_fields[0].bytes > 97 * 500 + 5000);
+
+    let tags = &report.collections[1];
+    assert_eq!(tags.documents, 1);
+    assert_eq!(tags.dead_bytes + tags.tombstone_bytes, 0);
+
+    let accounted = report.metadata_bytes + report.catalog_bytes + report.free_bytes + report.other_bytes
+        + report.collections.iter().map(|c| c.total_bytes()).sum::<u64>();
+    assert_eq!(accounted, report.file_size);
+}
@@ -0,0 +1,50 @@
+// Collection statistics: analyze(), the index choice it drives and the _stats collection
+mod common;
+
+use common::doc;
+use ironbase_core::statistics::STATS_COLLECTION;
+use ironbase_core::DatabaseCore;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn test_analyze_picks_the_most_selective_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    let db = DatabaseCore::open(&path).unwrap();
+    let orders = db.collection("orders").unwrap();
+    orders.create_index("a_status".to_string(), false).unwrap();
+    orders.create_index("sku".to_string(), false).unwrap();
+    let statuses = ["open", "closed", "void"];
+    orders.insert_many((0..2000).map(|n| doc(json!({
+        "a_status": statuses[n % 3],
+        "sku": n,
+    }))).collect()).unwrap();
+
+    let query = json!({"a_status": "open", "sku": {"$gte": 100, "$lt": 110}});
+    assert!(orders.explain(&query).unwrap().get("estimatedDocuments").is_none());
+
+    let statistics = orders.analyze().unwrap();
+    assert_eq!(statistics.documents, 2000);
+    assert_eq!(statistics.sampled, 1000);
+    assert_eq!(statistics.fields["a_status"].distinct, 3);
+
+    let plan = orders.explain(&query).unwrap();
+    assert_eq!(plan["field"], "sku");
+    let estimated = plan["estimatedDocuments"].as_u64().unwrap();
+    assert!((1..=50).contains(&estimated), "estimated {}", estimated);
+    assert_eq!(orders.find(&query).unwrap().len(), 3);
+
+    // Readable from the statistics collection, which list_collections hides
+    let stored = db.collection(STATS_COLLECTION).unwrap().find_one(&json!({"_id": "orders"})).unwrap().unwrap();
+    assert_eq!(stored["documents"], 2000);
+    assert!(!db.list_collections().contains(&STATS_COLLECTION.to_string()));
+    drop(orders);
+    drop(db);
+
+    // Kept across a reopen, dropped with the collection
+    let db = DatabaseCore::open(&path).unwrap();
+    assert_eq!(db.collection("orders").unwrap().explain(&query).unwrap()["field"], "sku");
+    db.drop_collection("orders").unwrap();
+    assert!(db.collection(STATS_COLLECTION).unwrap().find_one(&json!({"_id": "orders"})).unwrap().is_none());
+}
@@ -0,0 +1,73 @@
+// Operation traces: capture on one database, replay on another
+mod common;
+
+use common::doc;
+use ironbase_core::trace::read_trace;
+use ironbase_core::{DatabaseCore, TracedOp};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn capture_workload(db: &DatabaseCore) {
+    let users = db.collection("users").unwrap();
+    users.create_index("email".to_string(), true).unwrap();
+    users.insert_one(doc(json!({"email": "ann@x", "age": 31}))).unwrap();
+    users.insert_many(vec![doc(json!({"email": "bob@x", "age": 25})), doc(json!({"email": "cy@x", "age": 40}))]).unwrap();
+    // Fails on the unique index, in the capture and in the replay
+    assert!(users.insert_one(doc(json!({"email": "ann@x"}))).is_err());
+    users.update_many(&json!({"age": {"$gte": 30}}), &json!({"$inc": {"age": 1}})).unwrap();
+    users.delete_one(&json!({"email": "bob@x"})).unwrap();
+    users.find(&json!({"age": {"$gt": 20}})).unwrap();
+    users.aggregate(&json!([{"$group": {"_id": null, "total": {"$sum": "$age"}}}])).unwrap();
+}
+
+#[test]
+fn test_replay_reproduces_captured_workload() {
+    let temp_dir = TempDir::new().unwrap();
+    let trace_path = temp_dir.path().join("ops.trace");
+
+    let db = DatabaseCore::open(temp_dir.path().join("source.mlite")).unwrap();
+    db.start_trace(&trace_path).unwrap();
+    capture_workload(&db);
+    assert_eq!(db.stop_trace().unwrap(), 8);
+    // Not captured once stopped
+    db.collection("users").unwrap().find(&json!({})).unwrap();
+
+    let entries = read_trace(&trace_path).unwrap();
+    assert_eq!(entries.len(), 8);
+    assert_eq!(entries[0].op, TracedOp::CreateIndex { field: "email".to_string(), unique: true });
+    assert!(!entries[3].ok);
+    assert!(entries.windows(2).all(|pair| pair[0].seq + 1 == pair[1].seq));
+
+    let replica = DatabaseCore::open(temp_dir.path().join("replica.mlite")).unwrap();
+    let stats = replica.replay_trace(&trace_path).unwrap();
+    assert_eq!(stats.operations, 8);
+    assert_eq!(stats.failed, 1);
+    assert_eq!(stats.diverged, 0);
+
+    let sort = |db: &DatabaseCore| {
+        let mut docs = db.collection("users").unwrap().find(&json!({})).unwrap();
+        docs.sort_by_key(|doc| doc["email"].as_str().unwrap().to_string());
+        docs
+    };
+    assert_eq!(sort(&replica), sort(&db));
+}
+
+#[test]
+fn test_replay_reports_divergence() {
+    let temp_dir = TempDir::new().unwrap();
+    let trace_path = temp_dir.path().join("ops.trace");
+
+    let db = DatabaseCore::open(temp_dir.path().join("source.mlite")).unwrap();
+    db.start_trace(&trace_path).unwrap();
+    assert!(db.start_trace(&trace_path).is_err());
+    capture_workload(&db);
+    db.stop_trace().unwrap();
+
+    // The replica already holds one of the emails, so its insert_many fails
+    let replica = DatabaseCore::open(temp_dir.path().join("replica.mlite")).unwrap();
+    let users = replica.collection("users").unwrap();
+    users.insert_one(doc(json!({"email": "cy@x"}))).unwrap();
+    let stats = replica.replay_trace(&trace_path).unwrap();
+    assert_eq!(stats.first_divergence, Some(3));
+    assert!(stats.diverged >= 1);
+}
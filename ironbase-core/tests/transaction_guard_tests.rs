@@ -0,0 +1,53 @@
+// TransactionGuard: transactions begun with DatabaseCore::begin() roll back when dropped
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError, Result};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn transfer(db: &DatabaseCore, amount: i64) -> Result<()> {
+    let tx = db.begin();
+    tx.update_one("accounts", &json!({"name": "a"}), json!({"name": "a", "balance": 100 - amount}))?;
+    if amount > 100 {
+        return Err(MongoLiteError::InvalidQuery("insufficient funds".to_string()));
+    }
+    tx.update_one("accounts", &json!({"name": "b"}), json!({"name": "b", "balance": 100 + amount}))?;
+    tx.commit()
+}
+
+#[test]
+fn test_dropped_guard_rolls_back() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let accounts = db.collection("accounts").unwrap();
+    accounts.insert_many(vec![doc(json!({"name": "a", "balance": 100})), doc(json!({"name": "b", "balance": 100}))]).unwrap();
+    let balance = |name: &str| accounts.find_one(&json!({"name": name})).unwrap().unwrap()["balance"].clone();
+
+    // Early return: the write to "a" is discarded and its latch released
+    assert!(transfer(&db, 500).is_err());
+    assert_eq!(balance("a"), 100);
+    assert_eq!(db.lock_manager().held_count(1), 0);
+    assert!(db.get_transaction(1).is_none());
+
+    transfer(&db, 30).unwrap();
+    assert_eq!((balance("a"), balance("b")), (json!(70), json!(130)));
+
+    // Panic inside the transaction
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let tx = db.begin();
+        tx.insert_one("accounts", doc(json!({"name": "c", "balance": 1}))).unwrap();
+        assert_eq!(tx.find("accounts", &json!({"name": "c"}), Default::default()).unwrap().len(), 1);
+        panic!("handler failed");
+    }));
+    assert!(panicked.is_err());
+    assert!(accounts.find_one(&json!({"name": "c"})).unwrap().is_none());
+
+    // Explicit rollback, and a rolled back guard's drop does nothing more
+    let tx = db.begin();
+    let tx_id = tx.id();
+    tx.delete_one("accounts", &json!({"name": "b"})).unwrap();
+    tx.rollback().unwrap();
+    assert!(db.get_transaction(tx_id).is_none());
+    assert_eq!(accounts.count_documents(&json!({})).unwrap(), 2);
+}
@@ -0,0 +1,77 @@
+// Typed collection API (serde structs instead of raw JSON values)
+use ironbase_core::{Collection, DatabaseCore, DocumentId, FindOptions, MongoLiteError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tempfile::TempDir;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    id: Option<DocumentId>,
+    name: String,
+    age: u32,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn user(name: &str, age: u32) -> User {
+    User { id: None, name: name.to_string(), age, tags: vec!["new".to_string()] }
+}
+
+#[test]
+fn test_typed_insert_and_find() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users: Collection<User> = db.typed_collection("users").unwrap();
+
+    let alice_id = users.insert_one(&user("alice", 30)).unwrap();
+    users.insert_one(&user("bob", 17)).unwrap();
+
+    let adults = users.find(&json!({"age": {"$gte": 18}})).unwrap();
+    assert_eq!(adults.len(), 1);
+    assert_eq!(adults[0].name, "alice");
+    assert_eq!(adults[0].id, Some(alice_id.clone()));
+
+    let alice = users.find_by_id(&alice_id).unwrap().unwrap();
+    assert_eq!(alice.tags, vec!["new".to_string()]);
+    assert!(users.find_one(&json!({"name": "carol"})).unwrap().is_none());
+
+    let sorted = users.find_with_options(&json!({}), FindOptions::new().with_sort(vec![("age".to_string(), 1)])).unwrap();
+    assert_eq!(sorted.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), vec!["bob", "alice"]);
+}
+
+#[test]
+fn test_typed_updates_and_ids() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    let users: Collection<User> = db.typed_collection("users").unwrap();
+
+    // A caller-supplied _id is ignored - the database assigns it
+    let mut carol = user("carol", 40);
+    carol.id = Some(DocumentId::Int(999));
+    let carol_id = users.insert_one(&carol).unwrap();
+    assert_ne!(carol_id, DocumentId::Int(999));
+
+    users.update_one(&json!({"name": "carol"}), &json!({"$set": {"age": 41}})).unwrap();
+    assert_eq!(users.find_by_id(&carol_id).unwrap().unwrap().age, 41);
+
+    assert_eq!(users.delete_many(&json!({"age": {"$gt": 0}})).unwrap().deleted_count, 1);
+    assert_eq!(users.count_documents(&json!({})).unwrap(), 0);
+}
+
+#[test]
+fn test_typed_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+
+    // Non-object documents cannot be stored
+    let numbers: Collection<u32> = db.typed_collection("numbers").unwrap();
+    assert!(matches!(numbers.insert_one(&5), Err(MongoLiteError::Serialization(_))));
+
+    // Stored documents that do not fit the type fail to deserialize
+    db.collection("users").unwrap()
+        .insert_one(serde_json::from_value(json!({"name": "dave"})).unwrap())
+        .unwrap();
+    let users: Collection<User> = db.typed_collection("users").unwrap();
+    assert!(matches!(users.find(&json!({})), Err(MongoLiteError::Deserialization(_))));
+}
@@ -0,0 +1,114 @@
+// Views: plain (pipeline on read) and materialized (on demand / incremental)
+mod common;
+
+use common::doc;
+use ironbase_core::{DatabaseCore, MongoLiteError, OplogConfig, ViewRefresh};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+fn names(docs: &[Value]) -> Vec<&str> {
+    let mut names: Vec<&str> = docs.iter().map(|doc| doc["name"].as_str().unwrap()).collect();
+    names.sort();
+    names
+}
+
+fn seed(db: &DatabaseCore) {
+    let users = db.collection("users").unwrap();
+    users.insert_many(vec![
+        doc(json!({"name": "ann", "age": 34, "active": true})),
+        doc(json!({"name": "bob", "age": 17, "active": true})),
+        doc(json!({"name": "cy", "age": 52, "active": false})),
+    ]).unwrap();
+}
+
+#[test]
+fn test_plain_view_reruns_pipeline() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        seed(&db);
+        let pipeline = json!([{"$match": {"active": true}}, {"$project": {"name": 1, "age": 1}}]);
+        db.create_view("active_users", "users", &pipeline, None).unwrap();
+        assert!(matches!(db.create_view("active_users", "users", &pipeline, None), Err(MongoLiteError::CollectionExists(_))));
+        assert!(db.create_view("bad", "users", &json!([{"$out": "x"}]), None).is_err());
+        db.flush().unwrap();
+    }
+
+    let db = DatabaseCore::open(&path).unwrap();
+    let view = db.view("active_users").unwrap();
+    assert_eq!(names(&view.find(&json!({})).unwrap()), vec!["ann", "bob"]);
+    assert_eq!(names(&view.find(&json!({"age": {"$gte": 18}})).unwrap()), vec!["ann"]);
+    assert!(view.find(&json!({})).unwrap().iter().all(|doc| doc.get("active").is_none()));
+
+    // Source changes show up on the next read
+    db.collection("users").unwrap().update_one(&json!({"name": "cy"}), &json!({"$set": {"active": true}})).unwrap();
+    assert_eq!(view.count_documents(&json!({})).unwrap(), 3);
+    let total = view.aggregate(&json!([{"$group": {"_id": null, "age": {"$sum": "$age"}}}])).unwrap();
+    assert_eq!(total[0]["age"], json!(103));
+
+    // Read-only, and not a collection
+    assert!(db.collection("active_users").is_err());
+    assert!(db.collection("users").unwrap().aggregate(&json!([{"$match": {}}, {"$out": "active_users"}])).is_err());
+    assert!(db.create_view("nested", "active_users", &json!([{"$match": {}}]), None).is_err());
+    assert!(db.view("users").is_err());
+
+    db.drop_collection("active_users").unwrap();
+    assert!(db.view("active_users").is_err());
+}
+
+#[test]
+fn test_materialized_view_on_demand() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    seed(&db);
+    let pipeline = json!([{"$group": {"_id": "$active", "count": {"$sum": 1}}}]);
+    assert!(db.create_view("by_active", "users", &pipeline, Some(ViewRefresh::OnDemand)).is_err());
+
+    let pipeline = json!([{"$match": {"age": {"$gte": 18}}}]);
+    db.create_view("adults", "users", &pipeline, Some(ViewRefresh::OnDemand)).unwrap();
+    let stored = db.collection("adults").unwrap();
+    assert_eq!(names(&stored.find(&json!({})).unwrap()), vec!["ann", "cy"]);
+    assert!(stored.insert_one(doc(json!({"name": "dee"}))).is_err());
+    assert!(stored.delete_many(&json!({})).is_err());
+
+    // Stale until refreshed
+    db.collection("users").unwrap().insert_one(doc(json!({"name": "dee", "age": 40}))).unwrap();
+    let view = db.view("adults").unwrap();
+    assert_eq!(view.count_documents(&json!({})).unwrap(), 2);
+    db.refresh_view("adults").unwrap();
+    assert_eq!(names(&view.find(&json!({})).unwrap()), vec!["ann", "cy", "dee"]);
+}
+
+#[test]
+fn test_incremental_view_follows_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseCore::open(temp_dir.path().join("test.mlite")).unwrap();
+    db.set_oplog_config(OplogConfig::with_retention(1000));
+    seed(&db);
+
+    let pipeline = json!([{"$match": {"active": true}}, {"$addFields": {"adult": {"$gte": ["$age", 18]}}}]);
+    db.create_view("active", "users", &pipeline, Some(ViewRefresh::Incremental)).unwrap();
+    let pipeline = json!([{"$sort": {"age": -1}}, {"$limit": 1}]);
+    db.create_view("oldest", "users", &pipeline, Some(ViewRefresh::Incremental)).unwrap();
+    let active = db.view("active").unwrap();
+    let oldest = db.view("oldest").unwrap();
+
+    let users = db.collection("users").unwrap();
+    users.insert_one(doc(json!({"name": "dee", "age": 70, "active": true}))).unwrap();
+    users.update_one(&json!({"name": "bob"}), &json!({"$set": {"age": 18}})).unwrap();
+    users.update_one(&json!({"name": "ann"}), &json!({"$set": {"active": false}})).unwrap();
+    users.delete_one(&json!({"name": "cy"})).unwrap();
+
+    // From the oplog, one document at a time
+    let docs = active.find(&json!({})).unwrap();
+    assert_eq!(names(&docs), vec!["bob", "dee"]);
+    assert!(docs.iter().all(|doc| doc["adult"] == json!(true)));
+    // $sort/$limit cannot be maintained per document - recomputed instead
+    assert_eq!(names(&oldest.find(&json!({})).unwrap()), vec!["dee"]);
+
+    // Without the oplog the view recomputes, and still catches up
+    db.set_oplog_config(OplogConfig::default());
+    users.insert_one(doc(json!({"name": "eve", "age": 30, "active": true}))).unwrap();
+    assert_eq!(active.count_documents(&json!({"adult": true})).unwrap(), 3);
+}
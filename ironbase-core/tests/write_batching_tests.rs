@@ -0,0 +1,91 @@
+// Write batching: batched appends reach the data file in few writes and read back like single ones
+mod common;
+
+use common::doc;
+use ironbase_core::storage::{DEFAULT_PREALLOCATION, WRITE_BUFFER_LIMIT};
+use ironbase_core::{DatabaseCore, StorageConfig};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn data_writes(db: &DatabaseCore) -> u64 {
+    db.stats()["data_writes"].as_u64().unwrap()
+}
+
+#[test]
+fn test_batches_coalesce_appends() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("test.mlite");
+    {
+        let db = DatabaseCore::open(&path).unwrap();
+        let items = db.collection("items").unwrap();
+
+        // Single inserts: one write per record
+        let writes = data_writes(&db);
+        for n in 0..5 {
+            items.insert_one(doc(json!({"n": n}))).unwrap();
+        }
+        assert_eq!(data_writes(&db) - writes, 5);
+
+        // insert_many: one write per WRITE_BUFFER_LIMIT bytes
+        let writes = data_writes(&db);
+        let documents: Vec<_> = (5..3005).map(|n| doc(json!({"n": n, "padding": "x".repeat(1000)}))).collect();
+        items.insert_many(documents).unwrap();
+        let expected = (3000 * 1000 / WRITE_BUFFER_LIMIT) as u64 + 1;
+        assert!(data_writes(&db) - writes <= expected + 1);
+        assert_eq!(items.count_documents(&json!({})).unwrap(), 3005);
+        assert_eq!(items.find_one(&json!({"n": 3004})).unwrap().unwrap()["padding"], "x".repeat(1000));
+
+        // A transaction's inserts go out together
+        let writes = data_writes(&db);
+        let tx_id = db.begin_transaction();
+        for n in 3005..3055 {
+            db.insert_one_tx("items", doc(json!({"n": n})), tx_id).unwrap();
+        }
+        db.commit_transaction(tx_id).unwrap();
+        assert_eq!(data_writes(&db) - writes, 1);
+
+        // bulk_upsert reads and replaces inside its batch
+        let writes = data_writes(&db);
+        let documents: Vec<_> = (3050..3060).map(|n| doc(json!({"n": n, "upserted": true}))).collect();
+        let result = items.bulk_upsert(documents, &["n"]).unwrap();
+        assert_eq!((result.matched_count, result.upserted_count), (5, 5));
+        assert_eq!(data_writes(&db) - writes, 1);
+    }
+
+    // Everything batched was written - nothing is left for a flush on close
+    let db = DatabaseCore::open(&path).unwrap();
+    let items = db.collection("items").unwrap();
+    assert_eq!(items.count_documents(&json!({})).unwrap(), 3060);
+    assert_eq!(items.count_documents(&json!({"upserted": true})).unwrap(), 10);
+    assert_eq!(items.find_one(&json!({"n": 3054})).unwrap().unwrap()["upserted"], true);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_appends_preallocate_disk_space() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let allocated = |path: &std::path::Path| std::fs::metadata(path).unwrap().blocks() * 512;
+    let documents = || (0..2000).map(|n| doc(json!({"n": n, "padding": "x".repeat(500)}))).collect::<Vec<_>>();
+
+    // Space is reserved past the end of the file without growing it
+    let path = temp_dir.path().join("test.mlite");
+    let db = DatabaseCore::open(&path).unwrap();
+    db.collection("items").unwrap().insert_many(documents()).unwrap();
+    let len = std::fs::metadata(&path).unwrap().len();
+    assert!(allocated(&path) >= len + DEFAULT_PREALLOCATION / 2);
+
+    // Turned off, the file only has what it holds
+    let plain = temp_dir.path().join("plain.mlite");
+    let db = DatabaseCore::open_with_config(&plain, &StorageConfig::default().with_preallocation(0)).unwrap();
+    db.collection("items").unwrap().insert_many(documents()).unwrap();
+    assert!(allocated(&plain) < std::fs::metadata(&plain).unwrap().len() + DEFAULT_PREALLOCATION);
+
+    // Compaction writes its chunks vectored and gives the reserve back
+    db.collection("items").unwrap().delete_many(&json!({"n": {"$gte": 100}})).unwrap();
+    db.compact().unwrap();
+    let items = db.collection("items").unwrap();
+    assert_eq!(items.count_documents(&json!({})).unwrap(), 100);
+    assert_eq!(items.find_one(&json!({"n": 99})).unwrap().unwrap()["padding"], "x".repeat(500));
+}
@@ -0,0 +1,184 @@
+// ironbase-derive/src/lib.rs
+// #[derive(Queryable)] - typed query builders for document structs
+//
+// Generates, for `struct User { .. }`:
+// - `UserQuery`, a builder implementing ironbase_core::query_builder::FilterBuilder
+//   with one accessor per named field returning a typed `Field`
+// - `User::query()` creating an empty builder
+// - `From<UserQuery> for serde_json::Value`
+//
+// Field names follow `#[serde(rename = "...")]`; fields marked
+// `#[serde(skip)]` or `#[query(skip)]` get no accessor. `Option<T>` and
+// `Vec<T>` fields compare against `T`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Type};
+
+#[proc_macro_derive(Queryable, attributes(query))]
+pub fn derive_queryable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+struct QueryField {
+    accessor: Ident,
+    stored_name: String,
+    value_type: Type,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "Queryable does not support generic structs"));
+    }
+
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "Queryable needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "Queryable can only be derived for structs")),
+    };
+
+    let mut fields = Vec::new();
+    for field in named {
+        let ident = field.ident.clone().expect("named field");
+        let options = FieldOptions::parse(field)?;
+        if options.skip {
+            continue;
+        }
+
+        let accessor = match ident.to_string().strip_prefix("r#") {
+            Some(raw) => Ident::new_raw(raw, Span::call_site()),
+            None => ident.clone(),
+        };
+        fields.push(QueryField {
+            stored_name: options.rename.unwrap_or_else(|| accessor.to_string()),
+            accessor,
+            value_type: element_type(&field.ty).clone(),
+        });
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let builder = format_ident!("{}Query", name);
+    let doc = format!("Typed query builder for [`{}`] (generated by `#[derive(Queryable)]`)", name);
+    let krate = quote!(::ironbase_core::query_builder);
+
+    let accessors = fields.iter().map(|field| {
+        let QueryField { accessor, stored_name, value_type } = field;
+        let doc = format!("Condition on `{}`", stored_name);
+        quote! {
+            #[doc = #doc]
+            pub fn #accessor(self) -> #krate::Field<Self, #value_type> {
+                #krate::Field::new(self, #stored_name)
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default, PartialEq)]
+        #vis struct #builder {
+            filter: #krate::Filter,
+        }
+
+        impl #krate::FilterBuilder for #builder {
+            fn filter(&self) -> &#krate::Filter {
+                &self.filter
+            }
+
+            fn filter_mut(&mut self) -> &mut #krate::Filter {
+                &mut self.filter
+            }
+        }
+
+        impl #builder {
+            #(#accessors)*
+
+            /// The JSON query
+            pub fn build(&self) -> #krate::Value {
+                self.filter.to_json()
+            }
+        }
+
+        impl ::std::convert::From<#builder> for #krate::Value {
+            fn from(query: #builder) -> Self {
+                query.build()
+            }
+        }
+
+        impl #name {
+            /// Start a typed query on this document type
+            pub fn query() -> #builder {
+                #builder::default()
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    skip: bool,
+}
+
+impl FieldOptions {
+    /// Read `#[serde(rename = "..")]`, `#[serde(skip)]` and `#[query(skip)]`
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+
+        for attr in &field.attrs {
+            if attr.path().is_ident("query") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        options.skip = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown query attribute (expected `skip`)"))
+                    }
+                })?;
+            } else if attr.path().is_ident("serde") {
+                // Only the attributes that change the stored name matter here;
+                // everything else is serde's business
+                let _ = attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        let value: LitStr = meta.value()?.parse()?;
+                        options.rename = Some(value.value());
+                    } else if meta.path.is_ident("skip") {
+                        options.skip = true;
+                    } else if meta.input.peek(syn::Token![=]) {
+                        let _: syn::Expr = meta.value()?.parse()?;
+                    } else if meta.input.peek(syn::token::Paren) {
+                        let _ = meta.parse_nested_meta(|nested| {
+                            if nested.input.peek(syn::Token![=]) {
+                                let _: syn::Expr = nested.value()?.parse()?;
+                            }
+                            Ok(())
+                        });
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// `Option<T>` / `Vec<T>` -> `T`, anything else unchanged
+fn element_type(ty: &Type) -> &Type {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if segment.ident == "Option" || segment.ident == "Vec" {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let (1, Some(GenericArgument::Type(inner))) = (args.args.len(), args.args.first()) {
+                        return inner;
+                    }
+                }
+            }
+        }
+    }
+    ty
+}
@@ -28,6 +28,9 @@ TEST_SUITES = [
     ("test_compaction.py", "Compaction (comprehensive)"),
     ("test_reopen_fixed.py", "Database reopen/persistence"),
 
+    # Python binding
+    ("test_threads.py", "Multi-threaded use of the binding"),
+
     # Index persistence
     ("test_index_persistence_poc.py", "B+ tree persistence (Rust unit tests)"),
 ]
@@ -0,0 +1,148 @@
+#!/usr/bin/env python3
+"""
+Threaded stress test for the Python binding
+Several threads share one IronBase and one Collection object; the binding
+releases the GIL around core calls, so the threads really run concurrently.
+"""
+
+import os
+import sys
+import tempfile
+import threading
+
+from ironbase import IronBase, DatabaseClosedError
+
+THREADS = 8
+DOCS_PER_THREAD = 200
+
+
+def run_threads(target):
+    errors = []
+
+    def wrapper(n):
+        try:
+            target(n)
+        except Exception as e:  # noqa: BLE001 - reported below
+            errors.append(e)
+
+    threads = [threading.Thread(target=wrapper, args=(n,)) for n in range(THREADS)]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join(timeout=120)
+    assert not any(t.is_alive() for t in threads), "threads deadlocked"
+    assert not errors, errors
+
+
+def test_shared_collection(db):
+    """Concurrent inserts, reads, updates and deletes on one Collection"""
+    users = db.collection("users")
+    users.create_index("thread")
+
+    def work(n):
+        for i in range(DOCS_PER_THREAD):
+            users.insert_one({"thread": n, "seq": i, "hits": 0})
+            if i % 10 == 0:
+                users.update_many({"thread": n}, {"$inc": {"hits": 1}})
+                users.find({"thread": n, "seq": {"$lt": i}})
+                users.count_documents({"thread": n})
+        users.delete_many({"thread": n, "seq": {"$gte": DOCS_PER_THREAD // 2}})
+
+    run_threads(work)
+
+    for n in range(THREADS):
+        assert users.count_documents({"thread": n}) == DOCS_PER_THREAD // 2
+    assert users.validate(False)["valid"]
+    print("✓ shared collection")
+
+
+def test_collection_per_thread(db):
+    """Each thread opens its own handle while others write and compact"""
+
+    def work(n):
+        coll = db.collection(f"c{n % 3}")
+        coll.insert_many([{"thread": n, "seq": i} for i in range(DOCS_PER_THREAD)])
+        coll.aggregate([{"$match": {"thread": n}}, {"$group": {"_id": "$thread", "count": {"$sum": 1}}}])
+        if n == 0:
+            db.compact()
+
+    run_threads(work)
+
+    total = sum(db.collection(name).count_documents({}) for name in ("c0", "c1", "c2"))
+    assert total == THREADS * DOCS_PER_THREAD
+    print("✓ collection per thread")
+
+
+def test_transactions(db):
+    """Transactions from different threads on different documents"""
+    accounts = db.collection("accounts")
+    accounts.insert_many([{"owner": n, "balance": 0} for n in range(THREADS)])
+
+    def work(n):
+        for _ in range(20):
+            tx_id = db.begin_transaction()
+            db.update_one_tx("accounts", {"owner": n}, {"owner": n, "balance": 1}, tx_id)
+            db.commit_transaction(tx_id)
+
+    run_threads(work)
+    assert accounts.count_documents({"balance": 1}) == THREADS
+    print("✓ transactions")
+
+
+def test_progress_callback(db):
+    """An index build calling back into Python must not deadlock readers"""
+    items = db.collection("items")
+    items.insert_many([{"n": i} for i in range(2000)])
+    calls = []
+
+    def work(n):
+        if n == 0:
+            items.create_index("n", progress=lambda done, total: calls.append(done))
+        else:
+            for _ in range(20):
+                items.find_one({"n": n})
+
+    run_threads(work)
+    assert calls
+    print("✓ progress callback")
+
+
+def test_close_while_in_use(path):
+    """Closing from one thread makes the others fail cleanly"""
+    db = IronBase(path)
+    coll = db.collection("users")
+    closed = threading.Event()
+    outcomes = []
+
+    def work(n):
+        if n == 0:
+            db.close()
+            closed.set()
+            return
+        closed.wait()
+        try:
+            coll.find_one({})
+            outcomes.append("ok")
+        except DatabaseClosedError:
+            outcomes.append("closed")
+
+    run_threads(work)
+    assert outcomes == ["closed"] * (THREADS - 1)
+    print("✓ close while in use")
+
+
+def main():
+    with tempfile.TemporaryDirectory() as tmp:
+        path = os.path.join(tmp, "threads.mlite")
+        with IronBase(path) as db:
+            test_shared_collection(db)
+            test_collection_per_thread(db)
+            test_transactions(db)
+            test_progress_callback(db)
+        test_close_while_in_use(path)
+    print("All threading tests passed")
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())